@@ -1,14 +1,43 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use clap::Parser;
-use log::{info, LevelFilter};
+use log::LevelFilter;
 
-use voxel::EngineConfig;
+use voxel::world::world_config::{GeneratorKind, WorldConfig, DEFAULT_SEED};
+use voxel::{BenchmarkConfig, BenchmarkThresholds, EngineConfig, HeadlessConfig, ServerConfig, SoakConfig, BUILD_INFO};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
+#[command(version = BUILD_INFO)]
 struct Args {
-    /// Run a benchmark (unused as of now)
+    /// Fly along a scripted path, write a report of the frame times and chunk throughput and exit afterwards
     #[arg(short, long, default_value_t = false)]
     benchmark: bool,
+    /// Path of the JSON report of the benchmark. The frame times are written next to it as CSV.
+    #[arg(long, default_value = "benchmark.json")]
+    benchmark_report: PathBuf,
+    /// Fail the benchmark if the average frame time is above this many milliseconds
+    #[arg(long, value_name = "MS")]
+    max_frame_time: Option<f32>,
+    /// Fail the benchmark if the 99th percentile of the frame times is above this many milliseconds
+    #[arg(long, value_name = "MS")]
+    max_p99_frame_time: Option<f32>,
+    /// Fail the benchmark if fewer chunks than this are meshed per second
+    #[arg(long, value_name = "CHUNKS")]
+    min_meshed_chunks_per_second: Option<f32>,
+    /// Draw the chunks of the benchmark in no particular order instead of front to back, to compare the GPU time of the chunk passes
+    #[arg(long, default_value_t = false)]
+    benchmark_unsorted_chunks: bool,
+    /// Generate and mesh the chunks around the origin without opening a window, log the throughput and exit afterwards
+    #[arg(long, default_value_t = false)]
+    headless: bool,
+    /// Radius in chunks around the origin that is generated in headless mode
+    #[arg(long, value_name = "CHUNKS", default_value_t = 8)]
+    headless_radius: i32,
+    /// Save the chunks that are generated in headless mode to the world directory
+    #[arg(long, default_value_t = false)]
+    headless_save: bool,
     /// Use vertical sync
     #[arg(short, long, default_value_t = false)]
     vsync: bool,
@@ -18,6 +47,60 @@ struct Args {
     /// Make the window fullscreen
     #[arg(short, long, default_value_t = false)]
     fullscreen: bool,
+    /// Make the window background transparent
+    #[arg(short, long, default_value_t = false)]
+    transparent: bool,
+    /// Path of the settings file
+    #[arg(long, default_value = "settings.ron")]
+    settings: PathBuf,
+    /// Path of the file with the key bindings of the actions, e.g. `{Jump: ["Space"], BreakBlock: ["MouseLeft"]}`
+    #[arg(long, default_value = "bindings.ron")]
+    bindings: PathBuf,
+    /// Path of the file in which the portals of the world are stored
+    #[arg(long, default_value = "portals.ron")]
+    portals: PathBuf,
+    /// Directory into which the photos of the photo mode are saved
+    #[arg(long, default_value = "photos")]
+    photos: PathBuf,
+    /// Path of the worldgen preset file, which can be reloaded from the overlay
+    #[arg(long, default_value = "worldgen.ron")]
+    worldgen_preset: PathBuf,
+    /// Directory in which the modified chunks of the world are saved and loaded from
+    #[arg(long, default_value = "world")]
+    world_path: PathBuf,
+    /// Seed of a new world. Saved worlds keep the seed that they were created with.
+    #[arg(long, default_value_t = DEFAULT_SEED)]
+    seed: u32,
+    /// Terrain generator of a new world, either "terrain" or "flat". Saved worlds keep the generator that they were created with.
+    #[arg(long, default_value = "terrain")]
+    generator: GeneratorKind,
+    /// Texture atlas of the voxel faces with 16 x 16 tiles. Voxels are colored instead if it does not exist.
+    #[arg(long, default_value = "atlas.png")]
+    texture_atlas: PathBuf,
+    /// Let a bot walk and dig for the given number of minutes and exit afterwards
+    #[arg(long, value_name = "MINUTES")]
+    soak: Option<f32>,
+    /// Seed of the decisions of the soak test bot
+    #[arg(long, default_value_t = 0)]
+    soak_seed: u64,
+    /// Read admin commands like `stats`, `save-all` and `stop` from the standard input
+    #[arg(long, default_value_t = false)]
+    console: bool,
+    /// Accept admin commands line by line on this address, e.g. `127.0.0.1:4791`. It must only be reachable by admins.
+    #[arg(long, value_name = "ADDRESS")]
+    admin: Option<String>,
+    /// Serve the world on the given address to other players without opening a window
+    #[arg(long, value_name = "ADDRESS")]
+    server: Option<String>,
+    /// Play on the server with the given address instead of the local world
+    #[arg(long, value_name = "ADDRESS")]
+    connect: Option<String>,
+    /// Record the frames of the connection to the server into this file
+    #[arg(long, value_name = "PATH")]
+    record_packets: Option<PathBuf>,
+    /// Play a session that was recorded with `--record-packets` again instead of connecting to a server
+    #[arg(long, value_name = "PATH", conflicts_with = "connect")]
+    replay: Option<PathBuf>,
 }
 
 fn main() -> ! {
@@ -28,15 +111,92 @@ fn main() -> ! {
         .init();
 
     let args = Args::parse();
-    if args.benchmark {
-        info!("Running benchmark...");
+    log::info!("voxel {BUILD_INFO}");
+    let world_config = WorldConfig {
+        seed: args.seed,
+        generator_kind: args.generator,
+        ..WorldConfig::default()
+    };
+
+    if args.headless {
+        let headless_config = HeadlessConfig {
+            radius: args.headless_radius,
+            worldgen_preset_path: args.worldgen_preset,
+            world_path: args.headless_save.then_some(args.world_path),
+            world_config,
+        };
+
+        let exit_code = match voxel::run_headless(headless_config) {
+            Ok(()) => 0,
+            Err(err) => {
+                log::error!("Headless run failed: {err:#}");
+                1
+            }
+        };
+        std::process::exit(exit_code);
     }
 
+    if let Some(address) = args.server {
+        let server_config = ServerConfig {
+            address,
+            worldgen_preset_path: args.worldgen_preset,
+            world_path: Some(args.world_path),
+            world_config,
+            console: args.console,
+            admin_address: args.admin,
+        };
+
+        let exit_code = match voxel::run_server(server_config) {
+            Ok(()) => 0,
+            Err(err) => {
+                log::error!("Server failed: {err:#}");
+                1
+            }
+        };
+        std::process::exit(exit_code);
+    }
+
+    let server_address = match args.replay {
+        Some(recording) => match voxel::serve_recording(&recording) {
+            Ok(address) => Some(address),
+            Err(err) => {
+                log::error!("Could not replay {recording:?}: {err:#}");
+                std::process::exit(1);
+            }
+        },
+        None => args.connect,
+    };
+
     let engine_config = EngineConfig {
-        run_benchmark: args.benchmark,
+        benchmark: args.benchmark.then(|| BenchmarkConfig {
+            report_path: args.benchmark_report,
+            thresholds: BenchmarkThresholds {
+                max_average_frame_time_ms: args.max_frame_time,
+                max_p99_frame_time_ms: args.max_p99_frame_time,
+                min_meshed_chunks_per_second: args.min_meshed_chunks_per_second,
+            },
+            front_to_back: !args.benchmark_unsorted_chunks,
+        }),
         vsync: args.vsync,
         window_size: (args.window_size[0], args.window_size[1]),
         fullscreen: args.fullscreen,
+        transparent: args.transparent,
+        settings_path: args.settings,
+        bindings_path: args.bindings,
+        portals_path: args.portals,
+        photos_path: args.photos,
+        worldgen_preset_path: args.worldgen_preset,
+        world_path: args.world_path,
+        world_config,
+        texture_atlas_path: args.texture_atlas,
+        soak: args.soak.map(|minutes| SoakConfig {
+            duration: Duration::from_secs_f32(minutes * 60.0),
+            seed: args.soak_seed,
+        }),
+        console: args.console,
+        admin_address: args.admin,
+        server_address,
+        packet_recording_path: args.record_packets,
     };
 
     voxel::start(engine_config);
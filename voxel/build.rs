@@ -0,0 +1,34 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Embeds the commit and the profile that the engine is built with, see `src/build_info.rs`
+fn main() {
+    let hash = git(&["rev-parse", "--short=10", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|status| !status.is_empty());
+    println!("cargo:rustc-env=VOXEL_GIT_HASH={hash}{}", if dirty { "-dirty" } else { "" });
+    println!(
+        "cargo:rustc-env=VOXEL_BUILD_PROFILE={}",
+        std::env::var("PROFILE").unwrap_or_default()
+    );
+
+    // Rebuilt after commits and checkouts. Outside of a git checkout there is nothing to watch.
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        for file in ["HEAD", "index", "logs/HEAD"] {
+            let path = Path::new(&git_dir).join(file);
+            if path.exists() {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
+
+/// Trimmed output of a successful git command
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output.status.success().then(|| {
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .to_string()
+    })
+}
@@ -0,0 +1,68 @@
+//! Opens a window with a world whose overworld is generated by a custom terrain function instead of the built-in world generator.
+//! The world is kept in the `custom_generator_world` directory.
+//!
+//! ```sh
+//! cargo run --release --example custom_generator
+//! ```
+
+use std::sync::Arc;
+
+use log::LevelFilter;
+use voxel::prelude::*;
+
+/// Rolling hills made of two crossing sine waves
+struct SineHills;
+
+impl NoiseSource for SineHills {
+    fn sample(&self, point: [f64; 2]) -> f64 {
+        ((point[0] * 0.05).sin() + (point[1] * 0.07).cos()) / 2.0
+    }
+}
+
+/// Fills the chunks with stone below the surface of the noise, covered with a layer of grass
+struct HillsGenerator {
+    noise: Box<dyn NoiseSource>,
+    height: f64,
+}
+
+impl ChunkGenerator for HillsGenerator {
+    fn generate_chunk(&self, chunk_location: ChunkLocation) -> GeneratedChunk {
+        let mut data = ChunkData::new_filled_with_uniform_data(VoxelData::new(VoxelType::Air));
+        for local_location in LocalChunkLocation::iter() {
+            let WorldLocation(world_location) = WorldLocation::new(chunk_location, LocalChunkLocation::new(*local_location));
+            let surface = (self
+                .noise
+                .sample([world_location.x as f64, world_location.z as f64])
+                * self.height) as i32;
+
+            let ty = match world_location.y {
+                y if y < surface => VoxelType::Stone,
+                y if y == surface => VoxelType::Grass,
+                _ => continue,
+            };
+            data.set_voxel_data(local_location, VoxelData::new(ty));
+        }
+        data.compress();
+
+        GeneratedChunk {
+            data,
+            outside_voxels: Vec::new(),
+        }
+    }
+}
+
+fn main() {
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Info)
+        .filter_module("wgpu_hal", LevelFilter::Warn)
+        .init();
+
+    start(EngineConfig {
+        world_path: "custom_generator_world".into(),
+        generator: Some(Arc::new(HillsGenerator {
+            noise: Box::new(SineHills),
+            height: 12.0,
+        })),
+        ..EngineConfig::default()
+    });
+}
@@ -0,0 +1,35 @@
+//! Decodes mutated frames of the network protocol and panics at the first frame that breaks it.
+//!
+//! ```sh
+//! cargo run --release --example fuzz_packets -- 1000000 session.packets
+//! ```
+//!
+//! The first argument is the number of mutations, the others are recordings of `--record-packets` whose frames are mutated
+//! beside one message of every kind. The seed is logged, so that a failure can be reproduced with `FUZZ_SEED`.
+
+use anyhow::{Context, Result};
+use log::LevelFilter;
+use voxel::prelude::*;
+
+fn main() -> Result<()> {
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Info)
+        .init();
+
+    let mut args = std::env::args().skip(1);
+    let iterations = match args.next() {
+        Some(iterations) => iterations
+            .parse()
+            .context("the number of mutations must be a number")?,
+        None => 100_000,
+    };
+    let recordings: Vec<_> = args.map(Into::into).collect();
+    let seed = match std::env::var("FUZZ_SEED") {
+        Ok(seed) => seed
+            .parse()
+            .context("FUZZ_SEED must be a number")?,
+        Err(_) => fastrand::u64(..),
+    };
+
+    fuzz_packets(&recordings, seed, iterations)
+}
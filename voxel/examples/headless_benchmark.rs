@@ -0,0 +1,31 @@
+//! Generates and meshes the chunks around the origin without a window and logs the throughput.
+//!
+//! ```sh
+//! cargo run --release --example headless_benchmark -- 12
+//! ```
+//!
+//! The optional argument is the radius in chunks, the terrain is generated with the preset in `worldgen.ron` if it exists.
+
+use anyhow::{Context, Result};
+use log::LevelFilter;
+use voxel::prelude::*;
+
+fn main() -> Result<()> {
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Info)
+        .init();
+
+    let radius = match std::env::args().nth(1) {
+        Some(radius) => radius
+            .parse()
+            .context("the radius must be a number of chunks")?,
+        None => 8,
+    };
+
+    run_headless(HeadlessConfig {
+        radius,
+        worldgen_preset_path: "worldgen.ron".into(),
+        world_path: None,
+        world_config: WorldConfig::default(),
+    })
+}
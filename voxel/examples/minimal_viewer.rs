@@ -0,0 +1,24 @@
+//! Opens a window with a flat world, which is kept in the `example_world` directory.
+//!
+//! ```sh
+//! cargo run --example minimal_viewer
+//! ```
+
+use log::LevelFilter;
+use voxel::prelude::*;
+
+fn main() {
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Info)
+        .filter_module("wgpu_hal", LevelFilter::Warn)
+        .init();
+
+    start(EngineConfig {
+        world_path: "example_world".into(),
+        world_config: WorldConfig {
+            generator_kind: GeneratorKind::Flat,
+            ..WorldConfig::default()
+        },
+        ..EngineConfig::default()
+    });
+}
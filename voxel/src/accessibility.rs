@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use cgmath::{Matrix, Matrix3, Vector3};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+/// Settings for players who are sensitive to flashing and motion or who cannot tell some colors apart.
+///
+/// The field of view of the camera never changes, e.g. while sprinting, so there are no field of view kicks to limit.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Accessibility {
+    /// Hides the sun glare and adapts the exposure to bright surroundings as slowly as to the dark
+    pub reduced_flashing: bool,
+    /// Keeps the held block still instead of swinging it when a block is placed or broken
+    pub reduced_motion: bool,
+    pub palette: ColorPalette,
+}
+
+/// Colors of the voxels, which are adjusted for a color vision deficiency by every palette but the default one
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum ColorPalette {
+    #[default]
+    Default,
+    /// Red-green color blindness with missing green cones
+    Deuteranopia,
+    /// Red-green color blindness with missing red cones
+    Protanopia,
+    /// Blue-yellow color blindness
+    Tritanopia,
+}
+
+/// Index of the palette with which voxel colors are meshed, see [ColorPalette::active]
+static ACTIVE_PALETTE: AtomicU8 = AtomicU8::new(0);
+
+impl ColorPalette {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorPalette::Default => "Default",
+            ColorPalette::Deuteranopia => "Deuteranopia",
+            ColorPalette::Protanopia => "Protanopia",
+            ColorPalette::Tritanopia => "Tritanopia",
+        }
+    }
+
+    /// Palette with which the colors of voxels are meshed, on all threads
+    pub fn active() -> Self {
+        Self::iter()
+            .nth(ACTIVE_PALETTE.load(Ordering::Relaxed) as usize)
+            .unwrap_or_default()
+    }
+
+    /// Changes the palette of all meshes that are generated from now on. Existing meshes keep their colors until they are remeshed.
+    pub fn set_active(self) {
+        ACTIVE_PALETTE.store(self as u8, Ordering::Relaxed);
+    }
+
+    /// Daltonizes a color: the difference that is lost with the color vision deficiency is shifted into the channels
+    /// that can still be told apart
+    pub fn apply(self, color: Vector3<f32>) -> Vector3<f32> {
+        let Some(simulation) = self.simulation() else {
+            return color;
+        };
+
+        let lost = color - simulation * color;
+        let shift = rows([[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]]);
+        (color + shift * lost).map(|c| c.clamp(0.0, 1.0))
+    }
+
+    /// How colors are seen with the color vision deficiency of the palette
+    fn simulation(self) -> Option<Matrix3<f32>> {
+        let simulation = match self {
+            ColorPalette::Default => return None,
+            ColorPalette::Deuteranopia => [[0.625, 0.375, 0.0], [0.7, 0.3, 0.0], [0.0, 0.3, 0.7]],
+            ColorPalette::Protanopia => [[0.567, 0.433, 0.0], [0.558, 0.442, 0.0], [0.0, 0.242, 0.758]],
+            ColorPalette::Tritanopia => [[0.95, 0.05, 0.0], [0.0, 0.433, 0.567], [0.0, 0.475, 0.525]],
+        };
+
+        Some(rows(simulation))
+    }
+}
+
+/// Matrix from its rows, as cgmath matrices are constructed from their columns
+fn rows(rows: [[f32; 3]; 3]) -> Matrix3<f32> {
+    Matrix3::from(rows).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Vector3};
+    use strum::IntoEnumIterator;
+
+    use crate::accessibility::ColorPalette;
+
+    #[test]
+    fn test_palettes_keep_grays_and_change_colors() {
+        let gray = Vector3::new(0.3, 0.3, 0.3);
+        let red = Vector3::new(0.8, 0.1, 0.1);
+
+        assert_eq!(ColorPalette::Default.apply(red), red);
+        for palette in ColorPalette::iter().skip(1) {
+            assert!((palette.apply(gray) - gray).magnitude() < 1e-5, "{palette:?}");
+            assert!((palette.apply(red) - red).magnitude() > 0.01, "{palette:?}");
+        }
+    }
+}
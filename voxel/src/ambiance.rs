@@ -0,0 +1,200 @@
+// Without the audio feature, the ambiance loops are only synthesized by the tests
+#![cfg_attr(not(feature = "audio"), allow(dead_code))]
+
+use std::f32::consts::TAU;
+
+use fastrand::Rng;
+#[cfg(feature = "audio")]
+use log::{info, warn};
+
+use crate::cvars::CVar;
+use crate::environment::{Environment, EnvironmentState};
+
+const SAMPLE_RATE: u32 = 44100;
+/// Average time between two drips in caves in seconds
+const DRIP_INTERVAL: f32 = 1.5;
+/// Samples after which all swells repeat, so that their phase stays precise however long the loops play
+const SWELL_LOOP_LENGTH: u32 = 14 * SAMPLE_RATE;
+
+pub(crate) static AMBIANCE_VOLUME: CVar = cvar!("audio.ambiance_volume", 0.5);
+
+/// Plays the ambiance loop of every environment with the blend weight of the environment as its volume, see [EnvironmentState::weights].
+///
+/// Sound is only played if the engine is built with the `audio` feature and an audio output device is available.
+pub struct AmbiancePlayer {
+    /// The stream has to be kept alive for as long as the sinks play
+    #[cfg(feature = "audio")]
+    output: Option<(rodio::OutputStream, Vec<(Environment, rodio::Sink)>)>,
+}
+
+impl AmbiancePlayer {
+    /// Starts all ambiance loops muted on the default output device
+    #[cfg(feature = "audio")]
+    pub fn new() -> Self {
+        let output = match open_output() {
+            Ok(output) => Some(output),
+            Err(err) => {
+                warn!("Could not play the ambiance: {err:#}");
+                None
+            }
+        };
+
+        Self { output }
+    }
+
+    #[cfg(not(feature = "audio"))]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Fades the ambiance loops along with the transitions between environments
+    #[cfg(feature = "audio")]
+    pub fn update(&self, environment: &EnvironmentState) {
+        let Some((_, sinks)) = &self.output else {
+            return;
+        };
+
+        for (ambiance, sink) in sinks {
+            sink.set_volume(AMBIANCE_VOLUME.get() * environment.weights[*ambiance]);
+        }
+    }
+
+    #[cfg(not(feature = "audio"))]
+    pub fn update(&self, _environment: &EnvironmentState) {}
+}
+
+#[cfg(feature = "audio")]
+fn open_output() -> anyhow::Result<(rodio::OutputStream, Vec<(Environment, rodio::Sink)>)> {
+    use anyhow::Context;
+
+    let (stream, handle) = rodio::OutputStream::try_default().context("there is no audio output device")?;
+    let sinks = [Environment::Surface, Environment::Underground, Environment::Underwater]
+        .into_iter()
+        .map(|environment| {
+            let sink = rodio::Sink::try_new(&handle)?;
+            sink.set_volume(0.0);
+            sink.append(AmbianceSynth::new(environment));
+            Ok((environment, sink))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    info!("Playing the ambiance");
+
+    Ok((stream, sinks))
+}
+
+/// Endless loop of the ambiance of an environment, which is synthesized from filtered noise so that no sound files are needed.
+/// Mono samples from -1 to 1 at [SAMPLE_RATE].
+pub struct AmbianceSynth {
+    environment: Environment,
+    rng: Rng,
+    /// Index of the next sample in the [SWELL_LOOP_LENGTH]
+    time: u32,
+    /// White noise after a low-pass filter
+    filtered: f32,
+    drip_frequency: f32,
+    drip_phase: f32,
+    /// Loudness of the drip that is currently ringing, which decays with every sample
+    drip_amplitude: f32,
+}
+
+impl AmbianceSynth {
+    pub fn new(environment: Environment) -> Self {
+        Self {
+            environment,
+            rng: Rng::with_seed(environment as u64),
+            time: 0,
+            filtered: 0.0,
+            drip_frequency: 0.0,
+            drip_phase: 0.0,
+            drip_amplitude: 0.0,
+        }
+    }
+
+    /// Passes white noise through a one-pole low-pass filter, where a smaller `smoothing` leaves lower frequencies
+    fn filtered_noise(&mut self, smoothing: f32) -> f32 {
+        let noise = self.rng.f32() * 2.0 - 1.0;
+        self.filtered += smoothing * (noise - self.filtered);
+        self.filtered
+    }
+
+    /// Slow sine with the given period in seconds, which has to divide the [SWELL_LOOP_LENGTH], from 0 to 1
+    fn swell(&self, period: f32) -> f32 {
+        0.5 + 0.5 * (TAU * self.time as f32 / (period * SAMPLE_RATE as f32)).sin()
+    }
+
+    fn drip(&mut self) -> f32 {
+        if self.rng.f32() < 1.0 / (DRIP_INTERVAL * SAMPLE_RATE as f32) {
+            self.drip_frequency = 800.0 + 800.0 * self.rng.f32();
+            self.drip_amplitude = 0.4;
+        }
+        if self.drip_amplitude < 1e-3 {
+            return 0.0;
+        }
+
+        // Drips rise slightly in pitch while they ring
+        self.drip_frequency *= 1.00002;
+        self.drip_phase = (self.drip_phase + TAU * self.drip_frequency / SAMPLE_RATE as f32) % TAU;
+        self.drip_amplitude *= 0.9995;
+        self.drip_phase.sin() * self.drip_amplitude
+    }
+}
+
+impl Iterator for AmbianceSynth {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = match self.environment {
+            // Gusts of wind that swell and fade
+            Environment::Surface => self.filtered_noise(0.02) * 4.0 * (0.4 + 0.6 * self.swell(7.0)),
+            // A quiet rumble with water dripping from the ceiling
+            Environment::Underground => self.filtered_noise(0.002) * 3.0 + self.drip(),
+            // Muffled flow of water with slow waves
+            Environment::Underwater => self.filtered_noise(0.005) * 8.0 * (0.7 + 0.3 * self.swell(2.0)),
+        };
+        self.time = (self.time + 1) % SWELL_LOOP_LENGTH;
+
+        Some(sample.clamp(-1.0, 1.0))
+    }
+}
+
+#[cfg(feature = "audio")]
+impl rodio::Source for AmbianceSynth {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ambiance::{AmbianceSynth, SAMPLE_RATE};
+    use crate::environment::Environment;
+
+    #[test]
+    fn test_ambiance_loops_are_audible_without_clipping() {
+        for environment in [Environment::Surface, Environment::Underground, Environment::Underwater] {
+            let samples = AmbianceSynth::new(environment)
+                .take(3 * SAMPLE_RATE as usize)
+                .collect::<Vec<_>>();
+
+            let rms = (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt();
+            assert!(rms > 0.01, "{environment:?} is silent with an RMS of {rms}");
+            let clipped = samples
+                .iter()
+                .filter(|sample| sample.abs() >= 1.0)
+                .count();
+            assert!(clipped < samples.len() / 1000, "{environment:?} clips {clipped} samples");
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use cgmath::{InnerSpace, Point3, Vector3};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::rendering::camera::Camera;
+use crate::rendering::gpu_timer::GpuPassTiming;
+use crate::timing::TimerManager;
+use crate::world::chunk_manager::ChunkManager;
+
+/// Corners of the flight path relative to the start position. The camera flies through them in order at [FLIGHT_SPEED].
+const FLIGHT_PATH: [[f32; 3]; 6] = [
+    [0.0, 30.0, 0.0],
+    [400.0, 40.0, 0.0],
+    [400.0, 60.0, 400.0],
+    [-200.0, 40.0, 400.0],
+    [-200.0, 20.0, -200.0],
+    [0.0, 30.0, 0.0],
+];
+/// Speed of the camera along the flight path in voxels per second
+const FLIGHT_SPEED: f32 = 24.0;
+/// The camera looks slightly downwards, so that the terrain and not the sky fills most of the frame
+const LOOK_DOWN: f32 = -0.3;
+
+/// Settings of a benchmark run
+#[derive(Clone, Debug)]
+pub struct BenchmarkConfig {
+    /// JSON file that the summary is written to. The frame times of all frames are written next to it as CSV.
+    pub report_path: PathBuf,
+    pub thresholds: BenchmarkThresholds,
+    /// Draws the chunks from the closest to the farthest one, see [crate::world::chunk_renderer::ChunkRenderManager::front_to_back].
+    /// Comparing the GPU time of the chunk passes of runs with and without it shows how much overdraw it saves.
+    pub front_to_back: bool,
+}
+
+/// Limits that fail the benchmark if they are exceeded, e.g. to detect performance regressions in CI
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BenchmarkThresholds {
+    pub max_average_frame_time_ms: Option<f32>,
+    pub max_p99_frame_time_ms: Option<f32>,
+    pub min_meshed_chunks_per_second: Option<f32>,
+}
+
+/// Flies the camera along a scripted path and records frame times and chunk streaming throughput
+pub struct Benchmark {
+    config: BenchmarkConfig,
+    start: Point3<f32>,
+    /// Simulated time since the start of the flight
+    elapsed: Duration,
+    /// Wall time since the start of the flight in seconds
+    wall_time: f32,
+    frame_times_ms: Vec<f32>,
+    generation_time: f32,
+    meshing_time: f32,
+    /// Total GPU time and number of frames of every timed pass, by the name of the pass
+    gpu_pass_times: BTreeMap<String, (f32, u32)>,
+    /// Counters of the chunk manager at the start of the benchmark
+    initial_counts: (u64, u64),
+}
+
+/// Summary of a benchmark run
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub frames: usize,
+    pub duration_s: f32,
+    pub average_frame_time_ms: f32,
+    pub p50_frame_time_ms: f32,
+    pub p99_frame_time_ms: f32,
+    pub max_frame_time_ms: f32,
+    pub generated_chunks: u64,
+    pub generated_chunks_per_second: f32,
+    /// Time the main thread spent on attaching generated chunks, see [ChunkManager::generate_chunks]
+    pub generation_time_s: f32,
+    pub meshed_chunks: u64,
+    pub meshed_chunks_per_second: f32,
+    pub meshing_time_s: f32,
+    pub front_to_back: bool,
+    /// Average GPU time of every timed pass in the frames in which it was timed, by the name of the pass
+    pub average_gpu_pass_times_ms: BTreeMap<String, f32>,
+    /// Descriptions of all thresholds that were exceeded
+    pub failed_thresholds: Vec<String>,
+}
+
+impl Benchmark {
+    pub fn new(config: BenchmarkConfig, start: Point3<f32>, chunk_manager: &ChunkManager) -> Self {
+        info!(
+            "Running benchmark for {:.0}s, the report is written to {:?}",
+            path_length() / FLIGHT_SPEED,
+            config.report_path
+        );
+
+        Self {
+            config,
+            start,
+            elapsed: Duration::ZERO,
+            wall_time: 0.0,
+            frame_times_ms: Vec::new(),
+            generation_time: 0.0,
+            meshing_time: 0.0,
+            gpu_pass_times: BTreeMap::new(),
+            initial_counts: (chunk_manager.generated_chunks, chunk_manager.meshed_chunks),
+        }
+    }
+
+    pub fn front_to_back(&self) -> bool {
+        self.config.front_to_back
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed.as_secs_f32() * FLIGHT_SPEED >= path_length()
+    }
+
+    /// Advances the camera along the flight path by one simulation tick
+    pub fn update(&mut self, camera: &mut Camera, dt: Duration) {
+        self.elapsed += dt;
+
+        let (position, direction) = point_on_path(self.elapsed.as_secs_f32() * FLIGHT_SPEED);
+        camera.position = self.start + position;
+        camera.velocity = direction * FLIGHT_SPEED;
+        camera.look_in(Vector3::new(direction.x, LOOK_DOWN, direction.z));
+    }
+
+    /// Records a finished frame with the GPU timings that were read back most recently. Must be called after [TimerManager::finish_frame].
+    pub fn record_frame(&mut self, dt: Duration, timer: &TimerManager, gpu_timings: &[GpuPassTiming]) {
+        let last = |span: &str| {
+            timer
+                .find(&["render_all", "chunk_manager", span])
+                .map_or(0.0, |span| span.last())
+        };
+
+        self.wall_time += dt.as_secs_f32();
+        self.frame_times_ms
+            .push(dt.as_secs_f32() * 1000.0);
+        self.generation_time += last("chunk_manager_generate_chunks");
+        self.meshing_time += last("chunk_manager_meshing");
+        for timing in gpu_timings {
+            let (total, frames) = self
+                .gpu_pass_times
+                .entry(timing.name.clone())
+                .or_default();
+            *total += timing.duration;
+            *frames += 1;
+        }
+    }
+
+    pub fn report(&self, chunk_manager: &ChunkManager) -> BenchmarkReport {
+        let mut sorted = self.frame_times_ms.clone();
+        sorted.sort_by(f32::total_cmp);
+
+        let generated_chunks = chunk_manager.generated_chunks - self.initial_counts.0;
+        let meshed_chunks = chunk_manager.meshed_chunks - self.initial_counts.1;
+        let per_second = |count: u64| count as f32 / self.wall_time.max(f32::EPSILON);
+
+        let mut report = BenchmarkReport {
+            frames: sorted.len(),
+            duration_s: self.wall_time,
+            average_frame_time_ms: sorted.iter().sum::<f32>() / sorted.len().max(1) as f32,
+            p50_frame_time_ms: percentile(&sorted, 0.5),
+            p99_frame_time_ms: percentile(&sorted, 0.99),
+            max_frame_time_ms: sorted.last().copied().unwrap_or(0.0),
+            generated_chunks,
+            generated_chunks_per_second: per_second(generated_chunks),
+            generation_time_s: self.generation_time,
+            meshed_chunks,
+            meshed_chunks_per_second: per_second(meshed_chunks),
+            meshing_time_s: self.meshing_time,
+            front_to_back: self.config.front_to_back,
+            average_gpu_pass_times_ms: self
+                .gpu_pass_times
+                .iter()
+                .map(|(name, &(total, frames))| (name.clone(), total / frames as f32 * 1000.0))
+                .collect(),
+            failed_thresholds: Vec::new(),
+        };
+        report.failed_thresholds = report.check(&self.config.thresholds);
+
+        report
+    }
+
+    /// Writes the report and returns the exit code of the benchmark, which is 1 if any threshold was exceeded
+    pub fn finish(&self, chunk_manager: &ChunkManager) -> i32 {
+        let report = self.report(chunk_manager);
+        info!(
+            "Benchmark finished: {} frames, {:.2}ms average, {:.2}ms p99, {:.1} meshed chunks per second",
+            report.frames, report.average_frame_time_ms, report.p99_frame_time_ms, report.meshed_chunks_per_second
+        );
+
+        if let Err(err) = self.write_report(&report) {
+            warn!("Failed to write the benchmark report: {err:#}");
+            return 2;
+        }
+
+        for failed in &report.failed_thresholds {
+            warn!("Benchmark threshold exceeded: {failed}");
+        }
+
+        if report.failed_thresholds.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn write_report(&self, report: &BenchmarkReport) -> Result<()> {
+        let path = &self.config.report_path;
+        let json = serde_json::to_string_pretty(report).context("could not serialize the benchmark report")?;
+        fs::write(path, json).with_context(|| format!("could not write the benchmark report to {path:?}"))?;
+
+        let csv_path = path.with_extension("csv");
+        let csv: String = ["frame,frame_time_ms".to_owned()]
+            .into_iter()
+            .chain(
+                self.frame_times_ms
+                    .iter()
+                    .enumerate()
+                    .map(|(frame, time)| format!("{frame},{time}")),
+            )
+            .map(|line| line + "\n")
+            .collect();
+        fs::write(&csv_path, csv).with_context(|| format!("could not write the frame times to {csv_path:?}"))
+    }
+}
+
+impl BenchmarkReport {
+    /// Descriptions of all thresholds that this report exceeds
+    pub fn check(&self, thresholds: &BenchmarkThresholds) -> Vec<String> {
+        let mut failed = Vec::new();
+
+        if let Some(max) = thresholds.max_average_frame_time_ms {
+            if self.average_frame_time_ms > max {
+                failed.push(format!(
+                    "average frame time of {:.2}ms is above {max}ms",
+                    self.average_frame_time_ms
+                ));
+            }
+        }
+        if let Some(max) = thresholds.max_p99_frame_time_ms {
+            if self.p99_frame_time_ms > max {
+                failed.push(format!("p99 frame time of {:.2}ms is above {max}ms", self.p99_frame_time_ms));
+            }
+        }
+        if let Some(min) = thresholds.min_meshed_chunks_per_second {
+            if self.meshed_chunks_per_second < min {
+                failed.push(format!(
+                    "{:.1} meshed chunks per second is below {min}",
+                    self.meshed_chunks_per_second
+                ));
+            }
+        }
+
+        failed
+    }
+}
+
+/// Value below which the given fraction of the sorted values lie
+fn percentile(sorted: &[f32], fraction: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let index = ((sorted.len() - 1) as f32 * fraction).round() as usize;
+    sorted[index]
+}
+
+fn path_length() -> f32 {
+    FLIGHT_PATH
+        .windows(2)
+        .map(|corners| (Vector3::from(corners[1]) - Vector3::from(corners[0])).magnitude())
+        .sum()
+}
+
+/// Position relative to the start and normalized flight direction after flying the given distance along the path
+fn point_on_path(mut distance: f32) -> (Vector3<f32>, Vector3<f32>) {
+    for corners in FLIGHT_PATH.windows(2) {
+        let (from, to) = (Vector3::from(corners[0]), Vector3::from(corners[1]));
+        let length = (to - from).magnitude();
+        let direction = (to - from) / length;
+
+        if distance <= length {
+            return (from + direction * distance, direction);
+        }
+        distance -= length;
+    }
+
+    let [.., before_last, last] = FLIGHT_PATH.map(Vector3::from);
+    (last, (last - before_last).normalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use cgmath::Vector3;
+
+    use crate::benchmark::{percentile, point_on_path, BenchmarkReport, BenchmarkThresholds};
+
+    #[test]
+    fn test_point_on_path() {
+        let (position, direction) = point_on_path(0.0);
+        assert_eq!(position, Vector3::new(0.0, 30.0, 0.0));
+        assert!(direction.x > 0.99);
+
+        let (end, _) = point_on_path(f32::MAX);
+        assert_eq!(end, Vector3::new(0.0, 30.0, 0.0));
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let sorted: Vec<f32> = (1..=100).map(|ms| ms as f32).collect();
+        assert_eq!(percentile(&sorted, 0.5), 51.0);
+        assert_eq!(percentile(&sorted, 0.99), 99.0);
+
+        let report = BenchmarkReport {
+            frames: 100,
+            duration_s: 10.0,
+            average_frame_time_ms: 16.0,
+            p50_frame_time_ms: 15.0,
+            p99_frame_time_ms: 40.0,
+            max_frame_time_ms: 50.0,
+            generated_chunks: 1000,
+            generated_chunks_per_second: 100.0,
+            generation_time_s: 1.0,
+            meshed_chunks: 500,
+            meshed_chunks_per_second: 50.0,
+            meshing_time_s: 1.0,
+            front_to_back: true,
+            average_gpu_pass_times_ms: BTreeMap::new(),
+            failed_thresholds: Vec::new(),
+        };
+
+        assert!(report
+            .check(&BenchmarkThresholds::default())
+            .is_empty());
+        let failed = report.check(&BenchmarkThresholds {
+            max_average_frame_time_ms: Some(20.0),
+            max_p99_frame_time_ms: Some(33.0),
+            min_meshed_chunks_per_second: Some(60.0),
+        });
+        assert_eq!(failed.len(), 2);
+    }
+}
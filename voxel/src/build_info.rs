@@ -0,0 +1,13 @@
+//! Identification of the build of the engine, e.g. to tell which build created a world or wrote a log
+
+/// Version of the engine, the commit it was built from and the build profile, e.g. `0.1.0 (1a2b3c4d5e, release)`.
+///
+/// The commit has a `-dirty` suffix if the build had uncommitted changes and is `unknown` outside of a git checkout.
+pub const BUILD_INFO: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("VOXEL_GIT_HASH"),
+    ", ",
+    env!("VOXEL_BUILD_PROFILE"),
+    ")"
+);
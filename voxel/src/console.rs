@@ -0,0 +1,163 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+/// An admin command line from the console or the admin port, which is answered with the output of the command
+pub struct Command {
+    pub line: String,
+    reply: Sender<String>,
+}
+
+impl Command {
+    /// The whitespace separated words of the command line
+    pub fn words(&self) -> Vec<&str> {
+        self.line.split_whitespace().collect()
+    }
+
+    /// Sends the output of the command back to where it was entered
+    pub fn reply(self, output: String) {
+        // The connection that sent the command may have been closed in the meantime
+        let _ = self.reply.send(output);
+    }
+}
+
+/// Collects the admin commands of the standard input and of an optional TCP admin port, so that all of them are executed
+/// by their owner in one place
+pub struct CommandBus {
+    sender: Sender<Command>,
+    receiver: Receiver<Command>,
+}
+
+impl CommandBus {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver }
+    }
+
+    /// Reads commands from the standard input and prints their output, until it is closed
+    pub fn read_stdin(&self) -> Result<()> {
+        let sender = self.sender.clone();
+        thread::Builder::new()
+            .name("console".to_owned())
+            .spawn(move || {
+                for line in io::stdin().lock().lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    match send(&sender, line) {
+                        Some(output) => println!("{output}"),
+                        None => break,
+                    }
+                }
+            })?;
+
+        Ok(())
+    }
+
+    /// Accepts commands line by line on the address, e.g. `127.0.0.1:4791`, and answers every command with its output.
+    /// Its connections are not authenticated, so it must only be reachable by admins.
+    pub fn listen(&self, address: &str) -> Result<()> {
+        let listener = TcpListener::bind(address).with_context(|| format!("could not listen for admins on {address}"))?;
+        info!("Accepting admin commands on {address}");
+
+        let sender = self.sender.clone();
+        thread::Builder::new()
+            .name("admin listener".to_owned())
+            .spawn(move || run_admin_listener(listener, &sender))?;
+
+        Ok(())
+    }
+
+    /// Waits for the next command that is entered
+    pub fn recv(&self) -> Command {
+        self.receiver
+            .recv()
+            .expect("the bus to keep a sender")
+    }
+
+    /// The next command that was entered, if any, without waiting for one
+    pub fn try_recv(&self) -> Option<Command> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Sends a command line to the owner of the bus and waits for its output, or None if the bus was dropped
+fn send(sender: &Sender<Command>, line: String) -> Option<String> {
+    let (reply, output) = mpsc::channel();
+    sender.send(Command { line, reply }).ok()?;
+    output.recv().ok()
+}
+
+fn run_admin_listener(listener: TcpListener, sender: &Sender<Command>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Could not accept an admin connection: {err}");
+                continue;
+            }
+        };
+        let sender = sender.clone();
+        let spawned = thread::Builder::new()
+            .name("admin connection".to_owned())
+            .spawn(move || {
+                if let Err(err) = handle_admin(stream, &sender) {
+                    warn!("Closed an admin connection: {err:#}");
+                }
+            });
+        if let Err(err) = spawned {
+            warn!("Could not handle an admin connection: {err}");
+        }
+    }
+}
+
+/// Forwards the commands of an admin connection line by line, until it disconnects or the bus is dropped
+fn handle_admin(stream: TcpStream, sender: &Sender<Command>) -> Result<()> {
+    let address = stream.peer_addr()?;
+    info!("Admin connected from {address}");
+
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        info!("Admin command from {address}: {line}");
+        let Some(output) = send(sender, line) else {
+            break;
+        };
+        writeln!(writer, "{output}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::console::{send, CommandBus};
+
+    #[test]
+    fn commands_are_answered_by_the_owner_of_the_bus() {
+        let bus = CommandBus::new();
+        let sender = bus.sender.clone();
+        let client = thread::spawn(move || send(&sender, "kick  steve".to_owned()));
+
+        let command = loop {
+            if let Some(command) = bus.try_recv() {
+                break command;
+            }
+            thread::yield_now();
+        };
+        assert_eq!(command.words(), ["kick", "steve"]);
+        command.reply("Kicked steve".to_owned());
+
+        assert_eq!(client.join().unwrap().as_deref(), Some("Kicked steve"));
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use lazy_static::lazy_static;
+
+/// A constant that can be inspected and changed while the engine is running, e.g. from the console of the debug overlay.
+///
+/// Console variables are declared as statics with [cvar!] and listed in [CVARS], so that all of them are registered at startup.
+/// Values that differ from the default are saved with the [crate::settings::Settings].
+pub struct CVar {
+    pub name: &'static str,
+    pub default: f32,
+    /// Bits of the current value, so that reading it does not need to lock the registry
+    value: AtomicU32,
+    registered: AtomicBool,
+}
+
+impl CVar {
+    pub const fn new(name: &'static str, default: f32) -> Self {
+        Self {
+            name,
+            default,
+            value: AtomicU32::new(default.to_bits()),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get(&'static self) -> f32 {
+        // Console variables that are missing from [CVARS] are registered once they are read
+        if !self.registered.load(Ordering::Acquire) {
+            REGISTRY.lock().unwrap().register(self);
+        }
+
+        f32::from_bits(self.value.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, value: f32) {
+        self.value
+            .store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// All console variables of the engine, which are registered before any of them is read or set
+static CVARS: &[&CVar] = &[
+    &crate::ambiance::AMBIANCE_VOLUME,
+    &crate::rendering::camera::CAMERA_EYE_OFFSET,
+    &crate::rendering::camera::GRAVITY,
+    &crate::rendering::camera::JUMP_ACCELERATION,
+    &crate::world::chunk_manager::DATA_GEN_THREAD_BATCH_SIZE,
+    &crate::world::chunk_manager::MAX_UNLOADS_PER_TICK,
+    &crate::world::entities::ENTITY_GRAVITY,
+];
+
+lazy_static! {
+    static ref REGISTRY: Mutex<CVarRegistry> = {
+        let mut registry = CVarRegistry::default();
+        for &cvar in CVARS {
+            registry.register(cvar);
+        }
+        Mutex::new(registry)
+    };
+}
+
+/// All registered console variables, by their name
+#[derive(Default)]
+struct CVarRegistry {
+    cvars: BTreeMap<&'static str, &'static CVar>,
+}
+
+impl CVarRegistry {
+    fn register(&mut self, cvar: &'static CVar) {
+        if let Some(existing) = self.cvars.insert(cvar.name, cvar) {
+            if !std::ptr::eq(existing, cvar) {
+                panic!("the console variable {} is declared twice", cvar.name);
+            }
+        }
+        cvar.registered.store(true, Ordering::Release);
+    }
+
+    /// Sets a console variable by its name, or fails if there is no console variable with this name
+    fn set(&mut self, name: &str, value: f32) -> Result<()> {
+        let Some(cvar) = self.cvars.get(name) else {
+            bail!("there is no console variable {name:?}");
+        };
+
+        cvar.set(value);
+        Ok(())
+    }
+
+    /// Runs a console command and returns the text that is printed as its result.
+    ///
+    /// `<name>` prints the value of a console variable, `<name> <value>` sets it and `reset <name>` sets it back to its default.
+    fn execute(&mut self, command: &str) -> Result<String> {
+        let words = command.split_whitespace().collect::<Vec<_>>();
+        let (name, value) = match words[..] {
+            ["reset", name] => (name, None),
+            [name] => return self.describe(name),
+            [name, value] => (
+                name,
+                Some(
+                    value
+                        .parse::<f32>()
+                        .with_context(|| format!("{value:?} is not a number"))?,
+                ),
+            ),
+            _ => bail!("expected `<name>`, `<name> <value>` or `reset <name>`"),
+        };
+        let Some(cvar) = self.cvars.get(name) else {
+            bail!("there is no console variable {name:?}");
+        };
+
+        cvar.set(value.unwrap_or(cvar.default));
+        self.describe(name)
+    }
+
+    fn describe(&self, name: &str) -> Result<String> {
+        let Some(cvar) = self.cvars.get(name) else {
+            bail!("there is no console variable {name:?}");
+        };
+
+        Ok(format!(
+            "{name} = {} (default {})",
+            f32::from_bits(cvar.value.load(Ordering::Relaxed)),
+            cvar.default
+        ))
+    }
+
+    fn changed_values(&self) -> BTreeMap<String, f32> {
+        let mut values = BTreeMap::new();
+        for cvar in self.cvars.values() {
+            let value = f32::from_bits(cvar.value.load(Ordering::Relaxed));
+            if value != cvar.default {
+                values.insert(cvar.name.to_owned(), value);
+            }
+        }
+
+        values
+    }
+}
+
+/// Name, current value and default of every console variable, sorted by their name
+pub fn list() -> Vec<(&'static str, f32, f32)> {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .cvars
+        .values()
+        .map(|cvar| (cvar.name, f32::from_bits(cvar.value.load(Ordering::Relaxed)), cvar.default))
+        .collect()
+}
+
+/// Sets a console variable by its name, or fails if there is no console variable with this name
+pub fn set(name: &str, value: f32) -> Result<()> {
+    REGISTRY.lock().unwrap().set(name, value)
+}
+
+/// Runs a command of the console, see [CVarRegistry::execute]
+pub fn execute(command: &str) -> Result<String> {
+    REGISTRY.lock().unwrap().execute(command)
+}
+
+/// Values of all console variables that differ from their default, which are the ones that are saved
+pub fn changed_values() -> BTreeMap<String, f32> {
+    REGISTRY.lock().unwrap().changed_values()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cvars::{CVar, CVarRegistry, CVARS};
+
+    static SPEED: CVar = cvar!("test.speed", 2.0);
+    static HEIGHT: CVar = cvar!("test.height", 5.0);
+
+    #[test]
+    fn test_console_commands_change_registered_values() {
+        let mut registry = CVarRegistry::default();
+        registry.register(&SPEED);
+        registry.register(&HEIGHT);
+        registry.set("test.height", 7.0).unwrap();
+        assert_eq!(
+            f32::from_bits(
+                HEIGHT
+                    .value
+                    .load(std::sync::atomic::Ordering::Relaxed)
+            ),
+            7.0
+        );
+
+        assert_eq!(registry.execute("test.speed 3.5").unwrap(), "test.speed = 3.5 (default 2)");
+        assert_eq!(
+            registry
+                .changed_values()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [("test.height".to_owned(), 7.0), ("test.speed".to_owned(), 3.5)]
+        );
+        assert_eq!(registry.execute("reset test.speed").unwrap(), "test.speed = 2 (default 2)");
+
+        assert!(registry.execute("test.speed fast").is_err());
+        assert!(registry.execute("test.unknown").is_err());
+        assert!(registry.execute("").is_err());
+    }
+
+    static DEPTH: CVar = cvar!("test.depth", 1.0);
+
+    #[test]
+    fn test_console_commands_set_values_of_console_variables_that_are_not_registered_yet() {
+        let mut registry = CVarRegistry::default();
+        for &cvar in CVARS {
+            registry.register(cvar);
+        }
+        // Every console variable of the engine is known before it is read for the first time
+        assert_eq!(registry.execute("physics.gravity").unwrap(), "physics.gravity = 300 (default 300)");
+
+        registry.register(&DEPTH);
+        assert_eq!(registry.execute("test.depth 4").unwrap(), "test.depth = 4 (default 1)");
+
+        // Misspelled names are rejected instead of being saved with the settings
+        assert!(registry.execute("test.dpeth 4").is_err());
+        assert!(registry.execute("reset test.dpeth").is_err());
+        assert!(registry.set("test.dpeth", 4.0).is_err());
+        assert_eq!(
+            registry
+                .changed_values()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [("test.depth".to_owned(), 4.0)]
+        );
+    }
+}
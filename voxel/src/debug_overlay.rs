@@ -1,51 +1,159 @@
 use std::borrow::Borrow;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
 use std::mem;
-use std::sync::Arc;
+use std::path::PathBuf;
 
-use cgmath::Vector3;
-use egui::{ClippedPrimitive, CollapsingHeader, CollapsingResponse, Color32, Context, Slider, Ui, Visuals, WidgetText};
+use cgmath::{EuclideanSpace, Point3, Vector3};
+use egui::{
+    pos2, vec2, Align2, ClippedPrimitive, CollapsingHeader, CollapsingResponse, Color32, Context, FontId, Painter, Rect, Sense, Slider,
+    Stroke, Ui, Visuals, WidgetText,
+};
 use egui_wgpu::renderer::ScreenDescriptor;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
 use wgpu::TextureFormat::Depth32Float;
-use wgpu::{BindGroup, CommandEncoder, RenderPass};
+use wgpu::{CommandEncoder, RenderPass};
 use winit::event::WindowEvent;
 use winit::window::Window;
 
+use crate::accessibility::{Accessibility, ColorPalette};
+use crate::build_info::BUILD_INFO;
+use crate::cvars;
+use crate::environment::{Environment, EnvironmentState};
+use crate::game_mode::GameMode;
+use crate::photo_mode::{PhotoSettings, PHOTO_SUPERSAMPLING};
+use crate::power_saving::PowerSavingMode;
+use crate::rendering::day_cycle::DayCycle;
+use crate::rendering::gpu_timer::GpuPassTiming;
+use crate::rendering::shadow_cascades::SHADOW_CASCADE_COUNT;
+use crate::rendering::temporal::TemporalMode;
+use crate::rendering::uniform_ring::UniformBinding;
 use crate::rendering::{RenderCtx, Renderer};
+use crate::setup_wizard::SetupWizard;
 use crate::timing::TimerManager;
+use crate::world::chunk_manager::{ChunkFailure, DimensionId, DEFAULT_MESH_BUDGET};
+use crate::world::chunk_renderer::block_icons::BlockIcons;
+use crate::world::chunk_renderer::ray_tracing::LightingMode;
+use crate::world::chunk_renderer::{MeshingMode, RenderMode, DEFAULT_AMBIENT_OCCLUSION_STRENGTH};
+use crate::world::inventory::Inventory;
+use crate::world::location::ChunkLocation;
+use crate::world::noise_source::NoiseKind;
+use crate::world::portals::{Portal, Portals};
+use crate::world::voxel_data::VoxelType;
+use crate::world::worldgen::terrain_heightmap;
+use crate::world::worldgen_preset::WorldgenPreset;
+
+/// Number of pixels along each side of the terrain preview image
+const TERRAIN_PREVIEW_SIZE: usize = 256;
 
 pub struct DebugOverlay {
     winit_state: egui_winit::State,
     context: Context,
     renderer: egui_wgpu::Renderer,
     screen_descriptor: ScreenDescriptor,
-    render_ctx: Arc<RenderCtx>,
+    block_icons: BlockIcons,
+    block_icons_texture: egui::TextureId,
 
     paint_jobs: Option<Vec<ClippedPrimitive>>,
 
     last_fps_counts: VecDeque<f32>,
     pub render_distance: i32,
+    /// Seconds ahead of the player movement that chunks are prefetched
+    pub prefetch_time: f32,
+    /// Rings beyond the render distance that are generated while the streaming is idle
+    pub deep_generation_distance: i32,
+    pub voxel_memory_budget_mb: usize,
+    /// Milliseconds per frame that are spent on meshing and uploading chunks
+    pub mesh_budget_ms: f32,
     pub render_empty_chunks: bool,
+    pub depth_prepass: bool,
+    /// Draw the closest chunks first, so that more fragments are rejected by the early depth test
+    pub front_to_back: bool,
+    /// Keep CPU-side copies of chunk meshes, so that they do not have to be remeshed if the GPU device is lost
+    pub retain_cpu_meshes: bool,
+    pub meshing_mode: MeshingMode,
+    /// Draw the chunks in the generation, meshing and unload queues
+    pub queue_boxes: bool,
+    pub render_mode: RenderMode,
+    /// Outline the chunks around the camera, colored by their state
+    pub chunk_outlines: bool,
+    /// Distance in chunks from the camera up to which chunks are outlined
+    pub chunk_outline_distance: i32,
+    pub voxel_grid: bool,
+    pub clouds: bool,
+    /// Light the frame with one bounce of the sky light, see [crate::rendering::light_bounce::LightBounce]
+    pub light_bounce: bool,
+    /// Fraction of the sky that is covered by clouds
+    pub cloud_coverage: f32,
+    /// Density of the exponential fog in which far chunks fade, 0 to disable it
+    pub fog_density: f32,
+    /// Color of the distance fog, or None to use the fog color of the environment
+    pub fog_color: Option<[f32; 3]>,
+    /// Sun glare and exposure adaptation
+    pub lens_effects: bool,
+    pub temporal_mode: TemporalMode,
+    pub lighting_mode: LightingMode,
+    pub skin_ring_width: i32,
+    /// Distance in chunks after which the next coarser level of detail of chunk meshes is drawn, 0 to disable it
+    pub lod_distance: i32,
+    pub ambient_occlusion: bool,
+    /// How much fully occluded corners of voxel faces are darkened
+    pub ambient_occlusion_strength: f32,
+    /// Whether voxel faces are lit with normals that are smoothed across their edges
+    pub smooth_normals: bool,
+    /// Whether flying is requested. Only used in game modes that allow toggling it.
     pub no_clip: bool,
+    pub game_mode: GameMode,
+    pub inventory_open: bool,
+    /// Whether the simulation is paused. Rendering continues while paused.
+    pub paused: bool,
+    /// Set to advance a paused simulation by a single tick
+    pub step_requested: bool,
+    /// Multiplier for the speed of the simulation. Does not affect rendering.
+    pub timescale: f32,
+    /// Whether the photo mode is requested, which pauses the simulation and shows only the photo mode window
+    pub photo_mode: bool,
+    pub photo: PhotoSettings,
+    /// Set to take a photo with the current photo settings
+    pub photo_requested: bool,
+    /// Setup screen of the first run, which is shown instead of all other windows until the player is done
+    pub setup_wizard: Option<SetupWizard>,
+    pub theme: OverlayTheme,
+    pub accessibility: Accessibility,
+    pub power_saving_mode: PowerSavingMode,
+    /// Target of newly added portals
+    portal_target: [f32; 3],
+    portal_target_dimension: Option<String>,
+    /// Set to switch to another dimension
+    pub requested_dimension: Option<DimensionId>,
+    terrain_preview: TerrainPreview,
+    /// Set to load the worldgen preset file again and regenerate the active dimension with it
+    pub worldgen_preset_reload_requested: bool,
+    /// Set to switch back to the worldgen preset that was active before the last reload
+    pub worldgen_preset_swap_requested: bool,
+    /// Chunks within this distance to the player keep their terrain when the worldgen preset changes. Zero regenerates all chunks.
+    pub worldgen_keep_radius: i32,
+    /// Set to capture the next frame with RenderDoc
+    pub frame_capture_requested: bool,
+    /// Set to throw a copy of the held block as an entity
+    pub entity_spawn_requested: bool,
+    /// Command line of the console and the result of the last command
+    console_command: String,
+    console_output: String,
 
     output: Option<egui::FullOutput>,
 }
 
 impl DebugOverlay {
-    pub fn new(render_ctx: Arc<RenderCtx>, window: &Window) -> Self {
+    pub fn new(render_ctx: &RenderCtx, window: &Window, theme: OverlayTheme) -> Self {
         let context = Context::default();
         let winit_state = egui_winit::State::new(context.viewport_id(), window, None, None);
 
-        let render_pass = egui_wgpu::Renderer::new(
-            &render_ctx.device,
-            render_ctx
-                .surface_config
-                .try_lock()
-                .expect("i hope this isn't locked")
-                .format,
-            Some(Depth32Float),
-            1,
-        );
+        let mut render_pass = create_renderer(render_ctx);
+        let (block_icons, block_icons_texture) = register_block_icons(render_ctx, &mut render_pass);
 
         let screen_descriptor = ScreenDescriptor {
             pixels_per_point: window.scale_factor() as f32,
@@ -59,14 +167,81 @@ impl DebugOverlay {
             screen_descriptor,
             last_fps_counts: VecDeque::with_capacity(10),
             render_distance: 12,
+            prefetch_time: 2.0,
+            deep_generation_distance: 4,
+            voxel_memory_budget_mb: 256,
+            mesh_budget_ms: DEFAULT_MESH_BUDGET.as_secs_f32() * 1000.0,
             render_empty_chunks: false,
+            depth_prepass: false,
+            front_to_back: true,
+            retain_cpu_meshes: false,
+            meshing_mode: MeshingMode::default(),
+            queue_boxes: false,
+            render_mode: RenderMode::default(),
+            chunk_outlines: false,
+            chunk_outline_distance: 2,
+            voxel_grid: false,
+            clouds: true,
+            light_bounce: false,
+            cloud_coverage: 0.4,
+            fog_density: 0.0005,
+            fog_color: None,
+            lens_effects: true,
+            temporal_mode: TemporalMode::default(),
+            lighting_mode: LightingMode::default(),
+            skin_ring_width: 4,
+            lod_distance: 4,
+            ambient_occlusion: true,
+            ambient_occlusion_strength: DEFAULT_AMBIENT_OCCLUSION_STRENGTH,
+            smooth_normals: false,
             no_clip: true,
-            render_ctx,
+            game_mode: GameMode::Creative,
+            inventory_open: false,
+            paused: false,
+            step_requested: false,
+            timescale: 1.0,
+            photo_mode: false,
+            photo: PhotoSettings::default(),
+            photo_requested: false,
+            setup_wizard: None,
+            theme,
+            accessibility: Accessibility::default(),
+            power_saving_mode: PowerSavingMode::default(),
+            portal_target: [0.0; 3],
+            portal_target_dimension: None,
+            requested_dimension: None,
+            terrain_preview: TerrainPreview::default(),
+            worldgen_preset_reload_requested: false,
+            worldgen_preset_swap_requested: false,
+            worldgen_keep_radius: 0,
+            frame_capture_requested: false,
+            entity_spawn_requested: false,
+            console_command: String::new(),
+            console_output: String::new(),
+            block_icons,
+            block_icons_texture,
             paint_jobs: None,
             output: None,
         }
     }
 
+    /// Recreates the renderer after the device was lost.
+    /// The egui context is recreated as well, so that all of its textures are uploaded again.
+    pub fn rebuild_gpu_resources(&mut self, render_ctx: &RenderCtx) {
+        self.context = Context::default();
+        self.renderer = create_renderer(render_ctx);
+        (self.block_icons, self.block_icons_texture) = register_block_icons(render_ctx, &mut self.renderer);
+        self.paint_jobs = None;
+        self.terrain_preview.texture = None;
+    }
+
+    /// Renders the block icons again, e.g. after the palette of the voxel colors changed
+    pub fn rebuild_block_icons(&mut self, render_ctx: &RenderCtx) {
+        self.renderer
+            .free_texture(&self.block_icons_texture);
+        (self.block_icons, self.block_icons_texture) = register_block_icons(render_ctx, &mut self.renderer);
+    }
+
     pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
         let result = self
             .winit_state
@@ -81,7 +256,15 @@ impl DebugOverlay {
         result.consumed
     }
 
-    pub fn build_ui(&mut self, window: &Window, stats: PerFrameStats, timer: &mut TimerManager) {
+    pub fn build_ui(
+        &mut self,
+        window: &Window,
+        stats: PerFrameStats,
+        timer: &mut TimerManager,
+        portals: &mut Portals,
+        day_cycle: &mut DayCycle,
+        worldgen_preset: &WorldgenPreset,
+    ) {
         if self.last_fps_counts.len() == self.last_fps_counts.capacity() {
             self.last_fps_counts.pop_front();
         }
@@ -91,63 +274,540 @@ impl DebugOverlay {
         self.context
             .begin_frame(self.winit_state.take_egui_input(window));
 
-        self.context.set_visuals(Visuals {
-            window_fill: Color32::TRANSPARENT,
-            panel_fill: Color32::TRANSPARENT,
-            override_text_color: Some(Color32::RED),
-            faint_bg_color: Color32::RED,
-            extreme_bg_color: Color32::BLUE,
-            ..Default::default()
-        });
+        self.context.set_visuals(self.theme.visuals());
 
-        egui::CentralPanel::default().show(&self.context, |ui| {
-            ui.collapsing_opened("General", |ui| {
-                ui.label(format!("FPS: {:.1} ({:.2}ms)", average_fps, 1000.0 / average_fps));
-                ui.label(format!("Location: {:?}", stats.position));
-                ui.checkbox(&mut self.no_clip, "noclip");
-            });
+        if let Some(setup_wizard) = &mut self.setup_wizard {
+            egui::Window::new("Setup")
+                .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+                .collapsible(false)
+                .resizable(false)
+                .show(&self.context, |ui| setup_wizard.ui(ui, average_fps));
+            self.output = Some(self.context.end_frame());
+            return;
+        }
 
-            ui.collapsing_opened("Memory", |ui| {
-                ui.label(format!("Voxel data: {}MB", stats.total_voxel_data_size / 2_i32.pow(20) as usize));
-                ui.label(format!("Mesh data: {}MB", stats.total_mesh_data_size / 2_i32.pow(20) as usize));
-            });
+        if self.photo_mode {
+            egui::Window::new("Photo mode")
+                .default_pos([8.0, 8.0])
+                .show(&self.context, |ui| self.photo_mode_ui(ui));
+            self.output = Some(self.context.end_frame());
+            return;
+        }
 
-            ui.collapsing_opened("World generation", |ui| {
-                ui.label(format!("Total chunks: {}", stats.num_chunks));
-                ui.label(format!("Chunk gen queue size: {}", stats.current_chunkgen_queue_size));
-                ui.label(format!(
-                    "Generated pending chunk queue size: {}",
-                    stats.current_chunkdata_buffer_size
-                ));
-                ui.label(format!("Chunk mesh queue size: {}", stats.current_meshgen_queue_size));
-            });
+        egui::Window::new("Debug")
+            .default_pos([8.0, 8.0])
+            .show(&self.context, |ui| {
+                ui.collapsing_opened("General", |ui| {
+                    ui.label(format!("Build: {BUILD_INFO}"));
+                    ui.label(format!("FPS: {:.1} ({:.2}ms)", average_fps, 1000.0 / average_fps));
+                    egui::ComboBox::from_label("Power saving")
+                        .selected_text(self.power_saving_mode.name())
+                        .show_ui(ui, |ui| {
+                            for power_saving_mode in PowerSavingMode::iter() {
+                                ui.selectable_value(&mut self.power_saving_mode, power_saving_mode, power_saving_mode.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Halves the chunk generation threads, streams fewer chunks, limits the frame rate and the render distance",
+                        );
+                    if stats.power_saving {
+                        ui.label("Saving power");
+                    }
+                    ui.label(format!("Location: {:?}", stats.position));
+                    ui.label(format!("Environment: {}", stats.environment.current.name()))
+                        .on_hover_text(
+                            stats
+                                .environment
+                                .weights
+                                .iter()
+                                .map(|(environment, volume)| format!("{} ambiance: {:.0}%", environment.ambiance(), 100.0 * volume))
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        );
+                    let active_dimension = &stats.dimensions[stats.active_dimension];
+                    egui::ComboBox::from_label("Dimension")
+                        .selected_text(active_dimension)
+                        .show_ui(ui, |ui| {
+                            for (id, name) in stats.dimensions.iter().enumerate() {
+                                if ui
+                                    .selectable_label(id == stats.active_dimension, name)
+                                    .clicked()
+                                {
+                                    self.requested_dimension = Some(id);
+                                }
+                            }
+                        });
+                    egui::ComboBox::from_label("Game mode")
+                        .selected_text(self.game_mode.name())
+                        .show_ui(ui, |ui| {
+                            for game_mode in GameMode::iter() {
+                                ui.selectable_value(&mut self.game_mode, game_mode, game_mode.name());
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.add_enabled(
+                            self.game_mode.allows_flight_toggle(),
+                            egui::Checkbox::new(&mut self.no_clip, "noclip"),
+                        );
+                        ui.checkbox(&mut self.inventory_open, "inventory (E)");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.paused, "paused (P)");
+                        if ui
+                            .add_enabled(self.paused, egui::Button::new("step (.)"))
+                            .clicked()
+                        {
+                            self.step_requested = true;
+                        }
+                        if ui.button("photo mode (F2)").clicked() {
+                            self.photo_mode = true;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            Slider::new(&mut self.timescale, 0.01..=8.0)
+                                .logarithmic(true)
+                                .text("timescale"),
+                        );
+                        if ui.button("reset").clicked() {
+                            self.timescale = 1.0;
+                        }
+                    });
+                });
 
-            ui.collapsing_opened("Rendering", |ui| {
-                ui.add(Slider::new(&mut self.render_distance, 1..=32).text("Render distance"));
-                ui.label(format!(
-                    "Currently rendered chunk radius: {}",
-                    stats.currently_rendered_chunk_radius
-                ));
-                ui.label(format!("V: {}  T: {}", stats.num_vertices, stats.num_triangles));
-                ui.checkbox(&mut self.render_empty_chunks, "render empty chunks");
-            });
+                ui.collapsing_opened("Memory", |ui| {
+                    ui.label(format!("Voxel data: {}MB", stats.total_voxel_data_size / 2_i32.pow(20) as usize));
+                    ui.label(format!("Mesh data: {}MB", stats.total_mesh_data_size / 2_i32.pow(20) as usize));
+                    ui.label(format!(
+                        "Retained CPU meshes: {}MB",
+                        stats.retained_mesh_data_size / 2_i32.pow(20) as usize
+                    ));
+                    ui.label(format!("Chunks sharing GPU meshes: {}", stats.gpu_shared_chunks));
+                    ui.checkbox(&mut self.retain_cpu_meshes, "retain CPU meshes")
+                        .on_hover_text(
+                            "Keeps a copy of every chunk mesh generated afterwards, so that a lost GPU device does not require remeshing",
+                        );
+                });
 
-            ui.collapsing("Timing", |ui| {
-                timer
-                    .get_all()
-                    .iter()
-                    .for_each(|(name, duration_sec)| {
-                        ui.label(format!("{}: {:.2}ms", name, duration_sec * 1000.0));
+                ui.collapsing_opened("World generation", |ui| {
+                    ui.label(format!("Total chunks: {}", stats.num_chunks));
+                    ui.label(format!("Chunk gen queue size: {}", stats.current_chunkgen_queue_size));
+                    ui.label(format!(
+                        "Generated pending chunk queue size: {}",
+                        stats.current_chunkdata_buffer_size
+                    ));
+                    ui.label(format!("Chunk mesh queue size: {}", stats.current_meshgen_queue_size));
+                    ui.add(Slider::new(&mut self.mesh_budget_ms, 0.25..=16.0).text("Meshing budget (ms)"))
+                        .on_hover_text("Time per frame that is spent on meshing and uploading chunks, see the timing section");
+                    ui.add(Slider::new(&mut self.deep_generation_distance, 0..=16).text("Deep generation rings"))
+                        .on_hover_text(
+                            "Chunks beyond the render distance that are generated, but not meshed, while nothing else is streamed",
+                        );
+                    ui.add(Slider::new(&mut self.voxel_memory_budget_mb, 64..=4096).text("Voxel memory budget (MB)"));
+                    ui.label(format!("Deep generation radius: {}", stats.deep_generation_radius));
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.terrain_preview.open, "terrain preview");
+                        if ui.button("reload preset").clicked() {
+                            self.worldgen_preset_reload_requested = true;
+                        }
+                        if ui
+                            .add_enabled(stats.has_previous_worldgen_preset, egui::Button::new("swap with previous"))
+                            .on_hover_text("Switches between the reloaded preset and the one before it")
+                            .on_disabled_hover_text("Reload the preset first")
+                            .clicked()
+                        {
+                            self.worldgen_preset_swap_requested = true;
+                        }
                     });
-                timer.clear();
+                    ui.add(Slider::new(&mut self.worldgen_keep_radius, 0..=32).text("Keep chunks within"))
+                        .on_hover_text(
+                            "Chunks this close to the player keep their terrain when the preset changes, 0 regenerates all chunks",
+                        );
+                });
+
+                ui.collapsing("Diagnostics", |ui| {
+                    if stats.shader_errors.is_empty() {
+                        ui.label("No shader errors");
+                    }
+                    for (path, error) in &stats.shader_errors {
+                        ui.colored_label(Color32::RED, format!("{} failed to compile: {error}", path.display()))
+                            .on_hover_text("The previous pipelines are used until the shader compiles again");
+                    }
+
+                    if stats.failed_chunks.is_empty() {
+                        ui.label("No failed chunks");
+                        return;
+                    }
+
+                    ui.label(format!("Failed chunks: {}", stats.failed_chunks.len()))
+                        .on_hover_text("Chunks whose generation or meshing failed are rendered as magenta boxes until they are unloaded");
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for (location, failure) in &stats.failed_chunks {
+                                ui.colored_label(
+                                    Color32::from_rgb(255, 0, 255),
+                                    format!(
+                                        "[{}, {}, {}] {} failed: {}",
+                                        location.x,
+                                        location.y,
+                                        location.z,
+                                        failure.stage.name(),
+                                        failure.message
+                                    ),
+                                );
+                            }
+                        });
+                });
+
+                ui.collapsing_opened("Rendering", |ui| {
+                    ui.add(Slider::new(&mut self.render_distance, 1..=32).text("Render distance"));
+                    ui.add(Slider::new(&mut self.skin_ring_width, 0..=8).text("Heightmap skin ring"));
+                    ui.add(Slider::new(&mut self.lod_distance, 0..=16).text("LOD distance"));
+                    ui.add(Slider::new(&mut self.prefetch_time, 0.0..=5.0).text("Prefetch time (s)"));
+                    ui.label(format!(
+                        "Currently rendered chunk radius: {}",
+                        stats.currently_rendered_chunk_radius
+                    ));
+                    ui.label(format!("V: {}  T: {}", stats.num_vertices, stats.num_triangles));
+                    ui.label(if stats.gpu_culling {
+                        "Chunk rendering: GPU culled multi-draw"
+                    } else {
+                        "Chunk rendering: one draw per chunk"
+                    });
+                    ui.label(format!("Visible chunks: {}", stats.visible_chunks));
+                    ui.label(format!("Shadow casters per cascade: {:?}", stats.shadow_casters));
+                    ui.checkbox(&mut self.render_empty_chunks, "render empty chunks");
+                    ui.checkbox(&mut self.depth_prepass, "depth pre-pass")
+                        .on_hover_text("Compare the GPU time of the chunks pass in the timing section");
+                    ui.checkbox(&mut self.front_to_back, "front-to-back chunk order")
+                        .on_hover_text("Compare the GPU time of the chunks pass in the timing section");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.ambient_occlusion, "ambient occlusion")
+                            .on_hover_text("Toggling it remeshes all chunks");
+                        ui.add_enabled(
+                            self.ambient_occlusion,
+                            Slider::new(&mut self.ambient_occlusion_strength, 0.0..=1.0).text("strength"),
+                        );
+                    });
+                    ui.checkbox(&mut self.smooth_normals, "smooth shading")
+                        .on_hover_text("Toggling it remeshes all chunks");
+                    egui::ComboBox::from_label("Meshing")
+                        .selected_text(self.meshing_mode.name())
+                        .show_ui(ui, |ui| {
+                            for meshing_mode in MeshingMode::iter() {
+                                ui.selectable_value(&mut self.meshing_mode, meshing_mode, meshing_mode.name());
+                            }
+                        });
+                    ui.checkbox(&mut self.queue_boxes, "streaming queues")
+                        .on_hover_text(
+                            "Red: generating, orange: queued for generation, blue: queued for meshing, gray: queued for unloading",
+                        );
+                    egui::ComboBox::from_label("View")
+                        .selected_text(self.render_mode.name())
+                        .show_ui(ui, |ui| {
+                            for render_mode in RenderMode::iter() {
+                                ui.selectable_value(&mut self.render_mode, render_mode, render_mode.name());
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.chunk_outlines, "chunk outlines")
+                            .on_hover_text("Red: not generated, yellow: generated, green: meshed, blue: heightmap skin");
+                        ui.add_enabled(
+                            self.chunk_outlines,
+                            Slider::new(&mut self.chunk_outline_distance, 1..=8).text("distance"),
+                        );
+                    });
+                    ui.checkbox(&mut self.voxel_grid, "voxel grid");
+                    ui.checkbox(&mut self.clouds, "clouds")
+                        .on_hover_text("Disabling the clouds also removes their shadows");
+                    ui.add_enabled(self.clouds, Slider::new(&mut self.cloud_coverage, 0.0..=1.0).text("Cloud coverage"));
+                    ui.checkbox(&mut self.light_bounce, "light bounce")
+                        .on_hover_text("Darkens caves and lights overhangs with one bounce of the sky light, tinted by the nearby blocks");
+                    ui.add(
+                        Slider::new(&mut self.fog_density, 0.0..=0.01)
+                            .logarithmic(true)
+                            .text("Fog density"),
+                    )
+                    .on_hover_text("Far chunks fade into the fog, which hides the edge of the render distance");
+                    ui.horizontal(|ui| {
+                        let mut custom_color = self.fog_color.is_some();
+                        ui.checkbox(&mut custom_color, "custom fog color")
+                            .on_hover_text("Otherwise the fog has the color of the sky, or of the cave or water around the camera");
+                        let mut color = self
+                            .fog_color
+                            .unwrap_or(Environment::Surface.look().fog_color);
+                        ui.add_enabled_ui(custom_color, |ui| ui.color_edit_button_rgb(&mut color));
+                        self.fog_color = custom_color.then_some(color);
+                    });
+                    let minutes = (day_cycle.time_of_day * 24.0 * 60.0) as u32;
+                    ui.add(
+                        Slider::new(&mut day_cycle.time_of_day, 0.0..=1.0)
+                            .show_value(false)
+                            .text(format!("Time of day {:02}:{:02}", minutes / 60, minutes % 60)),
+                    );
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            Slider::new(&mut day_cycle.day_length, 10.0..=3600.0)
+                                .logarithmic(true)
+                                .text("Day length (s)"),
+                        );
+                        ui.checkbox(&mut day_cycle.paused, "paused");
+                    });
+                    ui.checkbox(&mut self.lens_effects, "lens effects")
+                        .on_hover_text("Sun glare, color grading and adaptation of the exposure when moving between caves and daylight");
+                    egui::ComboBox::from_label("Temporal effect")
+                        .selected_text(self.temporal_mode.name())
+                        .show_ui(ui, |ui| {
+                            for temporal_mode in TemporalMode::iter() {
+                                ui.selectable_value(&mut self.temporal_mode, temporal_mode, temporal_mode.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Camera motion blur or temporal anti-aliasing, which require a surface that can be copied from");
+                    egui::ComboBox::from_label("Lighting")
+                        .selected_text(self.lighting_mode.name())
+                        .show_ui(ui, |ui| {
+                            for lighting_mode in LightingMode::iter() {
+                                ui.selectable_value(&mut self.lighting_mode, lighting_mode, lighting_mode.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Ray traced ambient occlusion and sun shadows for high-end GPUs, which require compute shaders");
+                });
+
+                ui.collapsing("Blocks", |ui| {
+                    for &ty in &self.block_icons.voxel_types {
+                        let Some((min, max)) = self.block_icons.uv_rect(ty) else {
+                            continue;
+                        };
+
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::Image::new((self.block_icons_texture, vec2(32.0, 32.0)))
+                                    .uv(Rect::from_min_max(pos2(min[0], min[1]), pos2(max[0], max[1]))),
+                            );
+                            ui.label(format!("{ty:?}"));
+                        });
+                    }
+                });
+
+                ui.collapsing("Portals", |ui| {
+                    let mut removed = None;
+                    for (index, portal) in portals.portals.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{}: {:?} to {:?}", portal.name, portal.min, portal.target));
+                            if ui.button("remove").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                    }
+                    if let Some(index) = removed {
+                        portals.remove(index);
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Target:");
+                        for coordinate in &mut self.portal_target {
+                            ui.add(egui::DragValue::new(coordinate));
+                        }
+                    });
+                    egui::ComboBox::from_label("Target dimension")
+                        .selected_text(
+                            self.portal_target_dimension
+                                .as_deref()
+                                .unwrap_or("current"),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.portal_target_dimension, None, "current");
+                            for name in &stats.dimensions {
+                                ui.selectable_value(&mut self.portal_target_dimension, Some(name.clone()), name);
+                            }
+                        });
+                    if ui.button("Add portal here").clicked() {
+                        let position = Point3::from_vec(stats.position);
+                        let name = format!("Portal {}", portals.portals.len() + 1);
+                        portals.portals.push(Portal::around(
+                            name,
+                            position,
+                            1.5,
+                            active_dimension,
+                            self.portal_target.into(),
+                            self.portal_target_dimension.clone(),
+                        ));
+                        portals.arrive(position, active_dimension);
+                    }
+                });
+
+                ui.collapsing("Entities", |ui| {
+                    ui.label(format!("Entities: {}", stats.num_entities));
+                    if ui.button("throw held block").clicked() {
+                        self.entity_spawn_requested = true;
+                    }
+                });
+
+                ui.collapsing("Console", |ui| {
+                    let response = ui.text_edit_singleline(&mut self.console_command);
+                    if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                        self.console_output = match cvars::execute(&self.console_command) {
+                            Ok(output) => output,
+                            Err(err) => format!("{err:#}"),
+                        };
+                        self.console_command.clear();
+                        response.request_focus();
+                    }
+                    if !self.console_output.is_empty() {
+                        ui.label(self.console_output.as_str());
+                    }
+
+                    ui.separator();
+                    for (name, mut value, default) in cvars::list() {
+                        ui.horizontal(|ui| {
+                            let changed = ui
+                                .add(egui::DragValue::new(&mut value).speed(0.1))
+                                .changed();
+                            if ui
+                                .add_enabled(value != default, egui::Button::new("reset"))
+                                .clicked()
+                            {
+                                cvars::set(name, default).expect("Listed console variables to be registered");
+                            } else if changed {
+                                cvars::set(name, value).expect("Listed console variables to be registered");
+                            }
+                            ui.label(name);
+                        });
+                    }
+                });
+
+                ui.collapsing("Timing", |ui| {
+                    ui.add(Slider::new(&mut timer.history_len, 1..=600).text("Averaged frames"));
+                    flame_graph(ui, timer);
+                    for &root in timer.roots() {
+                        span_tree(ui, timer, root);
+                    }
+
+                    if !stats.gpu_timings.is_empty() {
+                        ui.separator();
+                        ui.label("GPU passes:");
+                        for timing in &stats.gpu_timings {
+                            ui.label(format!("{}: {:.2}ms", timing.name, timing.duration * 1000.0));
+                        }
+                    }
+
+                    ui.separator();
+                    if ui
+                        .add_enabled(stats.frame_capture_available, egui::Button::new("capture frame (F9)"))
+                        .on_disabled_hover_text("Build with the renderdoc feature and launch the game from RenderDoc")
+                        .clicked()
+                    {
+                        self.frame_capture_requested = true;
+                    }
+                });
+
+                ui.collapsing("Accessibility", |ui| {
+                    ui.checkbox(&mut self.accessibility.reduced_flashing, "reduced flashing")
+                        .on_hover_text("Hides the sun glare and slowly adapts the exposure when moving into bright surroundings");
+                    ui.checkbox(&mut self.accessibility.reduced_motion, "reduced motion")
+                        .on_hover_text("Does not swing the held block when placing or breaking blocks");
+                    egui::ComboBox::from_label("Block colors")
+                        .selected_text(self.accessibility.palette.name())
+                        .show_ui(ui, |ui| {
+                            for palette in ColorPalette::iter() {
+                                ui.selectable_value(&mut self.accessibility.palette, palette, palette.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Adjusts the block colors for a color vision deficiency");
+                });
+
+                ui.collapsing("Overlay", |ui| {
+                    egui::ComboBox::from_label("Color scheme")
+                        .selected_text(self.theme.color_scheme.name())
+                        .show_ui(ui, |ui| {
+                            for color_scheme in ColorScheme::iter() {
+                                ui.selectable_value(&mut self.theme.color_scheme, color_scheme, color_scheme.name());
+                            }
+                        });
+                    ui.add(Slider::new(&mut self.theme.background_opacity, 0.0..=1.0).text("Background opacity"));
+                });
             });
-        });
+
+        let mut inventory_open = self.inventory_open;
+        egui::Window::new("Inventory")
+            .open(&mut inventory_open)
+            .default_pos([8.0, 480.0])
+            .show(&self.context, |ui| self.inventory_ui(ui, &stats.inventory, stats.held_block));
+        self.inventory_open = inventory_open;
+
+        let mut terrain_preview_open = self.terrain_preview.open;
+        egui::Window::new("Terrain preview")
+            .open(&mut terrain_preview_open)
+            .default_pos([320.0, 8.0])
+            .show(&self.context, |ui| {
+                self.terrain_preview
+                    .ui(ui, stats.position, worldgen_preset)
+            });
+        self.terrain_preview.open = terrain_preview_open;
+
         self.output = Some(self.context.end_frame());
     }
 
+    fn photo_mode_ui(&mut self, ui: &mut Ui) {
+        ui.add(Slider::new(&mut self.photo.roll, -45.0..=45.0).text("roll (°)"));
+        ui.add(
+            Slider::new(&mut self.photo.focus_distance, 0.5..=256.0)
+                .logarithmic(true)
+                .text("focus distance"),
+        );
+        ui.add(Slider::new(&mut self.photo.aperture, 0.0..=16.0).text("depth of field blur"))
+            .on_hover_text("Blur radius in pixels of everything far away from the focus distance");
+        ui.add(Slider::new(&mut self.photo.exposure, 0.25..=2.0).text("exposure"));
+        ui.add(Slider::new(&mut self.photo.supersampling, PHOTO_SUPERSAMPLING).text("supersampling"))
+            .on_hover_text("Renders the photo at this multiple of the window size and scales it down to the window size");
+        ui.label("Lock the mouse to fly around and hide this window");
+
+        ui.horizontal(|ui| {
+            if ui.button("take photo (F12)").clicked() {
+                self.photo_requested = true;
+            }
+            if ui.button("exit (F2)").clicked() {
+                self.photo_mode = false;
+            }
+        });
+    }
+
+    fn inventory_ui(&self, ui: &mut Ui, inventory: &Inventory, held_block: VoxelType) {
+        if !self.game_mode.uses_inventory() {
+            ui.label(format!("{}: the inventory is not used", self.game_mode.name()));
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            for &ty in &self.block_icons.voxel_types {
+                let Some((min, max)) = self.block_icons.uv_rect(ty) else {
+                    continue;
+                };
+
+                ui.vertical(|ui| {
+                    let icon = ui.add(
+                        egui::Image::new((self.block_icons_texture, vec2(48.0, 48.0)))
+                            .uv(Rect::from_min_max(pos2(min[0], min[1]), pos2(max[0], max[1]))),
+                    );
+                    if ty == held_block {
+                        ui.painter()
+                            .rect_stroke(icon.rect, 2.0, ui.visuals().selection.stroke);
+                    }
+                    ui.label(format!("{}", inventory.count(ty)));
+                })
+                .response
+                .on_hover_text(format!("{ty:?}"));
+            }
+        });
+    }
+
     /// Must be called before rendering this overlay.
     /// This will tessellate the ui and upload all resources to the gpu
-    pub fn prepare_render(&mut self, command_encoder: &mut CommandEncoder) {
+    pub fn prepare_render(&mut self, render_ctx: &RenderCtx, command_encoder: &mut CommandEncoder) {
         let full_output = mem::take(&mut self.output).expect("Failed to get output of egui preparation result");
 
         let paint_jobs = self
@@ -157,12 +817,12 @@ impl DebugOverlay {
 
         for (t_id, tdelta) in tdelta.set {
             self.renderer
-                .update_texture(&self.render_ctx.device, &self.render_ctx.queue, t_id, &tdelta);
+                .update_texture(&render_ctx.device, &render_ctx.queue, t_id, &tdelta);
         }
 
         self.renderer.update_buffers(
-            &self.render_ctx.device,
-            &self.render_ctx.queue,
+            &render_ctx.device,
+            &render_ctx.queue,
             command_encoder,
             &paint_jobs,
             &self.screen_descriptor,
@@ -172,8 +832,141 @@ impl DebugOverlay {
     }
 }
 
+/// Heightmap image of the terrain of any seed, which is computed from the terrain height function without generating chunks
+struct TerrainPreview {
+    open: bool,
+    seed: u32,
+    /// Noise that replaces the terrain noise of the preset, if any
+    noise: Option<NoiseKind>,
+    /// Number of voxels between two pixels of the image
+    blocks_per_pixel: f64,
+    texture: Option<egui::TextureHandle>,
+}
+
+impl Default for TerrainPreview {
+    fn default() -> Self {
+        Self {
+            open: false,
+            seed: 123,
+            noise: None,
+            blocks_per_pixel: 4.0,
+            texture: None,
+        }
+    }
+}
+
+impl TerrainPreview {
+    fn ui(&mut self, ui: &mut Ui, position: Vector3<f32>, preset: &WorldgenPreset) {
+        ui.horizontal(|ui| {
+            ui.label("Seed:");
+            ui.add(egui::DragValue::new(&mut self.seed));
+        });
+        egui::ComboBox::from_label("Noise")
+            .selected_text(
+                self.noise
+                    .as_ref()
+                    .map_or("preset", NoiseKind::name),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.noise, None, "preset");
+                for noise in NoiseKind::examples() {
+                    let name = noise.name();
+                    ui.selectable_value(&mut self.noise, Some(noise), name);
+                }
+            });
+        ui.add(
+            Slider::new(&mut self.blocks_per_pixel, 0.25..=32.0)
+                .logarithmic(true)
+                .text("Blocks per pixel"),
+        );
+
+        if ui.button("Generate around player").clicked() || self.texture.is_none() {
+            let image = self.render(position, preset);
+            self.texture = Some(
+                ui.ctx()
+                    .load_texture("terrain preview", image, egui::TextureOptions::NEAREST),
+            );
+        }
+
+        if let Some(texture) = &self.texture {
+            ui.image((texture.id(), vec2(TERRAIN_PREVIEW_SIZE as f32, TERRAIN_PREVIEW_SIZE as f32)));
+        }
+    }
+
+    fn render(&self, position: Vector3<f32>, preset: &WorldgenPreset) -> egui::ColorImage {
+        let mut preset = preset.clone();
+        if let Some(noise) = &self.noise {
+            preset.terrain_noise = noise.clone();
+        }
+
+        let heights = terrain_heightmap(
+            self.seed,
+            &preset,
+            [position.x as f64, position.z as f64],
+            TERRAIN_PREVIEW_SIZE,
+            self.blocks_per_pixel,
+        );
+
+        let min = heights
+            .iter()
+            .copied()
+            .fold(f64::INFINITY, f64::min);
+        let max = heights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let range = (max - min).max(1.0);
+
+        let pixels = heights
+            .iter()
+            .map(|&height| {
+                if height < preset.sea_level {
+                    SEA_COLOR
+                } else {
+                    height_color(((height - min) / range) as f32)
+                }
+            })
+            .collect();
+
+        egui::ColorImage {
+            size: [TERRAIN_PREVIEW_SIZE, TERRAIN_PREVIEW_SIZE],
+            pixels,
+        }
+    }
+}
+
+const SEA_COLOR: Color32 = Color32::from_rgb(40, 80, 170);
+
+/// Color of a terrain height from 0.0 (lowest) to 1.0 (highest), from dark grass over light grass to stone
+fn height_color(height: f32) -> Color32 {
+    const LOW: [f32; 3] = [40.0, 90.0, 30.0];
+    const MID: [f32; 3] = [110.0, 170.0, 70.0];
+    const HIGH: [f32; 3] = [150.0, 150.0, 150.0];
+
+    let (from, to, t) = if height < 0.6 {
+        (LOW, MID, height / 0.6)
+    } else {
+        (MID, HIGH, (height - 0.6) / 0.4)
+    };
+    let [r, g, b] = [0, 1, 2].map(|i| (from[i] + (to[i] - from[i]) * t.clamp(0.0, 1.0)) as u8);
+
+    Color32::from_rgb(r, g, b)
+}
+
+fn create_renderer(render_ctx: &RenderCtx) -> egui_wgpu::Renderer {
+    egui_wgpu::Renderer::new(&render_ctx.device, render_ctx.surface_format, Some(Depth32Float), 1)
+}
+
+/// Renders the block icons and makes their atlas available to egui
+fn register_block_icons(render_ctx: &RenderCtx, renderer: &mut egui_wgpu::Renderer) -> (BlockIcons, egui::TextureId) {
+    let block_icons = BlockIcons::render(render_ctx);
+    let texture_id = renderer.register_native_texture(&render_ctx.device, &block_icons.atlas.view, wgpu::FilterMode::Linear);
+
+    (block_icons, texture_id)
+}
+
 impl Renderer for DebugOverlay {
-    fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, _camera_bind_group: &'a BindGroup, render_ctx: &RenderCtx) {
+    fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, _camera: UniformBinding<'a>, render_ctx: &RenderCtx) {
         let paint_jobs = self
             .paint_jobs
             .as_ref()
@@ -194,10 +987,204 @@ pub struct PerFrameStats {
     pub position: Vector3<f32>,
     pub total_voxel_data_size: usize,
     pub total_mesh_data_size: usize,
+    /// Size of the CPU-side copies of the chunk meshes in bytes
+    pub retained_mesh_data_size: usize,
+    /// Chunks meshed on the GPU that reuse the face instance buffer of another chunk
+    pub gpu_shared_chunks: usize,
+    /// Whether frames can be captured with RenderDoc
+    pub frame_capture_available: bool,
+    /// Whether power is saved in this frame, see [crate::power_saving::PowerSaving]
+    pub power_saving: bool,
     pub currently_rendered_chunk_radius: i32,
+    pub deep_generation_radius: i32,
     pub current_meshgen_queue_size: usize,
     pub current_chunkgen_queue_size: usize,
     pub current_chunkdata_buffer_size: usize,
+    pub gpu_culling: bool,
+    pub visible_chunks: u32,
+    pub shadow_casters: [usize; SHADOW_CASCADE_COUNT],
+    pub inventory: Inventory,
+    pub held_block: VoxelType,
+    pub dimensions: Vec<String>,
+    pub active_dimension: DimensionId,
+    pub gpu_timings: Vec<GpuPassTiming>,
+    pub environment: EnvironmentState,
+    /// Shaders whose last reload failed, with their errors, see [crate::rendering::shader_manager::ShaderManager]
+    pub shader_errors: Vec<(PathBuf, String)>,
+    /// Chunks of the active dimension whose generation or meshing failed, sorted by their location
+    pub failed_chunks: Vec<(ChunkLocation, ChunkFailure)>,
+    /// Whether a worldgen preset was replaced by a reload, see [DebugOverlay::worldgen_preset_swap_requested]
+    pub has_previous_worldgen_preset: bool,
+    /// Number of entities in the active dimension
+    pub num_entities: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum ColorScheme {
+    Dark,
+    Light,
+    HighContrast,
+}
+
+impl ColorScheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorScheme::Dark => "Dark",
+            ColorScheme::Light => "Light",
+            ColorScheme::HighContrast => "High contrast",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayTheme {
+    pub color_scheme: ColorScheme,
+    /// Opacity of the overlay background from 0.0 (fully transparent) to 1.0 (opaque)
+    pub background_opacity: f32,
+}
+
+impl Default for OverlayTheme {
+    fn default() -> Self {
+        Self {
+            color_scheme: ColorScheme::Dark,
+            background_opacity: 0.7,
+        }
+    }
+}
+
+impl OverlayTheme {
+    pub fn visuals(&self) -> Visuals {
+        let mut visuals = match self.color_scheme {
+            ColorScheme::Dark => Visuals::dark(),
+            ColorScheme::Light => Visuals::light(),
+            ColorScheme::HighContrast => Visuals {
+                window_fill: Color32::BLACK,
+                window_stroke: Stroke::new(1.0, Color32::WHITE),
+                override_text_color: Some(Color32::WHITE),
+                ..Visuals::dark()
+            },
+        };
+
+        let alpha = (self.background_opacity.clamp(0.0, 1.0) * 255.0) as u8;
+        let fill = visuals.window_fill;
+        visuals.window_fill = Color32::from_rgba_unmultiplied(fill.r(), fill.g(), fill.b(), alpha);
+        visuals.panel_fill = Color32::TRANSPARENT;
+
+        visuals
+    }
+}
+
+const FLAME_ROW_HEIGHT: f32 = 16.0;
+
+/// Shows a span and its children as a collapsible tree with their averaged timings
+fn span_tree(ui: &mut Ui, timer: &TimerManager, id: usize) {
+    let span = timer.node(id);
+    if !span.is_active() {
+        return;
+    }
+
+    let text = format!("{}: {:.2}ms (max {:.2}ms)", span.name, span.average() * 1000.0, span.max() * 1000.0);
+
+    if span
+        .children
+        .iter()
+        .any(|&child| timer.node(child).is_active())
+    {
+        CollapsingHeader::new(text)
+            .id_source(id)
+            .default_open(true)
+            .show(ui, |ui| {
+                for &child in &span.children {
+                    span_tree(ui, timer, child);
+                }
+            });
+    } else {
+        ui.label(text);
+    }
+}
+
+/// Draws all spans as a flame graph. Each row is one level of nesting, the horizontal axis is the averaged time within a frame.
+fn flame_graph(ui: &mut Ui, timer: &TimerManager) {
+    let frame_extent = timer
+        .roots()
+        .iter()
+        .map(|&id| timer.node(id))
+        .filter(|span| span.is_active())
+        .map(|span| span.average_offset() + span.average())
+        .fold(0.0, f32::max);
+    if frame_extent <= 0.0 {
+        return;
+    }
+
+    let depth = timer
+        .roots()
+        .iter()
+        .map(|&id| span_depth(timer, id))
+        .max()
+        .unwrap_or(0);
+
+    let width = ui.available_width();
+    let (response, painter) = ui.allocate_painter(vec2(width, depth as f32 * FLAME_ROW_HEIGHT), Sense::hover());
+    let scale = width / frame_extent;
+
+    for &root in timer.roots() {
+        flame_span(ui, &painter, timer, root, response.rect.left(), response.rect.top(), scale);
+    }
+}
+
+fn flame_span(ui: &Ui, painter: &Painter, timer: &TimerManager, id: usize, parent_x: f32, y: f32, scale: f32) {
+    let span = timer.node(id);
+    if !span.is_active() {
+        return;
+    }
+
+    let x = parent_x + span.average_offset() * scale;
+    let rect = Rect::from_min_size(pos2(x, y), vec2((span.average() * scale).max(1.0), FLAME_ROW_HEIGHT - 1.0));
+
+    painter.rect_filled(rect, 0.0, span_color(&span.name));
+    painter
+        .with_clip_rect(rect.intersect(painter.clip_rect()))
+        .text(
+            rect.left_center() + vec2(2.0, 0.0),
+            Align2::LEFT_CENTER,
+            &span.name,
+            FontId::monospace(10.0),
+            Color32::BLACK,
+        );
+    ui.interact(rect, ui.id().with(("flame_span", id)), Sense::hover())
+        .on_hover_text(format!("{}: {:.2}ms", span.name, span.average() * 1000.0));
+
+    for &child in &span.children {
+        flame_span(ui, painter, timer, child, x, y + FLAME_ROW_HEIGHT, scale);
+    }
+}
+
+fn span_depth(timer: &TimerManager, id: usize) -> usize {
+    let span = timer.node(id);
+    if !span.is_active() {
+        return 0;
+    }
+
+    1 + span
+        .children
+        .iter()
+        .map(|&child| span_depth(timer, child))
+        .max()
+        .unwrap_or(0)
+}
+
+/// A stable pastel color for every span name
+fn span_color(name: &str) -> Color32 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    Color32::from_rgb(
+        128 + (hash & 0x7F) as u8,
+        128 + ((hash >> 8) & 0x7F) as u8,
+        128 + ((hash >> 16) & 0x7F) as u8,
+    )
 }
 
 trait CollapsingOpened {
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use enum_map::{enum_map, EnumMap};
+
+use crate::world::voxel_data::VoxelType;
+
+/// Time constant with which the look and the ambiance fade from one environment to the next
+const TRANSITION_TIME: Duration = Duration::from_millis(800);
+/// Fraction of the sky rays below which the eye is considered to be underground
+const UNDERGROUND_SKY_VISIBILITY: f32 = 0.2;
+
+/// Surroundings of the camera, which decide the fog, the color grading and the ambiance
+#[derive(Copy, Clone, Debug, PartialEq, Eq, enum_map::Enum)]
+pub enum Environment {
+    Surface,
+    Underground,
+    Underwater,
+}
+
+impl Environment {
+    /// Detects the environment from the voxel that contains the eye and the fraction of the sky that is visible from it
+    pub fn detect(eye_voxel: Option<VoxelType>, sky_visibility: f32) -> Self {
+        if eye_voxel == Some(VoxelType::Water) {
+            Environment::Underwater
+        } else if sky_visibility < UNDERGROUND_SKY_VISIBILITY {
+            Environment::Underground
+        } else {
+            Environment::Surface
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Environment::Surface => "Surface",
+            Environment::Underground => "Underground",
+            Environment::Underwater => "Underwater",
+        }
+    }
+
+    /// Name of the ambiance loop that is played in this environment, see [crate::ambiance::AmbianceSynth]
+    pub fn ambiance(self) -> &'static str {
+        match self {
+            Environment::Surface => "wind",
+            Environment::Underground => "cave drips",
+            Environment::Underwater => "muffled water",
+        }
+    }
+
+    pub fn look(self) -> Look {
+        match self {
+            Environment::Surface => Look {
+                fog_color: [0.4941, 0.6627, 1.0],
+                fog_distance: 32.0 * 32.0,
+                tint: [1.0, 1.0, 1.0],
+            },
+            Environment::Underground => Look {
+                fog_color: [0.04, 0.04, 0.05],
+                fog_distance: 160.0,
+                tint: [1.0, 0.95, 0.88],
+            },
+            Environment::Underwater => Look {
+                fog_color: [0.02, 0.12, 0.25],
+                fog_distance: 40.0,
+                tint: [0.55, 0.8, 1.0],
+            },
+        }
+    }
+}
+
+/// Fog and color grading of an environment
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Look {
+    pub fog_color: [f32; 3],
+    /// Distance at which the fog completely hides the terrain
+    pub fog_distance: f32,
+    /// Factor by which the final image is multiplied
+    pub tint: [f32; 3],
+}
+
+impl Look {
+    /// Fog parameters of the camera uniform: color and distance
+    pub fn fog(&self) -> [f32; 4] {
+        let [r, g, b] = self.fog_color;
+        [r, g, b, self.fog_distance]
+    }
+
+    /// Distance fog parameters of the camera uniform: color and density.
+    /// Without a color, far chunks fade into the fog color of the environment, which is the color of the sky on the surface.
+    pub fn distance_fog(&self, density: f32, color: Option<[f32; 3]>) -> [f32; 4] {
+        let [r, g, b] = color.unwrap_or(self.fog_color);
+        [r, g, b, density]
+    }
+}
+
+/// The detected environment together with the progress of the transitions between environments
+#[derive(Clone, Debug)]
+pub struct EnvironmentState {
+    pub current: Environment,
+    /// Blend weight of every environment, which always sum up to 1.0. They fade towards the current environment.
+    /// Also used as the volumes of the ambiance loops, see [crate::ambiance::AmbiancePlayer].
+    pub weights: EnumMap<Environment, f32>,
+}
+
+impl EnvironmentState {
+    pub fn new() -> Self {
+        Self {
+            current: Environment::Surface,
+            weights: enum_map! {
+                Environment::Surface => 1.0,
+                _ => 0.0,
+            },
+        }
+    }
+
+    pub fn update(&mut self, detected: Environment, dt: Duration) {
+        self.current = detected;
+
+        let t = 1.0 - (-dt.as_secs_f32() / TRANSITION_TIME.as_secs_f32()).exp();
+        for (environment, weight) in &mut self.weights {
+            let target = if environment == detected { 1.0 } else { 0.0 };
+            *weight += (target - *weight) * t;
+        }
+    }
+
+    /// Looks of all environments blended by their weights
+    pub fn look(&self) -> Look {
+        let mut look = Look {
+            fog_color: [0.0; 3],
+            fog_distance: 0.0,
+            tint: [0.0; 3],
+        };
+
+        for (environment, &weight) in &self.weights {
+            let environment_look = environment.look();
+            for i in 0..3 {
+                look.fog_color[i] += weight * environment_look.fog_color[i];
+                look.tint[i] += weight * environment_look.tint[i];
+            }
+            look.fog_distance += weight * environment_look.fog_distance;
+        }
+
+        look
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::environment::{Environment, EnvironmentState};
+    use crate::world::voxel_data::VoxelType;
+
+    #[test]
+    fn test_weights_fade_to_detected_environment() {
+        let mut state = EnvironmentState::new();
+
+        for _ in 0..600 {
+            state.update(Environment::detect(Some(VoxelType::Water), 1.0), Duration::from_millis(16));
+
+            let total: f32 = state.weights.values().sum();
+            assert!((total - 1.0).abs() < 1e-4, "weights sum up to {total}");
+        }
+
+        assert_eq!(state.current, Environment::Underwater);
+        assert!(state.weights[Environment::Underwater] > 0.99);
+        assert_eq!(
+            state.look().fog_distance.round(),
+            Environment::Underwater
+                .look()
+                .fog_distance
+                .round()
+        );
+    }
+}
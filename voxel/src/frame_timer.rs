@@ -19,4 +19,9 @@ impl FrameTimer {
 
         dt
     }
+
+    /// Time at which the next frame starts if frames are limited to the given frame rate
+    pub fn next_frame(&self, max_fps: f32) -> Instant {
+        self.last_frame + Duration::from_secs_f32(1.0 / max_fps)
+    }
 }
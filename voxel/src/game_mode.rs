@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+/// Rules for how the player moves and interacts with the world
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Flying can be toggled, blocks can be placed without limit
+    Creative,
+    /// Walking with collision only, placed blocks have to be collected by breaking them first
+    Survival,
+    /// Always flying through the terrain, blocks cannot be broken or placed
+    Spectator,
+}
+
+impl GameMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Creative => "Creative",
+            GameMode::Survival => "Survival",
+            GameMode::Spectator => "Spectator",
+        }
+    }
+
+    /// Whether the player flies through the terrain without collision, given whether it was requested by the player
+    pub fn no_clip(self, requested: bool) -> bool {
+        match self {
+            GameMode::Creative => requested,
+            GameMode::Survival => false,
+            GameMode::Spectator => true,
+        }
+    }
+
+    /// Whether the player can toggle flying
+    pub fn allows_flight_toggle(self) -> bool {
+        self == GameMode::Creative
+    }
+
+    /// Whether blocks can be broken and placed
+    pub fn can_interact(self) -> bool {
+        self != GameMode::Spectator
+    }
+
+    /// Whether placed blocks are taken from the inventory instead of being unlimited
+    pub fn uses_inventory(self) -> bool {
+        self == GameMode::Survival
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::Result;
+use cgmath::Vector3;
+use itertools::iproduct;
+use log::info;
+use rayon::prelude::*;
+
+use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::meshing::NeighborChunks;
+use crate::world::chunk_renderer::{ChunkMeshData, MeshKind, MeshingMode};
+use crate::world::location::ChunkLocation;
+use crate::world::storage::WorldStorage;
+use crate::world::world_config::{WorldConfig, WORLD_CONFIG_FILE_NAME};
+use crate::world::worldgen::WorldGenerator;
+use crate::world::worldgen_preset::WorldgenPreset;
+
+/// Settings of a headless run, which generates and meshes chunks without a window or GPU
+#[derive(Clone, Debug)]
+pub struct HeadlessConfig {
+    /// Horizontal radius in chunks around the origin that is generated and meshed
+    pub radius: i32,
+    pub worldgen_preset_path: PathBuf,
+    /// Directory in which all generated chunks are saved, if any
+    pub world_path: Option<PathBuf>,
+    /// Config of the generated world, unless the world that is saved to already has one
+    pub world_config: WorldConfig,
+}
+
+/// Generates and meshes the chunks around the origin of the overworld on all cores and logs the throughput
+pub fn run_headless(config: HeadlessConfig) -> Result<()> {
+    let preset = WorldgenPreset::load(&config.worldgen_preset_path);
+    let world_config = match &config.world_path {
+        Some(world_path) => WorldConfig::load_or_create(&world_path.join(WORLD_CONFIG_FILE_NAME), config.world_config),
+        None => config.world_config,
+    };
+    let generator = WorldGenerator::new(world_config, preset.clone());
+
+    // Chunks are generated one chunk further out than they are meshed, so that every meshed chunk has all of its neighbors
+    let generated_locations = chunk_locations(config.radius + 1, preset.min_chunk_y - 1, preset.max_chunk_y + 1);
+    let start = Instant::now();
+    let chunks: HashMap<ChunkLocation, ChunkData> = generated_locations
+        .into_par_iter()
+        .map(|location| (location, generator.get_chunk_data_at(location)))
+        .collect();
+    let generation_time = start.elapsed().as_secs_f32();
+    info!(
+        "Generated {} chunks in {generation_time:.2}s ({:.1} chunks per second)",
+        chunks.len(),
+        chunks.len() as f32 / generation_time.max(f32::EPSILON)
+    );
+
+    let meshed_locations = chunk_locations(config.radius, preset.min_chunk_y, preset.max_chunk_y);
+    let start = Instant::now();
+    let (meshed_chunks, indices) = meshed_locations
+        .par_iter()
+        .filter(|location| !chunks[location].is_empty())
+        .map(|location| {
+            let neighbor_chunks = NeighborChunks::new(location, |loc| chunks.get(loc))?;
+            let mesh = ChunkMeshData::generate(
+                &chunks[location],
+                neighbor_chunks,
+                MeshKind::Voxels,
+                MeshingMode::default(),
+                true,
+                false,
+            );
+
+            Ok((1, mesh.levels[0].indices.len()))
+        })
+        .try_reduce(|| (0, 0), |a, b| Ok((a.0 + b.0, a.1 + b.1)))?;
+    let meshing_time = start.elapsed().as_secs_f32();
+    info!(
+        "Meshed {meshed_chunks} non-empty chunks with {} triangles in {meshing_time:.2}s ({:.1} chunks per second)",
+        indices / 3,
+        meshed_chunks as f32 / meshing_time.max(f32::EPSILON)
+    );
+
+    if let Some(world_path) = config.world_path {
+        let mut storage = WorldStorage::open(world_path.join("overworld"))?;
+        for &location in chunks.keys() {
+            storage.mark_modified(location);
+        }
+        let saved = storage.save(|location| chunks.get(&location))?;
+        info!("Saved {saved} chunks to {world_path:?}");
+    }
+
+    Ok(())
+}
+
+/// All chunk locations within the horizontal radius around the origin and the vertical range
+fn chunk_locations(radius: i32, min_y: i32, max_y: i32) -> Vec<ChunkLocation> {
+    iproduct!(-radius..=radius, min_y..=max_y, -radius..=radius)
+        .map(|(x, y, z)| ChunkLocation::new(Vector3::new(x, y, z)))
+        .collect()
+}
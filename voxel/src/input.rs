@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+use crate::ron_file;
+
+/// Something that the user can do with a key or a mouse button
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, EnumIter, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    /// Jumps while walking and flies up in no-clip mode
+    Jump,
+    /// Flies down in no-clip mode
+    Descend,
+    BreakBlock,
+    PlaceBlock,
+    /// Selects the block of the given slot of the block types as the held block
+    SelectBlock1,
+    SelectBlock2,
+    SelectBlock3,
+    SelectBlock4,
+    SelectBlock5,
+    SelectBlock6,
+    SelectBlock7,
+    SelectBlock8,
+    SelectBlock9,
+    ToggleInventory,
+    ToggleMouseLock,
+    TogglePause,
+    /// Pauses the simulation and detaches the camera from the player, or ends the photo mode
+    TogglePhotoMode,
+    TakePhoto,
+    /// Simulates a single tick while paused
+    StepFrame,
+    CaptureFrame,
+    Quit,
+}
+
+impl Action {
+    /// Index of the block type that is selected by this action, if it selects a block
+    pub fn block_slot(self) -> Option<usize> {
+        use Action::*;
+        [
+            SelectBlock1,
+            SelectBlock2,
+            SelectBlock3,
+            SelectBlock4,
+            SelectBlock5,
+            SelectBlock6,
+            SelectBlock7,
+            SelectBlock8,
+            SelectBlock9,
+        ]
+        .iter()
+        .position(|&action| action == self)
+    }
+
+    fn default_inputs(self) -> Vec<Input> {
+        use Action::*;
+        use VirtualKeyCode as Key;
+        let input = match self {
+            MoveForward => Input::Key(Key::W),
+            MoveBackward => Input::Key(Key::S),
+            MoveLeft => Input::Key(Key::A),
+            MoveRight => Input::Key(Key::D),
+            Jump => Input::Key(Key::Space),
+            Descend => Input::Key(Key::LShift),
+            BreakBlock => Input::Mouse(MouseButton::Left),
+            PlaceBlock => Input::Mouse(MouseButton::Right),
+            SelectBlock1 => Input::Key(Key::Key1),
+            SelectBlock2 => Input::Key(Key::Key2),
+            SelectBlock3 => Input::Key(Key::Key3),
+            SelectBlock4 => Input::Key(Key::Key4),
+            SelectBlock5 => Input::Key(Key::Key5),
+            SelectBlock6 => Input::Key(Key::Key6),
+            SelectBlock7 => Input::Key(Key::Key7),
+            SelectBlock8 => Input::Key(Key::Key8),
+            SelectBlock9 => Input::Key(Key::Key9),
+            ToggleInventory => Input::Key(Key::E),
+            ToggleMouseLock => Input::Key(Key::LAlt),
+            TogglePause => Input::Key(Key::P),
+            TogglePhotoMode => Input::Key(Key::F2),
+            TakePhoto => Input::Key(Key::F12),
+            StepFrame => Input::Key(Key::Period),
+            CaptureFrame => Input::Key(Key::F9),
+            Quit => Input::Key(Key::Escape),
+        };
+
+        vec![input]
+    }
+}
+
+/// A key or a mouse button, which is written by its name in the bindings file, e.g. `"W"` or `"MouseLeft"`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+/// Keys that can be bound, as winit's key codes do not implement serde without its serde feature
+const BINDABLE_KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8,
+        Key9, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Escape, Space, Return, Tab, Back, Insert, Delete, Home, End, PageUp,
+        PageDown, Left, Right, Up, Down, LShift, RShift, LControl, RControl, LAlt, RAlt, Period, Comma, Minus, Equals, Slash, Backslash,
+        Semicolon, Apostrophe, LBracket, RBracket, Grave, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8,
+        Numpad9,
+    ]
+};
+
+impl Input {
+    /// The pressed or released key or mouse button of a window event, if any
+    pub fn from_event(event: &Event<()>) -> Option<(Self, ElementState)> {
+        let Event::WindowEvent { event, .. } = event else {
+            return None;
+        };
+
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => Some((Input::Key(*key), *state)),
+            WindowEvent::MouseInput { button, state, .. } => Some((Input::Mouse(*button), *state)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Key(key) => write!(f, "{key:?}"),
+            Input::Mouse(MouseButton::Left) => write!(f, "MouseLeft"),
+            Input::Mouse(MouseButton::Right) => write!(f, "MouseRight"),
+            Input::Mouse(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Input::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{button}"),
+        }
+    }
+}
+
+impl TryFrom<String> for Input {
+    type Error = anyhow::Error;
+
+    fn try_from(name: String) -> Result<Self> {
+        let mouse_button = match name.strip_prefix("Mouse") {
+            Some("Left") => Some(MouseButton::Left),
+            Some("Right") => Some(MouseButton::Right),
+            Some("Middle") => Some(MouseButton::Middle),
+            Some(button) => button.parse().ok().map(MouseButton::Other),
+            None => None,
+        };
+        if let Some(button) = mouse_button {
+            return Ok(Input::Mouse(button));
+        }
+
+        BINDABLE_KEYS
+            .iter()
+            .find(|key| format!("{key:?}") == name)
+            .map(|&key| Input::Key(key))
+            .ok_or_else(|| anyhow!("{name:?} is not a key or mouse button that can be bound"))
+    }
+}
+
+impl From<Input> for String {
+    fn from(input: Input) -> Self {
+        input.to_string()
+    }
+}
+
+/// The keys and mouse buttons that trigger every action.
+/// An input can trigger multiple actions and an action can be triggered by multiple inputs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bindings {
+    pub bindings: BTreeMap<Action, Vec<Input>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            bindings: Action::iter()
+                .map(|action| (action, action.default_inputs()))
+                .collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Loads the bindings from the given file. Actions that are missing from the file keep their default bindings.
+    /// If the file does not exist or is invalid, the default bindings are used instead.
+    pub fn load(path: &Path) -> Self {
+        let mut bindings = Self::default();
+        let loaded: Self = ron_file::load_or_default(path, "key bindings");
+        bindings.bindings.extend(loaded.bindings);
+
+        bindings
+    }
+
+    /// Actions that are triggered by the given input
+    pub fn actions(&self, input: Input) -> impl Iterator<Item = Action> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, inputs)| inputs.contains(&input))
+            .map(|(&action, _)| action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{MouseButton, VirtualKeyCode};
+
+    use crate::input::{Action, Bindings, Input};
+
+    #[test]
+    fn test_bindings_are_written_and_read_by_input_names() {
+        let mut bindings = Bindings::default();
+        bindings.bindings.insert(
+            Action::Jump,
+            vec![Input::Key(VirtualKeyCode::Space), Input::Mouse(MouseButton::Other(4))],
+        );
+
+        let content = ron::to_string(&bindings).unwrap();
+        assert!(content.contains("Jump:[\"Space\",\"Mouse4\"]"));
+        assert_eq!(ron::from_str::<Bindings>(&content).unwrap(), bindings);
+
+        assert!(ron::from_str::<Bindings>("{Jump: [\"NotAKey\"]}").is_err());
+        assert_eq!(
+            bindings
+                .actions(Input::Mouse(MouseButton::Left))
+                .collect::<Vec<_>>(),
+            [Action::BreakBlock]
+        );
+    }
+}
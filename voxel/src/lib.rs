@@ -1,78 +1,317 @@
+use std::collections::HashMap;
+use std::mem;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use cgmath::{Deg, EuclideanSpace};
+use anyhow::{anyhow, bail, Result};
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Point3, Rad, Vector2, Vector3, Zero};
+use log::{info, warn};
+use strum::IntoEnumIterator;
 use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::event::{DeviceEvent, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event::{DeviceEvent, ElementState, Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::{Fullscreen, Window, WindowBuilder};
 
+pub use benchmark::{BenchmarkConfig, BenchmarkThresholds};
+pub use build_info::BUILD_INFO;
+pub use headless::{run_headless, HeadlessConfig};
+pub use network::fuzz::fuzz_packets;
+pub use network::recording::serve_recording;
+pub use network::server::{run_server, ServerConfig};
+pub use soak_test::SoakConfig;
 pub use starter::start;
 
+use crate::accessibility::ColorPalette;
+use crate::ambiance::AmbiancePlayer;
+use crate::benchmark::Benchmark;
+use crate::console::{Command, CommandBus};
+use crate::cvars;
 use crate::debug_overlay::{DebugOverlay, PerFrameStats};
+use crate::environment::{Environment, EnvironmentState};
 use crate::frame_timer::FrameTimer;
-use crate::rendering::camera::{Camera, CameraController};
-use crate::rendering::RenderCtx;
+use crate::input::{Action, Bindings, Input};
+use crate::network::client::ServerConnection;
+use crate::network::protocol::{ClientMessage, ServerMessage};
+use crate::photo_mode::PhotoSession;
+use crate::power_saving::{PowerSaving, POWER_SAVING_MAX_FPS, POWER_SAVING_MAX_RENDER_DISTANCE};
+use crate::rendering::camera::{Camera, CameraController, PLAYER_HALF_WIDTH, PLAYER_HEIGHT};
+use crate::rendering::clouds::CloudRenderer;
+use crate::rendering::day_cycle::DayCycle;
+use crate::rendering::debug_boxes::DebugBoxes;
+use crate::rendering::debug_lines::DebugLines;
+use crate::rendering::depth_of_field::DepthOfField;
+use crate::rendering::frame_capture::FrameCapture;
+use crate::rendering::layers::{RenderLayer, RenderLayers};
+use crate::rendering::lens::LensEffects;
+use crate::rendering::light_bounce::LightBounce;
+use crate::rendering::photo::{next_photo_path, PhotoCapture};
+use crate::rendering::readback::TextureReadback;
+use crate::rendering::shader_manager::{ShaderManager, CHUNK_SHADER_PATH};
+use crate::rendering::shadow_cascades::sun_direction;
+use crate::rendering::temporal::{TemporalEffects, TemporalMode};
+use crate::rendering::thumbnail::{save_thumbnail, THUMBNAIL_FILE_NAME};
+use crate::rendering::{RenderCtx, RenderHandle};
+use crate::settings::{GraphicsSettings, Settings};
+use crate::setup_wizard::SetupWizard;
+use crate::soak_test::SoakBot;
 use crate::timing::TimerManager;
-use crate::world::chunk_manager::ChunkManager;
+use crate::world::block_breaking::BlockBreaker;
+use crate::world::chunk_manager::{ChunkManager, DimensionId};
+use crate::world::chunk_renderer::block_icons::solid_voxel_types;
+use crate::world::chunk_renderer::entity_renderer::EntityRenderer;
+use crate::world::chunk_renderer::selection::SelectionRenderer;
+use crate::world::chunk_renderer::viewmodel::Viewmodel;
+use crate::world::entities::{Entities, Entity, EntityMesh};
+use crate::world::inventory::Inventory;
+use crate::world::location::{ChunkLocation, WorldLocation};
+use crate::world::players::{new_player_id, PlayerData, Players, PLAYERS_FILE_NAME};
+use crate::world::portals::Portals;
+use crate::world::voxel_data::{VoxelData, VoxelType};
+use crate::world::world_config::{WorldConfig, WORLD_CONFIG_FILE_NAME};
+use crate::world::worldgen::{ChunkGenerator, WorldGenerator};
+use crate::world::worldgen_preset::WorldgenPreset;
 
 #[macro_use]
 mod macros;
+mod accessibility;
+mod ambiance;
+mod benchmark;
+mod build_info;
+mod console;
+mod cvars;
 mod debug_overlay;
+mod environment;
 mod frame_timer;
+mod game_mode;
+mod headless;
+mod input;
+mod network;
+mod photo_mode;
+mod power_saving;
+pub mod prelude;
 mod rendering;
+mod ron_file;
+mod settings;
+mod setup_wizard;
+mod soak_test;
 mod starter;
 mod timing;
 pub(crate) mod util;
 pub mod vector_utils;
 pub mod world;
 
+/// Duration of a single fixed simulation tick
+const TICK_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Upper limit of simulation ticks per frame. Any remaining simulation time is dropped, so that slow frames do not accumulate even more ticks.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+/// Frames that take longer than this leave no headroom for generating chunks beyond the render distance
+const IDLE_FRAME_TIME: Duration = Duration::from_millis(20);
+/// Maximum distance from the eye at which voxels can be targeted
+const REACH: f32 = 8.0;
+/// Number of voxels from the camera to the edge of the debug voxel grid
+const VOXEL_GRID_RADIUS: i32 = 8;
+/// Speed in blocks per second with which the held block is thrown from the debug overlay
+const THROW_SPEED: f32 = 12.0;
+const THROWN_BLOCK_LIFETIME: Duration = Duration::from_secs(60);
+
 pub struct EngineConfig {
-    pub run_benchmark: bool,
+    /// Fly along a scripted path, write a report and exit after the benchmark
+    pub benchmark: Option<BenchmarkConfig>,
     pub vsync: bool,
     pub window_size: (u32, u32),
     pub fullscreen: bool,
+    /// Show whatever is behind the window where no terrain is rendered. Requires support by the platform's compositor.
+    pub transparent: bool,
+    pub settings_path: PathBuf,
+    /// Path of the file with the key bindings, which are the defaults if it does not exist
+    pub bindings_path: PathBuf,
+    pub portals_path: PathBuf,
+    /// Path of the worldgen preset file of all dimensions
+    pub worldgen_preset_path: PathBuf,
+    /// Directory in which the modified chunks of all dimensions are saved
+    pub world_path: PathBuf,
+    /// Seed and generator of the world, if it is created now. Saved worlds are always generated with their saved config.
+    pub world_config: WorldConfig,
+    /// Generates the chunks of the overworld instead of the generator of the world config, which still generates the nether
+    pub generator: Option<Arc<dyn ChunkGenerator>>,
+    /// Image with the textures of the voxel faces, which are untextured if it does not exist
+    pub texture_atlas_path: PathBuf,
+    /// Let a scripted bot play instead of the user and exit after the soak test
+    pub soak: Option<SoakConfig>,
+    /// Read admin commands from the standard input, see [Engine::execute_command]
+    pub console: bool,
+    /// Address on which admin commands are accepted line by line, e.g. `127.0.0.1:4791`
+    pub admin_address: Option<String>,
+    /// Directory into which the photos of the photo mode are saved
+    pub photos_path: PathBuf,
+    /// Play on the server with this address instead of the local world. The world config must match the one of the server.
+    pub server_address: Option<String>,
+    /// File into which the frames of the connection to the server are recorded, see [serve_recording]
+    pub packet_recording_path: Option<PathBuf>,
+}
+
+/// A windowed engine that plays the local world in the working directory, with the same files as the game binary uses by default
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            benchmark: None,
+            vsync: false,
+            window_size: (800, 600),
+            fullscreen: false,
+            transparent: false,
+            settings_path: PathBuf::from("settings.ron"),
+            bindings_path: PathBuf::from("bindings.ron"),
+            portals_path: PathBuf::from("portals.ron"),
+            worldgen_preset_path: PathBuf::from("worldgen.ron"),
+            world_path: PathBuf::from("world"),
+            world_config: WorldConfig::default(),
+            generator: None,
+            texture_atlas_path: PathBuf::from("atlas.png"),
+            soak: None,
+            console: false,
+            admin_address: None,
+            photos_path: PathBuf::from("photos"),
+            server_address: None,
+            packet_recording_path: None,
+        }
+    }
 }
 
 pub struct Engine {
     window: Window,
     frame_timer: FrameTimer,
-    render_ctx: Arc<RenderCtx>,
+    /// Scaled simulation time that has not been simulated yet
+    simulation_time: Duration,
+    render_ctx: RenderCtx,
+    vsync: bool,
+    transparent: bool,
 
     chunk_manager: ChunkManager,
+    /// Entities of the active dimension, which are simulated beside the chunks
+    entities: Entities,
+    entity_renderer: EntityRenderer,
 
     camera: Camera,
     camera_controller: CameraController,
+    viewmodel: Viewmodel,
+    block_breaker: BlockBreaker,
+    selection_renderer: SelectionRenderer,
+    debug_lines: DebugLines,
+    debug_boxes: DebugBoxes,
+    clouds: CloudRenderer,
+    lens: LensEffects,
+    temporal: TemporalEffects,
+    depth_of_field: DepthOfField,
+    light_bounce: LightBounce,
+    day_cycle: DayCycle,
+    /// Surroundings of the eye, which decide the fog, the color grading and the ambiance
+    environment: EnvironmentState,
+    ambiance: AmbiancePlayer,
+    /// Whether the break button is held
+    breaking: bool,
+    /// Set to place the held block at the targeted face in the next tick
+    place_requested: bool,
+    inventory: Inventory,
+    /// Set to copy the next rendered frame into [Engine::thumbnail_capture]
+    thumbnail_requested: bool,
+    thumbnail_capture: Option<TextureReadback>,
+    /// The player while the photo mode is active, which is restored when it ends
+    photo_session: Option<PhotoSession>,
+    /// Photo whose tiles are rendered in the current and the following frames
+    photo_capture: Option<PhotoCapture>,
+    photos_path: PathBuf,
+    frame_capture: FrameCapture,
+    power_saving: PowerSaving,
+    /// Moves the camera and edits the world instead of the user during a soak test
+    soak_bot: Option<SoakBot>,
+    /// Moves the camera and records frame times instead of the user during a benchmark
+    benchmark: Option<Benchmark>,
+    /// Admin commands of the console and the admin port, if any of them is enabled
+    console: Option<CommandBus>,
     mouse_locked: bool,
+    /// Latest window size that has not been applied to the surface yet. Resizes are applied once at the start of a frame.
+    pending_resize: Option<PhysicalSize<u32>>,
 
     egui_interface: DebugOverlay,
     timer: TimerManager,
+    /// Watches the chunk shader, whose pipelines are recompiled when it is modified
+    shader_manager: ShaderManager,
+
+    settings: Settings,
+    settings_path: PathBuf,
+    bindings: Bindings,
+    portals: Portals,
+    portals_path: PathBuf,
+    /// Saved state of the players of the world, into which the local player is saved on exit
+    players: Players,
+    player_id: String,
+    worldgen_preset: WorldgenPreset,
+    /// Preset that was active before the preset file was reloaded, so that both can be compared
+    previous_worldgen_preset: Option<WorldgenPreset>,
+    worldgen_preset_path: PathBuf,
+    /// Dimension and position of the other players on the server, by their player id
+    remote_players: HashMap<String, (String, Vector3<f32>)>,
 }
 
 impl Engine {
     fn new(event_loop: &EventLoop<()>, engine_config: EngineConfig) -> Self {
+        // Benchmarks and soak tests run unattended, so they never wait for the setup screen
+        let first_run = !engine_config.settings_path.exists() && engine_config.benchmark.is_none() && engine_config.soak.is_none();
+        let mut settings = Settings::load(&engine_config.settings_path);
+        for (name, &value) in &settings.cvars {
+            // Console variables that were renamed or removed since the settings were saved are dropped
+            if let Err(err) = cvars::set(name, value) {
+                warn!("Ignoring the saved console variable {name:?}: {err}");
+            }
+        }
+        settings.accessibility.palette.set_active();
+        if settings.graphics.worker_threads > 0 {
+            if let Err(err) = rayon::ThreadPoolBuilder::new()
+                .num_threads(settings.graphics.worker_threads)
+                .build_global()
+            {
+                warn!("Could not limit the worker threads: {err}");
+            }
+        }
+        let vsync = engine_config.vsync || settings.graphics.vsync;
+        let fullscreen = engine_config.fullscreen || settings.graphics.fullscreen;
+        let portals = Portals::load(&engine_config.portals_path);
+        let player_id = settings
+            .player_id
+            .get_or_insert_with(new_player_id)
+            .clone();
+        let players = Players::load(&engine_config.world_path.join(PLAYERS_FILE_NAME));
+        // A benchmark always starts at the beginning of its path
+        let player = players
+            .players
+            .get(&player_id)
+            .filter(|_| engine_config.benchmark.is_none())
+            .cloned();
+        let worldgen_preset = WorldgenPreset::load(&engine_config.worldgen_preset_path);
+        let world_config = WorldConfig::load_or_create(
+            &engine_config
+                .world_path
+                .join(WORLD_CONFIG_FILE_NAME),
+            engine_config.world_config.clone(),
+        );
+
         let window = WindowBuilder::new()
             .with_inner_size(PhysicalSize::new(engine_config.window_size.0, engine_config.window_size.1))
+            .with_transparent(engine_config.transparent)
             .build(event_loop)
             .unwrap();
-        if engine_config.fullscreen {
+        if fullscreen {
             window.set_fullscreen(Some(Fullscreen::Borderless(None)));
         }
 
-        let render_ctx = pollster::block_on(RenderCtx::new(&window, engine_config.vsync));
-
-        let render_ctx = Arc::new(render_ctx);
-
-        let (width, height) = {
-            let surface_config = render_ctx
-                .surface_config
-                .try_lock()
-                .expect("AAAAAA");
+        let frame_capture = FrameCapture::new();
+        let render_ctx = pollster::block_on(RenderCtx::new(&window, vsync, engine_config.transparent));
 
-            (surface_config.width, surface_config.height)
-        };
+        let (width, height) = render_ctx.surface_size();
 
-        let camera = Camera::new(
-            &*render_ctx,
+        let mut camera = Camera::new(
+            &render_ctx,
             (-79.21167, 5.4288225, -39.484493),
             Deg(-42.0),
             Deg(-20.0),
@@ -82,102 +321,903 @@ impl Engine {
             0.1,
             1000.0,
         );
+        if let Some(player) = &player {
+            camera.position = player.position.into();
+            camera.set_orientation(Rad(player.orientation[0]), Rad(player.orientation[1]));
+        }
 
         let mut timer = TimerManager::new();
-        timer.start("frame");
 
-        let mut chunk_manager = ChunkManager::new(camera.position.to_vec(), &render_ctx, &camera.bind_group_layout);
+        let overworld_generator = engine_config
+            .generator
+            .clone()
+            .unwrap_or_else(|| Arc::new(WorldGenerator::new(world_config.clone(), worldgen_preset.clone())));
+        let mut chunk_manager = ChunkManager::new(camera.position.to_vec(), &render_ctx, "overworld", overworld_generator);
+        let nether_generator = Arc::new(WorldGenerator::new(world_config.nether(), worldgen_preset.clone()));
+        chunk_manager
+            .add_dimension("nether", nether_generator, &render_ctx)
+            .expect("dimension names to be unique");
+        let recording_path = engine_config.packet_recording_path.as_deref();
+        let server = engine_config
+            .server_address
+            .as_ref()
+            .and_then(|address| match ServerConnection::connect(address, &player_id, recording_path) {
+                Ok(server) => Some(server),
+                Err(err) => {
+                    warn!("Could not connect to the server, playing in the local world instead: {err:#}");
+                    None
+                }
+            });
+        // The server keeps the world, so the local world is only opened when playing alone
+        match server {
+            Some(server) => chunk_manager.connect(server),
+            None => {
+                if let Err(err) = chunk_manager.open_world(&engine_config.world_path) {
+                    warn!(
+                        "Could not open the world at {:?}, it will not be saved: {err:#}",
+                        engine_config.world_path
+                    );
+                }
+            }
+        }
+        if let Some(dimension) = player
+            .as_ref()
+            .and_then(|player| chunk_manager.dimension(&player.dimension))
+        {
+            if let Err(err) = chunk_manager.switch_dimension(dimension) {
+                warn!("Could not restore the dimension of the player: {err:#}");
+            }
+        }
+        if engine_config.texture_atlas_path.exists() {
+            if let Err(err) = chunk_manager.load_texture_atlas(&engine_config.texture_atlas_path, &render_ctx) {
+                warn!(
+                    "Could not load the texture atlas from {:?}, voxels are not textured: {err:#}",
+                    engine_config.texture_atlas_path
+                );
+            }
+        }
         chunk_manager.generate_chunks(&mut timer);
-        chunk_manager.generate_chunk_meshes(&*render_ctx, &camera.bind_group_layout, &mut timer);
+        chunk_manager.generate_chunk_meshes(&render_ctx, &mut timer);
+
+        let soak_bot = engine_config
+            .soak
+            .map(|config| SoakBot::new(config, camera.position));
+        let benchmark = engine_config
+            .benchmark
+            .map(|config| Benchmark::new(config, camera.position, &chunk_manager));
+        let console = (engine_config.console || engine_config.admin_address.is_some()).then(|| {
+            let console = CommandBus::new();
+            if engine_config.console {
+                if let Err(err) = console.read_stdin() {
+                    warn!("Could not read admin commands from the standard input: {err:#}");
+                }
+            }
+            if let Some(admin_address) = &engine_config.admin_address {
+                if let Err(err) = console.listen(admin_address) {
+                    warn!("Could not open the admin port: {err:#}");
+                }
+            }
+            console
+        });
 
-        let imgui_overlay = DebugOverlay::new(Arc::clone(&render_ctx), &window);
+        let viewmodel = Viewmodel::new(&render_ctx);
+        let selection_renderer = SelectionRenderer::new(&render_ctx);
+        let debug_lines = DebugLines::new(&render_ctx);
+        let debug_boxes = DebugBoxes::new(&render_ctx);
+        let entity_renderer = EntityRenderer::new(&render_ctx);
+        let clouds = CloudRenderer::new(&render_ctx);
+        let lens = LensEffects::new(&render_ctx);
+        let temporal = TemporalEffects::new(&render_ctx);
+        let depth_of_field = DepthOfField::new(&render_ctx);
+        let light_bounce = LightBounce::new(&render_ctx);
+
+        let mut imgui_overlay = DebugOverlay::new(&render_ctx, &window, settings.overlay_theme);
+        let mut inventory = Inventory::new();
+        if let Some(player) = player {
+            imgui_overlay.game_mode = player.game_mode;
+            inventory = Inventory::from_stacks(player.inventory);
+        }
+        imgui_overlay.accessibility = settings.accessibility;
+        imgui_overlay.power_saving_mode = settings.power_saving;
+        imgui_overlay.render_distance = settings.graphics.render_distance;
+        if let Some(benchmark) = &benchmark {
+            imgui_overlay.front_to_back = benchmark.front_to_back();
+        }
+        imgui_overlay.setup_wizard = first_run.then(|| {
+            SetupWizard::new(GraphicsSettings {
+                vsync,
+                fullscreen,
+                ..settings.graphics
+            })
+        });
 
         Self {
             window,
             frame_timer: FrameTimer::new(),
+            simulation_time: Duration::ZERO,
             render_ctx,
+            vsync,
+            transparent: engine_config.transparent,
             camera,
-            camera_controller: CameraController::new(100.0, 0.5),
+            camera_controller: CameraController::new(100.0, settings.graphics.mouse_sensitivity),
+            viewmodel,
+            block_breaker: BlockBreaker::new(),
+            selection_renderer,
+            debug_lines,
+            debug_boxes,
+            clouds,
+            lens,
+            temporal,
+            depth_of_field,
+            light_bounce,
+            day_cycle: DayCycle::new(),
+            environment: EnvironmentState::new(),
+            ambiance: AmbiancePlayer::new(),
+            breaking: false,
+            place_requested: false,
+            inventory,
+            thumbnail_requested: false,
+            thumbnail_capture: None,
+            photo_session: None,
+            photo_capture: None,
+            photos_path: engine_config.photos_path,
+            frame_capture,
+            power_saving: PowerSaving::new(settings.power_saving),
+            soak_bot,
+            benchmark,
+            console,
             mouse_locked: false,
+            pending_resize: None,
             chunk_manager,
+            entities: Entities::new(),
+            entity_renderer,
             egui_interface: imgui_overlay,
             timer,
+            shader_manager: ShaderManager::new([PathBuf::from(CHUNK_SHADER_PATH)]),
+            settings,
+            settings_path: engine_config.settings_path,
+            bindings: Bindings::load(&engine_config.bindings_path),
+            portals,
+            portals_path: engine_config.portals_path,
+            players,
+            player_id,
+            worldgen_preset,
+            previous_worldgen_preset: None,
+            worldgen_preset_path: engine_config.worldgen_preset_path,
+            remote_players: HashMap::new(),
         }
     }
 
     fn render(&mut self) {
         self.timer.start("render_all");
-        let render_ctx = &*self.render_ctx;
+
+        if self.render_ctx.is_device_lost() {
+            self.rebuild_gpu_resources();
+        }
+
+        self.apply_pending_resize();
+        self.reload_modified_shaders();
 
         let dt = self.frame_timer.get_dt();
+        self.update_setup_wizard();
 
-        self.chunk_manager.render_distance = self.egui_interface.render_distance;
+        self.power_saving.mode = self.egui_interface.power_saving_mode;
+        let power_saving = self.power_saving_active();
+        self.chunk_manager.render_distance = if power_saving {
+            self.egui_interface
+                .render_distance
+                .min(POWER_SAVING_MAX_RENDER_DISTANCE)
+        } else {
+            self.egui_interface.render_distance
+        };
+        self.chunk_manager.set_power_saving(power_saving);
+        self.chunk_manager.prefetch_time = self.egui_interface.prefetch_time;
+        self.chunk_manager.deep_generation_distance = self.egui_interface.deep_generation_distance;
+        self.chunk_manager.voxel_memory_budget = self.egui_interface.voxel_memory_budget_mb * 1024 * 1024;
+        self.chunk_manager.mesh_budget = Duration::from_secs_f32(self.egui_interface.mesh_budget_ms / 1000.0);
+        self.chunk_manager.idle_generation = dt < IDLE_FRAME_TIME;
         self.chunk_manager.render_empty_chunks = self.egui_interface.render_empty_chunks;
-        self.camera_controller.no_clip = self.egui_interface.no_clip;
+        self.chunk_manager
+            .set_depth_prepass(self.egui_interface.depth_prepass);
+        self.chunk_manager
+            .set_front_to_back(self.egui_interface.front_to_back);
+        self.chunk_manager
+            .set_render_mode(self.egui_interface.render_mode);
+        self.chunk_manager
+            .set_meshing_mode(self.egui_interface.meshing_mode);
+        self.chunk_manager
+            .set_skin_ring_width(self.egui_interface.skin_ring_width);
+        self.chunk_manager
+            .set_lod_distance(self.egui_interface.lod_distance);
+        self.chunk_manager.set_ambient_occlusion(
+            self.egui_interface.ambient_occlusion,
+            self.egui_interface.ambient_occlusion_strength,
+        );
+        self.chunk_manager
+            .set_smooth_normals(self.egui_interface.smooth_normals);
+        self.chunk_manager
+            .set_retain_cpu_meshes(self.egui_interface.retain_cpu_meshes);
+        self.chunk_manager
+            .set_lighting_mode(self.egui_interface.lighting_mode);
+        self.clouds.enabled = self.egui_interface.clouds;
+        self.light_bounce.enabled = self.egui_interface.light_bounce;
+        self.clouds.coverage = self.egui_interface.cloud_coverage;
+        self.lens.enabled = self.egui_interface.lens_effects;
+        self.lens.reduced_flashing = self.egui_interface.accessibility.reduced_flashing;
+        self.viewmodel.reduced_motion = self.egui_interface.accessibility.reduced_motion;
+        if self.egui_interface.accessibility.palette != ColorPalette::active() {
+            self.set_color_palette(self.egui_interface.accessibility.palette);
+        }
+        self.temporal.mode = self.egui_interface.temporal_mode;
+        self.camera_controller.no_clip = self
+            .egui_interface
+            .game_mode
+            .no_clip(self.egui_interface.no_clip);
+        self.update_photo_mode(dt);
 
-        self.timer.start("update_camera");
+        if let Some(dimension) = self.egui_interface.requested_dimension.take() {
+            self.switch_dimension(dimension);
+        }
+        if mem::take(
+            &mut self
+                .egui_interface
+                .worldgen_preset_reload_requested,
+        ) {
+            self.reload_worldgen_preset();
+        }
+        if mem::take(&mut self.egui_interface.worldgen_preset_swap_requested) {
+            self.swap_worldgen_presets();
+        }
+        if mem::take(&mut self.egui_interface.frame_capture_requested) {
+            self.frame_capture.capture_next_frame();
+        }
+        if mem::take(&mut self.egui_interface.entity_spawn_requested) {
+            self.throw_held_block();
+        }
 
-        self.camera_controller
-            .update_physics(&mut self.camera, &self.chunk_manager, dt);
+        // Looking around follows the frame time instead of the simulation, so that it is smooth at any timescale and while paused
+        if self.soak_bot.is_none() && self.benchmark.is_none() {
+            self.camera_controller
+                .update_look(&mut self.camera, dt);
+        }
 
-        self.camera_controller
-            .update_camera(&mut self.camera, dt);
-        self.camera.update_buffer(&render_ctx);
-        self.timer.end("update_camera");
+        let step_requested = mem::take(&mut self.egui_interface.step_requested);
+        if !self.egui_interface.paused {
+            self.simulation_time += dt.mul_f32(self.egui_interface.timescale.max(0.0));
 
-        self.timer.start("chunk_manager");
-        self.chunk_manager
-            .update_player_location(self.camera.position.to_vec());
+            let mut ticks = 0;
+            while self.simulation_time >= TICK_DURATION && ticks < MAX_TICKS_PER_FRAME {
+                self.update(TICK_DURATION);
+                self.simulation_time -= TICK_DURATION;
+                ticks += 1;
+            }
 
-        self.chunk_manager
-            .generate_chunks(&mut self.timer);
+            if ticks == MAX_TICKS_PER_FRAME {
+                self.simulation_time = Duration::ZERO;
+            }
+        } else if step_requested {
+            self.update(TICK_DURATION);
+        }
 
-        self.chunk_manager
-            .generate_chunk_meshes(&*render_ctx, &self.camera.bind_group_layout, &mut self.timer);
+        // Follows the frame time instead of the simulation, so that transitions finish while the simulation is paused
+        let (sky_visibility, sun_visible) = self.sky_visibility();
+        let eye_voxel = self.chunk_manager.voxel_at(WorldLocation(
+            self.camera
+                .eye_position()
+                .to_vec()
+                .map(|c| c.floor() as i32),
+        ));
+        self.environment
+            .update(Environment::detect(eye_voxel, sky_visibility), dt);
+        self.ambiance.update(&self.environment);
+        let look = self.environment.look();
+        self.day_cycle.animate(dt);
 
-        self.timer.start("chunk_manager_unloading");
-        self.chunk_manager.unload_chunks();
-        self.timer.end("chunk_manager_unloading");
-        self.timer.end("chunk_manager");
+        let render_ctx = &self.render_ctx;
 
+        let mesh_size = self.chunk_manager.mesh_size();
         let stats = PerFrameStats {
             fps: 1.0 / dt.as_secs_f32(),
             last_frame_time: dt.as_secs_f32() * 1000.0,
             position: self.camera.position.to_vec(),
             num_chunks: self.chunk_manager.chunks.len() as u32,
-            num_vertices: self.chunk_manager.total_vertices,
-            num_triangles: self.chunk_manager.total_triangles,
+            num_vertices: mesh_size.vertices,
+            num_triangles: mesh_size.triangles,
             total_voxel_data_size: self.chunk_manager.total_voxel_data_size,
-            total_mesh_data_size: self.chunk_manager.total_mesh_data_size,
+            total_mesh_data_size: mesh_size.bytes,
+            retained_mesh_data_size: mesh_size.retained_bytes,
+            gpu_shared_chunks: self.chunk_manager.gpu_shared_chunks(),
+            frame_capture_available: self.frame_capture.is_available(),
+            power_saving,
             currently_rendered_chunk_radius: self.chunk_manager.current_chunk_mesh_radius - 1,
+            deep_generation_radius: self.chunk_manager.deep_generation_radius,
             current_meshgen_queue_size: self.chunk_manager.chunk_mesh_queue.len(),
             current_chunkgen_queue_size: self.chunk_manager.location_queue.len(),
             current_chunkdata_buffer_size: self.chunk_manager.generated_chunks_queue.len(),
+            gpu_culling: self.chunk_manager.uses_gpu_culling(),
+            visible_chunks: self.chunk_manager.visible_chunks(),
+            shadow_casters: self
+                .chunk_manager
+                .shadow_casters
+                .each_ref()
+                .map(Vec::len),
+            inventory: self.inventory.clone(),
+            held_block: self.viewmodel.selected,
+            dimensions: self.chunk_manager.dimension_names().to_vec(),
+            active_dimension: self.chunk_manager.active_dimension(),
+            gpu_timings: render_ctx
+                .gpu_timings
+                .lock()
+                .expect("GPU timings lock to not be poisoned")
+                .clone(),
+            environment: self.environment.clone(),
+            shader_errors: self.shader_manager.errors.clone(),
+            failed_chunks: self.chunk_manager.failed_chunks(),
+            has_previous_worldgen_preset: self.previous_worldgen_preset.is_some(),
+            num_entities: self.entities.len(),
         };
 
         self.timer.start("imgui_prepare");
-        self.egui_interface
-            .build_ui(&self.window, stats, &mut self.timer);
+        self.egui_interface.build_ui(
+            &self.window,
+            stats,
+            &mut self.timer,
+            &mut self.portals,
+            &mut self.day_cycle,
+            &self.worldgen_preset,
+        );
         self.timer.end("imgui_prepare");
 
-        let mut handle = render_ctx.start_rendering();
+        let Some(mut handle) = render_ctx.start_rendering() else {
+            // The surface is usable again in one of the next frames, or the device is rebuilt at the start of the next frame
+            self.timer.end("render_all");
+            self.timer.finish_frame();
+            return;
+        };
+        self.camera.jitter = self.temporal.update(&mut handle, &self.camera);
+        let distance_fog = look.distance_fog(self.egui_interface.fog_density, self.egui_interface.fog_color);
+        self.camera
+            .update_buffer(&mut handle, self.clouds.uniform(), look.fog(), distance_fog);
+        self.depth_of_field
+            .update(&mut handle, &self.camera);
+        self.light_bounce
+            .update(&mut handle, &self.camera, &self.chunk_manager);
+        self.viewmodel.update(&mut handle, dt);
+        self.lens
+            .update(&mut handle, dt, sky_visibility, sun_visible, look.tint);
+        self.selection_renderer
+            .update(&mut handle, self.block_breaker.target, self.block_breaker.progress());
+        self.update_debug_lines(&mut handle);
+        self.update_debug_boxes(&mut handle);
+        self.entity_renderer
+            .update(&mut handle, &self.entities);
 
         // Use command encoder to prepare egui
         self.egui_interface
-            .prepare_render(handle.get_command_encoder());
+            .prepare_render(render_ctx, handle.get_command_encoder());
+        self.chunk_manager
+            .update_lighting(&mut handle, &self.day_cycle.uniform());
+        self.chunk_manager
+            .prepare_render(&mut handle, &self.camera);
+
+        let mut layers = RenderLayers::default();
+        layers.add(RenderLayer::World, "chunks", &self.chunk_manager);
+        if let Some(ray_traced_lighting) = self.chunk_manager.ray_traced_lighting() {
+            // Only the terrain is darkened, the transparent surfaces and everything after them are drawn on top
+            layers.add_effect(RenderLayer::World, "ray_traced_lighting", ray_traced_lighting);
+        }
+        layers.add(RenderLayer::World, "water", self.chunk_manager.water_renderer());
+        layers.add(RenderLayer::World, "clouds", &self.clouds);
+        layers.add(RenderLayer::Entities, "entities", &self.entity_renderer);
+        // Photos only show the world, so the held block, the targeted voxel and the debug visualizations are hidden
+        let photo_mode = self.photo_session.is_some();
+        if !photo_mode {
+            layers.add(RenderLayer::World, "selection", &self.selection_renderer);
+            layers.add(RenderLayer::Entities, "viewmodel", &self.viewmodel);
+            layers.add(RenderLayer::Debug, "debug_lines", &self.debug_lines);
+            layers.add(RenderLayer::Debug, "debug_boxes", &self.debug_boxes);
+        }
+        layers.add_effect(RenderLayer::PostProcess, "light_bounce", &self.light_bounce);
+        layers.add_effect(RenderLayer::PostProcess, "temporal", &self.temporal);
+        layers.add_effect(RenderLayer::PostProcess, "depth_of_field", &self.depth_of_field);
+        layers.add(RenderLayer::PostProcess, "lens", &self.lens);
+        // The photo mode window is hidden while flying around with the locked mouse
+        if !(photo_mode && self.mouse_locked) {
+            layers.add(RenderLayer::Ui, "ui", &self.egui_interface);
+        }
 
         self.timer.start("render_3d");
-        handle.render(&self.chunk_manager, &self.camera);
-        self.timer.end("render_3d");
+        for layer in RenderLayer::iter() {
+            if layer == RenderLayer::Ui {
+                if mem::take(&mut self.thumbnail_requested) {
+                    // Captured before the UI is drawn, so that the thumbnail only shows the world
+                    self.thumbnail_capture = handle.read_surface();
+                }
+                if let Some(photo_capture) = &mut self.photo_capture {
+                    match handle.read_surface() {
+                        Some(tile) => photo_capture.add_tile(tile),
+                        None => {
+                            warn!("Could not take a photo, the surface does not support being copied from");
+                            self.photo_capture = None;
+                        }
+                    }
+                }
+                self.timer.end("render_3d");
+                self.timer.start("render_ui");
+            }
 
-        self.timer.start("render_ui");
-        handle.render(&mut self.egui_interface, &self.camera);
+            layers.render(&mut handle, layer, &self.camera);
+        }
         self.timer.end("render_ui");
 
         self.timer.start("render_final");
         handle.finish_rendering();
         self.timer.end("render_final");
+        self.save_finished_photo();
         self.timer.end("render_all");
+        self.timer.finish_frame();
+
+        if let Some(benchmark) = &mut self.benchmark {
+            let gpu_timings = self
+                .render_ctx
+                .gpu_timings
+                .lock()
+                .expect("GPU timings lock to not be poisoned");
+            benchmark.record_frame(dt, &self.timer, &gpu_timings);
+        }
+    }
+
+    /// Applies the settings of the setup screen while it is shown, so that their effect can be seen, and saves them once the player is done
+    fn update_setup_wizard(&mut self) {
+        let Some(setup_wizard) = &self.egui_interface.setup_wizard else {
+            return;
+        };
+        let graphics = setup_wizard.graphics;
+        let finished = setup_wizard.finished;
+
+        self.egui_interface.render_distance = graphics.render_distance;
+        self.camera_controller.sensitivity = graphics.mouse_sensitivity;
+        if graphics.vsync != self.vsync {
+            self.vsync = graphics.vsync;
+            self.render_ctx.set_vsync(graphics.vsync);
+        }
+        if graphics.fullscreen != self.window.fullscreen().is_some() {
+            self.window.set_fullscreen(
+                graphics
+                    .fullscreen
+                    .then_some(Fullscreen::Borderless(None)),
+            );
+        }
+        // The world is only entered once the setup is done
+        self.egui_interface.paused = !finished;
+
+        if finished {
+            self.egui_interface.setup_wizard = None;
+            self.settings.graphics = graphics;
+            self.save_settings();
+        }
+    }
+
+    /// Starts or ends the photo mode as requested by the debug overlay and moves the free camera while it is active.
+    /// Also prepares the camera and the post effects for the next tile of a photo that is being rendered.
+    fn update_photo_mode(&mut self, dt: Duration) {
+        if self.egui_interface.photo_mode != self.photo_session.is_some() {
+            if self.egui_interface.photo_mode {
+                self.start_photo_mode();
+            } else {
+                self.end_photo_mode();
+            }
+        }
+
+        let photo = self.egui_interface.photo;
+        let photo_mode = self.photo_session.is_some();
+        let photo_requested = mem::take(&mut self.egui_interface.photo_requested);
+        if photo_mode {
+            // The simulation stays paused, so the camera is moved once per frame instead of once per tick
+            self.egui_interface.paused = true;
+            self.camera_controller.no_clip = true;
+            self.camera_controller
+                .update_movement(&mut self.camera, dt);
+            self.camera.roll = Rad(photo.roll.to_radians() as f64);
+
+            if photo_requested && self.photo_capture.is_none() {
+                let path = next_photo_path(&self.photos_path);
+                self.photo_capture = Some(PhotoCapture::new(path, photo.supersampling));
+            }
+        }
+
+        self.lens.exposure_bias = if photo_mode { photo.exposure } else { 1.0 };
+        self.depth_of_field.focus_distance = photo.focus_distance;
+        self.depth_of_field.aperture = if photo_mode { photo.aperture } else { 0.0 };
+
+        // Screen space effects would only see the current tile, so they are disabled or scaled to the size of the whole photo
+        self.camera.tile = self
+            .photo_capture
+            .as_ref()
+            .and_then(PhotoCapture::current_tile);
+        self.lens.hide_glare = self.camera.tile.is_some();
+        self.depth_of_field.pixel_scale = self
+            .photo_capture
+            .as_ref()
+            .map_or(1.0, |photo_capture| photo_capture.tiles_per_side() as f32);
+        if self.camera.tile.is_some() {
+            self.temporal.mode = TemporalMode::Off;
+        }
+    }
+
+    /// Detaches the camera from the player and pauses the simulation, so that it can fly freely through the world
+    fn start_photo_mode(&mut self) {
+        self.photo_session = Some(PhotoSession {
+            position: self.camera.position,
+            velocity: self.camera.velocity,
+            orientation: self.camera.orientation(),
+            was_paused: self.egui_interface.paused,
+        });
+        self.egui_interface.paused = true;
+    }
+
+    /// Moves the camera back to the player and continues the simulation if it was running before the photo mode
+    fn end_photo_mode(&mut self) {
+        let Some(session) = self.photo_session.take() else {
+            return;
+        };
+
+        self.camera.position = session.position;
+        self.camera.velocity = session.velocity;
+        let (yaw, pitch) = session.orientation;
+        self.camera.set_orientation(yaw, pitch);
+        self.camera.roll = Rad(0.0);
+        self.egui_interface.paused = session.was_paused;
+        self.photo_capture = None;
+    }
+
+    /// Saves the photo once all of its tiles are rendered, waiting for the device to read back the last one
+    fn save_finished_photo(&mut self) {
+        match &self.photo_capture {
+            Some(photo_capture) if photo_capture.current_tile().is_none() => {}
+            _ => return,
+        }
+        let photo_capture = self
+            .photo_capture
+            .take()
+            .expect("the finished photo capture to exist");
+
+        self.render_ctx.device.poll(wgpu::Maintain::Wait);
+        let path = photo_capture.path.clone();
+        match photo_capture.save() {
+            Ok(()) => info!("Saved a photo to {}", path.display()),
+            Err(err) => warn!("Failed to save the photo: {err:#}"),
+        }
+    }
+
+    /// Recompiles the pipelines of the shaders that were modified on disk
+    fn reload_modified_shaders(&mut self) {
+        for (path, source) in self.shader_manager.modified_shaders() {
+            let result = self
+                .chunk_manager
+                .reload_chunk_shader(&self.render_ctx, &source);
+            self.shader_manager.report(path, result);
+        }
+    }
+
+    /// Collects the chunks in the streaming queues if they are enabled in the debug overlay
+    fn update_debug_boxes(&mut self, handle: &mut RenderHandle) {
+        if self.egui_interface.queue_boxes {
+            self.chunk_manager
+                .add_queue_boxes(&mut self.debug_boxes);
+        }
+        self.debug_boxes.update(handle);
+    }
+
+    /// Collects the chunk outlines and the voxel grid that are enabled in the debug overlay
+    fn update_debug_lines(&mut self, handle: &mut RenderHandle) {
+        let camera_position = self.camera.position.to_vec();
+        if self.egui_interface.chunk_outlines {
+            self.chunk_manager.add_chunk_outlines(
+                &mut self.debug_lines,
+                ChunkLocation::from_world_location_f32(camera_position),
+                self.egui_interface.chunk_outline_distance,
+            );
+        }
+        if self.egui_interface.voxel_grid {
+            self.debug_lines
+                .add_voxel_grid(camera_position.map(|c| c.floor() as i32), VOXEL_GRID_RADIUS, [1.0, 1.0, 1.0, 0.3]);
+        }
+        let dimension = &self.chunk_manager.dimension_names()[self.chunk_manager.active_dimension()];
+        for (player_dimension, position) in self.remote_players.values() {
+            if player_dimension == dimension {
+                let half_extents = Vector3::new(PLAYER_HALF_WIDTH, 0.0, PLAYER_HALF_WIDTH);
+                self.debug_lines.add_box(
+                    *position - half_extents,
+                    *position + half_extents + PLAYER_HEIGHT * Vector3::unit_y(),
+                    [1.0, 0.8, 0.2, 1.0],
+                );
+            }
+        }
+
+        self.debug_lines.update(handle);
+    }
+
+    /// Recreates the device after it was lost and all GPU resources from their CPU-side data
+    fn rebuild_gpu_resources(&mut self) {
+        warn!("Rebuilding all GPU resources");
+
+        pollster::block_on(self.render_ctx.recreate_device());
+        let render_ctx = &self.render_ctx;
+
+        self.camera.rebuild_gpu_resources(render_ctx);
+        self.viewmodel.rebuild_gpu_resources(render_ctx);
+        self.selection_renderer
+            .rebuild_gpu_resources(render_ctx);
+        self.debug_lines
+            .rebuild_gpu_resources(render_ctx);
+        self.debug_boxes
+            .rebuild_gpu_resources(render_ctx);
+        self.entity_renderer
+            .rebuild_gpu_resources(render_ctx);
+        self.clouds.rebuild_gpu_resources(render_ctx);
+        self.lens.rebuild_gpu_resources(render_ctx);
+        self.temporal.rebuild_gpu_resources(render_ctx);
+        self.depth_of_field
+            .rebuild_gpu_resources(render_ctx);
+        self.light_bounce
+            .rebuild_gpu_resources(render_ctx);
+        self.chunk_manager
+            .rebuild_gpu_resources(render_ctx);
+        self.egui_interface
+            .rebuild_gpu_resources(render_ctx);
+    }
+
+    /// Advances the simulation (camera physics and chunk streaming) by one fixed tick
+    fn update(&mut self, dt: Duration) {
+        self.timer.start("update_camera");
+
+        if let Some(soak_bot) = &mut self.soak_bot {
+            soak_bot.update(&mut self.camera, &mut self.chunk_manager, dt);
+        } else if let Some(benchmark) = &mut self.benchmark {
+            benchmark.update(&mut self.camera, dt);
+        } else {
+            self.camera_controller
+                .update_physics(&mut self.camera, &self.chunk_manager, dt);
+
+            self.camera_controller
+                .update_movement(&mut self.camera, dt);
+        }
+        self.timer.end("update_camera");
+
+        self.update_portals();
+        self.update_block_interaction(dt);
+        self.entities.update(&self.chunk_manager, dt);
+        self.chunk_manager.animate_water(dt);
+        self.clouds.animate(dt);
+
+        self.timer.start("chunk_manager");
+        self.chunk_manager
+            .update_player_location(self.camera.position.to_vec(), dt);
+        self.update_multiplayer();
+
+        self.chunk_manager
+            .generate_chunks(&mut self.timer);
+
+        self.chunk_manager
+            .generate_chunk_meshes(&self.render_ctx, &mut self.timer);
+
+        self.timer.start("chunk_manager_unloading");
+        self.chunk_manager
+            .unload_chunks(&self.render_ctx);
+        self.timer.end("chunk_manager_unloading");
+        self.timer.end("chunk_manager");
+    }
+
+    /// Sends the position of the player to the server and receives the chunks, edits and positions of the other players
+    fn update_multiplayer(&mut self) {
+        let Some(server) = self.chunk_manager.server() else {
+            return;
+        };
+        server.send(&ClientMessage::PlayerPosition {
+            dimension: self.chunk_manager.dimension_names()[self.chunk_manager.active_dimension()].clone(),
+            position: self.camera.position.to_vec(),
+        });
+
+        for message in self.chunk_manager.receive_from_server() {
+            match message {
+                ServerMessage::PlayerPosition {
+                    player_id,
+                    dimension,
+                    position,
+                } => {
+                    self.remote_players
+                        .insert(player_id, (dimension, position));
+                }
+                ServerMessage::PlayerLeft { player_id } => {
+                    self.remote_players.remove(&player_id);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Fraction of a few rays from the eye towards the sky that are not blocked by terrain, and whether the sun is not blocked
+    fn sky_visibility(&self) -> (f32, bool) {
+        const SKY_RAY_LENGTH: f32 = 64.0;
+        const SUN_RAY_LENGTH: f32 = 256.0;
+        let sky_directions = [
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(-1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 1.0),
+            Vector3::new(0.0, 1.0, -1.0),
+        ];
+
+        let eye = self.camera.eye_position().to_vec();
+        let visible_rays = sky_directions
+            .iter()
+            .filter(|&&direction| {
+                self.chunk_manager
+                    .raycast(eye, direction.normalize(), SKY_RAY_LENGTH)
+                    .is_none()
+            })
+            .count();
+        let sun_visible = self
+            .chunk_manager
+            .raycast(eye, -sun_direction(), SUN_RAY_LENGTH)
+            .is_none();
+
+        (visible_rays as f32 / sky_directions.len() as f32, sun_visible)
+    }
+
+    /// Teleports the player to the target of a portal when it is entered
+    fn update_portals(&mut self) {
+        let dimension = &self.chunk_manager.dimension_names()[self.chunk_manager.active_dimension()];
+        let Some(portal) = self
+            .portals
+            .update(self.camera.position, dimension)
+            .cloned()
+        else {
+            return;
+        };
+
+        info!("Teleporting through portal {:?} to {:?}", portal.name, portal.target);
+        if let Some(target_dimension) = &portal.target_dimension {
+            match self.chunk_manager.dimension(target_dimension) {
+                Some(id) => self.switch_dimension(id),
+                None => warn!(
+                    "The target dimension {target_dimension:?} of portal {:?} does not exist",
+                    portal.name
+                ),
+            }
+        }
+
+        self.camera.position = Point3::from(portal.target);
+        self.camera.velocity = Vector3::zero();
+
+        let dimension = &self.chunk_manager.dimension_names()[self.chunk_manager.active_dimension()];
+        self.portals
+            .arrive(self.camera.position, dimension);
+    }
+
+    /// Loads the worldgen preset file again and regenerates the active dimension with it. The current preset is kept if the file is invalid.
+    fn reload_worldgen_preset(&mut self) {
+        match WorldgenPreset::try_load(&self.worldgen_preset_path) {
+            Ok(preset) => {
+                info!("Reloaded worldgen preset from {:?}", self.worldgen_preset_path);
+                self.apply_worldgen_preset(preset.clone());
+                self.previous_worldgen_preset = Some(mem::replace(&mut self.worldgen_preset, preset));
+            }
+            Err(err) => warn!("Failed to reload worldgen preset from {:?}: {err:#}", self.worldgen_preset_path),
+        }
+    }
+
+    /// Switches back to the preset that was active before the last reload, e.g. to compare it with the reloaded preset
+    fn swap_worldgen_presets(&mut self) {
+        let Some(previous) = self.previous_worldgen_preset.take() else {
+            return;
+        };
+
+        self.apply_worldgen_preset(previous.clone());
+        self.previous_worldgen_preset = Some(mem::replace(&mut self.worldgen_preset, previous));
+    }
+
+    /// Generates the active dimension with another preset. Chunks near the player are kept if the overlay asks for it.
+    fn apply_worldgen_preset(&mut self, preset: WorldgenPreset) {
+        let result = match self.egui_interface.worldgen_keep_radius {
+            0 => self
+                .chunk_manager
+                .set_worldgen_preset(preset, &self.render_ctx),
+            keep_radius => self
+                .chunk_manager
+                .hot_swap_worldgen_preset(preset, keep_radius, &self.render_ctx),
+        };
+        if let Err(err) = result {
+            warn!("Could not apply the worldgen preset: {err:#}");
+        }
+    }
+
+    fn switch_dimension(&mut self, id: DimensionId) {
+        match self.chunk_manager.switch_dimension(id) {
+            Ok(()) => {
+                info!("Switched to dimension {:?}", self.chunk_manager.dimension_names()[id]);
+                // Entities only exist in the active dimension
+                self.entities.clear();
+            }
+            Err(err) => warn!("Failed to switch dimension: {err:#}"),
+        }
+    }
+
+    /// Spawns a small copy of the held block that is thrown in the view direction and despawns after a while
+    fn throw_held_block(&mut self) {
+        let forward = self.camera.forward();
+        let mut entity = Entity::new(
+            self.camera.eye_position().to_vec() + forward,
+            Vector3::new(0.4, 0.4, 0.4),
+            EntityMesh::Voxel(self.viewmodel.selected),
+        );
+        entity.velocity = forward * THROW_SPEED;
+        entity.lifetime = Some(THROWN_BLOCK_LIFETIME);
+        self.entities.spawn(entity);
+    }
+
+    /// Targets the voxel in the view direction, breaks it once the break button was held for its hardness
+    /// and places the held block in front of it if requested. Depending on the game mode, blocks are taken from the inventory.
+    fn update_block_interaction(&mut self, dt: Duration) {
+        let game_mode = self.egui_interface.game_mode;
+
+        let (origin, direction) = self.camera.screen_ray(Vector2::zero());
+        let target = self
+            .chunk_manager
+            .raycast(origin.to_vec(), direction, REACH)
+            .filter(|_| game_mode.can_interact())
+            .and_then(|hit| Some((hit, self.chunk_manager.voxel_at(hit.voxel)?)));
+
+        if let Some(broken) = self
+            .block_breaker
+            .update(target, self.breaking, dt)
+        {
+            let broken_type = self.chunk_manager.voxel_at(broken);
+
+            match self
+                .chunk_manager
+                .set_voxel(broken, VoxelData::new(VoxelType::Air))
+            {
+                Ok(()) => {
+                    if let Some(ty) = broken_type.filter(|&ty| game_mode.uses_inventory() && ty != VoxelType::Error) {
+                        self.inventory.add(ty);
+                    }
+                    self.viewmodel.swing();
+                }
+                Err(err) => warn!("Failed to break voxel: {err:#}"),
+            }
+        }
+
+        if mem::take(&mut self.place_requested) {
+            if let Some((hit, _)) = target {
+                self.place_block(WorldLocation(hit.voxel.0 + hit.normal));
+            }
+        }
+    }
+
+    fn place_block(&mut self, location: WorldLocation) {
+        let ty = self.viewmodel.selected;
+
+        if !self
+            .chunk_manager
+            .voxel_at(location)
+            .is_some_and(|ty| !ty.is_solid())
+        {
+            return;
+        }
+
+        if self.egui_interface.game_mode.uses_inventory() && !self.inventory.try_take(ty) {
+            return;
+        }
+
+        match self
+            .chunk_manager
+            .set_voxel(location, VoxelData::new(ty))
+        {
+            Ok(()) => self.viewmodel.swing(),
+            Err(err) => warn!("Failed to place voxel: {err:#}"),
+        }
     }
 
     fn handle_event(&mut self, event: Event<()>, control_flow: &mut ControlFlow) {
@@ -189,26 +1229,12 @@ impl Engine {
         }
 
         match event {
-            key_press!(VirtualKeyCode::Escape) | close_requested!() => *control_flow = ControlFlow::ExitWithCode(0),
-            key_press!(VirtualKeyCode::LAlt) => {
-                self.mouse_locked = !self.mouse_locked;
-                self.window.set_cursor_visible(!self.mouse_locked);
-            }
-            Event::WindowEvent {
-                event:
-                    WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                virtual_keycode: Some(virtual_keycode),
-                                state,
-                                ..
-                            },
-                        ..
-                    },
-                ..
-            } => {
-                self.camera_controller
-                    .process_keyboard(&virtual_keycode, &state);
+            close_requested!() => *control_flow = ControlFlow::ExitWithCode(0),
+            Event::LoopDestroyed => {
+                self.save_settings();
+                self.save_thumbnail();
+                self.save_world();
+                self.save_player();
             }
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
@@ -222,7 +1248,14 @@ impl Engine {
                         .expect("Could not center mouse");
                 }
             }
-            _ => {}
+            _ => {
+                if let Some((input, state)) = Input::from_event(&event) {
+                    let actions = self.bindings.actions(input).collect::<Vec<_>>();
+                    for action in actions {
+                        self.handle_action(action, state == ElementState::Pressed, control_flow);
+                    }
+                }
+            }
         }
 
         if let Event::WindowEvent { event, .. } = event {
@@ -230,19 +1263,225 @@ impl Engine {
         }
     }
 
+    /// Applies an action that was triggered by pressing or releasing one of its bound inputs
+    fn handle_action(&mut self, action: Action, is_pressed: bool, control_flow: &mut ControlFlow) {
+        if self
+            .camera_controller
+            .process_action(action, is_pressed)
+        {
+            return;
+        }
+        if let Some(slot) = action.block_slot() {
+            if is_pressed {
+                self.select_block(slot);
+            }
+            return;
+        }
+
+        match action {
+            Action::BreakBlock => self.breaking = is_pressed,
+            _ if !is_pressed => {}
+            Action::PlaceBlock => self.place_requested = true,
+            Action::ToggleInventory => self.egui_interface.inventory_open = !self.egui_interface.inventory_open,
+            Action::ToggleMouseLock => {
+                self.mouse_locked = !self.mouse_locked;
+                self.window.set_cursor_visible(!self.mouse_locked);
+            }
+            Action::TogglePause => self.egui_interface.paused = !self.egui_interface.paused,
+            Action::TogglePhotoMode => self.egui_interface.photo_mode = !self.egui_interface.photo_mode,
+            Action::TakePhoto => self.egui_interface.photo_requested = true,
+            Action::StepFrame => self.egui_interface.step_requested = true,
+            Action::CaptureFrame => self.frame_capture.capture_next_frame(),
+            Action::Quit => *control_flow = ControlFlow::ExitWithCode(0),
+            _ => {}
+        }
+    }
+
+    /// Selects the block type with the given index as the held block, in the order of the block types
+    fn select_block(&mut self, index: usize) {
+        if let Some(&ty) = solid_voxel_types().get(index) {
+            self.viewmodel.selected = ty;
+        }
+    }
+
+    /// Meshes the world, the held block, the entities and the block icons again with the colors of another palette
+    fn set_color_palette(&mut self, palette: ColorPalette) {
+        palette.set_active();
+        self.chunk_manager.remesh_all_chunks();
+        self.viewmodel
+            .rebuild_gpu_resources(&self.render_ctx);
+        self.entity_renderer
+            .rebuild_gpu_resources(&self.render_ctx);
+        self.egui_interface
+            .rebuild_block_icons(&self.render_ctx);
+    }
+
+    /// Whether power is saved in this frame. Benchmarks never save power, so that their frame times stay comparable.
+    fn power_saving_active(&mut self) -> bool {
+        self.power_saving.update() && self.benchmark.is_none()
+    }
+
+    /// Time until which the event loop waits before rendering the next frame, if the frame rate is limited
+    fn next_frame_time(&self) -> Option<Instant> {
+        (self.power_saving.is_active() && self.benchmark.is_none()).then(|| self.frame_timer.next_frame(POWER_SAVING_MAX_FPS))
+    }
+
+    /// Whether a soak test is running and its duration has passed
+    fn soak_test_finished(&self) -> bool {
+        let Some(soak_bot) = &self.soak_bot else {
+            return false;
+        };
+
+        if soak_bot.is_finished() {
+            info!(
+                "Soak test finished after {} steps and {} voxel edits",
+                soak_bot.steps, soak_bot.edits
+            );
+        }
+
+        soak_bot.is_finished()
+    }
+
+    /// The exit code of the benchmark once it is finished, after its report was written
+    fn benchmark_finished(&mut self) -> Option<i32> {
+        if !self.benchmark.as_ref()?.is_finished() {
+            return None;
+        }
+
+        let benchmark = self.benchmark.take()?;
+        Some(benchmark.finish(&self.chunk_manager))
+    }
+
+    /// Executes the admin commands that were entered since the last frame. Returns whether the `stop` command was among them.
+    fn execute_console_commands(&mut self) -> bool {
+        let Some(console) = &self.console else {
+            return false;
+        };
+
+        let commands = std::iter::from_fn(|| console.try_recv()).collect::<Vec<_>>();
+        let mut stop = false;
+        for command in commands {
+            stop |= command.words() == ["stop"];
+            let output = self
+                .execute_command(&command)
+                .unwrap_or_else(|err| format!("{err:#}"));
+            command.reply(output);
+        }
+
+        stop
+    }
+
+    /// Runs an admin command of the console or the admin port and returns the text that is answered as its result
+    fn execute_command(&mut self, command: &Command) -> Result<String> {
+        match command.words()[..] {
+            ["stats"] => Ok(format!(
+                "{} chunks loaded, {} generated and {} meshed in total, {:.1} MiB of voxel data, {} queued for generation, {} for meshing",
+                self.chunk_manager.chunks.len(),
+                self.chunk_manager.generated_chunks,
+                self.chunk_manager.meshed_chunks,
+                self.chunk_manager.total_voxel_data_size as f64 / (1024.0 * 1024.0),
+                self.chunk_manager.location_queue.len(),
+                self.chunk_manager.chunk_mesh_queue.len(),
+            )),
+            ["save-all"] => {
+                self.save_settings();
+                let written_regions = self.chunk_manager.save_world()?;
+                Ok(format!("Saved {written_regions} modified regions of the world"))
+            }
+            // The world is saved when the event loop is destroyed
+            ["stop"] => Ok("Stopping".to_owned()),
+            [] | ["help"] => Ok("Commands: stats, save-all, stop".to_owned()),
+            _ => bail!("unknown command {:?}, see `help`", command.line),
+        }
+    }
+
+    fn save_settings(&mut self) {
+        self.settings.overlay_theme = self.egui_interface.theme;
+        self.settings.accessibility = self.egui_interface.accessibility;
+        self.settings.power_saving = self.egui_interface.power_saving_mode;
+        self.settings.graphics.render_distance = self.egui_interface.render_distance;
+        self.settings.cvars = cvars::changed_values();
+
+        if let Err(err) = self.settings.save(&self.settings_path) {
+            warn!("Failed to save settings: {err:#}");
+        }
+        if let Err(err) = self.portals.save(&self.portals_path) {
+            warn!("Failed to save portals: {err:#}");
+        }
+    }
+
+    /// Renders one more frame and saves it as the thumbnail of the world, so that it can be recognized in a world list
+    fn save_thumbnail(&mut self) {
+        let Some(path) = self
+            .chunk_manager
+            .world_path()
+            .map(|world_path| world_path.join(THUMBNAIL_FILE_NAME))
+        else {
+            return;
+        };
+
+        self.thumbnail_requested = true;
+        self.render();
+        let Some(capture) = self.thumbnail_capture.take() else {
+            warn!("Could not save a thumbnail of the world, the surface does not support being copied from");
+            return;
+        };
+
+        self.render_ctx.device.poll(wgpu::Maintain::Wait);
+        let result = match capture.readback.try_take() {
+            Some(Ok(data)) => save_thumbnail(&capture, &data, &path),
+            Some(Err(err)) => Err(err),
+            None => Err(anyhow!("the captured frame was not read back")),
+        };
+
+        if let Err(err) = result {
+            warn!("Failed to save the thumbnail of the world: {err:#}");
+        }
+    }
+
+    fn save_world(&mut self) {
+        match self.chunk_manager.save_world() {
+            Ok(written_regions) => info!("Saved {written_regions} modified regions of the world"),
+            Err(err) => warn!("Failed to save the world: {err:#}"),
+        }
+    }
+
+    /// Saves the state of the local player in the world, so that it is restored when the world is opened again
+    fn save_player(&mut self) {
+        let Some(path) = self
+            .chunk_manager
+            .world_path()
+            .map(|world_path| world_path.join(PLAYERS_FILE_NAME))
+        else {
+            return;
+        };
+
+        let (yaw, pitch) = self.camera.orientation();
+        let player = PlayerData {
+            position: self.camera.position.into(),
+            orientation: [yaw.0, pitch.0],
+            dimension: self.chunk_manager.dimension_names()[self.chunk_manager.active_dimension()].clone(),
+            game_mode: self.egui_interface.game_mode,
+            inventory: self.inventory.stacks().collect(),
+        };
+        self.players
+            .players
+            .insert(self.player_id.clone(), player);
+
+        if let Err(err) = self.players.save(&path) {
+            warn!("Failed to save the players of the world: {err:#}");
+        }
+    }
+
     fn handle_resize(&mut self, event: &Event<()>) -> bool {
         match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::Resized(new_size) => {
-                    self.render_ctx.resize(new_size);
-                    self.camera
-                        .resize(new_size.width, new_size.height);
+                    self.pending_resize = Some(*new_size);
                     true
                 }
                 WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                    self.render_ctx.resize(new_inner_size);
-                    self.camera
-                        .resize(new_inner_size.width, new_inner_size.height);
+                    self.pending_resize = Some(**new_inner_size);
                     true
                 }
                 _ => false,
@@ -250,6 +1489,22 @@ impl Engine {
             _ => false,
         }
     }
+
+    /// Reconfigures the surface for the last size of the window, so that an interactive resize only reconfigures it once per frame
+    fn apply_pending_resize(&mut self) {
+        let Some(new_size) = self.pending_resize.take() else {
+            return;
+        };
+
+        // A minimized window has a size of zero, which cannot be used for the surface
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
+
+        self.render_ctx.resize(&new_size);
+        self.camera
+            .resize(new_size.width, new_size.height);
+    }
 }
 
 fn get_window_center_position(window: &Window) -> PhysicalPosition<u32> {
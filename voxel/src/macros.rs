@@ -1,4 +1,4 @@
-//! Shorthand for matching winit keyboard press events
+//! Shorthand for matching winit events and for declaring console variables
 //!
 //! # Example
 //! ## Without macro
@@ -7,16 +7,9 @@
 //!
 //! match event {
 //!     Event::WindowEvent {
-//!         event: WindowEvent::KeyboardInput {
-//!             input: KeyboardInput {
-//!                 virtual_keycode: Some(VirtualKeyCode::Escape),
-//!                 state: ElementState::Pressed,
-//!                 ..
-//!             },
-//!             ..
-//!         },
+//!         event: WindowEvent::CloseRequested,
 //!         ..
-//!     } => { println!("Escape was pressed!"); }
+//!     } => { println!("The window was closed!"); }
 //!     _ => {}
 //! }
 //! ```
@@ -26,26 +19,10 @@
 //! let event: Event<()>;
 //!
 //! match event {
-//!     matches_key_press!(VirtualKeyCode::Escape) => { println!("Escape was pressed!"); }
+//!     close_requested!() => { println!("The window was closed!"); }
 //!     _ => {}
 //! }
 //! ```
-macro_rules! key_press {
-    ( $x:path ) => {
-        winit::event::Event::WindowEvent {
-            event: winit::event::WindowEvent::KeyboardInput {
-                input: winit::event::KeyboardInput {
-                    virtual_keycode: Some($x),
-                    state: winit::event::ElementState::Pressed,
-                    ..
-                },
-                ..
-            },
-            ..
-        }
-    };
-}
-
 macro_rules! close_requested {
     () => {
         winit::event::Event::WindowEvent {
@@ -54,3 +31,16 @@ macro_rules! close_requested {
         }
     };
 }
+
+/// Declares a console variable with a name and a default value, see [crate::cvars::CVar]. The static is also added to [crate::cvars::CVARS].
+///
+/// ```ignore
+/// pub(crate) static GRAVITY: CVar = cvar!("physics.gravity", 300.0);
+///
+/// velocity.y -= dt * GRAVITY.get();
+/// ```
+macro_rules! cvar {
+    ( $name:literal, $default:expr ) => {
+        $crate::cvars::CVar::new($name, $default)
+    };
+}
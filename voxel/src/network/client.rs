@@ -0,0 +1,105 @@
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{Context, Result};
+use log::warn;
+
+use crate::network::protocol::{read_frame, write_frame, ClientMessage, ServerMessage};
+use crate::network::recording::{Direction, PacketRecorder};
+
+type SharedRecorder = Arc<Mutex<PacketRecorder>>;
+
+/// Connection of the engine to a server, whose messages are received on a separate thread
+pub struct ServerConnection {
+    stream: TcpStream,
+    messages: Receiver<ServerMessage>,
+    /// Cleared by the receiving thread once the server closed the connection
+    connected: Arc<AtomicBool>,
+    /// Records the frames in both directions, if the connection is recorded
+    recorder: Option<SharedRecorder>,
+}
+
+impl ServerConnection {
+    /// Connects to the server and joins it as the player with the given id. The frames of the connection are recorded into
+    /// the file at the recording path, if there is one, see [crate::network::recording].
+    pub fn connect(address: &str, player_id: &str, recording_path: Option<&Path>) -> Result<Self> {
+        let stream = TcpStream::connect(address).with_context(|| format!("could not connect to {address}"))?;
+        stream.set_nodelay(true)?;
+        let recorder = recording_path
+            .map(|path| PacketRecorder::create(path).map(|recorder| Arc::new(Mutex::new(recorder))))
+            .transpose()?;
+
+        let (sender, messages) = mpsc::channel();
+        let connected = Arc::new(AtomicBool::new(true));
+        let mut reader = stream.try_clone()?;
+        let reader_connected = Arc::clone(&connected);
+        let reader_recorder = recorder.clone();
+        thread::Builder::new()
+            .name("server connection".to_owned())
+            .spawn(move || loop {
+                let message = read_frame(&mut reader).and_then(|frame| {
+                    record(&reader_recorder, Direction::ToClient, &frame);
+                    ServerMessage::decode(&frame)
+                });
+                match message {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        warn!("Lost the connection to the server: {err:#}");
+                        reader_connected.store(false, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            })?;
+
+        let connection = Self {
+            stream,
+            messages,
+            connected,
+            recorder,
+        };
+        connection.send(&ClientMessage::Join {
+            player_id: player_id.to_owned(),
+        });
+
+        Ok(connection)
+    }
+
+    /// Sends a message to the server. Messages are dropped once the connection is lost.
+    pub fn send(&self, message: &ClientMessage) {
+        if !self.is_connected() {
+            return;
+        }
+
+        let frame = message.encode();
+        record(&self.recorder, Direction::ToServer, &frame);
+        if let Err(err) = write_frame(&mut &self.stream, &frame) {
+            warn!("Could not send a message to the server: {err:#}");
+            self.connected.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// All messages that were received since the last call
+    pub fn receive(&self) -> Vec<ServerMessage> {
+        self.messages.try_iter().collect()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+}
+
+fn record(recorder: &Option<SharedRecorder>, direction: Direction, frame: &[u8]) {
+    if let Some(recorder) = recorder {
+        if let Err(err) = recorder.lock().unwrap().record(direction, frame) {
+            warn!("Could not record a frame: {err:#}");
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use cgmath::Vector3;
+use fastrand::Rng;
+use log::info;
+
+use crate::network::protocol::{ClientMessage, ServerMessage};
+use crate::network::recording::read_recording;
+use crate::world::chunk_data::ChunkData;
+use crate::world::location::{ChunkLocation, LocalChunkLocation, WorldLocation};
+use crate::world::voxel_data::{VoxelData, VoxelType};
+
+/// Decodes a frame as a message in both directions. Decoding never panics, and every message that a frame decodes into must
+/// survive encoding, because the client and the server would disagree about it otherwise.
+pub fn fuzz_decode(frame: &[u8]) {
+    if let Ok(message) = ClientMessage::decode(frame) {
+        let encoded = message.encode();
+        let decoded = ClientMessage::decode(&encoded).expect("encoded client messages to decode");
+        assert_eq!(decoded.encode(), encoded, "client message of {frame:?} changed when it was encoded");
+    }
+    if let Ok(message) = ServerMessage::decode(frame) {
+        let encoded = message.encode();
+        let decoded = ServerMessage::decode(&encoded).expect("encoded server messages to decode");
+        assert_eq!(decoded.encode(), encoded, "server message of {frame:?} changed when it was encoded");
+    }
+}
+
+/// Feeds mutations of the frames of the recordings and of one message of every kind to [fuzz_decode], which panics at the
+/// first frame that breaks the protocol. The same seed always tries the same mutations.
+pub fn fuzz_packets(recordings: &[PathBuf], seed: u64, iterations: usize) -> Result<()> {
+    let mut corpus = sample_frames();
+    for path in recordings {
+        corpus.extend(
+            read_recording(path)?
+                .into_iter()
+                .map(|packet| packet.frame),
+        );
+    }
+    info!("Fuzzing {iterations} mutations of {} frames with the seed {seed}", corpus.len());
+
+    let rng = Rng::with_seed(seed);
+    for _ in 0..iterations {
+        let frame = &corpus[rng.usize(..corpus.len())];
+        fuzz_decode(&mutate(&rng, frame));
+    }
+
+    Ok(())
+}
+
+/// A few flipped bits, cut off or inserted bytes and replaced lengths or tags, or random bytes instead of the frame
+fn mutate(rng: &Rng, frame: &[u8]) -> Vec<u8> {
+    let mut frame = frame.to_vec();
+    for _ in 0..rng.usize(1..=4) {
+        match rng.u8(..5) {
+            0 if !frame.is_empty() => {
+                let index = rng.usize(..frame.len());
+                frame[index] ^= 1 << rng.u8(..8);
+            }
+            1 => frame.truncate(rng.usize(..=frame.len())),
+            2 => frame.insert(rng.usize(..=frame.len()), rng.u8(..)),
+            3 if !frame.is_empty() => {
+                let index = rng.usize(..frame.len());
+                frame[index] = [0, 1, 0x7f, 0x80, 0xff][rng.usize(..5)];
+            }
+            _ => frame = (0..rng.usize(..64)).map(|_| rng.u8(..)).collect(),
+        }
+    }
+
+    frame
+}
+
+fn sample_frames() -> Vec<Vec<u8>> {
+    let mut data = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+    data.set_voxel_data(
+        LocalChunkLocation::new_unchecked(Vector3::new(1, 2, 3)),
+        VoxelData::new(VoxelType::Air),
+    );
+    let location = WorldLocation(Vector3::new(-5, 64, 17));
+
+    let client_messages = [
+        ClientMessage::Join {
+            player_id: "player".to_owned(),
+        },
+        ClientMessage::RequestChunk {
+            dimension: "overworld".to_owned(),
+            location: ChunkLocation::new(Vector3::new(-1, 2, 0)),
+        },
+        ClientMessage::SetVoxel {
+            dimension: "overworld".to_owned(),
+            location,
+            voxel_data: VoxelData::new(VoxelType::Log),
+        },
+        ClientMessage::PlayerPosition {
+            dimension: "nether".to_owned(),
+            position: Vector3::new(0.5, 64.0, -2.25),
+        },
+    ];
+    let server_messages = [
+        ServerMessage::ChunkData {
+            dimension: "overworld".to_owned(),
+            location: ChunkLocation::new(Vector3::new(-1, 2, 0)),
+            data,
+            outside_voxels: vec![(location, VoxelType::Leaves)],
+            is_saved: true,
+        },
+        ServerMessage::VoxelChanged {
+            dimension: "overworld".to_owned(),
+            location,
+            voxel_data: VoxelData::new(VoxelType::Glass),
+        },
+        ServerMessage::PlayerPosition {
+            player_id: "player".to_owned(),
+            dimension: "nether".to_owned(),
+            position: Vector3::new(0.5, 64.0, -2.25),
+        },
+        ServerMessage::PlayerLeft {
+            player_id: "player".to_owned(),
+        },
+    ];
+
+    client_messages
+        .iter()
+        .map(ClientMessage::encode)
+        .chain(server_messages.iter().map(ServerMessage::encode))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::fuzz::fuzz_packets;
+
+    #[test]
+    fn test_mutated_frames_do_not_break_the_protocol() {
+        fuzz_packets(&[], 0, 20_000).unwrap();
+    }
+}
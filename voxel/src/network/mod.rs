@@ -0,0 +1,5 @@
+pub mod client;
+pub mod fuzz;
+pub mod protocol;
+pub mod recording;
+pub mod server;
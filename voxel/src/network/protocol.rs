@@ -0,0 +1,365 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, ensure, Context, Result};
+use cgmath::Vector3;
+use enum_map::Enum;
+
+use crate::world::chunk_data::ChunkData;
+use crate::world::location::{ChunkLocation, WorldLocation};
+use crate::world::storage::{decode_chunk, encode_chunk};
+use crate::world::voxel_data::{VoxelData, VoxelType};
+
+/// Frames that are larger than this are rejected, the largest frames are chunks of alternating voxels
+const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Message from a client to the server
+#[derive(Clone, Debug)]
+pub enum ClientMessage {
+    /// First message of every client, with the id under which the player is known to the other clients
+    Join {
+        player_id: String,
+    },
+    /// Asks for the data of a chunk, which the server answers with [ServerMessage::ChunkData]
+    RequestChunk {
+        dimension: String,
+        location: ChunkLocation,
+    },
+    /// A voxel that was edited by the player
+    SetVoxel {
+        dimension: String,
+        location: WorldLocation,
+        voxel_data: VoxelData,
+    },
+    PlayerPosition {
+        dimension: String,
+        position: Vector3<f32>,
+    },
+}
+
+/// Message from the server to a client
+#[derive(Clone)]
+pub enum ServerMessage {
+    /// Data of a requested chunk, with the same meaning as the fields of [crate::world::worldgen::GeneratedChunk]
+    ChunkData {
+        dimension: String,
+        location: ChunkLocation,
+        data: ChunkData,
+        outside_voxels: Vec<(WorldLocation, VoxelType)>,
+        /// Whether the data was loaded from the saved world, which already contains the structures of its neighbors
+        is_saved: bool,
+    },
+    /// A voxel that was edited by another player
+    VoxelChanged {
+        dimension: String,
+        location: WorldLocation,
+        voxel_data: VoxelData,
+    },
+    PlayerPosition {
+        player_id: String,
+        dimension: String,
+        position: Vector3<f32>,
+    },
+    PlayerLeft {
+        player_id: String,
+    },
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            ClientMessage::Join { player_id } => {
+                out.push(0);
+                put_str(&mut out, player_id);
+            }
+            ClientMessage::RequestChunk { dimension, location } => {
+                out.push(1);
+                put_str(&mut out, dimension);
+                put_vector(&mut out, **location);
+            }
+            ClientMessage::SetVoxel {
+                dimension,
+                location,
+                voxel_data,
+            } => {
+                out.push(2);
+                put_str(&mut out, dimension);
+                put_vector(&mut out, location.0);
+                out.push(voxel_data.ty.into_usize() as u8);
+            }
+            ClientMessage::PlayerPosition { dimension, position } => {
+                out.push(3);
+                put_str(&mut out, dimension);
+                put_position(&mut out, *position);
+            }
+        }
+
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        let message = match reader.u8()? {
+            0 => ClientMessage::Join { player_id: reader.str()? },
+            1 => ClientMessage::RequestChunk {
+                dimension: reader.str()?,
+                location: ChunkLocation::new(reader.vector()?),
+            },
+            2 => ClientMessage::SetVoxel {
+                dimension: reader.str()?,
+                location: WorldLocation(reader.vector()?),
+                voxel_data: VoxelData::new(reader.voxel_type()?),
+            },
+            3 => ClientMessage::PlayerPosition {
+                dimension: reader.str()?,
+                position: reader.position()?,
+            },
+            tag => bail!("unknown client message {tag}"),
+        };
+        reader.finish()?;
+
+        Ok(message)
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            ServerMessage::ChunkData {
+                dimension,
+                location,
+                data,
+                outside_voxels,
+                is_saved,
+            } => {
+                out.push(0);
+                put_str(&mut out, dimension);
+                put_vector(&mut out, **location);
+                out.push(*is_saved as u8);
+                out.extend_from_slice(&(outside_voxels.len() as u32).to_le_bytes());
+                for (voxel_location, voxel_type) in outside_voxels {
+                    put_vector(&mut out, voxel_location.0);
+                    out.push(voxel_type.into_usize() as u8);
+                }
+                // The chunk takes up the rest of the frame
+                encode_chunk(data, &mut out);
+            }
+            ServerMessage::VoxelChanged {
+                dimension,
+                location,
+                voxel_data,
+            } => {
+                out.push(1);
+                put_str(&mut out, dimension);
+                put_vector(&mut out, location.0);
+                out.push(voxel_data.ty.into_usize() as u8);
+            }
+            ServerMessage::PlayerPosition {
+                player_id,
+                dimension,
+                position,
+            } => {
+                out.push(2);
+                put_str(&mut out, player_id);
+                put_str(&mut out, dimension);
+                put_position(&mut out, *position);
+            }
+            ServerMessage::PlayerLeft { player_id } => {
+                out.push(3);
+                put_str(&mut out, player_id);
+            }
+        }
+
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        let message = match reader.u8()? {
+            0 => {
+                let dimension = reader.str()?;
+                let location = ChunkLocation::new(reader.vector()?);
+                let is_saved = reader.u8()? != 0;
+                let num_outside_voxels = reader.u32()? as usize;
+                let outside_voxels = (0..num_outside_voxels)
+                    .map(|_| -> Result<_> { Ok((WorldLocation(reader.vector()?), reader.voxel_type()?)) })
+                    .collect::<Result<_>>()?;
+                let data = decode_chunk(reader.rest()).context("invalid chunk data")?;
+
+                ServerMessage::ChunkData {
+                    dimension,
+                    location,
+                    data,
+                    outside_voxels,
+                    is_saved,
+                }
+            }
+            1 => ServerMessage::VoxelChanged {
+                dimension: reader.str()?,
+                location: WorldLocation(reader.vector()?),
+                voxel_data: VoxelData::new(reader.voxel_type()?),
+            },
+            2 => ServerMessage::PlayerPosition {
+                player_id: reader.str()?,
+                dimension: reader.str()?,
+                position: reader.position()?,
+            },
+            3 => ServerMessage::PlayerLeft { player_id: reader.str()? },
+            tag => bail!("unknown server message {tag}"),
+        };
+        reader.finish()?;
+
+        Ok(message)
+    }
+}
+
+/// Writes an encoded message, prefixed with its length
+pub fn write_frame(writer: &mut impl Write, message: &[u8]) -> Result<()> {
+    ensure!(message.len() <= MAX_FRAME_SIZE, "message of {} bytes is too large", message.len());
+    writer.write_all(&(message.len() as u32).to_le_bytes())?;
+    writer.write_all(message)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Reads the next message that was written with [write_frame]
+pub fn read_frame(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length) as usize;
+    ensure!(length <= MAX_FRAME_SIZE, "message of {length} bytes is too large");
+
+    let mut message = vec![0; length];
+    reader.read_exact(&mut message)?;
+
+    Ok(message)
+}
+
+fn put_str(out: &mut Vec<u8>, string: &str) {
+    out.extend_from_slice(&(string.len() as u16).to_le_bytes());
+    out.extend_from_slice(string.as_bytes());
+}
+
+fn put_vector(out: &mut Vec<u8>, vector: Vector3<i32>) {
+    for c in [vector.x, vector.y, vector.z] {
+        out.extend_from_slice(&c.to_le_bytes());
+    }
+}
+
+fn put_position(out: &mut Vec<u8>, position: Vector3<f32>) {
+    for c in [position.x, position.y, position.z] {
+        out.extend_from_slice(&c.to_le_bytes());
+    }
+}
+
+/// Reads the fields of a message in the order in which they were written
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        ensure!(self.0.len() >= N, "truncated message");
+        let (bytes, rest) = self.0.split_at(N);
+        self.0 = rest;
+
+        Ok(bytes.try_into().expect("N bytes to be split off"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn str(&mut self) -> Result<String> {
+        let length = u16::from_le_bytes(self.bytes()?) as usize;
+        ensure!(self.0.len() >= length, "truncated string");
+        let (bytes, rest) = self.0.split_at(length);
+        self.0 = rest;
+
+        String::from_utf8(bytes.to_vec()).context("invalid string")
+    }
+
+    fn vector(&mut self) -> Result<Vector3<i32>> {
+        Ok(Vector3::new(
+            i32::from_le_bytes(self.bytes()?),
+            i32::from_le_bytes(self.bytes()?),
+            i32::from_le_bytes(self.bytes()?),
+        ))
+    }
+
+    fn position(&mut self) -> Result<Vector3<f32>> {
+        Ok(Vector3::new(
+            f32::from_le_bytes(self.bytes()?),
+            f32::from_le_bytes(self.bytes()?),
+            f32::from_le_bytes(self.bytes()?),
+        ))
+    }
+
+    fn voxel_type(&mut self) -> Result<VoxelType> {
+        let byte = self.u8()? as usize;
+        ensure!(byte < VoxelType::LENGTH, "invalid voxel type {byte}");
+
+        Ok(VoxelType::from_usize(byte))
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.0)
+    }
+
+    fn finish(&self) -> Result<()> {
+        ensure!(self.0.is_empty(), "{} unexpected bytes at the end of the message", self.0.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::network::protocol::{read_frame, write_frame, ClientMessage, ServerMessage};
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::location::{ChunkLocation, LocalChunkLocation, WorldLocation};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    #[test]
+    fn test_messages_survive_encoding() {
+        let mut data = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+        data.set_voxel_data(
+            LocalChunkLocation::new_unchecked(Vector3::new(3, 4, 5)),
+            VoxelData::new(VoxelType::Glass),
+        );
+
+        let server_messages = [
+            ServerMessage::ChunkData {
+                dimension: "overworld".to_owned(),
+                location: ChunkLocation::new(Vector3::new(-1, 2, 3)),
+                data,
+                outside_voxels: vec![(WorldLocation(Vector3::new(31, 40, -2)), VoxelType::Leaves)],
+                is_saved: true,
+            },
+            ServerMessage::PlayerLeft {
+                player_id: "player".to_owned(),
+            },
+        ];
+        for message in server_messages {
+            let encoded = message.encode();
+            assert_eq!(ServerMessage::decode(&encoded).unwrap().encode(), encoded);
+        }
+
+        let client_message = ClientMessage::SetVoxel {
+            dimension: "nether".to_owned(),
+            location: WorldLocation(Vector3::new(1, -70, 9)),
+            voxel_data: VoxelData::new(VoxelType::Log),
+        };
+        let mut frames = Vec::new();
+        write_frame(&mut frames, &client_message.encode()).unwrap();
+        let frame = read_frame(&mut frames.as_slice()).unwrap();
+        assert_eq!(ClientMessage::decode(&frame).unwrap().encode(), client_message.encode());
+
+        assert!(ClientMessage::decode(&frame[..frame.len() - 1]).is_err());
+    }
+}
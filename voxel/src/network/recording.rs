@@ -0,0 +1,299 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, ensure, Context, Result};
+use log::{info, warn};
+
+use crate::network::protocol::{read_frame, write_frame, ClientMessage};
+
+/// Start of every recording, followed by its packets
+const MAGIC: &[u8; 8] = b"VOXPKTS1";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    ToServer,
+    ToClient,
+}
+
+/// Frame of a recorded connection, with the time since the recording was started
+#[derive(Clone, Debug)]
+pub struct RecordedPacket {
+    pub direction: Direction,
+    pub time: Duration,
+    pub frame: Vec<u8>,
+}
+
+/// Writes the frames of a connection into a file before they are decoded, so that they can be replayed or fuzzed later
+pub struct PacketRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl PacketRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("could not create the recording {path:?}"))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    /// Appends a frame, which is flushed right away so that the recording of a crashed session is complete
+    pub fn record(&mut self, direction: Direction, frame: &[u8]) -> Result<()> {
+        self.writer.write_all(&[direction as u8])?;
+        self.writer
+            .write_all(&(self.start.elapsed().as_micros() as u64).to_le_bytes())?;
+        write_frame(&mut self.writer, frame)
+    }
+}
+
+/// Reads the packets of a recording. A packet that was cut off while it was written ends the recording.
+pub fn read_recording(path: &Path) -> Result<Vec<RecordedPacket>> {
+    let file = File::open(path).with_context(|| format!("could not open the recording {path:?}"))?;
+    let mut reader = BufReader::new(file);
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    ensure!(&magic == MAGIC, "{path:?} is not a packet recording");
+
+    let mut packets = Vec::new();
+    loop {
+        match read_packet(&mut reader) {
+            Ok(Some(packet)) => packets.push(packet),
+            Ok(None) => break,
+            Err(err) if is_truncated(&err) => {
+                warn!("The recording {path:?} ends with a truncated packet");
+                break;
+            }
+            Err(err) => return Err(err.context(format!("invalid packet {} in {path:?}", packets.len()))),
+        }
+    }
+
+    Ok(packets)
+}
+
+/// Plays the recorded frames of the server to the next client that connects to the listener, so that a session can be
+/// reproduced without the server. Timed replays keep the recorded pace, others send all frames right away. Apart from the
+/// join, the frames of the client are ignored, and the connection stays open after the last frame until the client leaves.
+pub fn replay_to_client(listener: &TcpListener, packets: &[RecordedPacket], timed: bool) -> Result<()> {
+    let (stream, address) = listener.accept()?;
+    stream.set_nodelay(true)?;
+
+    let mut reader = stream.try_clone()?;
+    let join = ClientMessage::decode(&read_frame(&mut reader)?)?;
+    ensure!(matches!(join, ClientMessage::Join { .. }), "{address} did not join first");
+    // The client is never blocked by a full socket while the frames are replayed
+    thread::Builder::new()
+        .name("replay receiver".to_owned())
+        .spawn(move || while read_frame(&mut reader).is_ok() {})?;
+
+    let frames: Vec<_> = packets
+        .iter()
+        .filter(|packet| packet.direction == Direction::ToClient)
+        .collect();
+    info!("Replaying {} recorded frames to {address}", frames.len());
+
+    let start = Instant::now();
+    for packet in frames {
+        if timed {
+            if let Some(wait) = packet.time.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        write_frame(&mut &stream, &packet.frame)?;
+    }
+
+    Ok(())
+}
+
+/// Replays the recording in the background at its recorded pace to the first client that connects to the returned local
+/// address, see [replay_to_client]
+pub fn serve_recording(path: &Path) -> Result<String> {
+    let packets = read_recording(path)?;
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?.to_string();
+
+    thread::Builder::new()
+        .name("replay".to_owned())
+        .spawn(move || {
+            if let Err(err) = replay_to_client(&listener, &packets, true) {
+                warn!("Could not replay the recording: {err:#}");
+            }
+        })?;
+
+    Ok(address)
+}
+
+/// The next packet, or None at the end of the recording
+fn read_packet(reader: &mut impl Read) -> Result<Option<RecordedPacket>> {
+    let mut direction = [0];
+    if reader.read(&mut direction)? == 0 {
+        return Ok(None);
+    }
+    let direction = match direction[0] {
+        0 => Direction::ToServer,
+        1 => Direction::ToClient,
+        byte => bail!("unknown direction {byte}"),
+    };
+
+    let mut time = [0; 8];
+    reader.read_exact(&mut time)?;
+    let frame = read_frame(reader)?;
+
+    Ok(Some(RecordedPacket {
+        direction,
+        time: Duration::from_micros(u64::from_le_bytes(time)),
+        frame,
+    }))
+}
+
+fn is_truncated(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == ErrorKind::UnexpectedEof)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use cgmath::Vector3;
+
+    use crate::network::client::ServerConnection;
+    use crate::network::protocol::{read_frame, write_frame, ClientMessage, ServerMessage};
+    use crate::network::recording::{read_recording, replay_to_client, Direction, PacketRecorder};
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::location::{ChunkLocation, WorldLocation};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    fn recording_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("voxel-recording-{name}-test-{}", std::process::id()))
+    }
+
+    fn server_messages() -> Vec<ServerMessage> {
+        vec![
+            ServerMessage::ChunkData {
+                dimension: "overworld".to_owned(),
+                location: ChunkLocation::new(Vector3::new(0, -1, 2)),
+                data: ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone)),
+                outside_voxels: vec![(WorldLocation(Vector3::new(32, -1, 64)), VoxelType::Leaves)],
+                is_saved: false,
+            },
+            ServerMessage::VoxelChanged {
+                dimension: "overworld".to_owned(),
+                location: WorldLocation(Vector3::new(1, 2, 3)),
+                voxel_data: VoxelData::new(VoxelType::Glass),
+            },
+            ServerMessage::PlayerPosition {
+                player_id: "other".to_owned(),
+                dimension: "nether".to_owned(),
+                position: Vector3::new(0.5, 70.0, -3.25),
+            },
+            ServerMessage::PlayerLeft {
+                player_id: "other".to_owned(),
+            },
+        ]
+    }
+
+    /// Encodings of the next `count` messages that the connection receives
+    fn receive_frames(connection: &ServerConnection, count: usize) -> Vec<Vec<u8>> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut frames = Vec::new();
+        while frames.len() < count {
+            assert!(Instant::now() < deadline, "received only {} of {count} messages", frames.len());
+            frames.extend(
+                connection
+                    .receive()
+                    .iter()
+                    .map(ServerMessage::encode),
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        frames
+    }
+
+    #[test]
+    fn test_recordings_end_at_a_truncated_packet() {
+        let path = recording_path("truncated");
+        let mut recorder = PacketRecorder::create(&path).unwrap();
+        recorder
+            .record(Direction::ToServer, b"join")
+            .unwrap();
+        recorder
+            .record(Direction::ToClient, b"chunk")
+            .unwrap();
+        drop(recorder);
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[1, 0, 0]).unwrap();
+        drop(file);
+
+        let packets = read_recording(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let packets: Vec<_> = packets
+            .iter()
+            .map(|packet| (packet.direction, packet.frame.as_slice()))
+            .collect();
+        assert_eq!(
+            packets,
+            [(Direction::ToServer, b"join".as_slice()), (Direction::ToClient, b"chunk")]
+        );
+    }
+
+    #[test]
+    fn test_replayed_sessions_receive_the_recorded_messages() {
+        let messages = server_messages();
+        let sent: Vec<_> = messages
+            .iter()
+            .map(ServerMessage::encode)
+            .collect();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let join = ClientMessage::decode(&read_frame(&mut stream).unwrap()).unwrap();
+            assert!(matches!(join, ClientMessage::Join { player_id } if player_id == "recorder"));
+            for message in messages {
+                write_frame(&mut stream, &message.encode()).unwrap();
+            }
+        });
+
+        let path = recording_path("session");
+        let connection = ServerConnection::connect(&address, "recorder", Some(&path)).unwrap();
+        assert_eq!(receive_frames(&connection, sent.len()), sent);
+        server.join().unwrap();
+        drop(connection);
+
+        let packets = read_recording(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(packets[0].direction, Direction::ToServer);
+        let recorded: Vec<_> = packets
+            .iter()
+            .filter(|packet| packet.direction == Direction::ToClient)
+            .map(|packet| packet.frame.clone())
+            .collect();
+        assert_eq!(recorded, sent);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let replay = thread::spawn(move || replay_to_client(&listener, &packets, false));
+        let connection = ServerConnection::connect(&address, "replayer", None).unwrap();
+        assert_eq!(receive_frames(&connection, sent.len()), sent);
+        replay.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use log::{info, warn};
+
+use crate::console::{Command, CommandBus};
+use crate::network::protocol::{read_frame, write_frame, ClientMessage, ServerMessage};
+use crate::world::chunk_data::ChunkData;
+use crate::world::location::{ChunkLocation, WorldLocation};
+use crate::world::storage::WorldStorage;
+use crate::world::voxel_data::VoxelType;
+use crate::world::world_config::{WorldConfig, WORLD_CONFIG_FILE_NAME};
+use crate::world::world_edits::WorldEdits;
+use crate::world::worldgen::{GeneratedChunk, WorldGenerator};
+use crate::world::worldgen_preset::WorldgenPreset;
+
+/// Settings of a server, which generates the chunks for all connected engines and relays their edits and positions
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Address on which the server accepts connections, e.g. `0.0.0.0:4790`
+    pub address: String,
+    pub worldgen_preset_path: PathBuf,
+    /// Directory of a saved world whose chunks are served instead of generating them, if any
+    pub world_path: Option<PathBuf>,
+    /// Config of the generated world, unless the served world already has one
+    pub world_config: WorldConfig,
+    /// Read admin commands from the standard input, see [execute_command]
+    pub console: bool,
+    /// Address on which admin commands are accepted line by line, e.g. `127.0.0.1:4791`
+    pub admin_address: Option<String>,
+}
+
+/// A dimension of the served world. Edits are kept in memory until they are written to the saved world by the `save-all` command.
+struct ServerDimension {
+    generator: WorldGenerator,
+    storage: Option<Mutex<WorldStorage>>,
+    edits: Mutex<WorldEdits>,
+    /// Number of chunks that were generated and loaded from the saved world for the clients
+    generated_chunks: AtomicU64,
+    loaded_chunks: AtomicU64,
+    /// Total time in microseconds that was spent generating chunks
+    generation_time_us: AtomicU64,
+}
+
+struct ServerState {
+    dimensions: HashMap<String, ServerDimension>,
+    /// Connection of every client by its player id, through which messages are sent to it.
+    /// The connections are locked while a message is written, so that messages of different threads are not interleaved.
+    clients: Mutex<HashMap<String, ClientWriter>>,
+    /// Set by the `stop` command, after which no more connections are accepted
+    stopping: AtomicBool,
+    /// Address on which the listener of the clients can be reached from this machine, to wake it up when the server stops
+    wake_address: SocketAddr,
+}
+
+type ClientWriter = Arc<Mutex<TcpStream>>;
+
+/// Serves the overworld and the nether to all engines that connect to the address, until the listener fails or the server is stopped
+pub fn run_server(config: ServerConfig) -> Result<()> {
+    let preset = WorldgenPreset::load(&config.worldgen_preset_path);
+    let world_config = match &config.world_path {
+        Some(world_path) => WorldConfig::load_or_create(&world_path.join(WORLD_CONFIG_FILE_NAME), config.world_config),
+        None => config.world_config,
+    };
+
+    let listener = TcpListener::bind(&config.address).with_context(|| format!("could not listen on {}", config.address))?;
+    let mut wake_address = listener.local_addr()?;
+    if wake_address.ip().is_unspecified() {
+        wake_address.set_ip(if wake_address.is_ipv4() {
+            Ipv4Addr::LOCALHOST.into()
+        } else {
+            Ipv6Addr::LOCALHOST.into()
+        });
+    }
+
+    let mut dimensions = HashMap::new();
+    for (name, dimension_config) in [("overworld", world_config.clone()), ("nether", world_config.nether())] {
+        let storage = match &config.world_path {
+            Some(world_path) => Some(Mutex::new(WorldStorage::open(world_path.join(name))?)),
+            None => None,
+        };
+        dimensions.insert(
+            name.to_owned(),
+            ServerDimension {
+                generator: WorldGenerator::new(dimension_config, preset.clone()),
+                storage,
+                edits: Mutex::new(WorldEdits::new()),
+                generated_chunks: AtomicU64::new(0),
+                loaded_chunks: AtomicU64::new(0),
+                generation_time_us: AtomicU64::new(0),
+            },
+        );
+    }
+    let state = Arc::new(ServerState {
+        dimensions,
+        clients: Mutex::new(HashMap::new()),
+        stopping: AtomicBool::new(false),
+        wake_address,
+    });
+
+    if config.console || config.admin_address.is_some() {
+        let console = CommandBus::new();
+        if config.console {
+            console.read_stdin()?;
+        }
+        if let Some(admin_address) = &config.admin_address {
+            console.listen(admin_address)?;
+        }
+
+        let state = Arc::clone(&state);
+        thread::Builder::new()
+            .name("server commands".to_owned())
+            .spawn(move || execute_console_commands(&state, &console))?;
+    }
+
+    info!("Serving the world on {}", config.address);
+    for stream in listener.incoming() {
+        if state.stopping.load(Ordering::Relaxed) {
+            break;
+        }
+        let stream = stream?;
+        let state = Arc::clone(&state);
+        thread::Builder::new()
+            .name("client connection".to_owned())
+            .spawn(move || {
+                let address = stream.peer_addr().ok();
+                if let Err(err) = handle_client(&state, stream) {
+                    warn!("Closed the connection to {address:?}: {err:#}");
+                }
+            })?;
+    }
+
+    info!("Stopped the server");
+    Ok(())
+}
+
+/// Receives the messages of a client until it disconnects
+fn handle_client(state: &Arc<ServerState>, mut reader: TcpStream) -> Result<()> {
+    reader.set_nodelay(true)?;
+    let writer = Arc::new(Mutex::new(reader.try_clone()?));
+
+    let ClientMessage::Join { player_id } = ClientMessage::decode(&read_frame(&mut reader)?)? else {
+        bail!("the client did not join first");
+    };
+    // The leave of a second client with the same id would remove the connection of the first one
+    match state
+        .clients
+        .lock()
+        .unwrap()
+        .entry(player_id.clone())
+    {
+        Entry::Occupied(_) => bail!("player {player_id} is already connected"),
+        Entry::Vacant(entry) => entry.insert(Arc::clone(&writer)),
+    };
+    info!("Player {player_id} joined");
+
+    let result = receive_client_messages(state, &mut reader, &writer, &player_id);
+
+    info!("Player {player_id} left");
+    state.clients.lock().unwrap().remove(&player_id);
+    broadcast(
+        state,
+        &player_id,
+        &ServerMessage::PlayerLeft {
+            player_id: player_id.clone(),
+        },
+    );
+
+    result
+}
+
+fn receive_client_messages(state: &Arc<ServerState>, reader: &mut TcpStream, writer: &ClientWriter, player_id: &str) -> Result<()> {
+    loop {
+        match ClientMessage::decode(&read_frame(reader)?)? {
+            ClientMessage::Join { .. } => bail!("the client joined twice"),
+            ClientMessage::RequestChunk { dimension, location } => {
+                // Chunks are generated on all cores, so that a client that streams many chunks does not stall the others
+                let (state, writer) = (Arc::clone(state), Arc::clone(writer));
+                rayon::spawn(move || {
+                    let Some(server_dimension) = state.dimensions.get(&dimension) else {
+                        warn!("Player requested a chunk of the unknown dimension {dimension:?}");
+                        return;
+                    };
+                    let (data, outside_voxels, is_saved) = server_dimension.chunk(location);
+                    send(
+                        &writer,
+                        &ServerMessage::ChunkData {
+                            dimension,
+                            location,
+                            data,
+                            outside_voxels,
+                            is_saved,
+                        },
+                    );
+                });
+            }
+            ClientMessage::SetVoxel {
+                dimension,
+                location,
+                voxel_data,
+            } => {
+                let Some(server_dimension) = state.dimensions.get(&dimension) else {
+                    bail!("edit in the unknown dimension {dimension:?}");
+                };
+                let (chunk_location, local_location) = location.separate();
+                server_dimension
+                    .edits
+                    .lock()
+                    .unwrap()
+                    .record(chunk_location, local_location, voxel_data);
+
+                broadcast(
+                    state,
+                    player_id,
+                    &ServerMessage::VoxelChanged {
+                        dimension,
+                        location,
+                        voxel_data,
+                    },
+                );
+            }
+            ClientMessage::PlayerPosition { dimension, position } => broadcast(
+                state,
+                player_id,
+                &ServerMessage::PlayerPosition {
+                    player_id: player_id.to_owned(),
+                    dimension,
+                    position,
+                },
+            ),
+        }
+    }
+}
+
+impl ServerDimension {
+    /// Data of a chunk including the edits of all players, together with its structure voxels in other chunks and whether it was saved
+    fn chunk(&self, location: ChunkLocation) -> (ChunkData, Vec<(WorldLocation, VoxelType)>, bool) {
+        let saved = self.storage.as_ref().and_then(|storage| {
+            storage
+                .lock()
+                .unwrap()
+                .load_chunk(location)
+                .unwrap_or_else(|err| {
+                    warn!("Could not load the chunk at {location:?}, generating it instead: {err:#}");
+                    None
+                })
+        });
+        let (mut data, outside_voxels, is_saved) = match saved {
+            Some(data) => {
+                self.loaded_chunks.fetch_add(1, Ordering::Relaxed);
+                (data, self.generator.outside_structure_voxels(location), true)
+            }
+            None => {
+                let start = Instant::now();
+                let GeneratedChunk { data, outside_voxels } = self.generator.generate_chunk(location);
+                self.generation_time_us
+                    .fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+                self.generated_chunks
+                    .fetch_add(1, Ordering::Relaxed);
+                (data, outside_voxels, false)
+            }
+        };
+
+        if let Some(diff) = self.edits.lock().unwrap().diff(location) {
+            data.apply(&diff);
+            data.compress();
+        }
+
+        (data, outside_voxels, is_saved)
+    }
+}
+
+/// Executes the admin commands of the bus one after another, until the `stop` command
+fn execute_console_commands(state: &ServerState, console: &CommandBus) {
+    loop {
+        let command = console.recv();
+        let stop = command.words() == ["stop"];
+        let output = execute_command(state, &command).unwrap_or_else(|err| format!("{err:#}"));
+        command.reply(output);
+        if stop {
+            break;
+        }
+    }
+}
+
+/// Runs an admin command of the console or the admin port and returns the text that is answered as its result
+fn execute_command(state: &ServerState, command: &Command) -> Result<String> {
+    match command.words()[..] {
+        ["players"] => {
+            let players = state
+                .clients
+                .lock()
+                .unwrap()
+                .keys()
+                .sorted()
+                .join(", ");
+            Ok(format!("Players: {players}"))
+        }
+        ["kick", player_id] => {
+            let Some(writer) = state
+                .clients
+                .lock()
+                .unwrap()
+                .get(player_id)
+                .cloned()
+            else {
+                bail!("player {player_id} is not connected");
+            };
+            // The connection of the client fails to receive, after which it is closed like any other disconnect
+            writer
+                .lock()
+                .unwrap()
+                .shutdown(Shutdown::Both)
+                .context("could not close the connection")?;
+            Ok(format!("Kicked {player_id}"))
+        }
+        ["save-all"] => save_all(state),
+        ["stats"] => Ok(state
+            .dimensions
+            .iter()
+            .sorted_by_key(|(name, _)| *name)
+            .map(|(name, dimension)| {
+                let generated_chunks = dimension.generated_chunks.load(Ordering::Relaxed);
+                let generation_time_ms = dimension
+                    .generation_time_us
+                    .load(Ordering::Relaxed) as f64
+                    / 1000.0;
+                format!(
+                    "{name}: {generated_chunks} chunks generated ({:.2}ms each), {} loaded, {} edited",
+                    generation_time_ms / generated_chunks.max(1) as f64,
+                    dimension.loaded_chunks.load(Ordering::Relaxed),
+                    dimension
+                        .edits
+                        .lock()
+                        .unwrap()
+                        .chunk_locations()
+                        .count(),
+                )
+            })
+            .join("\n")),
+        ["stop"] => {
+            // A server without a saved world has nothing to save
+            let mut output = String::new();
+            if state
+                .dimensions
+                .values()
+                .all(|dimension| dimension.storage.is_some())
+            {
+                output = save_all(state)? + "\n";
+            }
+
+            state.stopping.store(true, Ordering::Relaxed);
+            for (_, writer) in client_writers(state, None) {
+                let _ = writer.lock().unwrap().shutdown(Shutdown::Both);
+            }
+            // Wakes up the listener, which checks whether the server is stopping after every accepted connection
+            let _ = TcpStream::connect(state.wake_address);
+
+            Ok(output + "Stopping the server")
+        }
+        [] | ["help"] => Ok("Commands: players, kick <player id>, save-all, stats, stop".to_owned()),
+        _ => bail!("unknown command {:?}, see `help`", command.line),
+    }
+}
+
+/// Writes the chunks with edits of all dimensions to the saved world and returns how many regions were written.
+/// The edits stay in memory, so that they are applied again to chunks that are generated because they could not be loaded.
+fn save_all(state: &ServerState) -> Result<String> {
+    let mut saved_regions = 0;
+    for (name, dimension) in &state.dimensions {
+        let Some(storage) = &dimension.storage else {
+            bail!("the served world is not saved to a directory");
+        };
+
+        let edited_chunks = dimension
+            .edits
+            .lock()
+            .unwrap()
+            .chunk_locations()
+            .collect_vec();
+        let chunks = edited_chunks
+            .into_iter()
+            .map(|location| (location, dimension.chunk(location).0))
+            .collect::<HashMap<_, _>>();
+
+        let mut storage = storage.lock().unwrap();
+        for &location in chunks.keys() {
+            storage.mark_modified(location);
+        }
+        saved_regions += storage
+            .save(|location| chunks.get(&location))
+            .with_context(|| format!("could not save the {name}"))?;
+    }
+
+    Ok(format!("Saved {saved_regions} regions"))
+}
+
+/// Sends a message to every client except the given player
+fn broadcast(state: &ServerState, sender: &str, message: &ServerMessage) {
+    let frame = message.encode();
+    for (player_id, writer) in client_writers(state, Some(sender)) {
+        if let Err(err) = write_frame(&mut *writer.lock().unwrap(), &frame) {
+            warn!("Could not send a message to player {player_id}: {err:#}");
+        }
+    }
+}
+
+/// The connections of all clients except the given player. The clients are not locked while their connections are written to,
+/// so that a client whose socket is full does not keep the others from joining and leaving.
+fn client_writers(state: &ServerState, except: Option<&str>) -> Vec<(String, ClientWriter)> {
+    state
+        .clients
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(player_id, _)| Some(player_id.as_str()) != except)
+        .map(|(player_id, writer)| (player_id.clone(), Arc::clone(writer)))
+        .collect()
+}
+
+fn send(writer: &ClientWriter, message: &ServerMessage) {
+    if let Err(err) = write_frame(&mut *writer.lock().unwrap(), &message.encode()) {
+        warn!("Could not send a message to a client: {err:#}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use crate::network::protocol::{write_frame, ClientMessage};
+    use crate::network::server::{handle_client, ServerState};
+
+    fn join(address: SocketAddr, player_id: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let join = ClientMessage::Join {
+            player_id: player_id.to_owned(),
+        };
+        write_frame(&mut stream, &join.encode()).unwrap();
+        stream
+    }
+
+    #[test]
+    fn test_players_cannot_join_twice() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let state = Arc::new(ServerState {
+            dimensions: HashMap::new(),
+            clients: Mutex::new(HashMap::new()),
+            stopping: AtomicBool::new(false),
+            wake_address: address,
+        });
+
+        let _first = join(address, "steve");
+        let (stream, _) = listener.accept().unwrap();
+        let first_state = Arc::clone(&state);
+        thread::spawn(move || handle_client(&first_state, stream));
+        let first_writer = loop {
+            if let Some(writer) = state.clients.lock().unwrap().get("steve") {
+                break Arc::clone(writer);
+            }
+            thread::yield_now();
+        };
+
+        let _second = join(address, "steve");
+        let (stream, _) = listener.accept().unwrap();
+        assert!(handle_client(&state, stream).is_err());
+        assert!(Arc::ptr_eq(&state.clients.lock().unwrap()["steve"], &first_writer));
+    }
+}
@@ -0,0 +1,43 @@
+use std::ops::RangeInclusive;
+
+use cgmath::{Point3, Rad, Vector3};
+
+/// Multiples of the window size at which photos can be rendered
+pub const PHOTO_SUPERSAMPLING: RangeInclusive<u32> = 2..=4;
+
+/// Camera settings of the photo mode, which are edited in the photo mode window of the debug overlay
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhotoSettings {
+    /// Rotation of the camera around its view direction in degrees
+    pub roll: f32,
+    /// Distance from the eye in blocks at which the image is sharp
+    pub focus_distance: f32,
+    /// Blur radius in pixels of surfaces far away from the focus distance, 0 disables the depth of field
+    pub aperture: f32,
+    /// Multiplier of the exposure that the eye adapted to
+    pub exposure: f32,
+    /// Photos are rendered at this multiple of the window size and scaled down to the window size
+    pub supersampling: u32,
+}
+
+impl Default for PhotoSettings {
+    fn default() -> Self {
+        Self {
+            roll: 0.0,
+            focus_distance: 10.0,
+            aperture: 0.0,
+            exposure: 1.0,
+            supersampling: 2,
+        }
+    }
+}
+
+/// The player when the photo mode started, which is restored when it ends, as the camera flies freely through the paused world
+pub struct PhotoSession {
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    /// Yaw and pitch of the camera
+    pub orientation: (Rad<f64>, Rad<f64>),
+    /// Whether the simulation was paused before the photo mode started
+    pub was_paused: bool,
+}
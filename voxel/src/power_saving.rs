@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+/// Frame rate to which the frames are limited while saving power
+pub const POWER_SAVING_MAX_FPS: f32 = 30.0;
+/// Render distance to which the render distance is limited while saving power
+pub const POWER_SAVING_MAX_RENDER_DISTANCE: i32 = 8;
+/// How often the power supply is checked again in [PowerSavingMode::Auto]
+const BATTERY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// When the engine uses fewer threads, streams fewer chunks and limits the frame rate, so that it does not keep all cores
+/// of a laptop busy
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum PowerSavingMode {
+    /// Save power while running on battery
+    #[default]
+    Auto,
+    On,
+    Off,
+}
+
+impl PowerSavingMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerSavingMode::Auto => "On battery",
+            PowerSavingMode::On => "Always",
+            PowerSavingMode::Off => "Never",
+        }
+    }
+}
+
+/// Decides whether power is saved right now, following the [PowerSavingMode] and the power supply
+pub struct PowerSaving {
+    pub mode: PowerSavingMode,
+    on_battery: bool,
+    last_battery_check: Option<Instant>,
+}
+
+impl PowerSaving {
+    pub fn new(mode: PowerSavingMode) -> Self {
+        Self {
+            mode,
+            on_battery: false,
+            last_battery_check: None,
+        }
+    }
+
+    /// Checks the power supply again if it was not checked recently and returns whether power is saved
+    pub fn update(&mut self) -> bool {
+        if self.mode == PowerSavingMode::Auto
+            && self
+                .last_battery_check
+                .map_or(true, |last_check| last_check.elapsed() >= BATTERY_CHECK_INTERVAL)
+        {
+            let on_battery = is_on_battery();
+            if on_battery != self.on_battery {
+                info!(
+                    "{}",
+                    if on_battery {
+                        "Running on battery, saving power"
+                    } else {
+                        "Running on external power, no longer saving power"
+                    }
+                );
+            }
+
+            self.on_battery = on_battery;
+            self.last_battery_check = Some(Instant::now());
+        }
+
+        self.is_active()
+    }
+
+    pub fn is_active(&self) -> bool {
+        match self.mode {
+            PowerSavingMode::Auto => self.on_battery,
+            PowerSavingMode::On => true,
+            PowerSavingMode::Off => false,
+        }
+    }
+}
+
+/// Whether any battery of the system is discharging
+#[cfg(target_os = "linux")]
+fn is_on_battery() -> bool {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+
+    entries.flatten().any(|entry| {
+        let read = |name: &str| std::fs::read_to_string(entry.path().join(name)).unwrap_or_default();
+        read("type").trim() == "Battery" && read("status").trim() == "Discharging"
+    })
+}
+
+/// The power supply can only be detected on Linux, other platforms have to enable [PowerSavingMode::On] manually
+#[cfg(not(target_os = "linux"))]
+fn is_on_battery() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::power_saving::{PowerSaving, PowerSavingMode};
+
+    #[test]
+    fn test_power_saving_follows_mode() {
+        assert!(PowerSaving::new(PowerSavingMode::On).update());
+        assert!(!PowerSaving::new(PowerSavingMode::Off).update());
+    }
+}
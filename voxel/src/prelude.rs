@@ -0,0 +1,28 @@
+//! The types that are needed to run the engine, a headless run or a server, and to generate and mesh chunks without the engine.
+//!
+//! ```no_run
+//! use voxel::prelude::*;
+//!
+//! start(EngineConfig::default());
+//! ```
+
+pub use crate::benchmark::{BenchmarkConfig, BenchmarkThresholds};
+pub use crate::build_info::BUILD_INFO;
+pub use crate::headless::{run_headless, HeadlessConfig};
+pub use crate::network::fuzz::fuzz_packets;
+pub use crate::network::recording::serve_recording;
+pub use crate::network::server::{run_server, ServerConfig};
+pub use crate::soak_test::SoakConfig;
+pub use crate::starter::start;
+pub use crate::world::chunk_data::ChunkData;
+pub use crate::world::chunk_renderer::meshing::NeighborChunks;
+pub use crate::world::chunk_renderer::{ChunkMeshData, MeshKind, MeshingMode};
+pub use crate::world::entities::{Entities, Entity, EntityId, EntityMesh};
+pub use crate::world::location::{ChunkLocation, LocalChunkLocation, WorldLocation};
+pub use crate::world::noise_source::NoiseSource;
+pub use crate::world::voxel_data::{VoxelData, VoxelType};
+pub use crate::world::world_config::{GeneratorKind, WorldConfig};
+pub use crate::world::worldgen::{ChunkGenerator, GeneratedChunk, WorldGenerator};
+pub use crate::world::worldgen_preset::WorldgenPreset;
+pub use crate::world::CHUNK_SIZE;
+pub use crate::EngineConfig;
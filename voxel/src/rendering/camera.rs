@@ -1,34 +1,71 @@
+use std::mem;
 use std::time::Duration;
 
 use bytemuck::Zeroable;
 use cgmath::num_traits::FloatConst;
-use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3, Zero};
-use wgpu::util::DeviceExt;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector2, Vector3, Vector4};
 use wgpu::BindingType;
-use winit::event::{ElementState, VirtualKeyCode};
 
-use crate::rendering::RenderCtx;
-use crate::world::chunk_manager::{Chunk, ChunkManager};
+use crate::cvars::CVar;
+use crate::input::Action;
+use crate::rendering::frustum::{Aabb, Frustum};
+use crate::rendering::shadow_cascades::{sun_direction, ShadowCascade, SHADOW_CASCADE_COUNT};
+use crate::rendering::uniform_ring::{UniformBinding, UniformRing};
+use crate::rendering::{RenderCtx, RenderHandle};
+use crate::world::chunk_manager::ChunkManager;
+use crate::world::collision::move_aabb_with_step;
 use crate::world::location::WorldLocation;
-use crate::world::voxel_data::VoxelType;
-
-const CAMERA_EYE_OFFSET: f32 = 100.0;
-const GRAVITY: f32 = 300.0;
-const JUMP_ACCELERATION: f32 = 10.0;
+use crate::world::voxel_data::{PhysicsMaterial, VoxelType};
+
+/// Entries of the bind group layout of [Camera::binding]
+pub const CAMERA_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    ty: BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: wgpu::BufferSize::new(mem::size_of::<RawCamera>() as u64),
+    },
+    // The water shader uses the camera position and the fog per fragment
+    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+    count: None,
+}];
+
+/// Fog parameters of cameras that are not affected by fog, e.g. the viewmodel
+pub const NO_FOG: [f32; 4] = [0.0, 0.0, 0.0, f32::MAX];
+/// Distance fog parameters of cameras that are not affected by fog, with a density of 0
+pub const NO_DISTANCE_FOG: [f32; 4] = [0.0; 4];
+
+/// Height of the eyes above the bottom of the player, a little below its [PLAYER_HEIGHT]
+pub(crate) static CAMERA_EYE_OFFSET: CVar = cvar!("camera.eye_offset", 1.6);
+pub(crate) static GRAVITY: CVar = cvar!("physics.gravity", 300.0);
+pub(crate) static JUMP_ACCELERATION: CVar = cvar!("physics.jump_acceleration", 10.0);
+/// Landing speeds below this do not bounce, so that bodies come to rest on bouncy surfaces
+const MIN_BOUNCE_SPEED: f32 = 5.0;
+/// Size of the collider of the player, whose bottom center is at the camera position
+pub const PLAYER_HALF_WIDTH: f32 = 0.3;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+/// Obstacles up to this height are stepped onto while walking, so that single voxel steps do not need a jump
+const STEP_HEIGHT: f32 = 1.0;
+/// Distance below the feet of the player at which the voxel that it stands on is looked up
+const GROUND_OFFSET: f32 = 0.01;
 
 pub struct Camera {
     pub position: Point3<f32>,
     pub velocity: Vector3<f32>,
 
+    /// Sub-pixel offset of the projection in normalized device coordinates, which only affects the rendered image
+    pub jitter: Vector2<f32>,
+
     yaw: Rad<f64>,
     pitch: Rad<f64>,
+    /// Rotation of the image around the view direction, e.g. for photos
+    pub roll: Rad<f64>,
+    /// Part of the screen that is rendered, or None to render the whole screen
+    pub tile: Option<ScreenTile>,
     projection: Projection,
 
     raw: RawCamera,
-    buffer: wgpu::Buffer,
-
-    pub bind_group_layout: wgpu::BindGroupLayout,
-    pub bind_group: wgpu::BindGroup,
+    uniforms: UniformRing<RawCamera>,
 }
 
 impl Camera {
@@ -51,75 +88,117 @@ impl Camera {
     {
         let position = position.into();
         let raw = RawCamera {
-            position: [position.x, position.y + CAMERA_EYE_OFFSET, position.z, 0.0],
+            position: [position.x, position.y + CAMERA_EYE_OFFSET.get(), position.z, 0.0],
             view_proj: [[0.0f32; 4]; 4],
+            clouds: [0.0; 4],
+            fog: NO_FOG,
+            distance_fog: NO_DISTANCE_FOG,
         };
 
-        let buffer = render_ctx
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Camera buffer"),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                contents: bytemuck::cast_slice(&[raw]),
-            });
-
-        let bind_group_layout = render_ctx
-            .device
-            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Camera bind group layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    ty: BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    count: None,
-                }],
-            });
-
-        let bind_group = render_ctx
-            .device
-            .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Camera bind group"),
-                layout: &bind_group_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: buffer.as_entire_binding(),
-                }],
-            });
+        let uniforms = Self::create_uniforms(render_ctx, &raw);
 
         Camera {
             position,
             velocity: Vector3::zeroed(),
+            jitter: Vector2::zeroed(),
             yaw: yaw.into(),
             pitch: pitch.into(),
+            roll: Rad(0.0),
+            tile: None,
             projection: Projection::new(width, height, fov_y, z_near, z_far),
             raw,
-            buffer,
-            bind_group_layout,
-            bind_group,
+            uniforms,
         }
     }
 
-    pub fn update_buffer(&mut self, render_ctx: &RenderCtx) {
+    fn create_uniforms(render_ctx: &RenderCtx, raw: &RawCamera) -> UniformRing<RawCamera> {
+        let bind_group_layout = render_ctx.bind_group_layout("Camera bind group layout", CAMERA_BIND_GROUP_LAYOUT_ENTRIES);
+        UniformRing::new(render_ctx, "Camera uniforms", &bind_group_layout, raw)
+    }
+
+    /// Recreates the uniform buffer on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, render_ctx: &RenderCtx) {
+        self.uniforms = Self::create_uniforms(render_ctx, &self.raw);
+    }
+
+    pub fn update_buffer(&mut self, handle: &mut RenderHandle, clouds: [f32; 4], fog: [f32; 4], distance_fog: [f32; 4]) {
+        self.raw.position = [self.position.x, self.position.y, self.position.z, 0.0];
+        self.raw.view_proj = (Matrix4::from_translation(self.jitter.extend(0.0)) * self.view_projection()).into();
+        self.raw.clouds = clouds;
+        self.raw.fog = fog;
+        self.raw.distance_fog = distance_fog;
+
+        self.uniforms.write(handle, &self.raw);
+    }
+
+    /// Camera uniforms of the current frame
+    pub fn binding(&self) -> UniformBinding {
+        self.uniforms.binding()
+    }
+
+    /// Position from which the world is viewed
+    pub fn eye_position(&self) -> Point3<f32> {
+        self.position + CAMERA_EYE_OFFSET.get() * Vector3::unit_y()
+    }
+
+    /// Normalized direction in which the camera looks
+    pub fn forward(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = (self.pitch.0 as f32).sin_cos();
         let (sin_yaw, cos_yaw) = (self.yaw.0 as f32).sin_cos();
 
-        let view = Matrix4::look_to_rh(
-            self.position + CAMERA_EYE_OFFSET * Vector3::unit_y(),
-            Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
-            Vector3::unit_y(),
-        );
-        let proj = self.projection.build_proj_matrix();
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
 
-        self.raw.position = [self.position.x, self.position.y, self.position.z, 0.0];
-        self.raw.view_proj = (proj * view).into();
+    /// Yaw and pitch of the view direction
+    pub fn orientation(&self) -> (Rad<f64>, Rad<f64>) {
+        (self.yaw, self.pitch)
+    }
+
+    pub fn set_orientation(&mut self, yaw: Rad<f64>, pitch: Rad<f64>) {
+        self.yaw = yaw;
+        self.pitch = pitch;
+    }
+
+    /// Turns the camera to look in the given direction, which does not need to be normalized
+    pub fn look_in(&mut self, direction: Vector3<f32>) {
+        let direction = direction.normalize();
+        self.yaw = Rad(direction.z.atan2(direction.x) as f64);
+        self.pitch = Rad(direction.y.asin() as f64);
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        let view_projection = self.untiled_view_projection();
+        match self.tile {
+            Some(tile) => tile.transform() * view_projection,
+            None => view_projection,
+        }
+    }
+
+    /// View projection of the whole screen, regardless of the rendered tile
+    fn untiled_view_projection(&self) -> Matrix4<f32> {
+        view_projection(self.eye_position(), self.forward(), Rad(self.roll.0 as f32), &self.projection)
+    }
+
+    /// View frustum of this camera, which is used to cull chunks
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.view_projection())
+    }
 
-        render_ctx
-            .queue
-            .write_buffer(&self.buffer, 0 as _, bytemuck::cast_slice(&[self.raw]));
+    /// Ray through a point on the screen in normalized device coordinates, where (-1, -1) is the bottom left and (1, 1) the top right corner.
+    /// Returns the origin on the near plane and the normalized direction of the ray.
+    pub fn screen_ray(&self, ndc: Vector2<f32>) -> (Point3<f32>, Vector3<f32>) {
+        screen_ray(self.view_projection(), ndc)
+    }
+
+    /// Shadow cascades of the sunlight that cover the view of this camera
+    pub fn shadow_cascades(&self) -> [ShadowCascade; SHADOW_CASCADE_COUNT] {
+        // Fitted to the whole screen, so that all tiles of a photo are shadowed the same
+        ShadowCascade::fit(
+            self.untiled_view_projection(),
+            self.projection.z_near,
+            self.projection.z_far,
+            sun_direction(),
+        )
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -127,6 +206,66 @@ impl Camera {
     }
 }
 
+fn view_projection(eye: Point3<f32>, forward: Vector3<f32>, roll: Rad<f32>, projection: &Projection) -> Matrix4<f32> {
+    let view = Matrix4::from_angle_z(roll) * Matrix4::look_to_rh(eye, forward, Vector3::unit_y());
+    let proj = projection.build_proj_matrix();
+
+    proj * view
+}
+
+/// Part of the screen when it is split into a grid of equally sized tiles, e.g. to render a photo at a multiple of the window size
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ScreenTile {
+    pub column: u32,
+    /// Row from the top of the screen
+    pub row: u32,
+    /// Number of columns and rows of the grid
+    pub tiles: u32,
+}
+
+impl ScreenTile {
+    /// Transform of normalized device coordinates that enlarges this tile to the whole screen
+    fn transform(self) -> Matrix4<f32> {
+        let tiles = self.tiles as f32;
+        let offset = |index: u32| tiles - 1.0 - 2.0 * index as f32;
+
+        Matrix4::from_translation(Vector3::new(offset(self.column), -offset(self.row), 0.0))
+            * Matrix4::from_nonuniform_scale(tiles, tiles, 1.0)
+    }
+}
+
+/// Unprojects a point on the screen onto the near and the far plane
+fn screen_ray(view_projection: Matrix4<f32>, ndc: Vector2<f32>) -> (Point3<f32>, Vector3<f32>) {
+    let inverse = view_projection
+        .invert()
+        .expect("View projection matrices are invertible");
+    let unproject = |depth: f32| Point3::from_homogeneous(inverse * Vector4::new(ndc.x, ndc.y, depth, 1.0));
+
+    let (near, far) = (unproject(0.0), unproject(1.0));
+    (near, (far - near).normalize())
+}
+
+/// Solid voxel below a collider, preferring the one below its center if it stands on several voxels
+fn ground_below(aabb: Aabb, chunk_manager: &ChunkManager) -> Option<VoxelType> {
+    let center = (aabb.min + aabb.max) / 2.0;
+    let y = (aabb.min.y - GROUND_OFFSET).floor() as i32;
+
+    [center.x, aabb.min.x, aabb.max.x]
+        .into_iter()
+        .flat_map(|x| [center.z, aabb.min.z, aabb.max.z].map(|z| (x, z)))
+        .filter_map(|(x, z)| chunk_manager.voxel_at(WorldLocation(Vector3::new(x.floor() as i32, y, z.floor() as i32))))
+        .find(|ty| ty.is_solid())
+}
+
+/// Collider of the player standing at the given position
+fn player_aabb(position: Point3<f32>) -> Aabb {
+    let half_extents = Vector3::new(PLAYER_HALF_WIDTH, 0.0, PLAYER_HALF_WIDTH);
+    Aabb {
+        min: position.to_vec() - half_extents,
+        max: position.to_vec() + half_extents + PLAYER_HEIGHT * Vector3::unit_y(),
+    }
+}
+
 pub struct Projection {
     aspect: f32,
     fov_y: Rad<f32>,
@@ -166,10 +305,12 @@ pub struct CameraController {
     rotate_horizontal: f64,
     rotate_vertical: f64,
     speed: f32,
-    sensitivity: f32,
+    pub sensitivity: f32,
     is_jumping: bool,
     pub no_clip: bool,
     pub is_grounded: bool,
+    /// Material of the voxel below the camera, or of air while falling
+    ground_material: PhysicsMaterial,
 }
 
 impl CameraController {
@@ -190,126 +331,85 @@ impl CameraController {
             is_jumping: false,
             no_clip: true,
             is_grounded: false,
+            ground_material: VoxelType::Air.physics_material(),
         }
     }
 
-    pub fn process_keyboard(&mut self, key: &VirtualKeyCode, state: &ElementState) -> bool {
-        let is_pressed = matches!(state, ElementState::Pressed);
-
-        use VirtualKeyCode::{LShift, Space, A, D, S, W};
-        match key {
-            W => {
-                self.forward = is_pressed;
-                true
-            }
-            S => {
-                self.backward = is_pressed;
-                true
-            }
-            A => {
-                self.left = is_pressed;
-                true
-            }
-            D => {
-                self.right = is_pressed;
-                true
-            }
-            Space => {
+    /// Applies a pressed or released movement action. Returns whether the action moves the camera.
+    pub fn process_action(&mut self, action: Action, is_pressed: bool) -> bool {
+        match action {
+            Action::MoveForward => self.forward = is_pressed,
+            Action::MoveBackward => self.backward = is_pressed,
+            Action::MoveLeft => self.left = is_pressed,
+            Action::MoveRight => self.right = is_pressed,
+            Action::Jump => {
                 self.up = is_pressed;
                 self.is_jumping = is_pressed;
-                true
             }
-            LShift => {
-                self.down = is_pressed;
-                true
-            }
-            _ => false,
+            Action::Descend => self.down = is_pressed,
+            _ => return false,
         }
+
+        true
     }
 
+    /// Accumulates mouse movement until the next call to [CameraController::update_look]
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx;
-        self.rotate_vertical = mouse_dy;
+        self.rotate_horizontal += mouse_dx;
+        self.rotate_vertical += mouse_dy;
     }
 
+    /// Applies gravity and moves the player collider through the world along its velocity, see [move_aabb_with_step]
     pub fn update_physics(&mut self, camera: &mut Camera, chunk_manager: &ChunkManager, dt: Duration) {
-        if !self.no_clip {
-            if !self.is_grounded || self.is_jumping {
-                let (chunk_location, local_chunk_location) = WorldLocation(
-                    (camera.position.to_vec() - 1.0 * Vector3::<f32>::unit_y())
-                        .cast::<i32>()
-                        .unwrap(),
-                )
-                .separate();
-
-                let mut is_grounded = chunk_manager
-                    .chunks
-                    .get(&chunk_location)
-                    .map(|chunk| {
-                        let chunk_data = match chunk {
-                            Chunk::None { .. } => return false,
-                            Chunk::Generated { data, .. } => data,
-                            Chunk::Meshed { data, .. } => data,
-                        };
-
-                        chunk_data.get_voxel(local_chunk_location).ty != VoxelType::Air
-                    })
-                    .unwrap_or(false);
-
-                if !is_grounded {
-                    camera.velocity.y -= dt.as_secs_f32() * GRAVITY;
-                } else {
-                    if self.is_jumping {
-                        camera.velocity.y += JUMP_ACCELERATION;
-                        self.is_grounded = false;
-                    }
-                }
-
-                if !is_grounded {
-                    let mut vertical_distance = dt.as_secs_f32() * camera.velocity.y;
-
-                    if vertical_distance < 0.0 {
-                        let mut current_neg_distance = 0.0;
-
-                        while current_neg_distance < -vertical_distance {
-                            let (chunk_location, local_chunk_location) = WorldLocation(
-                                (camera.position.to_vec() - (1.0 + current_neg_distance) * Vector3::<f32>::unit_y())
-                                    .cast::<i32>()
-                                    .unwrap(),
-                            )
-                            .separate();
-
-                            let mut is_grounded = chunk_manager
-                                .chunks
-                                .get(&chunk_location)
-                                .map(|chunk| {
-                                    let chunk_data = match chunk {
-                                        Chunk::None { .. } => return false,
-                                        Chunk::Generated { data, .. } => data,
-                                        Chunk::Meshed { data, .. } => data,
-                                    };
-
-                                    chunk_data.get_voxel(local_chunk_location).ty != VoxelType::Air
-                                })
-                                .unwrap_or(false);
-
-                            if is_grounded {
-                                vertical_distance = -current_neg_distance;
-                                camera.velocity = Vector3::zeroed();
-
-                                break;
-                            }
-                            current_neg_distance += 1.0;
-                        }
-                    }
-
-                    camera.position.y += vertical_distance;
-                }
-            }
+        if self.no_clip {
+            return;
         }
+
+        let dt = dt.as_secs_f32();
+        if self.is_grounded && self.is_jumping {
+            camera.velocity.y += JUMP_ACCELERATION.get();
+            self.is_grounded = false;
+        }
+        camera.velocity.y -= dt * GRAVITY.get();
+
+        // Voxels of chunks that are not generated yet do not collide, so that the player is never stuck in them
+        let is_solid = |location: WorldLocation| chunk_manager.is_solid_at(location) == Some(true);
+        let step_height = if self.is_grounded { STEP_HEIGHT } else { 0.0 };
+        let movement = move_aabb_with_step(player_aabb(camera.position), camera.velocity * dt, step_height, is_solid);
+        camera.position = Point3::new(
+            (movement.aabb.min.x + movement.aabb.max.x) / 2.0,
+            movement.aabb.min.y,
+            (movement.aabb.min.z + movement.aabb.max.z) / 2.0,
+        );
+
+        if movement.blocked[0] {
+            camera.velocity.x = 0.0;
+        }
+        if movement.blocked[2] {
+            camera.velocity.z = 0.0;
+        }
+
+        let landed = movement.blocked[1] && camera.velocity.y < 0.0;
+        self.ground_material = landed
+            .then(|| ground_below(movement.aabb, chunk_manager))
+            .flatten()
+            .unwrap_or(VoxelType::Air)
+            .physics_material();
+
+        if movement.blocked[1] {
+            // Bounce off of the ground depending on its material, ceilings stop the movement
+            let bounce_speed = -camera.velocity.y * self.ground_material.restitution;
+            camera.velocity.y = if landed && bounce_speed > MIN_BOUNCE_SPEED {
+                bounce_speed
+            } else {
+                0.0
+            };
+        }
+        self.is_grounded = landed && camera.velocity.y == 0.0;
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+    /// Moves the camera along the pressed movement actions, once per tick of the simulation
+    pub fn update_movement(&mut self, camera: &mut Camera, dt: Duration) {
         let dt = dt.as_secs_f32();
 
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
@@ -319,21 +419,43 @@ impl CameraController {
         let forward_speed = if self.forward { self.speed } else { 0.0 } + if self.backward { -self.speed } else { 0.0 };
         let right_speed = if self.right { self.speed } else { 0.0 } + if self.left { -self.speed } else { 0.0 };
 
-        if !forward_speed.is_zero() || !right_speed.is_zero() {
-            self.is_grounded = false;
-        }
-
-        camera.position += forward * forward_speed * dt;
-        camera.position += right * right_speed * dt;
+        let target_velocity = forward * forward_speed + right * right_speed;
 
         if self.no_clip {
+            camera.position += target_velocity * dt;
             camera.position.y += if self.up { self.speed * dt } else { 0.0 } + if self.down { -self.speed * dt } else { 0.0 };
+        } else {
+            // The horizontal velocity follows the input depending on the friction of the ground, so that slippery surfaces slide
+            let follow = (self.ground_material.friction * dt).min(1.0);
+            camera.velocity.x += (target_velocity.x - camera.velocity.x) * follow;
+            camera.velocity.z += (target_velocity.z - camera.velocity.z) * follow;
+        }
+    }
+
+    /// Rotates the camera by the mouse movement since the last frame.
+    /// Called once per rendered frame with the real frame time, so that looking around is smooth at any timescale and while paused.
+    pub fn update_look(&mut self, camera: &mut Camera, dt: Duration) {
+        let (yaw, pitch) = self.take_look_rotation(dt);
+        camera.yaw += yaw;
+        camera.pitch += pitch;
+
+        // Keep camera's angle from going to far
+        let safe_frac_pi_2 = f64::FRAC_PI_2() - 0.001;
+        if camera.pitch < -Rad(safe_frac_pi_2) {
+            camera.pitch = -Rad(safe_frac_pi_2);
+        } else if camera.pitch > Rad(safe_frac_pi_2) {
+            camera.pitch = Rad(safe_frac_pi_2);
         }
+    }
+
+    /// Change of yaw and pitch for the mouse movement since the last call, which is consumed
+    fn take_look_rotation(&mut self, dt: Duration) -> (Rad<f64>, Rad<f64>) {
+        let dt = dt.as_secs_f64();
 
         const FACTOR: f64 = 0.5;
 
-        camera.yaw += Rad(FACTOR * self.rotate_horizontal + self.last_rotate_horizontal) * self.sensitivity as f64 * dt as f64;
-        camera.pitch += Rad(FACTOR * (-self.rotate_vertical) + -self.last_rotate_vertical) * self.sensitivity as f64 * dt as f64;
+        let yaw = Rad(FACTOR * self.rotate_horizontal + self.last_rotate_horizontal) * self.sensitivity as f64 * dt;
+        let pitch = Rad(FACTOR * (-self.rotate_vertical) + -self.last_rotate_vertical) * self.sensitivity as f64 * dt;
 
         self.last_rotate_horizontal = (1.0 - FACTOR) * self.rotate_horizontal;
         self.last_rotate_vertical = (1.0 - FACTOR) * self.rotate_vertical;
@@ -341,13 +463,7 @@ impl CameraController {
         self.rotate_horizontal = 0.0;
         self.rotate_vertical = 0.0;
 
-        // Keep camera's angle from going to far
-        let safe_frac_pi_2 = f64::FRAC_PI_2() - 0.001;
-        if camera.pitch < -Rad(safe_frac_pi_2) {
-            camera.pitch = -Rad(safe_frac_pi_2);
-        } else if camera.pitch > Rad(safe_frac_pi_2) {
-            camera.pitch = Rad(safe_frac_pi_2);
-        }
+        (yaw, pitch)
     }
 }
 
@@ -356,12 +472,116 @@ impl CameraController {
 pub struct RawCamera {
     pub position: [f32; 4],
     pub view_proj: [[f32; 4]; 4],
+    /// Drift offset in x and z, coverage and shadow strength of the clouds, see [crate::rendering::clouds::CloudRenderer::uniform]
+    pub clouds: [f32; 4],
+    /// Color of the fog and the distance at which it completely hides the terrain, see [crate::environment::Look]
+    pub fog: [f32; 4],
+    /// Color and density of the exponential fog in which far chunks fade, see [crate::environment::Look::distance_fog]
+    pub distance_fog: [f32; 4],
 }
 
 #[rustfmt::skip]
-const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
     0.0, 0.0, 0.5, 0.0,
     0.0, 0.0, 0.5, 1.0,
 );
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, EuclideanSpace, InnerSpace, Point3, Rad, Vector2, Vector3, Vector4};
+
+    use std::time::Duration;
+
+    use crate::rendering::camera::{screen_ray, view_projection, CameraController, Projection, ScreenTile};
+    use crate::rendering::frustum::Frustum;
+
+    const EPSILON: f32 = 1e-3;
+
+    #[test]
+    fn test_screen_ray_matches_projection() {
+        let eye = Point3::new(10.0, 20.0, 30.0);
+        let forward = Vector3::new(1.0, 0.0, 0.0);
+        let projection = Projection::new(800, 600, Deg(80.0), 0.1, 1000.0);
+        let view_projection = view_projection(eye, forward, Rad(0.0), &projection);
+
+        let (origin, direction) = screen_ray(view_projection, Vector2::new(0.0, 0.0));
+        assert!((origin - (eye + forward * 0.1)).magnitude() < EPSILON);
+        assert!((direction - forward).magnitude() < EPSILON);
+
+        // The top and the right edge of the screen are half of the field of view away from the center
+        let (_, top) = screen_ray(view_projection, Vector2::new(0.0, 1.0));
+        assert!((Rad::from(Deg(40.0)).0 - top.angle(forward).0).abs() < EPSILON);
+        assert!(top.y > 0.0);
+
+        let (_, right) = screen_ray(view_projection, Vector2::new(1.0, 0.0));
+        let expected = (Rad::from(Deg(40.0)).0.tan() * 800.0 / 600.0).atan();
+        assert!((expected - right.angle(forward).0).abs() < EPSILON);
+        assert!(right.z > 0.0);
+    }
+
+    #[test]
+    fn test_screen_rays_stay_inside_frustum() {
+        let eye = Point3::new(0.0, 0.0, 0.0);
+        let projection = Projection::new(800, 600, Deg(80.0), 0.1, 1000.0);
+        let view_projection = view_projection(eye, Vector3::new(0.0, 0.0, -1.0), Rad(0.0), &projection);
+        let frustum = Frustum::from_view_projection(view_projection);
+
+        let inside = |point: Point3<f32>| {
+            let point = point.to_vec();
+            frustum.intersects_aabb(point - Vector3::new(0.01, 0.01, 0.01), point + Vector3::new(0.01, 0.01, 0.01))
+        };
+
+        let (origin, direction) = screen_ray(view_projection, Vector2::new(0.5, -0.5));
+        assert!(inside(origin + direction * 10.0));
+        assert!(!inside(eye + Vector3::new(0.0, 0.0, 10.0)));
+    }
+
+    #[test]
+    fn test_screen_tile_enlarges_its_part_of_the_screen() {
+        let top_right = ScreenTile {
+            column: 1,
+            row: 0,
+            tiles: 2,
+        };
+        let ndc = |tile: ScreenTile, x: f32, y: f32| {
+            let point = tile.transform() * Vector4::new(x, y, 0.5, 1.0);
+            Vector2::new(point.x, point.y)
+        };
+
+        assert!((ndc(top_right, 0.5, 0.5) - Vector2::new(0.0, 0.0)).magnitude() < EPSILON);
+        assert!((ndc(top_right, 0.0, 0.0) - Vector2::new(-1.0, -1.0)).magnitude() < EPSILON);
+        assert!((ndc(top_right, 1.0, 1.0) - Vector2::new(1.0, 1.0)).magnitude() < EPSILON);
+
+        let bottom_left = ScreenTile {
+            column: 0,
+            row: 2,
+            tiles: 3,
+        };
+        assert!((ndc(bottom_left, -1.0, -1.0) - Vector2::new(-1.0, -1.0)).magnitude() < EPSILON);
+        assert!((ndc(bottom_left, -1.0 / 3.0, -1.0 / 3.0) - Vector2::new(1.0, 1.0)).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn test_mouse_movement_is_applied_once_per_frame_without_accumulating() {
+        let frame = Duration::from_millis(16);
+        let mut controller = CameraController::new(10.0, 1.0);
+
+        // The same movement every frame turns the camera by the same angle every frame, however long the simulation is paused
+        let mut turns = Vec::new();
+        for _ in 0..10 {
+            controller.process_mouse(4.0, -2.0);
+            turns.push(controller.take_look_rotation(frame));
+        }
+        for &(yaw, pitch) in &turns[1..] {
+            assert!((yaw.0 - 4.0 * 0.016).abs() < 1e-9, "{yaw:?}");
+            assert!((pitch.0 - 2.0 * 0.016).abs() < 1e-9, "{pitch:?}");
+        }
+
+        // Once the mouse stops, the smoothing finishes within a frame instead of catching up with earlier frames
+        let (yaw, _) = controller.take_look_rotation(frame);
+        assert!((yaw.0 - 2.0 * 0.016).abs() < 1e-9, "{yaw:?}");
+        assert_eq!(controller.take_look_rotation(frame), (Rad(0.0), Rad(0.0)));
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use cgmath::Vector2;
+use wgpu::include_wgsl;
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::{RenderCtx, Renderer};
+
+/// Distance in blocks after which the cloud noise repeats, see clouds.wgsl
+const CLOUD_PERIOD: f32 = 8192.0;
+/// Speed and direction in which the clouds drift, in blocks per second
+const DRIFT_VELOCITY: Vector2<f32> = Vector2::new(2.0, 0.7);
+
+/// A flat layer of noise-driven clouds high above the terrain, which slowly drifts and darkens the terrain below it
+pub struct CloudRenderer {
+    pipeline: wgpu::RenderPipeline,
+    pub enabled: bool,
+    /// Fraction of the sky that is covered by clouds, from 0.0 to 1.0
+    pub coverage: f32,
+    /// Distance that the clouds have drifted. Wraps around after [CLOUD_PERIOD], which is invisible because the noise repeats.
+    offset: Vector2<f32>,
+}
+
+impl CloudRenderer {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        Self {
+            pipeline: create_cloud_pipeline(ctx),
+            enabled: true,
+            coverage: 0.4,
+            offset: Vector2::new(0.0, 0.0),
+        }
+    }
+
+    /// Recreates the pipeline on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        self.pipeline = create_cloud_pipeline(ctx);
+    }
+
+    pub fn animate(&mut self, dt: Duration) {
+        self.offset += DRIFT_VELOCITY * dt.as_secs_f32();
+        self.offset.x = self.offset.x.rem_euclid(CLOUD_PERIOD);
+        self.offset.y = self.offset.y.rem_euclid(CLOUD_PERIOD);
+    }
+
+    /// Parameters of the clouds in the camera uniform: drift offset, coverage and shadow strength.
+    /// Disabled clouds do not cast shadows.
+    pub fn uniform(&self) -> [f32; 4] {
+        let shadow_strength = if self.enabled { 0.35 } else { 0.0 };
+        [self.offset.x, self.offset.y, self.coverage, shadow_strength]
+    }
+}
+
+impl Renderer for CloudRenderer {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, _render_ctx: &RenderCtx) {
+        if !self.enabled {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        camera.bind(render_pass, 0);
+        render_pass.draw(0..6, 0..1);
+    }
+}
+
+fn create_cloud_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("clouds.wgsl"));
+
+    let layout = ctx.pipeline_layout("Cloud render pipeline layout", &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES], &[]);
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Cloud render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                entry_point: "fs_main",
+            }),
+            // Visible from above and below
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
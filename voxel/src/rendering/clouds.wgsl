@@ -0,0 +1,86 @@
+struct CameraUniform {
+	position: vec4<f32>,
+    view_proj: mat4x4<f32>,
+    // Drift offset in x and z, coverage and shadow strength of the clouds
+    clouds: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Keep in sync with the cloud shadows in shader.wgsl
+const CLOUD_HEIGHT: f32 = 180.0;
+// Half the size of the cloud layer around the camera. The clouds fade out towards its edges.
+const CLOUD_EXTENT: f32 = 1024.0;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Position in the cloud layer relative to the camera
+    @location(0) offset: vec2<f32>,
+    @location(1) cloud_position: vec2<f32>,
+};
+
+// Two triangles covering the cloud layer above the camera, generated from the vertex index
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	var CORNER_LOOKUP = array<vec2<f32>, 6>(
+		vec2(-1.0, -1.0),
+		vec2(1.0, -1.0),
+		vec2(-1.0, 1.0),
+		vec2(-1.0, 1.0),
+		vec2(1.0, -1.0),
+		vec2(1.0, 1.0));
+
+	let offset = CORNER_LOOKUP[vertex_index] * CLOUD_EXTENT;
+	let position = vec3(camera.position.x + offset.x, CLOUD_HEIGHT, camera.position.z + offset.y);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4(position, 1.0);
+    out.offset = offset;
+    out.cloud_position = position.xz + camera.clouds.xy;
+    return out;
+}
+
+// Outputs almost white with varying opacity only, so that no sRGB encoding is needed for non-sRGB surfaces
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let density = cloud_density(in.cloud_position, camera.clouds.z);
+	let fade = 1.0 - smoothstep(0.5 * CLOUD_EXTENT, CLOUD_EXTENT, length(in.offset));
+
+	return vec4(vec3(0.95), 0.85 * density * fade);
+}
+
+fn cloud_hash(cell: vec2<f32>, period: f32) -> f32 {
+	let wrapped = cell - period * floor(cell / period);
+	return fract(sin(dot(wrapped, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+fn value_noise(position: vec2<f32>, period: f32) -> f32 {
+	let cell = floor(position);
+	let f = fract(position);
+	let u = f * f * (3.0 - 2.0 * f);
+
+	let a = cloud_hash(cell, period);
+	let b = cloud_hash(cell + vec2(1.0, 0.0), period);
+	let c = cloud_hash(cell + vec2(0.0, 1.0), period);
+	let d = cloud_hash(cell + vec2(1.0, 1.0), period);
+
+	return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+// Fractal noise from 0.0 to 1.0 that repeats every 8192 blocks, so that the drift offset can wrap around without a jump
+fn cloud_density(position: vec2<f32>, coverage: f32) -> f32 {
+	var p = position / 32.0;
+	var period = 256.0;
+	var amplitude = 0.5;
+	var total = 0.0;
+
+	for (var octave = 0; octave < 4; octave++) {
+		total += amplitude * value_noise(p, period);
+		p *= 2.0;
+		period *= 2.0;
+		amplitude *= 0.5;
+	}
+
+	return smoothstep(1.0 - coverage, 1.15 - coverage, total / 0.9375);
+}
@@ -0,0 +1,136 @@
+use std::f32::consts::TAU;
+use std::mem;
+use std::time::Duration;
+
+use cgmath::{InnerSpace, Vector3, VectorSpace};
+
+/// Entries of the bind group layout of the lighting uniform, see [RawLighting]
+pub const LIGHTING_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    ty: wgpu::BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: wgpu::BufferSize::new(mem::size_of::<RawLighting>() as u64),
+    },
+    visibility: wgpu::ShaderStages::VERTEX,
+    count: None,
+}];
+
+/// Tilt of the path of the sun towards the z axis, so that the sun is never exactly overhead
+const SUN_PATH_TILT: f32 = 0.4;
+
+/// Time of day, which moves the sun across the sky and decides the color of the sunlight and the ambient light
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DayCycle {
+    /// Fraction of the day from 0.0 to 1.0. The sun rises at 0.25, is highest at 0.5 and sets at 0.75.
+    pub time_of_day: f32,
+    /// Real time in seconds that a full day takes
+    pub day_length: f32,
+    pub paused: bool,
+}
+
+impl DayCycle {
+    pub fn new() -> Self {
+        Self {
+            time_of_day: 0.4,
+            day_length: 20.0 * 60.0,
+            paused: false,
+        }
+    }
+
+    pub fn animate(&mut self, dt: Duration) {
+        if self.paused || self.day_length <= 0.0 {
+            return;
+        }
+
+        self.time_of_day = (self.time_of_day + dt.as_secs_f32() / self.day_length).rem_euclid(1.0);
+    }
+
+    /// Normalized direction from the terrain towards the sun, which is below the horizon at night
+    pub fn to_sun(&self) -> Vector3<f32> {
+        let angle = (self.time_of_day - 0.25) * TAU;
+        Vector3::new(angle.cos(), angle.sin(), SUN_PATH_TILT).normalize()
+    }
+
+    /// Color of the direct sunlight, which turns orange towards the horizon and fades out at night
+    pub fn sun_color(&self) -> Vector3<f32> {
+        let elevation = self.to_sun().y;
+        let noon = Vector3::new(0.8, 0.78, 0.72);
+        let horizon = Vector3::new(0.8, 0.45, 0.2);
+
+        horizon.lerp(noon, smoothstep(0.0, 0.4, elevation)) * smoothstep(-0.1, 0.1, elevation)
+    }
+
+    /// Color of the indirect light, which also reaches faces that point away from the sun
+    pub fn ambient_color(&self) -> Vector3<f32> {
+        let day = Vector3::new(0.2, 0.2, 0.22);
+        let night = Vector3::new(0.03, 0.04, 0.08);
+
+        night.lerp(day, smoothstep(-0.2, 0.2, self.to_sun().y))
+    }
+
+    pub fn uniform(&self) -> RawLighting {
+        let to_sun = self.to_sun();
+        let sun_color = self.sun_color();
+        let ambient_color = self.ambient_color();
+
+        RawLighting {
+            to_sun: [to_sun.x, to_sun.y, to_sun.z, 0.0],
+            sun_color: [sun_color.x, sun_color.y, sun_color.z, 0.0],
+            ambient_color: [ambient_color.x, ambient_color.y, ambient_color.z, 0.0],
+        }
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Must match the `LightingUniform` struct in `shader.wgsl`
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RawLighting {
+    pub to_sun: [f32; 4],
+    pub sun_color: [f32; 4],
+    /// The strength of the ambient occlusion is stored in w, see [crate::world::chunk_renderer::ChunkRenderManager::update_lighting]
+    pub ambient_color: [f32; 4],
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::rendering::day_cycle::DayCycle;
+
+    #[test]
+    fn test_sun_rises_and_sets() {
+        let at = |time_of_day: f32| DayCycle {
+            time_of_day,
+            ..DayCycle::new()
+        };
+
+        assert!(at(0.5).to_sun().y > 0.9);
+        assert!(at(0.0).to_sun().y < -0.9);
+        assert!(at(0.3).to_sun().x > 0.0 && at(0.7).to_sun().x < 0.0);
+
+        assert_eq!(at(0.0).sun_color().x, 0.0);
+        assert!(at(0.5).ambient_color().x > at(0.0).ambient_color().x);
+    }
+
+    #[test]
+    fn test_time_of_day_wraps_around() {
+        let mut day_cycle = DayCycle {
+            time_of_day: 0.9,
+            day_length: 10.0,
+            paused: false,
+        };
+
+        day_cycle.animate(Duration::from_secs(2));
+        assert!((day_cycle.time_of_day - 0.1).abs() < 1e-4);
+
+        day_cycle.paused = true;
+        day_cycle.animate(Duration::from_secs(2));
+        assert!((day_cycle.time_of_day - 0.1).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,139 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::Vector3;
+use wgpu::{include_wgsl, vertex_attr_array};
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::{RenderCtx, RenderHandle, Renderer};
+
+/// Boxes that fit into the instance buffer before it has to grow
+const INITIAL_CAPACITY: usize = 1 << 10;
+/// Every box is drawn as the two triangles of each of its faces, whose corners are generated in the vertex shader
+const VERTICES_PER_BOX: u32 = 36;
+
+/// Translucent colored boxes on top of the world for debugging, e.g. the chunks in the streaming queues.
+///
+/// The boxes are collected again every frame. They are drawn with one instanced draw call and do not write any depth,
+/// so that the boxes and the terrain behind them stay visible.
+pub struct DebugBoxes {
+    pipeline: wgpu::RenderPipeline,
+    instance_buffer: wgpu::Buffer,
+    /// Boxes of the next frame
+    instances: Vec<BoxInstance>,
+    /// Number of boxes that were uploaded for the current frame
+    num_instances: u32,
+}
+
+/// Must match the `BoxInstance` struct in `debug_boxes.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct BoxInstance {
+    min: [f32; 3],
+    max: [f32; 3],
+    color: [f32; 4],
+}
+
+impl DebugBoxes {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        Self {
+            pipeline: create_box_pipeline(ctx),
+            instance_buffer: create_instance_buffer(ctx, INITIAL_CAPACITY),
+            instances: Vec::new(),
+            num_instances: 0,
+        }
+    }
+
+    /// Recreates the pipeline and the instance buffer on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self::new(ctx);
+    }
+
+    pub fn add_box(&mut self, min: Vector3<f32>, max: Vector3<f32>, color: [f32; 4]) {
+        self.instances.push(BoxInstance {
+            min: min.into(),
+            max: max.into(),
+            color,
+        });
+    }
+
+    /// Uploads the boxes that were added since the last frame, which are drawn in this frame and then cleared
+    pub fn update(&mut self, handle: &mut RenderHandle) {
+        let capacity = (self.instance_buffer.size() / mem::size_of::<BoxInstance>() as u64) as usize;
+        if self.instances.len() > capacity {
+            self.instance_buffer = create_instance_buffer(handle.render_ctx(), self.instances.len().next_power_of_two());
+        }
+
+        handle.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.instances));
+        self.num_instances = self.instances.len() as u32;
+        self.instances.clear();
+    }
+}
+
+impl Renderer for DebugBoxes {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, _render_ctx: &RenderCtx) {
+        if self.num_instances == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        camera.bind(render_pass, 0);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..VERTICES_PER_BOX, 0..self.num_instances);
+    }
+}
+
+fn create_instance_buffer(ctx: &RenderCtx, capacity: usize) -> wgpu::Buffer {
+    ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Debug box instance buffer"),
+        size: (capacity * mem::size_of::<BoxInstance>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_box_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x4];
+
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("debug_boxes.wgsl"));
+
+    let layout = ctx.pipeline_layout("Debug box render pipeline layout", &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES], &[]);
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug box render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: mem::size_of::<BoxInstance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &ATTRIBUTES,
+                }],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                entry_point: "fs_main",
+            }),
+            primitive: Default::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
@@ -0,0 +1,170 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::Vector3;
+use itertools::iproduct;
+use wgpu::{include_wgsl, vertex_attr_array};
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::{RenderCtx, RenderHandle, Renderer};
+
+/// Vertices that fit into the vertex buffer before it has to grow
+const INITIAL_CAPACITY: usize = 1 << 12;
+
+/// Colored lines on top of the world for debugging, e.g. the outlines of chunks.
+///
+/// The lines are collected again every frame and are hidden behind the terrain in front of them.
+pub struct DebugLines {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    /// Lines of the next frame, two vertices per line
+    vertices: Vec<LineVertex>,
+    /// Number of vertices that were uploaded for the current frame
+    num_vertices: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl DebugLines {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        Self {
+            pipeline: create_line_pipeline(ctx),
+            vertex_buffer: create_vertex_buffer(ctx, INITIAL_CAPACITY),
+            vertices: Vec::new(),
+            num_vertices: 0,
+        }
+    }
+
+    /// Recreates the pipeline and the vertex buffer on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self::new(ctx);
+    }
+
+    pub fn add_line(&mut self, from: Vector3<f32>, to: Vector3<f32>, color: [f32; 4]) {
+        self.vertices.push(LineVertex {
+            position: from.into(),
+            color,
+        });
+        self.vertices.push(LineVertex {
+            position: to.into(),
+            color,
+        });
+    }
+
+    /// The twelve edges of an axis aligned box
+    pub fn add_box(&mut self, min: Vector3<f32>, max: Vector3<f32>, color: [f32; 4]) {
+        let corner = |x: bool, y: bool, z: bool| {
+            Vector3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        };
+
+        for (a, b) in iproduct!([false, true], [false, true]) {
+            self.add_line(corner(false, a, b), corner(true, a, b), color);
+            self.add_line(corner(a, false, b), corner(a, true, b), color);
+            self.add_line(corner(a, b, false), corner(a, b, true), color);
+        }
+    }
+
+    /// Lines through the voxel corners along every axis, in a cube around the voxel at the center
+    pub fn add_voxel_grid(&mut self, center: Vector3<i32>, radius: i32, color: [f32; 4]) {
+        let point = |x: i32, y: i32, z: i32| (center + Vector3::new(x, y, z)).map(|c| c as f32);
+
+        for (a, b) in iproduct!(-radius..=radius, -radius..=radius) {
+            self.add_line(point(-radius, a, b), point(radius, a, b), color);
+            self.add_line(point(a, -radius, b), point(a, radius, b), color);
+            self.add_line(point(a, b, -radius), point(a, b, radius), color);
+        }
+    }
+
+    /// Uploads the lines that were added since the last frame, which are drawn in this frame and then cleared
+    pub fn update(&mut self, handle: &mut RenderHandle) {
+        let capacity = (self.vertex_buffer.size() / mem::size_of::<LineVertex>() as u64) as usize;
+        if self.vertices.len() > capacity {
+            self.vertex_buffer = create_vertex_buffer(handle.render_ctx(), self.vertices.len().next_power_of_two());
+        }
+
+        handle.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.num_vertices = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+}
+
+impl Renderer for DebugLines {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, _render_ctx: &RenderCtx) {
+        if self.num_vertices == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        camera.bind(render_pass, 0);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
+
+fn create_vertex_buffer(ctx: &RenderCtx, capacity: usize) -> wgpu::Buffer {
+    ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Debug line vertex buffer"),
+        size: (capacity * mem::size_of::<LineVertex>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_line_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("debug_lines.wgsl"));
+
+    let layout = ctx.pipeline_layout("Debug line render pipeline layout", &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES], &[]);
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug line render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: mem::size_of::<LineVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &ATTRIBUTES,
+                }],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                entry_point: "fs_main",
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            // The faces are pushed back by their depth bias, so lines along the faces are drawn on top of them
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
@@ -0,0 +1,225 @@
+use std::mem;
+
+use bytemuck::Zeroable;
+use cgmath::SquareMatrix;
+use wgpu::{include_wgsl, BindingType};
+
+use crate::rendering::camera::Camera;
+use crate::rendering::render_targets::{RenderTargetDescriptor, RenderTargetSize};
+use crate::rendering::uniform_ring::UniformRing;
+use crate::rendering::{PostEffect, RenderCtx, RenderHandle};
+
+const DEPTH_OF_FIELD_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    ty: BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: wgpu::BufferSize::new(mem::size_of::<RawDepthOfField>() as u64),
+    },
+    visibility: wgpu::ShaderStages::FRAGMENT,
+    count: None,
+}];
+
+/// Copy of the current frame and the depth buffer
+const DEPTH_OF_FIELD_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[
+    wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 1,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Depth,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 2,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    },
+];
+
+const SCENE_TARGET: &str = "depth_of_field_scene";
+
+/// Blurs everything that is not at the focus distance, e.g. for photos.
+///
+/// The blur radius of every pixel grows with its distance from the focus plane, which is reconstructed from the depth buffer.
+/// Like the [crate::rendering::temporal::TemporalEffects], it copies the image out of the surface, which has to support being copied from.
+pub struct DepthOfField {
+    uniforms: UniformRing<RawDepthOfField>,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    /// Distance from the eye in blocks at which the image is sharp
+    pub focus_distance: f32,
+    /// Blur radius in pixels of surfaces that are infinitely far away from the focus plane, 0 disables the effect
+    pub aperture: f32,
+    /// The image is rendered at this multiple of the window size, so that the blur radius in pixels has to grow with it
+    pub pixel_scale: f32,
+}
+
+impl DepthOfField {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let layout = ctx.bind_group_layout("Depth of field bind group layout", DEPTH_OF_FIELD_BIND_GROUP_LAYOUT_ENTRIES);
+
+        ctx.render_targets.create(
+            &ctx.device,
+            SCENE_TARGET,
+            RenderTargetDescriptor {
+                label: "depth_of_field_scene_texture",
+                format: ctx.surface_format,
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+                size: RenderTargetSize::SurfaceScaled(1.0),
+            },
+            ctx.surface_size(),
+        );
+
+        Self {
+            uniforms: UniformRing::new(ctx, "Depth of field uniforms", &layout, &RawDepthOfField::zeroed()),
+            pipeline: create_depth_of_field_pipeline(ctx),
+            sampler: ctx
+                .device
+                .create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("Depth of field sampler"),
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    ..Default::default()
+                }),
+            focus_distance: 10.0,
+            aperture: 0.0,
+            pixel_scale: 1.0,
+        }
+    }
+
+    /// Recreates the uniform buffer, the pipeline and the target on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self {
+            focus_distance: self.focus_distance,
+            aperture: self.aperture,
+            pixel_scale: self.pixel_scale,
+            ..Self::new(ctx)
+        };
+    }
+
+    pub fn update(&mut self, handle: &mut RenderHandle, camera: &Camera) {
+        let eye = camera.eye_position();
+        self.uniforms.write(
+            handle,
+            &RawDepthOfField {
+                inverse_view_proj: camera
+                    .view_projection()
+                    .invert()
+                    .expect("View projection matrices are invertible")
+                    .into(),
+                eye: [eye.x, eye.y, eye.z, 0.0],
+                params: [self.focus_distance.max(0.1), self.aperture * self.pixel_scale, 0.0, 0.0],
+            },
+        );
+    }
+}
+
+impl PostEffect for DepthOfField {
+    fn apply(&self, handle: &mut RenderHandle, label: &str) {
+        if self.aperture <= 0.0 {
+            return;
+        }
+
+        let Some(scene) = handle.render_ctx.render_targets.get(SCENE_TARGET) else {
+            return;
+        };
+        if !handle.copy_surface_to(&scene) {
+            return;
+        }
+
+        let layout = handle.render_ctx.bind_group_layout(
+            "Depth of field textures bind group layout",
+            DEPTH_OF_FIELD_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES,
+        );
+        let textures = handle
+            .render_ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Depth of field textures bind group"),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&scene.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&handle.depth_texture().view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+        let mut render_pass = handle.begin_post_pass(label);
+        self.uniforms.binding().bind(&mut render_pass, 0);
+        render_pass.set_bind_group(1, &textures, &[]);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawDepthOfField {
+    inverse_view_proj: [[f32; 4]; 4],
+    eye: [f32; 4],
+    /// Focus distance and blur radius in pixels at an infinite distance from the focus plane
+    params: [f32; 4],
+}
+
+fn create_depth_of_field_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("depth_of_field.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Depth of field render pipeline layout",
+        &[
+            DEPTH_OF_FIELD_BIND_GROUP_LAYOUT_ENTRIES,
+            DEPTH_OF_FIELD_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES,
+        ],
+        &[],
+    );
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth of field render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                entry_point: "fs_main",
+            }),
+            primitive: Default::default(),
+            // Post passes have no depth attachment, so that the depth buffer can be sampled
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
@@ -0,0 +1,72 @@
+struct DepthOfFieldUniform {
+	inverse_view_proj: mat4x4<f32>,
+	eye: vec4<f32>,
+	// Focus distance and blur radius in pixels at an infinite distance from the focus plane
+	params: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> depth_of_field: DepthOfFieldUniform;
+
+@group(1) @binding(0)
+var scene: texture_2d<f32>;
+@group(1) @binding(1)
+var depth: texture_depth_2d;
+@group(1) @binding(2)
+var linear_sampler: sampler;
+
+// Samples on each of the rings of the blur disk
+const RING_SAMPLES: i32 = 8;
+const RINGS: i32 = 3;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	var POSITION_LOOKUP = array<vec2<f32>, 3>(
+		vec2(-1.0, -1.0),
+		vec2(3.0, -1.0),
+		vec2(-1.0, 3.0));
+
+	let position = POSITION_LOOKUP[vertex_index];
+
+    var out: VertexOutput;
+    out.clip_position = vec4(position, 0.0, 1.0);
+    out.uv = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return out;
+}
+
+// Blur radius in pixels of the surface at this pixel, which grows with its distance from the focus plane
+fn blur_radius(uv: vec2<f32>, pixel: vec2<i32>) -> f32 {
+	let depth = textureLoad(depth, pixel, 0);
+	let world = depth_of_field.inverse_view_proj * vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+	let distance = length(world.xyz / world.w - depth_of_field.eye.xyz);
+
+	let focus_distance = depth_of_field.params.x;
+	return depth_of_field.params.y * abs(distance - focus_distance) / max(distance, 0.0001);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let radius = blur_radius(in.uv, vec2<i32>(in.clip_position.xy));
+	let texel = radius / vec2<f32>(textureDimensions(scene));
+
+	var color = textureSampleLevel(scene, linear_sampler, in.uv, 0.0);
+	var weight = 1.0;
+	for (var ring = 1; ring <= RINGS; ring++) {
+		let ring_radius = f32(ring) / f32(RINGS);
+		for (var i = 0; i < RING_SAMPLES; i++) {
+			// Every ring is rotated by half a sample, so that the samples do not line up
+			let angle = (f32(i) + 0.5 * f32(ring)) * 6.2831853 / f32(RING_SAMPLES);
+			let offset = vec2(cos(angle), sin(angle)) * ring_radius * texel;
+			color += textureSampleLevel(scene, linear_sampler, in.uv + offset, 0.0);
+			weight += 1.0;
+		}
+	}
+
+	return color / weight;
+}
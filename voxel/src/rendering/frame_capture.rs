@@ -0,0 +1,57 @@
+#[cfg(feature = "renderdoc")]
+use log::info;
+use log::warn;
+
+/// Programmatic frame captures of RenderDoc, so that the GPU work of a frame with a rendering artifact can be inspected.
+///
+/// Captures are only possible if the engine is built with the `renderdoc` feature and launched from RenderDoc,
+/// otherwise requesting a capture only logs a warning.
+pub struct FrameCapture {
+    #[cfg(feature = "renderdoc")]
+    renderdoc: Option<renderdoc::RenderDoc<renderdoc::V110>>,
+}
+
+impl FrameCapture {
+    /// Connects to RenderDoc if it is loaded into the process. Must be created before the GPU device, so that RenderDoc can hook it.
+    #[cfg(feature = "renderdoc")]
+    pub fn new() -> Self {
+        let renderdoc = match renderdoc::RenderDoc::new() {
+            Ok(renderdoc) => {
+                info!("Connected to RenderDoc, frames can be captured with F9");
+                Some(renderdoc)
+            }
+            Err(err) => {
+                info!("RenderDoc is not available, frames cannot be captured: {err}");
+                None
+            }
+        };
+
+        Self { renderdoc }
+    }
+
+    #[cfg(not(feature = "renderdoc"))]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn is_available(&self) -> bool {
+        #[cfg(feature = "renderdoc")]
+        if self.renderdoc.is_some() {
+            return true;
+        }
+
+        false
+    }
+
+    /// Captures all GPU work of the next frame that is presented
+    pub fn capture_next_frame(&mut self) {
+        #[cfg(feature = "renderdoc")]
+        if let Some(renderdoc) = &mut self.renderdoc {
+            renderdoc.trigger_capture();
+            info!("Capturing the next frame with RenderDoc");
+            return;
+        }
+
+        warn!("Could not capture the frame, the engine has to be built with the renderdoc feature and launched from RenderDoc");
+    }
+}
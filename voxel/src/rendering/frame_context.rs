@@ -0,0 +1,78 @@
+use std::fmt;
+
+use wgpu::util::StagingBelt;
+
+use crate::rendering::gpu_timer::{GpuPassTiming, GpuTimerSlot};
+use crate::rendering::readback::Readback;
+
+/// Size of the chunks that the staging belt of every frame allocates
+const STAGING_BELT_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Resources that belong to a single frame in flight.
+///
+/// There is one frame context for every one of [FRAMES_IN_FLIGHT](crate::rendering::FRAMES_IN_FLIGHT) frames.
+/// A frame context is only reused after all previous frames that used it have been submitted,
+/// so that its resources can be reused instead of being allocated freshly every frame.
+pub struct FrameContext {
+    /// Index of this context in `0..FRAMES_IN_FLIGHT`, used to select per-frame slices of uniform buffers
+    pub index: usize,
+    /// Number of the frame that is currently recorded with this context
+    pub frame_number: u64,
+    /// Staging memory for buffer uploads that are recorded into the frame's command encoder
+    pub staging_belt: StagingBelt,
+    pub gpu_timer: Option<GpuTimerSlot>,
+    /// Readbacks that were recorded in this frame and are mapped after it is submitted
+    pub readbacks: Vec<Readback>,
+}
+
+impl FrameContext {
+    pub fn new(device: &wgpu::Device, index: usize) -> Self {
+        Self {
+            index,
+            frame_number: 0,
+            staging_belt: StagingBelt::new(STAGING_BELT_CHUNK_SIZE),
+            gpu_timer: GpuTimerSlot::is_supported(device).then(|| GpuTimerSlot::new(device)),
+            readbacks: Vec::new(),
+        }
+    }
+
+    /// Prepares this context for recording the given frame and returns the GPU timings of the frame that used it last
+    pub fn begin(&mut self, frame_number: u64, timestamp_period: f32) -> Option<Vec<GpuPassTiming>> {
+        self.frame_number = frame_number;
+
+        let gpu_timer = self.gpu_timer.as_mut()?;
+        let timings = gpu_timer.collect(timestamp_period);
+        gpu_timer.begin_frame();
+
+        timings
+    }
+
+    /// Must be called right before the frame's command encoder is finished
+    pub fn finish(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(readback) = self
+            .gpu_timer
+            .as_mut()
+            .and_then(|gpu_timer| gpu_timer.resolve(device, encoder))
+        {
+            self.readbacks.push(readback);
+        }
+        self.staging_belt.finish();
+    }
+
+    /// Must be called after the frame was submitted
+    pub fn after_submit(&mut self) {
+        self.staging_belt.recall();
+        for readback in self.readbacks.drain(..) {
+            readback.start_mapping();
+        }
+    }
+}
+
+impl fmt::Debug for FrameContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameContext")
+            .field("index", &self.index)
+            .field("frame_number", &self.frame_number)
+            .finish_non_exhaustive()
+    }
+}
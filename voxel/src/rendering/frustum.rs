@@ -0,0 +1,60 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3, Vector4};
+
+/// An axis aligned bounding box in world space
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+/// The six planes of a view frustum. Each plane is stored as `(normal, distance)` with the normal pointing into the frustum.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far plane
+    pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum planes from a view projection matrix that maps depth to the range 0..1
+    pub fn from_view_projection(view_proj: Matrix4<f32>) -> Self {
+        let row = |i: usize| view_proj.row(i);
+
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| plane / plane.truncate().magnitude());
+
+        Self { planes }
+    }
+
+    /// Checks whether an axis aligned bounding box is at least partially inside the frustum.
+    /// This test is conservative, it may return true for some boxes that are close to but outside the frustum.
+    pub fn intersects_aabb(&self, min: Vector3<f32>, max: Vector3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            let positive_vertex = Vector3::new(
+                if plane.x >= 0.0 { max.x } else { min.x },
+                if plane.y >= 0.0 { max.y } else { min.y },
+                if plane.z >= 0.0 { max.z } else { min.z },
+            );
+
+            plane.truncate().dot(positive_vertex) + plane.w >= 0.0
+        })
+    }
+
+    pub fn to_raw(&self) -> RawFrustum {
+        RawFrustum {
+            planes: self.planes.map(Into::into),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RawFrustum {
+    pub planes: [[f32; 4]; 6],
+}
@@ -0,0 +1,143 @@
+use std::mem;
+
+use crate::rendering::readback::Readback;
+
+/// Maximum number of timed passes per frame
+const MAX_TIMED_PASSES: u32 = 16;
+
+/// Timestamp queries of a single frame in flight.
+///
+/// Every timed pass writes a timestamp at its beginning and its end.
+/// After the frame is submitted the timestamps are read back and collected
+/// when this slot is used for rendering again, so that the CPU never waits for the GPU.
+pub struct GpuTimerSlot {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    /// Names of the passes that are timed in the frame that is currently recorded
+    pass_names: Vec<String>,
+    recording: bool,
+    /// Timestamps of a previous frame that have not been collected yet, with the names of their passes
+    pending: Option<(Readback, Vec<String>)>,
+}
+
+/// GPU duration of a render pass
+#[derive(Clone, Debug)]
+pub struct GpuPassTiming {
+    pub name: String,
+    /// Duration in seconds
+    pub duration: f32,
+}
+
+impl GpuTimerSlot {
+    pub fn is_supported(device: &wgpu::Device) -> bool {
+        device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("GPU timer query set"),
+                ty: wgpu::QueryType::Timestamp,
+                count: MAX_TIMED_PASSES * 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("GPU timer resolve buffer"),
+                size: MAX_TIMED_PASSES as u64 * 2 * mem::size_of::<u64>() as u64,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            pass_names: Vec::new(),
+            recording: false,
+            pending: None,
+        }
+    }
+
+    /// Returns the timings of the frame that was previously recorded into this slot, if they are available.
+    /// Must be called before this slot is used for a new frame.
+    pub fn collect(&mut self, timestamp_period: f32) -> Option<Vec<GpuPassTiming>> {
+        let data = self.pending.as_ref()?.0.try_take()?;
+        let (_, pass_names) = self.pending.take()?;
+
+        let timestamps: Vec<u64> = data
+            .ok()?
+            .chunks_exact(mem::size_of::<u64>())
+            .map(|bytes| {
+                u64::from_le_bytes(
+                    bytes
+                        .try_into()
+                        .expect("chunk to have the size of an u64"),
+                )
+            })
+            .collect();
+        let timings = pass_names
+            .into_iter()
+            .zip(timestamps.chunks_exact(2))
+            .map(|(name, ticks)| GpuPassTiming {
+                name,
+                duration: ticks[1].wrapping_sub(ticks[0]) as f32 * timestamp_period * 1e-9,
+            })
+            .collect();
+
+        Some(timings)
+    }
+
+    /// Starts recording a new frame. Nothing is timed if the timestamps of the last frame are still being read back.
+    pub fn begin_frame(&mut self) {
+        self.pass_names.clear();
+        self.recording = self.pending.is_none();
+    }
+
+    /// Returns the timestamp writes for a new timed render pass
+    pub fn render_pass_writes(&mut self, name: &str) -> Option<wgpu::RenderPassTimestampWrites> {
+        let index = self.next_query_index(name)?;
+
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    /// Returns the timestamp writes for a new timed compute pass
+    pub fn compute_pass_writes(&mut self, name: &str) -> Option<wgpu::ComputePassTimestampWrites> {
+        let index = self.next_query_index(name)?;
+
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    fn next_query_index(&mut self, name: &str) -> Option<u32> {
+        if !self.recording || self.pass_names.len() as u32 >= MAX_TIMED_PASSES {
+            return None;
+        }
+
+        let index = self.pass_names.len() as u32 * 2;
+        self.pass_names.push(name.to_owned());
+        Some(index)
+    }
+
+    /// Resolves the timestamps of the current frame and records their readback
+    pub fn resolve(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) -> Option<Readback> {
+        if !mem::take(&mut self.recording) || self.pass_names.is_empty() {
+            return None;
+        }
+
+        let num_queries = self.pass_names.len() as u32 * 2;
+        encoder.resolve_query_set(&self.query_set, 0..num_queries, &self.resolve_buffer, 0);
+        let readback = Readback::from_buffer(
+            device,
+            encoder,
+            &self.resolve_buffer,
+            0,
+            num_queries as u64 * mem::size_of::<u64>() as u64,
+        );
+
+        self.pending = Some((readback.clone(), mem::take(&mut self.pass_names)));
+        Some(readback)
+    }
+}
@@ -0,0 +1,99 @@
+use strum_macros::EnumIter;
+
+use crate::rendering::camera::Camera;
+use crate::rendering::{PostEffect, RenderHandle, Renderer};
+
+/// Layers of a frame, which are drawn in the order in which they are declared
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, EnumIter)]
+pub enum RenderLayer {
+    /// Terrain, water, sky and everything else that is part of the world itself
+    World,
+    Entities,
+    Particles,
+    /// Debug visualizations, which are drawn on top of the world
+    Debug,
+    /// Screen space effects of the camera, which must not affect the UI
+    PostProcess,
+    Ui,
+}
+
+/// Renderers of a single frame, ordered by their layer
+#[derive(Default)]
+pub struct RenderLayers<'a> {
+    renderers: Vec<(RenderLayer, &'static str, LayerEntry<'a>)>,
+}
+
+enum LayerEntry<'a> {
+    /// Draws into its own render pass with the depth buffer attached
+    Pass(&'a dyn Renderer),
+    Effect(&'a dyn PostEffect),
+}
+
+impl<'a> RenderLayers<'a> {
+    /// Registers a renderer, which is drawn after all renderers that were registered before it in the same layer
+    pub fn add(&mut self, layer: RenderLayer, label: &'static str, renderer: &'a dyn Renderer) {
+        self.renderers
+            .push((layer, label, LayerEntry::Pass(renderer)));
+    }
+
+    /// Registers a post effect, which is applied after all renderers that were registered before it in the same layer
+    pub fn add_effect(&mut self, layer: RenderLayer, label: &'static str, effect: &'a dyn PostEffect) {
+        self.renderers
+            .push((layer, label, LayerEntry::Effect(effect)));
+    }
+
+    /// Labels of the renderers of a layer in their draw order
+    pub fn labels(&self, layer: RenderLayer) -> impl Iterator<Item = &'static str> + '_ {
+        self.renderers
+            .iter()
+            .filter(move |(renderer_layer, ..)| *renderer_layer == layer)
+            .map(|(_, label, _)| *label)
+    }
+
+    /// Draws every renderer of the layer into its own render pass and applies the effects in between
+    pub fn render(&self, handle: &mut RenderHandle, layer: RenderLayer, camera: &Camera) {
+        for (_, label, entry) in self
+            .renderers
+            .iter()
+            .filter(|(renderer_layer, ..)| *renderer_layer == layer)
+        {
+            match *entry {
+                LayerEntry::Pass(renderer) => handle.render(label, renderer, camera),
+                LayerEntry::Effect(effect) => effect.apply(handle, label),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use crate::rendering::layers::{RenderLayer, RenderLayers};
+    use crate::rendering::uniform_ring::UniformBinding;
+    use crate::rendering::{RenderCtx, Renderer};
+
+    struct NoopRenderer;
+
+    impl Renderer for NoopRenderer {
+        fn render<'a>(&'a self, _: &mut wgpu::RenderPass<'a>, _: UniformBinding<'a>, _: &RenderCtx) {}
+    }
+
+    #[test]
+    fn test_layers_keep_registration_order() {
+        let renderer = NoopRenderer;
+        let mut layers = RenderLayers::default();
+        layers.add(RenderLayer::Ui, "ui", &renderer);
+        layers.add(RenderLayer::World, "chunks", &renderer);
+        layers.add(RenderLayer::World, "water", &renderer);
+
+        assert_eq!(
+            layers
+                .labels(RenderLayer::World)
+                .collect::<Vec<_>>(),
+            ["chunks", "water"]
+        );
+        assert_eq!(layers.labels(RenderLayer::Ui).collect::<Vec<_>>(), ["ui"]);
+        assert_eq!(RenderLayer::iter().last(), Some(RenderLayer::Ui));
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use wgpu::{BindGroupLayout, BindGroupLayoutEntry, PipelineLayout, PushConstantRange};
+
+/// Cache for bind group layouts and pipeline layouts, keyed by their descriptors.
+///
+/// Subsystems request layouts by their entries instead of passing layout objects through constructors,
+/// so that equal layouts are only created once and are shared by all pipelines that use them.
+#[derive(Debug, Default)]
+pub struct LayoutCache {
+    bind_group_layouts: Mutex<HashMap<Vec<BindGroupLayoutEntry>, Arc<BindGroupLayout>>>,
+    pipeline_layouts: Mutex<HashMap<PipelineLayoutKey, Arc<PipelineLayout>>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct PipelineLayoutKey {
+    bind_group_layouts: Vec<Vec<BindGroupLayoutEntry>>,
+    push_constant_ranges: Vec<PushConstantRange>,
+}
+
+impl LayoutCache {
+    /// Returns the bind group layout with the given entries, creating it if it does not exist yet.
+    /// The label is only used when the layout is created.
+    pub fn bind_group_layout(&self, device: &wgpu::Device, label: &str, entries: &[BindGroupLayoutEntry]) -> Arc<BindGroupLayout> {
+        let mut layouts = self
+            .bind_group_layouts
+            .lock()
+            .expect("Layout cache lock to not be poisoned");
+
+        Arc::clone(
+            layouts
+                .entry(entries.to_vec())
+                .or_insert_with(|| {
+                    Arc::new(device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: Some(label),
+                        entries,
+                    }))
+                }),
+        )
+    }
+
+    /// Returns the pipeline layout with the given bind group layouts and push constant ranges, creating it if it does not exist yet.
+    /// The label is only used when the layout is created.
+    pub fn pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        bind_group_layouts: &[&[BindGroupLayoutEntry]],
+        push_constant_ranges: &[PushConstantRange],
+    ) -> Arc<PipelineLayout> {
+        let key = PipelineLayoutKey {
+            bind_group_layouts: bind_group_layouts
+                .iter()
+                .map(|entries| entries.to_vec())
+                .collect(),
+            push_constant_ranges: push_constant_ranges.to_vec(),
+        };
+
+        if let Some(layout) = self
+            .pipeline_layouts
+            .lock()
+            .expect("Layout cache lock to not be poisoned")
+            .get(&key)
+        {
+            return Arc::clone(layout);
+        }
+
+        // The bind group layouts are requested before locking the pipeline layouts, as they are cached separately
+        let group_layouts: Vec<_> = bind_group_layouts
+            .iter()
+            .map(|entries| self.bind_group_layout(device, label, entries))
+            .collect();
+        let group_layout_refs: Vec<_> = group_layouts.iter().map(Arc::as_ref).collect();
+
+        let layout = Arc::new(device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &group_layout_refs,
+            push_constant_ranges,
+        }));
+
+        self.pipeline_layouts
+            .lock()
+            .expect("Layout cache lock to not be poisoned")
+            .insert(key, Arc::clone(&layout));
+
+        layout
+    }
+}
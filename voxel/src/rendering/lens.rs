@@ -0,0 +1,217 @@
+use std::mem;
+use std::time::Duration;
+
+use bytemuck::Zeroable;
+use wgpu::{include_wgsl, BindingType};
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::{UniformBinding, UniformRing};
+use crate::rendering::{RenderCtx, RenderHandle, Renderer};
+
+const LENS_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    ty: BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: wgpu::BufferSize::new(mem::size_of::<RawLens>() as u64),
+    },
+    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+    count: None,
+}];
+
+/// Exposure when no sky is visible at all, e.g. deep inside of a cave
+const MAX_EXPOSURE: f32 = 1.8;
+/// Time constant with which the exposure adapts to darker surroundings
+const DARK_ADAPTATION: Duration = Duration::from_millis(2500);
+/// Time constant with which the exposure adapts to brighter surroundings, which is faster than adapting to the dark
+const BRIGHT_ADAPTATION: Duration = Duration::from_millis(600);
+/// Time constant with which the glare fades in and out when the sun is covered or uncovered
+const GLARE_FADE: Duration = Duration::from_millis(150);
+
+/// Post pass on the final image that brightens it after moving into darker surroundings, applies the color grading
+/// of the environment and draws glare around the sun.
+///
+/// The surface has no HDR color target, so the exposure and the tint are applied by multiplicative blending with the rendered image.
+pub struct LensEffects {
+    uniforms: UniformRing<RawLens>,
+    exposure_pipeline: wgpu::RenderPipeline,
+    glare_pipeline: wgpu::RenderPipeline,
+    pub enabled: bool,
+    /// Hides the glare and adapts to brighter surroundings as slowly as to darker ones, see [crate::accessibility::Accessibility]
+    pub reduced_flashing: bool,
+    /// Multiplies the adapted exposure, e.g. to brighten or darken a photo
+    pub exposure_bias: f32,
+    /// Hides the glare immediately, e.g. while a photo is rendered in tiles, which would each draw the glare around their own center
+    pub hide_glare: bool,
+    exposure: f32,
+    /// Visibility of the sun glare from 0.0 to 1.0
+    glare: f32,
+}
+
+impl LensEffects {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let layout = ctx.bind_group_layout("Lens bind group layout", LENS_BIND_GROUP_LAYOUT_ENTRIES);
+        let (exposure_pipeline, glare_pipeline) = create_lens_pipelines(ctx);
+
+        Self {
+            uniforms: UniformRing::new(ctx, "Lens uniforms", &layout, &RawLens::zeroed()),
+            exposure_pipeline,
+            glare_pipeline,
+            enabled: true,
+            reduced_flashing: false,
+            exposure_bias: 1.0,
+            hide_glare: false,
+            exposure: 1.0,
+            glare: 0.0,
+        }
+    }
+
+    /// Recreates the uniform buffer and the pipelines on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        let (exposure, glare) = (self.exposure, self.glare);
+        *self = Self {
+            enabled: self.enabled,
+            reduced_flashing: self.reduced_flashing,
+            exposure_bias: self.exposure_bias,
+            hide_glare: self.hide_glare,
+            exposure,
+            glare,
+            ..Self::new(ctx)
+        };
+    }
+
+    /// Adapts the exposure to the fraction of the sky that is visible from the eye and fades the glare depending on whether the sun is visible.
+    /// The tint is the color grading of the current environment, which is already blended between environments.
+    pub fn update(&mut self, handle: &mut RenderHandle, dt: Duration, sky_visibility: f32, sun_visible: bool, tint: [f32; 3]) {
+        let target_exposure = MAX_EXPOSURE - (MAX_EXPOSURE - 1.0) * sky_visibility.clamp(0.0, 1.0);
+        let adaptation = if target_exposure > self.exposure || self.reduced_flashing {
+            DARK_ADAPTATION
+        } else {
+            BRIGHT_ADAPTATION
+        };
+        self.exposure = approach(self.exposure, target_exposure, dt, adaptation);
+        let glare_visible = sun_visible && !self.reduced_flashing;
+        self.glare = approach(self.glare, if glare_visible { 1.0 } else { 0.0 }, dt, GLARE_FADE);
+
+        let (width, height) = handle.render_ctx.surface_size();
+        self.uniforms.write(
+            handle,
+            &RawLens {
+                exposure: self.exposure * self.exposure_bias,
+                glare: if self.hide_glare { 0.0 } else { self.glare },
+                aspect: width as f32 / height as f32,
+                _padding: 0.0,
+                tint: [tint[0], tint[1], tint[2], 0.0],
+            },
+        );
+    }
+}
+
+/// Moves `current` towards `target` exponentially with the given time constant
+fn approach(current: f32, target: f32, dt: Duration, time_constant: Duration) -> f32 {
+    let t = 1.0 - (-dt.as_secs_f32() / time_constant.as_secs_f32()).exp();
+    current + (target - current) * t
+}
+
+impl Renderer for LensEffects {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, _render_ctx: &RenderCtx) {
+        if !self.enabled {
+            return;
+        }
+
+        camera.bind(render_pass, 0);
+        self.uniforms.binding().bind(render_pass, 1);
+
+        render_pass.set_pipeline(&self.exposure_pipeline);
+        render_pass.draw(0..3, 0..1);
+        render_pass.set_pipeline(&self.glare_pipeline);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawLens {
+    exposure: f32,
+    glare: f32,
+    /// Width divided by height of the surface, so that the glare is round
+    aspect: f32,
+    _padding: f32,
+    tint: [f32; 4],
+}
+
+fn create_lens_pipelines(ctx: &RenderCtx) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("lens.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Lens render pipeline layout",
+        &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES, LENS_BIND_GROUP_LAYOUT_ENTRIES],
+        &[],
+    );
+
+    // Keeps the alpha of the rendered image, so that transparent windows stay transparent
+    let keep_alpha = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    let create_pipeline = |label: &str, entry_point: &str, color: wgpu::BlendComponent| {
+        ctx.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    buffers: &[],
+                    entry_point: "vs_main",
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: Some(wgpu::BlendState { color, alpha: keep_alpha }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    entry_point,
+                }),
+                primitive: Default::default(),
+                // Covers the whole screen regardless of depth
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+            })
+    };
+
+    (
+        // 2 * source * destination, so that the image can be brightened up to twice as much
+        create_pipeline(
+            "Exposure render pipeline",
+            "fs_exposure",
+            wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Dst,
+                dst_factor: wgpu::BlendFactor::Src,
+                operation: wgpu::BlendOperation::Add,
+            },
+        ),
+        // The glare is added on top of the image
+        create_pipeline(
+            "Glare render pipeline",
+            "fs_glare",
+            wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        ),
+    )
+}
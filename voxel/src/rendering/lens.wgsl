@@ -0,0 +1,71 @@
+struct CameraUniform {
+	position: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+struct LensUniform {
+	exposure: f32,
+	glare: f32,
+	aspect: f32,
+	tint: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<uniform> lens: LensUniform;
+
+// Matches the light direction of the chunk shader
+const SUN_DIRECTION: vec3<f32> = vec3(1.0, 0.5, 0.7);
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) screen_position: vec2<f32>,
+    @location(1) sun_position: vec2<f32>,
+    @location(2) sun_visibility: f32,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	var POSITION_LOOKUP = array<vec2<f32>, 3>(
+		vec2(-1.0, -1.0),
+		vec2(3.0, -1.0),
+		vec2(-1.0, 3.0));
+
+	let position = POSITION_LOOKUP[vertex_index];
+	// Directions are not affected by the translation of the view
+	let sun_clip = camera.view_proj * vec4(normalize(SUN_DIRECTION), 0.0);
+
+    var out: VertexOutput;
+    out.clip_position = vec4(position, 0.0, 1.0);
+    out.screen_position = position;
+    out.sun_position = sun_clip.xy / max(sun_clip.w, 0.0001);
+    out.sun_visibility = select(0.0, lens.glare, sun_clip.w > 0.0);
+    return out;
+}
+
+// Multiplied with the image twice by the blend state of the exposure pipeline
+@fragment
+fn fs_exposure(in: VertexOutput) -> @location(0) vec4<f32> {
+	return vec4(0.5 * lens.exposure * lens.tint.rgb, 0.0);
+}
+
+// Glow around the sun and ghost sprites along the line from the sun through the center of the screen
+@fragment
+fn fs_glare(in: VertexOutput) -> @location(0) vec4<f32> {
+	let aspect = vec2(lens.aspect, 1.0);
+	let glow = 0.5 * exp(-6.0 * length((in.screen_position - in.sun_position) * aspect));
+
+	var ghosts = 0.0;
+	for (var i = 1; i < 4; i++) {
+		let ghost_position = in.sun_position * (1.0 - 0.7 * f32(i));
+		let radius = 0.03 * f32(i);
+		let distance = length((in.screen_position - ghost_position) * aspect);
+		ghosts += 0.06 * (1.0 - smoothstep(radius, radius * 1.5, distance));
+	}
+
+	let color = vec3(1.0, 0.92, 0.75) * glow + vec3(0.6, 0.8, 1.0) * ghosts;
+	return vec4(color * in.sun_visibility, 0.0);
+}
@@ -0,0 +1,317 @@
+use std::mem;
+
+use bytemuck::Zeroable;
+use cgmath::{Array, EuclideanSpace, SquareMatrix, Vector3};
+use wgpu::{include_wgsl, BindingType};
+
+use crate::rendering::camera::Camera;
+use crate::rendering::render_targets::{RenderTargetDescriptor, RenderTargetSize};
+use crate::rendering::uniform_ring::UniformRing;
+use crate::rendering::{PostEffect, RenderCtx, RenderHandle};
+use crate::world::chunk_manager::ChunkManager;
+use crate::world::chunk_renderer::light_bounce::{BounceGrid, CELL_SIZE};
+use crate::world::location::ChunkLocation;
+use crate::world::CHUNK_SIZE;
+
+/// Chunks along each side of the grid around the camera through which the bounce is flooded
+const GRID_CHUNKS: usize = 4;
+/// The grid is assembled and flooded again after this many frames, or as soon as the camera enters another chunk
+const BOUNCE_INTERVAL_FRAMES: u32 = 30;
+
+const LIGHT_BOUNCE_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    ty: BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: wgpu::BufferSize::new(mem::size_of::<RawLightBounce>() as u64),
+    },
+    visibility: wgpu::ShaderStages::FRAGMENT,
+    count: None,
+}];
+
+/// Copy of the current frame, the depth buffer and the flooded light of the grid
+const LIGHT_BOUNCE_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[
+    wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 1,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Depth,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 2,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D3,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 3,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    },
+];
+
+const SCENE_TARGET: &str = "light_bounce_scene";
+
+/// Coarse global illumination without ray tracing, which lights the frame with one bounce of the sky light.
+///
+/// The voxels of the chunks around the camera are averaged into a [BounceGrid], whose flooded light is uploaded as a 3D texture.
+/// Every pixel is multiplied with the light of the grid at its position, so caves and overhangs are darkened and softly lit in
+/// the colors of the surfaces near their openings. Like the [crate::rendering::depth_of_field::DepthOfField], it copies the image
+/// out of the surface, which has to support being copied from.
+pub struct LightBounce {
+    uniforms: UniformRing<RawLightBounce>,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    grid: BounceGrid,
+    /// Light of every cell of the grid, divided by 2 so that light up to twice the sky light fits into its format
+    light_texture: wgpu::Texture,
+    light_view: wgpu::TextureView,
+    /// Chunk at the minimum of the grid when it was last flooded
+    origin: Option<ChunkLocation>,
+    frames_until_flood: u32,
+    pub enabled: bool,
+}
+
+impl LightBounce {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let layout = ctx.bind_group_layout("Light bounce bind group layout", LIGHT_BOUNCE_BIND_GROUP_LAYOUT_ENTRIES);
+
+        ctx.render_targets.create(
+            &ctx.device,
+            SCENE_TARGET,
+            RenderTargetDescriptor {
+                label: "light_bounce_scene_texture",
+                format: ctx.surface_format,
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+                size: RenderTargetSize::SurfaceScaled(1.0),
+            },
+            ctx.surface_size(),
+        );
+
+        let grid = BounceGrid::new(GRID_CHUNKS);
+        let light_texture = ctx
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Light bounce texture"),
+                size: wgpu::Extent3d {
+                    width: grid.size() as u32,
+                    height: grid.size() as u32,
+                    depth_or_array_layers: grid.size() as u32,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+        let light_view = light_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            uniforms: UniformRing::new(ctx, "Light bounce uniforms", &layout, &RawLightBounce::zeroed()),
+            pipeline: create_light_bounce_pipeline(ctx),
+            sampler: ctx
+                .device
+                .create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("Light bounce sampler"),
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    ..Default::default()
+                }),
+            grid,
+            light_texture,
+            light_view,
+            origin: None,
+            frames_until_flood: 0,
+            enabled: false,
+        }
+    }
+
+    /// Recreates the uniform buffer, the pipeline, the textures and the target on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self {
+            enabled: self.enabled,
+            ..Self::new(ctx)
+        };
+    }
+
+    /// Floods the grid around the camera again if it is due and writes the uniforms of this frame
+    pub fn update(&mut self, handle: &mut RenderHandle, camera: &Camera, chunk_manager: &ChunkManager) {
+        if !self.enabled {
+            self.origin = None;
+            return;
+        }
+
+        let eye = camera.eye_position();
+        let origin = ChunkLocation::from_world_location_f32(eye.to_vec()) - ChunkLocation::new(Vector3::from_value(GRID_CHUNKS as i32 / 2));
+        if self.frames_until_flood == 0 || self.origin != Some(origin) {
+            chunk_manager.add_bounce_cells(&mut self.grid, origin);
+            self.upload_light(handle.render_ctx, &self.grid.propagate());
+            self.origin = Some(origin);
+            self.frames_until_flood = BOUNCE_INTERVAL_FRAMES;
+        }
+        self.frames_until_flood -= 1;
+
+        let grid_min = origin.to_world_location_f32();
+        self.uniforms.write(
+            handle,
+            &RawLightBounce {
+                inverse_view_proj: camera
+                    .view_projection()
+                    .invert()
+                    .expect("View projection matrices are invertible")
+                    .into(),
+                eye: [eye.x, eye.y, eye.z, 0.0],
+                grid_min: [grid_min.x, grid_min.y, grid_min.z, 0.0],
+                params: [(GRID_CHUNKS * CHUNK_SIZE) as f32, CELL_SIZE as f32, 0.0, 0.0],
+            },
+        );
+    }
+
+    fn upload_light(&self, ctx: &RenderCtx, light: &[Vector3<f32>]) {
+        let texels: Vec<u8> = light
+            .iter()
+            .flat_map(|light| {
+                let [r, g, b]: [f32; 3] = (*light * 0.5).into();
+                [r, g, b, 1.0].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect();
+
+        let size = self.grid.size() as u32;
+        ctx.queue.write_texture(
+            self.light_texture.as_image_copy(),
+            &texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size),
+                rows_per_image: Some(size),
+            },
+            wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: size,
+            },
+        );
+    }
+}
+
+impl PostEffect for LightBounce {
+    fn apply(&self, handle: &mut RenderHandle, label: &str) {
+        if !self.enabled || self.origin.is_none() {
+            return;
+        }
+
+        let Some(scene) = handle.render_ctx.render_targets.get(SCENE_TARGET) else {
+            return;
+        };
+        if !handle.copy_surface_to(&scene) {
+            return;
+        }
+
+        let layout = handle.render_ctx.bind_group_layout(
+            "Light bounce textures bind group layout",
+            LIGHT_BOUNCE_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES,
+        );
+        let textures = handle
+            .render_ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Light bounce textures bind group"),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&scene.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&handle.depth_texture().view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&self.light_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+        let mut render_pass = handle.begin_post_pass(label);
+        self.uniforms.binding().bind(&mut render_pass, 0);
+        render_pass.set_bind_group(1, &textures, &[]);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawLightBounce {
+    inverse_view_proj: [[f32; 4]; 4],
+    eye: [f32; 4],
+    /// World position of the minimum corner of the grid
+    grid_min: [f32; 4],
+    /// Size of the grid and of its cells in blocks
+    params: [f32; 4],
+}
+
+fn create_light_bounce_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("light_bounce.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Light bounce render pipeline layout",
+        &[
+            LIGHT_BOUNCE_BIND_GROUP_LAYOUT_ENTRIES,
+            LIGHT_BOUNCE_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES,
+        ],
+        &[],
+    );
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Light bounce render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                entry_point: "fs_main",
+            }),
+            primitive: Default::default(),
+            // Post passes have no depth attachment, so that the depth buffer can be sampled
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
@@ -0,0 +1,70 @@
+struct LightBounceUniform {
+	inverse_view_proj: mat4x4<f32>,
+	eye: vec4<f32>,
+	// World position of the minimum corner of the grid
+	grid_min: vec4<f32>,
+	// Size of the grid and of its cells in blocks
+	params: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> light_bounce: LightBounceUniform;
+
+@group(1) @binding(0)
+var scene: texture_2d<f32>;
+@group(1) @binding(1)
+var depth: texture_depth_2d;
+@group(1) @binding(2)
+var bounce_light: texture_3d<f32>;
+@group(1) @binding(3)
+var linear_sampler: sampler;
+
+// Light of the darkest caves, so that they do not turn completely black
+const BOUNCE_MIN_LIGHT: f32 = 0.3;
+// Fraction of the grid at each of its sides over which the effect fades out, so that the edge of the grid is not visible
+const EDGE_FADE: f32 = 0.125;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	var POSITION_LOOKUP = array<vec2<f32>, 3>(
+		vec2(-1.0, -1.0),
+		vec2(3.0, -1.0),
+		vec2(-1.0, 3.0));
+
+	let position = POSITION_LOOKUP[vertex_index];
+
+    var out: VertexOutput;
+    out.clip_position = vec4(position, 0.0, 1.0);
+    out.uv = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let color = textureSampleLevel(scene, linear_sampler, in.uv, 0.0);
+	let depth = textureLoad(depth, vec2<i32>(in.clip_position.xy), 0);
+	if depth >= 1.0 {
+		// The sky is not lit by the grid
+		return color;
+	}
+
+	let world = light_bounce.inverse_view_proj * vec4(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0, depth, 1.0);
+	let surface = world.xyz / world.w;
+	// Surfaces are lit by the open cell in front of them instead of the cell of their own voxels
+	let position = surface + normalize(light_bounce.eye.xyz - surface) * light_bounce.params.y * 0.5;
+	let uvw = (position - light_bounce.grid_min.xyz) / light_bounce.params.x;
+	let edge = min(min(uvw.x, uvw.y), min(uvw.z, min(min(1.0 - uvw.x, 1.0 - uvw.y), 1.0 - uvw.z)));
+	if edge <= 0.0 {
+		return color;
+	}
+
+	let light = textureSampleLevel(bounce_light, linear_sampler, uvw, 0.0).rgb * 2.0;
+	let factor = mix(vec3(1.0), clamp(light, vec3(BOUNCE_MIN_LIGHT), vec3(1.0)), clamp(edge / EDGE_FADE, 0.0, 1.0));
+	return vec4(color.rgb * factor, color.a);
+}
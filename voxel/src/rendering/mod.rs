@@ -1,17 +1,57 @@
 use std::default::Default;
 use std::mem::ManuallyDrop;
 use std::ops::{Deref, DerefMut};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 
-use wgpu::{PresentMode, StoreOp, TextureFormat};
+use log::{error, warn};
+use wgpu::{CompositeAlphaMode, PresentMode, StoreOp, TextureFormat};
 use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
 use crate::rendering::camera::Camera;
+use crate::rendering::frame_context::FrameContext;
+use crate::rendering::gpu_timer::GpuPassTiming;
+use crate::rendering::layout_cache::LayoutCache;
+use crate::rendering::readback::{Readback, TextureReadback};
+use crate::rendering::render_targets::{RenderTargetDescriptor, RenderTargetSize, RenderTargets};
 use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::UniformBinding;
 
 pub mod camera;
+pub mod clouds;
+pub mod day_cycle;
+pub mod debug_boxes;
+pub mod debug_lines;
+pub mod depth_of_field;
+pub mod frame_capture;
+pub mod frame_context;
+pub mod frustum;
+pub mod gpu_timer;
+pub mod layers;
+pub mod layout_cache;
+pub mod lens;
+pub mod light_bounce;
+pub mod photo;
+pub mod readback;
+pub mod render_targets;
+pub mod shader_manager;
+pub mod shadow_cascades;
+pub mod temporal;
 pub mod texture;
+pub mod thumbnail;
+pub mod uniform_ring;
+
+/// Number of frames that the CPU may record ahead of the GPU. Per-frame resources are allocated once for every frame in flight.
+pub const FRAMES_IN_FLIGHT: usize = 2;
+
+/// Background color of opaque windows, matches the fog color in the chunk shader
+const SKY_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.4941,
+    g: 0.6627,
+    b: 1.0,
+    a: 1.0,
+};
 
 pub trait HasBufferLayout {
     fn layout<'a>() -> wgpu::VertexBufferLayout<'a>;
@@ -22,20 +62,40 @@ pub struct RenderHandle<'a> {
     encoder: ManuallyDrop<wgpu::CommandEncoder>,
     target_texture: ManuallyDrop<wgpu::SurfaceTexture>,
     target_texture_view: wgpu::TextureView,
+    /// Depth texture at the time the frame was started, so that a resize during the frame does not affect it
+    depth_texture: Arc<Texture>,
     clear_before_next_render: bool,
+    frame: MutexGuard<'a, FrameContext>,
 }
 
 #[derive(Debug)]
 pub struct RenderCtx {
+    /// Kept to request a new device from after the device was lost
+    instance: wgpu::Instance,
     pub surface: wgpu::Surface,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    pub surface_config: Mutex<wgpu::SurfaceConfiguration>,
-    depth_texture: Mutex<Texture>,
+    /// Format of the surface, which never changes after creation
+    pub surface_format: TextureFormat,
+    /// Only changed through `&mut self`, so that frames read it without locking
+    surface_config: wgpu::SurfaceConfiguration,
+    pub downlevel_flags: wgpu::DownlevelFlags,
+    pub layouts: LayoutCache,
+    /// GPU durations of the render passes of the most recent frame whose timings have been read back
+    pub gpu_timings: Mutex<Vec<GpuPassTiming>>,
+    pub render_targets: RenderTargets,
+    /// Color that the surface is cleared with at the start of every frame
+    clear_color: wgpu::Color,
+    frames: Vec<Mutex<FrameContext>>,
+    frame_number: AtomicU64,
+    /// Set by the error handler of the device once it is lost. All GPU resources have to be recreated with a new context then.
+    device_lost: Arc<AtomicBool>,
 }
 
 impl RenderCtx {
-    pub async fn new(window: &Window, enable_vsync: bool) -> Self {
+    /// Creates a render context for the window.
+    /// If `transparent` is set, the surface is composited with whatever is behind the window wherever nothing is rendered.
+    pub async fn new(window: &Window, enable_vsync: bool, transparent: bool) -> Self {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -48,29 +108,7 @@ impl RenderCtx {
         // This is safe because RenderState owns both
         let surface = unsafe { instance.create_surface(&window) }.expect("WGPU failed to create a surface from the window");
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .expect("WGPU could not find a compatible adapter");
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::PUSH_CONSTANTS,
-                    limits: wgpu::Limits {
-                        max_push_constant_size: 12,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                },
-                None,
-            )
-            .await
-            .expect("Could not request device and queue");
+        let (adapter, device, queue, device_lost) = request_device(&instance, &surface).await;
 
         let surface_capabilities = surface.get_capabilities(&adapter);
         let surface_format = surface_capabilities
@@ -78,57 +116,174 @@ impl RenderCtx {
             .iter()
             .copied()
             .find(TextureFormat::is_srgb)
-            .expect("Could not find a surface capability that supports sRGB");
+            .unwrap_or_else(|| {
+                // Some GL stacks only expose linear formats. Shaders encode their output to sRGB themselves then.
+                let format = surface_capabilities.formats[0];
+                warn!("The surface does not support an sRGB format, falling back to {format:?} with gamma correction in the shaders");
+                format
+            });
 
         let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Copying from the surface is only used for screenshots, so it is not required
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | (surface_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: if enable_vsync {
-                PresentMode::AutoVsync
-            } else {
-                PresentMode::AutoNoVsync
-            },
-            alpha_mode: surface_capabilities.alpha_modes[0],
+            present_mode: present_mode(enable_vsync),
+            alpha_mode: choose_alpha_mode(&surface_capabilities.alpha_modes, transparent),
             view_formats: Vec::new(),
         };
 
         surface.configure(&device, &surface_config);
 
-        let depth_texture = Texture::new_depth_texture(&device, &surface_config);
+        let clear_color = if surface_config.alpha_mode == CompositeAlphaMode::Opaque {
+            if surface_format.is_srgb() {
+                SKY_COLOR
+            } else {
+                linear_to_srgb(SKY_COLOR)
+            }
+        } else {
+            wgpu::Color::TRANSPARENT
+        };
 
-        Self {
+        let surface_size = (surface_config.width, surface_config.height);
+        let mut render_ctx = Self {
+            instance,
             surface,
             device,
             queue,
-            surface_config: Mutex::new(surface_config),
-            depth_texture: Mutex::new(depth_texture),
-        }
+            surface_format,
+            surface_config,
+            downlevel_flags: adapter.get_downlevel_capabilities().flags,
+            layouts: LayoutCache::default(),
+            gpu_timings: Mutex::new(Vec::new()),
+            render_targets: RenderTargets::default(),
+            clear_color,
+            frames: Vec::new(),
+            frame_number: AtomicU64::new(0),
+            device_lost,
+        };
+        render_ctx.create_frame_resources(surface_size);
+
+        render_ctx
+    }
+
+    /// Creates the depth target and the resources of every frame in flight
+    fn create_frame_resources(&mut self, surface_size: (u32, u32)) {
+        self.render_targets.create(
+            &self.device,
+            RenderTargets::DEPTH,
+            RenderTargetDescriptor {
+                label: "depth_texture",
+                format: Texture::DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                size: RenderTargetSize::SurfaceScaled(1.0),
+            },
+            surface_size,
+        );
+
+        self.frames = (0..FRAMES_IN_FLIGHT)
+            .map(|index| Mutex::new(FrameContext::new(&self.device, index)))
+            .collect();
+    }
+
+    /// Whether the device was lost, e.g. because of a driver crash or reset
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Replaces the lost device with a new one and configures the surface for it.
+    /// The surface itself is kept, because a window can only be presented to by one surface at a time.
+    /// Everything that was created with the old device, including the render targets, has to be created again afterwards.
+    pub async fn recreate_device(&mut self) {
+        // Resources of the lost device are released before the new device is requested
+        self.frames.clear();
+        self.render_targets = RenderTargets::default();
+        self.layouts = LayoutCache::default();
+        self.gpu_timings = Mutex::new(Vec::new());
+
+        let (adapter, device, queue, device_lost) = request_device(&self.instance, &self.surface).await;
+        self.device = device;
+        self.queue = queue;
+        self.device_lost = device_lost;
+        self.downlevel_flags = adapter.get_downlevel_capabilities().flags;
+
+        let surface_size = self.surface_size();
+        self.surface
+            .configure(&self.device, &self.surface_config);
+        self.create_frame_resources(surface_size);
+    }
+
+    pub fn bind_group_layout(&self, label: &str, entries: &[wgpu::BindGroupLayoutEntry]) -> Arc<wgpu::BindGroupLayout> {
+        self.layouts
+            .bind_group_layout(&self.device, label, entries)
+    }
+
+    pub fn pipeline_layout(
+        &self,
+        label: &str,
+        bind_group_layouts: &[&[wgpu::BindGroupLayoutEntry]],
+        push_constant_ranges: &[wgpu::PushConstantRange],
+    ) -> Arc<wgpu::PipelineLayout> {
+        self.layouts
+            .pipeline_layout(&self.device, label, bind_group_layouts, push_constant_ranges)
     }
 
-    pub fn resize(&self, new_size: &PhysicalSize<u32>) {
+    pub fn resize(&mut self, new_size: &PhysicalSize<u32>) {
         assert!(new_size.width > 0 && new_size.height > 0, "Window size must be greater than zero");
 
-        let mut surface_config = self.surface_config.try_lock().expect("aa");
-        surface_config.width = new_size.width;
-        surface_config.height = new_size.height;
+        self.surface_config.width = new_size.width;
+        self.surface_config.height = new_size.height;
 
         self.surface
-            .configure(&self.device, &*surface_config);
+            .configure(&self.device, &self.surface_config);
 
-        let mut depth_texture = self
-            .depth_texture
-            .try_lock()
-            .expect("The depth texture is only locked by this function and while rendering");
-        *depth_texture = Texture::new_depth_texture(&self.device, &*surface_config);
+        self.render_targets
+            .resize(&self.device, self.surface_size());
     }
 
-    pub fn start_rendering(&self) -> RenderHandle {
-        let target_texture = self
-            .surface
-            .get_current_texture()
-            .expect("Could not retrieve new texture from surface");
+    /// Switches between presenting frames in sync with the display and as fast as possible
+    pub fn set_vsync(&mut self, enabled: bool) {
+        self.surface_config.present_mode = present_mode(enabled);
+
+        self.surface
+            .configure(&self.device, &self.surface_config);
+    }
+
+    /// Current width and height of the surface
+    pub fn surface_size(&self) -> (u32, u32) {
+        (self.surface_config.width, self.surface_config.height)
+    }
+
+    /// Whether the surface supports being copied from, which is required for screenshots and post effects that sample the image
+    pub fn can_copy_surface(&self) -> bool {
+        self.surface_config
+            .usage
+            .contains(wgpu::TextureUsages::COPY_SRC)
+    }
+
+    /// Starts recording a frame into the next texture of the surface.
+    /// Returns None if no texture could be acquired, in which case the frame is skipped.
+    pub fn start_rendering(&self) -> Option<RenderHandle> {
+        let target_texture = match self.surface.get_current_texture() {
+            Ok(target_texture) => target_texture,
+            Err(err @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                warn!("Skipping a frame and configuring the surface again: {err}");
+                self.surface
+                    .configure(&self.device, &self.surface_config);
+                return None;
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                warn!("Skipping a frame, because acquiring the surface texture timed out");
+                return None;
+            }
+            Err(err @ wgpu::SurfaceError::OutOfMemory) => {
+                if !self.device_lost.swap(true, Ordering::Relaxed) {
+                    error!("The GPU device was lost: {err}");
+                }
+                return None;
+            }
+        };
 
         let target_texture_view = target_texture
             .texture
@@ -137,42 +292,58 @@ impl RenderCtx {
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
 
-        RenderHandle {
+        // Runs the callbacks of finished buffer mappings, so that readbacks become available without waiting
+        self.device.poll(wgpu::Maintain::Poll);
+
+        let frame_number = self.frame_number.fetch_add(1, Ordering::Relaxed);
+        // Waits for a previous frame with the same index that is still being recorded
+        let mut frame = self.frames[frame_number as usize % FRAMES_IN_FLIGHT]
+            .lock()
+            .expect("Frame context lock to not be poisoned");
+
+        if let Some(timings) = frame.begin(frame_number, self.queue.get_timestamp_period()) {
+            *self
+                .gpu_timings
+                .lock()
+                .expect("GPU timings lock to not be poisoned") = timings;
+        }
+
+        let depth_texture = self
+            .render_targets
+            .get(RenderTargets::DEPTH)
+            .expect("Depth target to be created with the render context");
+
+        Some(RenderHandle {
             render_ctx: self,
             encoder: ManuallyDrop::new(encoder),
             target_texture: ManuallyDrop::new(target_texture),
             target_texture_view,
+            depth_texture,
             clear_before_next_render: true,
-        }
+            frame,
+        })
     }
 }
 
 impl RenderHandle<'_> {
-    pub fn render<T: Renderer>(&mut self, renderer: &T, camera: &Camera) {
+    /// Renders into a new render pass, which is timed on the GPU with the given label if timestamp queries are supported
+    pub fn render<T: Renderer + ?Sized>(&mut self, label: &str, renderer: &T, camera: &Camera) {
         let (load_op, depth_load_op) = if self.clear_before_next_render {
-            (
-                wgpu::LoadOp::Clear(wgpu::Color {
-                    r: 0.4941,
-                    g: 0.6627,
-                    b: 1.0,
-                    a: 1.0,
-                }),
-                wgpu::LoadOp::Clear(1.0),
-            )
+            (wgpu::LoadOp::Clear(self.render_ctx.clear_color), wgpu::LoadOp::Clear(1.0))
         } else {
             (wgpu::LoadOp::Load, wgpu::LoadOp::Load)
         };
 
-        let depth_texture = &self
-            .render_ctx
-            .depth_texture
-            .try_lock()
-            .expect("Mutex to be unlocked");
+        let timestamp_writes = self
+            .frame
+            .gpu_timer
+            .as_mut()
+            .and_then(|timer| timer.render_pass_writes(label));
 
         let mut render_pass = self
             .encoder
             .begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
+                label: Some(label),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &self.target_texture_view,
                     ops: wgpu::Operations {
@@ -182,7 +353,7 @@ impl RenderHandle<'_> {
                     resolve_target: None,
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_texture.view,
+                    view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
                         load: depth_load_op,
                         store: StoreOp::Store,
@@ -190,11 +361,129 @@ impl RenderHandle<'_> {
                     stencil_ops: None,
                 }),
                 occlusion_query_set: None,
-                timestamp_writes: None,
+                timestamp_writes,
             });
         self.clear_before_next_render = false;
 
-        renderer.render(&mut render_pass, &camera.bind_group, self.render_ctx);
+        renderer.render(&mut render_pass, camera.binding(), self.render_ctx);
+    }
+
+    /// Starts a render pass into the surface without a depth attachment, so that post effects can sample the depth buffer
+    pub fn begin_post_pass(&mut self, label: &str) -> wgpu::RenderPass {
+        let load_op = if self.clear_before_next_render {
+            wgpu::LoadOp::Clear(self.render_ctx.clear_color)
+        } else {
+            wgpu::LoadOp::Load
+        };
+        self.clear_before_next_render = false;
+
+        let timestamp_writes = self
+            .frame
+            .gpu_timer
+            .as_mut()
+            .and_then(|timer| timer.render_pass_writes(label));
+
+        self.encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.target_texture_view,
+                    ops: wgpu::Operations {
+                        load: load_op,
+                        store: StoreOp::Store,
+                    },
+                    resolve_target: None,
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes,
+            })
+    }
+
+    /// Starts a new compute pass, which is timed on the GPU with the given label if timestamp queries are supported
+    pub fn begin_compute_pass(&mut self, label: &str) -> wgpu::ComputePass {
+        let timestamp_writes = self
+            .frame
+            .gpu_timer
+            .as_mut()
+            .and_then(|timer| timer.compute_pass_writes(label));
+
+        self.encoder
+            .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some(label),
+                timestamp_writes,
+            })
+    }
+
+    /// Records an upload of `data` into `target` through the staging belt of the current frame
+    pub fn write_buffer(&mut self, target: &wgpu::Buffer, offset: wgpu::BufferAddress, data: &[u8]) {
+        let Some(size) = wgpu::BufferSize::new(data.len() as u64) else {
+            return;
+        };
+
+        self.frame
+            .staging_belt
+            .write_buffer(self.encoder.deref_mut(), target, offset, size, &self.render_ctx.device)
+            .copy_from_slice(data);
+    }
+
+    /// Copies `size` bytes of `source` back to the CPU.
+    /// The data becomes available through the returned [Readback] in one of the following frames.
+    pub fn read_buffer(&mut self, source: &wgpu::Buffer, offset: wgpu::BufferAddress, size: wgpu::BufferAddress) -> Readback {
+        let readback = Readback::from_buffer(&self.render_ctx.device, self.encoder.deref_mut(), source, offset, size);
+        self.frame.readbacks.push(readback.clone());
+
+        readback
+    }
+
+    /// Copies everything that was rendered into the surface so far back to the CPU,
+    /// or returns None if the surface does not support being copied from
+    pub fn read_surface(&mut self) -> Option<TextureReadback> {
+        let texture = &self.target_texture.texture;
+        if !texture
+            .usage()
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            return None;
+        }
+
+        let readback = Readback::from_texture(&self.render_ctx.device, self.encoder.deref_mut(), texture);
+        self.frame
+            .readbacks
+            .push(readback.readback.clone());
+
+        Some(readback)
+    }
+
+    /// Copies everything that was rendered into the surface so far into a texture with the size and format of the surface.
+    /// Returns false if the surface does not support being copied from.
+    pub fn copy_surface_to(&mut self, destination: &Texture) -> bool {
+        let source = &self.target_texture.texture;
+        if !source
+            .usage()
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            return false;
+        }
+
+        self.encoder
+            .copy_texture_to_texture(source.as_image_copy(), destination.texture.as_image_copy(), source.size());
+        true
+    }
+
+    /// Render context that this frame is recorded with
+    pub fn render_ctx(&self) -> &'a RenderCtx {
+        self.render_ctx
+    }
+
+    /// Depth buffer of this frame
+    pub fn depth_texture(&self) -> &Texture {
+        &self.depth_texture
+    }
+
+    /// Index of the current frame in `0..FRAMES_IN_FLIGHT`
+    pub fn frame_index(&self) -> usize {
+        self.frame.index
     }
 
     pub fn get_command_encoder(&mut self) -> &mut wgpu::CommandEncoder {
@@ -206,16 +495,137 @@ impl RenderHandle<'_> {
 
 impl Drop for RenderHandle<'_> {
     fn drop(&mut self) {
-        let encoder = unsafe { ManuallyDrop::take(&mut self.encoder) };
+        let mut encoder = unsafe { ManuallyDrop::take(&mut self.encoder) };
         let target_texture = unsafe { ManuallyDrop::take(&mut self.target_texture) };
 
+        self.frame
+            .finish(&self.render_ctx.device, &mut encoder);
         self.render_ctx
             .queue
             .submit(std::iter::once(encoder.finish()));
         target_texture.present();
+        self.frame.after_submit();
+    }
+}
+
+/// Requests a device that is compatible with the surface, together with the flag that its error handler sets once it is lost
+async fn request_device(instance: &wgpu::Instance, surface: &wgpu::Surface) -> (wgpu::Adapter, wgpu::Device, wgpu::Queue, Arc<AtomicBool>) {
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: Some(surface),
+            force_fallback_adapter: false,
+        })
+        .await
+        .expect("WGPU could not find a compatible adapter");
+
+    // Features that enable faster render paths if they are supported by the adapter
+    let optional_features = wgpu::Features::INDIRECT_FIRST_INSTANCE
+        | wgpu::Features::MULTI_DRAW_INDIRECT
+        | wgpu::Features::MULTI_DRAW_INDIRECT_COUNT
+        | wgpu::Features::TIMESTAMP_QUERY;
+
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::PUSH_CONSTANTS | (adapter.features() & optional_features),
+                limits: wgpu::Limits {
+                    // Chunk position and the time of the water animation
+                    max_push_constant_size: 16,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .expect("Could not request device and queue");
+
+    let device_lost = Arc::new(AtomicBool::new(false));
+    {
+        let device_lost = Arc::clone(&device_lost);
+        device.on_uncaptured_error(Box::new(move |err| {
+            if !is_device_lost_error(&err) {
+                // Broken pipelines and bindings have to be noticed during development, released builds keep running with a broken frame
+                if cfg!(debug_assertions) && matches!(err, wgpu::Error::Validation { .. }) {
+                    panic!("Uncaptured wgpu error: {err}");
+                }
+                error!("Uncaptured wgpu error: {err}");
+                return;
+            }
+
+            if !device_lost.swap(true, Ordering::Relaxed) {
+                error!("The GPU device was lost: {err}");
+            }
+        }));
+    }
+
+    (adapter, device, queue, device_lost)
+}
+
+/// Encodes a linear color for surfaces that are not sRGB, so that it is displayed the same as on sRGB surfaces
+fn linear_to_srgb(color: wgpu::Color) -> wgpu::Color {
+    let encode = |c: f64| {
+        if c < 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    };
+
+    wgpu::Color {
+        r: encode(color.r),
+        g: encode(color.g),
+        b: encode(color.b),
+        a: color.a,
+    }
+}
+
+fn present_mode(vsync: bool) -> PresentMode {
+    if vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    }
+}
+
+/// Picks an alpha mode that composites the surface with the desktop if a transparent window is requested, or an opaque one otherwise
+fn choose_alpha_mode(supported: &[CompositeAlphaMode], transparent: bool) -> CompositeAlphaMode {
+    let preferred: &[CompositeAlphaMode] = if transparent {
+        &[
+            CompositeAlphaMode::PreMultiplied,
+            CompositeAlphaMode::PostMultiplied,
+            CompositeAlphaMode::Inherit,
+        ]
+    } else {
+        &[CompositeAlphaMode::Opaque]
+    };
+
+    preferred
+        .iter()
+        .copied()
+        .find(|alpha_mode| supported.contains(alpha_mode))
+        .unwrap_or_else(|| {
+            if transparent {
+                warn!("The surface does not support transparency, falling back to {:?}", supported[0]);
+            }
+            supported[0]
+        })
+}
+
+/// wgpu 0.18 has no device lost callback yet, so a lost device is detected from the errors that its operations report
+fn is_device_lost_error(err: &wgpu::Error) -> bool {
+    match err {
+        wgpu::Error::OutOfMemory { .. } => true,
+        wgpu::Error::Validation { description, .. } => description.contains("device is lost"),
     }
 }
 
 pub trait Renderer {
-    fn render<'a>(&'a self, _: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, render_ctx: &RenderCtx);
+    fn render<'a>(&'a self, _: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, render_ctx: &RenderCtx);
+}
+
+/// Effect on the image that was rendered so far, which records its own copies and passes instead of drawing into a shared render pass
+pub trait PostEffect {
+    fn apply(&self, handle: &mut RenderHandle, label: &str);
 }
@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context, Result};
+
+use crate::rendering::camera::ScreenTile;
+use crate::rendering::readback::TextureReadback;
+use crate::rendering::thumbnail::{downscale, rgb_pixels, write_png};
+
+/// A photo that is rendered at a multiple of the window size, one tile of the size of the window per frame
+pub struct PhotoCapture {
+    pub path: PathBuf,
+    tiles_per_side: u32,
+    /// Readbacks of the tiles that were rendered so far, in the order of [PhotoCapture::current_tile]
+    captured: Vec<TextureReadback>,
+}
+
+impl PhotoCapture {
+    pub fn new(path: PathBuf, tiles_per_side: u32) -> Self {
+        Self {
+            path,
+            tiles_per_side,
+            captured: Vec::new(),
+        }
+    }
+
+    /// Tile that is rendered in the current frame, or None if all tiles were rendered
+    pub fn current_tile(&self) -> Option<ScreenTile> {
+        let index = self.captured.len() as u32;
+        (index < self.tiles_per_side * self.tiles_per_side).then(|| ScreenTile {
+            column: index % self.tiles_per_side,
+            row: index / self.tiles_per_side,
+            tiles: self.tiles_per_side,
+        })
+    }
+
+    pub fn tiles_per_side(&self) -> u32 {
+        self.tiles_per_side
+    }
+
+    /// Adds the readback of the rendered image of the current tile
+    pub fn add_tile(&mut self, capture: TextureReadback) {
+        self.captured.push(capture);
+    }
+
+    /// Saves the photo once all of its tiles are rendered and the device has finished the frames that read them back
+    pub fn save(self) -> Result<()> {
+        let tiles = self
+            .captured
+            .into_iter()
+            .map(|capture| {
+                let data = capture
+                    .readback
+                    .try_take()
+                    .ok_or_else(|| anyhow!("a tile of the photo was not read back"))??;
+                Ok((capture, data))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        save_photo(&tiles, self.tiles_per_side, &self.path)
+    }
+}
+
+/// Stitches the captured tiles of a photo, scales the image down to the size of a single tile and writes it as a PNG image,
+/// so that every pixel of the photo averages `tiles_per_side`² rendered pixels.
+/// The tiles are ordered in rows from the top left, see [ScreenTile].
+fn save_photo(tiles: &[(TextureReadback, Vec<u8>)], tiles_per_side: u32, path: &Path) -> Result<()> {
+    ensure!(
+        tiles.len() == (tiles_per_side * tiles_per_side) as usize,
+        "expected {} tiles, but {} were captured",
+        tiles_per_side * tiles_per_side,
+        tiles.len()
+    );
+    let (tile_width, tile_height) = (tiles[0].0.width, tiles[0].0.height);
+    ensure!(
+        tiles
+            .iter()
+            .all(|(capture, _)| (capture.width, capture.height) == (tile_width, tile_height)),
+        "the window was resized while the photo was rendered"
+    );
+
+    let rgb_tiles = tiles
+        .iter()
+        .map(|(capture, data)| rgb_pixels(capture, data))
+        .collect::<Result<Vec<_>>>()?;
+    let (width, height) = (tile_width * tiles_per_side, tile_height * tiles_per_side);
+    let rgb = stitch(&rgb_tiles, tiles_per_side, tile_width, tile_height);
+    let (width, height, pixels) = downscale(&rgb, width, height, tile_width);
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).with_context(|| format!("Could not create photo directory {}", directory.display()))?;
+    }
+    write_png(path, width, height, &pixels).context("Could not write photo")
+}
+
+/// First path of the form `photo_0001.png` in the directory that is not taken by another photo yet
+pub fn next_photo_path(directory: &Path) -> PathBuf {
+    (1..)
+        .map(|number| directory.join(format!("photo_{number:04}.png")))
+        .find(|path| !path.exists())
+        .expect("a photo number to be free")
+}
+
+/// Arranges RGB tiles of equal size, which are ordered in rows from the top left, into one image
+fn stitch(tiles: &[Vec<u8>], tiles_per_side: u32, tile_width: u32, tile_height: u32) -> Vec<u8> {
+    let row_len = (tile_width * 3) as usize;
+    let width = (tile_width * tiles_per_side) as usize;
+
+    let mut rgb = vec![0; row_len * tiles_per_side as usize * (tile_height * tiles_per_side) as usize];
+    for (index, tile) in tiles.iter().enumerate() {
+        let (column, row) = (index % tiles_per_side as usize, index / tiles_per_side as usize);
+        for (y, tile_row) in tile.chunks_exact(row_len).enumerate() {
+            let start = ((row * tile_height as usize + y) * width + column * tile_width as usize) * 3;
+            rgb[start..start + row_len].copy_from_slice(tile_row);
+        }
+    }
+
+    rgb
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rendering::photo::stitch;
+
+    #[test]
+    fn test_stitch_places_tiles_in_rows() {
+        // Tiles of 1x2 pixels, whose pixels are colored by their tile and row
+        let tiles: Vec<Vec<u8>> = (0..4)
+            .map(|tile| [tile * 10, 0, 0, tile * 10 + 1, 0, 0].to_vec())
+            .collect();
+
+        assert_eq!(
+            stitch(&tiles, 2, 1, 2),
+            [[0, 0, 0, 10, 0, 0], [1, 0, 0, 11, 0, 0], [20, 0, 0, 30, 0, 0], [21, 0, 0, 31, 0, 0],].concat()
+        );
+    }
+}
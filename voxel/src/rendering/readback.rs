@@ -0,0 +1,147 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, Result};
+
+/// Data that is copied from the GPU into a mappable buffer and read on the CPU as soon as it is available.
+///
+/// A readback is recorded into the command encoder of a frame with [RenderHandle::read_buffer](crate::rendering::RenderHandle::read_buffer).
+/// Its buffer is mapped after the frame is submitted and the device is polled at the start of every frame,
+/// so [Readback::try_take] never blocks.
+#[derive(Clone)]
+pub struct Readback {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    buffer: wgpu::Buffer,
+    state: Mutex<State>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum State {
+    Recorded,
+    Mapping,
+    Mapped,
+    Failed,
+    Taken,
+}
+
+impl Readback {
+    pub(super) fn from_buffer(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        size: wgpu::BufferAddress,
+    ) -> Self {
+        let buffer = create_readback_buffer(device, size);
+        encoder.copy_buffer_to_buffer(source, offset, &buffer, 0, size);
+
+        Self::new(buffer)
+    }
+
+    /// Copies a whole texture with a single layer and mip level. Rows are padded to [wgpu::COPY_BYTES_PER_ROW_ALIGNMENT].
+    pub(super) fn from_texture(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, source: &wgpu::Texture) -> TextureReadback {
+        let (width, height) = (source.width(), source.height());
+        let bytes_per_pixel = source
+            .format()
+            .block_size(None)
+            .expect("texture to have a color format");
+        let bytes_per_row = (width * bytes_per_pixel).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = create_readback_buffer(device, bytes_per_row as u64 * height as u64);
+        encoder.copy_texture_to_buffer(
+            source.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            source.size(),
+        );
+
+        TextureReadback {
+            readback: Self::new(buffer),
+            width,
+            height,
+            bytes_per_row,
+            format: source.format(),
+        }
+    }
+
+    fn new(buffer: wgpu::Buffer) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                buffer,
+                state: Mutex::new(State::Recorded),
+            }),
+        }
+    }
+
+    /// Starts mapping the buffer. Must only be called after the commands that copy the data have been submitted.
+    pub(super) fn start_mapping(&self) {
+        *self.shared.lock_state() = State::Mapping;
+
+        let shared = Arc::clone(&self.shared);
+        self.shared
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                *shared.lock_state() = if result.is_ok() { State::Mapped } else { State::Failed };
+            });
+    }
+
+    /// Returns the read data once it is available. The data can only be taken once.
+    pub fn try_take(&self) -> Option<Result<Vec<u8>>> {
+        let mut state = self.shared.lock_state();
+
+        match *state {
+            State::Recorded | State::Mapping | State::Taken => None,
+            State::Failed => {
+                *state = State::Taken;
+                Some(Err(anyhow!("Failed to map the readback buffer")))
+            }
+            State::Mapped => {
+                *state = State::Taken;
+                let data = self.shared.read_mapped();
+                self.shared.buffer.unmap();
+                Some(Ok(data))
+            }
+        }
+    }
+}
+
+/// A [Readback] of the texels of a texture, together with the layout of the read data
+#[derive(Clone)]
+pub struct TextureReadback {
+    pub readback: Readback,
+    pub width: u32,
+    pub height: u32,
+    /// Length of every row of the read data in bytes, including padding
+    pub bytes_per_row: u32,
+    pub format: wgpu::TextureFormat,
+}
+
+impl Shared {
+    fn lock_state(&self) -> MutexGuard<State> {
+        self.state
+            .lock()
+            .expect("Readback state lock to not be poisoned")
+    }
+
+    fn read_mapped(&self) -> Vec<u8> {
+        self.buffer.slice(..).get_mapped_range().to_vec()
+    }
+}
+
+fn create_readback_buffer(device: &wgpu::Device, size: wgpu::BufferAddress) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback buffer"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::rendering::texture::Texture;
+
+/// Size of a render target
+#[derive(Copy, Clone, Debug)]
+pub enum RenderTargetSize {
+    /// The size of the surface multiplied by a factor. The target is recreated whenever the surface is resized.
+    SurfaceScaled(f32),
+    /// A fixed size in pixels, e.g. for shadow maps
+    Fixed(u32, u32),
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderTargetDescriptor {
+    pub label: &'static str,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+    pub size: RenderTargetSize,
+}
+
+/// Owns all textures besides the surface that are rendered into, like the depth buffer, HDR color targets, shadow maps or SSAO buffers.
+///
+/// Targets are identified by a key and replaced as a whole when they are resized,
+/// so that a frame which still holds a target keeps rendering into the texture it started with.
+#[derive(Debug, Default)]
+pub struct RenderTargets {
+    targets: Mutex<HashMap<&'static str, RenderTarget>>,
+}
+
+#[derive(Debug)]
+struct RenderTarget {
+    descriptor: RenderTargetDescriptor,
+    texture: Arc<Texture>,
+}
+
+impl RenderTargets {
+    /// Depth buffer of the main render passes
+    pub const DEPTH: &'static str = "depth";
+
+    /// Creates the target with the given key, replacing any previous target with the same key
+    pub fn create(
+        &self,
+        device: &wgpu::Device,
+        key: &'static str,
+        descriptor: RenderTargetDescriptor,
+        surface_size: (u32, u32),
+    ) -> Arc<Texture> {
+        let texture = Arc::new(create_texture(device, &descriptor, surface_size));
+
+        self.lock_targets().insert(
+            key,
+            RenderTarget {
+                descriptor,
+                texture: Arc::clone(&texture),
+            },
+        );
+
+        texture
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<Texture>> {
+        self.lock_targets()
+            .get(key)
+            .map(|target| Arc::clone(&target.texture))
+    }
+
+    /// Recreates all targets whose size depends on the size of the surface
+    pub fn resize(&self, device: &wgpu::Device, surface_size: (u32, u32)) {
+        for target in self.lock_targets().values_mut() {
+            if let RenderTargetSize::SurfaceScaled(_) = target.descriptor.size {
+                target.texture = Arc::new(create_texture(device, &target.descriptor, surface_size));
+            }
+        }
+    }
+
+    fn lock_targets(&self) -> MutexGuard<HashMap<&'static str, RenderTarget>> {
+        self.targets
+            .lock()
+            .expect("Render targets lock to not be poisoned")
+    }
+}
+
+fn create_texture(device: &wgpu::Device, descriptor: &RenderTargetDescriptor, (surface_width, surface_height): (u32, u32)) -> Texture {
+    let (width, height) = match descriptor.size {
+        RenderTargetSize::SurfaceScaled(scale) => (
+            ((surface_width as f32 * scale) as u32).max(1),
+            ((surface_height as f32 * scale) as u32).max(1),
+        ),
+        RenderTargetSize::Fixed(width, height) => (width, height),
+    };
+
+    Texture::new_render_target(device, descriptor.label, width, height, descriptor.format, descriptor.usage)
+}
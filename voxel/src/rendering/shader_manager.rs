@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+
+use crate::rendering::RenderCtx;
+
+/// Source of the chunk shader in the repository, which is compiled into the binary and reloaded while it is edited
+pub const CHUNK_SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/world/shader.wgsl");
+
+/// Time between two checks whether a watched shader was modified
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches shader source files on disk, so that their pipelines can be recompiled while the shaders are edited.
+///
+/// All shaders are compiled into the binary, the files are only polled for changes. Missing files are ignored,
+/// e.g. if the binary runs outside of the repository.
+pub struct ShaderManager {
+    shaders: Vec<WatchedShader>,
+    last_poll: Instant,
+    /// Error of the last reload of every shader that failed to compile, shown in the debug overlay
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+struct WatchedShader {
+    path: PathBuf,
+    /// Modification time of the source that was last compiled, or of the source in the binary when the manager was created
+    modified: Option<SystemTime>,
+}
+
+impl ShaderManager {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let shaders = paths
+            .into_iter()
+            .map(|path| WatchedShader {
+                modified: modification_time(&path),
+                path,
+            })
+            .collect();
+
+        Self {
+            shaders,
+            last_poll: Instant::now(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Path and new source of every watched shader that was modified since the last call
+    pub fn modified_shaders(&mut self) -> Vec<(PathBuf, String)> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut modified_shaders = Vec::new();
+        for shader in &mut self.shaders {
+            let modified = modification_time(&shader.path);
+            if modified.is_none() || modified == shader.modified {
+                continue;
+            }
+            shader.modified = modified;
+
+            match fs::read_to_string(&shader.path) {
+                Ok(source) => modified_shaders.push((shader.path.clone(), source)),
+                Err(err) => warn!("Could not read the modified shader {:?}: {err}", shader.path),
+            }
+        }
+
+        modified_shaders
+    }
+
+    /// Records whether the reload of a shader succeeded, replacing its previous error
+    pub fn report(&mut self, path: PathBuf, result: Result<()>) {
+        self.errors
+            .retain(|(error_path, _)| *error_path != path);
+        match result {
+            Ok(()) => info!("Reloaded the shader {path:?}"),
+            Err(err) => {
+                warn!("Could not reload the shader {path:?}, keeping the previous pipelines: {err:#}");
+                self.errors.push((path, format!("{err:#}")));
+            }
+        }
+    }
+}
+
+/// Creates shader modules and pipelines and fails instead of panicking if any of them does not validate,
+/// e.g. because a reloaded shader does not compile
+pub fn validated<T>(ctx: &RenderCtx, create: impl FnOnce() -> T) -> Result<T> {
+    ctx.device
+        .push_error_scope(wgpu::ErrorFilter::Validation);
+    let created = create();
+    if let Some(err) = pollster::block_on(ctx.device.pop_error_scope()) {
+        bail!("{err}");
+    }
+
+    Ok(created)
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,122 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+
+use crate::rendering::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::rendering::frustum::{Aabb, Frustum};
+
+pub const SHADOW_CASCADE_COUNT: usize = 3;
+
+/// View distances at which the cascades end. The first cascade starts at the near plane of the camera.
+const CASCADE_SPLITS: [f32; SHADOW_CASCADE_COUNT] = [48.0, 160.0, 512.0];
+
+/// Distance that light frustums are extended towards the light, so that terrain outside of the view can still cast shadows into it
+const CASTER_MARGIN: f32 = 256.0;
+
+/// Direction in which the sunlight travels, matches the light direction of the chunk shader
+pub fn sun_direction() -> Vector3<f32> {
+    -Vector3::new(1.0, 0.5, 0.7).normalize()
+}
+
+/// A slice of the camera frustum together with an orthographic light projection that covers it
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowCascade {
+    pub light_view_proj: Matrix4<f32>,
+    pub light_frustum: Frustum,
+}
+
+impl ShadowCascade {
+    /// Splits the camera frustum at [CASCADE_SPLITS] and fits a light frustum around the bounding sphere of every slice.
+    /// Fitting spheres instead of boxes keeps the light frustums stable when the camera rotates.
+    pub fn fit(
+        camera_view_proj: Matrix4<f32>,
+        z_near: f32,
+        z_far: f32,
+        light_direction: Vector3<f32>,
+    ) -> [ShadowCascade; SHADOW_CASCADE_COUNT] {
+        let inverse = camera_view_proj
+            .invert()
+            .expect("the view projection matrix to be invertible");
+
+        // Corners of the near and far plane, in the same order
+        let corners = |depth: f32| {
+            [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+                let corner = inverse * Vector4::new(x, y, depth, 1.0);
+                corner.truncate() / corner.w
+            })
+        };
+        let (near_corners, far_corners) = (corners(0.0), corners(1.0));
+
+        // View depth changes linearly along the edges between the near and far plane
+        let slice_corners = |distance: f32| {
+            let t = ((distance - z_near) / (z_far - z_near)).clamp(0.0, 1.0);
+            [0, 1, 2, 3].map(|i| near_corners[i] + (far_corners[i] - near_corners[i]) * t)
+        };
+
+        let mut slice_start = z_near;
+        CASCADE_SPLITS.map(|slice_end| {
+            let slice: Vec<Vector3<f32>> = slice_corners(slice_start)
+                .into_iter()
+                .chain(slice_corners(slice_end))
+                .collect();
+            slice_start = slice_end;
+
+            let center = slice.iter().sum::<Vector3<f32>>() / slice.len() as f32;
+            let radius = slice
+                .iter()
+                .map(|corner| (corner - center).magnitude())
+                .fold(0.0, f32::max);
+
+            let center = Point3::from_vec(center);
+            let eye = center - light_direction * (radius + CASTER_MARGIN);
+            let up = if light_direction.y.abs() > 0.99 {
+                Vector3::unit_z()
+            } else {
+                Vector3::unit_y()
+            };
+
+            let view = Matrix4::look_at_rh(eye, center, up);
+            let proj = OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-radius, radius, -radius, radius, 0.0, 2.0 * radius + CASTER_MARGIN);
+            let light_view_proj = proj * view;
+
+            ShadowCascade {
+                light_view_proj,
+                light_frustum: Frustum::from_view_projection(light_view_proj),
+            }
+        })
+    }
+
+    /// Whether a box may cast a shadow into this cascade and has to be rendered into its shadow map
+    pub fn is_caster(&self, aabb: &Aabb) -> bool {
+        self.light_frustum
+            .intersects_aabb(aabb.min, aabb.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Matrix4, Point3, Vector3};
+
+    use crate::rendering::camera::OPENGL_TO_WGPU_MATRIX;
+    use crate::rendering::frustum::Aabb;
+    use crate::rendering::shadow_cascades::{sun_direction, ShadowCascade, CASCADE_SPLITS};
+
+    #[test]
+    fn test_cascades_cover_their_slices() {
+        let view = Matrix4::look_to_rh(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0), Vector3::unit_y());
+        let proj = OPENGL_TO_WGPU_MATRIX * cgmath::perspective(Deg(80.0), 16.0 / 9.0, 0.1, 1000.0);
+        let cascades = ShadowCascade::fit(proj * view, 0.1, 1000.0, sun_direction());
+
+        let point = |p: Vector3<f32>| Aabb { min: p, max: p };
+        let ahead = |distance: f32| Vector3::new(0.0, 0.0, -distance);
+
+        // A point in front of the camera is inside of the cascade whose slice contains it
+        assert!(cascades[0].is_caster(&point(ahead(CASCADE_SPLITS[0] / 2.0))));
+        assert!(cascades[1].is_caster(&point(ahead((CASCADE_SPLITS[0] + CASCADE_SPLITS[1]) / 2.0))));
+        assert!(cascades[2].is_caster(&point(ahead((CASCADE_SPLITS[1] + CASCADE_SPLITS[2]) / 2.0))));
+
+        // Terrain between the sun and the first slice can cast shadows into it
+        assert!(cascades[0].is_caster(&point(ahead(CASCADE_SPLITS[0] / 2.0) - sun_direction() * 100.0)));
+
+        // Far behind the camera and away from the sun nothing casts shadows into the first cascade
+        assert!(!cascades[0].is_caster(&point(ahead(-2000.0))));
+    }
+}
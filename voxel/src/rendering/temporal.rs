@@ -0,0 +1,356 @@
+use std::mem;
+
+use cgmath::{Matrix4, SquareMatrix, Vector2};
+use strum_macros::EnumIter;
+use wgpu::{include_wgsl, BindingType};
+
+use crate::rendering::camera::Camera;
+use crate::rendering::render_targets::{RenderTargetDescriptor, RenderTargetSize};
+use crate::rendering::uniform_ring::UniformRing;
+use crate::rendering::{PostEffect, RenderCtx, RenderHandle};
+
+const TEMPORAL_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    ty: BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: wgpu::BufferSize::new(mem::size_of::<RawTemporal>() as u64),
+    },
+    visibility: wgpu::ShaderStages::FRAGMENT,
+    count: None,
+}];
+
+/// Copy of the current frame, its copy from the previous frame and the depth buffer
+const TEMPORAL_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[
+    color_texture_entry(0),
+    color_texture_entry(1),
+    wgpu::BindGroupLayoutEntry {
+        binding: 2,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Depth,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 3,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    },
+];
+
+const fn color_texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+const SCENE_TARGET: &str = "temporal_scene";
+const HISTORY_TARGET: &str = "temporal_history";
+
+/// Fraction of the motion between two frames over which the image is blurred, like the shutter angle of a film camera
+const MOTION_BLUR_STRENGTH: f32 = 0.5;
+/// Weight of the reprojected previous frame in the anti-aliased image
+const HISTORY_WEIGHT: f32 = 0.9;
+/// Number of different sub-pixel offsets of the projection, after which the jitter sequence repeats
+const JITTER_SEQUENCE_LENGTH: u32 = 8;
+
+/// Post effect that uses the motion of the camera since the previous frame
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, EnumIter)]
+pub enum TemporalMode {
+    #[default]
+    Off,
+    /// Blurs the image along the screen space motion of every pixel
+    MotionBlur,
+    /// Jitters the projection by a fraction of a pixel every frame and blends each frame with the reprojected previous frames
+    Taa,
+}
+
+impl TemporalMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            TemporalMode::Off => "Off",
+            TemporalMode::MotionBlur => "Motion blur",
+            TemporalMode::Taa => "TAA",
+        }
+    }
+}
+
+/// Camera motion blur and temporal anti-aliasing.
+///
+/// The motion of every pixel is reconstructed from the depth buffer and the view projection of the previous frame, which is exact
+/// for the static terrain. Both effects copy the image rendered so far out of the surface, so they require a surface that can be copied from.
+pub struct TemporalEffects {
+    uniforms: UniformRing<RawTemporal>,
+    motion_blur_pipeline: wgpu::RenderPipeline,
+    taa_pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    pub mode: TemporalMode,
+    /// View projection of the previous frame without jitter
+    previous_view_projection: Option<Matrix4<f32>>,
+    /// Whether the history target contains the anti-aliased previous frame
+    has_history: bool,
+    frame: u32,
+}
+
+impl TemporalEffects {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let layout = ctx.bind_group_layout("Temporal bind group layout", TEMPORAL_BIND_GROUP_LAYOUT_ENTRIES);
+        let (motion_blur_pipeline, taa_pipeline) = create_temporal_pipelines(ctx);
+
+        for (key, label) in [
+            (SCENE_TARGET, "temporal_scene_texture"),
+            (HISTORY_TARGET, "temporal_history_texture"),
+        ] {
+            ctx.render_targets.create(
+                &ctx.device,
+                key,
+                RenderTargetDescriptor {
+                    label,
+                    format: ctx.surface_format,
+                    usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+                    size: RenderTargetSize::SurfaceScaled(1.0),
+                },
+                ctx.surface_size(),
+            );
+        }
+
+        Self {
+            uniforms: UniformRing::new(
+                ctx,
+                "Temporal uniforms",
+                &layout,
+                &RawTemporal::new(Matrix4::identity(), Matrix4::identity(), 0.0, false),
+            ),
+            motion_blur_pipeline,
+            taa_pipeline,
+            sampler: ctx
+                .device
+                .create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("Temporal sampler"),
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    ..Default::default()
+                }),
+            mode: TemporalMode::Off,
+            previous_view_projection: None,
+            has_history: false,
+            frame: 0,
+        }
+    }
+
+    /// Recreates the uniform buffer, the pipelines and the targets on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self {
+            mode: self.mode,
+            ..Self::new(ctx)
+        };
+    }
+
+    /// Writes the uniforms of this frame and returns the sub-pixel offset of the projection in normalized device coordinates,
+    /// which has to be applied to the camera before its buffer is updated
+    pub fn update(&mut self, handle: &mut RenderHandle, camera: &Camera) -> Vector2<f32> {
+        let view_projection = camera.view_projection();
+        let previous = self
+            .previous_view_projection
+            .replace(view_projection)
+            .unwrap_or(view_projection);
+
+        let taa = self.mode == TemporalMode::Taa;
+        let blur_strength = if self.mode == TemporalMode::MotionBlur {
+            MOTION_BLUR_STRENGTH
+        } else {
+            0.0
+        };
+        self.uniforms.write(
+            handle,
+            &RawTemporal::new(
+                view_projection
+                    .invert()
+                    .expect("View projection matrices are invertible"),
+                previous,
+                blur_strength,
+                taa && self.has_history,
+            ),
+        );
+        self.has_history = taa && handle.render_ctx.can_copy_surface();
+
+        if !taa {
+            return Vector2::new(0.0, 0.0);
+        }
+
+        self.frame = (self.frame + 1) % JITTER_SEQUENCE_LENGTH;
+        let (width, height) = handle.render_ctx.surface_size();
+        jitter(self.frame, width, height)
+    }
+}
+
+impl PostEffect for TemporalEffects {
+    fn apply(&self, handle: &mut RenderHandle, label: &str) {
+        let pipeline = match self.mode {
+            TemporalMode::Off => return,
+            TemporalMode::MotionBlur => &self.motion_blur_pipeline,
+            TemporalMode::Taa => &self.taa_pipeline,
+        };
+
+        let targets = &handle.render_ctx.render_targets;
+        let (Some(scene), Some(history)) = (targets.get(SCENE_TARGET), targets.get(HISTORY_TARGET)) else {
+            return;
+        };
+        if !handle.copy_surface_to(&scene) {
+            return;
+        }
+
+        let layout = handle
+            .render_ctx
+            .bind_group_layout("Temporal textures bind group layout", TEMPORAL_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES);
+        let textures = handle
+            .render_ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Temporal textures bind group"),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&scene.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&history.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&handle.depth_texture().view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+        {
+            let mut render_pass = handle.begin_post_pass(label);
+            self.uniforms.binding().bind(&mut render_pass, 0);
+            render_pass.set_bind_group(1, &textures, &[]);
+            render_pass.set_pipeline(pipeline);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        if self.mode == TemporalMode::Taa {
+            // The anti-aliased image is reprojected in the next frame before the lens effects and the UI are drawn on top of it
+            handle.copy_surface_to(&history);
+        }
+    }
+}
+
+/// Offset of the projection in normalized device coordinates by up to half a pixel, following a Halton sequence
+fn jitter(frame: u32, width: u32, height: u32) -> Vector2<f32> {
+    let offset = |base: u32, size: u32| (halton(frame + 1, base) - 0.5) * 2.0 / size as f32;
+    Vector2::new(offset(2, width), offset(3, height))
+}
+
+/// Element of the low discrepancy Halton sequence in `0.0..1.0`
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+
+    result
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawTemporal {
+    inverse_view_proj: [[f32; 4]; 4],
+    previous_view_proj: [[f32; 4]; 4],
+    /// Blur strength, weight of the history and whether the history is valid
+    params: [f32; 4],
+}
+
+impl RawTemporal {
+    fn new(inverse_view_proj: Matrix4<f32>, previous_view_proj: Matrix4<f32>, blur_strength: f32, has_history: bool) -> Self {
+        Self {
+            inverse_view_proj: inverse_view_proj.into(),
+            previous_view_proj: previous_view_proj.into(),
+            params: [blur_strength, HISTORY_WEIGHT, if has_history { 1.0 } else { 0.0 }, 0.0],
+        }
+    }
+}
+
+fn create_temporal_pipelines(ctx: &RenderCtx) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("temporal.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Temporal render pipeline layout",
+        &[TEMPORAL_BIND_GROUP_LAYOUT_ENTRIES, TEMPORAL_TEXTURES_BIND_GROUP_LAYOUT_ENTRIES],
+        &[],
+    );
+
+    let create_pipeline = |label: &str, entry_point: &str| {
+        ctx.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    buffers: &[],
+                    entry_point: "vs_main",
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    entry_point,
+                }),
+                primitive: Default::default(),
+                // Post passes have no depth attachment, so that the depth buffer can be sampled
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: None,
+            })
+    };
+
+    (
+        create_pipeline("Motion blur render pipeline", "fs_motion_blur"),
+        create_pipeline("TAA render pipeline", "fs_taa"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rendering::temporal::{halton, jitter, JITTER_SEQUENCE_LENGTH};
+
+    #[test]
+    fn test_jitter_stays_within_half_a_pixel() {
+        assert_eq!(halton(1, 2), 0.5);
+        assert_eq!(halton(3, 2), 0.75);
+        assert!((halton(2, 3) - 2.0 / 3.0).abs() < 1e-6);
+
+        for frame in 0..JITTER_SEQUENCE_LENGTH {
+            let offset = jitter(frame, 800, 600);
+            assert!(offset.x.abs() <= 1.0 / 800.0);
+            assert!(offset.y.abs() <= 1.0 / 600.0);
+        }
+    }
+}
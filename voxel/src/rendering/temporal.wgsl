@@ -0,0 +1,90 @@
+struct TemporalUniform {
+	inverse_view_proj: mat4x4<f32>,
+	previous_view_proj: mat4x4<f32>,
+	// Blur strength, weight of the history and whether the history is valid
+	params: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> temporal: TemporalUniform;
+
+@group(1) @binding(0)
+var scene: texture_2d<f32>;
+@group(1) @binding(1)
+var history: texture_2d<f32>;
+@group(1) @binding(2)
+var depth: texture_depth_2d;
+@group(1) @binding(3)
+var linear_sampler: sampler;
+
+const BLUR_SAMPLES: i32 = 8;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	var POSITION_LOOKUP = array<vec2<f32>, 3>(
+		vec2(-1.0, -1.0),
+		vec2(3.0, -1.0),
+		vec2(-1.0, 3.0));
+
+	let position = POSITION_LOOKUP[vertex_index];
+
+    var out: VertexOutput;
+    out.clip_position = vec4(position, 0.0, 1.0);
+    out.uv = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return out;
+}
+
+// Screen position of the surface at this pixel in the previous frame, reconstructed from the depth buffer
+fn previous_uv(uv: vec2<f32>, pixel: vec2<i32>) -> vec2<f32> {
+	let depth = textureLoad(depth, pixel, 0);
+	let world = temporal.inverse_view_proj * vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+	let previous_clip = temporal.previous_view_proj * vec4(world.xyz / world.w, 1.0);
+	let previous_ndc = previous_clip.xy / max(previous_clip.w, 0.0001);
+
+	return vec2(previous_ndc.x * 0.5 + 0.5, 0.5 - previous_ndc.y * 0.5);
+}
+
+@fragment
+fn fs_motion_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+	let velocity = (in.uv - previous_uv(in.uv, vec2<i32>(in.clip_position.xy))) * temporal.params.x;
+
+	var color = vec4(0.0);
+	for (var i = 0; i < BLUR_SAMPLES; i++) {
+		let t = f32(i) / f32(BLUR_SAMPLES - 1) - 0.5;
+		color += textureSampleLevel(scene, linear_sampler, in.uv + velocity * t, 0.0);
+	}
+
+	return color / f32(BLUR_SAMPLES);
+}
+
+@fragment
+fn fs_taa(in: VertexOutput) -> @location(0) vec4<f32> {
+	let pixel = vec2<i32>(in.clip_position.xy);
+	let current = textureLoad(scene, pixel, 0);
+
+	// The history is clamped to the colors around the pixel, so that disoccluded surfaces do not leave ghosts behind
+	let max_pixel = vec2<i32>(textureDimensions(scene)) - 1;
+	var low = current;
+	var high = current;
+	for (var dy = -1; dy <= 1; dy++) {
+		for (var dx = -1; dx <= 1; dx++) {
+			let neighbor = textureLoad(scene, clamp(pixel + vec2(dx, dy), vec2(0), max_pixel), 0);
+			low = min(low, neighbor);
+			high = max(high, neighbor);
+		}
+	}
+
+	let uv = previous_uv(in.uv, pixel);
+	if temporal.params.z == 0.0 || any(uv < vec2(0.0)) || any(uv > vec2(1.0)) {
+		return current;
+	}
+
+	let previous = clamp(textureSampleLevel(history, linear_sampler, uv, 0.0), low, high);
+	return mix(current, previous, temporal.params.y);
+}
@@ -1,3 +1,34 @@
+use std::fs::{self, File};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use wgpu::include_wgsl;
+
+use crate::rendering::RenderCtx;
+
+/// Entries of the bind group layout of [Texture::create_bind_group]
+pub const TEXTURE_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[
+    wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 1,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    },
+];
+
+/// Number of tiles in every row and column of a texture atlas, keep in sync with shader.wgsl
+pub const ATLAS_TILES_PER_ROW: u32 = 16;
+
 #[derive(Debug)]
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -8,38 +39,49 @@ pub struct Texture {
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-    pub fn new_depth_texture(device: &wgpu::Device, surface_config: &wgpu::SurfaceConfiguration) -> Self {
+    /// Creates a texture that can be rendered into and sampled from.
+    /// Depth targets get a comparison sampler, color targets a regular one.
+    pub fn new_render_target(
+        device: &wgpu::Device,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> Self {
         let size = wgpu::Extent3d {
-            width: surface_config.width,
-            height: surface_config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("depth_texture"),
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            format: Self::DEPTH_FORMAT,
+            label: Some(label),
+            usage,
+            format,
             dimension: wgpu::TextureDimension::D2,
             mip_level_count: 1,
             sample_count: 1,
             size,
-            view_formats: &[Self::DEPTH_FORMAT],
+            view_formats: &[format],
         });
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("depth_texture_view"),
+            label: Some(&format!("{label}_view")),
             ..Default::default()
         });
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("depth_texture_sampler"),
+            label: Some(&format!("{label}_sampler")),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Nearest,
             min_filter: wgpu::FilterMode::Nearest,
             mipmap_filter: wgpu::FilterMode::Nearest,
-            compare: Some(wgpu::CompareFunction::Less),
+            compare: format
+                .is_depth_stencil_format()
+                .then_some(wgpu::CompareFunction::Less),
             lod_min_clamp: 0.0,
             lod_max_clamp: 100.0,
             ..Default::default()
@@ -47,4 +89,418 @@ impl Texture {
 
         Self { texture, view, sampler }
     }
+
+    /// Loads a PNG or an uncompressed RGBA8 KTX2 image as a texture.
+    /// The mip levels of a KTX2 image are uploaded as they are, all other mip levels are generated on the GPU.
+    pub fn load_image(ctx: &RenderCtx, path: impl AsRef<Path>, sampler: &wgpu::SamplerDescriptor) -> Result<Self> {
+        let path = path.as_ref();
+        let image = decode_image(path).with_context(|| format!("Failed to load image {}", path.display()))?;
+
+        let (width, height) = (image.width, image.height);
+        // Images that only provide their base level get a full mip chain, which is generated on the GPU
+        let mip_level_count = match image.levels.len() {
+            1 => u32::BITS - width.max(height).leading_zeros(),
+            provided => provided as u32,
+        };
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let label = path.to_string_lossy();
+        let texture = ctx
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(&*label),
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: image.format,
+                dimension: wgpu::TextureDimension::D2,
+                mip_level_count,
+                sample_count: 1,
+                size,
+                view_formats: &[],
+            });
+
+        for (mip_level, pixels) in image.levels.iter().enumerate() {
+            let mip_size = size.mip_level_size(mip_level as u32, wgpu::TextureDimension::D2);
+            ctx.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    mip_level: mip_level as u32,
+                    ..texture.as_image_copy()
+                },
+                pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * mip_size.width),
+                    rows_per_image: Some(mip_size.height),
+                },
+                mip_size,
+            );
+        }
+
+        if image.levels.len() < mip_level_count as usize {
+            generate_mipmaps(ctx, &texture, image.format, mip_level_count);
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = ctx.device.create_sampler(sampler);
+
+        Ok(Self { texture, view, sampler })
+    }
+
+    /// Loads a texture atlas of [ATLAS_TILES_PER_ROW] x [ATLAS_TILES_PER_ROW] square tiles, which is not filtered up close
+    pub fn load_atlas(ctx: &RenderCtx, path: impl AsRef<Path>) -> Result<Self> {
+        let atlas = Self::load_image(
+            ctx,
+            path,
+            &wgpu::SamplerDescriptor {
+                label: Some("Texture atlas sampler"),
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            },
+        )?;
+
+        let (width, height) = (atlas.texture.width(), atlas.texture.height());
+        if width != height || width % ATLAS_TILES_PER_ROW != 0 {
+            bail!("The texture atlas must be a square of {ATLAS_TILES_PER_ROW} x {ATLAS_TILES_PER_ROW} tiles, but it is {width} x {height} pixels");
+        }
+
+        Ok(atlas)
+    }
+
+    /// A texture with a single transparent texel, e.g. in place of a texture that could not be loaded
+    pub fn transparent(ctx: &RenderCtx, label: &str) -> Self {
+        const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let size = wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = ctx
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                format: FORMAT,
+                dimension: wgpu::TextureDimension::D2,
+                mip_level_count: 1,
+                sample_count: 1,
+                size,
+                view_formats: &[],
+            });
+
+        ctx.queue.write_texture(
+            texture.as_image_copy(),
+            &[0; 4],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: Some(1),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = ctx
+            .device
+            .create_sampler(&wgpu::SamplerDescriptor::default());
+
+        Self { texture, view, sampler }
+    }
+
+    /// Creates a bind group with the view at binding 0 and the sampler at binding 1, see [TEXTURE_BIND_GROUP_LAYOUT_ENTRIES]
+    pub fn create_bind_group(&self, ctx: &RenderCtx, label: &str) -> wgpu::BindGroup {
+        let layout = ctx.bind_group_layout("Texture bind group layout", TEXTURE_BIND_GROUP_LAYOUT_ENTRIES);
+
+        ctx.device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&self.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+    }
+}
+
+/// Pixels of an image with 4 bytes per texel
+#[derive(Debug)]
+struct DecodedImage {
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    /// Texels of the base level followed by the texels of the mip levels that the image provides
+    levels: Vec<Vec<u8>>,
+}
+
+/// Decodes a KTX2 file if it has the `ktx2` extension and a PNG file otherwise
+fn decode_image(path: &Path) -> Result<DecodedImage> {
+    let is_ktx2 = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ktx2"));
+    if is_ktx2 {
+        return decode_ktx2(&fs::read(path)?);
+    }
+
+    let (width, height, pixels) = decode_png(path)?;
+    Ok(DecodedImage {
+        width,
+        height,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        levels: vec![pixels],
+    })
+}
+
+/// Decodes a PNG file into 8 bit RGBA pixels
+fn decode_png(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    data.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], u8::MAX])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        png::ColorType::Grayscale => data
+            .iter()
+            .flat_map(|&g| [g, g, g, u8::MAX])
+            .collect(),
+        png::ColorType::Indexed => bail!("Indexed images should have been expanded by the decoder"),
+    };
+
+    Ok((info.width, info.height, pixels))
+}
+
+/// File identifier at the start of every KTX2 file
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+/// Size of the identifier, the header and the index of a KTX2 file, after which the level index starts
+const KTX2_LEVEL_INDEX_OFFSET: usize = 80;
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+
+/// Decodes a KTX2 file with a single 2D image in an uncompressed RGBA8 format.
+/// Block compressed formats and supercompression would need a transcoder, so they are rejected.
+fn decode_ktx2(bytes: &[u8]) -> Result<DecodedImage> {
+    let u32_at = |offset: usize| -> Result<u32> {
+        let bytes = bytes
+            .get(offset..offset + 4)
+            .context("The KTX2 file is truncated")?;
+        Ok(u32::from_le_bytes(bytes.try_into()?))
+    };
+    let u64_at = |offset: usize| -> Result<u64> {
+        let bytes = bytes
+            .get(offset..offset + 8)
+            .context("The KTX2 file is truncated")?;
+        Ok(u64::from_le_bytes(bytes.try_into()?))
+    };
+
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        bail!("The file is not a KTX2 file");
+    }
+
+    let format = match u32_at(12)? {
+        VK_FORMAT_R8G8B8A8_SRGB => wgpu::TextureFormat::Rgba8UnormSrgb,
+        VK_FORMAT_R8G8B8A8_UNORM => wgpu::TextureFormat::Rgba8Unorm,
+        vk_format => bail!("The KTX2 format {vk_format} is not supported, only uncompressed RGBA8 images are"),
+    };
+    let (width, height, depth) = (u32_at(20)?, u32_at(24)?, u32_at(28)?);
+    let (layer_count, face_count, level_count) = (u32_at(32)?, u32_at(36)?, u32_at(40)?);
+    if width == 0 || height == 0 || depth != 0 || layer_count != 0 || face_count != 1 {
+        bail!("Only single 2D KTX2 images are supported, but the image is {width} x {height} x {depth} with {layer_count} layers and {face_count} faces");
+    }
+    if u32_at(44)? != 0 {
+        bail!("Supercompressed KTX2 images are not supported");
+    }
+
+    // A level count of 0 asks the loader to generate the mip levels
+    let max_level_count = u32::BITS - width.max(height).leading_zeros();
+    if level_count > max_level_count {
+        bail!("The KTX2 image has {level_count} mip levels, but a {width} x {height} image has at most {max_level_count}");
+    }
+
+    let levels = (0..level_count.max(1))
+        .map(|level| {
+            let index = KTX2_LEVEL_INDEX_OFFSET + 24 * level as usize;
+            let (offset, length) = (u64_at(index)? as usize, u64_at(index + 8)? as usize);
+            let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+            if length != 4 * level_width as usize * level_height as usize {
+                bail!("Mip level {level} of the KTX2 image has {length} bytes, but {level_width} x {level_height} texels");
+            }
+
+            let texels = bytes
+                .get(offset..offset + length)
+                .with_context(|| format!("Mip level {level} of the KTX2 file is truncated"))?;
+            Ok(texels.to_vec())
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(DecodedImage {
+        width,
+        height,
+        format,
+        levels,
+    })
+}
+
+/// Renders every mip level of the texture from the previous level with a linear filter
+fn generate_mipmaps(ctx: &RenderCtx, texture: &wgpu::Texture, format: wgpu::TextureFormat, mip_level_count: u32) {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("mipmap.wgsl"));
+    let layout = ctx.pipeline_layout("Mipmap pipeline layout", &[TEXTURE_BIND_GROUP_LAYOUT_ENTRIES], &[]);
+
+    let pipeline = ctx
+        .device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(format.into())],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        });
+
+    let sampler = ctx
+        .device
+        .create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+    let mip_views: Vec<_> = (0..mip_level_count)
+        .map(|mip_level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Mip level view"),
+                base_mip_level: mip_level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    let bind_group_layout = ctx.bind_group_layout("Texture bind group layout", TEXTURE_BIND_GROUP_LAYOUT_ENTRIES);
+    let mut encoder = ctx
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap encoder"),
+        });
+
+    for target in 1..mip_level_count as usize {
+        let bind_group = ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Mipmap bind group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&mip_views[target - 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mipmap pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &mip_views[target],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    ctx.queue.submit(Some(encoder.finish()));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rendering::texture::{decode_ktx2, KTX2_IDENTIFIER, KTX2_LEVEL_INDEX_OFFSET, VK_FORMAT_R8G8B8A8_SRGB};
+
+    /// A KTX2 file of a 2 x 1 image with the given format and mip levels
+    fn ktx2_file(vk_format: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+        let mut file = KTX2_IDENTIFIER.to_vec();
+        // Format, type size, width, height, depth, layers, faces, levels and supercompression
+        for value in [vk_format, 1, 2, 1, 0, 0, 1, levels.len() as u32, 0] {
+            file.extend(value.to_le_bytes());
+        }
+        // The data format descriptor, the key/value data and the supercompression data are empty
+        file.resize(KTX2_LEVEL_INDEX_OFFSET, 0);
+
+        let mut offset = KTX2_LEVEL_INDEX_OFFSET + 24 * levels.len();
+        for level in levels {
+            for value in [offset, level.len(), level.len()] {
+                file.extend((value as u64).to_le_bytes());
+            }
+            offset += level.len();
+        }
+        file.extend(levels.concat());
+
+        file
+    }
+
+    #[test]
+    fn test_decode_ktx2_with_mip_levels() {
+        let levels = vec![(0..8).collect::<Vec<u8>>(), vec![9, 10, 11, 12]];
+        let image = decode_ktx2(&ktx2_file(VK_FORMAT_R8G8B8A8_SRGB, &levels)).unwrap();
+
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(image.levels, levels);
+    }
+
+    #[test]
+    fn test_decode_ktx2_rejects_compressed_formats_and_truncated_levels() {
+        // VK_FORMAT_BC7_SRGB_BLOCK
+        assert!(decode_ktx2(&ktx2_file(146, &[vec![0; 16]])).is_err());
+
+        let mut truncated = ktx2_file(VK_FORMAT_R8G8B8A8_SRGB, &[vec![0; 8]]);
+        truncated.pop();
+        assert!(decode_ktx2(&truncated).is_err());
+    }
 }
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use itertools::iproduct;
+use wgpu::TextureFormat;
+
+use crate::rendering::readback::TextureReadback;
+
+/// Width of world thumbnails in pixels. The height follows from the aspect ratio of the captured frame.
+pub const THUMBNAIL_WIDTH: u32 = 256;
+/// Name of the thumbnail inside of the world directory
+pub const THUMBNAIL_FILE_NAME: &str = "thumbnail.png";
+
+/// Scales a captured frame down to a thumbnail and writes it as a PNG image
+pub fn save_thumbnail(capture: &TextureReadback, data: &[u8], path: &Path) -> Result<()> {
+    let rgb = rgb_pixels(capture, data)?;
+    let (width, height, pixels) = downscale(&rgb, capture.width, capture.height, THUMBNAIL_WIDTH);
+
+    write_png(path, width, height, &pixels).context("Could not write thumbnail")
+}
+
+/// RGB pixels of a captured frame, without the row padding and the alpha channel, as saved images are always opaque
+pub(super) fn rgb_pixels(capture: &TextureReadback, data: &[u8]) -> Result<Vec<u8>> {
+    let rgb_offsets = match capture.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => [0, 1, 2],
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => [2, 1, 0],
+        format => bail!("Images of the surface format {format:?} are not supported"),
+    };
+
+    Ok(data
+        .chunks_exact(capture.bytes_per_row as usize)
+        .take(capture.height as usize)
+        .flat_map(|row| row[..capture.width as usize * 4].chunks_exact(4))
+        .flat_map(|texel| rgb_offsets.map(|offset| texel[offset]))
+        .collect())
+}
+
+pub(super) fn write_png(path: &Path, width: u32, height: u32, rgb: &[u8]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Could not create image file {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgb)?;
+
+    Ok(())
+}
+
+/// Averages blocks of RGB pixels, so that the image is at most `max_width` pixels wide
+pub(super) fn downscale(rgb: &[u8], width: u32, height: u32, max_width: u32) -> (u32, u32, Vec<u8>) {
+    let factor = width.div_ceil(max_width).max(1);
+    let (scaled_width, scaled_height) = ((width / factor).max(1), (height / factor).max(1));
+
+    let mut pixels = Vec::with_capacity((scaled_width * scaled_height * 3) as usize);
+    for y in 0..scaled_height {
+        for x in 0..scaled_width {
+            let mut sum = [0u32; 3];
+            for (dx, dy) in iproduct!(0..factor, 0..factor) {
+                let index = (((y * factor + dy).min(height - 1) * width + (x * factor + dx).min(width - 1)) * 3) as usize;
+                for (sum, &value) in sum.iter_mut().zip(&rgb[index..index + 3]) {
+                    *sum += value as u32;
+                }
+            }
+
+            pixels.extend(sum.map(|channel| (channel / (factor * factor)) as u8));
+        }
+    }
+
+    (scaled_width, scaled_height, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rendering::thumbnail::downscale;
+
+    #[test]
+    fn test_downscale_averages_blocks() {
+        #[rustfmt::skip]
+        let rgb = [
+            0, 0, 0,  40, 0, 0,  10, 10, 10,  10, 10, 10,
+            20, 0, 0,  60, 0, 8,  10, 10, 10,  10, 10, 10,
+        ];
+
+        assert_eq!(downscale(&rgb, 4, 2, 2), (2, 1, vec![30, 0, 2, 10, 10, 10]));
+        assert_eq!(downscale(&rgb, 4, 2, 4), (4, 2, rgb.to_vec()));
+    }
+}
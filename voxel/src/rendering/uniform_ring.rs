@@ -0,0 +1,95 @@
+use std::marker::PhantomData;
+use std::mem;
+
+use bytemuck::Pod;
+
+use crate::rendering::{RenderCtx, RenderHandle, FRAMES_IN_FLIGHT};
+
+/// A uniform buffer with one slice per frame in flight.
+///
+/// Every frame writes its uniforms into the slice of its frame context and binds it with a dynamic offset,
+/// so that a write never touches the slice that a previous frame may still be reading from.
+pub struct UniformRing<T: Pod> {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// Size of a single slice, aligned to the minimum uniform buffer offset alignment
+    stride: u64,
+    current: usize,
+    _marker: PhantomData<T>,
+}
+
+/// A bind group together with the dynamic offset of the slice that is used for the current frame
+#[derive(Copy, Clone)]
+pub struct UniformBinding<'a> {
+    pub bind_group: &'a wgpu::BindGroup,
+    pub offset: u32,
+}
+
+impl<T: Pod> UniformRing<T> {
+    /// Creates the buffer and a bind group with the buffer at binding 0.
+    /// The layout entry at binding 0 must have a dynamic offset and a minimum binding size of `size_of::<T>()`.
+    pub fn new(ctx: &RenderCtx, label: &str, layout: &wgpu::BindGroupLayout, initial: &T) -> Self {
+        let alignment = ctx
+            .device
+            .limits()
+            .min_uniform_buffer_offset_alignment as u64;
+        let stride = (mem::size_of::<T>() as u64).next_multiple_of(alignment);
+
+        let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: stride * FRAMES_IN_FLIGHT as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(mem::size_of::<T>() as u64),
+                    }),
+                }],
+            });
+
+        let ring = Self {
+            buffer,
+            bind_group,
+            stride,
+            current: 0,
+            _marker: PhantomData,
+        };
+
+        for slot in 0..FRAMES_IN_FLIGHT {
+            ctx.queue
+                .write_buffer(&ring.buffer, slot as u64 * stride, bytemuck::bytes_of(initial));
+        }
+
+        ring
+    }
+
+    /// Writes the uniforms of the current frame into the slice that belongs to it
+    pub fn write(&mut self, handle: &mut RenderHandle, value: &T) {
+        self.current = handle.frame_index();
+
+        handle.write_buffer(&self.buffer, self.current as u64 * self.stride, bytemuck::bytes_of(value));
+    }
+
+    pub fn binding(&self) -> UniformBinding {
+        UniformBinding {
+            bind_group: &self.bind_group,
+            offset: (self.current as u64 * self.stride) as u32,
+        }
+    }
+}
+
+impl<'a> UniformBinding<'a> {
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass<'a>, index: u32) {
+        render_pass.set_bind_group(index, self.bind_group, &[self.offset]);
+    }
+}
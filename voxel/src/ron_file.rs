@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Loads a value from a RON file. If the file does not exist or is invalid, the default value is used instead.
+/// `what` names the contents of the file in the log, e.g. `"settings"`.
+pub fn load_or_default<T: DeserializeOwned + Default>(path: &Path, what: &str) -> T {
+    if !path.exists() {
+        return T::default();
+    }
+
+    match load(path, what) {
+        Ok(value) => value,
+        Err(err) => {
+            warn!("Could not load {what} from {path:?}, using the defaults instead: {err:#}");
+            T::default()
+        }
+    }
+}
+
+pub fn load<T: DeserializeOwned>(path: &Path, what: &str) -> Result<T> {
+    let content = fs::read_to_string(path).with_context(|| format!("could not read {what} file"))?;
+    ron::from_str(&content).with_context(|| format!("could not parse {what} file"))
+}
+
+/// Writes a value into a pretty RON file, whose directory is created if it does not exist yet
+pub fn save<T: Serialize>(path: &Path, value: &T, what: &str) -> Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).with_context(|| format!("could not create the directory {directory:?}"))?;
+    }
+
+    let content =
+        ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()).with_context(|| format!("could not serialize {what}"))?;
+    fs::write(path, content).with_context(|| format!("could not write {what} file {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::ron_file::{load_or_default, save};
+
+    #[test]
+    fn test_invalid_files_load_the_default() {
+        let directory = std::env::temp_dir().join(format!("voxel-ron-file-test-{}", std::process::id()));
+        let path = directory.join("values.ron");
+
+        assert_eq!(load_or_default::<Vec<u32>>(&path, "values"), Vec::<u32>::new());
+        save(&path, &vec![1u32, 2, 3], "values").unwrap();
+        assert_eq!(load_or_default::<Vec<u32>>(&path, "values"), [1, 2, 3]);
+        fs::write(&path, "[1, 2,").unwrap();
+        assert_eq!(load_or_default::<Vec<u32>>(&path, "values"), Vec::<u32>::new());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
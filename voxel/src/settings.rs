@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::Accessibility;
+use crate::debug_overlay::OverlayTheme;
+use crate::power_saving::PowerSavingMode;
+use crate::ron_file;
+
+/// User settings that are persisted between runs of the engine
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub overlay_theme: OverlayTheme,
+    /// Id under which the state of the local player is saved in every world, generated on the first run
+    pub player_id: Option<String>,
+    pub accessibility: Accessibility,
+    pub power_saving: PowerSavingMode,
+    pub graphics: GraphicsSettings,
+    /// Console variables that were changed from their default, see [crate::cvars]
+    pub cvars: BTreeMap<String, f32>,
+}
+
+/// Settings that are chosen on the setup screen of the first run, see [crate::setup_wizard::SetupWizard]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// Distance in chunks up to which chunks are rendered
+    pub render_distance: i32,
+    /// Also enabled by the command line flag
+    pub vsync: bool,
+    /// Also enabled by the command line flag
+    pub fullscreen: bool,
+    /// Threads that generate and mesh chunks, 0 for one per CPU core
+    pub worker_threads: usize,
+    pub mouse_sensitivity: f32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            render_distance: 12,
+            vsync: false,
+            fullscreen: false,
+            worker_threads: 0,
+            mouse_sensitivity: 0.5,
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the settings from the given file. If the file does not exist or is invalid, the default settings are used instead.
+    pub fn load(path: &Path) -> Self {
+        ron_file::load_or_default(path, "settings")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        ron_file::save(path, self, "settings")
+    }
+}
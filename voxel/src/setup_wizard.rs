@@ -0,0 +1,43 @@
+use std::num::NonZeroUsize;
+use std::thread;
+
+use egui::{Slider, Ui};
+
+use crate::settings::GraphicsSettings;
+
+/// Setup screen of the first run, on which the graphics settings are chosen before the world is entered.
+///
+/// The world is already rendered behind it with the chosen settings, so that their effect on the frame rate can be seen.
+pub struct SetupWizard {
+    pub graphics: GraphicsSettings,
+    /// Set once the player confirmed the settings
+    pub finished: bool,
+}
+
+impl SetupWizard {
+    pub fn new(graphics: GraphicsSettings) -> Self {
+        Self { graphics, finished: false }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, average_fps: f32) {
+        ui.label("Choose the settings to play with. The world behind this window is rendered with them.");
+        ui.label(format!("FPS: {:.1} ({:.2}ms)", average_fps, 1000.0 / average_fps));
+        ui.separator();
+
+        ui.add(Slider::new(&mut self.graphics.render_distance, 1..=32).text("render distance"))
+            .on_hover_text("Distance in chunks up to which the world is rendered");
+        ui.checkbox(&mut self.graphics.vsync, "vsync")
+            .on_hover_text("Limits the frame rate to the refresh rate of the display");
+        ui.checkbox(&mut self.graphics.fullscreen, "fullscreen");
+        let cores = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        ui.add(Slider::new(&mut self.graphics.worker_threads, 0..=cores).text("worker threads"))
+            .on_hover_text("Threads that generate and mesh chunks, 0 for one per CPU core. Applies from the next start.");
+        ui.add(Slider::new(&mut self.graphics.mouse_sensitivity, 0.1..=2.0).text("mouse sensitivity"));
+        ui.separator();
+
+        if ui.button("Enter the world").clicked() {
+            self.finished = true;
+        }
+        ui.label("All settings are saved in the settings file, where they can be changed later");
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use cgmath::{Point3, Vector2, Vector3};
+use fastrand::Rng;
+use log::{info, warn};
+
+use crate::rendering::camera::Camera;
+use crate::world::chunk_manager::ChunkManager;
+use crate::world::chunk_renderer::block_icons::solid_voxel_types;
+use crate::world::location::WorldLocation;
+use crate::world::pathfinding::PathCosts;
+use crate::world::voxel_data::{VoxelData, VoxelType};
+
+/// Time between two steps of the bot from one voxel column to the next
+const STEP_INTERVAL: Duration = Duration::from_millis(100);
+/// Maximum horizontal distance of a new walking target to the current position
+const TARGET_RANGE: i32 = 96;
+/// Highest step that the bot can climb. Higher walls are tunneled through.
+const MAX_CLIMB: i32 = 1;
+/// Vertical range around the bot that is searched for the terrain surface of a column
+const SURFACE_SEARCH_RANGE: i32 = 48;
+const DIG_DOWN_CHANCE: f32 = 0.03;
+const PLACE_CHANCE: f32 = 0.05;
+
+/// Settings of an unattended soak test run
+#[derive(Copy, Clone, Debug)]
+pub struct SoakConfig {
+    pub duration: Duration,
+    pub seed: u64,
+}
+
+/// A scripted player that walks over the terrain heightmap towards random targets, tunnels through walls that are too steep
+/// and digs and places blocks at random. It exercises voxel edits, remeshing and chunk streaming without any input.
+///
+/// The same seed always makes the same decisions, as long as the same terrain is generated.
+pub struct SoakBot {
+    rng: Rng,
+    end: Instant,
+    /// Column that the bot is currently walking towards
+    target: Vector2<i32>,
+    /// Remaining voxels of the path to the target, if one was found. Otherwise the bot walks straight and tunnels.
+    path: VecDeque<Vector3<i32>>,
+    /// Voxel in which the feet of the bot are
+    position: Vector3<i32>,
+    until_next_step: Duration,
+    pub steps: u64,
+    pub edits: u64,
+}
+
+impl SoakBot {
+    pub fn new(config: SoakConfig, start: Point3<f32>) -> Self {
+        info!("Running soak test for {:?} with seed {}", config.duration, config.seed);
+
+        let position = Vector3::new(start.x.floor() as i32, start.y.floor() as i32, start.z.floor() as i32);
+
+        let mut bot = Self {
+            rng: Rng::with_seed(config.seed),
+            end: Instant::now() + config.duration,
+            target: Vector2::new(position.x, position.z),
+            path: VecDeque::new(),
+            position,
+            until_next_step: Duration::ZERO,
+            steps: 0,
+            edits: 0,
+        };
+        bot.target = bot.random_target();
+
+        bot
+    }
+
+    pub fn is_finished(&self) -> bool {
+        Instant::now() >= self.end
+    }
+
+    /// Advances the bot by one simulation tick and moves the camera along with it
+    pub fn update(&mut self, camera: &mut Camera, chunk_manager: &mut ChunkManager, dt: Duration) {
+        self.until_next_step = self.until_next_step.saturating_sub(dt);
+        if self.until_next_step.is_zero() {
+            self.until_next_step = STEP_INTERVAL;
+            self.step(chunk_manager);
+        }
+
+        camera.position = Point3::new(self.position.x as f32 + 0.5, self.position.y as f32, self.position.z as f32 + 0.5);
+    }
+
+    fn step(&mut self, chunk_manager: &mut ChunkManager) {
+        self.steps += 1;
+
+        if Vector2::new(self.position.x, self.position.z) == self.target {
+            self.choose_target(chunk_manager);
+        }
+
+        let rolled = self.rng.f32();
+        if rolled < DIG_DOWN_CHANCE {
+            self.break_voxel(chunk_manager, self.position - Vector3::unit_y());
+        } else if rolled < DIG_DOWN_CHANCE + PLACE_CHANCE {
+            self.place_random_block(chunk_manager);
+        }
+
+        if let Some(next) = self.path.pop_front() {
+            if chunk_manager.is_walkable(WorldLocation(next)) {
+                self.position = next;
+                return;
+            }
+
+            // The path was changed by an edit
+            self.path.clear();
+        }
+
+        let next = self.position + self.next_direction();
+        let Some(surface) = surface_height(chunk_manager, next) else {
+            // The terrain in front of the bot is not generated yet, so walk somewhere else
+            self.choose_target(chunk_manager);
+            return;
+        };
+
+        if surface - self.position.y > MAX_CLIMB {
+            // Too steep to climb, dig a tunnel that is two voxels high instead
+            let tunnel = Vector3::new(next.x, self.position.y, next.z);
+            self.break_voxel(chunk_manager, tunnel);
+            self.break_voxel(chunk_manager, tunnel + Vector3::unit_y());
+            self.position = tunnel;
+        } else {
+            self.position = Vector3::new(next.x, surface, next.z);
+        }
+    }
+
+    /// Direction of one step towards the target along the axis with the larger distance
+    fn next_direction(&self) -> Vector3<i32> {
+        let dx = self.target.x - self.position.x;
+        let dz = self.target.y - self.position.z;
+
+        if dx.abs() >= dz.abs() {
+            Vector3::new(dx.signum(), 0, 0)
+        } else {
+            Vector3::new(0, 0, dz.signum())
+        }
+    }
+
+    /// Chooses a new random target and walks to it along a path over the terrain, if there is one
+    fn choose_target(&mut self, chunk_manager: &ChunkManager) {
+        self.target = self.random_target();
+        self.path.clear();
+
+        let goal = Vector3::new(self.target.x, self.position.y, self.target.y);
+        let Some(surface) = surface_height(chunk_manager, goal) else {
+            return;
+        };
+
+        if let Some(path) = chunk_manager.find_path(
+            WorldLocation(self.position),
+            WorldLocation(Vector3::new(goal.x, surface, goal.z)),
+            &PathCosts::default(),
+        ) {
+            self.path = path
+                .into_iter()
+                .skip(1)
+                .map(|location| location.0)
+                .collect();
+        }
+    }
+
+    fn random_target(&mut self) -> Vector2<i32> {
+        Vector2::new(
+            self.position.x + self.rng.i32(-TARGET_RANGE..=TARGET_RANGE),
+            self.position.z + self.rng.i32(-TARGET_RANGE..=TARGET_RANGE),
+        )
+    }
+
+    /// Places a random block next to the bot, on top of the terrain
+    fn place_random_block(&mut self, chunk_manager: &mut ChunkManager) {
+        let types = solid_voxel_types();
+        let Some(&ty) = types.get(self.rng.usize(0..types.len())) else {
+            return;
+        };
+
+        let side = [Vector3::unit_x(), -Vector3::unit_x(), Vector3::unit_z(), -Vector3::unit_z()][self.rng.usize(0..4)];
+        let column = self.position + side;
+        let Some(surface) = surface_height(chunk_manager, column) else {
+            return;
+        };
+
+        self.set_voxel(chunk_manager, Vector3::new(column.x, surface, column.z), ty);
+    }
+
+    fn break_voxel(&mut self, chunk_manager: &mut ChunkManager, location: Vector3<i32>) {
+        if chunk_manager
+            .voxel_at(WorldLocation(location))
+            .is_some_and(VoxelType::is_solid)
+        {
+            self.set_voxel(chunk_manager, location, VoxelType::Air);
+        }
+    }
+
+    fn set_voxel(&mut self, chunk_manager: &mut ChunkManager, location: Vector3<i32>, ty: VoxelType) {
+        match chunk_manager.set_voxel(WorldLocation(location), VoxelData::new(ty)) {
+            Ok(()) => self.edits += 1,
+            Err(err) => warn!("Soak test could not edit voxel: {err:#}"),
+        }
+    }
+}
+
+/// Height of the first non-solid voxel above the highest solid voxel of a column near the given location
+fn surface_height(chunk_manager: &ChunkManager, near: Vector3<i32>) -> Option<i32> {
+    let mut above_is_air = false;
+
+    for y in (near.y - SURFACE_SEARCH_RANGE..=near.y + SURFACE_SEARCH_RANGE).rev() {
+        let ty = chunk_manager.voxel_at(WorldLocation(Vector3::new(near.x, y, near.z)))?;
+
+        if ty.is_solid() && above_is_air {
+            return Some(y + 1);
+        }
+        above_is_air = !ty.is_solid();
+    }
+
+    None
+}
@@ -1,5 +1,7 @@
+use std::time::Instant;
+
 use winit::event::{Event, WindowEvent};
-use winit::event_loop::EventLoop;
+use winit::event_loop::{ControlFlow, EventLoop};
 
 use crate::{Engine, EngineConfig};
 
@@ -17,11 +19,22 @@ pub fn start(engine_config: EngineConfig) -> ! {
         }
 
         match event {
-            Event::MainEventsCleared => {
-                engine.window.request_redraw();
-            }
+            Event::MainEventsCleared => match engine.next_frame_time() {
+                Some(next_frame) if Instant::now() < next_frame => *control_flow = ControlFlow::WaitUntil(next_frame),
+                _ => {
+                    *control_flow = ControlFlow::Poll;
+                    engine.window.request_redraw();
+                }
+            },
             Event::RedrawRequested(_) => {
                 engine.render();
+
+                if engine.soak_test_finished() || engine.execute_console_commands() {
+                    *control_flow = ControlFlow::ExitWithCode(0);
+                }
+                if let Some(exit_code) = engine.benchmark_finished() {
+                    *control_flow = ControlFlow::ExitWithCode(exit_code);
+                }
             }
             _ => engine.handle_event(event, control_flow),
         }
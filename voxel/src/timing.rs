@@ -1,34 +1,161 @@
-use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::time::Instant;
 
-use itertools::Itertools;
+use log::warn;
 
+/// Default number of frames that span averages are computed over
+pub const DEFAULT_HISTORY_LEN: usize = 60;
+
+/// Records nested timing spans.
+///
+/// Spans started while another span is running become children of that span.
+/// Every call to [TimerManager::finish_frame] closes the current frame and stores the accumulated duration of each span,
+/// so that averages over the last [TimerManager::history_len] frames can be displayed.
 pub struct TimerManager {
-    pub current_timers: HashMap<String, Instant>,
-    pub finished_timers: HashMap<String, f32>,
+    nodes: Vec<SpanNode>,
+    roots: Vec<usize>,
+    open_spans: Vec<OpenSpan>,
+    frame_start: Instant,
+    pub history_len: usize,
+}
+
+struct OpenSpan {
+    node: usize,
+    start: Instant,
+}
+
+/// A span in the span tree. A span is identified by its name and its parent span.
+pub struct SpanNode {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Accumulated duration of this span in every of the last frames in seconds
+    durations: VecDeque<f32>,
+    /// Offset of the first start of this span relative to the start of its parent span in seconds
+    offsets: VecDeque<f32>,
+    current_duration: f32,
+    current_offset: Option<f32>,
+}
+
+impl SpanNode {
+    fn new(name: String, parent: Option<usize>) -> Self {
+        Self {
+            name,
+            parent,
+            children: Vec::new(),
+            durations: VecDeque::new(),
+            offsets: VecDeque::new(),
+            current_duration: 0.0,
+            current_offset: None,
+        }
+    }
+
+    /// Average duration per frame in seconds
+    pub fn average(&self) -> f32 {
+        average(&self.durations)
+    }
+
+    /// Duration in the most recently finished frame in seconds
+    pub fn last(&self) -> f32 {
+        self.durations.back().copied().unwrap_or(0.0)
+    }
+
+    /// Maximum duration of a single frame in seconds
+    pub fn max(&self) -> f32 {
+        self.durations.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Average start offset relative to the parent span in seconds
+    pub fn average_offset(&self) -> f32 {
+        average(&self.offsets)
+    }
+
+    /// Whether this span was running in any of the recorded frames
+    pub fn is_active(&self) -> bool {
+        self.durations.iter().any(|&d| d > 0.0)
+    }
 }
 
 impl TimerManager {
     pub fn new() -> Self {
         Self {
-            current_timers: HashMap::new(),
-            finished_timers: HashMap::new(),
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            open_spans: Vec::new(),
+            frame_start: Instant::now(),
+            history_len: DEFAULT_HISTORY_LEN,
         }
     }
 
     pub fn start<S: AsRef<str>>(&mut self, name: S) {
-        self.current_timers
-            .insert(name.as_ref().to_string(), Instant::now());
+        let now = Instant::now();
+        let name = name.as_ref();
+
+        let (parent, parent_start) = match self.open_spans.last() {
+            Some(open) => (Some(open.node), open.start),
+            None => (None, self.frame_start),
+        };
+
+        let siblings = match parent {
+            Some(parent) => &self.nodes[parent].children,
+            None => &self.roots,
+        };
+        let existing = siblings
+            .iter()
+            .copied()
+            .find(|&id| self.nodes[id].name == name);
+
+        let node = match existing {
+            Some(node) => node,
+            None => {
+                let node = self.nodes.len();
+                self.nodes
+                    .push(SpanNode::new(name.to_owned(), parent));
+                match parent {
+                    Some(parent) => self.nodes[parent].children.push(node),
+                    None => self.roots.push(node),
+                }
+                node
+            }
+        };
+
+        let span = &mut self.nodes[node];
+        if span.current_offset.is_none() {
+            span.current_offset = Some(now.duration_since(parent_start).as_secs_f32());
+        }
+
+        self.open_spans
+            .push(OpenSpan { node, start: now });
     }
 
+    /// Ends the innermost running span, which should have the given name.
+    /// Spans that were started inside of it but not ended are discarded with a warning, instead of crashing the engine.
     pub fn end<S: AsRef<str>>(&mut self, name: S) -> f32 {
-        let start = self
-            .current_timers
-            .remove(name.as_ref())
-            .expect("timer was not started yet");
-        let duration = Instant::now().duration_since(start).as_secs_f32();
-        self.finished_timers
-            .insert(name.as_ref().to_string(), duration);
+        let name = name.as_ref();
+        let Some(position) = self
+            .open_spans
+            .iter()
+            .rposition(|open| self.nodes[open.node].name == name)
+        else {
+            warn!("timer {name:?} can not be ended, as it was not started");
+            return 0.0;
+        };
+
+        for discarded in self.open_spans.drain(position + 1..) {
+            warn!(
+                "timer {:?} was not ended before the outer timer {name:?}, its duration is discarded",
+                self.nodes[discarded.node].name
+            );
+        }
+        let open = self
+            .open_spans
+            .pop()
+            .expect("the ended span to be open");
+
+        let duration = Instant::now()
+            .duration_since(open.start)
+            .as_secs_f32();
+        self.nodes[open.node].current_duration += duration;
 
         duration
     }
@@ -40,14 +167,56 @@ impl TimerManager {
         duration
     }
 
-    pub fn get_all(&self) -> Vec<(&String, f32)> {
-        self.finished_timers
-            .iter()
-            .map(|x| (x.0, *x.1))
-            .collect_vec()
+    /// Stores the durations of all spans of the current frame into the history and starts a new frame
+    pub fn finish_frame(&mut self) {
+        let history_len = self.history_len.max(1);
+
+        for span in &mut self.nodes {
+            span.durations.push_back(span.current_duration);
+            span.offsets
+                .push_back(span.current_offset.unwrap_or(0.0));
+            span.current_duration = 0.0;
+            span.current_offset = None;
+
+            while span.durations.len() > history_len {
+                span.durations.pop_front();
+                span.offsets.pop_front();
+            }
+        }
+
+        self.frame_start = Instant::now();
+    }
+
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
     }
 
-    pub fn clear(&mut self) {
-        self.finished_timers.clear();
+    pub fn node(&self, id: usize) -> &SpanNode {
+        &self.nodes[id]
+    }
+
+    /// Looks up a span by its path of names from the root, e.g. `["render_all", "chunk_manager"]`
+    pub fn find(&self, path: &[&str]) -> Option<&SpanNode> {
+        let mut candidates = &self.roots;
+        let mut found = None;
+
+        for name in path {
+            let id = candidates
+                .iter()
+                .copied()
+                .find(|&id| self.nodes[id].name == *name)?;
+            candidates = &self.nodes[id].children;
+            found = Some(id);
+        }
+
+        found.map(|id| &self.nodes[id])
+    }
+}
+
+fn average(values: &VecDeque<f32>) -> f32 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f32>() / values.len() as f32
     }
 }
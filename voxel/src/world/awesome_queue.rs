@@ -21,6 +21,11 @@ impl<T: Hash + PartialEq> AwesomeQueue<T> {
         self.queue.lock().unwrap().retain(|x| x != t);
     }
 
+    /// Removes all elements for which `keep` returns false
+    pub fn retain(&self, keep: impl FnMut(&T) -> bool) {
+        self.queue.lock().unwrap().retain(keep);
+    }
+
     pub fn take_all(&self) -> Vec<T> {
         let mut queue = self.queue.lock().unwrap();
         let num_elems = queue.len();
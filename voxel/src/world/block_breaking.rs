@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use crate::world::location::WorldLocation;
+use crate::world::raycast::RaycastHit;
+use crate::world::voxel_data::VoxelType;
+
+/// Progress of breaking the targeted voxel while the break button is held
+pub struct BlockBreaker {
+    /// The voxel face that is currently looked at
+    pub target: Option<RaycastHit>,
+    /// Time for which the targeted voxel has been broken so far
+    elapsed: Duration,
+    /// Hardness of the targeted voxel
+    hardness: Duration,
+}
+
+impl BlockBreaker {
+    pub fn new() -> Self {
+        Self {
+            target: None,
+            elapsed: Duration::ZERO,
+            hardness: Duration::ZERO,
+        }
+    }
+
+    /// Fraction of the targeted voxel that is broken, in `0.0..=1.0`
+    pub fn progress(&self) -> f32 {
+        if self.hardness.is_zero() {
+            return 0.0;
+        }
+
+        (self.elapsed.as_secs_f32() / self.hardness.as_secs_f32()).min(1.0)
+    }
+
+    /// Advances the breaking of the voxel at `target`. The progress is reset if the target changes or breaking is stopped.
+    ///
+    /// Returns the voxel once it is broken.
+    pub fn update(&mut self, target: Option<(RaycastHit, VoxelType)>, breaking: bool, dt: Duration) -> Option<WorldLocation> {
+        let previous_voxel = self.target.map(|hit| hit.voxel);
+        self.target = target.map(|(hit, _)| hit);
+
+        let Some((hit, ty)) = target.filter(|_| breaking) else {
+            self.elapsed = Duration::ZERO;
+            return None;
+        };
+
+        if previous_voxel != Some(hit.voxel) || self.hardness != ty.hardness() {
+            self.elapsed = Duration::ZERO;
+            self.hardness = ty.hardness();
+        }
+
+        self.elapsed += dt;
+        if self.elapsed < self.hardness {
+            return None;
+        }
+
+        self.elapsed = Duration::ZERO;
+        Some(hit.voxel)
+    }
+}
@@ -0,0 +1,112 @@
+// Size of a chunk in voxels, must be equal to CHUNK_SIZE
+const CHUNK_SIZE: f32 = 32.0;
+
+struct Frustum {
+	planes: array<vec4<f32>, 6>,
+}
+
+struct ChunkDrawRecord {
+	position: vec3<f32>,
+	index_count: u32,
+	first_index: u32,
+	base_vertex: i32,
+	active: u32,
+	_padding: u32,
+}
+
+struct DrawIndexedIndirect {
+	index_count: u32,
+	instance_count: u32,
+	first_index: u32,
+	base_vertex: i32,
+	first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> frustum: Frustum;
+
+@group(0) @binding(1)
+var<storage, read> records: array<ChunkDrawRecord>;
+
+@group(0) @binding(2)
+var<storage, read_write> commands: array<DrawIndexedIndirect>;
+
+@group(0) @binding(3)
+var<storage, read_write> draw_count: u32;
+
+struct DrawOrder {
+	length: u32,
+	// Indices of the records from the first to the last chunk that is drawn
+	indices: array<u32>,
+}
+
+@group(0) @binding(4)
+var<storage, read> draw_order: DrawOrder;
+
+// Must be equal to the size of the workgroup and of the offsets
+const WORKGROUP_SIZE: u32 = 256u;
+
+// Number of visible chunks of every thread, which is turned into their offset in the commands
+var<workgroup> offsets: array<u32, 256>;
+
+fn is_visible(record: ChunkDrawRecord) -> bool {
+	if (record.active == 0u || record.index_count == 0u) {
+		return false;
+	}
+
+	let aabb_min = record.position;
+	let aabb_max = record.position + vec3(CHUNK_SIZE);
+	for (var i = 0u; i < 6u; i++) {
+		let plane = frustum.planes[i];
+		let positive_vertex = select(aabb_min, aabb_max, plane.xyz >= vec3(0.0));
+		if (dot(plane.xyz, positive_vertex) + plane.w < 0.0) {
+			return false;
+		}
+	}
+	return true;
+}
+
+// Tests every chunk draw record against the camera frustum and writes a draw command for every visible chunk, keeping the draw order
+// so that the closest chunks can be drawn first. Every thread culls a contiguous range of the draw order and writes its commands after
+// the commands of all previous threads, so this runs as a single workgroup.
+// The record index is passed as the instance index, so that the vertex shader can look up the chunk position.
+@compute @workgroup_size(256)
+fn cs_main(@builtin(local_invocation_index) thread: u32) {
+	let per_thread = (draw_order.length + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+	let start = min(thread * per_thread, draw_order.length);
+	let end = min(start + per_thread, draw_order.length);
+
+	var visible = 0u;
+	for (var i = start; i < end; i++) {
+		if (is_visible(records[draw_order.indices[i]])) {
+			visible++;
+		}
+	}
+
+	// Inclusive prefix sum of the visible chunks of all threads
+	offsets[thread] = visible;
+	workgroupBarrier();
+	for (var stride = 1u; stride < WORKGROUP_SIZE; stride *= 2u) {
+		var sum = offsets[thread];
+		if (thread >= stride) {
+			sum += offsets[thread - stride];
+		}
+		workgroupBarrier();
+		offsets[thread] = sum;
+		workgroupBarrier();
+	}
+
+	var command_index = offsets[thread] - visible;
+	for (var i = start; i < end; i++) {
+		let record_index = draw_order.indices[i];
+		let record = records[record_index];
+		if (is_visible(record)) {
+			commands[command_index] = DrawIndexedIndirect(record.index_count, 1u, record.first_index, record.base_vertex, record_index);
+			command_index++;
+		}
+	}
+
+	if (thread == WORKGROUP_SIZE - 1u) {
+		draw_count = offsets[thread];
+	}
+}
@@ -1,12 +1,17 @@
 use std::fmt::{Debug, Formatter};
 
+use serde::{Deserialize, Serialize};
+
 use crate::world::location::{LocalChunkLocation, OutsideBounds, WithinBounds};
+use crate::world::palette::PalettedVoxels;
 use crate::world::voxel_data::{VoxelData, VoxelType};
 use crate::world::CHUNK_SIZE;
 
 #[derive(Clone)]
 pub enum ChunkData {
     Voxels(Box<[VoxelData; CHUNK_SIZE.pow(3)]>),
+    /// Compressed voxels of chunks with few distinct voxels, which is what edits of uniform chunks and the world generator produce
+    Paletted(PalettedVoxels),
     UniformType(VoxelData),
 }
 
@@ -45,6 +50,13 @@ impl ChunkData {
             return;
         }
 
+        if let Self::Paletted(voxels) = self {
+            if let Some(data) = voxels.uniform_voxel() {
+                *self = Self::new_with_uniform_data(data);
+            }
+            return;
+        }
+
         let mut uniform_data = None;
         for loc in LocalChunkLocation::iter() {
             if let Some(a) = uniform_data {
@@ -64,6 +76,7 @@ impl ChunkData {
     pub fn get_voxel(&self, local_chunk_location: LocalChunkLocation<WithinBounds>) -> &VoxelData {
         match self {
             Self::Voxels(data) => &data[Self::position_to_index(local_chunk_location)],
+            Self::Paletted(voxels) => voxels.get(Self::position_to_index(local_chunk_location)),
             Self::UniformType(voxel_data) => voxel_data,
         }
     }
@@ -71,17 +84,15 @@ impl ChunkData {
     pub fn set_voxel_data(&mut self, local_chunk_location: LocalChunkLocation<WithinBounds>, new_voxel_data: VoxelData) {
         match self {
             Self::Voxels(data) => data[Self::position_to_index(local_chunk_location)] = new_voxel_data,
+            Self::Paletted(voxels) => voxels.set(Self::position_to_index(local_chunk_location), new_voxel_data),
             Self::UniformType(uniform_data) => {
                 if *uniform_data == new_voxel_data {
                     return;
                 }
 
-                *self = Self::new_filled_with_uniform_data(*uniform_data);
-
-                match self {
-                    Self::Voxels(data) => data[Self::position_to_index(local_chunk_location)] = new_voxel_data,
-                    Self::UniformType(_) => unreachable!(),
-                }
+                let mut voxels = PalettedVoxels::new_uniform(*uniform_data);
+                voxels.set(Self::position_to_index(local_chunk_location), new_voxel_data);
+                *self = Self::Paletted(voxels);
             }
         }
     }
@@ -90,7 +101,149 @@ impl ChunkData {
         Some(self.get_voxel(local_chunk_location.try_into_checked()?))
     }
 
+    /// Changes that turn this chunk into `other`, as runs of consecutive changed voxels
+    pub fn diff(&self, other: &ChunkData) -> ChunkDiff {
+        let mut runs: Vec<(u16, Vec<VoxelData>)> = Vec::new();
+
+        if let (Self::UniformType(a), Self::UniformType(b)) = (self, other) {
+            if a == b {
+                return ChunkDiff::default();
+            }
+        }
+
+        for index in 0..CHUNK_SIZE.pow(3) {
+            let (old, new) = (self.get_voxel_by_index(index), other.get_voxel_by_index(index));
+            if old == new {
+                continue;
+            }
+
+            match runs.last_mut() {
+                Some((start, voxels)) if *start as usize + voxels.len() == index => voxels.push(new),
+                _ => runs.push((index as u16, vec![new])),
+            }
+        }
+
+        ChunkDiff { runs }
+    }
+
+    /// Applies changes that were created by [ChunkData::diff]
+    pub fn apply(&mut self, diff: &ChunkDiff) {
+        if diff.is_empty() {
+            return;
+        }
+
+        if let Self::UniformType(uniform_data) = self {
+            *self = Self::Paletted(PalettedVoxels::new_uniform(*uniform_data));
+        }
+
+        for (start, voxels) in &diff.runs {
+            let start = *start as usize;
+            match self {
+                Self::Voxels(data) => data[start..start + voxels.len()].copy_from_slice(voxels),
+                Self::Paletted(paletted) => {
+                    for (offset, &voxel) in voxels.iter().enumerate() {
+                        paletted.set(start + offset, voxel);
+                    }
+                }
+                Self::UniformType(_) => unreachable!("uniform chunks were converted above"),
+            }
+        }
+    }
+
+    /// Voxel at an index of [ChunkData::position_to_index]
+    pub fn get_voxel_by_index(&self, index: usize) -> VoxelData {
+        match self {
+            Self::Voxels(data) => data[index],
+            Self::Paletted(voxels) => *voxels.get(index),
+            Self::UniformType(voxel_data) => *voxel_data,
+        }
+    }
+
+    /// Converts the voxels into the most compact representation, a uniform chunk or a compacted palette
+    pub fn compress(&mut self) {
+        self.try_convert_into_uniform();
+
+        match self {
+            Self::Voxels(data) => *self = Self::Paletted(PalettedVoxels::from_voxels(data.iter().copied())),
+            Self::Paletted(voxels) => voxels.compact(),
+            Self::UniformType(_) => {}
+        }
+    }
+
+    /// Size of the voxel data in memory
+    pub fn size_in_bytes(&self) -> usize {
+        match self {
+            Self::Voxels(_) => CHUNK_SIZE.pow(3) * std::mem::size_of::<VoxelData>(),
+            Self::Paletted(voxels) => voxels.size_in_bytes(),
+            Self::UniformType(_) => std::mem::size_of::<VoxelData>(),
+        }
+    }
+
     pub fn position_to_index(local_chunk_location: LocalChunkLocation<WithinBounds>) -> usize {
         local_chunk_location.z as usize * CHUNK_SIZE.pow(2) + local_chunk_location.y as usize * CHUNK_SIZE + local_chunk_location.x as usize
     }
 }
+
+/// Compact list of voxel changes between two versions of a chunk, e.g. to send edits over the network,
+/// to undo them or to find the modified chunks that have to be saved
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkDiff {
+    /// Index of the first voxel of every run (see [ChunkData::position_to_index]) and the new data of its voxels, sorted by index
+    pub runs: Vec<(u16, Vec<VoxelData>)>,
+}
+
+impl ChunkDiff {
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// Number of changed voxels
+    pub fn len(&self) -> usize {
+        self.runs
+            .iter()
+            .map(|(_, voxels)| voxels.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::location::LocalChunkLocation;
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    #[test]
+    fn test_diff_roundtrip() {
+        let old = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+        let mut new = old.clone();
+        for x in 3..7 {
+            new.set_voxel_data(
+                LocalChunkLocation::new(Vector3::new(x, 1, 2))
+                    .try_into_checked()
+                    .unwrap(),
+                VoxelData::new(VoxelType::Air),
+            );
+        }
+        new.set_voxel_data(
+            LocalChunkLocation::new(Vector3::new(0, 31, 31))
+                .try_into_checked()
+                .unwrap(),
+            VoxelData::new(VoxelType::Water),
+        );
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.runs.len(), 2);
+        assert_eq!(diff.len(), 5);
+        assert!(new.diff(&new).is_empty());
+
+        let mut applied = old.clone();
+        applied.apply(&diff);
+        assert!(applied.diff(&new).is_empty());
+
+        // The reverse diff undoes the changes
+        applied.apply(&new.diff(&old));
+        assert!(applied.diff(&old).is_empty());
+    }
+}
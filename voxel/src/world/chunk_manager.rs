@@ -1,24 +1,51 @@
+use std::any::Any;
 use std::collections::vec_deque::VecDeque;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
-use std::sync::Arc;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use std::{mem, thread};
 
-use anyhow::{bail, Result};
-use cgmath::Vector3;
+use anyhow::{bail, Context, Result};
+use cgmath::{Array, EuclideanSpace, InnerSpace, Vector3, Zero};
 use itertools::{iproduct, Itertools};
+use log::warn;
 use rayon::prelude::*;
-use wgpu::{BindGroup, RenderPass};
+use wgpu::RenderPass;
 
-use crate::rendering::{RenderCtx, Renderer};
+use crate::cvars::CVar;
+use crate::network::client::ServerConnection;
+use crate::network::protocol::{ClientMessage, ServerMessage};
+use crate::rendering::camera::Camera;
+use crate::rendering::day_cycle::RawLighting;
+use crate::rendering::debug_boxes::DebugBoxes;
+use crate::rendering::debug_lines::DebugLines;
+use crate::rendering::shadow_cascades::SHADOW_CASCADE_COUNT;
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::{RenderCtx, RenderHandle, Renderer};
 use crate::timing::TimerManager;
 use crate::world::awesome_queue::AwesomeQueue;
 use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::light_bounce::{BounceGrid, ChunkCells, CELLS_PER_CHUNK};
 use crate::world::chunk_renderer::meshing::NeighborChunks;
-use crate::world::chunk_renderer::ChunkRenderManager;
-use crate::world::location::ChunkLocation;
+use crate::world::chunk_renderer::ray_tracing::{LightingMode, RayTracedLighting};
+use crate::world::chunk_renderer::texture_atlas::VoxelTextureAtlas;
+use crate::world::chunk_renderer::water::WaterRenderer;
+use crate::world::chunk_renderer::{ChunkDraw, ChunkMeshData, ChunkRenderManager, MeshKind, MeshSize, MeshingMode, RenderMode};
+use crate::world::collision::{collision_boxes, CollisionBox};
+use crate::world::job_queue::JobQueue;
+use crate::world::location::{ChunkLocation, LocalChunkLocation, WithinBounds, WorldLocation};
+use crate::world::occupancy::ChunkOccupancy;
+use crate::world::pathfinding::{find_path, is_walkable, PathCosts};
+use crate::world::raycast::{raycast, RaycastHit};
+use crate::world::storage::{RegionReader, WorldStorage};
 use crate::world::voxel_data::{VoxelData, VoxelType};
-use crate::world::worldgen::WorldGenerator;
+use crate::world::world_edits::WorldEdits;
+use crate::world::worldgen::{place_structure_voxel, ChunkGenerator, GeneratedChunk, WorldGenerator};
+use crate::world::worldgen_preset::WorldgenPreset;
 use crate::world::CHUNK_SIZE;
 
 #[derive(Debug)]
@@ -29,11 +56,14 @@ pub enum Chunk {
     },
     Generated {
         data: ChunkData,
+        occupancy: ChunkOccupancy,
         num_neighbors_generated: u8,
         queued_for_meshing: bool,
     },
     Meshed {
         data: ChunkData,
+        occupancy: ChunkOccupancy,
+        mesh_kind: MeshKind,
     },
 }
 
@@ -53,6 +83,23 @@ impl Chunk {
         }
     }
 
+    pub fn get_occupancy(&self) -> Option<&ChunkOccupancy> {
+        match self {
+            Chunk::Generated { occupancy, .. } => Some(occupancy),
+            Chunk::Meshed { occupancy, .. } => Some(occupancy),
+            Chunk::None { .. } => None,
+        }
+    }
+
+    /// Voxel data together with its occupancy, which has to be updated for every voxel that is changed
+    pub fn get_data_and_occupancy_mut(&mut self) -> Option<(&mut ChunkData, &mut ChunkOccupancy)> {
+        match self {
+            Chunk::Generated { data, occupancy, .. } => Some((data, occupancy)),
+            Chunk::Meshed { data, occupancy, .. } => Some((data, occupancy)),
+            Chunk::None { .. } => None,
+        }
+    }
+
     pub fn neighbor_count(&self) -> Option<u8> {
         match self {
             Chunk::None {
@@ -124,28 +171,32 @@ impl Chunk {
         }
     }
 
-    pub fn attach_data(&mut self, data: ChunkData) -> Result<()> {
+    /// Attaches generated data to the chunk, or replaces its data if it has some already and returns the replaced data.
+    /// A meshed chunk keeps its outdated mesh until it is remeshed, see [ChunkManager::set_chunk_data].
+    pub fn attach_data(&mut self, data: ChunkData) -> Option<ChunkData> {
+        if let Some((previous_data, occupancy)) = self.get_data_and_occupancy_mut() {
+            *occupancy = ChunkOccupancy::new(&data);
+            return Some(mem::replace(previous_data, data));
+        }
+
         let Chunk::None {
             num_neighbors_generated, ..
         } = *self
         else {
-            bail!(
-                "Cannot attach data to a chunk that is not of the StoredChunk::None type. Note: Updating chunk data is not yet supported. self={:?}",
-                self
-            )
+            unreachable!("only chunks without data have no data");
         };
-
         *self = Chunk::Generated {
+            occupancy: ChunkOccupancy::new(&data),
             data,
             num_neighbors_generated,
             queued_for_meshing: false,
         };
 
-        Ok(())
+        None
     }
 
-    pub fn attach_mesh(&mut self) -> Result<()> {
-        let Chunk::Generated { data, .. } = self else {
+    pub fn attach_mesh(&mut self, mesh_kind: MeshKind) -> Result<()> {
+        let Chunk::Generated { data, occupancy, .. } = self else {
             bail!(
                 "Cannot attach data to a chunk that is not of the StoredChunk::None type. self={:?}",
                 self
@@ -157,79 +208,364 @@ impl Chunk {
         const TEMP_EMPTY_DATA: ChunkData = ChunkData::UniformType(VoxelData::new(VoxelType::Air));
         let previous_chunk_data = mem::replace(data, TEMP_EMPTY_DATA.clone());
 
-        *self = Chunk::Meshed { data: previous_chunk_data };
+        *self = Chunk::Meshed {
+            data: previous_chunk_data,
+            occupancy: mem::replace(occupancy, ChunkOccupancy::EMPTY),
+            mesh_kind,
+        };
+
+        Ok(())
+    }
+
+    /// Decreases the neighbor count after a generated neighbor chunk was unloaded.
+    ///
+    /// Chunks that were meshed or queued for meshing become generated chunks that are not queued, as they can only be meshed
+    /// once all of their neighbors are generated again. Returns whether the chunk lost its mesh or its place in the mesh queue.
+    pub fn remove_neighbor(&mut self) -> bool {
+        match self {
+            Chunk::None {
+                num_neighbors_generated, ..
+            } => {
+                *num_neighbors_generated = num_neighbors_generated.saturating_sub(1);
+                false
+            }
+            Chunk::Generated {
+                num_neighbors_generated,
+                queued_for_meshing,
+                ..
+            } => {
+                *num_neighbors_generated = num_neighbors_generated.saturating_sub(1);
+                mem::replace(queued_for_meshing, false)
+            }
+            Chunk::Meshed { data, occupancy, .. } => {
+                let data = mem::replace(data, ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air)));
+                *self = Chunk::Generated {
+                    data,
+                    occupancy: mem::replace(occupancy, ChunkOccupancy::EMPTY),
+                    num_neighbors_generated: 25,
+                    queued_for_meshing: false,
+                };
+                true
+            }
+        }
+    }
+
+    /// Marks the chunk as neither queued for data generation nor for meshing, after its queued work was canceled
+    pub fn dequeue(&mut self) {
+        match self {
+            Chunk::None { queued_for_datagen, .. } => *queued_for_datagen = false,
+            Chunk::Generated { queued_for_meshing, .. } => *queued_for_meshing = false,
+            Chunk::Meshed { .. } => {}
+        }
+    }
+
+    /// Turns a meshed chunk back into a generated chunk that is queued for meshing, so that its mesh can be regenerated
+    pub fn detach_mesh(&mut self) -> Result<()> {
+        let Chunk::Meshed { data, occupancy, .. } = self else {
+            bail!("Cannot detach the mesh of a chunk that is not meshed. self={:?}", self)
+        };
+
+        const TEMP_EMPTY_DATA: ChunkData = ChunkData::UniformType(VoxelData::new(VoxelType::Air));
+        let previous_chunk_data = mem::replace(data, TEMP_EMPTY_DATA.clone());
+
+        *self = Chunk::Generated {
+            data: previous_chunk_data,
+            occupancy: mem::replace(occupancy, ChunkOccupancy::EMPTY),
+            num_neighbors_generated: 26,
+            queued_for_meshing: true,
+        };
 
         Ok(())
     }
 }
 
+/// Index of a dimension in [ChunkManager::dimension_names]
+pub type DimensionId = usize;
+
+/// Structure voxels that are placed into a chunk, by the chunk whose structures they belong to
+type StructureVoxels = HashMap<ChunkLocation, Vec<(LocalChunkLocation<WithinBounds>, VoxelType)>>;
+
+/// Manages the chunks of the active dimension, which are streamed around the player and rendered.
+///
+/// All other dimensions keep their chunks and meshes in a [Dimension], which is swapped with the active state when switching to it.
 pub struct ChunkManager {
     pub chunks: hashbrown::HashMap<ChunkLocation, Chunk>,
-    chunk_generator: Arc<WorldGenerator>,
+    chunk_generator: Arc<dyn ChunkGenerator>,
+    active_dimension: DimensionId,
+    dimension_names: Vec<String>,
+    inactive_dimensions: HashMap<DimensionId, Dimension>,
+    /// Directory in which every dimension saves its chunks in a subdirectory, if the world is persisted
+    world_path: Option<PathBuf>,
+    /// Saved chunks of the active dimension, which are loaded instead of being generated
+    storage: Option<WorldStorage>,
+    /// Textures of the voxel faces of all dimensions
+    texture_atlas: VoxelTextureAtlas,
     last_player_position: ChunkLocation,
+    /// World position of the player at the last call to [ChunkManager::update_player_location]
+    last_player_world_position: Vector3<f32>,
+    /// Smoothed velocity of the player in blocks per second, estimated from its movement
+    player_velocity: Vector3<f32>,
+    /// Chunk in which the player is predicted to be after [ChunkManager::prefetch_time]
+    pub predicted_player_position: ChunkLocation,
+    /// How many seconds ahead of the player movement chunks are prefetched. Zero disables the prediction.
+    pub prefetch_time: f32,
     pub chunk_mesh_queue: VecDeque<ChunkLocation>,
     current_chunk_generate_radius: i32,
     pub current_chunk_mesh_radius: i32,
+    /// Radius up to which chunks beyond the render distance are generated while the streaming is idle
+    pub deep_generation_radius: i32,
+    /// Number of rings beyond the render distance that are generated, but not meshed, while the streaming is idle
+    pub deep_generation_distance: i32,
+    /// Whether the last frame left enough time to generate chunks beyond the render distance
+    pub idle_generation: bool,
+    /// Deep generation stops once the voxel data of all chunks is larger than this many bytes
+    pub voxel_memory_budget: usize,
+    /// Whether fewer chunks are generated and meshed to save power, see [ChunkManager::set_power_saving]
+    power_saving: bool,
+    /// Number of chunk data generator threads that take requests, the others sleep
+    active_data_gen_threads: Arc<AtomicUsize>,
 
-    pub total_vertices: usize,
-    pub total_triangles: usize,
     pub total_voxel_data_size: usize,
-    pub total_mesh_data_size: usize,
+    /// Number of chunks whose voxel data was generated or loaded since the chunk manager was created
+    pub generated_chunks: u64,
+    /// Number of chunk meshes that were generated since the chunk manager was created
+    pub meshed_chunks: u64,
+    /// Chunks beyond the unload distance, which are unloaded a few at a time
+    unload_queue: VecDeque<ChunkLocation>,
+    /// Player chunk and unload distance of the last search for chunks to unload
+    last_unload_check: Option<(ChunkLocation, i32)>,
 
     pub render_distance: i32,
     pub render_empty_chunks: bool,
+    /// Width in chunks of the outermost ring of the render distance, whose chunks are meshed as heightmap skins
+    skin_ring_width: i32,
+    /// Time per frame that is spent on meshing chunks and uploading their meshes, halved while saving power
+    pub mesh_budget: Duration,
 
-    pub location_queue: Arc<AwesomeQueue<ChunkLocation>>,
+    /// Chunks whose data is generated by the data generation threads, the closest to the predicted player position first
+    pub location_queue: Arc<JobQueue<(DimensionId, ChunkLocation), ChunkGenRequest>>,
     pub generated_chunks_queue: Arc<AwesomeQueue<ChunkGenResult>>,
     // pub mesh_gen_queue: Arc<AwesomeQueue<(ChunkLocation)>>,
     // pub generated_meshes_queue: Arc<AwesomeQueue<ChunkGenResult>>,
     chunk_render_manager: ChunkRenderManager,
+    /// Chunks that may cast shadows into each shadow cascade, selected every frame before rendering
+    pub shadow_casters: [Vec<ChunkLocation>; SHADOW_CASCADE_COUNT],
+    /// Ray traced ambient occlusion and shadows of the active dimension, if compute shaders are supported
+    ray_traced_lighting: Option<RayTracedLighting>,
+    /// Direction towards the sun at the last call to [ChunkManager::update_lighting]
+    to_sun: Vector3<f32>,
+    /// Voxels that the structures of generated chunks place into other chunks, by the chunk that they are placed into.
+    /// They are kept until neither that chunk nor any of its neighbors are loaded, so that it gets them again when it is generated again.
+    structure_voxels: HashMap<ChunkLocation, StructureVoxels>,
+    /// Loaded chunks whose generation or meshing failed, see [ChunkManager::failed_chunks]
+    failed_chunks: HashMap<ChunkLocation, ChunkFailure>,
+    /// Edits of the player, which are applied again to chunks that are generated again after they were unloaded
+    edits: WorldEdits,
+    /// Server that generates the chunks of all dimensions and shares the edits with other players, see [ChunkManager::connect]
+    server: Option<ServerConnection>,
 }
 
 struct MeshGenQuery {
     chunk_data: [ChunkData; 27],
 }
 
-pub struct ChunkGenResult(ChunkLocation, ChunkData);
+/// The streaming state of a dimension that is not active, with the same meaning as the equally named fields of [ChunkManager]
+struct Dimension {
+    chunks: hashbrown::HashMap<ChunkLocation, Chunk>,
+    chunk_generator: Arc<dyn ChunkGenerator>,
+    chunk_mesh_queue: VecDeque<ChunkLocation>,
+    chunk_render_manager: ChunkRenderManager,
+    total_voxel_data_size: usize,
+    /// Chunk data that was generated for this dimension while it was inactive
+    pending_results: Vec<ChunkGenResult>,
+    storage: Option<WorldStorage>,
+    structure_voxels: HashMap<ChunkLocation, StructureVoxels>,
+    failed_chunks: HashMap<ChunkLocation, ChunkFailure>,
+    edits: WorldEdits,
+}
+
+impl Dimension {
+    fn new(
+        chunk_generator: Arc<dyn ChunkGenerator>,
+        storage: Option<WorldStorage>,
+        texture_atlas: &VoxelTextureAtlas,
+        render_ctx: &RenderCtx,
+    ) -> Self {
+        Self {
+            chunks: hashbrown::HashMap::new(),
+            chunk_generator,
+            chunk_mesh_queue: VecDeque::new(),
+            chunk_render_manager: ChunkRenderManager::new(render_ctx, Arc::clone(&texture_atlas.bind_group)),
+            total_voxel_data_size: 0,
+            pending_results: Vec::new(),
+            storage,
+            structure_voxels: HashMap::new(),
+            failed_chunks: HashMap::new(),
+            edits: WorldEdits::new(),
+        }
+    }
+}
+
+/// A chunk whose data is generated by the data generation threads with the generator of its dimension
+pub struct ChunkGenRequest {
+    dimension: DimensionId,
+    location: ChunkLocation,
+    generator: Arc<dyn ChunkGenerator>,
+    /// Saved chunks of the dimension, which are loaded instead of being generated
+    storage: Option<Arc<Mutex<RegionReader>>>,
+}
+
+impl ChunkGenRequest {
+    /// Loads the chunk from the storage of its dimension, or generates it if it was never saved or could not be loaded
+    fn load_or_generate(&self) -> ChunkGenResult {
+        let saved = self.storage.as_ref().and_then(|reader| {
+            reader
+                .lock()
+                .expect("Region reader lock to not be poisoned")
+                .load_chunk(self.location)
+                .unwrap_or_else(|err| {
+                    warn!("Could not load the chunk at {:?}, generating it instead: {err:#}", self.location);
+                    None
+                })
+        });
+        if let Some(data) = saved {
+            return ChunkGenResult {
+                dimension: self.dimension,
+                location: self.location,
+                data,
+                generator: Arc::clone(&self.generator),
+                structure_voxels: self.generator.outside_structure_voxels(self.location),
+                loaded_from_storage: true,
+                error: None,
+            };
+        }
+
+        // A panicking generator must not take the thread down with it, the chunk becomes a placeholder instead
+        let (GeneratedChunk { data, outside_voxels }, error) =
+            match panic::catch_unwind(AssertUnwindSafe(|| self.generator.generate_chunk(self.location))) {
+                Ok(generated) => (generated, None),
+                Err(payload) => (
+                    GeneratedChunk {
+                        data: ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Error)),
+                        outside_voxels: Vec::new(),
+                    },
+                    Some(panic_message(payload.as_ref())),
+                ),
+            };
+
+        ChunkGenResult {
+            dimension: self.dimension,
+            location: self.location,
+            data,
+            generator: Arc::clone(&self.generator),
+            structure_voxels: outside_voxels,
+            loaded_from_storage: false,
+            error,
+        }
+    }
+}
+
+/// Generated chunk data together with the generator that generated it, so that data of a replaced generator can be discarded
+pub struct ChunkGenResult {
+    dimension: DimensionId,
+    location: ChunkLocation,
+    data: ChunkData,
+    generator: Arc<dyn ChunkGenerator>,
+    /// Voxels of the structures of the chunk that are in other chunks
+    structure_voxels: Vec<(WorldLocation, VoxelType)>,
+    /// Whether the data was loaded from the storage of the world instead of being generated
+    loaded_from_storage: bool,
+    /// Why the generation failed, in which case the data is a [VoxelType::Error] placeholder
+    error: Option<String>,
+}
 
 impl PartialEq for ChunkGenResult {
     fn eq(&self, other: &Self) -> bool {
-        self.0.eq(&other.0)
+        (self.dimension, self.location) == (other.dimension, other.location)
     }
 }
 
 impl Hash for ChunkGenResult {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.hash(state)
+        (self.dimension, self.location).hash(state)
+    }
+}
+
+/// Step in which a chunk failed, see [ChunkFailure]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChunkFailureStage {
+    Generation,
+    Meshing,
+}
+
+impl ChunkFailureStage {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChunkFailureStage::Generation => "generation",
+            ChunkFailureStage::Meshing => "meshing",
+        }
     }
 }
 
+/// Why a chunk is rendered as a [VoxelType::Error] placeholder, see [ChunkManager::failed_chunks]
+#[derive(Clone, Debug)]
+pub struct ChunkFailure {
+    pub stage: ChunkFailureStage,
+    pub message: String,
+}
+
 const NUM_DATA_GEN_THREAD: usize = 8;
-const DATA_GEN_THREAD_BATCH_SIZE: usize = 20;
+/// Default of [ChunkManager::mesh_budget], which leaves most of a 60 FPS frame to the rest of the engine
+pub const DEFAULT_MESH_BUDGET: Duration = Duration::from_millis(2);
+/// Chunks are unloaded this many rings beyond the deep generation rings, so that moving back and forth at the border
+/// does not unload and generate the same chunks again and again
+const UNLOAD_MARGIN: i32 = 2;
+pub(crate) static MAX_UNLOADS_PER_TICK: CVar = cvar!("streaming.max_unloads_per_tick", 256.0);
+/// Movement faster than this in blocks per second is treated as a teleport and does not count towards the player velocity
+const MAX_PREDICTED_SPEED: f32 = 500.0;
+/// Time constant in seconds with which the estimated player velocity follows its actual movement
+const VELOCITY_SMOOTHING: f32 = 0.3;
+pub(crate) static DATA_GEN_THREAD_BATCH_SIZE: CVar = cvar!("streaming.data_gen_batch_size", 20.0);
 
 impl ChunkManager {
-    pub fn new(player_location: Vector3<f32>, render_ctx: &RenderCtx, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
-        let chunk_generator = Arc::new(WorldGenerator::new(123));
+    /// Creates the chunk manager with a single active dimension, whose chunks are generated with the given generator
+    pub fn new(
+        player_location: Vector3<f32>,
+        render_ctx: &RenderCtx,
+        dimension_name: &str,
+        chunk_generator: Arc<dyn ChunkGenerator>,
+    ) -> Self {
+        let texture_atlas = VoxelTextureAtlas::untextured(render_ctx);
 
-        let location_queue: Arc<AwesomeQueue<ChunkLocation>> = Arc::new(AwesomeQueue::new());
+        let location_queue: Arc<JobQueue<(DimensionId, ChunkLocation), ChunkGenRequest>> = Arc::new(JobQueue::new());
         let generated_chunks_queue: Arc<AwesomeQueue<ChunkGenResult>> = Arc::new(AwesomeQueue::new());
 
-        for _ in 0..NUM_DATA_GEN_THREAD {
-            let chunk_generator = Arc::clone(&chunk_generator);
+        let active_data_gen_threads = Arc::new(AtomicUsize::new(NUM_DATA_GEN_THREAD));
+        for index in 0..NUM_DATA_GEN_THREAD {
             let location_queue = Arc::clone(&location_queue);
             let generated_chunks_queue = Arc::clone(&generated_chunks_queue);
+            let active_data_gen_threads = Arc::clone(&active_data_gen_threads);
             thread::Builder::new()
                 .name("chunk data generator".to_owned())
                 .spawn(move || loop {
-                    let chunk_locs = location_queue.take_n(DATA_GEN_THREAD_BATCH_SIZE);
+                    if index >= active_data_gen_threads.load(Ordering::Relaxed) {
+                        thread::sleep(Duration::from_millis(50));
+                        continue;
+                    }
+
+                    let jobs = location_queue.take_n(DATA_GEN_THREAD_BATCH_SIZE.get().max(1.0) as usize);
 
-                    if chunk_locs.len() == 0 {
+                    if jobs.is_empty() {
                         thread::sleep(Duration::from_millis(5));
                     }
 
-                    chunk_locs
-                        .into_iter()
-                        .for_each(|loc| generated_chunks_queue.insert(ChunkGenResult(loc, chunk_generator.get_chunk_data_at(loc))));
+                    jobs.into_iter().for_each(|job| {
+                        // The player may have moved away from the chunk since it was taken with the batch
+                        if !job.token.is_canceled() {
+                            generated_chunks_queue.insert(job.payload.load_or_generate());
+                        }
+                        location_queue.finish(&job);
+                    });
                 })
                 .unwrap();
         }
@@ -237,28 +573,469 @@ impl ChunkManager {
         Self {
             chunks: hashbrown::HashMap::new(),
             chunk_generator,
+            active_dimension: 0,
+            dimension_names: vec![dimension_name.to_owned()],
+            inactive_dimensions: HashMap::new(),
+            world_path: None,
+            storage: None,
             last_player_position: ChunkLocation::from_world_location_f32(player_location),
+            last_player_world_position: player_location,
+            player_velocity: Vector3::zero(),
+            predicted_player_position: ChunkLocation::from_world_location_f32(player_location),
+            prefetch_time: 2.0,
             chunk_mesh_queue: VecDeque::new(),
             current_chunk_generate_radius: 0,
             current_chunk_mesh_radius: 0,
-            total_vertices: 0,
-            total_triangles: 0,
+            deep_generation_radius: 0,
+            deep_generation_distance: 4,
+            idle_generation: false,
+            voxel_memory_budget: 256 * 1024 * 1024,
+            power_saving: false,
+            active_data_gen_threads,
             total_voxel_data_size: 0,
-            total_mesh_data_size: 0,
+            generated_chunks: 0,
+            meshed_chunks: 0,
+            unload_queue: VecDeque::new(),
+            last_unload_check: None,
             render_distance: 16,
             render_empty_chunks: true,
+            skin_ring_width: 4,
+            mesh_budget: DEFAULT_MESH_BUDGET,
             location_queue,
             generated_chunks_queue,
-            chunk_render_manager: ChunkRenderManager::new(&render_ctx, camera_bind_group_layout),
+            chunk_render_manager: ChunkRenderManager::new(&render_ctx, Arc::clone(&texture_atlas.bind_group)),
+            texture_atlas,
+            shadow_casters: Default::default(),
+            ray_traced_lighting: RayTracedLighting::is_supported(render_ctx).then(|| RayTracedLighting::new(render_ctx)),
+            to_sun: Vector3::unit_y(),
+            structure_voxels: HashMap::new(),
+            failed_chunks: HashMap::new(),
+            edits: WorldEdits::new(),
+            server: None,
+        }
+    }
+
+    /// Adds a new inactive dimension, whose chunks are generated with the given generator
+    pub fn add_dimension(&mut self, name: &str, chunk_generator: Arc<dyn ChunkGenerator>, render_ctx: &RenderCtx) -> Result<DimensionId> {
+        if self.dimension(name).is_some() {
+            bail!("A dimension with the name {name:?} already exists");
+        }
+
+        let storage = self.open_storage(name)?;
+        let id = self.dimension_names.len();
+        self.dimension_names.push(name.to_owned());
+        self.inactive_dimensions
+            .insert(id, Dimension::new(chunk_generator, storage, &self.texture_atlas, render_ctx));
+
+        Ok(id)
+    }
+
+    /// Persists the chunks of all dimensions in the given directory. Chunks that were saved there before are loaded instead of generated.
+    ///
+    /// Must be called before the first chunks are generated, as chunks that are already generated are not loaded again.
+    pub fn open_world(&mut self, world_path: &Path) -> Result<()> {
+        self.world_path = Some(world_path.to_owned());
+
+        self.storage = self.open_storage(&self.dimension_names[self.active_dimension])?;
+        for (&id, dimension) in &mut self.inactive_dimensions {
+            dimension.storage = Self::open_dimension_storage(world_path, &self.dimension_names[id])?;
+        }
+
+        Ok(())
+    }
+
+    /// Directory of the world that was opened with [ChunkManager::open_world]
+    pub fn world_path(&self) -> Option<&Path> {
+        self.world_path.as_deref()
+    }
+
+    fn open_storage(&self, dimension_name: &str) -> Result<Option<WorldStorage>> {
+        match &self.world_path {
+            Some(world_path) => Self::open_dimension_storage(world_path, dimension_name),
+            None => Ok(None),
+        }
+    }
+
+    fn open_dimension_storage(world_path: &Path, dimension_name: &str) -> Result<Option<WorldStorage>> {
+        WorldStorage::open(world_path.join(dimension_name)).map(Some)
+    }
+
+    /// Saves the modified chunks of all dimensions and returns the number of written region files
+    pub fn save_world(&mut self) -> Result<usize> {
+        let mut written_regions = Self::save_dimension(&mut self.storage, &self.chunks)?;
+        for dimension in self.inactive_dimensions.values_mut() {
+            written_regions += Self::save_dimension(&mut dimension.storage, &dimension.chunks)?;
+        }
+
+        Ok(written_regions)
+    }
+
+    fn save_dimension(storage: &mut Option<WorldStorage>, chunks: &hashbrown::HashMap<ChunkLocation, Chunk>) -> Result<usize> {
+        match storage {
+            Some(storage) => storage.save(|location| chunks.get(&location).and_then(Chunk::get_data)),
+            None => Ok(0),
+        }
+    }
+
+    /// Requests the chunks of all dimensions from a server instead of generating or loading them, and sends it the edits of the player.
+    ///
+    /// Must be called before the first chunks are generated. The dimensions must have the same names as the ones of the server.
+    pub fn connect(&mut self, server: ServerConnection) {
+        self.server = Some(server);
+    }
+
+    pub fn server(&self) -> Option<&ServerConnection> {
+        self.server.as_ref()
+    }
+
+    /// Queues the chunks that were received from the server for attaching and applies the edits of other players.
+    /// Edits in inactive dimensions are not applied, their chunks are requested again once they are unloaded.
+    ///
+    /// Returns all other messages, e.g. the positions of other players.
+    pub fn receive_from_server(&mut self) -> Vec<ServerMessage> {
+        let Some(server) = &self.server else {
+            return Vec::new();
+        };
+
+        let messages = server.receive();
+        let mut other_messages = Vec::new();
+        for message in messages {
+            match message {
+                ServerMessage::ChunkData {
+                    dimension,
+                    location,
+                    data,
+                    outside_voxels,
+                    is_saved,
+                } => {
+                    let Some(id) = self.dimension(&dimension) else {
+                        warn!("Received a chunk of the unknown dimension {dimension:?}");
+                        continue;
+                    };
+                    let generator = match self.inactive_dimensions.get(&id) {
+                        Some(inactive) => &inactive.chunk_generator,
+                        None => &self.chunk_generator,
+                    };
+                    self.generated_chunks_queue.insert(ChunkGenResult {
+                        dimension: id,
+                        location,
+                        data,
+                        generator: Arc::clone(generator),
+                        structure_voxels: outside_voxels,
+                        loaded_from_storage: is_saved,
+                        error: None,
+                    });
+                }
+                ServerMessage::VoxelChanged {
+                    dimension,
+                    location,
+                    voxel_data,
+                } => {
+                    if self.dimension(&dimension) == Some(self.active_dimension) {
+                        // Chunks that are not generated yet get the edit from the server when they are requested
+                        let _ = self.edit_with(false, |transaction| transaction.set_voxel(location, voxel_data));
+                    }
+                }
+                message => other_messages.push(message),
+            }
+        }
+
+        other_messages
+    }
+
+    /// Loaded chunks of the active dimension whose generation or meshing failed, sorted by their location.
+    /// They are rendered as [VoxelType::Error] placeholders until they are unloaded.
+    pub fn failed_chunks(&self) -> Vec<(ChunkLocation, ChunkFailure)> {
+        self.failed_chunks
+            .iter()
+            .map(|(&location, failure)| (location, failure.clone()))
+            .sorted_by_key(|(location, _)| (location.x, location.y, location.z))
+            .collect()
+    }
+
+    /// Id of the dimension with the given name
+    pub fn dimension(&self, name: &str) -> Option<DimensionId> {
+        self.dimension_names
+            .iter()
+            .position(|dimension_name| dimension_name == name)
+    }
+
+    pub fn dimension_names(&self) -> &[String] {
+        &self.dimension_names
+    }
+
+    pub fn active_dimension(&self) -> DimensionId {
+        self.active_dimension
+    }
+
+    /// Makes another dimension the one that is streamed and rendered.
+    ///
+    /// The chunks and meshes of the previously active dimension are kept, so that switching back does not have to generate them again.
+    pub fn switch_dimension(&mut self, id: DimensionId) -> Result<()> {
+        if id == self.active_dimension {
+            return Ok(());
+        }
+        let Some(mut dimension) = self.inactive_dimensions.remove(&id) else {
+            bail!("There is no dimension with the id {id}");
+        };
+
+        self.swap_active_state(&mut dimension);
+        for result in mem::take(&mut dimension.pending_results) {
+            self.generated_chunks_queue.insert(result);
+        }
+        self.inactive_dimensions
+            .insert(self.active_dimension, dimension);
+        self.active_dimension = id;
+
+        // Start streaming around the player again, as the new dimension may not have been visited near the current location
+        self.current_chunk_generate_radius = 0;
+        self.current_chunk_mesh_radius = 0;
+        self.deep_generation_radius = 0;
+        self.requeue_changed_mesh_kinds();
+
+        Ok(())
+    }
+
+    /// Replaces the worldgen preset of the active dimension and generates all of its chunks again, see [ChunkManager::set_generator]
+    pub fn set_worldgen_preset(&mut self, preset: WorldgenPreset, render_ctx: &RenderCtx) -> Result<()> {
+        let generator = self.preset_generator(preset)?;
+        self.set_generator(generator, render_ctx);
+        Ok(())
+    }
+
+    /// Replaces the worldgen preset of the active dimension, see [ChunkManager::hot_swap_generator]
+    pub fn hot_swap_worldgen_preset(&mut self, preset: WorldgenPreset, keep_radius: i32, render_ctx: &RenderCtx) -> Result<()> {
+        let generator = self.preset_generator(preset)?;
+        self.hot_swap_generator(generator, keep_radius, render_ctx);
+        Ok(())
+    }
+
+    /// Generator of the world of the active dimension with another preset
+    fn preset_generator(&self, preset: WorldgenPreset) -> Result<Arc<dyn ChunkGenerator>> {
+        let world_config = self
+            .chunk_generator
+            .world_config()
+            .context("the generator of the active dimension has no worldgen presets")?;
+        Ok(Arc::new(WorldGenerator::new(world_config.clone(), preset)))
+    }
+
+    /// Replaces the generator of the active dimension and generates all of its chunks again.
+    /// Edits of the active dimension that were not saved are lost.
+    pub fn set_generator(&mut self, generator: Arc<dyn ChunkGenerator>, render_ctx: &RenderCtx) {
+        self.chunk_generator = generator;
+
+        self.chunks.clear();
+        self.chunk_mesh_queue.clear();
+        let active_dimension = self.active_dimension;
+        self.location_queue
+            .cancel_where(|&(dimension, _)| dimension == active_dimension);
+        self.chunk_render_manager = ChunkRenderManager::new(render_ctx, Arc::clone(&self.texture_atlas.bind_group));
+        if let Some(ray_traced_lighting) = &mut self.ray_traced_lighting {
+            ray_traced_lighting.clear();
+        }
+        self.total_voxel_data_size = 0;
+        self.structure_voxels.clear();
+        self.failed_chunks.clear();
+        self.edits.clear();
+        self.unload_queue.clear();
+        self.last_unload_check = None;
+        self.current_chunk_generate_radius = 0;
+        self.current_chunk_mesh_radius = 0;
+        self.deep_generation_radius = 0;
+    }
+
+    /// Replaces the generator of the active dimension, but keeps the chunks within the given distance to the player, so that
+    /// the terrain in view does not disappear. All chunks beyond it are generated again with the new generator.
+    ///
+    /// Structures of the kept chunks still reach into the regenerated chunks, so the border between both generators is not seamless.
+    pub fn hot_swap_generator(&mut self, generator: Arc<dyn ChunkGenerator>, keep_radius: i32, render_ctx: &RenderCtx) {
+        self.chunk_generator = generator;
+
+        // Chunks whose data is still generated with the previous generator are requested again, their results are discarded
+        let active_dimension = self.active_dimension;
+        self.location_queue
+            .cancel_where(|&(dimension, _)| dimension == active_dimension);
+        for chunk in self.chunks.values_mut() {
+            if let Chunk::None { .. } = chunk {
+                chunk.dequeue();
+            }
+        }
+
+        let player_location = self.last_player_position;
+        let regenerated = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|&location| Self::chunk_distance(location, player_location) > keep_radius)
+            .collect::<HashSet<_>>();
+        self.unload(&regenerated, render_ctx);
+        // The kept chunks at the border lost many generated neighbors at once, which are counted again when they are regenerated
+        Self::recount_generated_neighbors(&mut self.chunks, &regenerated);
+
+        // The regenerated chunks place the structures of the new generator instead
+        for structures in self.structure_voxels.values_mut() {
+            structures.retain(|source, _| !regenerated.contains(source));
+        }
+        self.structure_voxels
+            .retain(|_, structures| !structures.is_empty());
+
+        self.unload_queue.clear();
+        self.last_unload_check = None;
+        self.current_chunk_generate_radius = 0;
+        self.current_chunk_mesh_radius = 0;
+        self.deep_generation_radius = 0;
+    }
+
+    fn swap_active_state(&mut self, dimension: &mut Dimension) {
+        mem::swap(&mut self.chunks, &mut dimension.chunks);
+        mem::swap(&mut self.chunk_generator, &mut dimension.chunk_generator);
+        mem::swap(&mut self.storage, &mut dimension.storage);
+        mem::swap(&mut self.chunk_mesh_queue, &mut dimension.chunk_mesh_queue);
+        mem::swap(&mut self.chunk_render_manager, &mut dimension.chunk_render_manager);
+        mem::swap(&mut self.total_voxel_data_size, &mut dimension.total_voxel_data_size);
+        mem::swap(&mut self.structure_voxels, &mut dimension.structure_voxels);
+        mem::swap(&mut self.failed_chunks, &mut dimension.failed_chunks);
+        mem::swap(&mut self.edits, &mut dimension.edits);
+        // The unload queue belongs to the previously active dimension, whose chunks are kept while it is inactive
+        self.unload_queue.clear();
+        self.last_unload_check = None;
+        if let Some(ray_traced_lighting) = &mut self.ray_traced_lighting {
+            ray_traced_lighting.clear();
         }
     }
 
-    pub fn update_player_location(&mut self, player_location: Vector3<f32>) {
+    /// Moves the center of the streamed chunks to the player and predicts where the player will be after [ChunkManager::prefetch_time],
+    /// given the time since the last update
+    pub fn update_player_location(&mut self, player_location: Vector3<f32>, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        if dt > 0.0 {
+            let velocity = (player_location - self.last_player_world_position) / dt;
+            if velocity.magnitude() > MAX_PREDICTED_SPEED {
+                self.player_velocity = Vector3::zero();
+            } else {
+                self.player_velocity += (velocity - self.player_velocity) * (dt / VELOCITY_SMOOTHING).min(1.0);
+            }
+        }
+        self.last_player_world_position = player_location;
+
+        // The prediction stays well within the render distance, so that prefetched chunks are not unloaded right away
+        let max_lookahead = (self.render_distance / 2 * CHUNK_SIZE as i32) as f32;
+        let mut lookahead = self.player_velocity * self.prefetch_time;
+        if lookahead.magnitude() > max_lookahead {
+            lookahead = lookahead.normalize_to(max_lookahead);
+        }
+        self.predicted_player_position = ChunkLocation::from_world_location_f32(player_location + lookahead);
+
         let new_chunk_location = ChunkLocation::from_world_location_f32(player_location);
         if new_chunk_location != self.last_player_position {
             self.current_chunk_generate_radius = 0;
             self.current_chunk_mesh_radius = 0;
+            self.deep_generation_radius = 0;
             self.last_player_position = ChunkLocation::from_world_location_f32(player_location);
+            self.requeue_changed_mesh_kinds();
+            self.cancel_obsolete_work();
+        }
+    }
+
+    /// Cancels the data generation and meshing of chunks that are beyond the unload distance after the player moved,
+    /// instead of waiting for them to be unloaded, and lets the data generation continue with the chunks closest to the player
+    fn cancel_obsolete_work(&mut self) {
+        let unload_distance = self.render_distance + self.deep_generation_distance + UNLOAD_MARGIN;
+        let (active_dimension, player_location, predicted_player_position) =
+            (self.active_dimension, self.last_player_position, self.predicted_player_position);
+        let is_obsolete = |location| Self::chunk_distance(location, player_location) > unload_distance;
+
+        let mut canceled = Vec::new();
+        self.location_queue
+            .cancel_where(|&(dimension, location)| {
+                let obsolete = dimension == active_dimension && is_obsolete(location);
+                if obsolete {
+                    canceled.push(location);
+                }
+                obsolete
+            });
+        self.chunk_mesh_queue.retain(|&location| {
+            if is_obsolete(location) {
+                canceled.push(location);
+                return false;
+            }
+            true
+        });
+        for location in canceled {
+            if let Some(chunk) = self.chunks.get_mut(&location) {
+                chunk.dequeue();
+            }
+        }
+
+        self.location_queue
+            .reprioritize(|&(dimension, location)| {
+                if dimension == active_dimension {
+                    Self::data_gen_priority(location, predicted_player_position)
+                } else {
+                    i32::MAX
+                }
+            });
+        self.chunk_mesh_queue
+            .make_contiguous()
+            .sort_by_key(|&location| Self::chunk_distance(location, player_location));
+    }
+
+    /// Priority in the data generation queue, which takes the chunks closest to the predicted player position first
+    fn data_gen_priority(location: ChunkLocation, predicted_player_position: ChunkLocation) -> i32 {
+        let relative = location - predicted_player_position;
+        relative.x * relative.x + relative.y * relative.y + relative.z * relative.z
+    }
+
+    pub fn set_skin_ring_width(&mut self, skin_ring_width: i32) {
+        if skin_ring_width != self.skin_ring_width {
+            self.skin_ring_width = skin_ring_width;
+            self.requeue_changed_mesh_kinds();
+        }
+    }
+
+    /// Size of the mesh data of all chunks of the active dimension, including the face instances of chunks that are meshed on the GPU
+    pub fn mesh_size(&self) -> MeshSize {
+        let mesh_size = self.chunk_render_manager.mesh_size;
+        MeshSize {
+            bytes: mesh_size.bytes + self.chunk_render_manager.gpu_meshed_bytes(),
+            ..mesh_size
+        }
+    }
+
+    /// Distance between two chunks along the axis on which they are farthest apart, which matches the cube shaped generation rings
+    fn chunk_distance(a: ChunkLocation, b: ChunkLocation) -> i32 {
+        let relative = a - b;
+        relative
+            .x
+            .abs()
+            .max(relative.y.abs())
+            .max(relative.z.abs())
+    }
+
+    /// The kind of mesh that a chunk should have, depending on its horizontal distance to the player
+    fn mesh_kind_at(location: ChunkLocation, player_location: ChunkLocation, render_distance: i32, skin_ring_width: i32) -> MeshKind {
+        let relative = location - player_location;
+        let distance = relative.x.abs().max(relative.z.abs());
+
+        if skin_ring_width > 0 && distance > render_distance - skin_ring_width {
+            MeshKind::HeightmapSkin
+        } else {
+            MeshKind::Voxels
+        }
+    }
+
+    /// Queues all meshed chunks for remeshing whose mesh kind does not match their current distance to the player anymore
+    fn requeue_changed_mesh_kinds(&mut self) {
+        for (&location, chunk) in &mut self.chunks {
+            let Chunk::Meshed { mesh_kind, .. } = chunk else {
+                continue;
+            };
+
+            let wanted = Self::mesh_kind_at(location, self.last_player_position, self.render_distance, self.skin_ring_width);
+            if *mesh_kind != wanted {
+                chunk.detach_mesh().expect("chunk to be meshed");
+                self.chunk_mesh_queue.push_back(location);
+            }
         }
     }
 
@@ -266,117 +1043,223 @@ impl ChunkManager {
         timer.start("chunk_manager_generate_chunks");
         let last_player_position = self.last_player_position;
 
+        // Queues a chunk of the active dimension for the data generation threads, which load it from disk or generate it if it was never saved.
+        // When connected to a server, the chunk is requested from it instead.
+        let (active_dimension, chunk_generator, location_queue) = (
+            self.active_dimension,
+            Arc::clone(&self.chunk_generator),
+            Arc::clone(&self.location_queue),
+        );
+        let storage = self.storage.as_ref().map(WorldStorage::reader);
+        let predicted_player_position = self.predicted_player_position;
+        let (server, dimension_name) = (self.server.as_ref(), &self.dimension_names[self.active_dimension]);
+        let mut request_chunk_data = |location| {
+            // The server answers with the chunk data, which is attached once it is received
+            if let Some(server) = server {
+                server.send(&ClientMessage::RequestChunk {
+                    dimension: dimension_name.clone(),
+                    location,
+                });
+                return;
+            }
+
+            location_queue.push(
+                (active_dimension, location),
+                ChunkGenRequest {
+                    dimension: active_dimension,
+                    location,
+                    generator: Arc::clone(&chunk_generator),
+                    storage: storage.clone(),
+                },
+                Self::data_gen_priority(location, predicted_player_position),
+            );
+        };
+
+        let render_distance = self.render_distance;
+        let in_mesh_range = |location| Self::chunk_distance(location, last_player_position) <= render_distance;
+
         timer.start("chunk_manager_save");
-        self.generated_chunks_queue
+        let (results, inactive_results): (Vec<_>, Vec<_>) = self
+            .generated_chunks_queue
             .take_all()
             .into_iter()
-            .for_each(|ChunkGenResult(location, data)| {
-                match &data {
-                    ChunkData::Voxels(_) => {
-                        self.total_voxel_data_size += CHUNK_SIZE.pow(3) * mem::size_of::<VoxelData>();
-                    }
-                    ChunkData::UniformType(_) => {
-                        self.total_voxel_data_size += mem::size_of::<VoxelData>();
-                    }
-                }
+            .partition(|result| result.dimension == self.active_dimension);
+        let results = results
+            .into_iter()
+            .filter(|result| Arc::ptr_eq(&result.generator, &chunk_generator));
+
+        for result in inactive_results {
+            self.inactive_dimensions
+                .get_mut(&result.dimension)
+                .expect("chunk data to be generated for an existing dimension")
+                .pending_results
+                .push(result);
+        }
+
+        let mut generated_structures = Vec::new();
+        results.for_each(|result| {
+            let ChunkGenResult {
+                location,
+                mut data,
+                structure_voxels,
+                loaded_from_storage,
+                error,
+                ..
+            } = result;
+            // Results of chunks that were unloaded while they were generated are discarded,
+            // the chunk is requested again if it comes back into range
+            if !matches!(
+                self.chunks.get(&location),
+                Some(Chunk::None {
+                    queued_for_datagen: true,
+                    ..
+                })
+            ) {
+                return;
+            }
+
+            let is_placeholder = error.is_some();
+            if let Some(message) = error {
+                warn!("Failed to generate the chunk at {location:?}: {message}");
+                self.failed_chunks.insert(
+                    location,
+                    ChunkFailure {
+                        stage: ChunkFailureStage::Generation,
+                        message,
+                    },
+                );
+            }
 
-                // let is_regeneration = match self.chunks.get(&location) {
-                //     Some(Chunk::Generated {..}) => {
-                //         TODO if is Generated {queued: true}, then we must remove this from the mesh queue
-                // true
-                // }
-                // Some(Chunk::Meshed {..}) => {
-                //     Todo regenerate mesh
-                // true
-                // }
-                // _ => false,
-                // };
-
-                let mut chunk = self.chunks.entry(location).or_insert_with(|| Chunk::new());
-                chunk.attach_data(data).expect("chunk data to not be present already");
-                if chunk.neighbor_count() == Some(26) && chunk.enqueue_for_mesh_gen().unwrap() {
-                    self.chunk_mesh_queue.push_back(location);
+            // Structures of generated neighbors reach into this chunk. Saved chunks already contain them, together with the edits of the player.
+            // Placeholders of failed chunks are kept as they are.
+            if let Some(structures) = self
+                .structure_voxels
+                .get(&location)
+                .filter(|_| !loaded_from_storage && !is_placeholder)
+            {
+                for &(local_location, voxel_type) in structures.values().flatten() {
+                    place_structure_voxel(&mut data, local_location, voxel_type);
                 }
+                data.compress();
+            }
+            // Edits of a chunk that was unloaded before are applied on top, so that building survives moving away and back
+            if let Some(diff) = self
+                .edits
+                .diff(location)
+                .filter(|_| !loaded_from_storage && !is_placeholder)
+            {
+                data.apply(&diff);
+                data.compress();
+            }
+            generated_structures.push((location, structure_voxels));
 
+            self.total_voxel_data_size += data.size_in_bytes();
+            self.generated_chunks += 1;
 
-                // if !is_regeneration {
-                iproduct!(-1..=1, -1..=1, -1..=1).for_each(|(dx, dy, dz)| {
-                    if dx == 0 && dy == 0 && dz == 0 {
-                        return;
-                    }
-                    let loc = location + ChunkLocation::new(Vector3::new(dx, dy, dz));
+            // let is_regeneration = match self.chunks.get(&location) {
+            //     Some(Chunk::Generated {..}) => {
+            //         TODO if is Generated {queued: true}, then we must remove this from the mesh queue
+            // true
+            // }
+            // Some(Chunk::Meshed {..}) => {
+            //     Todo regenerate mesh
+            // true
+            // }
+            // _ => false,
+            // };
+
+            let chunk = Self::chunk_entry(&mut self.chunks, location);
+            let replaced_data = chunk.attach_data(data);
+            debug_assert!(replaced_data.is_none(), "only chunks without data are generated");
+            if let Some(ray_traced_lighting) = &mut self.ray_traced_lighting {
+                ray_traced_lighting.invalidate(location);
+            }
+            if chunk.neighbor_count() == Some(26) && in_mesh_range(location) && chunk.enqueue_for_mesh_gen().unwrap() {
+                self.chunk_mesh_queue.push_back(location);
+            }
 
-                    let chunk = self.chunks.entry(loc).or_insert(Chunk::new());
-                    let new_neighbor_count = chunk.inc_neighbor_count().expect("this chunk to not be meshed already, as the data for the current chunk (its neighbor chunk) has just been generated");
+            // if !is_regeneration {
+            iproduct!(-1..=1, -1..=1, -1..=1).for_each(|(dx, dy, dz)| {
+                if dx == 0 && dy == 0 && dz == 0 {
+                    return;
+                }
+                let loc = location + ChunkLocation::new(Vector3::new(dx, dy, dz));
+
+                let (chunk, new_neighbor_count) = Self::add_generated_neighbor(&mut self.chunks, loc);
 
-                    if new_neighbor_count == 26 {
-                        match chunk.enqueue_for_mesh_gen() {
-                            Ok(true) => self.chunk_mesh_queue.push_back(loc),
-                            Err(_) => if chunk.enqueue_for_data_gen().unwrap() { self.location_queue.insert(loc) },
-                            Ok(false) => {},
+                // Chunks beyond the render distance are only generated, they are meshed once they come into range
+                if new_neighbor_count == 26 && (chunk.get_data().is_none() || in_mesh_range(loc)) {
+                    match chunk.enqueue_for_mesh_gen() {
+                        Ok(true) => self.chunk_mesh_queue.push_back(loc),
+                        Err(_) => {
+                            if chunk.enqueue_for_data_gen().unwrap() {
+                                request_chunk_data(loc)
+                            }
                         }
+                        Ok(false) => {}
                     }
+                }
 
-                    // #[cfg(debug_assertions)]
-                    // let chunks_unsafe = &self.chunks as *const hashbrown::HashMap<ChunkLocation, Chunk>;
-                    // 
-                    // if let Some(s) = self.chunks.get_mut(&loc) {
-                    //     match s {
-                    //         Chunk::None { num_neighbors_generated, .. } => {
-                    //             *num_neighbors_generated += 1;
-                    // 
-                    //             // Check if the num_neighbors_generated value is really correct.
-                    //             // This is implemented as a debug assertion as it may be costly when done for a lot of chunks
-                    //             #[cfg(debug_assertions)]
-                    //             {
-                    // 
-                    //                 let mut count_generated = 0;
-                    //                 iproduct!(-1..=1, -1..=1, -1..=1).for_each(|(dx, dy, dz)| {
-                    //                     if dx == 0 && dy == 0 && dz == 0 {
-                    //                         return;
-                    //                     }
-                    // 
-                    //                     let d = loc + ChunkLocation::new(Vector3::new(dx, dy, dz));
-                    //                     // # SAFETY
-                    //                     // self.chunks is currently mutably borrowed by this function.
-                    //                     // Thus no other thread has access to it and we can safely access it to look at values without storing them
-                    //                     let chunks_unsafe2 = unsafe { &*chunks_unsafe };
-                    //                     match chunks_unsafe2.get(&d) {
-                    //                         Some(Chunk::Generated { .. }) | Some(Chunk::Meshed { .. }) => {
-                    //                             count_generated += 1;
-                    //                         }
-                    //                         _ => {}
-                    //                     }
-                    //                 });
-                    //                 assert_eq!(count_generated, *num_neighbors_generated, "Invalid state of chunks where the None chunk at {loc:?} has a num_neighbors_generated of {}, but it really is {}", *num_neighbors_generated, count_generated);
-                    //             }
-                    //             if *num_neighbors_generated >= 27 {
-                    //                 panic!("a num_neighbors_generated of 27 should be impossible here, as there cannot exist an empty chunk with all of its neighbors chunks already generated");
-                    //             }
-                    //         }
-                    //         Chunk::Generated {
-                    //             num_neighbors_generated,
-                    //             queued,
-                    //             ..
-                    //         } => {
-                    //             assert_eq!(
-                    //                 *queued, false,
-                    //                 "chunk should not be queued for meshing already because this chunk's data has just been generated"
-                    //             );
-                    // 
-                    //             *num_neighbors_generated += 1;
-                    //             if *num_neighbors_generated == 26 {
-                    //                 *queued = true;
-                    //                 self.chunk_mesh_queue.push_back(loc);
-                    //             }
-                    //         }
-                    //         Chunk::Meshed { .. } => {
-                    //             panic!("chunk should not be meshed already because this neighbor chunk's data has just been generated")
-                    //         }
-                    //     }
-                })
-                // }
-            });
+                // #[cfg(debug_assertions)]
+                // let chunks_unsafe = &self.chunks as *const hashbrown::HashMap<ChunkLocation, Chunk>;
+                //
+                // if let Some(s) = self.chunks.get_mut(&loc) {
+                //     match s {
+                //         Chunk::None { num_neighbors_generated, .. } => {
+                //             *num_neighbors_generated += 1;
+                //
+                //             // Check if the num_neighbors_generated value is really correct.
+                //             // This is implemented as a debug assertion as it may be costly when done for a lot of chunks
+                //             #[cfg(debug_assertions)]
+                //             {
+                //
+                //                 let mut count_generated = 0;
+                //                 iproduct!(-1..=1, -1..=1, -1..=1).for_each(|(dx, dy, dz)| {
+                //                     if dx == 0 && dy == 0 && dz == 0 {
+                //                         return;
+                //                     }
+                //
+                //                     let d = loc + ChunkLocation::new(Vector3::new(dx, dy, dz));
+                //                     // # SAFETY
+                //                     // self.chunks is currently mutably borrowed by this function.
+                //                     // Thus no other thread has access to it and we can safely access it to look at values without storing them
+                //                     let chunks_unsafe2 = unsafe { &*chunks_unsafe };
+                //                     match chunks_unsafe2.get(&d) {
+                //                         Some(Chunk::Generated { .. }) | Some(Chunk::Meshed { .. }) => {
+                //                             count_generated += 1;
+                //                         }
+                //                         _ => {}
+                //                     }
+                //                 });
+                //                 assert_eq!(count_generated, *num_neighbors_generated, "Invalid state of chunks where the None chunk at {loc:?} has a num_neighbors_generated of {}, but it really is {}", *num_neighbors_generated, count_generated);
+                //             }
+                //             if *num_neighbors_generated >= 27 {
+                //                 panic!("a num_neighbors_generated of 27 should be impossible here, as there cannot exist an empty chunk with all of its neighbors chunks already generated");
+                //             }
+                //         }
+                //         Chunk::Generated {
+                //             num_neighbors_generated,
+                //             queued,
+                //             ..
+                //         } => {
+                //             assert_eq!(
+                //                 *queued, false,
+                //                 "chunk should not be queued for meshing already because this chunk's data has just been generated"
+                //             );
+                //
+                //             *num_neighbors_generated += 1;
+                //             if *num_neighbors_generated == 26 {
+                //                 *queued = true;
+                //                 self.chunk_mesh_queue.push_back(loc);
+                //             }
+                //         }
+                //         Chunk::Meshed { .. } => {
+                //             panic!("chunk should not be meshed already because this neighbor chunk's data has just been generated")
+                //         }
+                //     }
+            })
+            // }
+        });
         timer.end("chunk_manager_save");
 
         timer.start("chunk_manager_request_chunks");
@@ -386,135 +1269,1195 @@ impl ChunkManager {
 
             let radius = self.current_chunk_generate_radius;
 
+            // The same ring is also queued around the predicted player position, so that chunks ahead of the movement are
+            // generated before the rings around the player reach them
+            let ring = |center: ChunkLocation| {
+                iproduct!(-radius..=radius, -radius..=radius, -radius..=radius)
+                    .map(move |(x, y, z)| center + ChunkLocation::new(Vector3::new(x, y, z)))
+            };
+            ring(last_player_position)
+                .chain(ring(predicted_player_position).filter(|&location| in_mesh_range(location)))
+                .filter(|&location| {
+                    let c = Self::chunk_entry(&mut self.chunks, location);
+
+                    // Chunks that were generated beyond the render distance before are meshed now that they are in range
+                    if c.get_data().is_some() && c.neighbor_count() == Some(26) && in_mesh_range(location) {
+                        if let Ok(true) = c.enqueue_for_mesh_gen() {
+                            self.chunk_mesh_queue.push_back(location);
+                        }
+                    }
+
+                    matches!(c.enqueue_for_data_gen(), Ok(true))
+                })
+                .for_each(&mut request_chunk_data);
+        }
+
+        // Once everything within the render distance is streamed, chunks beyond it are generated ring by ring,
+        // so that increasing the render distance later only has to mesh them
+        let streaming_idle = self.location_queue.len() == 0
+            && self.generated_chunks_queue.len() == 0
+            && self.chunk_mesh_queue.is_empty()
+            && self.current_chunk_generate_radius >= self.render_distance;
+        if self.idle_generation
+            && !self.power_saving
+            && streaming_idle
+            && self.deep_generation_radius < self.render_distance + self.deep_generation_distance
+            && self.total_voxel_data_size < self.voxel_memory_budget
+        {
+            self.deep_generation_radius = self
+                .deep_generation_radius
+                .max(self.render_distance)
+                + 1;
+
+            let radius = self.deep_generation_radius;
             iproduct!(-radius..=radius, -radius..=radius, -radius..=radius)
                 .map(|(x, y, z)| last_player_position + ChunkLocation::new(Vector3::new(x, y, z)))
+                .filter(|&location| Self::chunk_distance(location, last_player_position) == radius)
                 .for_each(|location| {
-                    let c = self
-                        .chunks
-                        .entry(location)
-                        .or_insert(Chunk::new());
+                    let c = Self::chunk_entry(&mut self.chunks, location);
 
                     if let Ok(true) = c.enqueue_for_data_gen() {
-                        self.location_queue.insert(location);
+                        request_chunk_data(location);
                     }
                 });
         }
         timer.end("chunk_manager_request_chunks");
 
+        for (location, outside_voxels) in generated_structures {
+            self.place_outside_structure_voxels(location, outside_voxels);
+        }
+
         timer.end("chunk_manager_generate_chunks");
     }
 
-    pub fn generate_chunk_meshes(
-        &mut self,
-        render_ctx: &RenderCtx,
-        camera_bind_group_layout: &wgpu::BindGroupLayout,
-        timer: &mut TimerManager,
-    ) {
-        const MAX_TIME: Duration = Duration::from_millis(2);
+    /// Meshes and uploads chunks from the mesh queue until the [ChunkManager::mesh_budget] of this frame is used up
+    pub fn generate_chunk_meshes(&mut self, render_ctx: &RenderCtx, timer: &mut TimerManager) {
+        const MAX_BATCH_SIZE: usize = 8;
 
         let start = Instant::now();
+        let budget = if self.power_saving {
+            self.mesh_budget / 2
+        } else {
+            self.mesh_budget
+        };
 
         timer.start("chunk_manager_meshing");
 
-        while start.elapsed() < MAX_TIME && self.chunk_mesh_queue.len() > 0 {
-            let locs_to_be_meshed = self
-                .chunk_mesh_queue
-                .drain(0..(8.min(self.chunk_mesh_queue.len())))
-                .collect_vec();
+        let (player_location, render_distance, skin_ring_width) = (self.last_player_position, self.render_distance, self.skin_ring_width);
 
-            locs_to_be_meshed
-                .into_iter()
-                // .filter(|location| {
-                //     let stored_chunk = self.chunks.get(location);
-                //     // TODO fix this check. currently an empty chunk will be 'queued' forever, even though it is not in the queue anymore
-                //     !matches!(
-                //         stored_chunk,
-                //         Some(Chunk::Generated {
-                //             data: ChunkData::UniformType(VoxelData { ty: VoxelType::Air }),
-                //             ..
-                //         })
-                //     )
-                // })
-                .for_each(|location| {
-                    let data = {
-                        let stored_chunk = self
-                            .chunks
-                            .get_mut(&location)
-                            .expect("Tried to generate mesh for chunk without data");
-
-                        let Chunk::Generated {
-                            data,
-                            num_neighbors_generated: 26,
-                            queued_for_meshing: true,
-                        } = stored_chunk
-                        else {
-                            panic!("Found invalid chunk while trying to generate mesh");
-                        };
-
-                        data.clone()
+        // Measured on the previous batch, so that the last batch of a frame is made small enough to fit into the rest of the budget
+        let mut time_per_chunk: Option<Duration> = None;
+        loop {
+            let remaining = budget.saturating_sub(start.elapsed());
+            if remaining.is_zero() || self.chunk_mesh_queue.is_empty() {
+                break;
+            }
+
+            let batch_size = time_per_chunk
+                .map_or(MAX_BATCH_SIZE, |time| {
+                    (remaining.as_secs_f32() / time.as_secs_f32()).ceil() as usize
+                })
+                .clamp(1, MAX_BATCH_SIZE.min(self.chunk_mesh_queue.len()));
+            let batch_start = Instant::now();
+
+            let locs_to_be_meshed = self
+                .chunk_mesh_queue
+                .drain(0..batch_size)
+                .map(|location| {
+                    let mesh_kind = Self::mesh_kind_at(location, player_location, render_distance, skin_ring_width);
+                    (location, mesh_kind, self.chunk_render_manager.meshes_on_cpu(mesh_kind))
+                })
+                .collect_vec();
+
+            // The CPU meshes of the batch are generated on all cores, e.g. all chunks that are touched by an edit at once.
+            // Only the GPU meshing and the uploads happen on this thread.
+            let chunks = &self.chunks;
+            let meshing_mode = self.chunk_render_manager.meshing_mode;
+            let ambient_occlusion = self.chunk_render_manager.ambient_occlusion;
+            let smooth_normals = self.chunk_render_manager.smooth_normals;
+            let failures = Mutex::new(Vec::new());
+            let mut cpu_meshes: HashMap<ChunkLocation, ChunkMeshData> = locs_to_be_meshed
+                .par_iter()
+                .filter(|(_, _, meshes_on_cpu)| *meshes_on_cpu)
+                .map(|&(location, mesh_kind, _)| {
+                    let data = Self::data_to_mesh(chunks, location);
+                    let generate = || {
+                        let neighbor_chunks = NeighborChunks::new(&location, |loc| chunks.get(loc).and_then(Chunk::get_data)).unwrap();
+                        ChunkMeshData::generate(data, neighbor_chunks, mesh_kind, meshing_mode, ambient_occlusion, smooth_normals)
                     };
 
-                    let neighbor_chunks = NeighborChunks::new(&location, |loc| {
-                        self.chunks
-                            .get(loc)
-                            .map(Chunk::get_data)
-                            .flatten()
-                    })
-                    .unwrap();
-
-                    self.chunk_render_manager
-                        .generate_chunk_renderer(&data, neighbor_chunks, render_ctx, location);
-
-                    self.chunks
-                        .get_mut(&location)
-                        .expect("Tried to generate mesh for chunk without data")
-                        .attach_mesh()
-                        .expect("this to not already have a mesh");
-                });
+                    // Chunks whose meshing panics are shown as a placeholder box that does not depend on their data or neighbors
+                    let mesh = panic::catch_unwind(AssertUnwindSafe(generate)).unwrap_or_else(|payload| {
+                        let message = panic_message(payload.as_ref());
+                        warn!("Failed to mesh the chunk at {location:?}: {message}");
+                        failures.lock().unwrap().push((location, message));
+
+                        let placeholder = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Error));
+                        let air = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+                        let neighbor_chunks = NeighborChunks::new(&location, |_| Some(&air)).expect("all neighbors to be air");
+                        ChunkMeshData::generate(
+                            &placeholder,
+                            neighbor_chunks,
+                            mesh_kind,
+                            meshing_mode,
+                            ambient_occlusion,
+                            smooth_normals,
+                        )
+                    });
+
+                    (location, mesh)
+                })
+                .collect();
+
+            // Chunks that failed to mesh before may succeed now, e.g. after they were edited
+            self.failed_chunks.retain(|location, failure| {
+                failure.stage == ChunkFailureStage::Generation
+                    || !locs_to_be_meshed
+                        .iter()
+                        .any(|(other, _, _)| other == location)
+            });
+            for (location, message) in failures.into_inner().unwrap() {
+                self.failed_chunks.insert(
+                    location,
+                    ChunkFailure {
+                        stage: ChunkFailureStage::Meshing,
+                        message,
+                    },
+                );
+            }
+
+            timer.start("chunk_manager_mesh_uploads");
+            for (location, mesh_kind, _) in locs_to_be_meshed {
+                match cpu_meshes.remove(&location) {
+                    Some(mesh) => self
+                        .chunk_render_manager
+                        .insert_mesh(render_ctx, location, mesh),
+                    None => {
+                        let chunks = &self.chunks;
+                        let neighbor_chunks = NeighborChunks::new(&location, |loc| chunks.get(loc).and_then(Chunk::get_data)).unwrap();
+                        self.chunk_render_manager.generate_chunk_renderer(
+                            Self::data_to_mesh(chunks, location),
+                            neighbor_chunks,
+                            render_ctx,
+                            location,
+                            mesh_kind,
+                        );
+                    }
+                }
+
+                self.chunks
+                    .get_mut(&location)
+                    .expect("Tried to generate mesh for chunk without data")
+                    .attach_mesh(mesh_kind)
+                    .expect("this to not already have a mesh");
+                self.meshed_chunks += 1;
+            }
+            timer.end("chunk_manager_mesh_uploads");
+
+            time_per_chunk = Some(batch_start.elapsed() / batch_size as u32);
         }
 
         timer.end("chunk_manager_meshing");
     }
 
-    pub fn unload_chunks(&mut self) {
-        return;
-        //     let unload_distance = self.render_distance;
-        //
-        //     let a: Vec<ChunkLocation> = self.chunks.keys().copied().collect_vec();
-        //     for loc in a {
-        //         let location_relative_to_player = self.last_player_position - loc;
-        //
-        //         if !((-unload_distance..=unload_distance).contains(&location_relative_to_player.x)
-        //             && (-unload_distance..=unload_distance).contains(&location_relative_to_player.y)
-        //             && (-unload_distance..=unload_distance).contains(&location_relative_to_player.z))
-        //         {
-        //             let chunk_data = self.chunks.remove(&loc).expect("wtf");
-        //             self.chunk_mesh_queue.clear();
-        //             //self.chunk_generate_queue.retain(|l| l != &loc);
-        //
-        //             if let Some(ChunkMesh::Generated(mesh)) = self.chunk_meshes.remove(&loc) {
-        //                 self.total_vertices -= mesh.vertices.len();
-        //                 self.total_triangles -= mesh.indices.len() / 3;
-        //                 self.total_mesh_data_size -= mem::size_of_val(mesh.indices.as_slice()) + mem::size_of_val(mesh.vertices.as_slice());
-        //             }
-        //
-        //             match &chunk_data {
-        //                 ChunkData::Voxels(_) => {
-        //                     self.total_voxel_data_size -= CHUNK_SIZE.pow(3) * mem::size_of::<VoxelData>();
-        //                 }
-        //                 ChunkData::UniformType(_) => {
-        //                     self.total_voxel_data_size -= mem::size_of::<VoxelData>();
-        //                 }
-        //             }
-        //         }
-        //     }
-        // }
+    /// Data of a chunk that was taken from the mesh queue, which must be generated and have all of its neighbors generated
+    fn data_to_mesh(chunks: &hashbrown::HashMap<ChunkLocation, Chunk>, location: ChunkLocation) -> &ChunkData {
+        let stored_chunk = chunks
+            .get(&location)
+            .expect("Tried to generate mesh for chunk without data");
+
+        let Chunk::Generated {
+            data,
+            num_neighbors_generated: 26,
+            queued_for_meshing: true,
+            ..
+        } = stored_chunk
+        else {
+            panic!("Found invalid chunk while trying to generate mesh");
+        };
+
+        data
+    }
+
+    /// Unloads chunks that are beyond the render distance and the deep generation rings.
+    ///
+    /// Chunks to unload are searched whenever the player enters another chunk, and are then unloaded a few per call,
+    /// so that leaving a large area does not stall a single tick. Chunks that came back into range in the meantime are kept.
+    pub fn unload_chunks(&mut self, render_ctx: &RenderCtx) {
+        let unload_distance = self.render_distance + self.deep_generation_distance + UNLOAD_MARGIN;
+        let player_location = self.last_player_position;
+
+        if self.last_unload_check != Some((player_location, unload_distance)) {
+            self.last_unload_check = Some((player_location, unload_distance));
+            self.unload_queue = self
+                .chunks
+                .keys()
+                .copied()
+                .filter(|&location| Self::chunk_distance(location, player_location) > unload_distance)
+                .collect();
+        }
+
+        let batch = self
+            .unload_queue
+            .drain(..(MAX_UNLOADS_PER_TICK.get() as usize).min(self.unload_queue.len()))
+            .filter(|&location| Self::chunk_distance(location, player_location) > unload_distance)
+            .collect::<HashSet<_>>();
+        self.unload(&batch, render_ctx);
+    }
+
+    /// Removes the given chunks together with their meshes and queued work, and unmeshes their neighbors
+    fn unload(&mut self, batch: &HashSet<ChunkLocation>, render_ctx: &RenderCtx) {
+        if batch.is_empty() {
+            return;
+        }
+
+        // Edits must not be lost when the edited chunk is generated again later
+        let has_edits = self.storage.as_ref().is_some_and(|storage| {
+            batch
+                .iter()
+                .any(|&location| storage.is_modified(location))
+        });
+        if has_edits {
+            if let Err(err) = Self::save_dimension(&mut self.storage, &self.chunks) {
+                warn!("Could not save chunks before unloading them: {err:#}");
+            }
+        }
+
+        // Neighbors that lost a generated neighbor cannot be meshed until it is generated again
+        let mut unmeshed_neighbors = HashSet::new();
+        for &location in batch {
+            let Some(chunk) = Self::remove_chunk(&mut self.chunks, location, &mut unmeshed_neighbors) else {
+                continue;
+            };
+            self.chunk_render_manager
+                .remove(render_ctx, location);
+            self.failed_chunks.remove(&location);
+
+            if let Some(data) = chunk.get_data() {
+                self.total_voxel_data_size -= data.size_in_bytes();
+            }
+        }
+
+        for &location in &unmeshed_neighbors {
+            self.chunk_render_manager
+                .remove(render_ctx, location);
+        }
+
+        // Structure voxels are only needed while the chunk that they are placed into can be generated again next to a loaded chunk
+        let chunks = &self.chunks;
+        let is_near_loaded_chunk = |location: ChunkLocation| {
+            iproduct!(-1..=1, -1..=1, -1..=1).any(|(dx, dy, dz)| {
+                chunks
+                    .get(&(location + ChunkLocation::new(Vector3::new(dx, dy, dz))))
+                    .is_some_and(|chunk| chunk.get_data().is_some())
+            })
+        };
+        for &location in batch {
+            for (dx, dy, dz) in iproduct!(-1..=1, -1..=1, -1..=1) {
+                let target = location + ChunkLocation::new(Vector3::new(dx, dy, dz));
+                if self.structure_voxels.contains_key(&target) && !is_near_loaded_chunk(target) {
+                    self.structure_voxels.remove(&target);
+                }
+            }
+        }
+
+        // Cancel the queued work of the unloaded chunks, and the meshing of chunks that lost a neighbor
+        let active_dimension = self.active_dimension;
+        self.location_queue
+            .cancel_where(|&(dimension, location)| dimension == active_dimension && batch.contains(&location));
+        self.chunk_mesh_queue
+            .retain(|location| !batch.contains(location) && !unmeshed_neighbors.contains(location));
+    }
+
+    /// Type of the voxel at the given location, if its chunk is generated
+    pub fn voxel_at(&self, location: WorldLocation) -> Option<VoxelType> {
+        let (chunk_location, local_location) = location.separate();
+
+        let data = self.chunks.get(&chunk_location)?.get_data()?;
+        Some(data.get_voxel(local_location).ty)
+    }
+
+    /// Whether the voxel at the given location is solid, if its chunk is generated. Only the occupancy of the chunk is read.
+    pub fn is_solid_at(&self, location: WorldLocation) -> Option<bool> {
+        let (chunk_location, local_location) = location.separate();
+
+        let occupancy = self
+            .chunks
+            .get(&chunk_location)?
+            .get_occupancy()?;
+        Some(occupancy.solid.get(local_location))
+    }
+
+    /// Finds the first generated solid voxel along a ray, see [raycast]
+    pub fn raycast(&self, origin: Vector3<f32>, direction: Vector3<f32>, max_distance: f32) -> Option<RaycastHit> {
+        raycast(origin, direction, max_distance, |location| self.is_solid_at(location) == Some(true))
+    }
+
+    /// Finds a walkable path between two voxels, see [find_path]. Voxels of chunks that are not generated yet are treated as solid.
+    pub fn find_path(&self, start: WorldLocation, goal: WorldLocation, costs: &PathCosts) -> Option<Vec<WorldLocation>> {
+        find_path(start, goal, costs, |location| self.is_solid_or_unknown(location))
+    }
+
+    /// Whether the feet of a walking body can be at the given voxel, see [is_walkable]
+    pub fn is_walkable(&self, location: WorldLocation) -> bool {
+        is_walkable(location, |location| self.is_solid_or_unknown(location))
+    }
+
+    /// Merged boxes of all generated solid voxels in a region, see [collision_boxes]
+    pub fn collision_boxes(&self, min: WorldLocation, max: WorldLocation) -> Vec<CollisionBox> {
+        collision_boxes(min, max, |location| self.is_solid_at(location) == Some(true))
+    }
+
+    fn is_solid_or_unknown(&self, location: WorldLocation) -> bool {
+        self.is_solid_at(location).unwrap_or(true)
+    }
+
+    /// Replaces a single voxel and queues its chunk for remeshing, together with all meshed neighbor chunks that touch the voxel.
+    ///
+    /// The affected chunks are queued at the front, so that the edit becomes visible in the next tick.
+    /// Use [ChunkManager::edit] to replace many voxels at once.
+    pub fn set_voxel(&mut self, location: WorldLocation, voxel_data: VoxelData) -> Result<()> {
+        self.edit(|transaction| transaction.set_voxel(location, voxel_data))
+    }
+
+    /// Applies many voxel edits, e.g. of a brush stroke or a prefab, and remeshes every affected chunk only once afterwards.
+    ///
+    /// The edits are visible to [EditTransaction::voxel_at] right away. The affected chunks are queued for remeshing at the front
+    /// when `edit` returns, also if some of the edits failed.
+    pub fn edit<T>(&mut self, edit: impl FnOnce(&mut EditTransaction) -> T) -> T {
+        self.edit_with(true, edit)
+    }
+
+    /// Like [ChunkManager::edit], but the edits of other players that were received from the server are not sent back to it
+    fn edit_with<T>(&mut self, send_to_server: bool, edit: impl FnOnce(&mut EditTransaction) -> T) -> T {
+        let mut transaction = EditTransaction {
+            chunk_manager: self,
+            affected_chunks: HashSet::new(),
+            send_to_server,
+        };
+        let result = edit(&mut transaction);
+        let affected_chunks = transaction.affected_chunks;
+        self.remesh_edited_chunks(affected_chunks);
+
+        result
+    }
+
+    /// Replaces all voxels of a generated chunk, e.g. with a chunk that was generated again or received from elsewhere.
+    /// The chunk is remeshed together with all of its meshed neighbors, whose meshes contain the faces on its border.
+    pub fn set_chunk_data(&mut self, location: ChunkLocation, data: ChunkData) -> Result<()> {
+        let Some(chunk) = self
+            .chunks
+            .get_mut(&location)
+            .filter(|chunk| chunk.get_data().is_some())
+        else {
+            bail!("Cannot replace the data of the chunk at {location:?}, it is not generated");
+        };
+        let size = data.size_in_bytes();
+        let previous_data = chunk
+            .attach_data(data)
+            .expect("a generated chunk to have data");
+        self.total_voxel_data_size = self.total_voxel_data_size + size - previous_data.size_in_bytes();
+        if let Some(storage) = &mut self.storage {
+            storage.mark_modified(location);
+        }
+        if let Some(ray_traced_lighting) = &mut self.ray_traced_lighting {
+            ray_traced_lighting.invalidate(location);
+        }
+
+        self.remesh_edited_chunks(
+            iproduct!(-1..=1, -1..=1, -1..=1).map(|(dx, dy, dz)| location + ChunkLocation::new(Vector3::new(dx, dy, dz))),
+        );
+
+        Ok(())
+    }
+
+    /// Queues the meshed chunks among the given chunks for remeshing at the front, so that their edits become visible in the next tick
+    fn remesh_edited_chunks(&mut self, locations: impl IntoIterator<Item = ChunkLocation>) {
+        for location in locations {
+            if let Some(chunk @ Chunk::Meshed { .. }) = self.chunks.get_mut(&location) {
+                chunk.detach_mesh().expect("chunk to be meshed");
+                self.chunk_mesh_queue.push_front(location);
+            }
+        }
+    }
+
+    /// Remembers the structure voxels that a generated chunk places into other chunks, and places them into the chunks that are
+    /// generated already. Chunks that got the voxels of this chunk before it was unloaded are not changed again, which keeps edits.
+    fn place_outside_structure_voxels(&mut self, location: ChunkLocation, outside_voxels: Vec<(WorldLocation, VoxelType)>) {
+        let mut targets: HashMap<ChunkLocation, Vec<_>> = HashMap::new();
+        for (voxel_location, voxel_type) in outside_voxels {
+            let (target, local_location) = voxel_location.separate();
+            targets
+                .entry(target)
+                .or_default()
+                .push((local_location, voxel_type));
+        }
+
+        let mut affected_chunks = HashSet::new();
+        for (target, voxels) in targets {
+            let structures = self.structure_voxels.entry(target).or_default();
+            if structures.contains_key(&location) {
+                continue;
+            }
+            let voxels = structures.entry(location).or_insert(voxels);
+
+            let Some((data, occupancy)) = self
+                .chunks
+                .get_mut(&target)
+                .and_then(Chunk::get_data_and_occupancy_mut)
+            else {
+                continue;
+            };
+            let previous_size = data.size_in_bytes();
+            let mut changed = false;
+            for &(local_location, voxel_type) in voxels.iter() {
+                if place_structure_voxel(data, local_location, voxel_type) {
+                    occupancy.update(local_location, voxel_type);
+                    changed = true;
+                    affected_chunks.extend(chunks_touching_voxel(target, local_location));
+                }
+            }
+
+            if changed {
+                data.compress();
+                self.total_voxel_data_size = self.total_voxel_data_size + data.size_in_bytes() - previous_size;
+                if let Some(ray_traced_lighting) = &mut self.ray_traced_lighting {
+                    ray_traced_lighting.invalidate(target);
+                }
+            }
+        }
+
+        self.remesh_edited_chunks(affected_chunks);
+    }
+
+    /// Lights the chunks of the active dimension, must be called before rendering them
+    pub fn update_lighting(&mut self, handle: &mut RenderHandle, lighting: &RawLighting) {
+        self.chunk_render_manager
+            .update_lighting(handle, lighting);
+        self.to_sun = Vector3::new(lighting.to_sun[0], lighting.to_sun[1], lighting.to_sun[2]);
+    }
+
+    /// Must be called before rendering the chunks
+    pub fn prepare_render(&mut self, handle: &mut RenderHandle, camera: &Camera) {
+        self.chunk_render_manager.prepare_render(
+            handle,
+            &camera.frustum(),
+            ChunkLocation::from_world_location_f32(camera.position.to_vec()),
+        );
+        if let Some(ray_traced_lighting) = &mut self.ray_traced_lighting {
+            let chunks = &self.chunks;
+            ray_traced_lighting.update(handle, camera, self.to_sun, |location| {
+                chunks
+                    .get(&location)
+                    .and_then(Chunk::get_occupancy)
+            });
+        }
+
+        let chunk_render_manager = &self.chunk_render_manager;
+        self.shadow_casters = camera.shadow_cascades().map(|cascade| {
+            chunk_render_manager
+                .chunk_draws()
+                .filter(|chunk| cascade.is_caster(&chunk.aabb))
+                .map(|chunk| chunk.location)
+                .collect()
+        });
+    }
+
+    pub fn uses_gpu_culling(&self) -> bool {
+        self.chunk_render_manager.uses_gpu_culling()
+    }
+
+    pub fn visible_chunks(&self) -> u32 {
+        self.chunk_render_manager.visible_chunks()
+    }
+
+    pub fn gpu_shared_chunks(&self) -> usize {
+        self.chunk_render_manager.gpu_shared_chunks()
+    }
+
+    /// All chunks that are currently drawn, see [ChunkRenderManager::chunk_draws]
+    pub fn chunk_draws(&self) -> impl Iterator<Item = ChunkDraw> + '_ {
+        self.chunk_render_manager.chunk_draws()
+    }
+
+    /// Recompiles the chunk render pipelines of all dimensions from a modified chunk shader, see [ChunkRenderManager::reload_shader].
+    /// Render managers that are created afterwards, e.g. when the worldgen preset changes, use the shader in the binary again.
+    pub fn reload_chunk_shader(&mut self, render_ctx: &RenderCtx, source: &str) -> Result<()> {
+        self.chunk_render_manager
+            .reload_shader(render_ctx, source)?;
+        for dimension in self.inactive_dimensions.values_mut() {
+            dimension
+                .chunk_render_manager
+                .reload_shader(render_ctx, source)?;
+        }
+
+        Ok(())
+    }
+
+    /// Uploads all chunk meshes again to the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, render_ctx: &RenderCtx) {
+        self.texture_atlas
+            .rebuild_gpu_resources(render_ctx);
+
+        let texture_atlas = &self.texture_atlas.bind_group;
+        let lost = self
+            .chunk_render_manager
+            .rebuild_gpu_resources(render_ctx, Arc::clone(texture_atlas));
+        Self::requeue_for_meshing(&mut self.chunks, &mut self.chunk_mesh_queue, lost);
+
+        for dimension in self.inactive_dimensions.values_mut() {
+            let lost = dimension
+                .chunk_render_manager
+                .rebuild_gpu_resources(render_ctx, Arc::clone(texture_atlas));
+            Self::requeue_for_meshing(&mut dimension.chunks, &mut dimension.chunk_mesh_queue, lost);
+        }
+
+        if let Some(ray_traced_lighting) = &mut self.ray_traced_lighting {
+            ray_traced_lighting.rebuild_gpu_resources(render_ctx);
+        }
+    }
+
+    /// The chunk at the location, which is created without data if it is not loaded.
+    /// A created chunk counts the generated chunks around it, as a chunk that is loaded again after it was unloaded can have
+    /// generated neighbors that stayed loaded and are never counted again.
+    fn chunk_entry(chunks: &mut hashbrown::HashMap<ChunkLocation, Chunk>, location: ChunkLocation) -> &mut Chunk {
+        if !chunks.contains_key(&location) {
+            let num_neighbors_generated = Self::generated_neighbors(chunks, location);
+            chunks.insert(
+                location,
+                Chunk::None {
+                    num_neighbors_generated,
+                    queued_for_datagen: false,
+                },
+            );
+        }
+
+        chunks
+            .get_mut(&location)
+            .expect("chunk to be inserted")
+    }
+
+    /// Number of the neighbors of the chunk at the location that have data
+    fn generated_neighbors(chunks: &hashbrown::HashMap<ChunkLocation, Chunk>, location: ChunkLocation) -> u8 {
+        iproduct!(-1..=1, -1..=1, -1..=1)
+            .filter(|&offset| offset != (0, 0, 0))
+            .filter(|&(dx, dy, dz)| {
+                chunks
+                    .get(&(location + ChunkLocation::new(Vector3::new(dx, dy, dz))))
+                    .is_some_and(|chunk| chunk.get_data().is_some())
+            })
+            .count() as u8
+    }
+
+    /// Sets the neighbor counts of the loaded chunks next to the removed chunks to their generated neighbors that are still loaded
+    fn recount_generated_neighbors(chunks: &mut hashbrown::HashMap<ChunkLocation, Chunk>, removed: &HashSet<ChunkLocation>) {
+        let kept_neighbors = removed
+            .iter()
+            .flat_map(|&location| {
+                iproduct!(-1..=1, -1..=1, -1..=1).map(move |(dx, dy, dz)| location + ChunkLocation::new(Vector3::new(dx, dy, dz)))
+            })
+            .filter(|location| !removed.contains(location))
+            .collect::<HashSet<_>>();
+
+        for location in kept_neighbors {
+            let count = Self::generated_neighbors(chunks, location);
+            if let Some(
+                Chunk::None {
+                    num_neighbors_generated, ..
+                }
+                | Chunk::Generated {
+                    num_neighbors_generated, ..
+                },
+            ) = chunks.get_mut(&location)
+            {
+                *num_neighbors_generated = count;
+            }
+        }
+    }
+
+    /// Counts a chunk whose data was just generated in the neighbor chunk at the location.
+    /// Returns the neighbor together with its new count of generated neighbors.
+    fn add_generated_neighbor(chunks: &mut hashbrown::HashMap<ChunkLocation, Chunk>, location: ChunkLocation) -> (&mut Chunk, u8) {
+        if !chunks.contains_key(&location) {
+            // A created chunk has counted the generated chunk already
+            let chunk = Self::chunk_entry(chunks, location);
+            let count = chunk
+                .neighbor_count()
+                .expect("a created chunk to not be meshed");
+            return (chunk, count);
+        }
+
+        let chunk = chunks
+            .get_mut(&location)
+            .expect("chunk to be loaded");
+        let count = chunk
+            .inc_neighbor_count()
+            .expect("this chunk to not be meshed already, as the data for the current chunk (its neighbor chunk) has just been generated");
+        (chunk, count)
+    }
+
+    /// Removes the chunk and uncounts it in its neighbors, if it was generated.
+    /// The neighbors that lost their mesh or their place in the mesh queue are added to `unmeshed_neighbors`.
+    fn remove_chunk(
+        chunks: &mut hashbrown::HashMap<ChunkLocation, Chunk>,
+        location: ChunkLocation,
+        unmeshed_neighbors: &mut HashSet<ChunkLocation>,
+    ) -> Option<Chunk> {
+        let chunk = chunks.remove(&location)?;
+        if chunk.get_data().is_some() {
+            // The removed chunk itself is not found anymore
+            for (dx, dy, dz) in iproduct!(-1..=1, -1..=1, -1..=1) {
+                let neighbor_location = location + ChunkLocation::new(Vector3::new(dx, dy, dz));
+                if let Some(neighbor) = chunks.get_mut(&neighbor_location) {
+                    if neighbor.remove_neighbor() {
+                        unmeshed_neighbors.insert(neighbor_location);
+                    }
+                }
+            }
+        }
+
+        Some(chunk)
+    }
+
+    /// Queues meshed chunks for meshing again, e.g. because their meshes were lost together with the GPU device
+    fn requeue_for_meshing(
+        chunks: &mut hashbrown::HashMap<ChunkLocation, Chunk>,
+        chunk_mesh_queue: &mut VecDeque<ChunkLocation>,
+        locations: Vec<ChunkLocation>,
+    ) {
+        for location in locations {
+            if let Some(chunk @ Chunk::Meshed { .. }) = chunks.get_mut(&location) {
+                chunk.detach_mesh().expect("chunk to be meshed");
+                chunk_mesh_queue.push_back(location);
+            }
+        }
+    }
+
+    /// Textures the voxel faces of all dimensions with the given texture atlas, see [crate::world::voxel_data::VoxelType::face_tiles]
+    pub fn load_texture_atlas(&mut self, path: &Path, render_ctx: &RenderCtx) -> Result<()> {
+        self.texture_atlas = VoxelTextureAtlas::load(render_ctx, path)?;
+
+        let texture_atlas = &self.texture_atlas.bind_group;
+        self.chunk_render_manager
+            .set_texture_atlas(Arc::clone(texture_atlas));
+        for dimension in self.inactive_dimensions.values_mut() {
+            dimension
+                .chunk_render_manager
+                .set_texture_atlas(Arc::clone(texture_atlas));
+        }
+
+        Ok(())
+    }
+
+    /// Transparent pass of the water surfaces and translucent voxels of the active dimension, drawn after all opaque geometry
+    pub fn water_renderer(&self) -> &WaterRenderer {
+        &self.chunk_render_manager.water
+    }
+
+    pub fn animate_water(&mut self, dt: Duration) {
+        self.chunk_render_manager.water.animate(dt);
+    }
+
+    /// Ray traced ambient occlusion and shadows, which are applied after the chunks are drawn. None if compute shaders are not supported.
+    pub fn ray_traced_lighting(&self) -> Option<&RayTracedLighting> {
+        self.ray_traced_lighting.as_ref()
+    }
+
+    /// Enables the ray traced lighting if it is selected and supported
+    pub fn set_lighting_mode(&mut self, lighting_mode: LightingMode) {
+        if let Some(ray_traced_lighting) = &mut self.ray_traced_lighting {
+            ray_traced_lighting.enabled = lighting_mode == LightingMode::RayTraced;
+        }
+    }
+
+    pub fn set_depth_prepass(&mut self, enabled: bool) {
+        self.chunk_render_manager.depth_prepass = enabled;
+    }
+
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.chunk_render_manager.render_mode = render_mode;
+    }
+
+    /// Adds the outlines of the loaded chunks around the given chunk, colored by how far they got through generation and meshing:
+    /// red chunks wait for their data, yellow chunks wait for the data of their neighbors, green chunks are meshed with voxels
+    /// and blue chunks with a heightmap skin
+    pub fn add_chunk_outlines(&self, lines: &mut DebugLines, center: ChunkLocation, distance: i32) {
+        // Inset, so that the outlines of neighboring chunks do not overlap
+        const INSET: f32 = 0.05;
+
+        for (x, y, z) in iproduct!(-distance..=distance, -distance..=distance, -distance..=distance) {
+            let location = center + ChunkLocation::new(Vector3::new(x, y, z));
+            let Some(chunk) = self.chunks.get(&location) else {
+                continue;
+            };
+
+            let color = match chunk {
+                Chunk::None { .. } => [1.0, 0.2, 0.2, 1.0],
+                Chunk::Generated { .. } => [1.0, 0.9, 0.2, 1.0],
+                Chunk::Meshed {
+                    mesh_kind: MeshKind::Voxels,
+                    ..
+                } => [0.2, 1.0, 0.3, 1.0],
+                Chunk::Meshed {
+                    mesh_kind: MeshKind::HeightmapSkin,
+                    ..
+                } => [0.3, 0.5, 1.0, 1.0],
+            };
+            let min = location.to_world_location_f32();
+            lines.add_box(
+                min + Vector3::from_value(INSET),
+                min + Vector3::from_value(CHUNK_SIZE as f32 - INSET),
+                color,
+            );
+        }
+    }
+
+    /// Halves the chunk data generator threads and the time spent meshing chunks per frame and stops generating chunks
+    /// beyond the render distance, so that streaming does not keep all cores busy
+    pub fn set_power_saving(&mut self, enabled: bool) {
+        self.power_saving = enabled;
+        self.active_data_gen_threads.store(
+            if enabled { NUM_DATA_GEN_THREAD / 2 } else { NUM_DATA_GEN_THREAD },
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Changes whether chunks are drawn from the closest to the farthest chunk, see [ChunkRenderManager::front_to_back]
+    pub fn set_front_to_back(&mut self, enabled: bool) {
+        self.chunk_render_manager.front_to_back = enabled;
+    }
+
+    /// Fills the bounce grid with the loaded chunks of the active dimension, starting at the given chunk at its minimum corner
+    pub fn add_bounce_cells(&self, grid: &mut BounceGrid, min: ChunkLocation) {
+        grid.clear();
+        let chunks_per_side = grid.size() / CELLS_PER_CHUNK;
+        for (x, y, z) in iproduct!(0..chunks_per_side, 0..chunks_per_side, 0..chunks_per_side) {
+            let location = min + ChunkLocation::new(Vector3::new(x as i32, y as i32, z as i32));
+            if let Some(data) = self
+                .chunks
+                .get(&location)
+                .and_then(Chunk::get_data)
+            {
+                grid.set_chunk(Vector3::new(x, y, z), &ChunkCells::new(data));
+            }
+        }
+    }
+
+    /// The chunks in the streaming queues of the active dimension as translucent boxes, one color per queue.
+    /// The boxes fade from the front to the back of every queue, so that the order in which chunks are taken is visible.
+    pub fn add_queue_boxes(&self, boxes: &mut DebugBoxes) {
+        // Inset of the first queue, so that the boxes of neighboring chunks and of different queues do not overlap
+        const INSET: f32 = 0.5;
+        const MAX_BOXES_PER_QUEUE: usize = 2048;
+
+        let active_dimension = self.active_dimension;
+        let in_active_dimension = |keys: Vec<(DimensionId, ChunkLocation)>| {
+            keys.into_iter()
+                .filter(move |&(dimension, _)| dimension == active_dimension)
+                .map(|(_, location)| location)
+                .collect_vec()
+        };
+        let queues = [
+            (in_active_dimension(self.location_queue.running_keys()), [1.0, 0.2, 0.2]),
+            (in_active_dimension(self.location_queue.queued_keys()), [1.0, 0.6, 0.1]),
+            (
+                self.chunk_mesh_queue
+                    .iter()
+                    .copied()
+                    .collect_vec(),
+                [0.2, 0.5, 1.0],
+            ),
+            (self.unload_queue.iter().copied().collect_vec(), [0.6, 0.6, 0.6]),
+        ];
+
+        for (queue_index, (locations, [r, g, b])) in queues.into_iter().enumerate() {
+            let inset = INSET * (queue_index + 1) as f32;
+            let len = locations.len().min(MAX_BOXES_PER_QUEUE);
+            for (index, location) in locations.into_iter().take(len).enumerate() {
+                let alpha = 0.5 - 0.4 * index as f32 / len as f32;
+                let min = location.to_world_location_f32();
+                boxes.add_box(
+                    min + Vector3::from_value(inset),
+                    min + Vector3::from_value(CHUNK_SIZE as f32 - inset),
+                    [r, g, b, alpha],
+                );
+            }
+        }
+    }
+
+    /// Changes whether CPU-side copies of the chunk meshes are kept, see [ChunkRenderManager::set_retain_cpu_meshes]
+    pub fn set_retain_cpu_meshes(&mut self, retain: bool) {
+        self.chunk_render_manager
+            .set_retain_cpu_meshes(retain);
+    }
+
+    /// Changes the distance in chunks after which coarser levels of detail are drawn, see [ChunkRenderManager::lod_distance]
+    pub fn set_lod_distance(&mut self, lod_distance: i32) {
+        self.chunk_render_manager.lod_distance = lod_distance;
+    }
+
+    /// Changes how voxel meshes are generated and queues all chunks with voxel meshes for remeshing
+    pub fn set_meshing_mode(&mut self, meshing_mode: MeshingMode) {
+        if meshing_mode == self.chunk_render_manager.meshing_mode {
+            return;
+        }
+        self.chunk_render_manager.meshing_mode = meshing_mode;
+        self.remesh_voxel_meshes();
+    }
+
+    /// Changes whether the ambient occlusion of voxel meshes is computed and how strong it is.
+    /// Toggling it requeues all chunks with voxel meshes for remeshing, while the strength only changes the lighting uniform.
+    pub fn set_ambient_occlusion(&mut self, enabled: bool, strength: f32) {
+        self.chunk_render_manager
+            .ambient_occlusion_strength = strength;
+        if enabled == self.chunk_render_manager.ambient_occlusion {
+            return;
+        }
+        self.chunk_render_manager.ambient_occlusion = enabled;
+        self.remesh_voxel_meshes();
+    }
+
+    /// Changes whether voxel meshes are lit with smoothed normals and queues all chunks with voxel meshes for remeshing
+    pub fn set_smooth_normals(&mut self, enabled: bool) {
+        if enabled == self.chunk_render_manager.smooth_normals {
+            return;
+        }
+        self.chunk_render_manager.smooth_normals = enabled;
+        self.remesh_voxel_meshes();
+    }
+
+    /// Queues all chunks with voxel meshes for remeshing after a setting of the voxel meshes changed
+    fn remesh_voxel_meshes(&mut self) {
+        for (&location, chunk) in &mut self.chunks {
+            if let Chunk::Meshed {
+                mesh_kind: MeshKind::Voxels,
+                ..
+            } = chunk
+            {
+                chunk.detach_mesh().expect("chunk to be meshed");
+                self.chunk_mesh_queue.push_back(location);
+            }
+        }
+    }
+
+    /// Queues all meshed chunks of the active dimension for remeshing, e.g. after the palette of the voxel colors changed
+    pub fn remesh_all_chunks(&mut self) {
+        for (&location, chunk) in &mut self.chunks {
+            if let Chunk::Meshed { .. } = chunk {
+                chunk.detach_mesh().expect("chunk to be meshed");
+                self.chunk_mesh_queue.push_back(location);
+            }
+        }
     }
 }
 
 impl Renderer for ChunkManager {
-    fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, camera_bind_group: &'a BindGroup, render_ctx: &RenderCtx) {
+    fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, camera: UniformBinding<'a>, render_ctx: &RenderCtx) {
         self.chunk_render_manager
-            .render(render_pass, camera_bind_group, render_ctx);
+            .render(render_pass, camera, render_ctx);
+    }
+}
+
+/// Voxel edits of [ChunkManager::edit], which are applied immediately but only remeshed once the transaction is finished
+pub struct EditTransaction<'a> {
+    chunk_manager: &'a mut ChunkManager,
+    /// Chunks whose meshes contain faces of an edited voxel
+    affected_chunks: HashSet<ChunkLocation>,
+    /// Whether the edits are sent to the server, if the chunk manager is connected to one
+    send_to_server: bool,
+}
+
+impl EditTransaction<'_> {
+    /// Replaces a single voxel. Its chunk is remeshed at the end of the transaction, together with all neighbor chunks that touch the voxel.
+    pub fn set_voxel(&mut self, location: WorldLocation, voxel_data: VoxelData) -> Result<()> {
+        let (chunk_location, local_location) = location.separate();
+        let chunk_manager = &mut *self.chunk_manager;
+
+        let Some((data, occupancy)) = chunk_manager
+            .chunks
+            .get_mut(&chunk_location)
+            .and_then(Chunk::get_data_and_occupancy_mut)
+        else {
+            bail!("Cannot set the voxel at {location:?}, its chunk is not generated");
+        };
+        let previous_size = data.size_in_bytes();
+        data.set_voxel_data(local_location, voxel_data);
+        occupancy.update(local_location, voxel_data.ty);
+        chunk_manager.total_voxel_data_size = chunk_manager.total_voxel_data_size + data.size_in_bytes() - previous_size;
+        chunk_manager
+            .edits
+            .record(chunk_location, local_location, voxel_data);
+        if let Some(storage) = &mut chunk_manager.storage {
+            storage.mark_modified(chunk_location);
+        }
+        if let Some(ray_traced_lighting) = &mut chunk_manager.ray_traced_lighting {
+            ray_traced_lighting.invalidate(chunk_location);
+        }
+        if let Some(server) = chunk_manager
+            .server
+            .as_ref()
+            .filter(|_| self.send_to_server)
+        {
+            server.send(&ClientMessage::SetVoxel {
+                dimension: chunk_manager.dimension_names[chunk_manager.active_dimension].clone(),
+                location,
+                voxel_data,
+            });
+        }
+
+        self.affected_chunks
+            .extend(chunks_touching_voxel(chunk_location, local_location));
+
+        Ok(())
+    }
+
+    /// Type of the voxel at the given location including the edits of this transaction, if its chunk is generated
+    pub fn voxel_at(&self, location: WorldLocation) -> Option<VoxelType> {
+        self.chunk_manager.voxel_at(location)
+    }
+
+    /// Number of chunks that are remeshed at the end of the transaction, if they are meshed
+    pub fn affected_chunks(&self) -> usize {
+        self.affected_chunks.len()
+    }
+}
+
+/// The chunk of a voxel and the neighbor chunks whose meshes contain it, which are only those that share the border it lies on
+fn chunks_touching_voxel(
+    chunk_location: ChunkLocation,
+    local_location: LocalChunkLocation<WithinBounds>,
+) -> impl Iterator<Item = ChunkLocation> {
+    let last = CHUNK_SIZE as i32 - 1;
+    let border_offset = |c: i32| match c {
+        0 => -1..=0,
+        c if c == last => 0..=1,
+        _ => 0..=0,
+    };
+
+    iproduct!(
+        border_offset(local_location.x),
+        border_offset(local_location.y),
+        border_offset(local_location.z)
+    )
+    .map(move |(dx, dy, dz)| chunk_location + ChunkLocation::new(Vector3::new(dx, dy, dz)))
+}
+
+/// Message of a caught panic, which is usually a string
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use cgmath::Vector3;
+    use itertools::{iproduct, Itertools};
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::chunk_manager::{Chunk, ChunkManager};
+    use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+    use crate::world::location::{ChunkLocation, LocalChunkLocation, WorldLocation};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    fn uniform_type(data: Option<&ChunkData>) -> Option<VoxelType> {
+        match data {
+            Some(ChunkData::UniformType(voxel_data)) => Some(voxel_data.ty),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_attach_data_replaces_the_data_of_generated_chunks() {
+        let mut chunk = Chunk::new();
+        assert!(chunk
+            .attach_data(ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone)))
+            .is_none());
+
+        let replaced = chunk.attach_data(ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air)));
+        assert_eq!(uniform_type(replaced.as_ref()), Some(VoxelType::Stone));
+        assert_eq!(uniform_type(chunk.get_data()), Some(VoxelType::Air));
+        assert_eq!(chunk.neighbor_count(), Some(0));
+    }
+
+    fn generate(chunks: &mut hashbrown::HashMap<ChunkLocation, Chunk>, location: ChunkLocation) {
+        ChunkManager::chunk_entry(chunks, location).attach_data(ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone)));
+        for (dx, dy, dz) in iproduct!(-1..=1, -1..=1, -1..=1).filter(|&offset| offset != (0, 0, 0)) {
+            ChunkManager::add_generated_neighbor(chunks, location + ChunkLocation::new(Vector3::new(dx, dy, dz)));
+        }
+    }
+
+    #[test]
+    fn test_chunks_that_are_loaded_again_are_meshed() {
+        let mut chunks = hashbrown::HashMap::new();
+        let center = ChunkLocation::new(Vector3::new(0, 0, 0));
+        for (x, y, z) in iproduct!(-1..=1, -1..=1, -1..=1) {
+            generate(&mut chunks, ChunkLocation::new(Vector3::new(x, y, z)));
+        }
+        assert_eq!(chunks[&center].neighbor_count(), Some(26));
+
+        let mut unmeshed_neighbors = HashSet::new();
+        ChunkManager::remove_chunk(&mut chunks, center, &mut unmeshed_neighbors);
+        ChunkManager::remove_chunk(&mut chunks, ChunkLocation::new(Vector3::new(2, 0, 0)), &mut unmeshed_neighbors);
+        assert_eq!(chunks[&ChunkLocation::new(Vector3::new(1, 0, 0))].neighbor_count(), Some(16));
+
+        // The neighbors that stayed loaded are counted when the chunks enter the loaded area again
+        assert_eq!(
+            ChunkManager::chunk_entry(&mut chunks, ChunkLocation::new(Vector3::new(2, 0, 0))).neighbor_count(),
+            Some(9)
+        );
+        generate(&mut chunks, center);
+        let chunk = chunks.get_mut(&center).unwrap();
+        assert_eq!(chunk.neighbor_count(), Some(26));
+        assert!(chunk.enqueue_for_mesh_gen().unwrap());
+        assert_eq!(chunks[&ChunkLocation::new(Vector3::new(1, 0, 0))].neighbor_count(), Some(17));
+    }
+
+    #[test]
+    fn test_chunks_at_the_border_of_a_hot_swap_are_meshed_again() {
+        let mut chunks = hashbrown::HashMap::new();
+        for (x, y, z) in iproduct!(-2..=2, -2..=2, -2..=2) {
+            generate(&mut chunks, ChunkLocation::new(Vector3::new(x, y, z)));
+        }
+
+        // Like the chunks beyond a keep radius of 1
+        let regenerated = iproduct!(-3..=3, -3..=3, -3..=3)
+            .filter(|&(x, y, z)| x.abs().max(y.abs()).max(z.abs()) > 1)
+            .map(|(x, y, z)| ChunkLocation::new(Vector3::new(x, y, z)))
+            .collect::<HashSet<_>>();
+        let mut unmeshed_neighbors = HashSet::new();
+        for &location in &regenerated {
+            ChunkManager::remove_chunk(&mut chunks, location, &mut unmeshed_neighbors);
+        }
+        ChunkManager::recount_generated_neighbors(&mut chunks, &regenerated);
+
+        let neighbor_count = |chunks: &hashbrown::HashMap<ChunkLocation, Chunk>, x, y, z| {
+            chunks[&ChunkLocation::new(Vector3::new(x, y, z))].neighbor_count()
+        };
+        assert_eq!(neighbor_count(&chunks, 0, 0, 0), Some(26));
+        assert_eq!(neighbor_count(&chunks, 1, 0, 0), Some(17));
+        assert_eq!(neighbor_count(&chunks, 1, 1, 1), Some(7));
+
+        for (x, y, z) in iproduct!(-2..=2, -2..=2, -2..=2).filter(|&(x, y, z)| x.abs().max(y.abs()).max(z.abs()) > 1) {
+            generate(&mut chunks, ChunkLocation::new(Vector3::new(x, y, z)));
+        }
+        for (x, y, z) in iproduct!(-1..=1, -1..=1, -1..=1) {
+            assert_eq!(neighbor_count(&chunks, x, y, z), Some(26));
+        }
+    }
+
+    /// A floor with a pillar and an overhang placed at `origin`, so that its faces have all kinds of ambient occlusion
+    fn scene_chunks(origin: Vector3<i32>) -> HashMap<ChunkLocation, ChunkData> {
+        let is_scene_voxel = |position: Vector3<i32>| {
+            let Vector3 { x, y, z } = position - origin;
+            let floor = y == 0 && (0..5).contains(&x) && (0..5).contains(&z);
+            let pillar = x == 2 && z == 2 && (1..4).contains(&y);
+            let overhang = y == 3 && x == 3 && (1..4).contains(&z);
+            floor || pillar || overhang
+        };
+
+        iproduct!(-1..=2, -1..=2, -1..=2)
+            .map(|(x, y, z)| {
+                let location = ChunkLocation::new(Vector3::new(x, y, z));
+                let mut data = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+                for local in LocalChunkLocation::iter() {
+                    if is_scene_voxel(WorldLocation::new(location, local.into_unknown()).0) {
+                        data.set_voxel_data(local, VoxelData::new(VoxelType::Stone));
+                    }
+                }
+                (location, data)
+            })
+            .collect()
+    }
+
+    /// Generates the chunks in the given order with the same neighbor counting as [ChunkManager::generate_chunks] and meshes every
+    /// chunk as soon as it is ready for meshing. Returns the faces relative to `origin` with their direction and ambient occlusion.
+    fn mesh_in_generation_order(
+        scene: &HashMap<ChunkLocation, ChunkData>,
+        order: &[ChunkLocation],
+        origin: Vector3<i32>,
+    ) -> Vec<(Vector3<i32>, usize, [u8; 4])> {
+        let mut chunks = hashbrown::HashMap::new();
+        let mut faces = Vec::new();
+        for &location in order {
+            let chunk = ChunkManager::chunk_entry(&mut chunks, location);
+            chunk.attach_data(scene[&location].clone());
+
+            let mut ready = Vec::new();
+            if chunk.neighbor_count() == Some(26) && chunk.enqueue_for_mesh_gen().unwrap() {
+                ready.push(location);
+            }
+            for (dx, dy, dz) in iproduct!(-1..=1, -1..=1, -1..=1).filter(|&offset| offset != (0, 0, 0)) {
+                let neighbor_location = location + ChunkLocation::new(Vector3::new(dx, dy, dz));
+                let (neighbor, count) = ChunkManager::add_generated_neighbor(&mut chunks, neighbor_location);
+                if count == 26 && matches!(neighbor.enqueue_for_mesh_gen(), Ok(true)) {
+                    ready.push(neighbor_location);
+                }
+            }
+
+            for location in ready {
+                let neighbor_chunks = NeighborChunks::new(&location, |location| chunks.get(location).and_then(Chunk::get_data))
+                    .expect("all neighbors of a chunk that is ready for meshing to be generated");
+                let data = chunks[&location]
+                    .get_data()
+                    .expect("chunk to be generated");
+                faces.extend(
+                    ChunkMeshGenerator::generate_culled_mesh(data, &neighbor_chunks, true)
+                        .into_iter()
+                        .map(|quad| {
+                            (
+                                WorldLocation::new(location, quad.position.into_unknown()).0 - origin,
+                                quad.direction as usize,
+                                quad.ambient_occlusion_values.map(|ao| ao as u8),
+                            )
+                        }),
+                );
+            }
+        }
+
+        faces.sort_by_key(|&(position, direction, ao)| (position.x, position.y, position.z, direction, ao));
+        faces
+    }
+
+    #[test]
+    fn test_ambient_occlusion_across_chunk_borders_does_not_depend_on_the_generation_order() {
+        let locations = iproduct!(-1..=2, -1..=2, -1..=2)
+            .map(|(x, y, z)| ChunkLocation::new(Vector3::new(x, y, z)))
+            .collect::<Vec<_>>();
+
+        let inside_one_chunk = Vector3::new(8, 8, 8);
+        let reference = mesh_in_generation_order(&scene_chunks(inside_one_chunk), &locations, inside_one_chunk);
+        assert!(!reference.is_empty());
+
+        // The scene crosses the borders between all eight chunks around the corner at (32, 32, 32).
+        // Every chunk is meshed when its last neighbor is generated, which is a different neighbor in every order.
+        let across_borders = Vector3::new(30, 30, 30);
+        let scene = scene_chunks(across_borders);
+        let reversed = locations.iter().rev().copied().collect::<Vec<_>>();
+        // Every 7th chunk, which visits all 64 chunks as 7 and 64 are coprime
+        let strided = (0..locations.len())
+            .map(|i| locations[i * 7 % locations.len()])
+            .collect::<Vec<_>>();
+        // The chunks around the corner first, so that they wait the longest for their outer neighbors
+        let inside_out = locations
+            .iter()
+            .copied()
+            .sorted_by_key(|location| {
+                let distance = location.map(|c| (2 * c - 1).abs());
+                distance.x + distance.y + distance.z
+            })
+            .collect::<Vec<_>>();
+
+        for order in [&locations, &reversed, &strided, &inside_out] {
+            assert_eq!(
+                mesh_in_generation_order(&scene, order, across_borders),
+                reference,
+                "the faces of the scene across the chunk borders differ from the faces inside of a single chunk"
+            );
+        }
     }
 }
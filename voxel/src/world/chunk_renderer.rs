@@ -1,92 +1,297 @@
 use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
 
-use wgpu::util::DeviceExt;
-use wgpu::{include_wgsl, BufferUsages, PushConstantRange, ShaderStages};
+use anyhow::Result;
+use cgmath::{Array, Vector3};
+use log::{error, info};
+use strum_macros::EnumIter;
+use wgpu::{include_wgsl, PushConstantRange, ShaderStages};
 
-use crate::rendering::texture::Texture;
-use crate::rendering::{RenderCtx, Renderer};
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::day_cycle::{DayCycle, RawLighting, LIGHTING_BIND_GROUP_LAYOUT_ENTRIES};
+use crate::rendering::frustum::{Aabb, Frustum};
+use crate::rendering::shader_manager::validated;
+use crate::rendering::texture::{Texture, TEXTURE_BIND_GROUP_LAYOUT_ENTRIES};
+use crate::rendering::uniform_ring::{UniformBinding, UniformRing};
+use crate::rendering::{RenderCtx, RenderHandle, Renderer};
 use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::gpu_culling::GpuCulling;
+use crate::world::chunk_renderer::gpu_meshing::GpuMesher;
+use crate::world::chunk_renderer::mesh_arena::{MeshAllocation, MeshArena};
+use crate::world::chunk_renderer::meshing::lod::LOD_SCALES;
 use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+use crate::world::chunk_renderer::smooth_terrain::{SmoothMesh, SmoothTerrainRenderer};
 use crate::world::chunk_renderer::vertex::Vertex;
+use crate::world::chunk_renderer::water::WaterRenderer;
 use crate::world::location::ChunkLocation;
+use crate::world::CHUNK_SIZE;
 
+pub mod block_icons;
+pub mod entity_renderer;
+pub mod gpu_culling;
+pub mod gpu_meshing;
+pub mod light_bounce;
+pub mod mesh_arena;
 pub mod meshing;
+pub mod ray_tracing;
+pub mod selection;
+pub mod smooth_terrain;
+pub mod texture_atlas;
 pub mod vertex;
+pub mod viewmodel;
+pub mod water;
+
+/// The kind of mesh that is generated for a chunk
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MeshKind {
+    /// One face per visible voxel side
+    Voxels,
+    /// A coarse heightmap of the terrain surface, used for distant chunks near the horizon
+    HeightmapSkin,
+}
+
+/// How the faces of voxels are turned into quads
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, EnumIter)]
+pub enum MeshingMode {
+    /// One quad per visible voxel face
+    #[default]
+    Culled,
+    /// Neighboring faces of the same voxel type are merged into larger quads, see [ChunkMeshGenerator::generate_greedy_mesh]
+    Greedy,
+    /// The faces of culled meshes are generated in a compute shader, see [GpuMesher]. Falls back to culled meshes on the CPU
+    /// if compute shaders are not supported.
+    Gpu,
+    /// Experimental smooth terrain instead of voxel faces, see [ChunkMeshGenerator::generate_marching_cubes_mesh]
+    MarchingCubes,
+}
+
+impl MeshingMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            MeshingMode::Culled => "Culled",
+            MeshingMode::Greedy => "Greedy",
+            MeshingMode::Gpu => "GPU",
+            MeshingMode::MarchingCubes => "Marching cubes (experimental)",
+        }
+    }
+}
+
+/// How the chunks are drawn, the modes other than [RenderMode::Filled] are debug views
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, EnumIter)]
+pub enum RenderMode {
+    #[default]
+    Filled,
+    /// Only the edges of the triangles
+    Wireframe,
+    /// The direction of every face as a color
+    Normals,
+    /// Only the ambient occlusion of the meshes, see [ChunkRenderManager::ambient_occlusion]
+    AmbientOcclusion,
+    /// Lines where the borders of the chunks cut through the faces
+    ChunkBounds,
+}
+
+impl RenderMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderMode::Filled => "Filled",
+            RenderMode::Wireframe => "Wireframe",
+            RenderMode::Normals => "Normals",
+            RenderMode::AmbientOcclusion => "AO only",
+            RenderMode::ChunkBounds => "Chunk bounds",
+        }
+    }
+
+    /// Pass that draws the chunks in this mode, the debug views do not use the depth pre-pass
+    fn pass(self) -> ChunkPass {
+        match self {
+            RenderMode::Filled => ChunkPass::Default,
+            RenderMode::Wireframe => ChunkPass::Wireframe,
+            RenderMode::Normals => ChunkPass::Normals,
+            RenderMode::AmbientOcclusion => ChunkPass::AmbientOcclusion,
+            RenderMode::ChunkBounds => ChunkPass::ChunkBounds,
+        }
+    }
+}
+
+/// Size of chunk meshes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshSize {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// Size of the vertices and indices on the GPU
+    pub bytes: usize,
+    /// Size of the CPU-side copies that are kept, see [ChunkRenderManager::set_retain_cpu_meshes]
+    pub retained_bytes: usize,
+}
+
+/// Default of [ChunkRenderManager::ambient_occlusion_strength]
+pub const DEFAULT_AMBIENT_OCCLUSION_STRENGTH: f32 = 0.2;
+
+/// 64MiB of vertices of the batched render path
+const BATCHED_VERTEX_CAPACITY: u32 = 1 << 23;
+/// 48MiB of indices of the batched render path
+const BATCHED_INDEX_CAPACITY: u32 = 3 << 22;
 
 pub struct ChunkRenderManager {
     renderers: HashMap<ChunkLocation, ChunkRenderer>,
+    /// Meshes of all chunks of the batched render path, which is used if GPU culling is not supported.
+    /// All chunks are drawn from the same buffers with a single pipeline and bind group setup.
+    arena: Option<MeshArena>,
+    meshes: HashMap<ChunkLocation, ChunkMesh>,
+    /// Total size of all [ChunkRenderManager::meshes]
+    pub mesh_size: MeshSize,
+
+    pipelines: ChunkPipelines,
+    /// Layout of the per-chunk and the GPU meshed render pipelines, which are created again by [ChunkRenderManager::reload_shader]
+    render_pipeline_layout: wgpu::PipelineLayout,
+    /// Bind group of the [texture_atlas::VoxelTextureAtlas]
+    texture_atlas: Arc<wgpu::BindGroup>,
+    /// Direction and color of the sunlight, see [ChunkRenderManager::update_lighting]
+    lighting: UniformRing<RawLighting>,
 
-    render_pipeline: wgpu::RenderPipeline,
+    /// Renders all chunks into the depth buffer first, so that every visible fragment is only shaded once
+    pub depth_prepass: bool,
+    pub render_mode: RenderMode,
+    /// Draws the closest chunks first, so that the fragments of the chunks behind them are rejected by the early depth test.
+    /// The culling shader of the GPU driven render path keeps this order when it compacts the draws of the visible chunks.
+    pub front_to_back: bool,
+    /// All chunks in the order in which they are drawn, see [ChunkRenderManager::sort_front_to_back]
+    draw_order: Vec<ChunkLocation>,
+    /// Used for the voxel meshes of all chunks that are meshed afterwards
+    pub meshing_mode: MeshingMode,
+    /// Distance in chunks from the camera after which the next coarser level of detail of voxel meshes is drawn, 0 to always draw the full mesh
+    pub lod_distance: i32,
+    /// Whether the ambient occlusion of the voxel meshes that are meshed afterwards is computed
+    pub ambient_occlusion: bool,
+    /// How much fully occluded corners are darkened, from 0 to 1
+    pub ambient_occlusion_strength: f32,
+    /// Whether the voxel meshes that are meshed afterwards are lit with normals smoothed across their edges, see
+    /// [ChunkMeshGenerator::smooth_normals]. The GPU mesher does not smooth normals, so chunks are meshed on the CPU instead.
+    pub smooth_normals: bool,
+    /// Keeps CPU-side copies of all chunk meshes that are generated afterwards, so that they can be uploaded again without remeshing
+    /// if the GPU resources have to be rebuilt
+    retain_cpu_meshes: bool,
+
+    /// GPU driven render path, used instead of the batched render path if it is supported by the device
+    gpu_culling: Option<GpuCulling>,
+    /// Number of chunks inside of the frustum, counted on the CPU if GPU culling is not used
+    visible_chunks: u32,
+    /// Meshes the voxel meshes of chunks on the GPU if the [MeshingMode::Gpu] is used and supported by the device
+    gpu_mesher: Option<GpuMesher>,
+
+    /// Water surfaces of the chunks, which are drawn in a separate transparent pass
+    pub water: WaterRenderer,
+    /// Meshes of the chunks that were meshed with [MeshingMode::MarchingCubes]
+    smooth_terrain: SmoothTerrainRenderer,
+}
+
+/// A chunk that has a non-empty mesh and is drawn by the chunk renderer
+#[derive(Copy, Clone, Debug)]
+pub struct ChunkDraw {
+    pub location: ChunkLocation,
+    pub aabb: Aabb,
+    pub index_count: u32,
+    /// Number of instances the mesh is drawn with
+    pub instance_count: u32,
 }
 
 impl ChunkRenderManager {
-    pub fn new(ctx: &RenderCtx, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+    pub fn new(ctx: &RenderCtx, texture_atlas: Arc<wgpu::BindGroup>) -> Self {
         let shader = ctx
             .device
             .create_shader_module(include_wgsl!("shader.wgsl"));
 
-        let render_pipeline_layout = ctx
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Mesh render pipeline layout"),
-                push_constant_ranges: &[PushConstantRange {
-                    stages: ShaderStages::VERTEX,
-                    range: 0..12,
-                }],
-                bind_group_layouts: &[camera_bind_group_layout],
-            });
-
-        let render_pipeline = ctx
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Default render pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    buffers: &[Vertex::layout()],
-                    entry_point: "vs_main",
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: ctx
-                            .surface_config
-                            .try_lock()
-                            .expect("i also hope this isn't locked")
-                            .format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                    entry_point: "fs_main",
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    cull_mode: Some(wgpu::Face::Back),
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: Texture::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: Default::default(),
-                    bias: wgpu::DepthBiasState {
-                        constant: 2,
-                        slope_scale: 2.0,
-                        clamp: 0.0,
-                    },
-                }),
-                multisample: Default::default(),
-                multiview: None,
-            });
+        let render_pipeline_layout = ctx.pipeline_layout(
+            "Mesh render pipeline layout",
+            &[
+                CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+                TEXTURE_BIND_GROUP_LAYOUT_ENTRIES,
+                LIGHTING_BIND_GROUP_LAYOUT_ENTRIES,
+            ],
+            &[PushConstantRange {
+                stages: ShaderStages::VERTEX,
+                range: 0..12,
+            }],
+        );
+
+        let pipelines = ChunkPipelines::new(ctx, &render_pipeline_layout, &shader, "vs_chunk", Vertex::layout());
+
+        let lighting_layout = ctx.bind_group_layout("Lighting bind group layout", LIGHTING_BIND_GROUP_LAYOUT_ENTRIES);
+        let lighting = UniformRing::new(ctx, "Lighting uniforms", &lighting_layout, &DayCycle::new().uniform());
+
+        let gpu_culling = if GpuCulling::is_supported(ctx) {
+            info!("Using GPU driven chunk rendering");
+            Some(GpuCulling::new(ctx, &shader))
+        } else {
+            info!("GPU driven chunk rendering is not supported, falling back to batched draw calls");
+            None
+        };
+        let arena = gpu_culling
+            .is_none()
+            .then(|| MeshArena::new(ctx, BATCHED_VERTEX_CAPACITY, BATCHED_INDEX_CAPACITY));
+
+        let gpu_mesher = GpuMesher::is_supported(ctx).then(|| GpuMesher::new(ctx, &render_pipeline_layout, &shader));
 
         Self {
             renderers: HashMap::new(),
-            render_pipeline,
+            arena,
+            meshes: HashMap::new(),
+            mesh_size: MeshSize::default(),
+            pipelines,
+            render_pipeline_layout,
+            texture_atlas,
+            lighting,
+            depth_prepass: false,
+            render_mode: RenderMode::default(),
+            front_to_back: true,
+            draw_order: Vec::new(),
+            meshing_mode: MeshingMode::default(),
+            lod_distance: 4,
+            ambient_occlusion: true,
+            ambient_occlusion_strength: DEFAULT_AMBIENT_OCCLUSION_STRENGTH,
+            smooth_normals: false,
+            retain_cpu_meshes: false,
+            gpu_culling,
+            visible_chunks: 0,
+            gpu_mesher,
+            water: WaterRenderer::new(ctx),
+            smooth_terrain: SmoothTerrainRenderer::new(ctx),
+        }
+    }
+
+    /// Compiles the chunk shader from the given source and replaces the pipelines of all render paths with ones that use it.
+    /// If the shader or any of the pipelines does not validate, the previous pipelines are kept.
+    pub fn reload_shader(&mut self, ctx: &RenderCtx, source: &str) -> Result<()> {
+        let (pipelines, culling_pipelines, mesher_pipelines) = validated(ctx, || {
+            let shader = ctx
+                .device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("Reloaded chunk shader"),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                });
+
+            (
+                ChunkPipelines::new(ctx, &self.render_pipeline_layout, &shader, "vs_chunk", Vertex::layout()),
+                self.gpu_culling
+                    .as_ref()
+                    .map(|gpu_culling| gpu_culling.create_pipelines(ctx, &shader)),
+                self.gpu_mesher
+                    .as_ref()
+                    .map(|_| GpuMesher::create_pipelines(ctx, &self.render_pipeline_layout, &shader)),
+            )
+        })?;
+
+        self.pipelines = pipelines;
+        if let (Some(gpu_culling), Some(pipelines)) = (&mut self.gpu_culling, culling_pipelines) {
+            gpu_culling.pipelines = pipelines;
+        }
+        if let (Some(gpu_mesher), Some(pipelines)) = (&mut self.gpu_mesher, mesher_pipelines) {
+            gpu_mesher.pipelines = pipelines;
         }
+
+        Ok(())
     }
 
     pub fn generate_chunk_renderer(
@@ -95,58 +300,769 @@ impl ChunkRenderManager {
         neighbor_chunks: NeighborChunks,
         ctx: &RenderCtx,
         chunk_location: ChunkLocation,
+        mesh_kind: MeshKind,
     ) {
-        let quads = ChunkMeshGenerator::generate_culled_mesh(chunk_data, neighbor_chunks);
+        if let (Some(gpu_mesher), MeshingMode::Gpu, MeshKind::Voxels, false) =
+            (&mut self.gpu_mesher, self.meshing_mode, mesh_kind, self.smooth_normals)
+        {
+            gpu_mesher.insert(ctx, chunk_location, chunk_data, &neighbor_chunks);
+            let (water, translucent) = ChunkMeshData::generate_transparent(chunk_data, &neighbor_chunks);
+            self.insert_transparent(ctx, chunk_location, Some(water), Some(translucent));
+            self.remove_cpu_mesh(ctx, chunk_location);
+            self.smooth_terrain.remove(chunk_location);
+            return;
+        }
 
-        let (vertices, mut indices) = ChunkMeshGenerator::generate_mesh_from_quads(quads);
+        let mesh = ChunkMeshData::generate(
+            chunk_data,
+            neighbor_chunks,
+            mesh_kind,
+            self.meshing_mode,
+            self.ambient_occlusion,
+            self.smooth_normals,
+        );
+        self.insert_mesh(ctx, chunk_location, mesh);
+    }
 
-        let vertex_buffer = ctx
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Chunks vertex buffer"),
-                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-                contents: bytemuck::cast_slice(&vertices),
-            });
+    /// Whether meshes of this kind are generated on the CPU with [ChunkMeshData::generate], which does not need the render manager
+    /// and can run on any thread. Their meshes are then uploaded with [ChunkRenderManager::insert_mesh].
+    pub fn meshes_on_cpu(&self, mesh_kind: MeshKind) -> bool {
+        !(self.gpu_mesher.is_some() && self.meshing_mode == MeshingMode::Gpu && mesh_kind == MeshKind::Voxels && !self.smooth_normals)
+    }
 
-        let index_buffer = ctx
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Chunks index buffer"),
-                usage: wgpu::BufferUsages::INDEX | BufferUsages::COPY_DST,
-                contents: bytemuck::cast_slice(&indices),
-            });
-
-        let renderer = ChunkRenderer {
-            vertex_buffer,
-            index_buffer,
-            num_indices: indices.len() as u32,
+    /// Uploads a mesh that was generated on the CPU and replaces the previous mesh of the chunk
+    pub fn insert_mesh(&mut self, ctx: &RenderCtx, chunk_location: ChunkLocation, mesh: ChunkMeshData) {
+        if let Some(gpu_mesher) = &mut self.gpu_mesher {
+            gpu_mesher.remove(chunk_location);
+        }
+
+        let ChunkMeshData {
+            levels,
+            water,
+            translucent,
+            smooth,
+        } = mesh;
+        self.insert_transparent(ctx, chunk_location, water, translucent);
+        self.smooth_terrain.remove(chunk_location);
+        if let Some(smooth) = smooth {
+            self.smooth_terrain
+                .insert(ctx, chunk_location, smooth);
+        }
+
+        // A mesh without any level has nothing to draw, so the previous mesh of the chunk is removed instead of being kept
+        if levels.is_empty() {
+            self.remove_cpu_mesh(ctx, chunk_location);
+            return;
+        }
+
+        let previous_level = self
+            .meshes
+            .get(&chunk_location)
+            .map_or(0, |mesh| mesh.selected_level);
+        let selected_level = previous_level.min(levels.len().saturating_sub(1));
+        self.upload(ctx, chunk_location, &levels, selected_level);
+
+        let mesh = ChunkMesh {
+            level_sizes: levels
+                .iter()
+                .map(|level| (level.vertices.len(), level.indices.len()))
+                .collect(),
+            selected_level,
+            retained: self.retain_cpu_meshes.then_some(levels),
         };
+        self.add_mesh_size(mesh.size());
+        if let Some(previous) = self.meshes.insert(chunk_location, mesh) {
+            self.remove_mesh_size(previous.size());
+        }
+    }
 
-        self.renderers.insert(chunk_location, renderer);
+    fn insert_transparent(
+        &mut self,
+        ctx: &RenderCtx,
+        chunk_location: ChunkLocation,
+        water: Option<MeshLevel>,
+        translucent: Option<MeshLevel>,
+    ) {
+        self.water.remove(chunk_location);
+        if let Some(water) = water {
+            self.water
+                .insert(ctx, chunk_location, water.vertices, water.indices);
+        }
+        if let Some(translucent) = translucent {
+            self.water
+                .insert_translucent(ctx, chunk_location, translucent.vertices, translucent.indices);
+        }
     }
-}
 
-impl Renderer for ChunkRenderManager {
-    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, render_ctx: &RenderCtx) {
-        for (position, renderer) in &self.renderers {
-            render_pass.set_pipeline(&self.render_pipeline);
+    /// Removes the mesh, the GPU buffers, the transparent meshes and the smooth mesh of a chunk
+    pub fn remove(&mut self, ctx: &RenderCtx, chunk_location: ChunkLocation) {
+        self.remove_cpu_mesh(ctx, chunk_location);
+        if let Some(gpu_mesher) = &mut self.gpu_mesher {
+            gpu_mesher.remove(chunk_location);
+        }
+        self.water.remove(chunk_location);
+        self.smooth_terrain.remove(chunk_location);
+    }
+
+    /// Removes the opaque mesh of a chunk that was generated on the CPU
+    fn remove_cpu_mesh(&mut self, ctx: &RenderCtx, chunk_location: ChunkLocation) {
+        if let (Some(renderer), Some(arena)) = (self.renderers.remove(&chunk_location), &mut self.arena) {
+            renderer
+                .levels
+                .into_iter()
+                .for_each(|mesh| arena.free(mesh));
+        }
+        if let Some(gpu_culling) = &mut self.gpu_culling {
+            gpu_culling.remove(ctx, &chunk_location);
+        }
+
+        if let Some(mesh) = self.meshes.remove(&chunk_location) {
+            self.remove_mesh_size(mesh.size());
+        }
+    }
 
-            render_pass.set_vertex_buffer(0, renderer.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(renderer.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    fn add_mesh_size(&mut self, size: MeshSize) {
+        self.mesh_size.vertices += size.vertices;
+        self.mesh_size.triangles += size.triangles;
+        self.mesh_size.bytes += size.bytes;
+        self.mesh_size.retained_bytes += size.retained_bytes;
+    }
+
+    fn remove_mesh_size(&mut self, size: MeshSize) {
+        self.mesh_size.vertices -= size.vertices;
+        self.mesh_size.triangles -= size.triangles;
+        self.mesh_size.bytes -= size.bytes;
+        self.mesh_size.retained_bytes -= size.retained_bytes;
+    }
+
+    /// Changes whether CPU-side copies of chunk meshes are kept. Disabling it frees all copies that were kept so far.
+    pub fn set_retain_cpu_meshes(&mut self, retain: bool) {
+        if retain == self.retain_cpu_meshes {
+            return;
+        }
+        self.retain_cpu_meshes = retain;
+
+        if !retain {
+            for mesh in self.meshes.values_mut() {
+                mesh.retained = None;
+            }
+            self.mesh_size.retained_bytes = 0;
+        }
+    }
+
+    /// Recreates the pipelines and all chunk buffers on the device of a new render context.
+    ///
+    /// Meshes with a CPU-side copy are uploaded again. Returns the chunks whose meshes were not kept or were meshed on the GPU,
+    /// which have to be remeshed.
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx, texture_atlas: Arc<wgpu::BindGroup>) -> Vec<ChunkLocation> {
+        let previous = mem::replace(self, Self::new(ctx, texture_atlas));
+        self.depth_prepass = previous.depth_prepass;
+        self.render_mode = previous.render_mode;
+        self.front_to_back = previous.front_to_back;
+        self.meshing_mode = previous.meshing_mode;
+        self.lod_distance = previous.lod_distance;
+        self.ambient_occlusion = previous.ambient_occlusion;
+        self.ambient_occlusion_strength = previous.ambient_occlusion_strength;
+        self.smooth_normals = previous.smooth_normals;
+        self.retain_cpu_meshes = previous.retain_cpu_meshes;
+        self.water = previous.water;
+        self.water.rebuild_gpu_resources(ctx);
+        self.smooth_terrain = previous.smooth_terrain;
+        self.smooth_terrain.rebuild_gpu_resources(ctx);
+
+        let mut lost: Vec<ChunkLocation> = previous
+            .gpu_mesher
+            .iter()
+            .flat_map(GpuMesher::locations)
+            .collect();
+        for (location, mesh) in previous.meshes {
+            match &mesh.retained {
+                Some(levels) => {
+                    self.upload(ctx, location, levels, mesh.selected_level);
+                    self.add_mesh_size(mesh.size());
+                    self.meshes.insert(location, mesh);
+                }
+                None => lost.push(location),
+            }
+        }
+
+        lost
+    }
+
+    /// Writes the lighting of the current frame together with the strength of the ambient occlusion, must be called before rendering
+    pub fn update_lighting(&mut self, handle: &mut RenderHandle, lighting: &RawLighting) {
+        let mut lighting = *lighting;
+        lighting.ambient_color[3] = if self.ambient_occlusion {
+            self.ambient_occlusion_strength
+        } else {
+            0.0
+        };
+        self.lighting.write(handle, &lighting);
+    }
+
+    pub fn set_texture_atlas(&mut self, texture_atlas: Arc<wgpu::BindGroup>) {
+        self.texture_atlas = texture_atlas;
+    }
+
+    fn upload(&mut self, ctx: &RenderCtx, chunk_location: ChunkLocation, levels: &[MeshLevel], selected_level: usize) {
+        if let Some(gpu_culling) = &mut self.gpu_culling {
+            gpu_culling.insert(ctx, chunk_location, levels, selected_level);
+            return;
+        }
+
+        let arena = self
+            .arena
+            .as_mut()
+            .expect("batched render path to be used without GPU culling");
+        // The previous mesh is only replaced once the new one was uploaded, so that the chunk is still drawn if the arena is full
+        let mut allocations = Vec::with_capacity(levels.len());
+        for level in levels {
+            // Not every device without GPU culling supports a base vertex
+            let Some(mesh) = arena.upload_rebased(ctx, &level.vertices, &level.indices) else {
+                error!("Could not upload the mesh of chunk {chunk_location:?}, the chunk mesh arena cannot grow any further");
+                allocations
+                    .into_iter()
+                    .for_each(|mesh| arena.free(mesh));
+                return;
+            };
+            allocations.push(mesh);
+        }
+
+        if let Some(previous) = self
+            .renderers
+            .insert(chunk_location, ChunkRenderer { levels: allocations })
+        {
+            previous
+                .levels
+                .into_iter()
+                .for_each(|mesh| arena.free(mesh));
+        }
+    }
 
-            render_pass.set_bind_group(0, camera_bind_group, &[]);
+    /// Selects the level of detail of every chunk mesh for the camera position
+    fn select_levels(&mut self, handle: &mut RenderHandle, camera_location: ChunkLocation) {
+        let lod_distance = self.lod_distance;
+
+        for (&location, mesh) in &mut self.meshes {
+            let level = if lod_distance > 0 {
+                (chunk_distance(location, camera_location) / lod_distance) as usize
+            } else {
+                0
+            };
+
+            let level = level.min(mesh.level_sizes.len().saturating_sub(1));
+            if level == mesh.selected_level {
+                continue;
+            }
+
+            mesh.selected_level = level;
+            if let Some(gpu_culling) = &mut self.gpu_culling {
+                gpu_culling.select_level(handle, &location, level);
+            }
+        }
+    }
+
+    /// Orders the chunks from the closest to the farthest chunk to the camera with a bucket sort by their distance in chunks.
+    /// Without [ChunkRenderManager::front_to_back], they keep the order of the hash maps.
+    fn sort_front_to_back(&mut self, camera_location: ChunkLocation) {
+        self.draw_order.clear();
+        self.draw_order
+            .extend(self.renderers.keys().copied());
+        if let Some(gpu_mesher) = &self.gpu_mesher {
+            self.draw_order.extend(gpu_mesher.locations());
+        }
+        if let Some(gpu_culling) = &self.gpu_culling {
+            self.draw_order.extend(gpu_culling.locations());
+        }
+
+        if !self.front_to_back {
+            return;
+        }
+
+        let mut buckets: Vec<Vec<ChunkLocation>> = Vec::new();
+        for location in self.draw_order.drain(..) {
+            let distance = chunk_distance(location, camera_location) as usize;
+            if distance >= buckets.len() {
+                buckets.resize_with(distance + 1, Vec::new);
+            }
+            buckets[distance].push(location);
+        }
+        self.draw_order
+            .extend(buckets.into_iter().flatten());
+    }
+
+    /// Must be called before rendering
+    pub fn prepare_render(&mut self, handle: &mut RenderHandle, frustum: &Frustum, camera_location: ChunkLocation) {
+        self.select_levels(handle, camera_location);
+        self.sort_front_to_back(camera_location);
+        self.water.sort_back_to_front(camera_location);
+
+        match &mut self.gpu_culling {
+            Some(gpu_culling) => gpu_culling.prepare(handle, frustum, &self.draw_order),
+            None => {
+                self.visible_chunks = self
+                    .chunk_draws()
+                    .filter(|chunk| frustum.intersects_aabb(chunk.aabb.min, chunk.aabb.max))
+                    .count() as u32;
+            }
+        }
+    }
+
+    /// All chunks that are drawn, so that other systems like occlusion culling or shadow cascades can use the same set of chunks
+    pub fn chunk_draws(&self) -> impl Iterator<Item = ChunkDraw> + '_ {
+        self.meshes
+            .iter()
+            .filter(|(_, mesh)| mesh.selected_index_count() > 0)
+            .map(|(&location, mesh)| {
+                let min = location.to_world_location_f32();
+
+                ChunkDraw {
+                    location,
+                    aabb: Aabb {
+                        min,
+                        max: min + Vector3::from_value(CHUNK_SIZE as f32),
+                    },
+                    index_count: mesh.selected_index_count() as u32,
+                    instance_count: 1,
+                }
+            })
+    }
+
+    pub fn uses_gpu_culling(&self) -> bool {
+        self.gpu_culling.is_some()
+    }
+
+    /// Size of the face instance buffers of the chunks that are meshed on the GPU
+    pub fn gpu_meshed_bytes(&self) -> usize {
+        self.gpu_mesher
+            .as_ref()
+            .map_or(0, |gpu_mesher| gpu_mesher.allocated_bytes)
+    }
+
+    /// Number of chunks meshed on the GPU that draw the faces of another chunk with the same voxels
+    pub fn gpu_shared_chunks(&self) -> usize {
+        self.gpu_mesher
+            .as_ref()
+            .map_or(0, GpuMesher::shared_chunks)
+    }
+
+    /// Number of chunks that passed the GPU culling test, or that are inside of the frustum if GPU culling is not used
+    pub fn visible_chunks(&self) -> u32 {
+        match &self.gpu_culling {
+            Some(gpu_culling) => gpu_culling.visible_chunks,
+            None => self.visible_chunks,
+        }
+    }
+
+    fn draw_chunks<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, pass: ChunkPass) {
+        if let Some(gpu_mesher) = &self.gpu_mesher {
+            gpu_mesher.render(
+                render_pass,
+                camera,
+                &self.texture_atlas,
+                self.lighting.binding(),
+                gpu_mesher.pipelines.get(pass),
+                &self.draw_order,
+            );
+        }
+
+        if let Some(gpu_culling) = &self.gpu_culling {
+            gpu_culling.render(
+                render_pass,
+                camera,
+                &self.texture_atlas,
+                self.lighting.binding(),
+                gpu_culling.pipelines.get(pass),
+            );
+            return;
+        }
+
+        let Some(arena) = &self.arena else {
+            return;
+        };
+        if self.renderers.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(self.pipelines.get(pass));
+        camera.bind(render_pass, 0);
+        render_pass.set_bind_group(1, &self.texture_atlas, &[]);
+        self.lighting.binding().bind(render_pass, 2);
+        render_pass.set_vertex_buffer(0, arena.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(arena.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        for position in &self.draw_order {
+            let Some(renderer) = self.renderers.get(position) else {
+                continue;
+            };
+            let selected_level = self
+                .meshes
+                .get(position)
+                .map_or(0, |mesh| mesh.selected_level);
+            // A kept previous mesh may have fewer levels than the mesh that could not be uploaded
+            let Some(mesh) = renderer
+                .levels
+                .get(selected_level.min(renderer.levels.len().saturating_sub(1)))
+            else {
+                continue;
+            };
+            if mesh.indices.is_empty() {
+                continue;
+            }
 
             // Push current chunk location
             let loc = [position.to_world_location_f32()];
             render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::cast_slice(&loc));
 
-            render_pass.draw_indexed(0..renderer.num_indices, 0, 0..1);
+            render_pass.draw_indexed(mesh.indices.clone(), 0, 0..1);
+        }
+    }
+}
+
+/// Distance between two chunks along the axis on which they are farthest apart
+fn chunk_distance(a: ChunkLocation, b: ChunkLocation) -> i32 {
+    let relative = a - b;
+    relative
+        .x
+        .abs()
+        .max(relative.y.abs())
+        .max(relative.z.abs())
+}
+
+impl Renderer for ChunkRenderManager {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, _render_ctx: &RenderCtx) {
+        if self.render_mode != RenderMode::Filled {
+            self.draw_chunks(render_pass, camera, self.render_mode.pass());
+        } else if self.depth_prepass {
+            self.draw_chunks(render_pass, camera, ChunkPass::DepthOnly);
+            self.draw_chunks(render_pass, camera, ChunkPass::DepthEqual);
+        } else {
+            self.draw_chunks(render_pass, camera, ChunkPass::Default);
         }
+
+        self.smooth_terrain
+            .render(render_pass, camera, self.lighting.binding());
+    }
+}
+
+/// All levels of detail of the mesh of a chunk, see [LOD_SCALES]
+struct ChunkMesh {
+    /// Number of vertices and indices of every level
+    level_sizes: Vec<(usize, usize)>,
+    /// Level that is drawn, selected every frame by the distance to the camera
+    selected_level: usize,
+    /// CPU-side copy of all levels, if [ChunkRenderManager::retain_cpu_meshes] was set when the chunk was meshed
+    retained: Option<Vec<MeshLevel>>,
+}
+
+impl ChunkMesh {
+    fn selected_index_count(&self) -> usize {
+        self.level_sizes[self.selected_level].1
+    }
+
+    /// Size of all levels together, as all of them are uploaded
+    fn size(&self) -> MeshSize {
+        let vertices = self
+            .level_sizes
+            .iter()
+            .map(|&(vertices, _)| vertices)
+            .sum();
+        let indices: usize = self
+            .level_sizes
+            .iter()
+            .map(|&(_, indices)| indices)
+            .sum();
+        let bytes = vertices * mem::size_of::<Vertex>() + indices * mem::size_of::<u32>();
+
+        MeshSize {
+            vertices,
+            triangles: indices / 3,
+            bytes,
+            retained_bytes: if self.retained.is_some() { bytes } else { 0 },
+        }
+    }
+}
+
+/// CPU-side mesh of a chunk, which does not depend on any GPU resources
+pub struct ChunkMeshData {
+    /// Every level of detail, see [LOD_SCALES]. Heightmap skins only have a single level.
+    pub levels: Vec<MeshLevel>,
+    /// Water surfaces, which are only generated for voxel meshes
+    pub water: Option<MeshLevel>,
+    /// Faces of translucent voxels like glass, which are only generated for voxel meshes
+    pub translucent: Option<MeshLevel>,
+    /// Replaces the levels of voxel meshes that are meshed with [MeshingMode::MarchingCubes]
+    pub smooth: Option<SmoothMesh>,
+}
+
+impl ChunkMeshData {
+    pub fn generate(
+        chunk_data: &ChunkData,
+        neighbor_chunks: NeighborChunks,
+        mesh_kind: MeshKind,
+        meshing_mode: MeshingMode,
+        ambient_occlusion: bool,
+        smooth_normals: bool,
+    ) -> Self {
+        match mesh_kind {
+            MeshKind::Voxels if meshing_mode == MeshingMode::MarchingCubes => {
+                let (water, translucent) = Self::generate_transparent(chunk_data, &neighbor_chunks);
+
+                Self {
+                    // The smooth terrain has no voxel faces and no coarser levels of detail
+                    levels: vec![MeshLevel::from((Vec::new(), Vec::new()))],
+                    water: Some(water),
+                    translucent: Some(translucent),
+                    smooth: Some(ChunkMeshGenerator::generate_marching_cubes_mesh(chunk_data, &neighbor_chunks)),
+                }
+            }
+            MeshKind::Voxels => {
+                let (water, translucent) = Self::generate_transparent(chunk_data, &neighbor_chunks);
+
+                let mut quads = match meshing_mode {
+                    MeshingMode::Culled | MeshingMode::Gpu => {
+                        ChunkMeshGenerator::generate_culled_mesh(chunk_data, &neighbor_chunks, ambient_occlusion)
+                    }
+                    MeshingMode::Greedy => ChunkMeshGenerator::generate_greedy_mesh(chunk_data, &neighbor_chunks, ambient_occlusion),
+                    MeshingMode::MarchingCubes => unreachable!("smooth terrain to be meshed without voxel faces"),
+                };
+                if smooth_normals {
+                    ChunkMeshGenerator::smooth_normals(&mut quads, chunk_data, &neighbor_chunks);
+                }
+
+                // The full resolution mesh keeps partial voxels, ambient occlusion and smoothed normals,
+                // only the coarser levels are simplified
+                let coarse_levels = LOD_SCALES[1..]
+                    .iter()
+                    .map(|&scale| ChunkMeshGenerator::generate_lod_mesh(chunk_data, &neighbor_chunks, scale));
+
+                let levels = [quads]
+                    .into_iter()
+                    .chain(coarse_levels)
+                    .map(|quads| MeshLevel::from(ChunkMeshGenerator::generate_mesh_from_quads(quads)))
+                    .collect();
+
+                Self {
+                    levels,
+                    water: Some(water),
+                    translucent: Some(translucent),
+                    smooth: None,
+                }
+            }
+            MeshKind::HeightmapSkin => Self {
+                levels: vec![MeshLevel::from(ChunkMeshGenerator::generate_heightmap_skin(
+                    chunk_data,
+                    neighbor_chunks,
+                ))],
+                water: None,
+                translucent: None,
+                smooth: None,
+            },
+        }
+    }
+
+    /// Water surfaces and translucent voxels of a voxel mesh
+    pub fn generate_transparent(chunk_data: &ChunkData, neighbor_chunks: &NeighborChunks) -> (MeshLevel, MeshLevel) {
+        let water_quads = ChunkMeshGenerator::generate_water_mesh(chunk_data, neighbor_chunks);
+        let translucent_quads = ChunkMeshGenerator::generate_translucent_mesh(chunk_data, neighbor_chunks);
+
+        (
+            MeshLevel::from(ChunkMeshGenerator::generate_mesh_from_quads(water_quads)),
+            MeshLevel::from(ChunkMeshGenerator::generate_mesh_from_quads(translucent_quads)),
+        )
+    }
+}
+
+pub struct MeshLevel {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl From<(Vec<Vertex>, Vec<u32>)> for MeshLevel {
+    fn from((vertices, indices): (Vec<Vertex>, Vec<u32>)) -> Self {
+        Self { vertices, indices }
     }
 }
 
 pub struct ChunkRenderer {
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    /// Allocations of every level of detail of the chunk mesh in the arena of the batched render path
+    levels: Vec<MeshAllocation>,
+}
+
+/// Render pipelines of one chunk render path
+pub struct ChunkPipelines {
+    /// Writes color and depth in a single pass
+    pub default: wgpu::RenderPipeline,
+    /// Only writes depth, used by the depth pre-pass
+    pub depth_only: wgpu::RenderPipeline,
+    /// Only shades fragments that have the depth written by the depth pre-pass
+    pub depth_equal: wgpu::RenderPipeline,
+    /// Debug views of the [RenderMode]s
+    pub wireframe: wgpu::RenderPipeline,
+    pub normals: wgpu::RenderPipeline,
+    pub ambient_occlusion: wgpu::RenderPipeline,
+    pub chunk_bounds: wgpu::RenderPipeline,
+}
+
+#[derive(Copy, Clone)]
+enum ChunkPass {
+    Default,
+    DepthOnly,
+    DepthEqual,
+    Wireframe,
+    Normals,
+    AmbientOcclusion,
+    ChunkBounds,
+}
+
+impl ChunkPipelines {
+    fn new(
+        ctx: &RenderCtx,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        vertex_entry_point: &str,
+        vertex_buffer: wgpu::VertexBufferLayout,
+    ) -> Self {
+        let create = |pass| create_chunk_render_pipeline(ctx, layout, shader, vertex_entry_point, vertex_buffer.clone(), pass);
+
+        Self {
+            default: create(ChunkPass::Default),
+            depth_only: create(ChunkPass::DepthOnly),
+            depth_equal: create(ChunkPass::DepthEqual),
+            wireframe: create(ChunkPass::Wireframe),
+            normals: create(ChunkPass::Normals),
+            ambient_occlusion: create(ChunkPass::AmbientOcclusion),
+            chunk_bounds: create(ChunkPass::ChunkBounds),
+        }
+    }
+
+    fn get(&self, pass: ChunkPass) -> &wgpu::RenderPipeline {
+        match pass {
+            ChunkPass::Default => &self.default,
+            ChunkPass::DepthOnly => &self.depth_only,
+            ChunkPass::DepthEqual => &self.depth_equal,
+            ChunkPass::Wireframe => &self.wireframe,
+            ChunkPass::Normals => &self.normals,
+            ChunkPass::AmbientOcclusion => &self.ambient_occlusion,
+            ChunkPass::ChunkBounds => &self.chunk_bounds,
+        }
+    }
+}
+
+fn create_chunk_render_pipeline(
+    ctx: &RenderCtx,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    vertex_entry_point: &str,
+    vertex_buffer: wgpu::VertexBufferLayout,
+    pass: ChunkPass,
+) -> wgpu::RenderPipeline {
+    let format = ctx.surface_format;
+
+    // Non sRGB surfaces need the shader to encode the color
+    let color_entry_point = if format.is_srgb() { "fs_textured" } else { "fs_textured_gamma" };
+    let chunk_bounds_entry_point = if format.is_srgb() {
+        "fs_chunk_bounds"
+    } else {
+        "fs_chunk_bounds_gamma"
+    };
+
+    let (label, fragment_entry_point, write_mask, depth_write_enabled, depth_compare) = match pass {
+        ChunkPass::Default => (
+            "Default render pipeline",
+            color_entry_point,
+            wgpu::ColorWrites::ALL,
+            true,
+            wgpu::CompareFunction::Less,
+        ),
+        ChunkPass::DepthOnly => (
+            "Depth pre-pass render pipeline",
+            "fs_depth_only",
+            wgpu::ColorWrites::empty(),
+            true,
+            wgpu::CompareFunction::Less,
+        ),
+        ChunkPass::DepthEqual => (
+            "Depth equal render pipeline",
+            color_entry_point,
+            wgpu::ColorWrites::ALL,
+            false,
+            wgpu::CompareFunction::Equal,
+        ),
+        ChunkPass::Wireframe => (
+            "Wireframe render pipeline",
+            color_entry_point,
+            wgpu::ColorWrites::ALL,
+            true,
+            wgpu::CompareFunction::Less,
+        ),
+        ChunkPass::Normals => (
+            "Normals render pipeline",
+            "fs_normals",
+            wgpu::ColorWrites::ALL,
+            true,
+            wgpu::CompareFunction::Less,
+        ),
+        ChunkPass::AmbientOcclusion => (
+            "Ambient occlusion render pipeline",
+            "fs_ambient_occlusion",
+            wgpu::ColorWrites::ALL,
+            true,
+            wgpu::CompareFunction::Less,
+        ),
+        ChunkPass::ChunkBounds => (
+            "Chunk bounds render pipeline",
+            chunk_bounds_entry_point,
+            wgpu::ColorWrites::ALL,
+            true,
+            wgpu::CompareFunction::Less,
+        ),
+    };
+    // Requested by every device, see RenderCtx::new
+    let polygon_mode = match pass {
+        ChunkPass::Wireframe => wgpu::PolygonMode::Line,
+        _ => wgpu::PolygonMode::Fill,
+    };
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                buffers: &[vertex_buffer],
+                entry_point: vertex_entry_point,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask,
+                })],
+                entry_point: fragment_entry_point,
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: Some(wgpu::Face::Back),
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                polygon_mode,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled,
+                depth_compare,
+                stencil: Default::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        })
 }
@@ -0,0 +1,203 @@
+use cgmath::{EuclideanSpace, Matrix4, Point3, Vector3};
+use enum_map::Enum;
+use wgpu::util::DeviceExt;
+use wgpu::{include_wgsl, PushConstantRange, ShaderStages};
+
+use crate::rendering::camera::{RawCamera, CAMERA_BIND_GROUP_LAYOUT_ENTRIES, NO_DISTANCE_FOG, NO_FOG, OPENGL_TO_WGPU_MATRIX};
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::UniformRing;
+use crate::rendering::RenderCtx;
+use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+use crate::world::chunk_renderer::vertex::Vertex;
+use crate::world::location::{ChunkLocation, LocalChunkLocation};
+use crate::world::voxel_data::{VoxelData, VoxelType};
+
+/// Width and height in pixels of a single icon in the atlas
+pub const BLOCK_ICON_SIZE: u32 = 64;
+
+const ATLAS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Isometric previews of all solid voxel types, rendered once into a texture atlas with a single row of icons
+pub struct BlockIcons {
+    pub atlas: Texture,
+    /// Voxel types in the order of their icons in the atlas
+    pub voxel_types: Vec<VoxelType>,
+}
+
+/// GPU buffers of the mesh of a single voxel
+pub(super) struct VoxelMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+}
+
+impl BlockIcons {
+    pub fn render(ctx: &RenderCtx) -> Self {
+        let voxel_types = solid_voxel_types();
+
+        let atlas = Texture::new_render_target(
+            &ctx.device,
+            "Block icon atlas",
+            BLOCK_ICON_SIZE * voxel_types.len() as u32,
+            BLOCK_ICON_SIZE,
+            ATLAS_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+
+        let meshes: Vec<VoxelMesh> = voxel_types
+            .iter()
+            .map(|&ty| create_voxel_mesh(ctx, ty))
+            .collect();
+
+        let camera_layout = ctx.bind_group_layout("Camera bind group layout", CAMERA_BIND_GROUP_LAYOUT_ENTRIES);
+        let camera = UniformRing::new(ctx, "Block icon camera", &camera_layout, &icon_camera());
+        let pipeline = create_icon_pipeline(ctx);
+
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Block icon encoder"),
+            });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Block icons"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &atlas.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(&pipeline);
+            camera.binding().bind(&mut render_pass, 0);
+            render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::cast_slice(&[[0.0f32; 3]]));
+
+            for (i, mesh) in meshes.iter().enumerate() {
+                let x = (i as u32 * BLOCK_ICON_SIZE) as f32;
+                render_pass.set_viewport(x, 0.0, BLOCK_ICON_SIZE as f32, BLOCK_ICON_SIZE as f32, 0.0, 1.0);
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+            }
+        }
+
+        ctx.queue.submit(Some(encoder.finish()));
+
+        Self { atlas, voxel_types }
+    }
+
+    /// Texture coordinates of the top left and bottom right corners of the icon of a voxel type
+    pub fn uv_rect(&self, ty: VoxelType) -> Option<([f32; 2], [f32; 2])> {
+        let index = self
+            .voxel_types
+            .iter()
+            .position(|&other| other == ty)?;
+        let width = 1.0 / self.voxel_types.len() as f32;
+
+        Some(([index as f32 * width, 0.0], [(index + 1) as f32 * width, 1.0]))
+    }
+}
+
+/// All voxel types that can be held and placed, see [VoxelType::is_solid]. The [VoxelType::Error] placeholder cannot be placed.
+pub fn solid_voxel_types() -> Vec<VoxelType> {
+    (0..VoxelType::LENGTH)
+        .map(VoxelType::from_usize)
+        .filter(|&ty| ty.is_solid() && ty != VoxelType::Error)
+        .collect()
+}
+
+/// Meshes a unit voxel of the given type at the origin with the regular chunk mesher, so that it looks like the voxels in the world
+pub(super) fn create_voxel_mesh(ctx: &RenderCtx, ty: VoxelType) -> VoxelMesh {
+    let air = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+
+    let mut data = ChunkData::new_filled_with_uniform_data(VoxelData::new(VoxelType::Air));
+    data.set_voxel_data(LocalChunkLocation::new_unchecked(Vector3::new(0, 0, 0)), VoxelData::new(ty));
+
+    let neighbor_chunks = NeighborChunks::new(&ChunkLocation::new(Vector3::new(0, 0, 0)), |_| Some(&air)).expect("all neighbors to be air");
+    let quads = ChunkMeshGenerator::generate_culled_mesh(&data, &neighbor_chunks, true);
+    let (vertices, indices) = ChunkMeshGenerator::generate_mesh_from_quads(quads);
+
+    VoxelMesh {
+        vertex_buffer: ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Voxel vertex buffer"),
+                usage: wgpu::BufferUsages::VERTEX,
+                contents: bytemuck::cast_slice(&vertices),
+            }),
+        index_buffer: ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Voxel index buffer"),
+                usage: wgpu::BufferUsages::INDEX,
+                contents: bytemuck::cast_slice(&indices),
+            }),
+        num_indices: indices.len() as u32,
+    }
+}
+
+/// Orthographic camera that looks at the unit voxel at the origin from above its positive x, y and z sides
+fn icon_camera() -> RawCamera {
+    let center = Point3::new(0.5, 0.5, 0.5);
+    let eye = center + Vector3::new(1.0, 1.0, 1.0) * 3.0;
+
+    // The silhouette of a unit cube seen along its diagonal is about 1.41 wide and 1.63 high
+    const HALF_EXTENT: f32 = 0.85;
+    let view = Matrix4::look_at_rh(eye, center, Vector3::unit_y());
+    let proj = OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-HALF_EXTENT, HALF_EXTENT, -HALF_EXTENT, HALF_EXTENT, 0.1, 10.0);
+
+    RawCamera {
+        position: eye.to_vec().extend(0.0).into(),
+        view_proj: (proj * view).into(),
+        clouds: [0.0; 4],
+        fog: NO_FOG,
+        distance_fog: NO_DISTANCE_FOG,
+    }
+}
+
+fn create_icon_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("../shader.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Mesh render pipeline layout",
+        &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES],
+        &[PushConstantRange {
+            stages: ShaderStages::VERTEX,
+            range: 0..12,
+        }],
+    );
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Block icon render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[Vertex::layout()],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(ATLAS_FORMAT.into())],
+                entry_point: "fs_main",
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            // A single convex voxel does not need depth testing once its back faces are culled
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
@@ -0,0 +1,175 @@
+use std::mem;
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use itertools::Itertools;
+use wgpu::{include_wgsl, vertex_attr_array};
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::{RenderCtx, RenderHandle, Renderer};
+use crate::world::chunk_renderer::block_icons::{create_voxel_mesh, solid_voxel_types, VoxelMesh};
+use crate::world::chunk_renderer::vertex::Vertex;
+use crate::world::entities::{Entities, EntityMesh};
+use crate::world::voxel_data::VoxelType;
+
+/// Instances that fit into the instance buffer before it has to grow
+const INITIAL_CAPACITY: usize = 1 << 8;
+
+/// Draws all entities with one instanced draw call per mesh
+pub struct EntityRenderer {
+    meshes: Vec<(VoxelType, VoxelMesh)>,
+    pipeline: wgpu::RenderPipeline,
+    instance_buffer: wgpu::Buffer,
+    /// Instances of the current frame for every mesh, as a range of the instance buffer
+    draws: Vec<(usize, Range<u32>)>,
+}
+
+/// Must match the `EntityInstance` struct in `shader.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct EntityInstance {
+    min: [f32; 3],
+    size: [f32; 3],
+}
+
+impl EntityRenderer {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let meshes = solid_voxel_types()
+            .into_iter()
+            .map(|ty| (ty, create_voxel_mesh(ctx, ty)))
+            .collect();
+
+        Self {
+            meshes,
+            pipeline: create_entity_pipeline(ctx),
+            instance_buffer: create_instance_buffer(ctx, INITIAL_CAPACITY),
+            draws: Vec::new(),
+        }
+    }
+
+    /// Recreates the meshes, the pipeline and the instance buffer on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self::new(ctx);
+    }
+
+    /// Uploads the instances of all entities at their current positions, which are drawn in this frame.
+    /// Entities whose mesh does not exist, e.g. voxels of non-solid types, are not drawn.
+    pub fn update(&mut self, handle: &mut RenderHandle, entities: &Entities) {
+        let instances_by_mesh = entities
+            .iter()
+            .filter_map(|(_, entity)| {
+                let EntityMesh::Voxel(ty) = entity.mesh;
+                let mesh = self
+                    .meshes
+                    .iter()
+                    .position(|(mesh_type, _)| *mesh_type == ty)?;
+                let aabb = entity.aabb();
+                Some((
+                    mesh,
+                    EntityInstance {
+                        min: aabb.min.into(),
+                        size: entity.size.into(),
+                    },
+                ))
+            })
+            .sorted_by_key(|&(mesh, _)| mesh)
+            .collect_vec();
+
+        // The instances of every mesh are contiguous, as they are sorted by their mesh
+        self.draws.clear();
+        for (index, &(mesh, _)) in instances_by_mesh.iter().enumerate() {
+            let index = index as u32;
+            match self.draws.last_mut() {
+                Some((last_mesh, instances)) if *last_mesh == mesh => instances.end = index + 1,
+                _ => self.draws.push((mesh, index..index + 1)),
+            }
+        }
+
+        let capacity = (self.instance_buffer.size() / mem::size_of::<EntityInstance>() as u64) as usize;
+        if instances_by_mesh.len() > capacity {
+            self.instance_buffer = create_instance_buffer(handle.render_ctx(), instances_by_mesh.len().next_power_of_two());
+        }
+        let instances = instances_by_mesh
+            .into_iter()
+            .map(|(_, instance)| instance)
+            .collect_vec();
+        handle.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+}
+
+impl Renderer for EntityRenderer {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, _render_ctx: &RenderCtx) {
+        if self.draws.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        camera.bind(render_pass, 0);
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        for (mesh, instances) in &self.draws {
+            let (_, mesh) = &self.meshes[*mesh];
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..mesh.num_indices, 0, instances.clone());
+        }
+    }
+}
+
+fn create_instance_buffer(ctx: &RenderCtx, capacity: usize) -> wgpu::Buffer {
+    ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Entity instance buffer"),
+        size: (capacity * mem::size_of::<EntityInstance>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_entity_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 2] = vertex_attr_array![2 => Float32x3, 3 => Float32x3];
+
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("../shader.wgsl"));
+
+    let layout = ctx.pipeline_layout("Entity render pipeline layout", &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES], &[]);
+
+    let fragment_entry_point = if ctx.surface_format.is_srgb() { "fs_main" } else { "fs_main_gamma" };
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Entity render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[
+                    Vertex::layout(),
+                    wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<EntityInstance>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &INSTANCE_ATTRIBUTES,
+                    },
+                ],
+                entry_point: "vs_entity",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(ctx.surface_format.into())],
+                entry_point: fragment_entry_point,
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
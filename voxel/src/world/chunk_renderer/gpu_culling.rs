@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use log::error;
+use wgpu::{include_wgsl, BufferBindingType, BufferUsages, ShaderStages};
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::day_cycle::LIGHTING_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::frustum::{Frustum, RawFrustum};
+use crate::rendering::readback::Readback;
+use crate::rendering::texture::TEXTURE_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::{RenderCtx, RenderHandle};
+use crate::world::chunk_renderer::mesh_arena::{MeshAllocation, MeshArena};
+use crate::world::chunk_renderer::vertex::Vertex;
+use crate::world::chunk_renderer::{ChunkPipelines, MeshLevel};
+use crate::world::location::ChunkLocation;
+
+/// Maximum number of non-empty chunk meshes
+const MAX_CHUNK_RECORDS: u32 = 1 << 16;
+/// 128MiB of vertices initially, the arena grows when it is full
+const VERTEX_CAPACITY: u32 = 1 << 24;
+/// 96MiB of indices, every quad consists of 4 vertices and 6 indices
+const INDEX_CAPACITY: u32 = 3 << 23;
+
+/// GPU driven chunk rendering.
+///
+/// All chunk meshes are stored in a shared [MeshArena] and have a draw record in a storage buffer.
+/// Every frame a compute pass tests all records against the camera frustum and writes compacted indirect draw commands
+/// in the draw order of the records, which are then drawn with a single indirect multi-draw.
+pub struct GpuCulling {
+    arena: MeshArena,
+    slots: HashMap<ChunkLocation, ChunkSlot>,
+    free_slots: Vec<u32>,
+    /// Number of record slots that have been used at least once
+    num_slots: u32,
+
+    records_buffer: wgpu::Buffer,
+    /// Number of records followed by the indices of the records in the order in which they are drawn
+    draw_order_buffer: wgpu::Buffer,
+    /// Contents of the [GpuCulling::draw_order_buffer], kept to reuse its allocation
+    draw_order: Vec<u32>,
+    commands_buffer: wgpu::Buffer,
+    draw_count_buffer: wgpu::Buffer,
+    frustum_buffer: wgpu::Buffer,
+
+    culling_pipeline: wgpu::ComputePipeline,
+    culling_bind_group: wgpu::BindGroup,
+    pub pipelines: ChunkPipelines,
+    /// Layout of the render pipelines, with which they are created again when the chunk shader is reloaded
+    render_pipeline_layout: wgpu::PipelineLayout,
+    records_bind_group: wgpu::BindGroup,
+
+    /// Whether the number of draws can be read from the GPU written draw count
+    use_draw_count: bool,
+
+    /// Number of chunks that passed the culling test in a recent frame
+    pub visible_chunks: u32,
+    draw_count_readback: Option<Readback>,
+}
+
+struct ChunkSlot {
+    index: u32,
+    /// Allocations of every level of detail of the chunk mesh, of which only the selected one is drawn
+    levels: Vec<MeshAllocation>,
+    position: [f32; 3],
+}
+
+/// Must match the `ChunkDrawRecord` struct in `chunk_culling.wgsl` and `shader.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct RawChunkDrawRecord {
+    position: [f32; 3],
+    index_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    active: u32,
+    _padding: u32,
+}
+
+impl ChunkSlot {
+    fn record(&self, level: usize) -> RawChunkDrawRecord {
+        // Slots keep their previous mesh when a new one does not fit into the arena, whose levels were selected for the new mesh
+        let Some(mesh) = self
+            .levels
+            .get(level.min(self.levels.len().saturating_sub(1)))
+        else {
+            // Inactive, so that the culling shader skips it
+            return RawChunkDrawRecord::zeroed();
+        };
+
+        RawChunkDrawRecord {
+            position: self.position,
+            index_count: mesh.indices.len() as u32,
+            first_index: mesh.indices.start,
+            base_vertex: mesh.vertices.start as i32,
+            active: 1,
+            _padding: 0,
+        }
+    }
+}
+
+fn record_offset(index: u32) -> u64 {
+    index as u64 * mem::size_of::<RawChunkDrawRecord>() as u64
+}
+
+/// Size of the arguments of a single `draw_indexed_indirect` call, which are written by the culling shader
+const DRAW_INDEXED_INDIRECT_SIZE: u64 = 5 * mem::size_of::<u32>() as u64;
+
+impl GpuCulling {
+    pub fn is_supported(ctx: &RenderCtx) -> bool {
+        let required_features = wgpu::Features::INDIRECT_FIRST_INSTANCE | wgpu::Features::MULTI_DRAW_INDIRECT;
+        let required_flags =
+            wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::VERTEX_STORAGE | wgpu::DownlevelFlags::INDIRECT_EXECUTION;
+
+        ctx.device.features().contains(required_features) && ctx.downlevel_flags.contains(required_flags)
+    }
+
+    pub fn new(ctx: &RenderCtx, shader: &wgpu::ShaderModule) -> Self {
+        let records_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk draw records buffer"),
+            size: MAX_CHUNK_RECORDS as u64 * mem::size_of::<RawChunkDrawRecord>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let draw_order_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk draw order buffer"),
+            size: (MAX_CHUNK_RECORDS as u64 + 1) * mem::size_of::<u32>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let commands_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk draw commands buffer"),
+            size: MAX_CHUNK_RECORDS as u64 * DRAW_INDEXED_INDIRECT_SIZE,
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let draw_count_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk draw count buffer"),
+            size: mem::size_of::<u32>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let frustum_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk culling frustum buffer"),
+            size: mem::size_of::<RawFrustum>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let culling_bind_group_layout_entries = [
+            buffer_layout_entry(0, ShaderStages::COMPUTE, BufferBindingType::Uniform),
+            buffer_layout_entry(1, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: true }),
+            buffer_layout_entry(2, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: false }),
+            buffer_layout_entry(3, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: false }),
+            buffer_layout_entry(4, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: true }),
+        ];
+        let culling_bind_group_layout = ctx.bind_group_layout("Chunk culling bind group layout", &culling_bind_group_layout_entries);
+
+        let culling_bind_group = ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Chunk culling bind group"),
+                layout: &culling_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: frustum_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: records_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: commands_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: draw_count_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: draw_order_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+        let culling_shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("../chunk_culling.wgsl"));
+
+        let culling_pipeline_layout = ctx.pipeline_layout("Chunk culling pipeline layout", &[&culling_bind_group_layout_entries], &[]);
+
+        let culling_pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Chunk culling pipeline"),
+                layout: Some(&culling_pipeline_layout),
+                module: &culling_shader,
+                entry_point: "cs_main",
+            });
+
+        let records_bind_group_layout_entries = [buffer_layout_entry(
+            0,
+            ShaderStages::VERTEX,
+            BufferBindingType::Storage { read_only: true },
+        )];
+        let records_bind_group_layout = ctx.bind_group_layout("Chunk draw records bind group layout", &records_bind_group_layout_entries);
+
+        let records_bind_group = ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Chunk draw records bind group"),
+                layout: &records_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: records_buffer.as_entire_binding(),
+                }],
+            });
+
+        let render_pipeline_layout = ctx.pipeline_layout(
+            "Indirect mesh render pipeline layout",
+            &[
+                CAMERA_BIND_GROUP_LAYOUT_ENTRIES,
+                TEXTURE_BIND_GROUP_LAYOUT_ENTRIES,
+                LIGHTING_BIND_GROUP_LAYOUT_ENTRIES,
+                &records_bind_group_layout_entries,
+            ],
+            &[],
+        );
+
+        Self {
+            arena: MeshArena::new(ctx, VERTEX_CAPACITY, INDEX_CAPACITY),
+            slots: HashMap::new(),
+            free_slots: Vec::new(),
+            num_slots: 0,
+            records_buffer,
+            draw_order_buffer,
+            draw_order: Vec::new(),
+            commands_buffer,
+            draw_count_buffer,
+            frustum_buffer,
+            culling_pipeline,
+            culling_bind_group,
+            pipelines: ChunkPipelines::new(ctx, &render_pipeline_layout, shader, "vs_main_indirect", Vertex::layout()),
+            render_pipeline_layout,
+            records_bind_group,
+            use_draw_count: ctx
+                .device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT),
+            visible_chunks: 0,
+            draw_count_readback: None,
+        }
+    }
+
+    /// Uploads all levels of detail of the mesh of a chunk, replacing any previous mesh of the same chunk.
+    /// The previous mesh is only freed once the new one is uploaded, so it is kept and drawn if the new one does not fit.
+    pub(super) fn insert(&mut self, ctx: &RenderCtx, location: ChunkLocation, levels: &[MeshLevel], selected_level: usize) {
+        if levels
+            .iter()
+            .all(|level| level.indices.is_empty())
+        {
+            self.remove(ctx, &location);
+            return;
+        }
+
+        let mut allocations = Vec::with_capacity(levels.len());
+        for level in levels {
+            let Some(mesh) = self
+                .arena
+                .upload(ctx, &level.vertices, &level.indices)
+            else {
+                error!("Could not upload the mesh of chunk {location:?}, the chunk mesh arena cannot grow any further");
+                allocations
+                    .into_iter()
+                    .for_each(|mesh| self.arena.free(mesh));
+                return;
+            };
+            allocations.push(mesh);
+        }
+
+        let index = match self.slots.remove(&location) {
+            Some(previous) => {
+                previous
+                    .levels
+                    .into_iter()
+                    .for_each(|mesh| self.arena.free(mesh));
+                previous.index
+            }
+            None => match self.free_slots.pop() {
+                Some(index) => index,
+                None if self.num_slots < MAX_CHUNK_RECORDS => {
+                    self.num_slots += 1;
+                    self.num_slots - 1
+                }
+                None => {
+                    error!("Could not render chunk {location:?}, the maximum number of {MAX_CHUNK_RECORDS} rendered chunks is reached");
+                    allocations
+                        .into_iter()
+                        .for_each(|mesh| self.arena.free(mesh));
+                    return;
+                }
+            },
+        };
+
+        let slot = ChunkSlot {
+            index,
+            levels: allocations,
+            position: location.to_world_location_f32().into(),
+        };
+        ctx.queue.write_buffer(
+            &self.records_buffer,
+            record_offset(index),
+            bytemuck::bytes_of(&slot.record(selected_level)),
+        );
+
+        self.slots.insert(location, slot);
+    }
+
+    /// Render pipelines of the GPU driven render path with another chunk shader, see [ChunkRenderManager::reload_shader]
+    ///
+    /// [ChunkRenderManager::reload_shader]: crate::world::chunk_renderer::ChunkRenderManager::reload_shader
+    pub fn create_pipelines(&self, ctx: &RenderCtx, shader: &wgpu::ShaderModule) -> ChunkPipelines {
+        ChunkPipelines::new(ctx, &self.render_pipeline_layout, shader, "vs_main_indirect", Vertex::layout())
+    }
+
+    pub fn remove(&mut self, ctx: &RenderCtx, location: &ChunkLocation) {
+        if let Some(slot) = self.slots.remove(location) {
+            ctx.queue.write_buffer(
+                &self.records_buffer,
+                record_offset(slot.index),
+                bytemuck::bytes_of(&RawChunkDrawRecord::zeroed()),
+            );
+            slot.levels
+                .into_iter()
+                .for_each(|mesh| self.arena.free(mesh));
+            self.free_slots.push(slot.index);
+        }
+    }
+
+    /// Draws another level of detail of the mesh of a chunk
+    pub fn select_level(&mut self, handle: &mut RenderHandle, location: &ChunkLocation, level: usize) {
+        if let Some(slot) = self.slots.get(location) {
+            handle.write_buffer(
+                &self.records_buffer,
+                record_offset(slot.index),
+                bytemuck::bytes_of(&slot.record(level)),
+            );
+        }
+    }
+
+    /// Chunks that have a mesh, in no particular order
+    pub fn locations(&self) -> impl Iterator<Item = ChunkLocation> + '_ {
+        self.slots.keys().copied()
+    }
+
+    /// Records the culling compute pass. Must be called before rendering.
+    /// The visible chunks are drawn in the order of `draw_order`, chunks that are not in it are not drawn.
+    pub fn prepare(&mut self, handle: &mut RenderHandle, frustum: &Frustum, draw_order: &[ChunkLocation]) {
+        if self.num_slots == 0 {
+            return;
+        }
+
+        if let Some(Ok(data)) = self
+            .draw_count_readback
+            .as_ref()
+            .and_then(Readback::try_take)
+        {
+            self.visible_chunks = u32::from_le_bytes(
+                data[..4]
+                    .try_into()
+                    .expect("draw count to be an u32"),
+            );
+            self.draw_count_readback = None;
+        }
+
+        handle.write_buffer(&self.frustum_buffer, 0, bytemuck::bytes_of(&frustum.to_raw()));
+
+        self.draw_order.clear();
+        self.draw_order.push(0);
+        self.draw_order.extend(
+            draw_order
+                .iter()
+                .filter_map(|location| self.slots.get(location))
+                .map(|slot| slot.index),
+        );
+        self.draw_order[0] = self.draw_order.len() as u32 - 1;
+        handle.write_buffer(&self.draw_order_buffer, 0, bytemuck::cast_slice(&self.draw_order));
+
+        let encoder = handle.get_command_encoder();
+        encoder.clear_buffer(&self.draw_count_buffer, 0, None);
+        if !self.use_draw_count {
+            // All commands after the visible ones are drawn too, so they have to be zeroed
+            encoder.clear_buffer(
+                &self.commands_buffer,
+                0,
+                wgpu::BufferSize::new(self.num_slots as u64 * DRAW_INDEXED_INDIRECT_SIZE),
+            );
+        }
+
+        let mut compute_pass = handle.begin_compute_pass("chunk_culling");
+        compute_pass.set_pipeline(&self.culling_pipeline);
+        compute_pass.set_bind_group(0, &self.culling_bind_group, &[]);
+        // A single workgroup, so that the commands can be compacted without losing the draw order, see `chunk_culling.wgsl`
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        drop(compute_pass);
+
+        // Only one readback of the draw count is in flight at a time
+        if self.draw_count_readback.is_none() {
+            self.draw_count_readback = Some(handle.read_buffer(&self.draw_count_buffer, 0, mem::size_of::<u32>() as u64));
+        }
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera: UniformBinding<'a>,
+        texture_atlas: &'a wgpu::BindGroup,
+        lighting: UniformBinding<'a>,
+        pipeline: &'a wgpu::RenderPipeline,
+    ) {
+        if self.num_slots == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(pipeline);
+        camera.bind(render_pass, 0);
+        render_pass.set_bind_group(1, texture_atlas, &[]);
+        lighting.bind(render_pass, 2);
+        render_pass.set_bind_group(3, &self.records_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.arena.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.arena.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        if self.use_draw_count {
+            render_pass.multi_draw_indexed_indirect_count(&self.commands_buffer, 0, &self.draw_count_buffer, 0, self.num_slots);
+        } else {
+            render_pass.multi_draw_indexed_indirect(&self.commands_buffer, 0, self.num_slots);
+        }
+    }
+}
+
+pub(super) const fn buffer_layout_entry(binding: u32, visibility: ShaderStages, ty: BufferBindingType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
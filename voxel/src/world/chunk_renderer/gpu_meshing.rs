@@ -0,0 +1,448 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+use cgmath::num_traits::ToPrimitive;
+use cgmath::Vector3;
+use enum_map::Enum;
+use itertools::iproduct;
+use strum::IntoEnumIterator;
+use wgpu::util::DeviceExt;
+use wgpu::{include_wgsl, vertex_attr_array, BufferBindingType, BufferUsages, ShaderStages};
+
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::RenderCtx;
+use crate::vector_utils::{MapElemWise, RemEuclid};
+use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::gpu_culling::buffer_layout_entry;
+use crate::world::chunk_renderer::meshing::{voxel_type_to_color_lookup, NeighborChunks};
+use crate::world::chunk_renderer::ChunkPipelines;
+use crate::world::location::{ChunkLocation, LocalChunkLocation};
+use crate::world::voxel_data::{VoxelShape, VoxelType};
+use crate::world::CHUNK_SIZE;
+
+/// Voxels along each side of the grid that is uploaded for a chunk, which includes one layer of voxels of the neighbor chunks
+const PADDED_SIZE: usize = CHUNK_SIZE + 2;
+/// Must match the size of the `tiles` array of `ChunkVoxels` in `gpu_meshing.wgsl`
+const MAX_VOXEL_TYPES: usize = 32;
+const MESHING_WORKGROUP_SIZE: u32 = 4;
+
+const OPAQUE: u32 = 0x100;
+const MESHED: u32 = 0x200;
+
+static_assertions::const_assert!(VoxelType::LENGTH <= MAX_VOXEL_TYPES);
+
+/// Meshing backend that generates the faces of voxel meshes in a compute shader instead of [ChunkMeshGenerator::generate_culled_mesh].
+///
+/// The voxels of a chunk are uploaded into a storage buffer, from which the compute shader appends one instance per visible face
+/// to the instance buffer of the chunk. The number of faces is counted directly in the arguments of the indirect draw of the chunk,
+/// so it never has to be read back. Chunks meshed this way have the same faces and ambient occlusion as culled meshes,
+/// but no coarser levels of detail, and are not part of [crate::world::chunk_renderer::ChunkRenderManager::chunk_draws].
+///
+/// The faces are relative to the chunk, so chunks with identical voxels and neighbor voxels, which are common with uniform terrain
+/// and flat worlds, share a single face instance buffer that is only meshed once.
+///
+/// [ChunkMeshGenerator::generate_culled_mesh]: crate::world::chunk_renderer::meshing::ChunkMeshGenerator::generate_culled_mesh
+pub struct GpuMesher {
+    meshing_pipeline: wgpu::ComputePipeline,
+    meshing_bind_group_layout: wgpu::BindGroupLayout,
+    /// Voxels of the chunk that is meshed next, shared by all chunks as they are meshed one after another
+    voxels_buffer: wgpu::Buffer,
+    pub pipelines: ChunkPipelines,
+    chunks: HashMap<ChunkLocation, GpuMeshedChunk>,
+    /// Meshes by the [ChunkVoxels::content_hash] of the voxels they were meshed from
+    meshes: HashMap<u64, SharedFaces>,
+    /// Size of the face instance buffers of all meshes, each shared buffer is only counted once
+    pub allocated_bytes: usize,
+}
+
+struct GpuMeshedChunk {
+    /// Key of the mesh of the chunk in [GpuMesher::meshes]
+    content_hash: u64,
+    position: [f32; 3],
+}
+
+/// Faces of all chunks with the same voxels
+struct SharedFaces {
+    faces_buffer: wgpu::Buffer,
+    draw_buffer: wgpu::Buffer,
+    /// Number of chunks that draw these faces, the buffers are freed when the last one is removed
+    ref_count: usize,
+}
+
+/// Must match the `FaceInstance` struct in `gpu_meshing.wgsl` and `shader.wgsl`
+const FACE_INSTANCE_SIZE: u64 = 3 * mem::size_of::<u32>() as u64;
+
+impl GpuMesher {
+    pub fn is_supported(ctx: &RenderCtx) -> bool {
+        ctx.downlevel_flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION)
+    }
+
+    /// Creates the meshing pipeline and the render pipelines, which draw the face instances with the `vs_face_instance` entry point
+    /// of the chunk shader and the same layout as the per-chunk render path
+    pub fn new(ctx: &RenderCtx, render_pipeline_layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule) -> Self {
+        let meshing_bind_group_layout_entries = [
+            buffer_layout_entry(0, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: true }),
+            buffer_layout_entry(1, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: false }),
+            buffer_layout_entry(2, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: false }),
+        ];
+        let meshing_bind_group_layout = ctx.bind_group_layout("Chunk meshing bind group layout", &meshing_bind_group_layout_entries);
+
+        let meshing_shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("../gpu_meshing.wgsl"));
+        let meshing_pipeline_layout = ctx.pipeline_layout("Chunk meshing pipeline layout", &[&meshing_bind_group_layout_entries], &[]);
+
+        let meshing_pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Chunk meshing pipeline"),
+                layout: Some(&meshing_pipeline_layout),
+                module: &meshing_shader,
+                entry_point: "cs_main",
+            });
+
+        let voxels_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk meshing voxels buffer"),
+            size: (VOXELS_LEN * mem::size_of::<u32>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            meshing_pipeline,
+            meshing_bind_group_layout,
+            voxels_buffer,
+            pipelines: Self::create_pipelines(ctx, render_pipeline_layout, shader),
+            chunks: HashMap::new(),
+            meshes: HashMap::new(),
+            allocated_bytes: 0,
+        }
+    }
+
+    /// Render pipelines of the face instances with another chunk shader, see [GpuMesher::new]
+    pub fn create_pipelines(ctx: &RenderCtx, render_pipeline_layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule) -> ChunkPipelines {
+        ChunkPipelines::new(ctx, render_pipeline_layout, shader, "vs_face_instance", face_instance_layout())
+    }
+
+    /// Meshes a chunk on the GPU, replacing any previous mesh of the same chunk. Chunks without any visible faces are not drawn.
+    /// If another chunk with the same voxels is already meshed, its faces are reused without meshing the chunk again.
+    pub fn insert(&mut self, ctx: &RenderCtx, location: ChunkLocation, chunk_data: &ChunkData, neighbor_chunks: &NeighborChunks) {
+        let voxels = ChunkVoxels::new(chunk_data, neighbor_chunks);
+        if voxels.max_faces == 0 {
+            self.remove(location);
+            return;
+        }
+
+        let content_hash = voxels.content_hash();
+        match self.meshes.get_mut(&content_hash) {
+            Some(mesh) => mesh.ref_count += 1,
+            None => {
+                let mesh = self.mesh(ctx, voxels);
+                self.allocated_bytes += mesh.faces_buffer.size() as usize;
+                self.meshes.insert(content_hash, mesh);
+            }
+        }
+
+        // Removed after the new mesh is referenced, so that remeshing a chunk without changes keeps its buffers
+        self.remove(location);
+        self.chunks.insert(
+            location,
+            GpuMeshedChunk {
+                content_hash,
+                position: location.to_world_location_f32().into(),
+            },
+        );
+    }
+
+    /// Dispatches the meshing shader for the voxels of a chunk
+    fn mesh(&self, ctx: &RenderCtx, ChunkVoxels { words, max_faces }: ChunkVoxels) -> SharedFaces {
+        let faces_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk face instance buffer"),
+            size: max_faces as u64 * FACE_INSTANCE_SIZE,
+            usage: BufferUsages::STORAGE | BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        // Six vertices per face, the faces are counted by the meshing shader
+        let draw_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Chunk face draw buffer"),
+                usage: BufferUsages::STORAGE | BufferUsages::INDIRECT,
+                contents: bytemuck::cast_slice(&[6u32, 0, 0, 0]),
+            });
+
+        let bind_group = ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Chunk meshing bind group"),
+                layout: &self.meshing_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.voxels_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: faces_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: draw_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+        // Every chunk is submitted on its own, as the write of the voxels of the next chunk is only
+        // ordered after the submissions before it
+        ctx.queue
+            .write_buffer(&self.voxels_buffer, 0, bytemuck::cast_slice(&words));
+
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Chunk meshing encoder"),
+            });
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Chunk meshing pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.meshing_pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        let workgroups = CHUNK_SIZE as u32 / MESHING_WORKGROUP_SIZE;
+        compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+        drop(compute_pass);
+        ctx.queue.submit([encoder.finish()]);
+
+        SharedFaces {
+            faces_buffer,
+            draw_buffer,
+            ref_count: 1,
+        }
+    }
+
+    pub fn remove(&mut self, location: ChunkLocation) {
+        let Some(chunk) = self.chunks.remove(&location) else {
+            return;
+        };
+
+        let mesh = self
+            .meshes
+            .get_mut(&chunk.content_hash)
+            .expect("mesh of a chunk to exist while the chunk references it");
+        mesh.ref_count -= 1;
+        if mesh.ref_count == 0 {
+            self.allocated_bytes -= mesh.faces_buffer.size() as usize;
+            self.meshes.remove(&chunk.content_hash);
+        }
+    }
+
+    /// Number of chunks that reuse the faces of another chunk with the same voxels
+    pub fn shared_chunks(&self) -> usize {
+        self.chunks.len() - self.meshes.len()
+    }
+
+    /// All chunks that are meshed on the GPU
+    pub fn locations(&self) -> impl Iterator<Item = ChunkLocation> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera: UniformBinding<'a>,
+        texture_atlas: &'a wgpu::BindGroup,
+        lighting: UniformBinding<'a>,
+        pipeline: &'a wgpu::RenderPipeline,
+        draw_order: &[ChunkLocation],
+    ) {
+        if self.chunks.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(pipeline);
+        camera.bind(render_pass, 0);
+        render_pass.set_bind_group(1, texture_atlas, &[]);
+        lighting.bind(render_pass, 2);
+
+        // The draw order also contains the chunks that are meshed on the CPU
+        for chunk in draw_order
+            .iter()
+            .filter_map(|location| self.chunks.get(location))
+        {
+            let mesh = &self.meshes[&chunk.content_hash];
+            render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::cast_slice(&chunk.position));
+            render_pass.set_vertex_buffer(0, mesh.faces_buffer.slice(..));
+            render_pass.draw_indirect(&mesh.draw_buffer, 0);
+        }
+    }
+}
+
+fn face_instance_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = vertex_attr_array![0 => Uint32, 1 => Uint32, 2 => Uint32];
+
+    wgpu::VertexBufferLayout {
+        array_stride: FACE_INSTANCE_SIZE,
+        attributes: &ATTRIBUTES,
+        step_mode: wgpu::VertexStepMode::Instance,
+    }
+}
+
+/// Number of words of `ChunkVoxels` in `gpu_meshing.wgsl`
+const VOXELS_LEN: usize = MAX_VOXEL_TYPES + PADDED_SIZE.pow(3) + CHUNK_SIZE.pow(3);
+
+/// CPU-side contents of the voxels buffer for a single chunk
+struct ChunkVoxels {
+    words: Vec<u32>,
+    /// Upper bound of the number of faces of the chunk, which is the capacity of its face instance buffer
+    max_faces: u32,
+}
+
+impl ChunkVoxels {
+    fn new(chunk_data: &ChunkData, neighbor_chunks: &NeighborChunks) -> Self {
+        let mut words = vec![0; VOXELS_LEN];
+
+        for ty in VoxelType::iter() {
+            if let Some(tiles) = ty.face_tiles() {
+                words[ty.into_usize()] = (tiles.top as u32 + 1) | (tiles.side as u32 + 1) << 8 | (tiles.bottom as u32 + 1) << 16;
+            }
+        }
+
+        let (grid, colors) = words[MAX_VOXEL_TYPES..].split_at_mut(PADDED_SIZE.pow(3));
+        let mut meshed = 0;
+        let mut transparent = 0;
+
+        for (index, (z, y, x)) in iproduct!(-1..=CHUNK_SIZE as i32, -1..=CHUNK_SIZE as i32, -1..=CHUNK_SIZE as i32).enumerate() {
+            let position = Vector3::new(x, y, z);
+            let chunk_offset = position.map_elem_wise(|c| c.div_euclid(CHUNK_SIZE as i32));
+            let local = LocalChunkLocation::new(position.rem_euclid(CHUNK_SIZE as i32))
+                .try_into_checked()
+                .expect("remainder to be within the chunk");
+
+            let ty = if chunk_offset == Vector3::new(0, 0, 0) {
+                chunk_data.get_voxel(local).ty
+            } else {
+                neighbor_chunks
+                    .get(ChunkLocation::new(chunk_offset))
+                    .get_voxel(local)
+                    .ty
+            };
+
+            // Same voxels as ChunkMeshGenerator::generate_culled_mesh
+            let is_meshed = matches!(ty.shape(), VoxelShape::Cube | VoxelShape::Layer { .. });
+            let lowered = match ty.shape() {
+                VoxelShape::Layer { height } => 8 - height as u32,
+                _ => 0,
+            };
+            grid[index] =
+                ty.into_usize() as u32 | if ty.is_opaque() { OPAQUE } else { 0 } | if is_meshed { MESHED } else { 0 } | lowered << 10;
+
+            if chunk_offset != Vector3::new(0, 0, 0) {
+                continue;
+            }
+
+            if !ty.is_opaque() {
+                transparent += 1;
+            }
+
+            if is_meshed {
+                meshed += 1;
+                let color = voxel_type_to_color_lookup(ty, &local).map(|c| (255.0 * c).to_u8().unwrap() as u32);
+                colors[ChunkData::position_to_index(local)] = color.x << 24 | color.y << 16 | color.z << 8;
+            }
+        }
+
+        // Every face lies between a meshed voxel and a voxel that is not opaque, which is either inside of the chunk
+        // or one of the neighbor voxels along the sides of the chunk
+        let max_faces = (6 * meshed).min(6 * transparent + 6 * CHUNK_SIZE.pow(2) as u32);
+
+        Self { words, max_faces }
+    }
+
+    /// Hash of everything that the faces of the chunk are meshed from, which includes the neighbor voxels along its sides.
+    /// The faces do not depend on the location of the chunk, so chunks with the same hash can share them.
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.words.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cgmath::Vector3;
+    use itertools::iproduct;
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::chunk_renderer::gpu_meshing::ChunkVoxels;
+    use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+    use crate::world::location::{ChunkLocation, LocalChunkLocation};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    #[test]
+    fn test_max_faces_bounds_culled_mesh() {
+        let origin = ChunkLocation::new(Vector3::new(0, 0, 0));
+        let mut chunks: HashMap<ChunkLocation, ChunkData> = iproduct!(-1..=1, -1..=1, -1..=1)
+            .map(|(x, y, z)| {
+                let ty = if y < 0 { VoxelType::Stone } else { VoxelType::Air };
+                (
+                    ChunkLocation::new(Vector3::new(x, y, z)),
+                    ChunkData::new_with_uniform_data(VoxelData::new(ty)),
+                )
+            })
+            .collect();
+
+        // A checkerboard has the most faces per voxel
+        let center = chunks.get_mut(&origin).unwrap();
+        for local in LocalChunkLocation::iter() {
+            if (local.x + local.y + local.z) % 2 == 0 {
+                center.set_voxel_data(local, VoxelData::new(VoxelType::Stone));
+            }
+        }
+
+        let neighbor_chunks = NeighborChunks::new(&origin, |location| chunks.get(location)).unwrap();
+        let faces = ChunkMeshGenerator::generate_culled_mesh(&chunks[&origin], &neighbor_chunks, true).len();
+        let max_faces = ChunkVoxels::new(&chunks[&origin], &neighbor_chunks).max_faces as usize;
+
+        assert!(faces <= max_faces, "{faces} faces exceed the capacity of {max_faces}");
+        assert!(max_faces < 2 * faces);
+
+        let empty = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+        assert_eq!(ChunkVoxels::new(&empty, &neighbor_chunks).max_faces, 0);
+    }
+
+    #[test]
+    fn test_identical_chunks_share_content_hash() {
+        let mut chunks: HashMap<ChunkLocation, ChunkData> = iproduct!(-1..=2, -2..=1, -1..=1)
+            .map(|(x, y, z)| {
+                let ty = if y < 0 { VoxelType::Stone } else { VoxelType::Air };
+                (
+                    ChunkLocation::new(Vector3::new(x, y, z)),
+                    ChunkData::new_with_uniform_data(VoxelData::new(ty)),
+                )
+            })
+            .collect();
+        let content_hash = |chunks: &HashMap<ChunkLocation, ChunkData>, location: ChunkLocation| {
+            let neighbor_chunks = NeighborChunks::new(&location, |location| chunks.get(location)).unwrap();
+            ChunkVoxels::new(&chunks[&location], &neighbor_chunks).content_hash()
+        };
+
+        // Both chunks lie on the same flat surface, so their faces are the same relative to the chunk
+        let origin = ChunkLocation::new(Vector3::new(0, 0, 0));
+        let next = ChunkLocation::new(Vector3::new(1, 0, 0));
+        let surface = content_hash(&chunks, origin);
+        assert_eq!(content_hash(&chunks, next), surface);
+        assert_ne!(content_hash(&chunks, ChunkLocation::new(Vector3::new(0, -1, 0))), surface);
+
+        chunks.get_mut(&next).unwrap().set_voxel_data(
+            LocalChunkLocation::new_unchecked(Vector3::new(4, 0, 4)),
+            VoxelData::new(VoxelType::Stone),
+        );
+        assert_ne!(content_hash(&chunks, next), surface);
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::VecDeque;
+
+use cgmath::{Vector3, Zero};
+use itertools::iproduct;
+
+use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::meshing::voxel_type_to_color_lookup;
+use crate::world::location::LocalChunkLocation;
+use crate::world::CHUNK_SIZE;
+
+/// Voxels along each side of a cell of the [BounceGrid]
+pub const CELL_SIZE: usize = 8;
+pub const CELLS_PER_CHUNK: usize = CHUNK_SIZE / CELL_SIZE;
+const CHUNK_CELLS: usize = CELLS_PER_CHUNK.pow(3);
+
+/// Part of the light that a cell passes on to each of its neighbors while the bounce is flooded through the grid
+const BOUNCE_FALLOFF: f32 = 0.75;
+/// Part of the light that hits a surface that it reflects, before it is tinted by the colors of the surface
+const BOUNCE_STRENGTH: f32 = 0.8;
+/// Bounced light below this is not propagated any further
+const MIN_LIGHT: f32 = 0.01;
+
+/// Average of the voxels in every cell of a chunk, which the [BounceGrid] is assembled from
+#[derive(Clone, Debug)]
+pub struct ChunkCells {
+    /// Fraction of opaque voxels in each cell, see [crate::world::voxel_data::VoxelType::is_opaque]
+    opacity: [f32; CHUNK_CELLS],
+    /// Average color of the opaque voxels in each cell, which tints the light that the cell reflects
+    albedo: [Vector3<f32>; CHUNK_CELLS],
+}
+
+impl ChunkCells {
+    pub fn new(data: &ChunkData) -> Self {
+        let mut cells = Self {
+            opacity: [0.0; CHUNK_CELLS],
+            albedo: [Vector3::zero(); CHUNK_CELLS],
+        };
+        if let ChunkData::UniformType(voxel_data) = data {
+            if voxel_data.ty.is_opaque() {
+                cells.opacity = [1.0; CHUNK_CELLS];
+                cells.albedo =
+                    [voxel_type_to_color_lookup(voxel_data.ty, &LocalChunkLocation::new_unchecked(Vector3::zero())); CHUNK_CELLS];
+            }
+            return cells;
+        }
+
+        for location in LocalChunkLocation::iter() {
+            let ty = data.get_voxel(location).ty;
+            if !ty.is_opaque() {
+                continue;
+            }
+            let cell = cell_index(location.cast::<usize>().unwrap() / CELL_SIZE, CELLS_PER_CHUNK);
+            cells.opacity[cell] += 1.0;
+            cells.albedo[cell] += voxel_type_to_color_lookup(ty, &location);
+        }
+
+        for (opacity, albedo) in cells.opacity.iter_mut().zip(&mut cells.albedo) {
+            if *opacity > 0.0 {
+                *albedo /= *opacity;
+            }
+            *opacity /= CELL_SIZE.pow(3) as f32;
+        }
+        cells
+    }
+}
+
+/// Coarse grid of the voxels around the camera, through which one bounce of the sky light is flood-filled, see
+/// [crate::rendering::light_bounce::LightBounce].
+///
+/// The sky light falls straight down every column of cells and is absorbed by their opaque voxels. Every cell that absorbs light
+/// reflects a part of it, tinted by the colors of its voxels, which then spreads through the open cells around it and fades with every
+/// cell. So caves and overhangs that the sky does not reach are lit softly by the surfaces near their openings.
+/// Cells outside of the chunks that were added are open.
+pub struct BounceGrid {
+    /// Cells along each side of the grid
+    size: usize,
+    opacity: Vec<f32>,
+    albedo: Vec<Vector3<f32>>,
+}
+
+impl BounceGrid {
+    pub fn new(chunks_per_side: usize) -> Self {
+        let size = chunks_per_side * CELLS_PER_CHUNK;
+        Self {
+            size,
+            opacity: vec![0.0; size.pow(3)],
+            albedo: vec![Vector3::zero(); size.pow(3)],
+        }
+    }
+
+    /// Cells along each side of the grid
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Opens all cells again, e.g. before the grid is assembled around another position
+    pub fn clear(&mut self) {
+        self.opacity.fill(0.0);
+        self.albedo.fill(Vector3::zero());
+    }
+
+    /// Copies the cells of a chunk into the grid, where `chunk` is the position of the chunk in chunks from the minimum of the grid
+    pub fn set_chunk(&mut self, chunk: Vector3<usize>, cells: &ChunkCells) {
+        for (x, y, z) in iproduct!(0..CELLS_PER_CHUNK, 0..CELLS_PER_CHUNK, 0..CELLS_PER_CHUNK) {
+            let cell = Vector3::new(x, y, z);
+            let index = cell_index(chunk * CELLS_PER_CHUNK + cell, self.size);
+            self.opacity[index] = cells.opacity[cell_index(cell, CELLS_PER_CHUNK)];
+            self.albedo[index] = cells.albedo[cell_index(cell, CELLS_PER_CHUNK)];
+        }
+    }
+
+    /// Direct sky light together with its bounce in every cell, ordered by z, then y, then x.
+    /// The light of open cells under the sky is 1.
+    pub fn propagate(&self) -> Vec<Vector3<f32>> {
+        let size = self.size;
+        let mut light = vec![Vector3::zero(); size.pow(3)];
+
+        // The sky light that reaches each cell, and the light that the cell reflects from what its voxels absorb
+        let mut bounce = vec![Vector3::zero(); size.pow(3)];
+        for (x, z) in iproduct!(0..size, 0..size) {
+            let mut sky = 1.0;
+            for y in (0..size).rev() {
+                let index = cell_index(Vector3::new(x, y, z), size);
+                light[index] = Vector3::new(sky, sky, sky);
+                bounce[index] = self.albedo[index] * (sky * self.opacity[index] * BOUNCE_STRENGTH);
+                sky *= 1.0 - self.opacity[index];
+            }
+        }
+
+        let mut queue: VecDeque<usize> = (0..size.pow(3))
+            .filter(|&index| max_channel(bounce[index]) > MIN_LIGHT)
+            .collect();
+        while let Some(index) = queue.pop_front() {
+            let cell = Vector3::new(index % size, index / size % size, index / size.pow(2));
+            for neighbor in neighbors(cell, size) {
+                let neighbor_index = cell_index(neighbor, size);
+                let spread = bounce[index] * (BOUNCE_FALLOFF * (1.0 - self.opacity[neighbor_index]));
+                let current = bounce[neighbor_index];
+                if max_channel(spread - current) <= MIN_LIGHT * 0.1 || max_channel(spread) <= MIN_LIGHT {
+                    continue;
+                }
+
+                bounce[neighbor_index] = Vector3::new(current.x.max(spread.x), current.y.max(spread.y), current.z.max(spread.z));
+                queue.push_back(neighbor_index);
+            }
+        }
+
+        for ((light, bounce), opacity) in light.iter_mut().zip(bounce).zip(&self.opacity) {
+            // Opaque cells only reflect their light to their neighbors and are not lit by their own reflection
+            *light += bounce * (1.0 - opacity);
+        }
+        light
+    }
+}
+
+fn cell_index(cell: Vector3<usize>, size: usize) -> usize {
+    (cell.z * size + cell.y) * size + cell.x
+}
+
+fn neighbors(cell: Vector3<usize>, size: usize) -> impl Iterator<Item = Vector3<usize>> {
+    let cell = cell.cast::<isize>().unwrap();
+    [
+        Vector3::new(1, 0, 0),
+        Vector3::new(-1, 0, 0),
+        Vector3::new(0, 1, 0),
+        Vector3::new(0, -1, 0),
+        Vector3::new(0, 0, 1),
+        Vector3::new(0, 0, -1),
+    ]
+    .into_iter()
+    .map(move |offset| cell + offset)
+    .filter(move |neighbor| {
+        [neighbor.x, neighbor.y, neighbor.z]
+            .iter()
+            .all(|&c| (0..size as isize).contains(&c))
+    })
+    .map(|neighbor| neighbor.cast().unwrap())
+}
+
+fn max_channel(color: Vector3<f32>) -> f32 {
+    color.x.max(color.y).max(color.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+    use itertools::iproduct;
+
+    use crate::world::chunk_renderer::light_bounce::{cell_index, BounceGrid, ChunkCells, CELLS_PER_CHUNK, CHUNK_CELLS};
+
+    #[test]
+    fn test_bounce_lights_the_space_under_an_overhang_in_the_color_of_the_floor() {
+        // A green floor, with a roof over the cells at x < 2
+        let mut grid = BounceGrid::new(1);
+        let size = grid.size();
+        for (x, z) in iproduct!(0..size, 0..size) {
+            let floor = cell_index(Vector3::new(x, 0, z), size);
+            grid.opacity[floor] = 1.0;
+            grid.albedo[floor] = Vector3::new(0.2, 0.8, 0.2);
+            if x < 2 {
+                grid.opacity[cell_index(Vector3::new(x, size - 1, z), size)] = 1.0;
+            }
+        }
+
+        let light = grid.propagate();
+        let open = light[cell_index(Vector3::new(size - 1, 1, 0), size)];
+        let under_roof = light[cell_index(Vector3::new(0, 1, 0), size)];
+
+        assert!(open.x >= 1.0, "{open:?}");
+        assert!(under_roof.y > 0.0 && under_roof.y < 1.0, "{under_roof:?}");
+        assert!(under_roof.y > under_roof.x, "{under_roof:?}");
+    }
+
+    /// Opacity and albedo of a cell of a scene of 2 chunks per side, with a floor whose color changes between the chunks,
+    /// a roof that reaches across the chunk border and a pillar that is partly opaque
+    fn scene_cell(cell: Vector3<usize>) -> (f32, Vector3<f32>) {
+        let size = 2 * CELLS_PER_CHUNK;
+        if cell.y == 0 {
+            (1.0, Vector3::new(0.2, 0.3 + 0.5 * (cell.x / CELLS_PER_CHUNK) as f32, 0.2))
+        } else if cell.y == size - 1 && cell.x < CELLS_PER_CHUNK + 1 {
+            (1.0, Vector3::new(0.9, 0.1, 0.1))
+        } else if cell.x == CELLS_PER_CHUNK && cell.z == CELLS_PER_CHUNK - 1 {
+            (0.5, Vector3::new(0.1, 0.1, 0.9))
+        } else {
+            (0.0, Vector3::new(0.0, 0.0, 0.0))
+        }
+    }
+
+    fn scene_chunk_cells(chunk: Vector3<usize>) -> ChunkCells {
+        let mut cells = ChunkCells {
+            opacity: [0.0; CHUNK_CELLS],
+            albedo: [Vector3::new(0.0, 0.0, 0.0); CHUNK_CELLS],
+        };
+        for (x, y, z) in iproduct!(0..CELLS_PER_CHUNK, 0..CELLS_PER_CHUNK, 0..CELLS_PER_CHUNK) {
+            let cell = Vector3::new(x, y, z);
+            let index = cell_index(cell, CELLS_PER_CHUNK);
+            (cells.opacity[index], cells.albedo[index]) = scene_cell(chunk * CELLS_PER_CHUNK + cell);
+        }
+        cells
+    }
+
+    /// Grid of the scene written in one pass, with the cells of all chunks that are not in `chunks` open
+    fn reference_grid(chunks: &[Vector3<usize>]) -> BounceGrid {
+        let mut grid = BounceGrid::new(2);
+        let size = grid.size();
+        for (x, y, z) in iproduct!(0..size, 0..size, 0..size) {
+            let cell = Vector3::new(x, y, z);
+            if chunks.contains(&(cell / CELLS_PER_CHUNK)) {
+                let index = cell_index(cell, size);
+                (grid.opacity[index], grid.albedo[index]) = scene_cell(cell);
+            }
+        }
+        grid
+    }
+
+    fn assert_same_light_at_chunk_borders(grid: &BounceGrid, reference: &BounceGrid) {
+        let size = grid.size();
+        let light = grid.propagate();
+        let reference_light = reference.propagate();
+        let at_border = |c: usize| c % CELLS_PER_CHUNK == 0 || c % CELLS_PER_CHUNK == CELLS_PER_CHUNK - 1;
+        for (x, y, z) in iproduct!(0..size, 0..size, 0..size) {
+            if !(at_border(x) || at_border(y) || at_border(z)) {
+                continue;
+            }
+            let index = cell_index(Vector3::new(x, y, z), size);
+            assert_eq!(light[index], reference_light[index], "cell {x} {y} {z}");
+        }
+    }
+
+    #[test]
+    fn test_bounce_at_chunk_borders_does_not_depend_on_the_order_that_chunks_are_set_in() {
+        let all_chunks = iproduct!(0..2, 0..2, 0..2)
+            .map(|(x, y, z)| Vector3::new(x, y, z))
+            .collect::<Vec<_>>();
+        let reference = reference_grid(&all_chunks);
+
+        let mut reversed = all_chunks.clone();
+        reversed.reverse();
+        let mut rotated = all_chunks.clone();
+        rotated.rotate_left(3);
+        let interleaved = [0, 7, 2, 5, 4, 3, 6, 1].map(|i| all_chunks[i]);
+        for order in [&all_chunks[..], &reversed[..], &rotated[..], &interleaved[..]] {
+            let mut grid = BounceGrid::new(2);
+            for &chunk in order {
+                grid.set_chunk(chunk, &scene_chunk_cells(chunk));
+            }
+            assert_same_light_at_chunk_borders(&grid, &reference);
+        }
+
+        // Chunks that are set again replace their cells completely, e.g. after they were meshed again
+        let mut grid = BounceGrid::new(2);
+        for &chunk in &reversed {
+            grid.set_chunk(chunk, &scene_chunk_cells(Vector3::new(1, 1, 1) - chunk));
+        }
+        for &chunk in &interleaved {
+            grid.set_chunk(chunk, &scene_chunk_cells(chunk));
+        }
+        assert_same_light_at_chunk_borders(&grid, &reference);
+    }
+
+    #[test]
+    fn test_bounce_at_chunk_borders_of_a_partly_filled_grid_matches_a_grid_filled_at_once() {
+        let subsets = [
+            vec![Vector3::new(0, 0, 0), Vector3::new(1, 0, 0)],
+            vec![Vector3::new(1, 1, 0), Vector3::new(0, 0, 0), Vector3::new(0, 1, 1)],
+            vec![Vector3::new(0, 1, 0), Vector3::new(1, 1, 0), Vector3::new(0, 1, 1), Vector3::new(1, 1, 1)],
+            vec![Vector3::new(1, 0, 1), Vector3::new(0, 1, 0)],
+        ];
+
+        // The same grid is cleared and reused for every subset, like it is when the camera moves
+        let mut grid = BounceGrid::new(2);
+        for chunks in &subsets {
+            grid.clear();
+            for &chunk in chunks.iter().rev() {
+                grid.set_chunk(chunk, &scene_chunk_cells(chunk));
+            }
+            assert_same_light_at_chunk_borders(&grid, &reference_grid(chunks));
+        }
+    }
+}
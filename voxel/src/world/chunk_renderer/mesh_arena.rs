@@ -0,0 +1,290 @@
+use std::mem;
+use std::ops::Range;
+
+use log::info;
+use wgpu::BufferUsages;
+
+use crate::rendering::RenderCtx;
+use crate::world::chunk_renderer::vertex::Vertex;
+
+/// Shared vertex and index buffers that store the meshes of many chunks.
+/// Meshes are placed into free ranges of both buffers, so that all of them can be drawn without rebinding buffers.
+/// A full buffer is replaced by a larger copy of itself, up to the maximum buffer size of the device.
+pub struct MeshArena {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    vertices: RangeAllocator,
+    indices: RangeAllocator,
+}
+
+/// The location of a single mesh inside of a [MeshArena]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MeshAllocation {
+    pub vertices: Range<u32>,
+    pub indices: Range<u32>,
+}
+
+impl MeshArena {
+    pub fn new(ctx: &RenderCtx, vertex_capacity: u32, index_capacity: u32) -> Self {
+        Self {
+            vertex_buffer: create_buffer(
+                ctx,
+                VERTEX_BUFFER_LABEL,
+                vertex_capacity,
+                mem::size_of::<Vertex>(),
+                BufferUsages::VERTEX,
+            ),
+            index_buffer: create_buffer(ctx, INDEX_BUFFER_LABEL, index_capacity, mem::size_of::<u32>(), BufferUsages::INDEX),
+            vertices: RangeAllocator::new(vertex_capacity),
+            indices: RangeAllocator::new(index_capacity),
+        }
+    }
+
+    /// Uploads a mesh into the arena, growing it if there is not enough free space left.
+    /// Returns `None` if the mesh does not fit even after growing the arena to the maximum buffer size of the device.
+    pub fn upload(&mut self, ctx: &RenderCtx, vertices: &[Vertex], indices: &[u32]) -> Option<MeshAllocation> {
+        self.upload_with_offset(ctx, vertices, indices, false)
+    }
+
+    /// Uploads a mesh into the arena like [MeshArena::upload], but offsets its indices by the position of its vertices in the arena,
+    /// so that it can be drawn without a base vertex
+    pub fn upload_rebased(&mut self, ctx: &RenderCtx, vertices: &[Vertex], indices: &[u32]) -> Option<MeshAllocation> {
+        self.upload_with_offset(ctx, vertices, indices, true)
+    }
+
+    fn upload_with_offset(&mut self, ctx: &RenderCtx, vertices: &[Vertex], indices: &[u32], rebase: bool) -> Option<MeshAllocation> {
+        let vertex_range = allocate_or_grow(
+            ctx,
+            &mut self.vertex_buffer,
+            &mut self.vertices,
+            VERTEX_BUFFER_LABEL,
+            mem::size_of::<Vertex>(),
+            vertices.len() as u32,
+        )?;
+        let Some(index_range) = allocate_or_grow(
+            ctx,
+            &mut self.index_buffer,
+            &mut self.indices,
+            INDEX_BUFFER_LABEL,
+            mem::size_of::<u32>(),
+            indices.len() as u32,
+        ) else {
+            self.vertices.free(vertex_range);
+            return None;
+        };
+
+        if !vertices.is_empty() {
+            ctx.queue.write_buffer(
+                &self.vertex_buffer,
+                vertex_range.start as u64 * mem::size_of::<Vertex>() as u64,
+                bytemuck::cast_slice(vertices),
+            );
+        }
+        if !indices.is_empty() {
+            let rebased: Vec<u32>;
+            let indices = if rebase {
+                rebased = indices
+                    .iter()
+                    .map(|index| index + vertex_range.start)
+                    .collect();
+                &rebased[..]
+            } else {
+                indices
+            };
+
+            ctx.queue.write_buffer(
+                &self.index_buffer,
+                index_range.start as u64 * mem::size_of::<u32>() as u64,
+                bytemuck::cast_slice(indices),
+            );
+        }
+
+        Some(MeshAllocation {
+            vertices: vertex_range,
+            indices: index_range,
+        })
+    }
+
+    pub fn free(&mut self, allocation: MeshAllocation) {
+        self.vertices.free(allocation.vertices);
+        self.indices.free(allocation.indices);
+    }
+
+    /// Number of vertices and indices that are currently in use
+    pub fn used(&self) -> (u32, u32) {
+        (self.vertices.used(), self.indices.used())
+    }
+}
+
+const VERTEX_BUFFER_LABEL: &str = "Chunk mesh arena vertex buffer";
+const INDEX_BUFFER_LABEL: &str = "Chunk mesh arena index buffer";
+
+fn create_buffer(ctx: &RenderCtx, label: &str, capacity: u32, element_size: usize, usage: BufferUsages) -> wgpu::Buffer {
+    ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: capacity as u64 * element_size as u64,
+        // Copied from when the buffer is replaced by a larger one
+        usage: usage | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+/// Allocates `size` elements of the buffer. If they do not fit, the buffer is first replaced by a larger copy of itself,
+/// with twice its capacity or as much as the device allows.
+fn allocate_or_grow(
+    ctx: &RenderCtx,
+    buffer: &mut wgpu::Buffer,
+    allocator: &mut RangeAllocator,
+    label: &str,
+    element_size: usize,
+    size: u32,
+) -> Option<Range<u32>> {
+    if let Some(range) = allocator.allocate(size) {
+        return Some(range);
+    }
+
+    let max_capacity = (ctx.device.limits().max_buffer_size / element_size as u64).min(u32::MAX as u64) as u32;
+    let capacity = allocator
+        .capacity
+        .saturating_mul(2)
+        .max(allocator.capacity.saturating_add(size))
+        .min(max_capacity);
+    if capacity < allocator.capacity.saturating_add(size) {
+        return None;
+    }
+
+    let grown = create_buffer(ctx, label, capacity, element_size, buffer.usage());
+    let mut encoder = ctx
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Chunk mesh arena grow encoder"),
+        });
+    encoder.copy_buffer_to_buffer(buffer, 0, &grown, 0, buffer.size());
+    // Writes to the old buffer that are still queued are executed before this submission, so they are copied too
+    ctx.queue.submit([encoder.finish()]);
+    info!("Grew the {} to {} MiB", label.to_lowercase(), grown.size() / (1024 * 1024));
+
+    *buffer = grown;
+    allocator.grow(capacity);
+    allocator.allocate(size)
+}
+
+/// First-fit allocator for ranges of elements inside of a buffer
+struct RangeAllocator {
+    capacity: u32,
+    /// Free ranges sorted by their start, never adjacent to each other
+    free_ranges: Vec<Range<u32>>,
+}
+
+impl RangeAllocator {
+    fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            free_ranges: vec![0..capacity],
+        }
+    }
+
+    fn allocate(&mut self, size: u32) -> Option<Range<u32>> {
+        if size == 0 {
+            return Some(0..0);
+        }
+
+        let idx = self
+            .free_ranges
+            .iter()
+            .position(|range| range.len() as u32 >= size)?;
+
+        let free_range = &mut self.free_ranges[idx];
+        let allocated = free_range.start..(free_range.start + size);
+        free_range.start += size;
+
+        if free_range.start == free_range.end {
+            self.free_ranges.remove(idx);
+        }
+
+        Some(allocated)
+    }
+
+    fn free(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+
+        let idx = self
+            .free_ranges
+            .partition_point(|free_range| free_range.start < range.start);
+        self.free_ranges.insert(idx, range);
+
+        // Merge with the following range
+        if idx + 1 < self.free_ranges.len() && self.free_ranges[idx].end == self.free_ranges[idx + 1].start {
+            let next = self.free_ranges.remove(idx + 1);
+            self.free_ranges[idx].end = next.end;
+        }
+
+        // Merge with the preceding range
+        if idx > 0 && self.free_ranges[idx - 1].end == self.free_ranges[idx].start {
+            let current = self.free_ranges.remove(idx);
+            self.free_ranges[idx - 1].end = current.end;
+        }
+    }
+
+    /// Adds free elements at the end, up to the new capacity
+    fn grow(&mut self, capacity: u32) {
+        let added = self.capacity..capacity;
+        self.capacity = capacity;
+        self.free(added);
+    }
+
+    fn used(&self) -> u32 {
+        self.capacity
+            - self
+                .free_ranges
+                .iter()
+                .map(|range| range.len() as u32)
+                .sum::<u32>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::chunk_renderer::mesh_arena::RangeAllocator;
+
+    #[test]
+    fn test_range_allocator() {
+        let mut allocator = RangeAllocator::new(100);
+
+        let a = allocator.allocate(30).unwrap();
+        let b = allocator.allocate(30).unwrap();
+        let c = allocator.allocate(30).unwrap();
+        assert_eq!((a.clone(), b.clone(), c.clone()), (0..30, 30..60, 60..90));
+        assert_eq!(allocator.allocate(20), None);
+        assert_eq!(allocator.used(), 90);
+
+        allocator.free(b);
+        assert_eq!(allocator.allocate(40), None);
+
+        allocator.free(a);
+        assert_eq!(allocator.free_ranges, vec![0..60, 90..100]);
+
+        allocator.free(c);
+        assert_eq!(allocator.free_ranges, vec![0..100]);
+        assert_eq!(allocator.used(), 0);
+
+        assert_eq!(allocator.allocate(100), Some(0..100));
+    }
+
+    #[test]
+    fn test_range_allocator_grow() {
+        let mut allocator = RangeAllocator::new(100);
+
+        let a = allocator.allocate(60).unwrap();
+        allocator.allocate(30).unwrap();
+        allocator.free(a);
+        assert_eq!(allocator.allocate(70), None);
+
+        allocator.grow(200);
+        assert_eq!(allocator.free_ranges, vec![0..60, 90..200]);
+        assert_eq!(allocator.allocate(70), Some(90..160));
+        assert_eq!(allocator.used(), 100);
+    }
+}
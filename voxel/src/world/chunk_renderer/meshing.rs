@@ -13,16 +13,21 @@ use itertools::iproduct;
 use lazy_static::lazy_static;
 use strum::IntoEnumIterator;
 
+use crate::accessibility::ColorPalette;
 use crate::vector_utils::{AbsValue, RemEuclid};
 use crate::world::chunk_data::ChunkData;
 use crate::world::chunk_renderer::meshing::direction::Direction;
 use crate::world::chunk_renderer::meshing::quad::{FaceData, Quad};
 use crate::world::chunk_renderer::vertex::Vertex;
 use crate::world::location::{ChunkLocation, LocalChunkLocation, WithinBounds, WorldLocation};
-use crate::world::voxel_data::VoxelType;
+use crate::world::voxel_data::{VoxelShape, VoxelType};
 use crate::world::CHUNK_SIZE;
 
 pub mod direction;
+pub mod greedy;
+pub mod heightmap_skin;
+pub mod lod;
+pub mod marching_cubes;
 pub mod quad;
 
 pub struct ChunkMeshGenerator {
@@ -37,7 +42,6 @@ impl ChunkMeshGenerator {
         for quad in quads {
             let base_index = vertices.len() as u32;
 
-            let mut pos = quad.position.to_f32();
             let direction = quad
                 .direction
                 .to_vec()
@@ -45,38 +49,31 @@ impl ChunkMeshGenerator {
                 .expect("Conversion from i32 to f32 is safe")
                 .abs();
 
-            let (axis1, axis2) = quad.direction.get_normal_axes();
-            let (axis1, axis2) = (axis1.cast::<f32>().unwrap().abs(), axis2.cast::<f32>().unwrap().abs());
-
             let is_backside = match quad.direction {
                 Direction::XPos | Direction::YPos | Direction::ZPos => false,
                 Direction::XNeg | Direction::YNeg | Direction::ZNeg => true,
             };
 
-            if !is_backside {
-                pos += direction;
+            // Only the vertices at the top of the voxel are lowered, so that partial voxels keep their bottom
+            let voxel_top = quad.position.y + 1;
+            for (corner, position) in quad.corners().into_iter().enumerate() {
+                let lowered = if position.y >= voxel_top { quad.data.lowered } else { 0 };
+                let mut vertex = Vertex::new(
+                    position
+                        .cast::<f32>()
+                        .expect("Conversion from i32 to f32 is safe"),
+                    quad.data.color,
+                    direction,
+                    quad.ambient_occlusion_values[corner],
+                )
+                .lowered(lowered)
+                .textured(quad.data.texture_tile);
+                if let Some(smooth_normals) = quad.smooth_normals {
+                    vertex = vertex.smoothed(smooth_normals[corner]);
+                }
+                vertices.push(vertex);
             }
 
-            vertices.push(Vertex::new(pos, quad.data.color, direction, quad.ambient_occlusion_values[0]));
-            vertices.push(Vertex::new(
-                pos + axis1,
-                quad.data.color,
-                direction,
-                quad.ambient_occlusion_values[1],
-            ));
-            vertices.push(Vertex::new(
-                pos + axis2,
-                quad.data.color,
-                direction,
-                quad.ambient_occlusion_values[2],
-            ));
-            vertices.push(Vertex::new(
-                pos + axis1 + axis2,
-                quad.data.color,
-                direction,
-                quad.ambient_occlusion_values[3],
-            ));
-
             {
                 if is_backside && quad.reversed_orientation {
                     [0, 1, 2, 2, 1, 3]
@@ -95,12 +92,88 @@ impl ChunkMeshGenerator {
         (vertices, indices)
     }
 
-    pub fn generate_culled_mesh(data: &ChunkData, neighbor_chunks: NeighborChunks) -> Vec<Quad> {
+    /// Faces of all solid and partial voxels that are not covered by a solid voxel or by a partial voxel of the same type.
+    /// Without ambient occlusion, all corners are fully lit and the voxels around the corners are not looked up.
+    pub fn generate_culled_mesh(data: &ChunkData, neighbor_chunks: &NeighborChunks, ambient_occlusion: bool) -> Vec<Quad> {
+        Self::generate_faces(
+            data,
+            neighbor_chunks,
+            |ty| matches!(ty.shape(), VoxelShape::Cube | VoxelShape::Layer { .. }),
+            |ty, neighbor| !neighbor.is_opaque() && neighbor != ty,
+            ambient_occlusion,
+        )
+    }
+
+    /// Faces of all translucent voxels that are not covered by an opaque voxel or by a voxel of the same type,
+    /// which are rendered in the transparent pass after the water surfaces
+    pub fn generate_translucent_mesh(data: &ChunkData, neighbor_chunks: &NeighborChunks) -> Vec<Quad> {
+        Self::generate_faces(
+            data,
+            neighbor_chunks,
+            |ty| ty.shape() == VoxelShape::Translucent,
+            |ty, neighbor| !neighbor.is_opaque() && neighbor != ty,
+            true,
+        )
+    }
+
+    /// Faces of all water voxels that border air, which are rendered in the transparent water pass
+    pub fn generate_water_mesh(data: &ChunkData, neighbor_chunks: &NeighborChunks) -> Vec<Quad> {
+        Self::generate_faces(
+            data,
+            neighbor_chunks,
+            |ty| ty == VoxelType::Water,
+            |_, neighbor| neighbor == VoxelType::Air,
+            true,
+        )
+    }
+
+    /// Averages a normal for every corner of the quads from the opaque voxels around it, so that the light changes smoothly
+    /// across the edges of blocky terrain without changing its geometry.
+    ///
+    /// The normal points away from the opaque voxels among the eight voxels that touch the corner and is rounded to the directions
+    /// towards the 26 neighbors of a voxel. Corners whose normal would not point out of their face keep the normal of the face.
+    pub fn smooth_normals(quads: &mut [Quad], data: &ChunkData, neighbor_chunks: &NeighborChunks) {
+        for quad in quads {
+            let face_normal = quad.direction.to_vec();
+
+            quad.smooth_normals = Some(quad.corners().map(|corner| {
+                let mut gradient = Vector3::zero();
+                for (dx, dy, dz) in iproduct!(-1..=0, -1..=0, -1..=0) {
+                    let offset = Vector3::new(dx, dy, dz);
+                    if !lod::voxel_at(data, neighbor_chunks, corner + offset).is_opaque() {
+                        gradient += offset * 2 + Vector3::from_value(1);
+                    }
+                }
+
+                let normal = gradient.map(i32::signum);
+                if normal.mul_element_wise(face_normal).sum() > 0 {
+                    normal
+                } else {
+                    face_normal
+                }
+            }));
+        }
+    }
+
+    /// Faces of all voxels for which `is_meshed` is true, towards the neighbors for which `is_visible_against(voxel, neighbor)` is true
+    fn generate_faces(
+        data: &ChunkData,
+        neighbor_chunks: &NeighborChunks,
+        is_meshed: impl Fn(VoxelType) -> bool,
+        is_visible_against: impl Fn(VoxelType, VoxelType) -> bool,
+        ambient_occlusion: bool,
+    ) -> Vec<Quad> {
         let mut quads = Vec::new();
 
         LocalChunkLocation::iter()
-            .filter(|&pos| data.get_voxel(pos).ty != VoxelType::Air)
+            .filter(|&pos| is_meshed(data.get_voxel(pos).ty))
             .for_each(|pos| {
+                let ty = data.get_voxel(pos).ty;
+                let lowered = match ty.shape() {
+                    VoxelShape::Layer { height } => 8 - height,
+                    _ => 0,
+                };
+
                 for dir in Direction::iter() {
                     let neighbor_voxel_location = pos + dir;
                     let (mut axis1, mut axis2) = dir.get_normal_axes();
@@ -146,9 +219,15 @@ impl ChunkMeshGenerator {
                     };
 
                     let calc_ao = |dir1: Vector3<i32>, dir2: Vector3<i32>| {
-                        let s1 = get_voxel_in_world(neighbor_voxel_location + dir1).ty != VoxelType::Air;
-                        let s2 = get_voxel_in_world(neighbor_voxel_location + dir2).ty != VoxelType::Air;
-                        let c = get_voxel_in_world(neighbor_voxel_location + dir1 + dir2).ty != VoxelType::Air;
+                        let s1 = get_voxel_in_world(neighbor_voxel_location + dir1)
+                            .ty
+                            .is_opaque();
+                        let s2 = get_voxel_in_world(neighbor_voxel_location + dir2)
+                            .ty
+                            .is_opaque();
+                        let c = get_voxel_in_world(neighbor_voxel_location + dir1 + dir2)
+                            .ty
+                            .is_opaque();
 
                         if s1 && s2 {
                             0.0
@@ -157,10 +236,16 @@ impl ChunkMeshGenerator {
                         }
                     };
 
-                    let ao_1 = calc_ao(axis1.neg(), axis2.neg());
-                    let ao_2 = calc_ao(axis1, axis2.neg());
-                    let ao_3 = calc_ao(axis1.neg(), axis2);
-                    let ao_4 = calc_ao(axis1, axis2);
+                    let [ao_1, ao_2, ao_3, ao_4] = if ambient_occlusion {
+                        [
+                            calc_ao(axis1.neg(), axis2.neg()),
+                            calc_ao(axis1, axis2.neg()),
+                            calc_ao(axis1.neg(), axis2),
+                            calc_ao(axis1, axis2),
+                        ]
+                    } else {
+                        [3.0; 4]
+                    };
 
                     let reverse_quad_orientation = ao_1 + ao_4 <= ao_2 + ao_3;
                     // let reverse_quad_orientation = false;
@@ -168,13 +253,17 @@ impl ChunkMeshGenerator {
                     let quad = Quad::new(
                         pos,
                         dir,
-                        FaceData::new(voxel_type_to_color_lookup(data.get_voxel(pos).ty, &pos)),
+                        FaceData::new(
+                            voxel_type_to_color_lookup(ty, &pos),
+                            lowered,
+                            ty.face_tiles().map(|tiles| tiles.tile(dir)),
+                        ),
                         [ao_1, ao_2, ao_3, ao_4],
                         reverse_quad_orientation,
                     );
 
                     if let Some(same_chunk_neighbor) = neighbor_voxel_location.try_into_checked() {
-                        if data.get_voxel(same_chunk_neighbor).ty == VoxelType::Air {
+                        if is_visible_against(ty, data.get_voxel(same_chunk_neighbor).ty) {
                             quads.push(quad);
                         }
                     } else {
@@ -184,7 +273,7 @@ impl ChunkMeshGenerator {
                             .try_into_checked()
                             .expect("aa");
 
-                        if chunk.get_voxel(neighbor_local).ty == VoxelType::Air {
+                        if is_visible_against(ty, chunk.get_voxel(neighbor_local).ty) {
                             quads.push(quad);
                         }
                     }
@@ -244,6 +333,14 @@ lazy_static! {
         VoxelType::Dirt => generate_voxel_type_map(VoxelType::Dirt),
         VoxelType::Grass => generate_voxel_type_map(VoxelType::Grass),
         VoxelType::Stone => generate_voxel_type_map(VoxelType::Stone),
+        VoxelType::Sand => generate_voxel_type_map(VoxelType::Sand),
+        VoxelType::Water => generate_voxel_type_map(VoxelType::Water),
+        VoxelType::Snow => generate_voxel_type_map(VoxelType::Snow),
+        VoxelType::Ice => generate_voxel_type_map(VoxelType::Ice),
+        VoxelType::Glass => generate_voxel_type_map(VoxelType::Glass),
+        VoxelType::Log => generate_voxel_type_map(VoxelType::Log),
+        VoxelType::Leaves => generate_voxel_type_map(VoxelType::Leaves),
+        VoxelType::Error => generate_voxel_type_map(VoxelType::Error),
     };
 }
 
@@ -261,15 +358,18 @@ fn generate_voxel_type_map(voxel_type: VoxelType) -> Vec<Vector3<f32>> {
         .collect()
 }
 
-fn voxel_type_to_color_lookup(ty: VoxelType, local_voxel_position: &LocalChunkLocation<WithinBounds>) -> Vector3<f32> {
-    VOXEL_TYPE_RAND_MAP[ty]
+/// Color of a voxel of the given type at the given position, in the active [ColorPalette]
+pub(super) fn voxel_type_to_color_lookup(ty: VoxelType, local_voxel_position: &LocalChunkLocation<WithinBounds>) -> Vector3<f32> {
+    let color = VOXEL_TYPE_RAND_MAP[ty]
         .get(
             local_voxel_position.x as usize * CHUNK_SIZE * CHUNK_SIZE
                 + local_voxel_position.y as usize * CHUNK_SIZE
                 + local_voxel_position.z as usize,
         )
         .unwrap()
-        .clone()
+        .clone();
+
+    ColorPalette::active().apply(color)
 }
 
 fn voxel_type_to_color(ty: VoxelType, voxel_position: WorldLocation) -> Vector3<f32> {
@@ -282,6 +382,14 @@ fn voxel_type_to_color(ty: VoxelType, voxel_position: WorldLocation) -> Vector3<
         VoxelType::Dirt => Vector3::new(rand(&mut rng, 0.12..0.18), rand(&mut rng, 0.06..0.14), 0.02),
         VoxelType::Grass => Vector3::new(rand(&mut rng, 0.07..0.11), rand(&mut rng, 0.28..0.32), rand(&mut rng, 0.01..0.04)),
         VoxelType::Stone => v(rand(&mut rng, 0.25..0.35)),
+        VoxelType::Sand => Vector3::new(rand(&mut rng, 0.52..0.58), rand(&mut rng, 0.44..0.48), rand(&mut rng, 0.24..0.28)),
+        VoxelType::Water => Vector3::new(0.02, rand(&mut rng, 0.09..0.11), rand(&mut rng, 0.28..0.3)),
+        VoxelType::Snow => v(rand(&mut rng, 0.86..0.9)),
+        VoxelType::Ice => Vector3::new(rand(&mut rng, 0.52..0.56), rand(&mut rng, 0.66..0.7), 0.82),
+        VoxelType::Glass => Vector3::new(0.78, rand(&mut rng, 0.86..0.88), 0.9),
+        VoxelType::Log => Vector3::new(rand(&mut rng, 0.2..0.24), rand(&mut rng, 0.11..0.14), 0.05),
+        VoxelType::Leaves => Vector3::new(rand(&mut rng, 0.03..0.06), rand(&mut rng, 0.18..0.25), 0.03),
+        VoxelType::Error => Vector3::new(1.0, 0.0, 1.0),
     }
 }
 
@@ -293,3 +401,122 @@ fn v(f: f32) -> Vector3<f32> {
 fn rand(rng: &mut Rng, range: Range<f32>) -> f32 {
     rng.f32() * (range.end - range.start) + range.start
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cgmath::Vector3;
+    use itertools::{iproduct, Itertools};
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+    use crate::world::location::{ChunkLocation, LocalChunkLocation, WorldLocation};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    /// A floor with a pillar and an overhang, so that its faces have all kinds of ambient occlusion
+    fn is_scene_voxel(position: Vector3<i32>) -> bool {
+        let Vector3 { x, y, z } = position;
+        let floor = y == 0 && (0..5).contains(&x) && (0..5).contains(&z);
+        let pillar = x == 2 && z == 2 && (1..4).contains(&y);
+        let overhang = y == 3 && x == 3 && (1..4).contains(&z);
+
+        floor || pillar || overhang
+    }
+
+    /// Faces of the scene placed at `origin` with their position relative to the origin, direction and ambient occlusion
+    fn scene_faces(origin: Vector3<i32>, ambient_occlusion: bool) -> Vec<(Vector3<i32>, usize, [u8; 4])> {
+        let chunks: HashMap<ChunkLocation, ChunkData> = iproduct!(-1..=2, -1..=2, -1..=2)
+            .map(|(x, y, z)| {
+                let chunk_location = ChunkLocation::new(Vector3::new(x, y, z));
+                let mut data = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+                for local in LocalChunkLocation::iter() {
+                    if is_scene_voxel(WorldLocation::new(chunk_location, local.into_unknown()).0 - origin) {
+                        data.set_voxel_data(local, VoxelData::new(VoxelType::Stone));
+                    }
+                }
+                (chunk_location, data)
+            })
+            .collect();
+
+        iproduct!(0..=1, 0..=1, 0..=1)
+            .flat_map(|(x, y, z)| {
+                let location = ChunkLocation::new(Vector3::new(x, y, z));
+                let neighbor_chunks = NeighborChunks::new(&location, |location| chunks.get(location)).unwrap();
+
+                ChunkMeshGenerator::generate_culled_mesh(&chunks[&location], &neighbor_chunks, ambient_occlusion)
+                    .into_iter()
+                    .map(move |quad| {
+                        (
+                            WorldLocation::new(location, quad.position.into_unknown()).0 - origin,
+                            quad.direction as usize,
+                            quad.ambient_occlusion_values.map(|ao| ao as u8),
+                        )
+                    })
+            })
+            .sorted_by_key(|&(position, direction, ao)| (position.x, position.y, position.z, direction, ao))
+            .collect()
+    }
+
+    #[test]
+    fn test_ambient_occlusion_across_chunk_borders() {
+        let inside_one_chunk = scene_faces(Vector3::new(8, 8, 8), true);
+        assert!(!inside_one_chunk.is_empty());
+
+        // The scene crosses the borders between all eight chunks around the corner at (32, 32, 32)
+        for origin in [Vector3::new(30, 30, 30), Vector3::new(31, 29, 28), Vector3::new(29, 31, 31)] {
+            assert_eq!(
+                scene_faces(origin, true),
+                inside_one_chunk,
+                "the faces of the scene at {origin:?} differ from the faces inside of a single chunk"
+            );
+        }
+    }
+
+    #[test]
+    fn test_smooth_normals_of_single_voxel_point_away_from_it() {
+        let mut data = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+        let voxel = LocalChunkLocation::new_unchecked(Vector3::new(4, 4, 4));
+        data.set_voxel_data(voxel, VoxelData::new(VoxelType::Stone));
+        let air = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+        let neighbor_chunks = NeighborChunks::new(&ChunkLocation::new(Vector3::new(0, 0, 0)), |_| Some(&air)).unwrap();
+
+        let mut quads = ChunkMeshGenerator::generate_culled_mesh(&data, &neighbor_chunks, true);
+        ChunkMeshGenerator::smooth_normals(&mut quads, &data, &neighbor_chunks);
+
+        assert_eq!(quads.len(), 6);
+        for quad in quads {
+            for (corner, normal) in quad
+                .corners()
+                .into_iter()
+                .zip(quad.smooth_normals.unwrap())
+            {
+                // Every corner of the voxel points diagonally away from its center at (4.5, 4.5, 4.5)
+                assert_eq!(normal, (corner * 2 - Vector3::new(9, 9, 9)).map(i32::signum));
+            }
+        }
+    }
+
+    #[test]
+    fn test_faces_without_ambient_occlusion_are_fully_lit() {
+        let with_ambient_occlusion = scene_faces(Vector3::new(30, 30, 30), true);
+        let without_ambient_occlusion = scene_faces(Vector3::new(30, 30, 30), false);
+
+        assert!(with_ambient_occlusion
+            .iter()
+            .any(|&(_, _, ao)| ao != [3; 4]));
+        assert!(without_ambient_occlusion
+            .iter()
+            .all(|&(_, _, ao)| ao == [3; 4]));
+        assert_eq!(
+            without_ambient_occlusion
+                .iter()
+                .map(|&(position, direction, _)| (position, direction))
+                .collect::<Vec<_>>(),
+            with_ambient_occlusion
+                .iter()
+                .map(|&(position, direction, _)| (position, direction))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use cgmath::Vector3;
 use strum_macros::EnumIter;
 
-#[derive(EnumIter, Copy, Clone, Debug)]
+#[derive(EnumIter, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     XPos,
     XNeg,
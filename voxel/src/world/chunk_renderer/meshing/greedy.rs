@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+use itertools::iproduct;
+
+use crate::vector_utils::AbsValue;
+use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::meshing::direction::Direction;
+use crate::world::chunk_renderer::meshing::quad::Quad;
+use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+use crate::world::voxel_data::VoxelType;
+use crate::world::CHUNK_SIZE;
+
+impl ChunkMeshGenerator {
+    /// Generates the same faces as [ChunkMeshGenerator::generate_culled_mesh], but merges neighboring faces of the same voxel type
+    /// and ambient occlusion into larger quads, so that large flat areas only need a few quads.
+    ///
+    /// Merged quads take the color of their first voxel. Faces of partial voxels are never merged, as only their top is lowered.
+    pub fn generate_greedy_mesh(data: &ChunkData, neighbor_chunks: &NeighborChunks, ambient_occlusion: bool) -> Vec<Quad> {
+        let faces = Self::generate_culled_mesh(data, neighbor_chunks, ambient_occlusion);
+        let mut quads = Vec::with_capacity(faces.len() / 4);
+
+        // Every slice of faces with the same direction is a grid of CHUNK_SIZE x CHUNK_SIZE faces along the normal axes of the direction
+        let mut slices: HashMap<(Direction, i32), Vec<Option<Quad>>> = HashMap::new();
+        for face in faces {
+            if face.data.lowered != 0 {
+                quads.push(face);
+                continue;
+            }
+
+            let (slice, u, v) = face_coordinates(&face);
+            slices
+                .entry((face.direction, slice))
+                .or_insert_with(|| {
+                    (0..CHUNK_SIZE * CHUNK_SIZE)
+                        .map(|_| None)
+                        .collect()
+                })[u * CHUNK_SIZE + v] = Some(face);
+        }
+
+        let merge_key = |quad: &Quad| -> (VoxelType, [f32; 4]) { (data.get_voxel(quad.position).ty, quad.ambient_occlusion_values) };
+
+        for mut grid in slices.into_values() {
+            for (u, v) in iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE) {
+                let Some(mut quad) = grid[u * CHUNK_SIZE + v].take() else {
+                    continue;
+                };
+
+                let key = merge_key(&quad);
+                let mergeable = |grid: &[Option<Quad>], u: usize, v: usize| {
+                    grid[u * CHUNK_SIZE + v]
+                        .as_ref()
+                        .is_some_and(|other| merge_key(other) == key)
+                };
+
+                // Grow along the first axis as far as possible, then add whole rows along the second axis
+                let mut width = 1;
+                while u + width < CHUNK_SIZE && mergeable(&grid, u + width, v) {
+                    width += 1;
+                }
+                let mut height = 1;
+                while v + height < CHUNK_SIZE && (u..u + width).all(|u| mergeable(&grid, u, v + height)) {
+                    height += 1;
+                }
+
+                for (u, v) in iproduct!(u..u + width, v..v + height) {
+                    grid[u * CHUNK_SIZE + v] = None;
+                }
+
+                quad.size = [width as i32, height as i32];
+                quads.push(quad);
+            }
+        }
+
+        quads
+    }
+}
+
+/// Position of a face along the axis of its direction and along the two normal axes of its direction
+fn face_coordinates(quad: &Quad) -> (i32, usize, usize) {
+    let (axis1, axis2) = quad.direction.get_normal_axes();
+    let along = |axis: Vector3<i32>| quad.position.mul_element_wise(axis.abs()).sum();
+
+    (along(quad.direction.to_vec()), along(axis1) as usize, along(axis2) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+    use crate::world::location::ChunkLocation;
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+    use crate::world::CHUNK_SIZE;
+
+    #[test]
+    fn test_greedy_mesh_merges_flat_faces() {
+        let stone = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+        let air = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+        let neighbor_chunks = NeighborChunks::new(&ChunkLocation::new(Vector3::new(0, 0, 0)), |_| Some(&air)).unwrap();
+
+        let quads = ChunkMeshGenerator::generate_greedy_mesh(&stone, &neighbor_chunks, true);
+
+        assert_eq!(quads.len(), 6, "every side of a solid chunk is a single quad");
+        assert!(quads
+            .iter()
+            .all(|quad| quad.size == [CHUNK_SIZE as i32; 2]));
+    }
+}
@@ -0,0 +1,115 @@
+use cgmath::Vector3;
+use itertools::iproduct;
+
+use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::meshing::{voxel_type_to_color_lookup, ChunkMeshGenerator, NeighborChunks};
+use crate::world::chunk_renderer::vertex::Vertex;
+use crate::world::location::{ChunkLocation, LocalChunkLocation, WithinBounds};
+use crate::world::voxel_data::VoxelType;
+use crate::world::CHUNK_SIZE;
+
+/// Number of voxel columns along each horizontal axis that are merged into a single skin patch
+pub const SKIN_PATCH_SIZE: usize = 4;
+const PATCHES_PER_AXIS: usize = CHUNK_SIZE / SKIN_PATCH_SIZE;
+
+/// Highest exposed voxel surface inside of a patch
+#[derive(Copy, Clone)]
+struct PatchSurface {
+    /// Height of the top face of the surface voxel
+    height: u8,
+    voxel: LocalChunkLocation<WithinBounds>,
+    ty: VoxelType,
+}
+
+impl ChunkMeshGenerator {
+    /// Generates a heightmap skin for distant chunks.
+    ///
+    /// Instead of voxel faces, every patch of [SKIN_PATCH_SIZE]x[SKIN_PATCH_SIZE] columns is represented by two triangles
+    /// that are placed at the highest exposed surface of the patch. Neighboring patches share their corner heights,
+    /// so the skin is closed inside of a chunk.
+    pub fn generate_heightmap_skin(data: &ChunkData, neighbor_chunks: NeighborChunks) -> (Vec<Vertex>, Vec<u32>) {
+        let chunk_above = neighbor_chunks.get(ChunkLocation::new(Vector3::new(0, 1, 0)));
+
+        let mut patches = [[None; PATCHES_PER_AXIS]; PATCHES_PER_AXIS];
+        for (px, pz) in iproduct!(0..PATCHES_PER_AXIS, 0..PATCHES_PER_AXIS) {
+            patches[px][pz] = patch_surface(data, chunk_above, px, pz);
+        }
+
+        // Every corner takes the height of the highest adjacent patch
+        let corner_height = |cx: usize, cz: usize| {
+            iproduct!(
+                cx.saturating_sub(1)..(cx + 1).min(PATCHES_PER_AXIS),
+                cz.saturating_sub(1)..(cz + 1).min(PATCHES_PER_AXIS)
+            )
+            .filter_map(|(px, pz)| patches[px][pz].map(|surface: PatchSurface| surface.height))
+            .max()
+            .unwrap_or(0)
+        };
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for (px, pz) in iproduct!(0..PATCHES_PER_AXIS, 0..PATCHES_PER_AXIS) {
+            let Some(surface) = patches[px][pz] else {
+                continue;
+            };
+
+            let color = voxel_type_to_color_lookup(surface.ty, &surface.voxel);
+            let up = Vector3::new(0.0, 1.0, 0.0);
+            let base_index = vertices.len() as u32;
+
+            for (cx, cz) in [(px, pz), (px + 1, pz), (px, pz + 1), (px + 1, pz + 1)] {
+                let position = Vector3::new(
+                    (cx * SKIN_PATCH_SIZE) as f32,
+                    corner_height(cx, cz) as f32,
+                    (cz * SKIN_PATCH_SIZE) as f32,
+                );
+                vertices.push(Vertex::new(position, color, up, 3.0));
+            }
+
+            [0, 2, 1, 1, 2, 3]
+                .iter()
+                .for_each(|i| indices.push(base_index + i));
+        }
+
+        (vertices, indices)
+    }
+}
+
+fn patch_surface(data: &ChunkData, chunk_above: &ChunkData, px: usize, pz: usize) -> Option<PatchSurface> {
+    iproduct!(0..SKIN_PATCH_SIZE, 0..SKIN_PATCH_SIZE)
+        .filter_map(|(dx, dz)| {
+            column_surface(
+                data,
+                chunk_above,
+                (px * SKIN_PATCH_SIZE + dx) as i32,
+                (pz * SKIN_PATCH_SIZE + dz) as i32,
+            )
+        })
+        .max_by_key(|surface| surface.height)
+}
+
+/// Finds the highest solid voxel of a column that has air above it
+fn column_surface(data: &ChunkData, chunk_above: &ChunkData, x: i32, z: i32) -> Option<PatchSurface> {
+    let mut above_is_air = chunk_above
+        .get_voxel(LocalChunkLocation::new_unchecked(Vector3::new(x, 0, z)))
+        .ty
+        == VoxelType::Air;
+
+    for y in (0..CHUNK_SIZE as i32).rev() {
+        let voxel = LocalChunkLocation::new_unchecked(Vector3::new(x, y, z));
+        let ty = data.get_voxel(voxel).ty;
+
+        if ty != VoxelType::Air && above_is_air {
+            return Some(PatchSurface {
+                height: y as u8 + 1,
+                voxel,
+                ty,
+            });
+        }
+
+        above_is_air = ty == VoxelType::Air;
+    }
+
+    None
+}
@@ -0,0 +1,137 @@
+use cgmath::Vector3;
+use itertools::iproduct;
+use strum::IntoEnumIterator;
+
+use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::meshing::direction::Direction;
+use crate::world::chunk_renderer::meshing::quad::{FaceData, Quad};
+use crate::world::chunk_renderer::meshing::{voxel_type_to_color_lookup, ChunkMeshGenerator, NeighborChunks};
+use crate::world::location::{ChunkLocation, LocalChunkLocation};
+use crate::world::voxel_data::VoxelType;
+use crate::world::CHUNK_SIZE;
+
+/// Edge length in voxels of the cells of every level of detail. Level 0 is the full resolution mesh.
+pub const LOD_SCALES: [i32; 3] = [1, 2, 4];
+
+impl ChunkMeshGenerator {
+    /// Generates a simplified mesh in which every cube of `scale`³ voxels is merged into a single cell.
+    ///
+    /// A cell is solid if at least half of its voxels are opaque, and takes the type of its topmost opaque voxel,
+    /// so that the surface keeps its color. Faces are only generated between solid and non-solid cells and have no ambient occlusion.
+    pub fn generate_lod_mesh(data: &ChunkData, neighbor_chunks: &NeighborChunks, scale: i32) -> Vec<Quad> {
+        assert!(
+            scale > 0 && CHUNK_SIZE as i32 % scale == 0,
+            "the scale has to divide the chunk size"
+        );
+
+        let cells_per_axis = CHUNK_SIZE as i32 / scale;
+        let mut quads = Vec::new();
+
+        for (x, y, z) in iproduct!(0..cells_per_axis, 0..cells_per_axis, 0..cells_per_axis) {
+            let cell = Vector3::new(x, y, z);
+            let Some(ty) = cell_type(data, neighbor_chunks, cell, scale) else {
+                continue;
+            };
+
+            for direction in Direction::iter() {
+                if cell_type(data, neighbor_chunks, cell + direction.to_vec(), scale).is_some() {
+                    continue;
+                }
+
+                // Quads are placed at the voxel of the cell that touches the face, so that faces in positive directions
+                // are moved to the far side of the cell
+                let origin = cell * scale;
+                let offset = direction.to_vec().map(|c| c.max(0)) * (scale - 1);
+                let position = LocalChunkLocation::new(origin + offset)
+                    .try_into_checked()
+                    .expect("the cell to be inside of the chunk");
+
+                let mut quad = Quad::new(
+                    position,
+                    direction,
+                    FaceData::new(
+                        voxel_type_to_color_lookup(ty, &position),
+                        0,
+                        ty.face_tiles().map(|tiles| tiles.tile(direction)),
+                    ),
+                    [3.0; 4],
+                    false,
+                );
+                quad.size = [scale, scale];
+                quads.push(quad);
+            }
+        }
+
+        quads
+    }
+}
+
+/// Type of a cell, or None if less than half of its voxels are opaque. Cells outside of the chunk are read from the neighbor chunks.
+fn cell_type(data: &ChunkData, neighbor_chunks: &NeighborChunks, cell: Vector3<i32>, scale: i32) -> Option<VoxelType> {
+    let mut solid_voxels = 0;
+    let mut top = None;
+
+    for (dy, dx, dz) in iproduct!((0..scale).rev(), 0..scale, 0..scale) {
+        let ty = voxel_at(data, neighbor_chunks, cell * scale + Vector3::new(dx, dy, dz));
+
+        if ty.is_opaque() {
+            solid_voxels += 1;
+            top.get_or_insert(ty);
+        }
+    }
+
+    top.filter(|_| 2 * solid_voxels >= scale.pow(3))
+}
+
+/// Voxel at a position relative to the chunk, which may be inside of one of the neighbor chunks
+pub(super) fn voxel_at(data: &ChunkData, neighbor_chunks: &NeighborChunks, position: Vector3<i32>) -> VoxelType {
+    let size = CHUNK_SIZE as i32;
+    let chunk_offset = position.map(|c| c.div_euclid(size));
+    let local = LocalChunkLocation::new(position.map(|c| c.rem_euclid(size)))
+        .try_into_checked()
+        .expect("the remainder to be inside of the chunk");
+
+    if chunk_offset == Vector3::new(0, 0, 0) {
+        data.get_voxel(local).ty
+    } else {
+        neighbor_chunks
+            .get(ChunkLocation::new(chunk_offset))
+            .get_voxel(local)
+            .ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+    use itertools::iproduct;
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+    use crate::world::location::{ChunkLocation, LocalChunkLocation};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    #[test]
+    fn test_lod_mesh_merges_cells() {
+        let air = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+
+        // A solid 4x4x4 cube at the origin of the chunk
+        let mut data = air.clone();
+        for (x, y, z) in iproduct!(0..4, 0..4, 0..4) {
+            data.set_voxel_data(
+                LocalChunkLocation::new(Vector3::new(x, y, z))
+                    .try_into_checked()
+                    .unwrap(),
+                VoxelData::new(VoxelType::Stone),
+            );
+        }
+        let neighbor_chunks = NeighborChunks::new(&ChunkLocation::new(Vector3::new(0, 0, 0)), |_| Some(&air)).unwrap();
+
+        let full = ChunkMeshGenerator::generate_lod_mesh(&data, &neighbor_chunks, 1);
+        assert_eq!(full.len(), 6 * 16);
+
+        let merged = ChunkMeshGenerator::generate_lod_mesh(&data, &neighbor_chunks, 4);
+        assert_eq!(merged.len(), 6);
+        assert!(merged.iter().all(|quad| quad.size == [4, 4]));
+    }
+}
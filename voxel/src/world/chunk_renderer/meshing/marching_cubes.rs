@@ -0,0 +1,300 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+use itertools::iproduct;
+use lazy_static::lazy_static;
+
+use crate::vector_utils::RemEuclid;
+use crate::world::chunk_data::ChunkData;
+use crate::world::chunk_renderer::meshing::{lod, voxel_type_to_color_lookup, ChunkMeshGenerator, NeighborChunks};
+use crate::world::chunk_renderer::smooth_terrain::{SmoothMesh, SmoothVertex};
+use crate::world::location::LocalChunkLocation;
+use crate::world::CHUNK_SIZE;
+
+/// Density above which a point is inside of the terrain
+const ISO_LEVEL: f32 = 0.5;
+/// Share of the density of a voxel that comes from the voxels around it. Below a half, opaque voxels are always inside of the terrain,
+/// so that the neighbors only move the surface between them and no thin features disappear.
+const NEIGHBOR_WEIGHT: f32 = 0.4;
+
+lazy_static! {
+    /// The twelve edges of a cell as pairs of corners, corner `i` is at `(i & 1, i >> 1 & 1, i >> 2 & 1)`
+    static ref CELL_EDGES: Vec<(usize, usize)> = iproduct!(0..3, 0..8)
+        .filter(|&(axis, corner)| corner & 1 << axis == 0)
+        .map(|(axis, corner)| (corner, corner | 1 << axis))
+        .collect();
+
+    /// Triangles of the surface in a cell as edges of [CELL_EDGES], for every combination of corners that are inside of the terrain
+    static ref CELL_TRIANGLES: Vec<Vec<[usize; 3]>> = (0..256).map(cell_triangles).collect();
+}
+
+impl ChunkMeshGenerator {
+    /// Generates a smooth triangle mesh of the terrain with marching cubes, which is an experimental alternative to the voxel faces.
+    ///
+    /// The density of a voxel is mostly whether it is opaque and partly the share of opaque voxels around it, which rounds off steps.
+    /// The cells of the chunk span between the centers of its voxels and the voxels of the next chunk in the positive directions.
+    pub fn generate_marching_cubes_mesh(data: &ChunkData, neighbor_chunks: &NeighborChunks) -> SmoothMesh {
+        let size = CHUNK_SIZE as i32;
+
+        // Opaque voxels from two voxels before to two voxels after the chunk, which are needed for the density gradient at the far corners
+        let opaque = Grid::new(-2, size + 2, |position| {
+            f32::from(u8::from(lod::voxel_at(data, neighbor_chunks, position).is_opaque()))
+        });
+        let density = Grid::new(-1, size + 1, |position| {
+            let neighbors = iproduct!(-1..=1, -1..=1, -1..=1)
+                .filter(|&offset| offset != (0, 0, 0))
+                .map(|(dx, dy, dz)| opaque.get(position + Vector3::new(dx, dy, dz)))
+                .sum::<f32>()
+                / 26.0;
+            (1.0 - NEIGHBOR_WEIGHT) * opaque.get(position) + NEIGHBOR_WEIGHT * neighbors
+        });
+        let corner_data = Grid::new(0, size, |position| {
+            let gradient = Vector3::new(
+                density.get(position + Vector3::unit_x()) - density.get(position - Vector3::unit_x()),
+                density.get(position + Vector3::unit_y()) - density.get(position - Vector3::unit_y()),
+                density.get(position + Vector3::unit_z()) - density.get(position - Vector3::unit_z()),
+            );
+            // The density decreases towards the outside of the terrain
+            let normal = if gradient.magnitude2() > 0.0 {
+                -gradient.normalize()
+            } else {
+                Vector3::unit_y()
+            };
+            (density.get(position), normal)
+        });
+
+        let mut mesh = SmoothMesh::default();
+        for (x, y, z) in iproduct!(0..size, 0..size, 0..size) {
+            let cell = Vector3::new(x, y, z);
+            let corner = |index: usize| cell + Vector3::new(index as i32 & 1, index as i32 >> 1 & 1, index as i32 >> 2 & 1);
+
+            let case = (0..8)
+                .filter(|&index| corner_data.get(corner(index)).0 > ISO_LEVEL)
+                .fold(0, |case, index| case | 1 << index);
+            let triangles = &CELL_TRIANGLES[case];
+            if triangles.is_empty() {
+                continue;
+            }
+
+            let color = cell_color(data, neighbor_chunks, cell);
+            let vertex = |edge: usize| {
+                let (a, b) = CELL_EDGES[edge];
+                let ((density_a, normal_a), (density_b, normal_b)) = (corner_data.get(corner(a)), corner_data.get(corner(b)));
+                let t = (ISO_LEVEL - density_a) / (density_b - density_a);
+
+                // The lattice points of the densities are at the centers of the voxels
+                let center = |index: usize| corner(index).cast::<f32>().unwrap() + Vector3::from_value(0.5);
+                SmoothVertex {
+                    position: center(a).lerp(center(b), t).into(),
+                    normal: normal_a.lerp(normal_b, t).normalize().into(),
+                    color: color.into(),
+                }
+            };
+
+            for triangle in triangles {
+                mesh.indices
+                    .extend((0..3).map(|i| (mesh.vertices.len() + i) as u32));
+                mesh.vertices
+                    .extend(triangle.iter().map(|&edge| vertex(edge)));
+            }
+        }
+
+        mesh
+    }
+}
+
+/// Values at the lattice points of a cube, from `min` to `max` along every axis
+struct Grid<T> {
+    min: i32,
+    len: usize,
+    values: Vec<T>,
+}
+
+impl<T: Copy> Grid<T> {
+    fn new(min: i32, max: i32, value: impl Fn(Vector3<i32>) -> T) -> Self {
+        let values = iproduct!(min..=max, min..=max, min..=max)
+            .map(|(x, y, z)| value(Vector3::new(x, y, z)))
+            .collect();
+
+        Self {
+            min,
+            len: (max - min + 1) as usize,
+            values,
+        }
+    }
+
+    fn get(&self, position: Vector3<i32>) -> T {
+        let index = position.map(|c| (c - self.min) as usize);
+        self.values[(index.x * self.len + index.y) * self.len + index.z]
+    }
+}
+
+/// Color of the topmost opaque voxel at the corners of the cell, so that the surface keeps the color of e.g. grass
+fn cell_color(data: &ChunkData, neighbor_chunks: &NeighborChunks, cell: Vector3<i32>) -> Vector3<f32> {
+    for (dy, dx, dz) in iproduct!((0..=1).rev(), 0..=1, 0..=1) {
+        let position = cell + Vector3::new(dx, dy, dz);
+        let ty = lod::voxel_at(data, neighbor_chunks, position);
+        if ty.is_opaque() {
+            let local = LocalChunkLocation::new(position.rem_euclid(CHUNK_SIZE as i32))
+                .try_into_checked()
+                .expect("the remainder to be inside of the chunk");
+            return voxel_type_to_color_lookup(ty, &local);
+        }
+    }
+
+    unreachable!("cells with a surface to have an opaque voxel at a corner")
+}
+
+/// Triangles of the surface in a cell with the given corners inside of the terrain, facing outwards.
+///
+/// On every face of the cell the crossings of the surface with its edges are connected into segments, which cut off the inside corners.
+/// The segments of all faces form closed loops around the inside corners, which are triangulated as fans. The neighboring cell
+/// connects the crossings on their shared face in the same way, so the surface does not have holes.
+fn cell_triangles(case: usize) -> Vec<[usize; 3]> {
+    let inside = |corner: usize| case & 1 << corner != 0;
+    let edge = |a: usize, b: usize| {
+        CELL_EDGES
+            .iter()
+            .position(|&edge| edge == (a.min(b), a.max(b)))
+            .expect("the corners to be adjacent")
+    };
+
+    // Segment of the surface loop that leaves the edge, for every edge that is crossed by the surface
+    let mut next = [None; 12];
+    for (axis, side) in iproduct!(0..3, 0..2) {
+        // Corners of the face in counterclockwise order as seen from outside of the cell
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut corners = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(du, dv)| side << axis | du << u | dv << v);
+        if side == 0 {
+            corners.reverse();
+        }
+
+        // Crossings in the same order, and whether the surface is entered when walking along the border of the face
+        let crossings: Vec<(usize, bool)> = (0..4)
+            .map(|i| (corners[i], corners[(i + 1) % 4]))
+            .filter(|&(a, b)| inside(a) != inside(b))
+            .map(|(a, b)| (edge(a, b), inside(b)))
+            .collect();
+
+        for (i, &(from, entering)) in crossings.iter().enumerate() {
+            if entering {
+                next[from] = Some(crossings[(i + 1) % crossings.len()].0);
+            }
+        }
+    }
+
+    let mut triangles = Vec::new();
+    while let Some(start) = next.iter().position(Option::is_some) {
+        let mut surface_loop = vec![start];
+        let mut current = next[start]
+            .take()
+            .expect("the edge to be crossed");
+        while current != start {
+            surface_loop.push(current);
+            current = next[current]
+                .take()
+                .expect("the segments to form closed loops");
+        }
+
+        triangles.extend((1..surface_loop.len() - 1).map(|i| [surface_loop[0], surface_loop[i], surface_loop[i + 1]]));
+    }
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cgmath::{InnerSpace, Vector3};
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::chunk_renderer::meshing::marching_cubes::{CELL_EDGES, CELL_TRIANGLES};
+    use crate::world::chunk_renderer::meshing::{ChunkMeshGenerator, NeighborChunks};
+    use crate::world::chunk_renderer::smooth_terrain::SmoothMesh;
+    use crate::world::location::{ChunkLocation, LocalChunkLocation};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    #[test]
+    fn test_cell_triangles_are_closed_within_the_cell() {
+        for (case, triangles) in CELL_TRIANGLES.iter().enumerate() {
+            assert_eq!(triangles.is_empty(), case == 0 || case == 255);
+
+            // Every edge between two crossings is used once in each direction, either inside the cell or by the neighboring cell
+            // on a face, and the crossed edges of the cell are exactly the edges between inside and outside corners
+            let mut used_edges = triangles
+                .iter()
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>();
+            used_edges.sort();
+            used_edges.dedup();
+            let crossed_edges = (0..12)
+                .filter(|&edge| {
+                    let (a, b) = CELL_EDGES[edge];
+                    (case >> a & 1) != (case >> b & 1)
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(used_edges, crossed_edges);
+        }
+    }
+
+    /// Marching cubes mesh of a chunk with stone at the given voxels, surrounded by air
+    fn stone_mesh(voxels: &[Vector3<i32>]) -> SmoothMesh {
+        let air = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+        let mut data = air.clone();
+        for &position in voxels {
+            data.set_voxel_data(
+                LocalChunkLocation::new(position)
+                    .try_into_checked()
+                    .unwrap(),
+                VoxelData::new(VoxelType::Stone),
+            );
+        }
+        let neighbor_chunks = NeighborChunks::new(&ChunkLocation::new(Vector3::new(0, 0, 0)), |_| Some(&air)).unwrap();
+
+        ChunkMeshGenerator::generate_marching_cubes_mesh(&data, &neighbor_chunks)
+    }
+
+    #[test]
+    fn test_marching_cubes_mesh_is_closed() {
+        let mesh = stone_mesh(&[
+            Vector3::new(4, 4, 4),
+            Vector3::new(5, 4, 4),
+            Vector3::new(4, 5, 4),
+            Vector3::new(10, 3, 7),
+        ]);
+        assert!(!mesh.indices.is_empty());
+
+        // Every edge of a closed surface is shared by two triangles, which use it in opposite directions
+        let key = |index: u32| {
+            mesh.vertices[index as usize]
+                .position
+                .map(|c| (c * 1024.0).round() as i32)
+        };
+        let mut edges = HashMap::new();
+        for triangle in mesh.indices.chunks(3) {
+            for i in 0..3 {
+                *edges
+                    .entry((key(triangle[i]), key(triangle[(i + 1) % 3])))
+                    .or_insert(0) += 1;
+            }
+        }
+        for (&(from, to), &count) in &edges {
+            assert_eq!(count, 1);
+            assert_eq!(edges.get(&(to, from)), Some(&1));
+        }
+    }
+
+    #[test]
+    fn test_marching_cubes_triangles_face_outwards() {
+        let mesh = stone_mesh(&[Vector3::new(4, 4, 4)]);
+        let center = Vector3::new(4.5, 4.5, 4.5);
+
+        // Counterclockwise triangles face away from the voxel, so that their back faces can be culled
+        assert_eq!(mesh.indices.len(), 8 * 3);
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(mesh.vertices[triangle[i] as usize].position));
+            assert!((b - a).cross(c - a).dot(a - center) > 0.0);
+        }
+    }
+}
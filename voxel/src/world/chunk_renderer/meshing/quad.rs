@@ -1,5 +1,6 @@
 use cgmath::Vector3;
 
+use crate::vector_utils::AbsValue;
 use crate::world::chunk_renderer::meshing::direction::Direction;
 use crate::world::location::{LocalChunkLocation, WithinBounds};
 
@@ -10,6 +11,11 @@ pub struct Quad {
     pub data: FaceData,
     pub ambient_occlusion_values: [f32; 4],
     pub reversed_orientation: bool,
+    /// Number of voxels that the quad covers along the two normal axes of its direction
+    pub size: [i32; 2],
+    /// Normals of the corners that are averaged from the voxels around them, see [crate::world::chunk_renderer::meshing::ChunkMeshGenerator::smooth_normals].
+    /// Every component is -1, 0 or 1. Quads without them are shaded with the normal of their direction.
+    pub smooth_normals: Option<[Vector3<i32>; 4]>,
 }
 
 impl Quad {
@@ -26,17 +32,41 @@ impl Quad {
             data,
             ambient_occlusion_values: ao_values,
             reversed_orientation,
+            size: [1, 1],
+            smooth_normals: None,
         }
     }
+
+    /// Positions of the four corners relative to the chunk, in the same order as [Quad::ambient_occlusion_values]
+    pub fn corners(&self) -> [Vector3<i32>; 4] {
+        let (axis1, axis2) = self.direction.get_normal_axes();
+        let (axis1, axis2) = (axis1.abs() * self.size[0], axis2.abs() * self.size[1]);
+
+        // Faces towards the positive axes are on the far side of their voxel
+        let origin = match self.direction {
+            Direction::XPos | Direction::YPos | Direction::ZPos => *self.position + self.direction.to_vec(),
+            Direction::XNeg | Direction::YNeg | Direction::ZNeg => *self.position,
+        };
+
+        [origin, origin + axis1, origin + axis2, origin + axis1 + axis2]
+    }
 }
 
 #[derive(Debug)]
 pub struct FaceData {
     pub color: Vector3<f32>,
+    /// Distance by which the top edge of the face is moved down in eighths of a voxel, for partial voxels
+    pub lowered: u8,
+    /// Tile of the texture atlas that is drawn on the face instead of its color, see [crate::world::voxel_data::FaceTiles]
+    pub texture_tile: Option<u8>,
 }
 
 impl FaceData {
-    pub fn new(color: Vector3<f32>) -> Self {
-        Self { color }
+    pub fn new(color: Vector3<f32>, lowered: u8, texture_tile: Option<u8>) -> Self {
+        Self {
+            color,
+            lowered,
+            texture_tile,
+        }
     }
 }
@@ -0,0 +1,407 @@
+use std::mem;
+
+use bytemuck::Zeroable;
+use cgmath::{EuclideanSpace, InnerSpace, SquareMatrix, Vector3};
+use itertools::iproduct;
+use strum_macros::EnumIter;
+use wgpu::{include_wgsl, BindingType, BufferBindingType, BufferUsages, ShaderStages};
+
+use crate::rendering::camera::Camera;
+use crate::rendering::render_targets::{RenderTargetDescriptor, RenderTargetSize};
+use crate::rendering::uniform_ring::UniformRing;
+use crate::rendering::{PostEffect, RenderCtx, RenderHandle};
+use crate::vector_utils::RemEuclid;
+use crate::world::chunk_renderer::gpu_culling::buffer_layout_entry;
+use crate::world::location::ChunkLocation;
+use crate::world::occupancy::{ChunkOccupancy, OCCUPANCY_ROWS};
+
+/// Chunks along each side of the occupancy volume around the camera, must match `VOLUME_CHUNKS` in `ray_tracing.wgsl`
+const VOLUME_CHUNKS: i32 = 8;
+const VOLUME_SLOTS: usize = (VOLUME_CHUNKS * VOLUME_CHUNKS * VOLUME_CHUNKS) as usize;
+/// One bit per voxel of a chunk, uploaded in the layout of [crate::world::occupancy::OccupancyMask]
+const WORDS_PER_CHUNK: usize = OCCUPANCY_ROWS;
+/// Chunks whose occupancy is uploaded per frame at most, so that crossing a chunk border does not upload the whole volume at once
+const UPLOADS_PER_FRAME: usize = 32;
+const TRACING_WORKGROUP_SIZE: u32 = 8;
+
+const RAY_TRACING_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    ty: BindingType::Buffer {
+        ty: BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: wgpu::BufferSize::new(mem::size_of::<RawRayTracing>() as u64),
+    },
+    visibility: ShaderStages::COMPUTE,
+    count: None,
+}];
+
+/// Occupancy of the volume and its chunk slots, the depth buffer and the traced lighting
+const TRACING_RESOURCES_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[
+    buffer_layout_entry(0, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: true }),
+    buffer_layout_entry(1, ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: true }),
+    wgpu::BindGroupLayoutEntry {
+        binding: 2,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Depth,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 3,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: LIGHTING_FORMAT,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+    },
+];
+
+/// Traced lighting, at a binding that does not collide with the uniforms in the shared shader module
+const COMPOSITE_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 1,
+    visibility: ShaderStages::FRAGMENT,
+    ty: BindingType::Texture {
+        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+        view_dimension: wgpu::TextureViewDimension::D2,
+        multisampled: false,
+    },
+    count: None,
+}];
+
+const LIGHTING_TARGET: &str = "ray_traced_lighting";
+const LIGHTING_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// How the ambient occlusion and the shadows of the terrain are computed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, EnumIter)]
+pub enum LightingMode {
+    /// Ambient occlusion of the voxel corners that is baked into the chunk meshes
+    #[default]
+    Rasterized,
+    /// Additionally traces ambient occlusion and sun shadow rays through the voxels around the camera, see [RayTracedLighting]
+    RayTraced,
+}
+
+impl LightingMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            LightingMode::Rasterized => "Rasterized",
+            LightingMode::RayTraced => "Ray traced",
+        }
+    }
+}
+
+/// Ray traced ambient occlusion and sun shadows for high-end GPUs.
+///
+/// Whether each voxel of the chunks around the camera is opaque is uploaded as a bitmask into a volume of [VOLUME_CHUNKS] chunks along
+/// each side, which wraps around so that only chunks that enter it have to be uploaded. A compute shader reconstructs the surface of every
+/// pixel from the depth buffer and walks rays from it through the volume voxel by voxel. The traced lighting is multiplied onto the image,
+/// so the terrain is darkened on top of the baked ambient occlusion of the rasterized path.
+pub struct RayTracedLighting {
+    uniforms: UniformRing<RawRayTracing>,
+    tracing_pipeline: wgpu::ComputePipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    occupancy_buffer: wgpu::Buffer,
+    /// Chunk location of every slot of the volume and whether its occupancy was uploaded, see [slot_index]
+    slots_buffer: wgpu::Buffer,
+    /// Chunk whose occupancy is stored in each slot, or None if the slot has to be uploaded again
+    slots: Vec<Option<ChunkLocation>>,
+    /// Set to mark all slots of the slots buffer as empty in the next frame, so that no stale chunks are traced until they are uploaded
+    reset_slots: bool,
+    pub enabled: bool,
+    frame: u32,
+}
+
+impl RayTracedLighting {
+    pub fn is_supported(ctx: &RenderCtx) -> bool {
+        ctx.downlevel_flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+    }
+
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let layout = ctx.bind_group_layout("Ray tracing bind group layout", RAY_TRACING_BIND_GROUP_LAYOUT_ENTRIES);
+        let (tracing_pipeline, composite_pipeline) = create_ray_tracing_pipelines(ctx);
+
+        ctx.render_targets.create(
+            &ctx.device,
+            LIGHTING_TARGET,
+            RenderTargetDescriptor {
+                label: "ray_traced_lighting_texture",
+                format: LIGHTING_FORMAT,
+                usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+                size: RenderTargetSize::SurfaceScaled(1.0),
+            },
+            ctx.surface_size(),
+        );
+
+        let create_buffer = |label: &str, size: usize| {
+            ctx.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as u64,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        Self {
+            uniforms: UniformRing::new(ctx, "Ray tracing uniforms", &layout, &RawRayTracing::zeroed()),
+            tracing_pipeline,
+            composite_pipeline,
+            occupancy_buffer: create_buffer("Occupancy buffer", VOLUME_SLOTS * WORDS_PER_CHUNK * mem::size_of::<u32>()),
+            slots_buffer: create_buffer("Occupancy slots buffer", VOLUME_SLOTS * mem::size_of::<[i32; 4]>()),
+            slots: vec![None; VOLUME_SLOTS],
+            reset_slots: false,
+            enabled: false,
+            frame: 0,
+        }
+    }
+
+    /// Recreates the buffers and the pipelines on the device of a new render context. The occupancy is uploaded again.
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self {
+            enabled: self.enabled,
+            ..Self::new(ctx)
+        };
+    }
+
+    /// Uploads the occupancy of the chunk again once it is in the volume, e.g. because its voxels were edited
+    pub fn invalidate(&mut self, location: ChunkLocation) {
+        let slot = &mut self.slots[slot_index(location)];
+        if *slot == Some(location) {
+            *slot = None;
+        }
+    }
+
+    /// Uploads the occupancy of all chunks again, e.g. after switching to another dimension
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+        self.reset_slots = true;
+    }
+
+    /// Centers the volume around the camera, uploads the occupancy of chunks that entered it and writes the uniforms of this frame.
+    /// Chunks whose data is not generated yet are traced as empty until they are available.
+    pub fn update<'a>(
+        &mut self,
+        handle: &mut RenderHandle,
+        camera: &Camera,
+        to_sun: Vector3<f32>,
+        chunk_occupancy: impl Fn(ChunkLocation) -> Option<&'a ChunkOccupancy>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        if mem::take(&mut self.reset_slots) {
+            handle.write_buffer(&self.slots_buffer, 0, &vec![0; VOLUME_SLOTS * mem::size_of::<[i32; 4]>()]);
+        }
+
+        let center = ChunkLocation::from_world_location_f32(camera.position.to_vec());
+        let volume_min = center - ChunkLocation::new(Vector3::new(VOLUME_CHUNKS / 2, VOLUME_CHUNKS / 2, VOLUME_CHUNKS / 2));
+
+        let mut uploads = 0;
+        for (x, y, z) in iproduct!(0..VOLUME_CHUNKS, 0..VOLUME_CHUNKS, 0..VOLUME_CHUNKS) {
+            if uploads == UPLOADS_PER_FRAME {
+                break;
+            }
+            let location = volume_min + ChunkLocation::new(Vector3::new(x, y, z));
+            let index = slot_index(location);
+            if self.slots[index] == Some(location) {
+                continue;
+            }
+            let Some(occupancy) = chunk_occupancy(location) else {
+                continue;
+            };
+
+            handle.write_buffer(
+                &self.occupancy_buffer,
+                (index * WORDS_PER_CHUNK * mem::size_of::<u32>()) as u64,
+                bytemuck::cast_slice(&occupancy.opaque.rows()[..]),
+            );
+            handle.write_buffer(
+                &self.slots_buffer,
+                (index * mem::size_of::<[i32; 4]>()) as u64,
+                bytemuck::bytes_of(&[location.x, location.y, location.z, 1]),
+            );
+            self.slots[index] = Some(location);
+            uploads += 1;
+        }
+
+        self.frame = self.frame.wrapping_add(1);
+        self.uniforms.write(
+            handle,
+            &RawRayTracing {
+                inverse_view_proj: camera
+                    .view_projection()
+                    .invert()
+                    .expect("View projection matrices are invertible")
+                    .into(),
+                to_sun: to_sun.normalize().extend(0.0).into(),
+                volume_min: [volume_min.x, volume_min.y, volume_min.z, self.frame as i32],
+            },
+        );
+    }
+}
+
+impl PostEffect for RayTracedLighting {
+    fn apply(&self, handle: &mut RenderHandle, label: &str) {
+        if !self.enabled {
+            return;
+        }
+        let ctx = handle.render_ctx();
+        let Some(lighting) = ctx.render_targets.get(LIGHTING_TARGET) else {
+            return;
+        };
+
+        let resources_layout = ctx.bind_group_layout(
+            "Ray tracing resources bind group layout",
+            TRACING_RESOURCES_BIND_GROUP_LAYOUT_ENTRIES,
+        );
+        let resources = ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Ray tracing resources bind group"),
+                layout: &resources_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.occupancy_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: self.slots_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&handle.depth_texture().view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&lighting.view),
+                    },
+                ],
+            });
+        let composite_layout = ctx.bind_group_layout("Ray tracing composite bind group layout", COMPOSITE_BIND_GROUP_LAYOUT_ENTRIES);
+        let composite = ctx
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Ray tracing composite bind group"),
+                layout: &composite_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&lighting.view),
+                }],
+            });
+
+        let (width, height) = (lighting.texture.width(), lighting.texture.height());
+        let uniforms = self.uniforms.binding();
+        {
+            let mut compute_pass = handle.begin_compute_pass(label);
+            compute_pass.set_pipeline(&self.tracing_pipeline);
+            compute_pass.set_bind_group(0, uniforms.bind_group, &[uniforms.offset]);
+            compute_pass.set_bind_group(1, &resources, &[]);
+            compute_pass.dispatch_workgroups(width.div_ceil(TRACING_WORKGROUP_SIZE), height.div_ceil(TRACING_WORKGROUP_SIZE), 1);
+        }
+
+        let mut render_pass = handle.begin_post_pass("ray_traced_lighting_composite");
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &composite, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Index of the slot of the volume in which a chunk is stored, which is the same for all chunks that are [VOLUME_CHUNKS] apart
+fn slot_index(location: ChunkLocation) -> usize {
+    let slot = (*location).rem_euclid(VOLUME_CHUNKS);
+    ((slot.z * VOLUME_CHUNKS + slot.y) * VOLUME_CHUNKS + slot.x) as usize
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawRayTracing {
+    inverse_view_proj: [[f32; 4]; 4],
+    to_sun: [f32; 4],
+    /// Minimum chunk of the volume and the frame number, which varies the directions of the ambient occlusion rays
+    volume_min: [i32; 4],
+}
+
+fn create_ray_tracing_pipelines(ctx: &RenderCtx) -> (wgpu::ComputePipeline, wgpu::RenderPipeline) {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("../ray_tracing.wgsl"));
+
+    let tracing_layout = ctx.pipeline_layout(
+        "Ray tracing compute pipeline layout",
+        &[RAY_TRACING_BIND_GROUP_LAYOUT_ENTRIES, TRACING_RESOURCES_BIND_GROUP_LAYOUT_ENTRIES],
+        &[],
+    );
+    let tracing_pipeline = ctx
+        .device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Ray tracing compute pipeline"),
+            layout: Some(&tracing_layout),
+            module: &shader,
+            entry_point: "cs_trace",
+        });
+
+    let composite_layout = ctx.pipeline_layout("Ray tracing composite pipeline layout", &[COMPOSITE_BIND_GROUP_LAYOUT_ENTRIES], &[]);
+    let composite_pipeline = ctx
+        .device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Ray tracing composite render pipeline"),
+            layout: Some(&composite_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    // The traced lighting is multiplied onto the image and keeps its alpha, so that transparent windows stay transparent
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Dst,
+                            dst_factor: wgpu::BlendFactor::Zero,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                entry_point: "fs_composite",
+            }),
+            primitive: Default::default(),
+            // Post passes have no depth attachment, so that the depth buffer can be sampled
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        });
+
+    (tracing_pipeline, composite_pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::chunk_renderer::ray_tracing::{slot_index, VOLUME_CHUNKS, VOLUME_SLOTS};
+    use crate::world::location::ChunkLocation;
+
+    #[test]
+    fn test_volume_slots_wrap_around() {
+        let slot = |x, y, z| slot_index(ChunkLocation::new(Vector3::new(x, y, z)));
+
+        assert_eq!(slot(0, 0, 0), slot(VOLUME_CHUNKS, -VOLUME_CHUNKS, 2 * VOLUME_CHUNKS));
+        assert_eq!(slot(-1, -1, -1), VOLUME_SLOTS - 1);
+        assert_ne!(slot(1, 0, 0), slot(0, 1, 0));
+    }
+}
@@ -0,0 +1,150 @@
+use std::mem;
+
+use bytemuck::Zeroable;
+use cgmath::Vector3;
+use wgpu::{include_wgsl, BindingType};
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::{UniformBinding, UniformRing};
+use crate::rendering::{RenderCtx, RenderHandle, Renderer};
+use crate::world::raycast::RaycastHit;
+
+const SELECTION_BIND_GROUP_LAYOUT_ENTRIES: &[wgpu::BindGroupLayoutEntry] = &[wgpu::BindGroupLayoutEntry {
+    binding: 0,
+    ty: BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: true,
+        min_binding_size: wgpu::BufferSize::new(mem::size_of::<RawSelection>() as u64),
+    },
+    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+    count: None,
+}];
+
+/// Distance by which the overlay is moved in front of the targeted face, so that it does not fight with the face for depth
+const FACE_OFFSET: f32 = 0.002;
+
+/// Outline of the targeted voxel face, with cracks that grow with the break progress
+pub struct SelectionRenderer {
+    uniforms: UniformRing<RawSelection>,
+    pipeline: wgpu::RenderPipeline,
+    visible: bool,
+}
+
+impl SelectionRenderer {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let layout = ctx.bind_group_layout("Selection bind group layout", SELECTION_BIND_GROUP_LAYOUT_ENTRIES);
+
+        Self {
+            uniforms: UniformRing::new(ctx, "Selection uniforms", &layout, &RawSelection::zeroed()),
+            pipeline: create_selection_pipeline(ctx),
+            visible: false,
+        }
+    }
+
+    /// Recreates the uniform buffer and the pipeline on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self::new(ctx);
+    }
+
+    /// Writes the targeted face of the current frame. Nothing is drawn if there is no target.
+    pub fn update(&mut self, handle: &mut RenderHandle, target: Option<RaycastHit>, break_progress: f32) {
+        self.visible = target.is_some();
+
+        if let Some(hit) = target {
+            self.uniforms
+                .write(handle, &RawSelection::from_hit(hit, break_progress));
+        }
+    }
+}
+
+impl Renderer for SelectionRenderer {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, _render_ctx: &RenderCtx) {
+        if !self.visible {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        camera.bind(render_pass, 0);
+        self.uniforms.binding().bind(render_pass, 1);
+        render_pass.draw(0..6, 0..1);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawSelection {
+    origin: [f32; 4],
+    axis_u: [f32; 4],
+    axis_v: [f32; 4],
+    break_progress: f32,
+    _padding: [f32; 3],
+}
+
+impl RawSelection {
+    fn from_hit(hit: RaycastHit, break_progress: f32) -> Self {
+        let normal = hit.normal.map(|c| c as f32);
+
+        // The two axes that span the face are the ones that are not along its normal
+        let axis_u = Vector3::new(normal.y.abs(), normal.z.abs(), normal.x.abs());
+        let axis_v = Vector3::new(normal.z.abs(), normal.x.abs(), normal.y.abs());
+
+        let center = hit.voxel.to_f32() + Vector3::new(0.5, 0.5, 0.5) + (0.5 + FACE_OFFSET) * normal;
+        let origin = center - 0.5 * axis_u - 0.5 * axis_v;
+
+        Self {
+            origin: origin.extend(0.0).into(),
+            axis_u: axis_u.extend(0.0).into(),
+            axis_v: axis_v.extend(0.0).into(),
+            break_progress,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+fn create_selection_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("../selection.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Selection render pipeline layout",
+        &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES, SELECTION_BIND_GROUP_LAYOUT_ENTRIES],
+        &[],
+    );
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Selection render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                entry_point: "fs_main",
+            }),
+            // The winding of the quad depends on the direction of the face normal, so both sides are drawn
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Tested against the depth of the world, so that the overlay is hidden behind other terrain
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use wgpu::{include_wgsl, vertex_attr_array, BufferUsages, PushConstantRange, ShaderStages};
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::day_cycle::LIGHTING_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::RenderCtx;
+use crate::world::location::ChunkLocation;
+
+/// Vertex of the smooth terrain, which unlike [crate::world::chunk_renderer::vertex::Vertex] is not placed on the voxel grid
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct SmoothVertex {
+    /// Relative to the chunk
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 3],
+}
+
+/// Triangle mesh of the smooth terrain of a chunk, see [ChunkMeshGenerator::generate_marching_cubes_mesh]
+///
+/// [ChunkMeshGenerator::generate_marching_cubes_mesh]:
+///     crate::world::chunk_renderer::meshing::ChunkMeshGenerator::generate_marching_cubes_mesh
+#[derive(Default)]
+pub struct SmoothMesh {
+    pub vertices: Vec<SmoothVertex>,
+    pub indices: Vec<u32>,
+}
+
+/// Draws the chunks that were meshed with [MeshingMode::MarchingCubes], with one draw call per chunk.
+///
+/// The meshes are drawn together with the voxel meshes of the other chunks, but ignore the debug views of the [RenderMode]s.
+///
+/// [MeshingMode::MarchingCubes]: crate::world::chunk_renderer::MeshingMode::MarchingCubes
+/// [RenderMode]: crate::world::chunk_renderer::RenderMode
+pub struct SmoothTerrainRenderer {
+    pipeline: wgpu::RenderPipeline,
+    /// CPU-side copies of all meshes, used to upload them again if the GPU resources have to be rebuilt
+    meshes: HashMap<ChunkLocation, SmoothMesh>,
+    buffers: HashMap<ChunkLocation, SmoothBuffers>,
+}
+
+struct SmoothBuffers {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+}
+
+impl SmoothTerrainRenderer {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        Self {
+            pipeline: create_smooth_terrain_pipeline(ctx),
+            meshes: HashMap::new(),
+            buffers: HashMap::new(),
+        }
+    }
+
+    /// Recreates the pipeline and all buffers on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        self.pipeline = create_smooth_terrain_pipeline(ctx);
+        self.buffers = self
+            .meshes
+            .iter()
+            .map(|(&location, mesh)| (location, SmoothBuffers::new(ctx, mesh)))
+            .collect();
+    }
+
+    /// Replaces the smooth mesh of a chunk. Chunks without triangles are not drawn.
+    pub fn insert(&mut self, ctx: &RenderCtx, location: ChunkLocation, mesh: SmoothMesh) {
+        if mesh.indices.is_empty() {
+            self.remove(location);
+            return;
+        }
+
+        self.buffers
+            .insert(location, SmoothBuffers::new(ctx, &mesh));
+        self.meshes.insert(location, mesh);
+    }
+
+    pub fn remove(&mut self, location: ChunkLocation) {
+        self.meshes.remove(&location);
+        self.buffers.remove(&location);
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, lighting: UniformBinding<'a>) {
+        if self.buffers.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        camera.bind(render_pass, 0);
+        lighting.bind(render_pass, 1);
+
+        for (location, buffers) in &self.buffers {
+            let position = location.to_world_location_f32();
+            render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::cast_slice(&[position.x, position.y, position.z]));
+            render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..buffers.num_indices, 0, 0..1);
+        }
+    }
+}
+
+impl SmoothBuffers {
+    fn new(ctx: &RenderCtx, mesh: &SmoothMesh) -> Self {
+        Self {
+            vertex_buffer: ctx
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Smooth terrain vertex buffer"),
+                    usage: BufferUsages::VERTEX,
+                    contents: bytemuck::cast_slice(&mesh.vertices),
+                }),
+            index_buffer: ctx
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Smooth terrain index buffer"),
+                    usage: BufferUsages::INDEX,
+                    contents: bytemuck::cast_slice(&mesh.indices),
+                }),
+            num_indices: mesh.indices.len() as u32,
+        }
+    }
+}
+
+fn create_smooth_terrain_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3];
+
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("../smooth_terrain.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Smooth terrain render pipeline layout",
+        &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES, LIGHTING_BIND_GROUP_LAYOUT_ENTRIES],
+        &[PushConstantRange {
+            stages: ShaderStages::VERTEX,
+            range: 0..12,
+        }],
+    );
+
+    // Non sRGB surfaces need the shader to encode the color
+    let fragment_entry_point = if ctx.surface_format.is_srgb() { "fs_main" } else { "fs_main_gamma" };
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Smooth terrain render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: mem::size_of::<SmoothVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &ATTRIBUTES,
+                }],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                entry_point: fragment_entry_point,
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
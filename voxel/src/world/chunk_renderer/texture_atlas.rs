@@ -0,0 +1,50 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::warn;
+
+use crate::rendering::texture::Texture;
+use crate::rendering::RenderCtx;
+
+/// Texture atlas of the voxel faces, which is shared by the chunk renderers of all dimensions.
+///
+/// Without a loaded atlas a single transparent texel is bound instead. Transparent texels show the color of the voxel,
+/// so that all faces keep their untextured colors.
+pub struct VoxelTextureAtlas {
+    /// Image from which the atlas was loaded, used to load it again if the GPU resources have to be rebuilt
+    path: Option<PathBuf>,
+    pub bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl VoxelTextureAtlas {
+    /// An atlas without any textures
+    pub fn untextured(ctx: &RenderCtx) -> Self {
+        Self {
+            path: None,
+            bind_group: Arc::new(
+                Texture::transparent(ctx, "Untextured voxel atlas").create_bind_group(ctx, "Voxel texture atlas bind group"),
+            ),
+        }
+    }
+
+    pub fn load(ctx: &RenderCtx, path: &Path) -> Result<Self> {
+        let atlas = Texture::load_atlas(ctx, path)?;
+
+        Ok(Self {
+            path: Some(path.to_owned()),
+            bind_group: Arc::new(atlas.create_bind_group(ctx, "Voxel texture atlas bind group")),
+        })
+    }
+
+    /// Loads the atlas again on the device of a new render context, or falls back to no textures if that fails
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = match &self.path {
+            Some(path) => Self::load(ctx, path).unwrap_or_else(|err| {
+                warn!("Could not reload the texture atlas from {path:?}: {err:#}");
+                Self::untextured(ctx)
+            }),
+            None => Self::untextured(ctx),
+        };
+    }
+}
@@ -7,16 +7,19 @@ use wgpu::vertex_attr_array;
 
 /// Layout:
 /// 0: u32
-///   - x: u8
-///   - y: u8
-///   - z: u8
+///   - x: u8: the lower 6 bits are the position, the upper 2 bits are the x component of the smoothed normal
+///   - y: u8: like x
+///   - z: u8: like x
 ///   - r: u8
 /// 1: u32
 ///   - g: u8
 ///   - b: u8
 ///   - normal: 3 bits:  0, 1, 2, 3, 4, 5 => (0, 0, 1), (0, 1, 0), (1, 0, 0), (0, 0, -1), (0, -1, 0), (-1, 0, 0)
 ///   - ao: 2 bits
-///   - _unused: 11 bits
+///   - lowered: 3 bits: distance by which the vertex is moved down, in eighths of a voxel
+///   - texture: u8: 0 => untextured, n => tile n - 1 of the texture atlas
+///
+/// The components of the smoothed normal are 0, 1, 3 => 0, 1, -1. A smoothed normal of zero means that the face normal is used.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct Vertex {
@@ -52,6 +55,37 @@ impl Vertex {
         }
     }
 
+    /// Moves the vertex down by the given number of eighths of a voxel, which is used for the top of partial voxels
+    pub fn lowered(mut self, eighths: u8) -> Self {
+        assert!(eighths < 8);
+        self.color_g_b_normal_ao |= (eighths as u32) << 8;
+        self
+    }
+
+    /// Draws the given tile of the texture atlas on the vertex instead of its color
+    pub fn textured(mut self, tile: Option<u8>) -> Self {
+        if let Some(tile) = tile {
+            assert!(tile < u8::MAX);
+            self.color_g_b_normal_ao |= tile as u32 + 1;
+        }
+        self
+    }
+
+    /// Lights the vertex with the given normal instead of the normal of its face, whose components have to be -1, 0 or 1
+    pub fn smoothed(mut self, normal: Vector3<i32>) -> Self {
+        let code = |component: i32| -> u32 {
+            match component {
+                0 => 0,
+                1 => 1,
+                -1 => 3,
+                _ => panic!("invalid smoothed normal component"),
+            }
+        };
+
+        self.position_x_y_z_color_r |= code(normal.x) << 30 | code(normal.y) << 22 | code(normal.z) << 14;
+        self
+    }
+
     pub fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
         const ATTRIBUTES: [wgpu::VertexAttribute; 2] = vertex_attr_array![0 => Uint32, 1 => Uint32];
 
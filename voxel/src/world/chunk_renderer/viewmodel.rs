@@ -0,0 +1,165 @@
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use cgmath::{Deg, Matrix4, Vector3};
+use wgpu::{include_wgsl, PushConstantRange, ShaderStages};
+
+use crate::rendering::camera::{RawCamera, CAMERA_BIND_GROUP_LAYOUT_ENTRIES, NO_DISTANCE_FOG, NO_FOG, OPENGL_TO_WGPU_MATRIX};
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::{UniformBinding, UniformRing};
+use crate::rendering::{RenderCtx, RenderHandle, Renderer};
+use crate::world::chunk_renderer::block_icons::{create_voxel_mesh, solid_voxel_types, VoxelMesh};
+use crate::world::chunk_renderer::vertex::Vertex;
+use crate::world::voxel_data::VoxelType;
+
+const SWING_DURATION: Duration = Duration::from_millis(250);
+
+/// The currently held block, drawn as a small 3D model in the bottom right corner of the screen.
+///
+/// It has its own projection and is drawn without depth testing into a square viewport, so that it is never hidden by the world.
+pub struct Viewmodel {
+    meshes: Vec<(VoxelType, VoxelMesh)>,
+    camera: UniformRing<RawCamera>,
+    pipeline: wgpu::RenderPipeline,
+    pub selected: VoxelType,
+    /// Remaining time of the current swing animation
+    swing_remaining: Duration,
+    /// Skips the swing animation, see [crate::accessibility::Accessibility]
+    pub reduced_motion: bool,
+}
+
+impl Viewmodel {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let meshes = solid_voxel_types()
+            .into_iter()
+            .map(|ty| (ty, create_voxel_mesh(ctx, ty)))
+            .collect();
+
+        let camera_layout = ctx.bind_group_layout("Camera bind group layout", CAMERA_BIND_GROUP_LAYOUT_ENTRIES);
+        let camera = UniformRing::new(ctx, "Viewmodel camera", &camera_layout, &viewmodel_camera(0.0));
+
+        Self {
+            meshes,
+            camera,
+            pipeline: create_viewmodel_pipeline(ctx),
+            selected: VoxelType::Grass,
+            swing_remaining: Duration::ZERO,
+            reduced_motion: false,
+        }
+    }
+
+    /// Recreates the meshes and the pipeline on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        *self = Self {
+            selected: self.selected,
+            reduced_motion: self.reduced_motion,
+            ..Self::new(ctx)
+        };
+    }
+
+    /// Starts the swing animation, e.g. when a block is placed or broken
+    pub fn swing(&mut self) {
+        if self.reduced_motion {
+            return;
+        }
+        self.swing_remaining = SWING_DURATION;
+    }
+
+    /// Advances the swing animation and writes the transform of the current frame
+    pub fn update(&mut self, handle: &mut RenderHandle, dt: Duration) {
+        self.swing_remaining = self.swing_remaining.saturating_sub(dt);
+        let progress = 1.0 - self.swing_remaining.as_secs_f32() / SWING_DURATION.as_secs_f32();
+
+        self.camera
+            .write(handle, &viewmodel_camera(progress));
+    }
+}
+
+impl Renderer for Viewmodel {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, _camera: UniformBinding<'a>, render_ctx: &RenderCtx) {
+        let Some((_, mesh)) = self
+            .meshes
+            .iter()
+            .find(|(ty, _)| *ty == self.selected)
+        else {
+            return;
+        };
+
+        let (width, height) = render_ctx.surface_size();
+        let size = (width.min(height) / 3) as f32;
+        render_pass.set_viewport(width as f32 - size, height as f32 - size, size, size, 0.0, 1.0);
+
+        render_pass.set_pipeline(&self.pipeline);
+        self.camera.binding().bind(render_pass, 0);
+        render_pass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::cast_slice(&[[0.0f32; 3]]));
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+    }
+}
+
+/// Transform of the held block, which is tilted down by the swing animation at `swing_progress` in `0.0..=1.0`
+fn viewmodel_camera(swing_progress: f32) -> RawCamera {
+    let swing = Deg(-50.0 * (swing_progress * PI).sin());
+
+    let proj = OPENGL_TO_WGPU_MATRIX * cgmath::perspective(Deg(50.0), 1.0, 0.1, 10.0);
+    let model = Matrix4::from_translation(Vector3::new(0.0, 0.0, -2.4))
+        * Matrix4::from_angle_x(Deg(20.0) + swing)
+        * Matrix4::from_angle_y(Deg(-35.0))
+        * Matrix4::from_translation(Vector3::new(-0.5, -0.5, -0.5));
+
+    RawCamera {
+        position: [0.5, 0.5, 2.9, 0.0],
+        view_proj: (proj * model).into(),
+        clouds: [0.0; 4],
+        fog: NO_FOG,
+        distance_fog: NO_DISTANCE_FOG,
+    }
+}
+
+fn create_viewmodel_pipeline(ctx: &RenderCtx) -> wgpu::RenderPipeline {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("../shader.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Mesh render pipeline layout",
+        &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES],
+        &[PushConstantRange {
+            stages: ShaderStages::VERTEX,
+            range: 0..12,
+        }],
+    );
+
+    let fragment_entry_point = if ctx.surface_format.is_srgb() { "fs_main" } else { "fs_main_gamma" };
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Viewmodel render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[Vertex::layout()],
+                entry_point: "vs_main",
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                targets: &[Some(ctx.surface_format.into())],
+                entry_point: fragment_entry_point,
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            // Always drawn on top of the world. The voxel is convex, so back face culling is enough to draw it correctly.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        })
+}
@@ -0,0 +1,249 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use wgpu::util::DeviceExt;
+use wgpu::{include_wgsl, BufferUsages, PushConstantRange, ShaderStages};
+
+use crate::rendering::camera::CAMERA_BIND_GROUP_LAYOUT_ENTRIES;
+use crate::rendering::texture::Texture;
+use crate::rendering::uniform_ring::UniformBinding;
+use crate::rendering::{RenderCtx, Renderer};
+use crate::world::chunk_renderer::vertex::Vertex;
+use crate::world::location::ChunkLocation;
+
+/// Transparent pass that draws the water surfaces with scrolling ripples and the translucent voxels of all chunks after the opaque terrain.
+///
+/// Chunks are drawn back to front, see [WaterRenderer::sort_back_to_front]. They are always drawn with one draw call per mesh,
+/// as only few chunks contain water surfaces or translucent voxels.
+pub struct WaterRenderer {
+    pipeline: wgpu::RenderPipeline,
+    translucent_pipeline: wgpu::RenderPipeline,
+    water: TransparentMeshes,
+    translucent: TransparentMeshes,
+    /// Chunks with any transparent mesh, from the farthest to the closest chunk
+    draw_order: Vec<ChunkLocation>,
+    /// Time of the ripple animation in seconds
+    time: f32,
+}
+
+/// One kind of transparent mesh of all chunks
+#[derive(Default)]
+struct TransparentMeshes {
+    /// CPU-side copies of all meshes, used to upload them again if the GPU resources have to be rebuilt
+    meshes: HashMap<ChunkLocation, (Vec<Vertex>, Vec<u32>)>,
+    buffers: HashMap<ChunkLocation, WaterBuffers>,
+}
+
+struct WaterBuffers {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+}
+
+impl WaterRenderer {
+    pub fn new(ctx: &RenderCtx) -> Self {
+        let (pipeline, translucent_pipeline) = create_water_pipelines(ctx);
+
+        Self {
+            pipeline,
+            translucent_pipeline,
+            water: TransparentMeshes::default(),
+            translucent: TransparentMeshes::default(),
+            draw_order: Vec::new(),
+            time: 0.0,
+        }
+    }
+
+    /// Recreates the pipelines and all buffers on the device of a new render context
+    pub fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        (self.pipeline, self.translucent_pipeline) = create_water_pipelines(ctx);
+        self.water.rebuild_gpu_resources(ctx);
+        self.translucent.rebuild_gpu_resources(ctx);
+    }
+
+    /// Replaces the water mesh of a chunk. Chunks without water surfaces are not drawn.
+    pub fn insert(&mut self, ctx: &RenderCtx, location: ChunkLocation, vertices: Vec<Vertex>, indices: Vec<u32>) {
+        self.water
+            .insert(ctx, location, vertices, indices);
+    }
+
+    /// Replaces the mesh of the translucent voxels of a chunk, see [ChunkMeshGenerator::generate_translucent_mesh]
+    ///
+    /// [ChunkMeshGenerator::generate_translucent_mesh]: crate::world::chunk_renderer::meshing::ChunkMeshGenerator::generate_translucent_mesh
+    pub fn insert_translucent(&mut self, ctx: &RenderCtx, location: ChunkLocation, vertices: Vec<Vertex>, indices: Vec<u32>) {
+        self.translucent
+            .insert(ctx, location, vertices, indices);
+    }
+
+    /// Removes the water surfaces and the translucent voxels of a chunk
+    pub fn remove(&mut self, location: ChunkLocation) {
+        self.water.remove(location);
+        self.translucent.remove(location);
+    }
+
+    pub fn animate(&mut self, dt: Duration) {
+        self.time += dt.as_secs_f32();
+    }
+
+    /// Orders the chunks from the farthest to the closest chunk to the camera, so that overlapping transparent surfaces blend correctly.
+    /// Surfaces within a chunk are not sorted.
+    pub fn sort_back_to_front(&mut self, camera_location: ChunkLocation) {
+        self.draw_order.clear();
+        self.draw_order
+            .extend(self.water.buffers.keys().copied());
+        self.draw_order.extend(
+            self.translucent
+                .buffers
+                .keys()
+                .filter(|location| !self.water.buffers.contains_key(location))
+                .copied(),
+        );
+
+        self.draw_order.sort_by_key(|&location| {
+            let offset = location - camera_location;
+            Reverse(offset.x * offset.x + offset.y * offset.y + offset.z * offset.z)
+        });
+    }
+}
+
+impl TransparentMeshes {
+    fn insert(&mut self, ctx: &RenderCtx, location: ChunkLocation, vertices: Vec<Vertex>, indices: Vec<u32>) {
+        if indices.is_empty() {
+            self.remove(location);
+            return;
+        }
+
+        self.buffers
+            .insert(location, WaterBuffers::new(ctx, &vertices, &indices));
+        self.meshes.insert(location, (vertices, indices));
+    }
+
+    fn remove(&mut self, location: ChunkLocation) {
+        self.meshes.remove(&location);
+        self.buffers.remove(&location);
+    }
+
+    fn rebuild_gpu_resources(&mut self, ctx: &RenderCtx) {
+        self.buffers.clear();
+
+        for (&location, (vertices, indices)) in &self.meshes {
+            self.buffers
+                .insert(location, WaterBuffers::new(ctx, vertices, indices));
+        }
+    }
+}
+
+impl WaterBuffers {
+    fn new(ctx: &RenderCtx, vertices: &[Vertex], indices: &[u32]) -> Self {
+        Self {
+            vertex_buffer: ctx
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Water vertex buffer"),
+                    usage: BufferUsages::VERTEX,
+                    contents: bytemuck::cast_slice(vertices),
+                }),
+            index_buffer: ctx
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Water index buffer"),
+                    usage: BufferUsages::INDEX,
+                    contents: bytemuck::cast_slice(indices),
+                }),
+            num_indices: indices.len() as u32,
+        }
+    }
+}
+
+impl Renderer for WaterRenderer {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera: UniformBinding<'a>, _render_ctx: &RenderCtx) {
+        camera.bind(render_pass, 0);
+
+        for location in &self.draw_order {
+            let position = location.to_world_location_f32();
+            let push_constants = [position.x, position.y, position.z, self.time];
+
+            for (pipeline, meshes) in [(&self.pipeline, &self.water), (&self.translucent_pipeline, &self.translucent)] {
+                let Some(buffers) = meshes.buffers.get(location) else {
+                    continue;
+                };
+
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_push_constants(ShaderStages::VERTEX_FRAGMENT, 0, bytemuck::cast_slice(&push_constants));
+                render_pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..buffers.num_indices, 0, 0..1);
+            }
+        }
+    }
+}
+
+fn create_water_pipelines(ctx: &RenderCtx) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("../water.wgsl"));
+
+    let layout = ctx.pipeline_layout(
+        "Water render pipeline layout",
+        &[CAMERA_BIND_GROUP_LAYOUT_ENTRIES],
+        &[PushConstantRange {
+            stages: ShaderStages::VERTEX_FRAGMENT,
+            range: 0..16,
+        }],
+    );
+
+    let create_pipeline = |label: &str, vertex_entry_point: &str, fragment_entry_point: &str, cull_mode: Option<wgpu::Face>| {
+        // Non sRGB surfaces need the shader to encode the color
+        let fragment_entry_point = if ctx.surface_format.is_srgb() {
+            fragment_entry_point.to_owned()
+        } else {
+            format!("{fragment_entry_point}_gamma")
+        };
+
+        ctx.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    buffers: &[Vertex::layout()],
+                    entry_point: vertex_entry_point,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    entry_point: &fragment_entry_point,
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode,
+                    ..Default::default()
+                },
+                // Hidden behind terrain, but does not hide anything itself
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+            })
+    };
+
+    (
+        // The surface is also visible from below when diving
+        create_pipeline("Water render pipeline", "vs_main", "fs_main", None),
+        create_pipeline(
+            "Translucent voxel render pipeline",
+            "vs_translucent",
+            "fs_translucent",
+            Some(wgpu::Face::Back),
+        ),
+    )
+}
@@ -0,0 +1,315 @@
+use cgmath::Vector3;
+use itertools::iproduct;
+
+use crate::rendering::frustum::Aabb;
+use crate::world::location::WorldLocation;
+
+/// Tolerance for boxes that exactly touch a voxel face, so that they are not considered to overlap it
+const SWEEP_EPSILON: f32 = 1e-4;
+
+/// An axis aligned box of solid voxels from `min` (inclusive) to `max` (exclusive)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CollisionBox {
+    pub min: Vector3<i32>,
+    pub max: Vector3<i32>,
+}
+
+impl CollisionBox {
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max).map(|c| c as f32 / 2.0)
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min).map(|c| c as f32 / 2.0)
+    }
+}
+
+/// Simplified collision geometry of all solid voxels in the region from `min` (inclusive) to `max` (exclusive),
+/// meant to be used by external physics engines.
+///
+/// Neighboring solid voxels are greedily merged into boxes, first along x, then along z and then along y.
+/// Every solid voxel is covered by exactly one box.
+pub fn collision_boxes(min: WorldLocation, max: WorldLocation, is_solid: impl Fn(WorldLocation) -> bool) -> Vec<CollisionBox> {
+    let size = (max.0 - min.0).map(|c| c.max(0) as usize);
+    let index = |p: Vector3<i32>| {
+        let local = (p - min.0).map(|c| c as usize);
+        (local.y * size.z + local.z) * size.x + local.x
+    };
+
+    let mut covered = vec![false; size.x * size.y * size.z];
+    let is_free_solid = |covered: &[bool], p: Vector3<i32>| !covered[index(p)] && is_solid(WorldLocation(p));
+
+    let mut boxes = Vec::new();
+    for (y, z, x) in iproduct!(min.0.y..max.0.y, min.0.z..max.0.z, min.0.x..max.0.x) {
+        let start = Vector3::new(x, y, z);
+        if !is_free_solid(&covered, start) {
+            continue;
+        }
+
+        let mut end = start + Vector3::new(1, 1, 1);
+        while end.x < max.0.x && is_free_solid(&covered, Vector3::new(end.x, y, z)) {
+            end.x += 1;
+        }
+        while end.z < max.0.z && (x..end.x).all(|x| is_free_solid(&covered, Vector3::new(x, y, end.z))) {
+            end.z += 1;
+        }
+        while end.y < max.0.y && iproduct!(x..end.x, z..end.z).all(|(x, z)| is_free_solid(&covered, Vector3::new(x, end.y, z))) {
+            end.y += 1;
+        }
+
+        for (y, z, x) in iproduct!(y..end.y, z..end.z, x..end.x) {
+            covered[index(Vector3::new(x, y, z))] = true;
+        }
+
+        boxes.push(CollisionBox { min: start, max: end });
+    }
+
+    boxes
+}
+
+/// Triangulates the surfaces of the boxes into a single mesh of vertex positions and triangle indices
+pub fn collision_mesh(boxes: &[CollisionBox]) -> (Vec<[f32; 3]>, Vec<[u32; 3]>) {
+    /// Corners of a unit cube and the triangles of its faces, wound counter-clockwise when seen from the outside
+    const CORNERS: [[i32; 3]; 8] = [
+        [0, 0, 0],
+        [1, 0, 0],
+        [1, 1, 0],
+        [0, 1, 0],
+        [0, 0, 1],
+        [1, 0, 1],
+        [1, 1, 1],
+        [0, 1, 1],
+    ];
+    const TRIANGLES: [[u32; 3]; 12] = [
+        [0, 2, 1],
+        [0, 3, 2],
+        [4, 5, 6],
+        [4, 6, 7],
+        [0, 1, 5],
+        [0, 5, 4],
+        [3, 6, 2],
+        [3, 7, 6],
+        [0, 4, 7],
+        [0, 7, 3],
+        [1, 2, 6],
+        [1, 6, 5],
+    ];
+
+    let mut vertices = Vec::with_capacity(boxes.len() * CORNERS.len());
+    let mut indices = Vec::with_capacity(boxes.len() * TRIANGLES.len());
+
+    for collision_box in boxes {
+        let base_index = vertices.len() as u32;
+
+        vertices.extend(CORNERS.iter().map(|corner| {
+            [0, 1, 2].map(|axis| {
+                let c = if corner[axis] == 0 {
+                    collision_box.min[axis]
+                } else {
+                    collision_box.max[axis]
+                };
+                c as f32
+            })
+        }));
+        indices.extend(
+            TRIANGLES
+                .iter()
+                .map(|triangle| triangle.map(|i| base_index + i)),
+        );
+    }
+
+    (vertices, indices)
+}
+
+/// Result of moving a box through the voxels, see [move_aabb]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AabbMovement {
+    pub aabb: Aabb,
+    /// Whether the movement was stopped by a solid voxel along the x, y and z axis
+    pub blocked: [bool; 3],
+}
+
+/// Moves a box by `movement` one axis after another, first along y and then along x and z.
+/// The box stops in front of the first solid voxel along every axis.
+pub fn move_aabb(aabb: Aabb, movement: Vector3<f32>, is_solid: impl Fn(WorldLocation) -> bool) -> AabbMovement {
+    let mut result = AabbMovement { aabb, blocked: [false; 3] };
+
+    for axis in [1, 0, 2] {
+        let (distance, blocked) = sweep_axis(result.aabb, axis, movement[axis], &is_solid);
+        result.aabb.min[axis] += distance;
+        result.aabb.max[axis] += distance;
+        result.blocked[axis] = blocked;
+    }
+
+    result
+}
+
+/// Like [move_aabb], but if the horizontal movement is blocked, the box may step up by at most `step_height` onto the obstacle.
+/// The step is only taken if it lets the box move further horizontally.
+pub fn move_aabb_with_step(aabb: Aabb, movement: Vector3<f32>, step_height: f32, is_solid: impl Fn(WorldLocation) -> bool) -> AabbMovement {
+    let direct = move_aabb(aabb, movement, &is_solid);
+    if step_height <= 0.0 || !(direct.blocked[0] || direct.blocked[2]) {
+        return direct;
+    }
+
+    let raised = move_aabb(aabb, Vector3::new(0.0, step_height, 0.0), &is_solid);
+    let moved = move_aabb(raised.aabb, Vector3::new(movement.x, 0.0, movement.z), &is_solid);
+    let lowered = move_aabb(
+        moved.aabb,
+        Vector3::new(0.0, movement.y.min(0.0) - (raised.aabb.min.y - aabb.min.y), 0.0),
+        &is_solid,
+    );
+
+    let horizontal_distance = |result: &AabbMovement| {
+        let offset = result.aabb.min - aabb.min;
+        offset.x * offset.x + offset.z * offset.z
+    };
+    if horizontal_distance(&lowered) > horizontal_distance(&direct) + SWEEP_EPSILON {
+        AabbMovement {
+            aabb: lowered.aabb,
+            blocked: [moved.blocked[0], lowered.blocked[1], moved.blocked[2]],
+        }
+    } else {
+        direct
+    }
+}
+
+/// Distance that the box can move along the axis before it hits a solid voxel and whether it was stopped
+fn sweep_axis(aabb: Aabb, axis: usize, distance: f32, is_solid: &impl Fn(WorldLocation) -> bool) -> (f32, bool) {
+    if distance == 0.0 {
+        return (0.0, false);
+    }
+
+    // Voxels that the box overlaps on the other two axes
+    let [u, v] = [(axis + 1) % 3, (axis + 2) % 3];
+    let cells = |axis: usize| (aabb.min[axis] + SWEEP_EPSILON).floor() as i32..(aabb.max[axis] - SWEEP_EPSILON).ceil() as i32;
+    let is_layer_solid = |layer: i32| {
+        iproduct!(cells(u), cells(v)).any(|(a, b)| {
+            let mut location = Vector3::new(0, 0, 0);
+            location[axis] = layer;
+            location[u] = a;
+            location[v] = b;
+            is_solid(WorldLocation(location))
+        })
+    };
+
+    if distance > 0.0 {
+        let face = aabb.max[axis];
+        for layer in (face - SWEEP_EPSILON).ceil() as i32..(face + distance).ceil() as i32 {
+            if is_layer_solid(layer) {
+                return ((layer as f32 - face).max(0.0), true);
+            }
+        }
+    } else {
+        let face = aabb.min[axis];
+        for layer in ((face + distance).floor() as i32..(face + SWEEP_EPSILON).floor() as i32).rev() {
+            if is_layer_solid(layer) {
+                return ((layer as f32 + 1.0 - face).min(0.0), true);
+            }
+        }
+    }
+
+    (distance, false)
+}
+
+/// A compound rapier collider with one cuboid per box
+#[cfg(feature = "rapier")]
+pub fn rapier_collider(boxes: &[CollisionBox]) -> rapier3d::geometry::ColliderBuilder {
+    use rapier3d::geometry::{ColliderBuilder, SharedShape};
+    use rapier3d::math::Isometry;
+
+    let shapes = boxes
+        .iter()
+        .map(|collision_box| {
+            let center = collision_box.center();
+            let half_extents = collision_box.half_extents();
+
+            (
+                Isometry::translation(center.x, center.y, center.z),
+                SharedShape::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            )
+        })
+        .collect();
+
+    ColliderBuilder::compound(shapes)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::rendering::frustum::Aabb;
+    use crate::world::collision::{collision_boxes, move_aabb, move_aabb_with_step, CollisionBox};
+    use crate::world::location::WorldLocation;
+
+    #[test]
+    fn test_collision_boxes_are_merged() {
+        // A solid 4x2x3 block with a single voxel column of height one on top of it
+        let is_solid = |location: WorldLocation| {
+            let p = location.0;
+            let lower = (0..4).contains(&p.x) && (0..2).contains(&p.y) && (0..3).contains(&p.z);
+            lower || p == Vector3::new(1, 2, 1)
+        };
+
+        let boxes = collision_boxes(
+            WorldLocation(Vector3::new(-2, -2, -2)),
+            WorldLocation(Vector3::new(8, 8, 8)),
+            is_solid,
+        );
+
+        assert_eq!(
+            boxes,
+            vec![
+                CollisionBox {
+                    min: Vector3::new(0, 0, 0),
+                    max: Vector3::new(4, 2, 3),
+                },
+                CollisionBox {
+                    min: Vector3::new(1, 2, 1),
+                    max: Vector3::new(2, 3, 2),
+                },
+            ]
+        );
+    }
+
+    /// Box of the size of the player, 0.6 × 1.8 × 0.6 blocks, with its bottom center at the given position
+    fn body_at(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb {
+            min: Vector3::new(x - 0.3, y, z - 0.3),
+            max: Vector3::new(x + 0.3, y + 1.8, z + 0.3),
+        }
+    }
+
+    #[test]
+    fn test_move_aabb_stops_at_walls_and_floor() {
+        // Floor at y = 0 and a wall at x = 2
+        let is_solid = |location: WorldLocation| location.0.y < 0 || location.0.x == 2;
+
+        let fall = move_aabb(body_at(0.5, 0.5, 0.5), Vector3::new(0.0, -2.0, 0.0), is_solid);
+        assert_eq!(fall.aabb.min.y, 0.0);
+        assert_eq!(fall.blocked, [false, true, false]);
+
+        let walk = move_aabb(body_at(0.5, 0.0, 0.5), Vector3::new(3.0, 0.0, 0.5), is_solid);
+        assert!((walk.aabb.max.x - 2.0).abs() < 1e-5);
+        assert!((walk.aabb.min.z - 0.7).abs() < 1e-5);
+        assert_eq!(walk.blocked, [true, false, false]);
+    }
+
+    #[test]
+    fn test_move_aabb_steps_up() {
+        // Floor at y = 0 with a step of a single voxel from x = 2 and a wall of two voxels from x = 6
+        let is_solid = |location: WorldLocation| {
+            let p = location.0;
+            p.y < 0 || (p.x >= 2 && p.y == 0) || (p.x >= 6 && p.y == 1)
+        };
+
+        let step = move_aabb_with_step(body_at(1.5, 0.0, 0.5), Vector3::new(1.0, -0.01, 0.0), 1.0, is_solid);
+        assert!((step.aabb.min.x - 2.2).abs() < 1e-5);
+        assert_eq!(step.aabb.min.y, 1.0);
+
+        let wall = move_aabb_with_step(body_at(5.5, 1.0, 0.5), Vector3::new(1.0, -0.01, 0.0), 0.5, is_solid);
+        assert!((wall.aabb.max.x - 6.0).abs() < 1e-5);
+        assert_eq!(wall.aabb.min.y, 1.0);
+        assert!(wall.blocked[0]);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use cgmath::Vector3;
+
+use crate::cvars::CVar;
+use crate::rendering::frustum::Aabb;
+use crate::world::chunk_manager::ChunkManager;
+use crate::world::collision::move_aabb;
+use crate::world::location::WorldLocation;
+use crate::world::voxel_data::VoxelType;
+
+/// Acceleration of entities that fall, in blocks per second squared
+pub(crate) static ENTITY_GRAVITY: CVar = cvar!("entities.gravity", 30.0);
+/// Fraction of the horizontal velocity that grounded entities keep per second
+const GROUND_FRICTION: f32 = 0.05;
+
+/// Handle of an entity, which stays valid until the entity is despawned
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(u64);
+
+/// Mesh with which an entity is drawn by the [crate::world::chunk_renderer::entity_renderer::EntityRenderer]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EntityMesh {
+    /// A voxel of the given type, stretched to the size of the entity
+    Voxel(VoxelType),
+}
+
+/// Something that moves through the world independently of the voxel grid, e.g. a falling block
+#[derive(Clone, Debug)]
+pub struct Entity {
+    /// Bottom center of the collider
+    pub position: Vector3<f32>,
+    /// Velocity in blocks per second
+    pub velocity: Vector3<f32>,
+    /// Size of the collider and the mesh
+    pub size: Vector3<f32>,
+    pub mesh: EntityMesh,
+    /// Whether the entity falls. Entities without gravity fly along their velocity until they hit a voxel.
+    pub gravity: bool,
+    /// Whether the entity stands on a voxel after the last update
+    pub grounded: bool,
+    /// Simulated time after which the entity is despawned, if any
+    pub lifetime: Option<Duration>,
+}
+
+impl Entity {
+    pub fn new(position: Vector3<f32>, size: Vector3<f32>, mesh: EntityMesh) -> Self {
+        Self {
+            position,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            size,
+            mesh,
+            gravity: true,
+            grounded: false,
+            lifetime: None,
+        }
+    }
+
+    /// Collider of the entity at its current position
+    pub fn aabb(&self) -> Aabb {
+        let half_extents = Vector3::new(self.size.x / 2.0, 0.0, self.size.z / 2.0);
+        Aabb {
+            min: self.position - half_extents,
+            max: self.position + half_extents + Vector3::new(0.0, self.size.y, 0.0),
+        }
+    }
+}
+
+/// All entities of the active dimension, which are simulated every tick beside the chunks
+#[derive(Default)]
+pub struct Entities {
+    entities: HashMap<EntityId, Entity>,
+    next_id: u64,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, entity: Entity) -> EntityId {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.entities.insert(id, entity);
+
+        id
+    }
+
+    pub fn despawn(&mut self, id: EntityId) -> Option<Entity> {
+        self.entities.remove(&id)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.entities.get_mut(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.entities
+            .iter()
+            .map(|(&id, entity)| (id, entity))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    /// Applies gravity and moves every entity through the voxels along its velocity, see [move_aabb].
+    ///
+    /// Entities in chunks that are not generated yet are frozen, so that they do not fall through the missing terrain.
+    /// Expired entities are despawned.
+    pub fn update(&mut self, chunk_manager: &ChunkManager, dt: Duration) {
+        for entity in self.entities.values_mut() {
+            entity.lifetime = entity
+                .lifetime
+                .map(|lifetime| lifetime.saturating_sub(dt));
+        }
+        self.entities
+            .retain(|_, entity| entity.lifetime != Some(Duration::ZERO));
+
+        let dt = dt.as_secs_f32();
+        let is_solid = |location: WorldLocation| chunk_manager.is_solid_at(location) == Some(true);
+        for entity in self.entities.values_mut() {
+            let location = WorldLocation(entity.position.map(|c| c.floor() as i32));
+            if chunk_manager.voxel_at(location).is_none() {
+                continue;
+            }
+
+            if entity.gravity {
+                entity.velocity.y -= dt * ENTITY_GRAVITY.get();
+            }
+            if entity.grounded {
+                let friction = GROUND_FRICTION.powf(dt);
+                entity.velocity.x *= friction;
+                entity.velocity.z *= friction;
+            }
+
+            let movement = move_aabb(entity.aabb(), entity.velocity * dt, is_solid);
+            entity.position = Vector3::new(
+                (movement.aabb.min.x + movement.aabb.max.x) / 2.0,
+                movement.aabb.min.y,
+                (movement.aabb.min.z + movement.aabb.max.z) / 2.0,
+            );
+            entity.grounded = entity.gravity && movement.blocked[1] && entity.velocity.y < 0.0;
+            for (axis, &blocked) in movement.blocked.iter().enumerate() {
+                if blocked {
+                    entity.velocity[axis] = 0.0;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// Keep in sync with CHUNK_SIZE in world/mod.rs
+const CHUNK_SIZE: i32 = 32;
+// The chunk and one layer of voxels of its neighbor chunks around it
+const PADDED_SIZE: i32 = 34;
+
+const OPAQUE: u32 = 0x100u;
+const MESHED: u32 = 0x200u;
+
+// Voxels of a chunk, see GpuMesher::insert
+struct ChunkVoxels {
+	// Top, side and bottom tile of the texture atlas of every voxel type, each plus one and 0 for untextured types
+	tiles: array<u32, 32>,
+	// Type of the voxel, whether it is opaque and meshed and by how many eighths of a voxel its top is lowered
+	grid: array<u32, 39304>,
+	// Color of every voxel of the chunk, 0xRRGGBB00
+	colors: array<u32, 32768>,
+}
+
+// Must match the input of vs_face_instance in shader.wgsl
+struct FaceInstance {
+	voxel_x_y_z_color_r: u32,
+	color_g_b_normal_lowered_texture: u32,
+	corners: u32,
+}
+
+// Arguments of draw_indirect, the number of faces is counted in the instance count
+struct DrawIndirect {
+	vertex_count: u32,
+	instance_count: atomic<u32>,
+	first_vertex: u32,
+	first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> voxels: ChunkVoxels;
+@group(0) @binding(1)
+var<storage, read_write> faces: array<FaceInstance>;
+@group(0) @binding(2)
+var<storage, read_write> draw: DrawIndirect;
+
+fn cell(position: vec3<i32>) -> u32 {
+	let padded = position + 1;
+	return voxels.grid[(padded.z * PADDED_SIZE + padded.y) * PADDED_SIZE + padded.x];
+}
+
+fn is_opaque(position: vec3<i32>) -> bool {
+	return (cell(position) & OPAQUE) != 0u;
+}
+
+// Same ambient occlusion as in ChunkMeshGenerator::generate_faces
+fn corner_ao(position: vec3<i32>, dir1: vec3<i32>, dir2: vec3<i32>) -> u32 {
+	let s1 = is_opaque(position + dir1);
+	let s2 = is_opaque(position + dir2);
+	let c = is_opaque(position + dir1 + dir2);
+
+	if (s1 && s2) {
+		return 0u;
+	}
+	return 3u - (u32(s1) + u32(s2) + u32(c));
+}
+
+// One invocation per voxel, which appends the visible faces of the voxel to the face instances
+@compute @workgroup_size(4, 4, 4)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+	let position = vec3<i32>(id);
+	let voxel = cell(position);
+	if ((voxel & MESHED) == 0u) {
+		return;
+	}
+
+	let ty = voxel & 0xFFu;
+	let lowered = (voxel >> 10u) & 7u;
+	let color = voxels.colors[(position.z * CHUNK_SIZE + position.y) * CHUNK_SIZE + position.x];
+
+	// In the order of Direction
+	var DIRECTIONS = array<vec3<i32>, 6>(
+		vec3(1, 0, 0),
+		vec3(-1, 0, 0),
+		vec3(0, 1, 0),
+		vec3(0, -1, 0),
+		vec3(0, 0, 1),
+		vec3(0, 0, -1));
+
+	for (var d = 0u; d < 6u; d++) {
+		let direction = DIRECTIONS[d];
+		let neighbor = cell(position + direction);
+
+		// Same rule as ChunkMeshGenerator::generate_culled_mesh
+		if ((neighbor & OPAQUE) != 0u || (neighbor & 0xFFu) == ty) {
+			continue;
+		}
+
+		let axis1 = abs(direction).yzx;
+		let axis2 = abs(direction).zxy;
+		let ao0 = corner_ao(position + direction, -axis1, -axis2);
+		let ao1 = corner_ao(position + direction, axis1, -axis2);
+		let ao2 = corner_ao(position + direction, -axis1, axis2);
+		let ao3 = corner_ao(position + direction, axis1, axis2);
+		let flipped = ao0 + ao3 <= ao1 + ao2;
+
+		// Top, bottom or side tile
+		let tile_shift = select(select(8u, 0u, d == 2u), 16u, d == 3u);
+		let texture = (voxels.tiles[ty] >> tile_shift) & 0xFFu;
+		// Normals are always positive, see Vertex::new
+		let normal = 2u - d / 2u;
+
+		let index = atomicAdd(&draw.instance_count, 1u);
+		if (index >= arrayLength(&faces)) {
+			atomicSub(&draw.instance_count, 1u);
+			return;
+		}
+
+		faces[index] = FaceInstance(
+			(u32(position.x) << 24u) | (u32(position.y) << 16u) | (u32(position.z) << 8u) | (color >> 24u),
+			((color << 8u) & 0xFFFF0000u) | (normal << 13u) | (lowered << 8u) | texture,
+			ao0 | (ao1 << 2u) | (ao2 << 4u) | (ao3 << 6u) | (u32(flipped) << 8u) | (d << 9u),
+		);
+	}
+}
@@ -0,0 +1,55 @@
+use enum_map::EnumMap;
+
+use crate::world::voxel_data::VoxelType;
+
+/// Number of collected blocks of every voxel type. Blocks are gained by breaking voxels and consumed by placing them.
+#[derive(Clone, Debug, Default)]
+pub struct Inventory {
+    counts: EnumMap<VoxelType, u32>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inventory with the given number of blocks of every voxel type
+    pub fn from_stacks(stacks: impl IntoIterator<Item = (VoxelType, u32)>) -> Self {
+        let mut inventory = Self::new();
+        for (ty, count) in stacks {
+            if ty != VoxelType::Air {
+                inventory.counts[ty] += count;
+            }
+        }
+
+        inventory
+    }
+
+    /// Number of blocks of every voxel type that there are any of
+    pub fn stacks(&self) -> impl Iterator<Item = (VoxelType, u32)> + '_ {
+        self.counts
+            .iter()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(ty, &count)| (ty, count))
+    }
+
+    pub fn count(&self, ty: VoxelType) -> u32 {
+        self.counts[ty]
+    }
+
+    pub fn add(&mut self, ty: VoxelType) {
+        if ty != VoxelType::Air {
+            self.counts[ty] += 1;
+        }
+    }
+
+    /// Removes a single block of the given type. Returns false if there is none left.
+    pub fn try_take(&mut self, ty: VoxelType) -> bool {
+        let Some(count) = self.counts[ty].checked_sub(1) else {
+            return false;
+        };
+
+        self.counts[ty] = count;
+        true
+    }
+}
@@ -0,0 +1,218 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+use std::mem;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex};
+
+/// Flag with which a queued or running job is told that its result is not needed anymore
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.0.load(atomic::Ordering::Relaxed)
+    }
+}
+
+/// A job that was taken from a [JobQueue] by a worker thread
+pub struct Job<K, T> {
+    pub key: K,
+    pub payload: T,
+    pub token: CancellationToken,
+    priority: i32,
+    /// Jobs with the same priority are taken in the order in which they were queued
+    sequence: u64,
+}
+
+impl<K, T> PartialEq for Job<K, T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.priority, self.sequence) == (other.priority, other.sequence)
+    }
+}
+
+impl<K, T> Eq for Job<K, T> {}
+
+impl<K, T> PartialOrd for Job<K, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K, T> Ord for Job<K, T> {
+    /// The job with the lowest priority value and then the lowest sequence number is the greatest, so that it is at the top of the heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.priority, other.sequence).cmp(&(self.priority, self.sequence))
+    }
+}
+
+/// Jobs for worker threads, which are taken with the lowest priority value first.
+///
+/// Jobs are identified by their key, with which queued jobs can be canceled and running jobs can be told to stop,
+/// e.g. the generation of chunks that the player moved away from.
+pub struct JobQueue<K, T> {
+    state: Mutex<JobQueueState<K, T>>,
+}
+
+struct JobQueueState<K, T> {
+    queued: BinaryHeap<Job<K, T>>,
+    /// Tokens of the jobs that were taken by a worker and not finished yet
+    running: HashMap<K, CancellationToken>,
+    next_sequence: u64,
+}
+
+impl<K: Hash + Eq + Clone, T> JobQueue<K, T> {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(JobQueueState {
+                queued: BinaryHeap::new(),
+                running: HashMap::new(),
+                next_sequence: 0,
+            }),
+        }
+    }
+
+    pub fn push(&self, key: K, payload: T, priority: i32) {
+        let mut state = self.state.lock().unwrap();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.queued.push(Job {
+            key,
+            payload,
+            token: CancellationToken::default(),
+            priority,
+            sequence,
+        });
+    }
+
+    /// Takes up to the given number of jobs with the lowest priority values. They are running until [JobQueue::finish] is called.
+    pub fn take_n(&self, max_num_jobs: usize) -> Vec<Job<K, T>> {
+        let mut state = self.state.lock().unwrap();
+
+        let mut jobs = Vec::new();
+        while jobs.len() < max_num_jobs {
+            let Some(job) = state.queued.pop() else {
+                break;
+            };
+            state
+                .running
+                .insert(job.key.clone(), job.token.clone());
+            jobs.push(job);
+        }
+
+        jobs
+    }
+
+    /// Marks a taken job as finished, after which it cannot be canceled anymore
+    pub fn finish(&self, job: &Job<K, T>) {
+        self.state
+            .lock()
+            .unwrap()
+            .running
+            .remove(&job.key);
+    }
+
+    /// Changes the priorities of all queued jobs, e.g. after the player moved
+    pub fn reprioritize(&self, mut priority: impl FnMut(&K) -> i32) {
+        let mut state = self.state.lock().unwrap();
+        let mut jobs = mem::take(&mut state.queued).into_vec();
+        for job in &mut jobs {
+            job.priority = priority(&job.key);
+        }
+        state.queued = BinaryHeap::from(jobs);
+    }
+
+    /// Removes the queued jobs for which `cancel` returns true and cancels the running ones
+    pub fn cancel_where(&self, mut cancel: impl FnMut(&K) -> bool) {
+        let mut state = self.state.lock().unwrap();
+        state.queued.retain(|job| !cancel(&job.key));
+        state.running.retain(|key, token| {
+            if cancel(key) {
+                token.cancel();
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Keys of the queued jobs in the order in which they are taken, without the running ones
+    pub fn queued_keys(&self) -> Vec<K> {
+        let state = self.state.lock().unwrap();
+        let mut jobs = state.queued.iter().collect::<Vec<_>>();
+        jobs.sort_by(|a, b| b.cmp(a));
+
+        jobs.into_iter()
+            .map(|job| job.key.clone())
+            .collect()
+    }
+
+    /// Keys of the jobs that were taken by a worker and not finished yet
+    pub fn running_keys(&self) -> Vec<K> {
+        self.state
+            .lock()
+            .unwrap()
+            .running
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Number of queued jobs, without the running ones
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().queued.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::job_queue::JobQueue;
+
+    #[test]
+    fn test_jobs_are_taken_by_priority() {
+        let queue = JobQueue::new();
+        queue.push(1, "far", 10);
+        queue.push(2, "near", 1);
+        queue.push(3, "also near", 1);
+
+        let keys = queue
+            .take_n(2)
+            .iter()
+            .map(|job| job.key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec![2, 3]);
+
+        queue.push(4, "nearer after moving", 20);
+        queue.reprioritize(|&key| if key == 4 { 0 } else { 5 });
+        assert_eq!(queue.take_n(1)[0].key, 4);
+    }
+
+    #[test]
+    fn test_queued_keys_are_listed_in_the_order_they_are_taken() {
+        let queue = JobQueue::new();
+        queue.push(1, (), 10);
+        queue.push(2, (), 1);
+        queue.push(3, (), 5);
+        queue.push(4, (), 1);
+        queue.take_n(1);
+
+        assert_eq!(queue.queued_keys(), vec![4, 3, 1]);
+        assert_eq!(queue.running_keys(), vec![2]);
+    }
+
+    #[test]
+    fn test_canceled_jobs() {
+        let queue = JobQueue::new();
+        queue.push(1, (), 0);
+        queue.push(2, (), 0);
+        let running = queue.take_n(1);
+
+        queue.cancel_where(|_| true);
+
+        assert_eq!(queue.len(), 0);
+        assert!(running[0].token.is_canceled());
+    }
+}
@@ -5,11 +5,11 @@ use std::ops::{Add, Deref, DerefMut, Sub};
 use cgmath::Vector3;
 use itertools::iproduct;
 
-use crate::vector_utils::RemEuclid;
+use crate::vector_utils::{MapElemWise, RemEuclid};
 use crate::world::CHUNK_SIZE;
 
 /// An absolute location in the world. It contains a chunk location and a local chunk location encoded into a single Vector3.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct WorldLocation(pub Vector3<i32>);
 
 impl WorldLocation {
@@ -18,16 +18,10 @@ impl WorldLocation {
     }
 
     pub fn separate(self) -> (ChunkLocation, LocalChunkLocation<WithinBounds>) {
-        let mut chunk_location = ChunkLocation::new(self.0 / CHUNK_SIZE as i32);
-        if self.0.x < 0 {
-            chunk_location.0.x -= 1;
-        }
-        if self.0.y < 0 {
-            chunk_location.0.y -= 1;
-        }
-        if self.0.z < 0 {
-            chunk_location.0.z -= 1;
-        }
+        let chunk_location = ChunkLocation::new(
+            self.0
+                .map_elem_wise(|c| c.div_euclid(CHUNK_SIZE as i32)),
+        );
         let local_chunk_location = LocalChunkLocation::new_unchecked(self.0.rem_euclid(CHUNK_SIZE as i32));
 
         (chunk_location, local_chunk_location)
@@ -244,5 +238,9 @@ mod tests {
             negative_world_location.separate().1.location,
             Vector3::new(CHUNK_SIZE as i32 - 1, CHUNK_SIZE as i32 - 1, 1)
         );
+
+        let negative_chunk_border = WorldLocation(Vector3::new(-32, 0, -64));
+        assert_eq!(negative_chunk_border.separate().0 .0, Vector3::new(-1, 0, -2));
+        assert_eq!(negative_chunk_border.separate().1.location, Vector3::new(0, 0, 0));
     }
 }
@@ -1,9 +1,25 @@
 mod awesome_queue;
+pub mod block_breaking;
 pub mod chunk_data;
 pub mod chunk_manager;
 pub mod chunk_renderer;
+pub mod collision;
+pub mod entities;
+pub mod inventory;
+mod job_queue;
 pub mod location;
+pub mod noise_source;
+pub mod occupancy;
+pub mod palette;
+pub mod pathfinding;
+pub mod players;
+pub mod portals;
+pub mod raycast;
+pub mod storage;
 pub mod voxel_data;
+pub mod world_config;
+pub mod world_edits;
 pub mod worldgen;
+pub mod worldgen_preset;
 
 pub const CHUNK_SIZE: usize = 32;
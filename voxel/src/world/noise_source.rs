@@ -0,0 +1,175 @@
+use noise::{NoiseFn, OpenSimplex, Perlin};
+use serde::{Deserialize, Serialize};
+
+/// A deterministic 2D noise function, which returns values from roughly -1.0 to 1.0
+pub trait NoiseSource: Send + Sync {
+    fn sample(&self, point: [f64; 2]) -> f64;
+}
+
+impl NoiseSource for Perlin {
+    fn sample(&self, point: [f64; 2]) -> f64 {
+        self.get(point)
+    }
+}
+
+impl NoiseSource for OpenSimplex {
+    fn sample(&self, point: [f64; 2]) -> f64 {
+        self.get(point)
+    }
+}
+
+/// Fractal noise made of sharp ridges, which looks like mountain ranges
+pub struct RidgedFbm {
+    pub source: Box<dyn NoiseSource>,
+    pub octaves: u32,
+    /// Factor by which the frequency grows from one octave to the next
+    pub lacunarity: f64,
+    /// Factor by which the amplitude shrinks from one octave to the next
+    pub gain: f64,
+}
+
+impl NoiseSource for RidgedFbm {
+    fn sample(&self, point: [f64; 2]) -> f64 {
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut total_amplitude = 0.0;
+
+        for octave in 0..self.octaves {
+            // Offset every octave, so that the ridges of different octaves do not all cross at the origin
+            let offset = octave as f64 * 17.3;
+            let ridge = 1.0
+                - self
+                    .source
+                    .sample([point[0] * frequency + offset, point[1] * frequency + offset])
+                    .abs();
+
+            total += ridge * ridge * amplitude;
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if total_amplitude == 0.0 {
+            return 0.0;
+        }
+
+        2.0 * total / total_amplitude - 1.0
+    }
+}
+
+/// Samples a noise source at coordinates that are displaced by another noise, which breaks up grid aligned features
+pub struct DomainWarp {
+    pub source: Box<dyn NoiseSource>,
+    pub warp: Box<dyn NoiseSource>,
+    /// Maximum displacement in units of the sampled coordinates
+    pub strength: f64,
+    /// Frequency of the displacement relative to the sampled coordinates
+    pub scale: f64,
+}
+
+impl NoiseSource for DomainWarp {
+    fn sample(&self, point: [f64; 2]) -> f64 {
+        let warp_point = [point[0] * self.scale, point[1] * self.scale];
+        let dx = self.warp.sample(warp_point);
+        let dz = self
+            .warp
+            .sample([warp_point[0] + 5.2, warp_point[1] + 1.3]);
+
+        self.source
+            .sample([point[0] + self.strength * dx, point[1] + self.strength * dz])
+    }
+}
+
+/// Configuration of a noise source, which can be stored in worldgen presets
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum NoiseKind {
+    #[default]
+    Perlin,
+    OpenSimplex,
+    RidgedFbm {
+        source: Box<NoiseKind>,
+        octaves: u32,
+        lacunarity: f64,
+        gain: f64,
+    },
+    DomainWarp {
+        source: Box<NoiseKind>,
+        strength: f64,
+        scale: f64,
+    },
+}
+
+impl NoiseKind {
+    /// One example of every kind of noise, e.g. to choose from in the ui
+    pub fn examples() -> Vec<NoiseKind> {
+        vec![
+            NoiseKind::Perlin,
+            NoiseKind::OpenSimplex,
+            NoiseKind::RidgedFbm {
+                source: Box::new(NoiseKind::OpenSimplex),
+                octaves: 4,
+                lacunarity: 2.0,
+                gain: 0.5,
+            },
+            NoiseKind::DomainWarp {
+                source: Box::new(NoiseKind::OpenSimplex),
+                strength: 0.8,
+                scale: 0.5,
+            },
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoiseKind::Perlin => "Perlin",
+            NoiseKind::OpenSimplex => "OpenSimplex",
+            NoiseKind::RidgedFbm { .. } => "Ridged fBm",
+            NoiseKind::DomainWarp { .. } => "Domain warp",
+        }
+    }
+
+    pub fn build(&self, seed: u32) -> Box<dyn NoiseSource> {
+        match self {
+            NoiseKind::Perlin => Box::new(Perlin::new(seed)),
+            NoiseKind::OpenSimplex => Box::new(OpenSimplex::new(seed)),
+            NoiseKind::RidgedFbm {
+                source,
+                octaves,
+                lacunarity,
+                gain,
+            } => Box::new(RidgedFbm {
+                source: source.build(seed),
+                octaves: *octaves,
+                lacunarity: *lacunarity,
+                gain: *gain,
+            }),
+            NoiseKind::DomainWarp { source, strength, scale } => Box::new(DomainWarp {
+                source: source.build(seed),
+                warp: source.build(seed.wrapping_add(1)),
+                strength: *strength,
+                scale: *scale,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::noise_source::NoiseKind;
+
+    #[test]
+    fn test_noise_kinds_stay_in_range() {
+        for kind in NoiseKind::examples() {
+            let noise = kind.build(42);
+
+            for i in 0..1000 {
+                let point = [i as f64 * 0.37, i as f64 * -0.21 + 3.0];
+                let value = noise.sample(point);
+
+                assert!((-1.5..=1.5).contains(&value), "{} returned {value}", kind.name());
+                assert_eq!(value, noise.sample(point), "{} is not deterministic", kind.name());
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::borrow::Cow;
+
+use crate::world::chunk_data::ChunkData;
+use crate::world::location::{LocalChunkLocation, WithinBounds};
+use crate::world::voxel_data::VoxelType;
+use crate::world::CHUNK_SIZE;
+
+/// Number of rows of voxels along x in a chunk, which is the number of words of an [OccupancyMask]
+pub const OCCUPANCY_ROWS: usize = CHUNK_SIZE * CHUNK_SIZE;
+
+// Every row of voxels along x fits into a single word
+static_assertions::const_assert_eq!(CHUNK_SIZE, u32::BITS as usize);
+
+/// One bit per voxel of a chunk, so that checking a property of a voxel does not have to read its voxel data.
+///
+/// Every row of voxels along x is stored in one word at index `z * CHUNK_SIZE + y`, with the voxel at x in bit x.
+/// This is also the layout of the occupancy that the ray traced lighting uploads. Uniform masks do not store any rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OccupancyMask {
+    Uniform(bool),
+    Rows(Box<[u32; OCCUPANCY_ROWS]>),
+}
+
+impl OccupancyMask {
+    /// Mask of the voxels whose type matches the predicate
+    pub fn new(data: &ChunkData, predicate: impl Fn(VoxelType) -> bool) -> Self {
+        if let ChunkData::UniformType(voxel_data) = data {
+            return Self::Uniform(predicate(voxel_data.ty));
+        }
+
+        let mut rows = Box::new([0; OCCUPANCY_ROWS]);
+        for index in 0..CHUNK_SIZE.pow(3) {
+            if predicate(data.get_voxel_by_index(index).ty) {
+                rows[index / CHUNK_SIZE] |= 1 << (index % CHUNK_SIZE);
+            }
+        }
+
+        let mut mask = Self::Rows(rows);
+        mask.try_convert_into_uniform();
+        mask
+    }
+
+    pub fn get(&self, location: LocalChunkLocation<WithinBounds>) -> bool {
+        match self {
+            Self::Uniform(value) => *value,
+            Self::Rows(rows) => (rows[row_index(location)] >> location.x) & 1 != 0,
+        }
+    }
+
+    pub fn set(&mut self, location: LocalChunkLocation<WithinBounds>, value: bool) {
+        if let Self::Uniform(uniform_value) = *self {
+            if uniform_value == value {
+                return;
+            }
+            *self = Self::Rows(Box::new([if uniform_value { u32::MAX } else { 0 }; OCCUPANCY_ROWS]));
+        }
+
+        let Self::Rows(rows) = self else {
+            unreachable!("uniform masks were converted above");
+        };
+        let row = &mut rows[row_index(location)];
+        if value {
+            *row |= 1 << location.x;
+        } else {
+            *row &= !(1 << location.x);
+        }
+    }
+
+    /// All rows of the mask, see [OccupancyMask] for their layout
+    pub fn rows(&self) -> Cow<'_, [u32; OCCUPANCY_ROWS]> {
+        match self {
+            Self::Uniform(value) => Cow::Owned([if *value { u32::MAX } else { 0 }; OCCUPANCY_ROWS]),
+            Self::Rows(rows) => Cow::Borrowed(rows),
+        }
+    }
+
+    fn try_convert_into_uniform(&mut self) {
+        if let Self::Rows(rows) = self {
+            if rows.iter().all(|&row| row == 0) {
+                *self = Self::Uniform(false);
+            } else if rows.iter().all(|&row| row == u32::MAX) {
+                *self = Self::Uniform(true);
+            }
+        }
+    }
+}
+
+/// Which voxels of a chunk are solid and which are opaque, see [VoxelType::is_solid] and [VoxelType::is_opaque].
+///
+/// It is kept next to the voxel data of every generated chunk and must be updated whenever one of its voxels changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkOccupancy {
+    pub solid: OccupancyMask,
+    pub opaque: OccupancyMask,
+}
+
+impl ChunkOccupancy {
+    /// Occupancy of a chunk without any solid voxels
+    pub const EMPTY: Self = Self {
+        solid: OccupancyMask::Uniform(false),
+        opaque: OccupancyMask::Uniform(false),
+    };
+
+    pub fn new(data: &ChunkData) -> Self {
+        Self {
+            solid: OccupancyMask::new(data, VoxelType::is_solid),
+            opaque: OccupancyMask::new(data, VoxelType::is_opaque),
+        }
+    }
+
+    /// Updates the occupancy after the type of a voxel changed
+    pub fn update(&mut self, location: LocalChunkLocation<WithinBounds>, voxel_type: VoxelType) {
+        self.solid.set(location, voxel_type.is_solid());
+        self.opaque.set(location, voxel_type.is_opaque());
+    }
+}
+
+fn row_index(location: LocalChunkLocation<WithinBounds>) -> usize {
+    location.z as usize * CHUNK_SIZE + location.y as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::location::LocalChunkLocation;
+    use crate::world::occupancy::{ChunkOccupancy, OccupancyMask};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    #[test]
+    fn test_occupancy_bits_match_opaque_voxels() {
+        let mut data = ChunkData::new_filled_with_uniform_data(VoxelData::new(VoxelType::Air));
+        data.set_voxel_data(
+            LocalChunkLocation::new_unchecked(Vector3::new(3, 1, 2)),
+            VoxelData::new(VoxelType::Stone),
+        );
+        data.set_voxel_data(
+            LocalChunkLocation::new_unchecked(Vector3::new(4, 1, 2)),
+            VoxelData::new(VoxelType::Water),
+        );
+
+        let rows = ChunkOccupancy::new(&data)
+            .opaque
+            .rows()
+            .into_owned();
+        assert_eq!(rows[2 * 32 + 1], 1 << 3);
+        assert_eq!(
+            rows.iter()
+                .map(|row| row.count_ones())
+                .sum::<u32>(),
+            1
+        );
+
+        let solid = ChunkData::new_filled_with_uniform_data(VoxelData::new(VoxelType::Stone));
+        assert_eq!(ChunkOccupancy::new(&solid).opaque, OccupancyMask::Uniform(true));
+        assert!(ChunkOccupancy::new(&solid)
+            .opaque
+            .rows()
+            .iter()
+            .all(|&row| row == u32::MAX));
+    }
+
+    #[test]
+    fn test_occupancy_follows_edits() {
+        let location = LocalChunkLocation::new_unchecked(Vector3::new(31, 0, 5));
+        let mut occupancy = ChunkOccupancy::EMPTY;
+
+        occupancy.update(location, VoxelType::Glass);
+        assert!(occupancy.solid.get(location));
+        assert!(!occupancy.opaque.get(location));
+        assert!(!occupancy
+            .solid
+            .get(LocalChunkLocation::new_unchecked(Vector3::new(30, 0, 5))));
+
+        occupancy.update(location, VoxelType::Air);
+        assert!(!occupancy.solid.get(location));
+    }
+}
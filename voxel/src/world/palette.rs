@@ -0,0 +1,183 @@
+use std::mem;
+
+use crate::world::voxel_data::VoxelData;
+use crate::world::CHUNK_SIZE;
+
+const VOXELS: usize = CHUNK_SIZE.pow(3);
+
+/// Voxels of a chunk stored as bit-packed indices into a small palette of the distinct voxels of the chunk.
+///
+/// Indices never cross the boundary of a word, so a word holds `64 / bits_per_index` indices and the remaining bits are unused.
+/// The palette only grows while voxels are set, see [PalettedVoxels::compact] to drop unused entries.
+#[derive(Clone, Debug)]
+pub struct PalettedVoxels {
+    palette: Vec<VoxelData>,
+    bits_per_index: u32,
+    words: Vec<u64>,
+}
+
+impl PalettedVoxels {
+    /// A chunk in which every voxel has the same data
+    pub fn new_uniform(voxel_data: VoxelData) -> Self {
+        Self {
+            palette: vec![voxel_data],
+            bits_per_index: 1,
+            words: vec![0; words_for(1)],
+        }
+    }
+
+    pub fn from_voxels(voxels: impl IntoIterator<Item = VoxelData>) -> Self {
+        let mut palette = Vec::new();
+        let indices: Vec<usize> = voxels
+            .into_iter()
+            .map(|voxel| match palette.iter().position(|&other| other == voxel) {
+                Some(index) => index,
+                None => {
+                    palette.push(voxel);
+                    palette.len() - 1
+                }
+            })
+            .collect();
+        assert_eq!(indices.len(), VOXELS, "expected the voxels of a whole chunk");
+
+        let bits_per_index = bits_for(palette.len());
+        let mut voxels = Self {
+            palette,
+            bits_per_index,
+            words: vec![0; words_for(bits_per_index)],
+        };
+        for (index, palette_index) in indices.into_iter().enumerate() {
+            voxels.write_index(index, palette_index);
+        }
+
+        voxels
+    }
+
+    /// Voxel at an index of [crate::world::chunk_data::ChunkData::position_to_index]
+    pub fn get(&self, index: usize) -> &VoxelData {
+        &self.palette[self.read_index(index)]
+    }
+
+    pub fn set(&mut self, index: usize, voxel_data: VoxelData) {
+        let palette_index = match self
+            .palette
+            .iter()
+            .position(|&other| other == voxel_data)
+        {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(voxel_data);
+                if bits_for(self.palette.len()) > self.bits_per_index {
+                    self.repack(bits_for(self.palette.len()));
+                }
+                self.palette.len() - 1
+            }
+        };
+
+        self.write_index(index, palette_index);
+    }
+
+    /// The only voxel of the palette, if every voxel is the same
+    pub fn uniform_voxel(&self) -> Option<VoxelData> {
+        let first = *self.get(0);
+        (0..VOXELS)
+            .all(|index| *self.get(index) == first)
+            .then_some(first)
+    }
+
+    /// Removes palette entries that are no longer used by any voxel and shrinks the indices accordingly
+    pub fn compact(&mut self) {
+        *self = Self::from_voxels((0..VOXELS).map(|index| *self.get(index)));
+    }
+
+    pub fn palette_len(&self) -> usize {
+        self.palette.len()
+    }
+
+    /// Size of the palette and the packed indices in memory
+    pub fn size_in_bytes(&self) -> usize {
+        self.palette.len() * mem::size_of::<VoxelData>() + self.words.len() * mem::size_of::<u64>()
+    }
+
+    fn read_index(&self, index: usize) -> usize {
+        let (word, shift) = self.locate(index);
+        ((self.words[word] >> shift) & self.mask()) as usize
+    }
+
+    fn write_index(&mut self, index: usize, palette_index: usize) {
+        let (word, shift) = self.locate(index);
+        let mask = self.mask();
+        self.words[word] = (self.words[word] & !(mask << shift)) | ((palette_index as u64 & mask) << shift);
+    }
+
+    /// Word and bit offset of the index of a voxel
+    fn locate(&self, index: usize) -> (usize, u32) {
+        let per_word = (u64::BITS / self.bits_per_index) as usize;
+        (index / per_word, (index % per_word) as u32 * self.bits_per_index)
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits_per_index) - 1
+    }
+
+    fn repack(&mut self, bits_per_index: u32) {
+        let previous = mem::replace(
+            self,
+            Self {
+                palette: Vec::new(),
+                bits_per_index,
+                words: vec![0; words_for(bits_per_index)],
+            },
+        );
+
+        for index in 0..VOXELS {
+            self.write_index(index, previous.read_index(index));
+        }
+        self.palette = previous.palette;
+    }
+}
+
+/// Bits that are needed to index a palette of this length, at least one
+fn bits_for(palette_len: usize) -> u32 {
+    (usize::BITS - palette_len.saturating_sub(1).leading_zeros()).max(1)
+}
+
+fn words_for(bits_per_index: u32) -> usize {
+    VOXELS.div_ceil((u64::BITS / bits_per_index) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::palette::PalettedVoxels;
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+    use crate::world::CHUNK_SIZE;
+
+    #[test]
+    fn test_palette_grows_and_compacts() {
+        let mut voxels = PalettedVoxels::new_uniform(VoxelData::new(VoxelType::Air));
+        let types = [
+            VoxelType::Dirt,
+            VoxelType::Grass,
+            VoxelType::Stone,
+            VoxelType::Sand,
+            VoxelType::Water,
+        ];
+        for (index, &ty) in types.iter().enumerate() {
+            voxels.set(index * 1000, VoxelData::new(ty));
+        }
+
+        assert_eq!(voxels.palette_len(), 6);
+        for (index, &ty) in types.iter().enumerate() {
+            assert_eq!(voxels.get(index * 1000).ty, ty);
+            assert_eq!(voxels.get(index * 1000 + 1).ty, VoxelType::Air);
+        }
+        assert!(voxels.size_in_bytes() < CHUNK_SIZE.pow(3) * std::mem::size_of::<VoxelData>());
+
+        for index in 0..types.len() {
+            voxels.set(index * 1000, VoxelData::new(VoxelType::Air));
+        }
+        voxels.compact();
+        assert_eq!(voxels.palette_len(), 1);
+        assert_eq!(voxels.uniform_voxel(), Some(VoxelData::new(VoxelType::Air)));
+    }
+}
@@ -0,0 +1,192 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use cgmath::Vector3;
+
+use crate::world::location::WorldLocation;
+
+/// Costs of the moves between walkable voxels, see [is_walkable]
+#[derive(Copy, Clone, Debug)]
+pub struct PathCosts {
+    /// Cost of walking one voxel horizontally
+    pub step: f32,
+    /// Additional cost of jumping one voxel up
+    pub jump: f32,
+    /// Additional cost per voxel of falling down
+    pub fall_per_voxel: f32,
+    /// Highest drop that is taken
+    pub max_fall: i32,
+    /// Number of visited voxels after which the search gives up
+    pub max_visited: usize,
+}
+
+impl Default for PathCosts {
+    fn default() -> Self {
+        Self {
+            step: 1.0,
+            jump: 2.0,
+            fall_per_voxel: 0.5,
+            max_fall: 3,
+            max_visited: 20_000,
+        }
+    }
+}
+
+/// Finds the cheapest path of walkable voxels from `start` to `goal` with A*.
+///
+/// Between two voxels of the path, the feet move one voxel horizontally and possibly jump up by one or fall down.
+/// Returns all voxels of the path including the start and the goal, or None if there is no path within [PathCosts::max_visited].
+pub fn find_path(
+    start: WorldLocation,
+    goal: WorldLocation,
+    costs: &PathCosts,
+    is_solid: impl Fn(WorldLocation) -> bool,
+) -> Option<Vec<WorldLocation>> {
+    let walkable = |location: Vector3<i32>| is_walkable(WorldLocation(location), &is_solid);
+    let heuristic = |location: Vector3<i32>| ((goal.0.x - location.x).abs() + (goal.0.z - location.z).abs()) as f32 * costs.step;
+
+    let mut open = BinaryHeap::new();
+    let mut came_from = HashMap::new();
+    let mut cost_so_far = HashMap::from([(start.0, 0.0)]);
+    open.push(OpenNode {
+        estimated_cost: heuristic(start.0),
+        location: start.0,
+    });
+
+    while let Some(OpenNode { location, .. }) = open.pop() {
+        if location == goal.0 {
+            let mut path = vec![WorldLocation(location)];
+            let mut current = location;
+            while let Some(&previous) = came_from.get(&current) {
+                path.push(WorldLocation(previous));
+                current = previous;
+            }
+            path.reverse();
+
+            return Some(path);
+        }
+
+        if cost_so_far.len() > costs.max_visited {
+            return None;
+        }
+
+        let current_cost = cost_so_far[&location];
+        for (neighbor, move_cost) in neighbors(location, costs, &walkable, &is_solid) {
+            let cost = current_cost + move_cost;
+            if cost_so_far
+                .get(&neighbor)
+                .is_some_and(|&known| known <= cost)
+            {
+                continue;
+            }
+
+            cost_so_far.insert(neighbor, cost);
+            came_from.insert(neighbor, location);
+            open.push(OpenNode {
+                estimated_cost: cost + heuristic(neighbor),
+                location: neighbor,
+            });
+        }
+    }
+
+    None
+}
+
+/// Whether the feet can be at the given voxel, i.e. it and the voxel above it are free and the voxel below it is solid
+pub fn is_walkable(location: WorldLocation, is_solid: impl Fn(WorldLocation) -> bool) -> bool {
+    let location = location.0;
+    !is_solid(WorldLocation(location)) && !is_solid(WorldLocation(location + UP)) && is_solid(WorldLocation(location - UP))
+}
+
+const UP: Vector3<i32> = Vector3::new(0, 1, 0);
+const HORIZONTAL_DIRECTIONS: [Vector3<i32>; 4] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(-1, 0, 0),
+    Vector3::new(0, 0, 1),
+    Vector3::new(0, 0, -1),
+];
+
+/// All walkable voxels that can be reached from `location` with a single move, together with the cost of the move
+fn neighbors(
+    location: Vector3<i32>,
+    costs: &PathCosts,
+    walkable: &impl Fn(Vector3<i32>) -> bool,
+    is_solid: &impl Fn(WorldLocation) -> bool,
+) -> Vec<(Vector3<i32>, f32)> {
+    let mut neighbors = Vec::new();
+
+    for direction in HORIZONTAL_DIRECTIONS {
+        let next = location + direction;
+
+        if walkable(next) {
+            neighbors.push((next, costs.step));
+        } else if is_solid(WorldLocation(next)) {
+            // Jumping needs room above the head before moving onto the higher voxel
+            if walkable(next + UP) && !is_solid(WorldLocation(location + UP * 2)) {
+                neighbors.push((next + UP, costs.step + costs.jump));
+            }
+        } else if !is_solid(WorldLocation(next + UP)) {
+            // Falls down to the first voxel with ground below it
+            if let Some(drop) = (1..=costs.max_fall).find(|&drop| is_solid(WorldLocation(next - UP * (drop + 1)))) {
+                neighbors.push((next - UP * drop, costs.step + costs.fall_per_voxel * drop as f32));
+            }
+        }
+    }
+
+    neighbors
+}
+
+/// A voxel that is yet to be visited, ordered so that the voxel with the lowest estimated cost is visited first
+struct OpenNode {
+    estimated_cost: f32,
+    location: Vector3<i32>,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimated_cost
+            .total_cmp(&self.estimated_cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::location::WorldLocation;
+    use crate::world::pathfinding::{find_path, PathCosts};
+
+    #[test]
+    fn test_path_over_a_step() {
+        // Flat ground at y < 0 with a wall of height one at x = 3
+        let is_solid = |location: WorldLocation| location.0.y < 0 || (location.0.x == 3 && location.0.y == 0);
+
+        let path = find_path(
+            WorldLocation(Vector3::new(0, 0, 0)),
+            WorldLocation(Vector3::new(6, 0, 0)),
+            &PathCosts::default(),
+            is_solid,
+        )
+        .expect("a path to be found");
+
+        assert_eq!(path.first(), Some(&WorldLocation(Vector3::new(0, 0, 0))));
+        assert_eq!(path.last(), Some(&WorldLocation(Vector3::new(6, 0, 0))));
+        assert_eq!(path.len(), 7);
+        assert!(path.contains(&WorldLocation(Vector3::new(3, 1, 0))));
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::game_mode::GameMode;
+use crate::ron_file;
+use crate::world::voxel_data::VoxelType;
+
+/// Name of the file in the world directory in which the state of all players is saved
+pub const PLAYERS_FILE_NAME: &str = "players.ron";
+
+/// State of a player that is restored when the player joins the world again
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerData {
+    /// Bottom center of the collider of the player
+    pub position: [f32; 3],
+    /// Yaw and pitch of the view direction in radians
+    pub orientation: [f64; 2],
+    /// Name of the dimension that the player is in
+    pub dimension: String,
+    pub game_mode: GameMode,
+    /// Collected blocks of every voxel type that the player has any of
+    #[serde(default)]
+    pub inventory: Vec<(VoxelType, u32)>,
+}
+
+/// Saved state of every player that has joined the world, keyed by the id of the player
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Players {
+    pub players: HashMap<String, PlayerData>,
+}
+
+impl Players {
+    /// Loads the players from the given file. If the file does not exist or is invalid, no player has joined the world yet.
+    pub fn load(path: &Path) -> Self {
+        ron_file::load_or_default(path, "players")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        ron_file::save(path, self, "players")
+    }
+}
+
+/// A random id in the format of a version 4 UUID, under which the state of a player is saved
+pub fn new_player_id() -> String {
+    let bits = (fastrand::u128(..) & !(0xf000 << 64) | (0x4000 << 64)) & !(0b11 << 62) | (0b10 << 62);
+    let hex = format!("{bits:032x}");
+
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_mode::GameMode;
+    use crate::world::players::{new_player_id, PlayerData, Players};
+    use crate::world::voxel_data::VoxelType;
+
+    #[test]
+    fn test_player_id_is_uuid_v4() {
+        let id = new_player_id();
+
+        assert_eq!(id.len(), 36);
+        assert_eq!(id.as_bytes()[14], b'4');
+        assert!(matches!(id.as_bytes()[19], b'8' | b'9' | b'a' | b'b'));
+        assert_ne!(id, new_player_id());
+    }
+
+    #[test]
+    fn test_players_roundtrip() {
+        let mut players = Players::default();
+        players.players.insert(
+            new_player_id(),
+            PlayerData {
+                position: [1.0, 2.5, -3.0],
+                orientation: [0.5, -0.25],
+                dimension: "nether".to_owned(),
+                game_mode: GameMode::Survival,
+                inventory: vec![(VoxelType::Stone, 12)],
+            },
+        );
+
+        let content = ron::to_string(&players).unwrap();
+        assert_eq!(ron::from_str::<Players>(&content).unwrap(), players);
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use anyhow::Result;
+use cgmath::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::ron_file;
+
+/// A trigger volume that teleports the player to its target when it is entered
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Portal {
+    pub name: String,
+    /// Minimum corner of the trigger volume
+    pub min: [f32; 3],
+    /// Maximum corner of the trigger volume
+    pub max: [f32; 3],
+    pub target: [f32; 3],
+    /// Dimension in which the portal is, or None if it is in all dimensions
+    #[serde(default)]
+    pub dimension: Option<String>,
+    /// Dimension to which the player is teleported, or None to stay in the current one
+    #[serde(default)]
+    pub target_dimension: Option<String>,
+}
+
+impl Portal {
+    /// A cube shaped portal with the given center in the given dimension
+    pub fn around(
+        name: String,
+        center: Point3<f32>,
+        half_size: f32,
+        dimension: &str,
+        target: Point3<f32>,
+        target_dimension: Option<String>,
+    ) -> Self {
+        let half_size = Vector3::new(half_size, half_size, half_size);
+
+        Self {
+            name,
+            min: (center - half_size).into(),
+            max: (center + half_size).into(),
+            target: target.into(),
+            dimension: Some(dimension.to_owned()),
+            target_dimension,
+        }
+    }
+
+    pub fn contains(&self, position: Point3<f32>, dimension: &str) -> bool {
+        let position: [f32; 3] = position.into();
+
+        self.dimension
+            .as_ref()
+            .map_or(true, |portal_dimension| portal_dimension == dimension)
+            && (0..3).all(|axis| (self.min[axis]..=self.max[axis]).contains(&position[axis]))
+    }
+}
+
+/// All portals of the world, which are persisted between runs of the engine
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Portals {
+    pub portals: Vec<Portal>,
+    /// Index of the portal that the player is currently inside of. It only triggers again once the player has left it.
+    #[serde(skip)]
+    entered: Option<usize>,
+}
+
+impl Portals {
+    /// Loads the portals from the given file. If the file does not exist or is invalid, there are no portals.
+    pub fn load(path: &Path) -> Self {
+        ron_file::load_or_default(path, "portals")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        ron_file::save(path, self, "portals")
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.portals.remove(index);
+        self.entered = None;
+    }
+
+    /// Returns the portal that is entered at the given position, if the player was not inside of it before
+    pub fn update(&mut self, position: Point3<f32>, dimension: &str) -> Option<&Portal> {
+        let inside = self
+            .portals
+            .iter()
+            .position(|portal| portal.contains(position, dimension));
+
+        let entered = inside.filter(|&index| self.entered != Some(index));
+        self.entered = inside;
+
+        entered.map(|index| &self.portals[index])
+    }
+
+    /// Marks the portal at the given position as already entered, so that arriving inside of a portal does not trigger it
+    pub fn arrive(&mut self, position: Point3<f32>, dimension: &str) {
+        self.entered = self
+            .portals
+            .iter()
+            .position(|portal| portal.contains(position, dimension));
+    }
+}
@@ -0,0 +1,195 @@
+// Keep in sync with CHUNK_SIZE in world/mod.rs and VOLUME_CHUNKS in ray_tracing.rs
+const CHUNK_SIZE: i32 = 32;
+const VOLUME_CHUNKS: i32 = 8;
+const WORDS_PER_CHUNK: i32 = 1024;
+
+const AO_RAYS: i32 = 6;
+// Distance in voxels up to which surfaces occlude the ambient light
+const AO_DISTANCE: f32 = 6.0;
+const AO_STRENGTH: f32 = 0.6;
+// Shadow rays stop at the border of the volume, which is at least half of its size away from the camera
+const SHADOW_DISTANCE: f32 = 128.0;
+const SHADOW_STRENGTH: f32 = 0.5;
+// Offset of the ray origins along the normal, so that rays do not hit the voxel of the surface that they start on
+const SURFACE_OFFSET: f32 = 0.02;
+
+struct RayTracingUniform {
+	inverse_view_proj: mat4x4<f32>,
+	to_sun: vec4<f32>,
+	// Minimum chunk of the volume and the frame number
+	volume_min: vec4<i32>,
+}
+
+@group(0) @binding(0)
+var<uniform> tracing: RayTracingUniform;
+
+// One bit per opaque voxel, with one word per row of voxels along x
+@group(1) @binding(0)
+var<storage, read> occupancy: array<u32>;
+// Chunk location whose occupancy is stored in each slot and whether it was uploaded
+@group(1) @binding(1)
+var<storage, read> slots: array<vec4<i32>>;
+@group(1) @binding(2)
+var depth: texture_depth_2d;
+@group(1) @binding(3)
+var lighting_output: texture_storage_2d<rgba8unorm, write>;
+
+// Traced lighting in the composite pass, at a binding that does not collide with the uniforms of the tracing pass
+@group(0) @binding(1)
+var lighting: texture_2d<f32>;
+
+// Chunks that are outside of the volume or not uploaded yet are empty
+fn is_occupied(voxel: vec3<i32>) -> bool {
+	let chunk = voxel >> vec3(5u);
+	let relative = chunk - tracing.volume_min.xyz;
+	if any(relative < vec3(0)) || any(relative >= vec3(VOLUME_CHUNKS)) {
+		return false;
+	}
+
+	let wrapped = ((chunk % VOLUME_CHUNKS) + VOLUME_CHUNKS) % VOLUME_CHUNKS;
+	let slot = (wrapped.z * VOLUME_CHUNKS + wrapped.y) * VOLUME_CHUNKS + wrapped.x;
+	let stored = slots[slot];
+	if stored.w == 0 || any(stored.xyz != chunk) {
+		return false;
+	}
+
+	let local = voxel & vec3(CHUNK_SIZE - 1);
+	let word = occupancy[slot * WORDS_PER_CHUNK + local.z * CHUNK_SIZE + local.y];
+	return ((word >> u32(local.x)) & 1u) != 0u;
+}
+
+// Walks along the ray voxel by voxel and returns whether it hits an opaque voxel within the distance
+fn trace(origin: vec3<f32>, direction: vec3<f32>, max_distance: f32) -> bool {
+	var voxel = vec3<i32>(floor(origin));
+	let step = vec3<i32>(sign(direction));
+	let delta = 1.0 / max(abs(direction), vec3(1e-6));
+	// Distance along the ray to the next voxel border on every axis
+	var side = (sign(direction) * (vec3<f32>(voxel) - origin) + sign(direction) * 0.5 + 0.5) * delta;
+
+	loop {
+		if min(side.x, min(side.y, side.z)) > max_distance {
+			return false;
+		}
+
+		if side.x < side.y && side.x < side.z {
+			voxel.x += step.x;
+			side.x += delta.x;
+		} else if side.y < side.z {
+			voxel.y += step.y;
+			side.y += delta.y;
+		} else {
+			voxel.z += step.z;
+			side.z += delta.z;
+		}
+
+		if is_occupied(voxel) {
+			return true;
+		}
+	}
+
+	return false;
+}
+
+fn world_position(pixel: vec2<i32>, size: vec2<i32>) -> vec3<f32> {
+	let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(size);
+	let depth = textureLoad(depth, clamp(pixel, vec2(0), size - 1), 0);
+	let world = tracing.inverse_view_proj * vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+	return world.xyz / world.w;
+}
+
+// Voxel faces are axis aligned, so the normal reconstructed from the neighboring pixels is snapped to the closest axis
+fn face_normal(pixel: vec2<i32>, size: vec2<i32>, position: vec3<f32>) -> vec3<f32> {
+	let dx = world_position(pixel + vec2(1, 0), size) - position;
+	let dy = world_position(pixel + vec2(0, 1), size) - position;
+	let normal = cross(dy, dx);
+
+	let magnitude = abs(normal);
+	if magnitude.x > magnitude.y && magnitude.x > magnitude.z {
+		return vec3(sign(normal.x), 0.0, 0.0);
+	} else if magnitude.y > magnitude.z {
+		return vec3(0.0, sign(normal.y), 0.0);
+	}
+	return vec3(0.0, 0.0, sign(normal.z));
+}
+
+fn hash(value: u32) -> u32 {
+	var x = value;
+	x ^= x >> 16u;
+	x *= 0x7feb352du;
+	x ^= x >> 15u;
+	x *= 0x846ca68bu;
+	x ^= x >> 16u;
+	return x;
+}
+
+fn random(seed: u32) -> f32 {
+	return f32(hash(seed) & 0xffffffu) / 16777216.0;
+}
+
+// Cosine weighted direction in the hemisphere around the normal
+fn hemisphere_direction(normal: vec3<f32>, u: f32, v: f32) -> vec3<f32> {
+	let tangent = normalize(select(vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), abs(normal.x) > 0.5));
+	let bitangent = cross(normal, tangent);
+	let radius = sqrt(u);
+	let angle = 6.2831853 * v;
+	return normalize(tangent * radius * cos(angle) + bitangent * radius * sin(angle) + normal * sqrt(1.0 - u));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_trace(@builtin(global_invocation_id) id: vec3<u32>) {
+	let size = vec2<i32>(textureDimensions(lighting_output));
+	let pixel = vec2<i32>(id.xy);
+	if any(pixel >= size) {
+		return;
+	}
+
+	// The sky is not lit by the terrain
+	if textureLoad(depth, pixel, 0) >= 1.0 {
+		textureStore(lighting_output, pixel, vec4(1.0));
+		return;
+	}
+
+	let position = world_position(pixel, size);
+	let normal = face_normal(pixel, size, position);
+	let origin = position + normal * SURFACE_OFFSET;
+
+	var seed = (id.y * u32(size.x) + id.x) * 9781u + u32(tracing.volume_min.w) * 6271u;
+	var occluded = 0;
+	for (var i = 0; i < AO_RAYS; i++) {
+		let direction = hemisphere_direction(normal, random(seed), random(seed + 1u));
+		seed += 2u;
+		if trace(origin, direction, AO_DISTANCE) {
+			occluded += 1;
+		}
+	}
+	var light = 1.0 - AO_STRENGTH * f32(occluded) / f32(AO_RAYS);
+
+	let to_sun = tracing.to_sun.xyz;
+	if to_sun.y > 0.0 && dot(normal, to_sun) > 0.0 && trace(origin, to_sun, SHADOW_DISTANCE) {
+		light *= 1.0 - SHADOW_STRENGTH;
+	}
+
+	textureStore(lighting_output, pixel, vec4(light, light, light, 1.0));
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	var POSITION_LOOKUP = array<vec2<f32>, 3>(
+		vec2(-1.0, -1.0),
+		vec2(3.0, -1.0),
+		vec2(-1.0, 3.0));
+
+    var out: VertexOutput;
+    out.clip_position = vec4(POSITION_LOOKUP[vertex_index], 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+	return textureLoad(lighting, vec2<i32>(in.clip_position.xy), 0);
+}
@@ -0,0 +1,105 @@
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use crate::world::location::WorldLocation;
+
+/// The first solid voxel that is hit by a ray
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub voxel: WorldLocation,
+    /// Normal of the face through which the ray entered the voxel
+    pub normal: Vector3<i32>,
+    /// Distance from the origin of the ray to the hit face
+    pub distance: f32,
+}
+
+/// Walks along a ray through the voxel grid and returns the first voxel for which `is_solid` returns true.
+///
+/// Every voxel that the ray passes through is visited exactly once, in order of its distance to the origin.
+/// A voxel that contains the origin is never hit, as there is no face through which the ray entered it.
+pub fn raycast(
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+    mut is_solid: impl FnMut(WorldLocation) -> bool,
+) -> Option<RaycastHit> {
+    if direction.is_zero() {
+        return None;
+    }
+    let direction = direction.normalize();
+
+    let mut voxel = Vector3::new(origin.x.floor() as i32, origin.y.floor() as i32, origin.z.floor() as i32);
+    let step = Vector3::new(axis_step(direction.x), axis_step(direction.y), axis_step(direction.z));
+
+    // Distance along the ray between two voxel borders, and to the next voxel border of each axis
+    let delta = direction.map(|d| (1.0 / d).abs());
+    let mut next_border = Vector3::new(
+        initial_border_distance(origin.x, direction.x, delta.x),
+        initial_border_distance(origin.y, direction.y, delta.y),
+        initial_border_distance(origin.z, direction.z, delta.z),
+    );
+
+    loop {
+        let (axis, distance) = [next_border.x, next_border.y, next_border.z]
+            .into_iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("there to be three axes");
+
+        if distance > max_distance {
+            return None;
+        }
+
+        voxel[axis] += step[axis];
+        next_border[axis] += delta[axis];
+
+        if is_solid(WorldLocation(voxel)) {
+            let mut normal = Vector3::zero();
+            normal[axis] = -step[axis];
+
+            return Some(RaycastHit {
+                voxel: WorldLocation(voxel),
+                normal,
+                distance,
+            });
+        }
+    }
+}
+
+fn axis_step(direction: f32) -> i32 {
+    if direction > 0.0 {
+        1
+    } else {
+        -1
+    }
+}
+
+fn initial_border_distance(origin: f32, direction: f32, delta: f32) -> f32 {
+    if direction == 0.0 {
+        f32::INFINITY
+    } else if direction > 0.0 {
+        (origin.floor() + 1.0 - origin) * delta
+    } else {
+        (origin - origin.floor()) * delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::location::WorldLocation;
+    use crate::world::raycast::raycast;
+
+    #[test]
+    fn test_raycast_hits_ground() {
+        let ground = |location: WorldLocation| location.0.y <= 0;
+
+        let hit = raycast(Vector3::new(0.5, 5.5, 0.5), Vector3::new(0.0, -1.0, 0.0), 8.0, ground).expect("the ray to hit the ground");
+        assert_eq!(hit.voxel, WorldLocation(Vector3::new(0, 0, 0)));
+        assert_eq!(hit.normal, Vector3::new(0, 1, 0));
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+
+        assert!(raycast(Vector3::new(0.5, 5.5, 0.5), Vector3::new(0.0, -1.0, 0.0), 4.0, ground).is_none());
+        assert!(raycast(Vector3::new(0.5, 5.5, 0.5), Vector3::new(0.0, 1.0, 0.0), 100.0, ground).is_none());
+    }
+}
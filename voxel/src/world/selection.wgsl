@@ -0,0 +1,67 @@
+struct CameraUniform {
+	position: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+// The targeted voxel face, spanned by two axes starting at its origin corner
+struct SelectionUniform {
+	origin: vec4<f32>,
+	axis_u: vec4<f32>,
+	axis_v: vec4<f32>,
+	break_progress: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<uniform> selection: SelectionUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Two triangles covering the face, generated from the vertex index
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	var UV_LOOKUP = array<vec2<f32>, 6>(
+		vec2(0.0, 0.0),
+		vec2(1.0, 0.0),
+		vec2(0.0, 1.0),
+		vec2(0.0, 1.0),
+		vec2(1.0, 0.0),
+		vec2(1.0, 1.0));
+
+	let uv = UV_LOOKUP[vertex_index];
+	let position = selection.origin.xyz + uv.x * selection.axis_u.xyz + uv.y * selection.axis_v.xyz;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4(position, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Outputs black with varying opacity only, so that no sRGB encoding is needed for non-sRGB surfaces
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let edge_distance = min(min(in.uv.x, 1.0 - in.uv.x), min(in.uv.y, 1.0 - in.uv.y));
+	if (edge_distance < 0.03) {
+		return vec4(0.0, 0.0, 0.0, 0.7);
+	}
+
+	// Cracks along the borders of a grid of cells, each of which starts to crack at a random break progress
+	let cell = floor(in.uv * 6.0);
+	let in_cell = fract(in.uv * 6.0);
+	let cell_edge_distance = min(min(in_cell.x, 1.0 - in_cell.x), min(in_cell.y, 1.0 - in_cell.y));
+
+	if (hash(cell) < selection.break_progress && cell_edge_distance < 0.12) {
+		return vec4(0.0, 0.0, 0.0, 0.6);
+	}
+
+	return vec4(0.0);
+}
+
+fn hash(cell: vec2<f32>) -> f32 {
+	return fract(sin(dot(cell, vec2(12.9898, 78.233))) * 43758.5453);
+}
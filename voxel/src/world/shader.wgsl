@@ -1,8 +1,21 @@
 struct CameraUniform {
 	position: vec4<f32>,
     view_proj: mat4x4<f32>,
+    // Drift offset in x and z, coverage and shadow strength of the clouds
+    clouds: vec4<f32>,
+    // Color of the fog and the distance at which it completely hides the terrain
+    fog: vec4<f32>,
+    // Color and density of the exponential fog in which far chunks fade
+    distance_fog: vec4<f32>,
 }
 
+// Keep in sync with clouds.wgsl
+const CLOUD_HEIGHT: f32 = 180.0;
+// Direction towards the sun of the pipelines without a lighting uniform, e.g. the viewmodel
+const SUN_DIRECTION: vec3<f32> = vec3(1.0, 0.5, 0.7);
+// Keep in sync with ATLAS_TILES_PER_ROW in texture.rs
+const ATLAS_TILES_PER_ROW: f32 = 16.0;
+
 //struct ModelUniform {
 //	pos: vec2<f32>,
 //}
@@ -18,45 +31,211 @@ struct VertexInput {
 }
 
 struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
+    // Invariant, so that the depth pre-pass and the color pass compute exactly the same depth
+    @builtin(position) @invariant clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    // Brightness of the vertex before fog, which is applied to the texture of textured faces
+    @location(1) light: vec3<f32>,
+    @location(2) fog: f32,
+    // Position on the face in voxels, which repeats the texture tile on every voxel of merged faces
+    @location(3) uv: vec2<f32>,
+    // 0 for untextured faces, otherwise the tile of the texture atlas plus one
+    @location(4) @interpolate(flat) texture: u32,
+    // Used by the distance fog and the debug views of the chunk render modes
+    @location(5) world_position: vec3<f32>,
+    @location(6) @interpolate(flat) normal: vec3<f32>,
+    @location(7) ambient_occlusion: f32,
 };
 
+// Chunk draw records of the GPU driven render path, see chunk_culling.wgsl
+struct ChunkDrawRecord {
+	position: vec3<f32>,
+	index_count: u32,
+	first_index: u32,
+	base_vertex: i32,
+	active: u32,
+	_padding: u32,
+}
+
+@group(1) @binding(0)
+var atlas_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var atlas_sampler: sampler;
+
+// Sunlight of the chunk pipelines, which changes over the course of the day, see day_cycle.rs
+struct LightingUniform {
+	// Direction from the terrain towards the sun
+	to_sun: vec4<f32>,
+	sun_color: vec4<f32>,
+	// Strength of the ambient occlusion in w, 0 if it is disabled
+	ambient_color: vec4<f32>,
+}
+
+@group(2) @binding(0)
+var<uniform> lighting: LightingUniform;
+
+@group(3) @binding(0)
+var<storage, read> chunk_draw_records: array<ChunkDrawRecord>;
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+	return shade_vertex(model, position_offset, normalize(SUN_DIRECTION), vec3(0.8), vec4(vec3(0.2), 0.2));
+}
+
+// Entry point of the per-chunk render path, which is lit by the sun of the lighting uniform
 @vertex
-fn vs_main(
-    model: VertexInput,
-    @builtin(vertex_index) in_vertex_index: u32,
+fn vs_chunk(model: VertexInput) -> VertexOutput {
+	return shade_vertex(model, position_offset, lighting.to_sun.xyz, lighting.sun_color.rgb, lighting.ambient_color);
+}
+
+// Entry point of the GPU driven render path. The chunk position is looked up via the instance index of the indirect draw.
+@vertex
+fn vs_main_indirect(model: VertexInput, @builtin(instance_index) instance_index: u32) -> VertexOutput {
+	return shade_vertex(
+		model,
+		chunk_draw_records[instance_index].position,
+		lighting.to_sun.xyz,
+		lighting.sun_color.rgb,
+		lighting.ambient_color,
+	);
+}
+
+// Face instances of the GPU meshing path, see gpu_meshing.wgsl
+struct FaceInstance {
+	@location(0) voxel_x_y_z_color_r: u32,
+	@location(1) color_g_b_normal_lowered_texture: u32,
+	// Ambient occlusion of the four corners, whether the diagonal of the quad is flipped and the direction of the face
+	@location(2) corners: u32,
+}
+
+// Entry point of the GPU meshing path, which draws six vertices for every face instance
+@vertex
+fn vs_face_instance(face: FaceInstance, @builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	// In the order of Direction
+	var DIRECTIONS = array<vec3<f32>, 6>(
+		vec3(1.0, 0.0, 0.0),
+		vec3(-1.0, 0.0, 0.0),
+		vec3(0.0, 1.0, 0.0),
+		vec3(0.0, -1.0, 0.0),
+		vec3(0.0, 0.0, 1.0),
+		vec3(0.0, 0.0, -1.0));
+	// Corners of the two triangles of front and back faces with and without a flipped diagonal,
+	// in the same order as in ChunkMeshGenerator::generate_mesh_from_quads
+	var CORNER_LOOKUP = array<u32, 24>(
+		2u, 3u, 0u, 0u, 3u, 1u,
+		2u, 1u, 0u, 3u, 1u, 2u,
+		0u, 1u, 3u, 3u, 2u, 0u,
+		0u, 1u, 2u, 2u, 1u, 3u);
+
+	let direction_index = (face.corners >> 9u) & 7u;
+	let is_backside = (direction_index & 1u) == 1u;
+	let flipped = (face.corners >> 8u) & 1u;
+	let corner = CORNER_LOOKUP[(u32(is_backside) * 2u + flipped) * 6u + vertex_index];
+
+	let normal = abs(DIRECTIONS[direction_index]);
+	var offset = select(normal, vec3(0.0), is_backside);
+	offset += normal.yzx * f32(corner & 1u) + normal.zxy * f32(corner >> 1u);
+
+	let voxel = vec3(
+		face.voxel_x_y_z_color_r >> 24u,
+		(face.voxel_x_y_z_color_r >> 16u) & 0xFFu,
+		(face.voxel_x_y_z_color_r >> 8u) & 0xFFu,
+	);
+	let position = voxel + vec3<u32>(offset);
+	let ao = (face.corners >> (corner * 2u)) & 3u;
+
+	// Only the corners at the top of the voxel are lowered, so that partial voxels keep their bottom
+	var color_g_b_normal_ao = (face.color_g_b_normal_lowered_texture & ~0x700u) | (ao << 11u);
+	if (offset.y >= 1.0) {
+		color_g_b_normal_ao |= face.color_g_b_normal_lowered_texture & 0x700u;
+	}
+
+	let model = VertexInput(
+		(position.x << 24u) | (position.y << 16u) | (position.z << 8u) | (face.voxel_x_y_z_color_r & 0xFFu),
+		color_g_b_normal_ao,
+	);
+	return shade_vertex(model, position_offset, lighting.to_sun.xyz, lighting.sun_color.rgb, lighting.ambient_color);
+}
+
+// Instances of the entity renderer, see entity_renderer.rs
+struct EntityInstance {
+	// Minimum corner and size of the collider, to which the voxel mesh is stretched
+	@location(2) min: vec3<f32>,
+	@location(3) size: vec3<f32>,
+}
+
+// Entry point of the entity renderer, which draws a voxel mesh for every entity instance
+@vertex
+fn vs_entity(model: VertexInput, entity: EntityInstance) -> VertexOutput {
+	return shade_scaled_vertex(model, entity.min, entity.size, normalize(SUN_DIRECTION), vec3(0.8), vec4(vec3(0.2), 0.2));
+}
+
+fn shade_vertex(model: VertexInput, chunk_position: vec3<f32>, to_sun: vec3<f32>, sun_color: vec3<f32>, ambient_color: vec4<f32>) -> VertexOutput {
+	return shade_scaled_vertex(model, chunk_position, vec3(1.0), to_sun, sun_color, ambient_color);
+}
+
+// Like shade_vertex, but the model is scaled along every axis before it is moved to its position
+fn shade_scaled_vertex(
+	model: VertexInput,
+	chunk_position: vec3<f32>,
+	scale: vec3<f32>,
+	to_sun: vec3<f32>,
+	sun_color: vec3<f32>,
+	ambient_color: vec4<f32>,
 ) -> VertexOutput {
-	var model_position: vec3<f32> = parse_model_position(model.position_x_y_z_color_r);
+	var model_position: vec3<f32> = parse_model_position(model.position_x_y_z_color_r, model.color_g_b_normal_ao);
 	var model_color: vec3<f32> = parse_model_color(model.position_x_y_z_color_r, model.color_g_b_normal_ao);
 	var model_normal: vec3<f32> = parse_model_normal(model.color_g_b_normal_ao);
 	var model_ao: f32 = parse_model_ao(model.color_g_b_normal_ao);
+	var smooth_normal: vec3<f32> = parse_model_smooth_normal(model.position_x_y_z_color_r);
+	// Only the light uses the smoothed normal, the face normal is still needed e.g. for the texture coordinates
+	var light_normal = select(model_normal, normalize(smooth_normal), any(smooth_normal != vec3(0.0)));
 
-	var vertex_position = model_position + position_offset;
+	var vertex_position = model_position * scale + chunk_position;
 
     var out: VertexOutput;
     out.clip_position = camera.view_proj * vec4((vertex_position), 1.0);
 
-    var brightness: f32;
-    brightness = 0.2 + 0.8*dot(model_normal, vec3(1.0, 0.5, 0.7));
+	var sunlight = sun_color * max(dot(light_normal, to_sun), 0.0);
 
 	var fog_dist: f32;
 	fog_dist = distance(camera.position.xyz, vertex_position);
-	var fog: f32 = 1.0 - max(0.0, min(1.0, pow(fog_dist / (32.0*32.0), 16.0)));
+	var fog: f32 = fog_factor(fog_dist);
 
 	var ambient_occlusion = model_ao / 3.0; // shadow 0.0 <-> 1.0 light
 
-	var color = (brightness - 0.2 * (1.0 - ambient_occlusion)) * model_color;
+	// Soft shadows of the clouds, sampled where the sunlight towards this vertex passes through the cloud layer
+	if (camera.clouds.w > 0.0 && vertex_position.y < CLOUD_HEIGHT && to_sun.y > 0.0) {
+		let cloud_position = vertex_position.xz + to_sun.xz * (CLOUD_HEIGHT - vertex_position.y) / to_sun.y;
+		sunlight *= 1.0 - camera.clouds.w * cloud_density(cloud_position + camera.clouds.xy, camera.clouds.z);
+	}
 
-    out.color = mix(vec3(0.4941, 0.6627, 1.0), color, fog);
+	let light = (ambient_color.rgb + sunlight) * (1.0 - ambient_color.w * (1.0 - ambient_occlusion));
+
+    out.color = mix(camera.fog.xyz, light * model_color, fog);
+    out.light = light;
+    out.fog = fog;
+    out.uv = face_uv(model_position, model_normal);
+    out.texture = model.color_g_b_normal_ao & 0x000000FFu;
+    out.world_position = vertex_position;
+    out.normal = model_normal;
+    out.ambient_occlusion = ambient_occlusion;
     return out;
 }
 
-fn parse_model_position(model1: u32) -> vec3<f32> {
+// Sharp fog at the end of the render distance, but gradual fog for the short fog distances in caves and under water
+fn fog_factor(fog_dist: f32) -> f32 {
+	let exponent = clamp(camera.fog.w / 64.0, 2.0, 16.0);
+	return 1.0 - clamp(pow(fog_dist / camera.fog.w, exponent), 0.0, 1.0);
+}
+
+fn parse_model_position(model1: u32, model2: u32) -> vec3<f32> {
+	var lowered: f32 = f32((model2 & 0x00000700u) >> 8u) / 8.0;
+
 	return vec3(
-		f32((model1 & 0xFF000000u) >> 24u),
-		f32((model1 & 0x00FF0000u) >> 16u),
-		f32((model1 & 0x0000FF00u) >> 8u),
+		f32((model1 & 0x3F000000u) >> 24u),
+		f32((model1 & 0x003F0000u) >> 16u) - lowered,
+		f32((model1 & 0x00003F00u) >> 8u),
 	);
 }
 fn parse_model_color(model1: u32, model2: u32) -> vec3<f32> {
@@ -81,14 +260,129 @@ fn parse_model_normal(model2: u32) -> vec3<f32> {
 	return NORMAL_LOOKUP[normal_idx];
 }
 
+// Zero if the vertex is lit with its face normal
+fn parse_model_smooth_normal(model1: u32) -> vec3<f32> {
+	var COMPONENT_LOOKUP = array<f32, 4>(0.0, 1.0, 0.0, -1.0);
+
+	return vec3(
+		COMPONENT_LOOKUP[(model1 >> 30u) & 3u],
+		COMPONENT_LOOKUP[(model1 >> 22u) & 3u],
+		COMPONENT_LOOKUP[(model1 >> 14u) & 3u],
+	);
+}
+
 fn parse_model_ao(model2: u32) -> f32 {
 	return f32((model2 & 0x00001800u) >> 11u);
 }
 
+// Texture coordinates of a face, with the v axis pointing downwards on the sides
+fn face_uv(position: vec3<f32>, normal: vec3<f32>) -> vec2<f32> {
+	if (normal.y != 0.0) {
+		return position.xz;
+	} else if (normal.x != 0.0) {
+		return vec2(position.z, -position.y);
+	} else {
+		return vec2(position.x, -position.y);
+	}
+}
+
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    return vec4<f32>(distance_fog(in.color, in.world_position), 1.0);
+}
+
+// Used instead of fs_main if the surface format is not sRGB, so that the color has to be encoded by the shader
+@fragment
+fn fs_main_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(linear_to_srgb(distance_fog(in.color, in.world_position)), 1.0);
+}
+
+// Used by the chunk pipelines instead of fs_main, which draws the texture atlas on textured faces
+@fragment
+fn fs_textured(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(distance_fog(textured_color(in), in.world_position), 1.0);
+}
+
+@fragment
+fn fs_textured_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(linear_to_srgb(distance_fog(textured_color(in), in.world_position)), 1.0);
+}
+
+// Exponential fog per fragment, which fades far chunks gradually instead of only at the end of the render distance
+fn distance_fog(color: vec3<f32>, world_position: vec3<f32>) -> vec3<f32> {
+	let amount = 1.0 - exp(-camera.distance_fog.w * distance(camera.position.xyz, world_position));
+	return mix(color, camera.distance_fog.rgb, amount);
+}
+
+fn textured_color(in: VertexOutput) -> vec3<f32> {
+	// The gradients of the unwrapped coordinates select the mip level, as the wrapped coordinates jump at every voxel border
+	let uv_dx = dpdx(in.uv) / ATLAS_TILES_PER_ROW;
+	let uv_dy = dpdy(in.uv) / ATLAS_TILES_PER_ROW;
+
+	if (in.texture == 0u) {
+		return in.color;
+	}
+
+	let tile = f32(in.texture - 1u);
+	let tile_origin = vec2(tile % ATLAS_TILES_PER_ROW, floor(tile / ATLAS_TILES_PER_ROW));
+	let atlas_uv = (tile_origin + fract(in.uv)) / ATLAS_TILES_PER_ROW;
+	let texel = textureSampleGrad(atlas_texture, atlas_sampler, atlas_uv, uv_dx, uv_dy);
+
+	// Transparent texels, e.g. of the placeholder atlas if none is loaded, show the color of the voxel instead
+	let textured = mix(camera.fog.xyz, in.light * texel.rgb, in.fog);
+	return mix(in.color, textured, texel.a);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, color < vec3(0.0031308));
+}
+
+// Used by the depth pre-pass, which does not write any color
+@fragment
+fn fs_depth_only() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0);
+}
+
+// Debug view of the face directions, with every axis mapped from -1..1 to 0..1
+@fragment
+fn fs_normals(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.normal * 0.5 + 0.5, 1.0);
+}
+
+// Debug view of the ambient occlusion of the meshes, without any other lighting
+@fragment
+fn fs_ambient_occlusion(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(vec3(in.ambient_occlusion), 1.0);
+}
+
+// Debug view of the chunk borders, drawn as lines where the borders cut through the faces
+@fragment
+fn fs_chunk_bounds(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(chunk_bounds_color(in), 1.0);
+}
+
+@fragment
+fn fs_chunk_bounds_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(linear_to_srgb(chunk_bounds_color(in)), 1.0);
+}
+
+fn chunk_bounds_color(in: VertexOutput) -> vec3<f32> {
+	// Keep in sync with CHUNK_SIZE in world/mod.rs
+	let chunk_size = 32.0;
+	let border_distance = abs(in.world_position - round(in.world_position / chunk_size) * chunk_size);
+	// Faces that lie on a border are not filled, only the borders along the face are drawn
+	let along_face = select(border_distance, vec3(chunk_size), in.normal != vec3(0.0));
+	// Two pixels wide at any distance
+	let line_width = 2.0 * fwidth(in.world_position);
+
+	let color = textured_color(in);
+	if any(along_face < line_width) {
+		return mix(vec3(1.0, 0.8, 0.0), color, 0.2);
+	}
+	return color;
 }
 
 
@@ -109,3 +403,39 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 //		ao_color.y = 1.0;
 //		ao_color.z = 1.0;
 //	}
+
+// Same cloud noise as in clouds.wgsl
+fn cloud_hash(cell: vec2<f32>, period: f32) -> f32 {
+	let wrapped = cell - period * floor(cell / period);
+	return fract(sin(dot(wrapped, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+fn value_noise(position: vec2<f32>, period: f32) -> f32 {
+	let cell = floor(position);
+	let f = fract(position);
+	let u = f * f * (3.0 - 2.0 * f);
+
+	let a = cloud_hash(cell, period);
+	let b = cloud_hash(cell + vec2(1.0, 0.0), period);
+	let c = cloud_hash(cell + vec2(0.0, 1.0), period);
+	let d = cloud_hash(cell + vec2(1.0, 1.0), period);
+
+	return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+// Fractal noise from 0.0 to 1.0 that repeats every 8192 blocks, so that the drift offset can wrap around without a jump
+fn cloud_density(position: vec2<f32>, coverage: f32) -> f32 {
+	var p = position / 32.0;
+	var period = 256.0;
+	var amplitude = 0.5;
+	var total = 0.0;
+
+	for (var octave = 0; octave < 4; octave++) {
+		total += amplitude * value_noise(p, period);
+		p *= 2.0;
+		period *= 2.0;
+		amplitude *= 0.5;
+	}
+
+	return smoothstep(1.0 - coverage, 1.15 - coverage, total / 0.9375);
+}
@@ -0,0 +1,68 @@
+struct CameraUniform {
+	position: vec4<f32>,
+    view_proj: mat4x4<f32>,
+    clouds: vec4<f32>,
+    // Color of the fog and the distance at which it completely hides the terrain
+    fog: vec4<f32>,
+}
+
+// Same lighting as the chunk pipelines, see shader.wgsl
+struct LightingUniform {
+	to_sun: vec4<f32>,
+	sun_color: vec4<f32>,
+	ambient_color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<uniform> lighting: LightingUniform;
+
+var<push_constant> chunk_position: vec3<f32>;
+
+struct VertexInput {
+	@location(0) position: vec3<f32>,
+	@location(1) normal: vec3<f32>,
+	@location(2) color: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+	let world_position = model.position + chunk_position;
+	let sunlight = lighting.sun_color.rgb * max(dot(model.normal, lighting.to_sun.xyz), 0.0);
+	let light = lighting.ambient_color.rgb + sunlight;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4(world_position, 1.0);
+    out.color = mix(camera.fog.xyz, light * model.color, fog_factor(distance(camera.position.xyz, world_position)));
+    return out;
+}
+
+// Same fog as in shader.wgsl
+fn fog_factor(fog_dist: f32) -> f32 {
+	let exponent = clamp(camera.fog.w / 64.0, 2.0, 16.0);
+	return 1.0 - clamp(pow(fog_dist / camera.fog.w, exponent), 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(in.color, 1.0);
+}
+
+// Used instead of fs_main if the surface format is not sRGB, so that the color has to be encoded by the shader
+@fragment
+fn fs_main_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(linear_to_srgb(in.color), 1.0);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, color < vec3(0.0031308));
+}
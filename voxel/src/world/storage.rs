@@ -0,0 +1,501 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{bail, ensure, Context, Result};
+use cgmath::Vector3;
+use enum_map::Enum;
+use itertools::{iproduct, Itertools};
+use log::warn;
+use memmap2::Mmap;
+
+use crate::vector_utils::MapElemWise;
+use crate::world::chunk_data::ChunkData;
+use crate::world::location::ChunkLocation;
+use crate::world::voxel_data::{VoxelData, VoxelType};
+use crate::world::CHUNK_SIZE;
+
+/// Number of chunks along each side of a region. All chunks of a region are stored in the same file.
+pub const REGION_SIZE: i32 = 8;
+const REGION_CHUNKS: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+const MAGIC: &[u8; 4] = b"VXRG";
+/// Version 2 added a CRC32 of every chunk to its header entry. Files of version 1 are still read without checking them.
+const VERSION: u32 = 2;
+/// Offset, length and CRC32 of a chunk
+const ENTRY_SIZE: usize = 12;
+const VERSION_1_ENTRY_SIZE: usize = 8;
+/// Magic, version and the entries of all chunks of the region
+const HEADER_SIZE: usize = 8 + REGION_CHUNKS * ENTRY_SIZE;
+
+/// Encoding of a chunk that consists of a single voxel type
+const UNIFORM_CHUNK: u8 = 0;
+/// Encoding of a chunk as runs of equal voxels in index order
+const RUN_LENGTH_CHUNK: u8 = 1;
+
+/// Maximum number of region files that are kept mapped by a [RegionReader]
+const MAX_MAPPED_REGIONS: usize = 64;
+
+/// The location of a region of [REGION_SIZE]³ chunks
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RegionLocation(pub Vector3<i32>);
+
+impl RegionLocation {
+    pub fn of(chunk_location: ChunkLocation) -> Self {
+        Self(chunk_location.map_elem_wise(|c| c.div_euclid(REGION_SIZE)))
+    }
+
+    pub fn file_name(self) -> String {
+        format!("r.{}.{}.{}.region", self.0.x, self.0.y, self.0.z)
+    }
+
+    /// All chunk locations within this region
+    pub fn chunks(self) -> impl Iterator<Item = ChunkLocation> {
+        iproduct!(0..REGION_SIZE, 0..REGION_SIZE, 0..REGION_SIZE)
+            .map(move |(x, y, z)| ChunkLocation::new(self.0 * REGION_SIZE + Vector3::new(x, y, z)))
+    }
+
+    /// Index of a chunk of this region in the header of the region file
+    fn chunk_index(chunk_location: ChunkLocation) -> usize {
+        let local = chunk_location.map_elem_wise(|c| c.rem_euclid(REGION_SIZE));
+        (local.z * REGION_SIZE * REGION_SIZE + local.y * REGION_SIZE + local.x) as usize
+    }
+}
+
+/// Appends the encoding of a chunk to `out`. Uniform chunks are stored as a single voxel.
+///
+/// Voxel types are stored as their index in [VoxelType], so new types have to be added at the end to keep saved worlds readable.
+pub fn encode_chunk(data: &ChunkData, out: &mut Vec<u8>) {
+    match data {
+        ChunkData::UniformType(voxel) => {
+            out.push(UNIFORM_CHUNK);
+            out.push(voxel.ty.into_usize() as u8);
+        }
+        ChunkData::Voxels(_) | ChunkData::Paletted(_) => {
+            out.push(RUN_LENGTH_CHUNK);
+
+            let mut push_run = |length: usize, voxel: VoxelData| {
+                out.extend_from_slice(&(length as u16).to_le_bytes());
+                out.push(voxel.ty.into_usize() as u8);
+            };
+
+            let mut run_start = 0;
+            for index in 1..CHUNK_SIZE.pow(3) {
+                if data.get_voxel_by_index(index) != data.get_voxel_by_index(run_start) {
+                    push_run(index - run_start, data.get_voxel_by_index(run_start));
+                    run_start = index;
+                }
+            }
+            push_run(CHUNK_SIZE.pow(3) - run_start, data.get_voxel_by_index(run_start));
+        }
+    }
+}
+
+pub fn decode_chunk(bytes: &[u8]) -> Result<ChunkData> {
+    let decode_voxel = |byte: u8| -> Result<VoxelData> {
+        ensure!((byte as usize) < VoxelType::LENGTH, "invalid voxel type {byte}");
+        Ok(VoxelData::new(VoxelType::from_usize(byte as usize)))
+    };
+
+    match bytes.split_first() {
+        Some((&UNIFORM_CHUNK, &[voxel])) => Ok(ChunkData::new_with_uniform_data(decode_voxel(voxel)?)),
+        Some((&RUN_LENGTH_CHUNK, runs)) => {
+            ensure!(runs.len() % 3 == 0, "truncated run of voxels");
+
+            let mut voxels = Vec::with_capacity(CHUNK_SIZE.pow(3));
+            for run in runs.chunks_exact(3) {
+                let length = u16::from_le_bytes([run[0], run[1]]) as usize;
+                ensure!(voxels.len() + length <= CHUNK_SIZE.pow(3), "too many voxels");
+                voxels.resize(voxels.len() + length, decode_voxel(run[2])?);
+            }
+
+            let voxels = voxels
+                .into_boxed_slice()
+                .try_into()
+                .map_err(|voxels: Box<[VoxelData]>| anyhow::anyhow!("expected {} voxels, got {}", CHUNK_SIZE.pow(3), voxels.len()))?;
+            let mut data = ChunkData::Voxels(voxels);
+            data.compress();
+            Ok(data)
+        }
+        Some((encoding, _)) => bail!("unknown chunk encoding {encoding}"),
+        None => bail!("empty chunk"),
+    }
+}
+
+/// Writes all given chunks of one region into a region file, replacing the previous file.
+///
+/// The file is written to a temporary file first and then renamed, so that regions which are currently mapped by a
+/// [RegionReader] are never modified in place.
+pub fn write_region(path: &Path, chunks: &[(ChunkLocation, &ChunkData)]) -> Result<()> {
+    let mut table = vec![(0u32, 0u32, 0u32); REGION_CHUNKS];
+    let mut payload = Vec::new();
+
+    for &(location, data) in chunks {
+        let start = payload.len();
+        encode_chunk(data, &mut payload);
+        table[RegionLocation::chunk_index(location)] = (
+            (HEADER_SIZE + start) as u32,
+            (payload.len() - start) as u32,
+            crc32fast::hash(&payload[start..]),
+        );
+    }
+
+    let mut content = Vec::with_capacity(HEADER_SIZE + payload.len());
+    content.extend_from_slice(MAGIC);
+    content.extend_from_slice(&VERSION.to_le_bytes());
+    for (offset, length, crc) in table {
+        content.extend_from_slice(&offset.to_le_bytes());
+        content.extend_from_slice(&length.to_le_bytes());
+        content.extend_from_slice(&crc.to_le_bytes());
+    }
+    content.extend_from_slice(&payload);
+
+    let temporary_path = path.with_extension("region.tmp");
+    fs::write(&temporary_path, content).with_context(|| format!("could not write region file {temporary_path:?}"))?;
+    fs::rename(&temporary_path, path).with_context(|| format!("could not replace region file {path:?}"))
+}
+
+/// A region file that is mapped into memory read-only.
+/// Opening it does not read the chunks, they are only decoded from the mapped bytes when they are requested.
+pub struct MappedRegion {
+    mmap: Mmap,
+    /// Size of the header entry of a chunk, which depends on the version of the file
+    entry_size: usize,
+}
+
+impl MappedRegion {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("could not open region file {path:?}"))?;
+
+        // # Safety
+        // Region files are only replaced by renaming a new file over them (see [write_region]), which keeps the mapped file unchanged.
+        let mmap = unsafe { Mmap::map(&file) }.with_context(|| format!("could not map region file {path:?}"))?;
+
+        ensure!(mmap.len() >= 8 && &mmap[0..4] == MAGIC, "{path:?} is not a region file");
+        let version = u32::from_le_bytes(mmap[4..8].try_into().unwrap());
+        let entry_size = match version {
+            1 => VERSION_1_ENTRY_SIZE,
+            VERSION => ENTRY_SIZE,
+            _ => bail!("region file {path:?} has the unsupported version {version}"),
+        };
+        ensure!(mmap.len() >= 8 + REGION_CHUNKS * entry_size, "region file {path:?} is too short");
+
+        Ok(Self { mmap, entry_size })
+    }
+
+    /// Decodes a chunk of this region, or returns None if the chunk was not saved.
+    /// Fails if the saved bytes of the chunk do not match their checksum.
+    pub fn chunk(&self, chunk_location: ChunkLocation) -> Result<Option<ChunkData>> {
+        let entry = 8 + RegionLocation::chunk_index(chunk_location) * self.entry_size;
+        let read_u32 = |at: usize| u32::from_le_bytes(self.mmap[at..at + 4].try_into().unwrap());
+        let (offset, length) = (read_u32(entry) as usize, read_u32(entry + 4) as usize);
+
+        if length == 0 {
+            return Ok(None);
+        }
+
+        let bytes = self
+            .mmap
+            .get(offset..offset + length)
+            .context("chunk is outside of the region file")?;
+        if self.entry_size == ENTRY_SIZE {
+            ensure!(crc32fast::hash(bytes) == read_u32(entry + 8), "the saved chunk is corrupted");
+        }
+        decode_chunk(bytes).map(Some)
+    }
+}
+
+/// Read-only access to the region files of a saved world directory.
+/// Regions are mapped when one of their chunks is requested for the first time, the least recently opened ones are unmapped again.
+pub struct RegionReader {
+    directory: PathBuf,
+    /// Mapped regions, or None for regions without a file
+    regions: HashMap<RegionLocation, Option<MappedRegion>>,
+    open_order: VecDeque<RegionLocation>,
+}
+
+impl RegionReader {
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            regions: HashMap::new(),
+            open_order: VecDeque::new(),
+        }
+    }
+
+    /// Loads a saved chunk, or returns None if the chunk was never saved.
+    ///
+    /// Region files that cannot be opened are moved aside with a warning and treated as if they did not exist,
+    /// so that their chunks are generated again instead of failing every time they are loaded.
+    pub fn load_chunk(&mut self, chunk_location: ChunkLocation) -> Result<Option<ChunkData>> {
+        let region_location = RegionLocation::of(chunk_location);
+
+        if !self.regions.contains_key(&region_location) {
+            let path = self.directory.join(region_location.file_name());
+            let region = if path.exists() { Self::open_or_move_aside(&path) } else { None };
+
+            if self.open_order.len() >= MAX_MAPPED_REGIONS {
+                if let Some(oldest) = self.open_order.pop_front() {
+                    self.regions.remove(&oldest);
+                }
+            }
+            self.regions.insert(region_location, region);
+            self.open_order.push_back(region_location);
+        }
+
+        match &self.regions[&region_location] {
+            Some(region) => region.chunk(chunk_location),
+            None => Ok(None),
+        }
+    }
+
+    fn open_or_move_aside(path: &Path) -> Option<MappedRegion> {
+        let err = match MappedRegion::open(path) {
+            Ok(region) => return Some(region),
+            Err(err) => err,
+        };
+
+        let corrupted_path = path.with_extension("region.corrupted");
+        warn!("Could not open the region file {path:?}, its chunks are generated again and it is moved to {corrupted_path:?}: {err:#}");
+        if let Err(err) = fs::rename(path, &corrupted_path) {
+            warn!("Could not move the region file {path:?} aside: {err}");
+        }
+
+        None
+    }
+
+    /// Unmaps a region, e.g. before it is written again, so that the next load sees the new file
+    pub fn forget_region(&mut self, region_location: RegionLocation) {
+        self.regions.remove(&region_location);
+        self.open_order
+            .retain(|&location| location != region_location);
+    }
+}
+
+/// The saved chunks of one dimension of a world, stored as region files in a directory.
+///
+/// Only chunks that were modified are saved, all other chunks are generated again when they are loaded.
+pub struct WorldStorage {
+    directory: PathBuf,
+    /// Shared with the data generation threads, which load the saved chunks, see [WorldStorage::reader]
+    reader: Arc<Mutex<RegionReader>>,
+    /// Chunks that were modified since the last save
+    modified: HashSet<ChunkLocation>,
+}
+
+impl WorldStorage {
+    /// Opens the saved chunks in the given directory, which is created if it does not exist yet
+    pub fn open(directory: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&directory).with_context(|| format!("could not create world directory {directory:?}"))?;
+
+        Ok(Self {
+            reader: Arc::new(Mutex::new(RegionReader::new(directory.clone()))),
+            directory,
+            modified: HashSet::new(),
+        })
+    }
+
+    /// Loads a saved chunk, or returns None if the chunk was never saved and has to be generated
+    pub fn load_chunk(&self, chunk_location: ChunkLocation) -> Result<Option<ChunkData>> {
+        self.lock_reader().load_chunk(chunk_location)
+    }
+
+    /// Reader of the saved chunks, so that they can be loaded off the main thread
+    pub fn reader(&self) -> Arc<Mutex<RegionReader>> {
+        Arc::clone(&self.reader)
+    }
+
+    fn lock_reader(&self) -> MutexGuard<RegionReader> {
+        self.reader
+            .lock()
+            .expect("Region reader lock to not be poisoned")
+    }
+
+    /// Marks a chunk to be written by the next [WorldStorage::save]
+    pub fn mark_modified(&mut self, chunk_location: ChunkLocation) {
+        self.modified.insert(chunk_location);
+    }
+
+    pub fn is_modified(&self, chunk_location: ChunkLocation) -> bool {
+        self.modified.contains(&chunk_location)
+    }
+
+    pub fn has_unsaved_changes(&self) -> bool {
+        !self.modified.is_empty()
+    }
+
+    /// Writes all modified chunks, whose current data is returned by `chunk_data`, and returns the number of written regions.
+    ///
+    /// Every affected region file is written again with both its previously saved chunks and the modified ones.
+    /// Modified chunks without data are skipped and keep their previously saved data.
+    pub fn save<'a>(&mut self, chunk_data: impl Fn(ChunkLocation) -> Option<&'a ChunkData>) -> Result<usize> {
+        let regions = self
+            .modified
+            .iter()
+            .map(|&location| RegionLocation::of(location))
+            .unique()
+            .collect_vec();
+
+        let mut reader = self.lock_reader();
+        for &region_location in &regions {
+            let mut chunks = Vec::new();
+            for location in region_location.chunks() {
+                let modified_data = if self.modified.contains(&location) {
+                    chunk_data(location)
+                } else {
+                    None
+                };
+                // Corrupted chunks were generated again when they were loaded, so they are dropped unless they are modified
+                let data = match modified_data {
+                    Some(data) => Some(data.clone()),
+                    None => reader
+                        .load_chunk(location)
+                        .unwrap_or_else(|err| {
+                            warn!("Dropping the saved chunk at {location:?}: {err:#}");
+                            None
+                        }),
+                };
+                chunks.extend(data.map(|data| (location, data)));
+            }
+
+            reader.forget_region(region_location);
+            let chunks = chunks
+                .iter()
+                .map(|(location, data)| (*location, data))
+                .collect_vec();
+            write_region(&self.directory.join(region_location.file_name()), &chunks)?;
+        }
+
+        drop(reader);
+        self.modified.clear();
+        Ok(regions.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::location::{ChunkLocation, LocalChunkLocation};
+    use crate::world::storage::{decode_chunk, encode_chunk, write_region, RegionLocation, RegionReader, WorldStorage};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+
+    #[test]
+    fn test_region_roundtrip() {
+        let uniform = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+        let mut edited = uniform.clone();
+        edited.set_voxel_data(
+            LocalChunkLocation::new(Vector3::new(1, 2, 3))
+                .try_into_checked()
+                .unwrap(),
+            VoxelData::new(VoxelType::Water),
+        );
+
+        let mut bytes = Vec::new();
+        encode_chunk(&uniform, &mut bytes);
+        assert_eq!(bytes.len(), 2, "uniform chunks are stored as a single voxel");
+        assert!(decode_chunk(&bytes)
+            .unwrap()
+            .diff(&uniform)
+            .is_empty());
+
+        let directory = std::env::temp_dir().join(format!("voxel-region-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let (a, b) = (
+            ChunkLocation::new(Vector3::new(-1, 0, 2)),
+            ChunkLocation::new(Vector3::new(-8, 7, 0)),
+        );
+        assert_eq!(RegionLocation::of(a), RegionLocation::of(b));
+        let path = directory.join(RegionLocation::of(a).file_name());
+        write_region(&path, &[(a, &uniform), (b, &edited)]).unwrap();
+
+        let mut reader = RegionReader::new(directory.clone());
+        assert!(reader
+            .load_chunk(a)
+            .unwrap()
+            .unwrap()
+            .diff(&uniform)
+            .is_empty());
+        assert!(reader
+            .load_chunk(b)
+            .unwrap()
+            .unwrap()
+            .diff(&edited)
+            .is_empty());
+        assert!(reader
+            .load_chunk(ChunkLocation::new(Vector3::new(0, 0, 0)))
+            .unwrap()
+            .is_none());
+        assert!(reader
+            .load_chunk(ChunkLocation::new(Vector3::new(100, 0, 0)))
+            .unwrap()
+            .is_none());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_chunks_are_detected() {
+        let directory = std::env::temp_dir().join(format!("voxel-corruption-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let (a, b) = (ChunkLocation::new(Vector3::new(0, 0, 0)), ChunkLocation::new(Vector3::new(1, 0, 0)));
+        let stone = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+        let path = directory.join(RegionLocation::of(a).file_name());
+        write_region(&path, &[(a, &stone), (b, &stone)]).unwrap();
+
+        // Flip the voxel type of b, which is the last chunk in the payload
+        let mut content = std::fs::read(&path).unwrap();
+        *content.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, &content).unwrap();
+
+        let mut reader = RegionReader::new(directory.clone());
+        assert!(reader.load_chunk(a).unwrap().is_some());
+        assert!(reader.load_chunk(b).is_err());
+
+        // A broken header makes the whole region unreadable, so it is moved aside and its chunks are generated again
+        std::fs::write(&path, b"VXRG").unwrap();
+        let mut reader = RegionReader::new(directory.clone());
+        assert!(reader.load_chunk(a).unwrap().is_none());
+        assert!(!path.exists());
+        assert!(path.with_extension("region.corrupted").exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_world_storage_keeps_saved_chunks() {
+        let directory = std::env::temp_dir().join(format!("voxel-world-test-{}", std::process::id()));
+        let (a, b) = (ChunkLocation::new(Vector3::new(0, 0, 0)), ChunkLocation::new(Vector3::new(1, 0, 0)));
+        let stone = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+        let water = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Water));
+
+        let mut storage = WorldStorage::open(directory.clone()).unwrap();
+        storage.mark_modified(a);
+        assert_eq!(storage.save(|_| Some(&stone)).unwrap(), 1);
+        assert!(!storage.has_unsaved_changes());
+
+        // Saving another chunk of the same region keeps the previously saved one
+        storage.mark_modified(b);
+        storage.save(|_| Some(&water)).unwrap();
+
+        let storage = WorldStorage::open(directory.clone()).unwrap();
+        assert!(storage
+            .load_chunk(a)
+            .unwrap()
+            .unwrap()
+            .diff(&stone)
+            .is_empty());
+        assert!(storage
+            .load_chunk(b)
+            .unwrap()
+            .unwrap()
+            .diff(&water)
+            .is_empty());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
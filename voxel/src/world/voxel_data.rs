@@ -1,4 +1,10 @@
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::world::chunk_renderer::meshing::direction::Direction;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct VoxelData {
     pub ty: VoxelType,
 }
@@ -9,10 +15,167 @@ impl VoxelData {
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, enum_map::Enum)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, enum_map::Enum, Serialize, Deserialize)]
 pub enum VoxelType {
     Air,
     Dirt,
     Grass,
     Stone,
+    Sand,
+    Water,
+    /// Thin layer of snow on top of the terrain, see [VoxelShape::Layer]
+    Snow,
+    Ice,
+    /// Translucent voxel, see [VoxelShape::Translucent]
+    Glass,
+    /// Trunk of the trees that are placed by the structure generation
+    Log,
+    Leaves,
+    /// Placeholder of chunks whose generation or meshing failed, which is rendered as a magenta box instead of leaving a hole in
+    /// the world. It is never generated otherwise and cannot be held.
+    Error,
+}
+
+/// Geometry of a voxel type, which decides how it is meshed and whether it collides
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VoxelShape {
+    Empty,
+    /// Full solid voxel
+    Cube,
+    /// Partial voxel that covers the bottom of its cell up to the given height in eighths of a voxel.
+    /// It is rendered with the opaque terrain, but does not collide or hide the faces of its neighbors.
+    Layer {
+        height: u8,
+    },
+    /// Voxel that is rendered in the transparent water pass
+    Liquid,
+    /// Full voxel that collides, but is rendered in the transparent pass and does not hide the faces of its neighbors
+    Translucent,
+}
+
+/// Physical properties of the surface of a voxel type
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhysicsMaterial {
+    /// How quickly the horizontal velocity of a body on top of this surface follows its movement input, per second
+    pub friction: f32,
+    /// Fraction of the vertical velocity that is kept as upwards velocity when landing on this surface
+    pub restitution: f32,
+}
+
+/// Tiles of the texture atlas that are used for the faces of a voxel type, see [crate::rendering::texture::ATLAS_TILES_PER_ROW]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FaceTiles {
+    pub top: u8,
+    pub side: u8,
+    pub bottom: u8,
+}
+
+impl FaceTiles {
+    pub const fn uniform(tile: u8) -> Self {
+        Self {
+            top: tile,
+            side: tile,
+            bottom: tile,
+        }
+    }
+
+    pub const fn tile(self, direction: Direction) -> u8 {
+        match direction {
+            Direction::YPos => self.top,
+            Direction::YNeg => self.bottom,
+            Direction::XPos | Direction::XNeg | Direction::ZPos | Direction::ZNeg => self.side,
+        }
+    }
+}
+
+impl VoxelType {
+    pub const fn shape(self) -> VoxelShape {
+        match self {
+            VoxelType::Air => VoxelShape::Empty,
+            VoxelType::Water => VoxelShape::Liquid,
+            VoxelType::Snow => VoxelShape::Layer { height: 2 },
+            VoxelType::Glass => VoxelShape::Translucent,
+            VoxelType::Dirt
+            | VoxelType::Grass
+            | VoxelType::Stone
+            | VoxelType::Sand
+            | VoxelType::Ice
+            | VoxelType::Log
+            | VoxelType::Leaves
+            | VoxelType::Error => VoxelShape::Cube,
+        }
+    }
+
+    /// Whether this voxel blocks movement and can be targeted. Voxels with a [VoxelShape::Cube] or [VoxelShape::Translucent] are solid.
+    pub const fn is_solid(self) -> bool {
+        matches!(self.shape(), VoxelShape::Cube | VoxelShape::Translucent)
+    }
+
+    /// Whether this voxel hides the faces of its neighbors and occludes ambient light. Only voxels with a [VoxelShape::Cube] are opaque.
+    pub const fn is_opaque(self) -> bool {
+        matches!(self.shape(), VoxelShape::Cube)
+    }
+
+    /// Tiles of the texture atlas for the faces of this voxel type, or None if it is not textured.
+    /// Tiles are numbered row by row from the top left of the atlas.
+    pub const fn face_tiles(self) -> Option<FaceTiles> {
+        match self {
+            VoxelType::Air | VoxelType::Water | VoxelType::Glass | VoxelType::Log | VoxelType::Leaves | VoxelType::Error => None,
+            VoxelType::Dirt => Some(FaceTiles::uniform(0)),
+            VoxelType::Grass => Some(FaceTiles {
+                top: 1,
+                side: 2,
+                bottom: 0,
+            }),
+            VoxelType::Stone => Some(FaceTiles::uniform(3)),
+            VoxelType::Sand => Some(FaceTiles::uniform(4)),
+            VoxelType::Snow => Some(FaceTiles::uniform(5)),
+            VoxelType::Ice => Some(FaceTiles::uniform(6)),
+        }
+    }
+
+    /// Time it takes to break a voxel of this type
+    pub const fn hardness(self) -> Duration {
+        match self {
+            VoxelType::Air | VoxelType::Water => Duration::ZERO,
+            VoxelType::Snow => Duration::from_millis(100),
+            VoxelType::Leaves => Duration::from_millis(200),
+            VoxelType::Glass => Duration::from_millis(300),
+            VoxelType::Sand => Duration::from_millis(400),
+            VoxelType::Dirt => Duration::from_millis(500),
+            VoxelType::Grass => Duration::from_millis(600),
+            VoxelType::Ice => Duration::from_millis(700),
+            VoxelType::Log => Duration::from_millis(1000),
+            VoxelType::Stone | VoxelType::Error => Duration::from_millis(1500),
+        }
+    }
+
+    pub const fn physics_material(self) -> PhysicsMaterial {
+        match self {
+            // Used while in the air, so that the movement can still be steered a little
+            VoxelType::Air => PhysicsMaterial {
+                friction: 2.0,
+                restitution: 0.0,
+            },
+            // Movement in water is sluggish, but can still be steered
+            VoxelType::Water => PhysicsMaterial {
+                friction: 4.0,
+                restitution: 0.0,
+            },
+            VoxelType::Dirt | VoxelType::Grass | VoxelType::Sand | VoxelType::Snow | VoxelType::Log | VoxelType::Leaves => {
+                PhysicsMaterial {
+                    friction: 20.0,
+                    restitution: 0.0,
+                }
+            }
+            VoxelType::Stone | VoxelType::Glass | VoxelType::Error => PhysicsMaterial {
+                friction: 12.0,
+                restitution: 0.1,
+            },
+            VoxelType::Ice => PhysicsMaterial {
+                friction: 1.5,
+                restitution: 0.0,
+            },
+        }
+    }
 }
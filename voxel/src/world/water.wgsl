@@ -0,0 +1,153 @@
+struct CameraUniform {
+	position: vec4<f32>,
+    view_proj: mat4x4<f32>,
+    clouds: vec4<f32>,
+    // Color of the fog and the distance at which it completely hides the terrain
+    fog: vec4<f32>,
+}
+
+struct WaterPushConstants {
+	chunk_position: vec3<f32>,
+	// Seconds since the start of the water animation
+	time: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+var<push_constant> water: WaterPushConstants;
+
+struct VertexInput {
+	@location(0) position_x_y_z_color_r: u32,
+	@location(1) color_g_b_normal_ao: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+// Same vertex layout as the chunk meshes, see shader.wgsl
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+	// The surface is lowered a little, so that the shore is visible above it
+	return unpack_vertex(model, vec3(0.0, -0.1, 0.0));
+}
+
+@vertex
+fn vs_translucent(model: VertexInput) -> VertexOutput {
+	return unpack_vertex(model, vec3(0.0));
+}
+
+fn unpack_vertex(model: VertexInput, offset: vec3<f32>) -> VertexOutput {
+	let position = vec3(
+		f32((model.position_x_y_z_color_r & 0xFF000000u) >> 24u),
+		f32((model.position_x_y_z_color_r & 0x00FF0000u) >> 16u),
+		f32((model.position_x_y_z_color_r & 0x0000FF00u) >> 8u),
+	);
+	let color = vec3(
+		f32(model.position_x_y_z_color_r & 0x000000FFu) / 255.0,
+		f32((model.color_g_b_normal_ao & 0xFF000000u) >> 24u) / 255.0,
+		f32((model.color_g_b_normal_ao & 0x00FF0000u) >> 16u) / 255.0,
+	);
+
+	var NORMAL_LOOKUP = array<vec3<f32>, 6>(
+		vec3(0.0, 0.0, 1.0),
+		vec3(0.0, 1.0, 0.0),
+		vec3(1.0, 0.0, 0.0),
+		vec3(0.0, 0.0, -1.0),
+		vec3(0.0, -1.0, 0.0),
+		vec3(-1.0, 0.0, 0.0));
+
+	let world_position = position + water.chunk_position + offset;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4(world_position, 1.0);
+    out.world_position = world_position;
+    out.color = color;
+    out.normal = NORMAL_LOOKUP[(model.color_g_b_normal_ao & 0x0000E000u) >> 13u];
+    return out;
+}
+
+// Normal of the face, tilted by two sets of waves that scroll in different directions
+fn ripple_normal(in: VertexOutput) -> vec3<f32> {
+	let p = in.world_position.xz;
+	let t = water.time;
+
+	let tilt = vec2(
+		0.6 * cos(1.3 * p.x + 0.7 * p.y + 1.7 * t) + 0.4 * cos(2.9 * p.x - 1.1 * p.y + 2.3 * t),
+		0.6 * cos(0.9 * p.x - 1.4 * p.y + 1.3 * t) + 0.4 * cos(-1.7 * p.x + 2.6 * p.y + 2.9 * t),
+	);
+
+	return normalize(in.normal + 0.08 * vec3(tilt.x, 0.0, tilt.y) * in.normal.y);
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+	let normal = ripple_normal(in);
+	let to_sun = normalize(vec3(1.0, 0.5, 0.7));
+	let to_camera = normalize(camera.position.xyz - in.world_position);
+
+	let diffuse = 0.4 + 0.6 * max(dot(normal, to_sun), 0.0);
+	let specular = pow(max(dot(reflect(-to_sun, normal), to_camera), 0.0), 32.0);
+	// Looking at the water from a flat angle reflects more of the sky, so it is less transparent
+	let fresnel = pow(1.0 - max(dot(normal, to_camera), 0.0), 3.0);
+
+	let sky = vec3(0.4941, 0.6627, 1.0);
+	var color = mix(diffuse * in.color, sky, 0.5 * fresnel) + vec3(specular);
+
+	let fog_dist = distance(camera.position.xyz, in.world_position);
+	color = mix(camera.fog.xyz, color, fog_factor(fog_dist));
+
+	return vec4(color, mix(0.6, 0.9, fresnel));
+}
+
+// Translucent voxels like glass, which are flat and more transparent than water when looked at straight on
+fn shade_translucent(in: VertexOutput) -> vec4<f32> {
+	let to_sun = normalize(vec3(1.0, 0.5, 0.7));
+	let to_camera = normalize(camera.position.xyz - in.world_position);
+
+	let diffuse = 0.6 + 0.4 * max(dot(in.normal, to_sun), 0.0);
+	let fresnel = pow(1.0 - abs(dot(in.normal, to_camera)), 3.0);
+
+	let fog_dist = distance(camera.position.xyz, in.world_position);
+	let color = mix(camera.fog.xyz, diffuse * in.color, fog_factor(fog_dist));
+
+	return vec4(color, mix(0.3, 0.8, fresnel));
+}
+
+// Same fog as in shader.wgsl. Sharp fog at the end of the render distance, but gradual fog for the short fog distances in caves and under water
+fn fog_factor(fog_dist: f32) -> f32 {
+	let exponent = clamp(camera.fog.w / 64.0, 2.0, 16.0);
+	return 1.0 - clamp(pow(fog_dist / camera.fog.w, exponent), 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+// Used instead of fs_main if the surface format is not sRGB, so that the color has to be encoded by the shader
+@fragment
+fn fs_main_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+	let color = shade(in);
+    return vec4(linear_to_srgb(color.rgb), color.a);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, color < vec3(0.0031308));
+}
+
+@fragment
+fn fs_translucent(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade_translucent(in);
+}
+
+@fragment
+fn fs_translucent_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+	let color = shade_translucent(in);
+    return vec4(linear_to_srgb(color.rgb), color.a);
+}
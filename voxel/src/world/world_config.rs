@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumString;
+
+use crate::build_info::BUILD_INFO;
+use crate::ron_file;
+
+/// Name of the file in the world directory in which the config that the world was created with is saved
+pub const WORLD_CONFIG_FILE_NAME: &str = "world.ron";
+
+/// Seed of worlds that were created before the seed could be chosen
+pub const DEFAULT_SEED: u32 = 123;
+/// The nether of worlds that were created before the seed could be chosen used the seed 666
+const NETHER_SEED_OFFSET: u32 = 543;
+
+/// Terrain generator of a world
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum GeneratorKind {
+    /// Noise based terrain with biomes, caves and structures, see [crate::world::worldgen_preset::WorldgenPreset]
+    #[default]
+    Terrain,
+    /// Flat grass surface at [WorldConfig::flat_height] without any structures
+    Flat,
+}
+
+/// Everything that decides how the chunks of a world are generated.
+///
+/// It is saved in the world directory when the world is created, so that a saved world is always generated the same way.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    pub seed: u32,
+    pub generator_kind: GeneratorKind,
+    /// Height of the surface of [GeneratorKind::Flat] worlds
+    pub flat_height: i32,
+    /// Build that created the world, see [BUILD_INFO]. None for worlds that were created before it was recorded.
+    /// It does not affect the generation, so it is ignored when configs are compared.
+    pub created_with: Option<String>,
+}
+
+impl PartialEq for WorldConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.seed == other.seed && self.generator_kind == other.generator_kind && self.flat_height == other.flat_height
+    }
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            generator_kind: GeneratorKind::default(),
+            flat_height: 0,
+            created_with: None,
+        }
+    }
+}
+
+impl WorldConfig {
+    /// Config of the nether, whose seed is derived from the seed of the world
+    pub fn nether(&self) -> Self {
+        Self {
+            seed: self.seed.wrapping_add(NETHER_SEED_OFFSET),
+            ..self.clone()
+        }
+    }
+
+    /// Loads the config that the world at the given path was created with.
+    /// If the world has no saved config yet, it is created with the given config, which is saved for the next time.
+    pub fn load_or_create(path: &Path, new_world: WorldConfig) -> Self {
+        if !path.exists() {
+            let new_world = WorldConfig {
+                created_with: Some(BUILD_INFO.to_string()),
+                ..new_world
+            };
+            if let Err(err) = new_world.save(path) {
+                warn!("Could not save the world config to {path:?}: {err:#}");
+            }
+            return new_world;
+        }
+
+        match ron_file::load::<Self>(path, "world config") {
+            Ok(saved) => {
+                info!(
+                    "Loading a world that was created with {}",
+                    saved
+                        .created_with
+                        .as_deref()
+                        .unwrap_or("an unknown build")
+                );
+                if saved != new_world {
+                    info!("Generating the world with its saved config {saved:?} instead of {new_world:?}");
+                }
+                saved
+            }
+            Err(err) => {
+                warn!("Could not load the world config from {path:?}, using {new_world:?} instead: {err:#}");
+                new_world
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        ron_file::save(path, self, "world config")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::world::world_config::{GeneratorKind, WorldConfig};
+
+    #[test]
+    fn test_nether_seed_of_default_world() {
+        let config = WorldConfig::default();
+
+        assert_eq!(config.nether().seed, 666);
+        assert_eq!(config.nether().generator_kind, config.generator_kind);
+    }
+
+    #[test]
+    fn test_configs_of_different_builds_are_equal() {
+        let config = WorldConfig {
+            created_with: Some("0.1.0 (unknown, debug)".to_string()),
+            ..WorldConfig::default()
+        };
+
+        assert_eq!(config, WorldConfig::default());
+        assert_ne!(
+            config,
+            WorldConfig {
+                seed: 1,
+                ..WorldConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_generator_kind_from_str() {
+        assert_eq!(GeneratorKind::from_str("flat").unwrap(), GeneratorKind::Flat);
+        assert_eq!(GeneratorKind::from_str("terrain").unwrap(), GeneratorKind::Terrain);
+        assert!(GeneratorKind::from_str("caves").is_err());
+    }
+}
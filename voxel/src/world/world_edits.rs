@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::world::chunk_data::{ChunkData, ChunkDiff};
+use crate::world::location::{ChunkLocation, LocalChunkLocation, WithinBounds};
+use crate::world::voxel_data::VoxelData;
+
+/// Voxels that were edited by the player, so that they can be applied again when an unloaded chunk is generated again.
+///
+/// Only the last edit of every voxel is kept, in memory. Saved chunks of a persisted world are loaded instead, as they contain the edits.
+#[derive(Clone, Debug, Default)]
+pub struct WorldEdits {
+    /// Edited voxels by their index (see [ChunkData::position_to_index]), by the chunk that contains them
+    chunks: HashMap<ChunkLocation, BTreeMap<u16, VoxelData>>,
+}
+
+impl WorldEdits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, chunk_location: ChunkLocation, local_location: LocalChunkLocation<WithinBounds>, voxel_data: VoxelData) {
+        self.chunks
+            .entry(chunk_location)
+            .or_default()
+            .insert(ChunkData::position_to_index(local_location) as u16, voxel_data);
+    }
+
+    /// Changes that apply the edits of a chunk on top of its generated data, if it was edited
+    pub fn diff(&self, chunk_location: ChunkLocation) -> Option<ChunkDiff> {
+        let voxels = self.chunks.get(&chunk_location)?;
+
+        let mut runs: Vec<(u16, Vec<VoxelData>)> = Vec::new();
+        for (&index, &voxel_data) in voxels {
+            match runs.last_mut() {
+                Some((start, voxels)) if *start as usize + voxels.len() == index as usize => voxels.push(voxel_data),
+                _ => runs.push((index, vec![voxel_data])),
+            }
+        }
+
+        Some(ChunkDiff { runs })
+    }
+
+    /// Chunks that contain at least one edited voxel
+    pub fn chunk_locations(&self) -> impl Iterator<Item = ChunkLocation> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::chunk_data::ChunkData;
+    use crate::world::location::{ChunkLocation, LocalChunkLocation};
+    use crate::world::voxel_data::{VoxelData, VoxelType};
+    use crate::world::world_edits::WorldEdits;
+
+    #[test]
+    fn test_edits_are_applied_to_regenerated_chunk() {
+        let chunk_location = ChunkLocation::new(Vector3::new(1, -2, 3));
+        let generated = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+
+        let mut edited = generated.clone();
+        let mut edits = WorldEdits::new();
+        for (x, ty) in [(4, VoxelType::Glass), (5, VoxelType::Air), (9, VoxelType::Log), (4, VoxelType::Air)] {
+            let local_location = LocalChunkLocation::new_unchecked(Vector3::new(x, 7, 2));
+            edited.set_voxel_data(local_location, VoxelData::new(ty));
+            edits.record(chunk_location, local_location, VoxelData::new(ty));
+        }
+
+        let mut regenerated = generated.clone();
+        regenerated.apply(&edits.diff(chunk_location).unwrap());
+
+        assert_eq!(regenerated.diff(&edited), Default::default());
+        assert_eq!(edits.diff(chunk_location).unwrap().len(), 3);
+        assert!(edits
+            .diff(ChunkLocation::new(Vector3::new(0, 0, 0)))
+            .is_none());
+    }
+}
@@ -1,30 +1,215 @@
 use cgmath::num_traits::real::Real;
 use cgmath::num_traits::Pow;
+use cgmath::Vector3;
+use fastrand::Rng;
+use itertools::iproduct;
 use noise::{NoiseFn, Perlin};
 
 use crate::world::chunk_data::ChunkData;
-use crate::world::location::{ChunkLocation, LocalChunkLocation};
+use crate::world::location::{ChunkLocation, LocalChunkLocation, WithinBounds, WorldLocation};
+use crate::world::noise_source::NoiseSource;
 use crate::world::voxel_data::{VoxelData, VoxelType};
+use crate::world::world_config::{GeneratorKind, WorldConfig};
+use crate::world::worldgen_preset::{Biome, NoiseLayer, WorldgenPreset};
 use crate::world::CHUNK_SIZE;
 
 pub struct WorldGenerator {
-    world_seed: u32,
+    config: WorldConfig,
+    preset: WorldgenPreset,
 }
 
 impl WorldGenerator {
-    pub fn new(world_seed: u32) -> Self {
-        Self { world_seed }
+    pub fn new(config: WorldConfig, preset: WorldgenPreset) -> Self {
+        Self { config, preset }
     }
 
+    pub fn world_seed(&self) -> u32 {
+        self.config.seed
+    }
+
+    pub fn config(&self) -> &WorldConfig {
+        &self.config
+    }
+
+    /// Voxels of a chunk including its own structures, but without the structures of its neighbors that reach into it
     pub fn get_chunk_data_at(&self, chunk_location: ChunkLocation) -> ChunkData {
         // ChunkData::new_with_uniform_data(VoxelData::world(VoxelType::Dirt))
-        // flat_perlin_terrain(self.world_seed, chunk_location)
+        // ChunkData::Voxels(Box::new(CONST_CHUNK.clone()))
         //perlin_3d(1, chunk_location)
-        ChunkData::Voxels(Box::new(CONST_CHUNK.clone()))
+        self.generate_chunk(chunk_location).data
+    }
+
+    /// Generates the terrain of a chunk and places the structures whose origin is in the chunk.
+    /// Structure voxels in other chunks are returned separately, so that they can be placed once those chunks are generated.
+    pub fn generate_chunk(&self, chunk_location: ChunkLocation) -> GeneratedChunk {
+        let mut data = match self.config.generator_kind {
+            GeneratorKind::Terrain => flat_perlin_terrain(self.config.seed, &self.preset, chunk_location),
+            GeneratorKind::Flat => flat_terrain(self.config.flat_height, chunk_location),
+        };
+        let mut outside_voxels = Vec::new();
+
+        let structure_voxels = self.structure_voxels(chunk_location);
+        let has_structures = !structure_voxels.is_empty();
+        for (location, voxel_type) in structure_voxels {
+            match location.separate() {
+                (target, local_location) if target == chunk_location => {
+                    place_structure_voxel(&mut data, local_location, voxel_type);
+                }
+                _ => outside_voxels.push((location, voxel_type)),
+            }
+        }
+
+        if has_structures {
+            data.compress();
+        }
+
+        GeneratedChunk { data, outside_voxels }
+    }
+
+    /// Structure voxels of a chunk that are in other chunks, without generating its terrain.
+    /// This is needed for chunks that are loaded from storage, whose own voxels already contain their structures.
+    pub fn outside_structure_voxels(&self, chunk_location: ChunkLocation) -> Vec<(WorldLocation, VoxelType)> {
+        self.structure_voxels(chunk_location)
+            .into_iter()
+            .filter(|(location, _)| location.separate().0 != chunk_location)
+            .collect()
+    }
+
+    /// Voxels of all structures whose origin is in the chunk.
+    /// They only depend on the world seed and the chunk location, so a chunk always gets the same structures.
+    fn structure_voxels(&self, chunk_location: ChunkLocation) -> Vec<(WorldLocation, VoxelType)> {
+        let mut voxels = Vec::new();
+        if self.config.generator_kind != GeneratorKind::Terrain
+            || chunk_location.y > self.preset.max_chunk_y
+            || chunk_location.y < self.preset.min_chunk_y
+        {
+            return voxels;
+        }
+
+        let preset = &self.preset.structures;
+        let sampler = TerrainSampler::new(self.config.seed, &self.preset);
+        let mut rng = Rng::with_seed(chunk_seed(self.config.seed, chunk_location));
+
+        for _ in 0..preset.tree_attempts {
+            let origin = surface_origin(&sampler, &mut rng, chunk_location);
+            let trunk_height = rng.i32(preset.trunk_height[0]..=preset.trunk_height[1].max(preset.trunk_height[0]));
+
+            if let Some(origin) =
+                origin.filter(|&origin| sampler.base_voxel((origin - Vector3::unit_y()).cast().unwrap()) == VoxelType::Grass)
+            {
+                tree(origin, trunk_height, &mut voxels);
+            }
+        }
+
+        if rng.f64() < preset.boulder_chance {
+            let origin = surface_origin(&sampler, &mut rng, chunk_location);
+            let radius = rng.i32(1..=preset.max_boulder_radius.max(1));
+
+            if let Some(origin) = origin {
+                boulder(origin, radius, &mut voxels);
+            }
+        }
+
+        voxels
     }
 }
 
-const CONST_CHUNK: [VoxelData; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE] = a();
+/// Terrain of a chunk with its structures, see [WorldGenerator::generate_chunk]
+pub struct GeneratedChunk {
+    pub data: ChunkData,
+    /// Voxels of the structures of the chunk that are in neighboring chunks
+    pub outside_voxels: Vec<(WorldLocation, VoxelType)>,
+}
+
+/// Generates the chunks of a dimension on the data generation threads of the [crate::world::chunk_manager::ChunkManager].
+///
+/// A chunk must always be generated the same way, because unloaded chunks are generated again when the player returns.
+pub trait ChunkGenerator: Send + Sync {
+    fn generate_chunk(&self, chunk_location: ChunkLocation) -> GeneratedChunk;
+
+    /// Structure voxels of a chunk that are in other chunks, which are placed around chunks that are loaded from storage
+    fn outside_structure_voxels(&self, _chunk_location: ChunkLocation) -> Vec<(WorldLocation, VoxelType)> {
+        Vec::new()
+    }
+
+    /// Config of the world that the generator is built from. Worldgen presets can only be applied to generators that have one.
+    fn world_config(&self) -> Option<&WorldConfig> {
+        None
+    }
+}
+
+impl ChunkGenerator for WorldGenerator {
+    fn generate_chunk(&self, chunk_location: ChunkLocation) -> GeneratedChunk {
+        WorldGenerator::generate_chunk(self, chunk_location)
+    }
+
+    fn outside_structure_voxels(&self, chunk_location: ChunkLocation) -> Vec<(WorldLocation, VoxelType)> {
+        WorldGenerator::outside_structure_voxels(self, chunk_location)
+    }
+
+    fn world_config(&self) -> Option<&WorldConfig> {
+        Some(&self.config)
+    }
+}
+
+/// Places a voxel of a structure, unless it is taken by the terrain or another structure. Returns whether the voxel was placed.
+pub fn place_structure_voxel(data: &mut ChunkData, location: LocalChunkLocation<WithinBounds>, voxel_type: VoxelType) -> bool {
+    if !matches!(data.get_voxel(location).ty, VoxelType::Air | VoxelType::Snow) {
+        return false;
+    }
+
+    data.set_voxel_data(location, VoxelData::new(voxel_type));
+    true
+}
+
+/// Seed of the structures of a chunk, which mixes the world seed with the chunk location
+fn chunk_seed(world_seed: u32, chunk_location: ChunkLocation) -> u64 {
+    [chunk_location.x, chunk_location.y, chunk_location.z]
+        .into_iter()
+        .fold(world_seed as u64, |seed, coordinate| {
+            (seed ^ coordinate as u32 as u64)
+                .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+                .rotate_left(31)
+        })
+}
+
+/// First voxel above the terrain surface in a random column of the chunk, if it is in the chunk and not covered by water
+fn surface_origin(sampler: &TerrainSampler, rng: &mut Rng, chunk_location: ChunkLocation) -> Option<Vector3<i32>> {
+    let chunk_origin = chunk_location.to_world_location_f64();
+    let x = chunk_origin.x as i32 + rng.i32(0..CHUNK_SIZE as i32);
+    let z = chunk_origin.z as i32 + rng.i32(0..CHUNK_SIZE as i32);
+    let origin = Vector3::new(x, sampler.height(x as f64, z as f64).ceil() as i32, z);
+
+    let surface = sampler.base_voxel((origin - Vector3::unit_y()).cast().unwrap());
+    let is_in_chunk = origin.y.div_euclid(CHUNK_SIZE as i32) == chunk_location.y;
+    let is_open = sampler.base_voxel(origin.cast().unwrap()) == VoxelType::Air;
+
+    (is_in_chunk && is_open && surface.is_solid()).then_some(origin)
+}
+
+/// A log trunk that starts at the origin, with a crown of leaves around its top
+fn tree(origin: Vector3<i32>, trunk_height: i32, voxels: &mut Vec<(WorldLocation, VoxelType)>) {
+    voxels.extend((0..trunk_height).map(|y| (WorldLocation(origin + Vector3::new(0, y, 0)), VoxelType::Log)));
+
+    let top = origin + Vector3::new(0, trunk_height - 1, 0);
+    for (x, y, z) in iproduct!(-2..=2, -2..=1, -2..=2) {
+        let max_distance = if y == 1 { 1 } else { 5 };
+        if x * x + z * z <= max_distance {
+            voxels.push((WorldLocation(top + Vector3::new(x, y, z)), VoxelType::Leaves));
+        }
+    }
+}
+
+/// A roughly spherical stone, whose center is at the origin and which is partly sunk into the terrain
+fn boulder(origin: Vector3<i32>, radius: i32, voxels: &mut Vec<(WorldLocation, VoxelType)>) {
+    for (x, y, z) in iproduct!(-radius..=radius, -radius..=radius, -radius..=radius) {
+        if x * x + y * y + z * z <= radius * radius + radius {
+            voxels.push((WorldLocation(origin + Vector3::new(x, y, z)), VoxelType::Stone));
+        }
+    }
+}
+
+pub const CONST_CHUNK: [VoxelData; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE] = a();
 
 const fn a() -> [VoxelData; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE] {
     let mut data = [VoxelData::new(VoxelType::Air); CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
@@ -68,7 +253,7 @@ pub fn waves(chunk_location: ChunkLocation) -> ChunkData {
         }
     });
 
-    chunk_voxel_data.try_convert_into_uniform();
+    chunk_voxel_data.compress();
 
     chunk_voxel_data
 }
@@ -102,115 +287,250 @@ pub fn perlin_3d(world_seed: u32, chunk_location: ChunkLocation) -> ChunkData {
 }
 const EMPTY_CHUNK: ChunkData = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
 const STONE_CHUNK: ChunkData = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Stone));
+/// Layers of dirt between the grass and the stone of [flat_terrain]
+const FLAT_DIRT_DEPTH: i32 = 3;
 
-pub fn flat_perlin_terrain(world_seed: u32, chunk_location: ChunkLocation) -> ChunkData {
+pub fn flat_perlin_terrain(world_seed: u32, preset: &WorldgenPreset, chunk_location: ChunkLocation) -> ChunkData {
     // Create empty chunk data
     let mut chunk_voxel_data = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
 
-    let mut perlin = Perlin::new(world_seed);
-    let mut cave_perlin = Perlin::new(world_seed + 1);
-
-    let octaves = vec![
-        NoiseLayer { scale: 0.002, weight: 1.5 },
-        NoiseLayer { scale: 0.007, weight: 0.9 },
-        NoiseLayer { scale: 0.02, weight: 0.3 },
-        NoiseLayer { scale: 0.07, weight: 0.06 },
-        NoiseLayer { scale: 0.4, weight: 0.03 },
-    ];
-
-    if chunk_location.y > 2 {
+    if chunk_location.y > preset.max_chunk_y {
         return EMPTY_CHUNK.clone();
     }
 
-    if chunk_location.y < -3 {
+    if chunk_location.y < preset.min_chunk_y {
         return STONE_CHUNK.clone();
     }
 
+    let sampler = TerrainSampler::new(world_seed, preset);
+
     // Fill empty chunk data with randomly selected voxels
     LocalChunkLocation::iter().for_each(|pos| {
         let coords = pos.to_f64() + chunk_location.to_world_location_f64();
 
-        let layered_perlin = perlin.get_layered(&octaves, [coords.x, coords.z]);
-        let normalized_height = (layered_perlin + 1.0) / 2.0;
-        let height = 16.0 * normalized_height + 1.0;
+        let voxel_type = sampler.decorated_voxel(coords);
+        if voxel_type != VoxelType::Air {
+            chunk_voxel_data.set_voxel_data(pos, VoxelData::new(voxel_type));
+        }
+    });
+
+    chunk_voxel_data.compress();
+
+    chunk_voxel_data
+}
+
+/// Grass on top of a few layers of dirt above stone, with the grass at the given height
+pub fn flat_terrain(height: i32, chunk_location: ChunkLocation) -> ChunkData {
+    let chunk_bottom = chunk_location.y * CHUNK_SIZE as i32;
+    if chunk_bottom > height {
+        return EMPTY_CHUNK.clone();
+    }
+    if chunk_bottom + (CHUNK_SIZE as i32) < height - FLAT_DIRT_DEPTH {
+        return STONE_CHUNK.clone();
+    }
+
+    let mut chunk_voxel_data = ChunkData::new_with_uniform_data(VoxelData::new(VoxelType::Air));
+    LocalChunkLocation::iter().for_each(|pos| {
+        let y = chunk_bottom + pos.y;
+        let voxel_type = match height - y {
+            0 => VoxelType::Grass,
+            depth if depth > FLAT_DIRT_DEPTH => VoxelType::Stone,
+            depth if depth > 0 => VoxelType::Dirt,
+            _ => VoxelType::Air,
+        };
+        if voxel_type != VoxelType::Air {
+            chunk_voxel_data.set_voxel_data(pos, VoxelData::new(voxel_type));
+        }
+    });
+
+    chunk_voxel_data.compress();
+
+    chunk_voxel_data
+}
+
+/// Samples the voxels of [flat_perlin_terrain] at any world position.
+///
+/// Because the terrain is a pure function of the position, the decoration stage can query the neighbors of a voxel
+/// without waiting for the neighboring chunks to be generated.
+struct TerrainSampler<'a> {
+    preset: &'a WorldgenPreset,
+    terrain_noise: Box<dyn NoiseSource>,
+    cave_perlin: Perlin,
+    biome_perlin: Perlin,
+}
+
+impl<'a> TerrainSampler<'a> {
+    fn new(world_seed: u32, preset: &'a WorldgenPreset) -> Self {
+        Self {
+            preset,
+            terrain_noise: preset.terrain_noise.build(world_seed),
+            cave_perlin: Perlin::new(world_seed + 1),
+            biome_perlin: Perlin::new(world_seed + 2),
+        }
+    }
+
+    fn height(&self, x: f64, z: f64) -> f64 {
+        terrain_height(self.preset, self.terrain_noise.as_ref(), x, z)
+    }
+
+    fn biome(&self, x: f64, z: f64) -> Option<&'a Biome> {
+        self.preset.biome(
+            self.biome_perlin
+                .get_layered(&self.preset.biome_layers, [x, z]),
+        )
+    }
+
+    /// Voxel of the terrain, the sea and the caves before the decoration stage
+    fn base_voxel(&self, coords: Vector3<f64>) -> VoxelType {
+        let preset = self.preset;
+        let caves = &preset.caves;
+        let cave_perlin = &self.cave_perlin;
+        let column = [coords.x, coords.z];
+
+        let height = self.height(coords.x, coords.z);
+        let (surface, filler) = if height < preset.sea_level + preset.beach_height {
+            (VoxelType::Sand, VoxelType::Sand)
+        } else {
+            self.biome(coords.x, coords.z)
+                .map_or((VoxelType::Grass, VoxelType::Dirt), |biome| (biome.surface, biome.filler))
+        };
 
         let voxel_type = if coords.y < height {
             {
                 if coords.y + 1.0 < height {
-                    if coords.y + 6.0 < height {
+                    if coords.y + preset.stone_depth < height {
                         VoxelType::Stone
                     } else {
-                        VoxelType::Dirt
+                        filler
                     }
                 } else {
-                    VoxelType::Grass
+                    surface
                 }
             }
+        } else if coords.y < preset.sea_level {
+            VoxelType::Water
         } else {
             VoxelType::Air
         };
 
-        if coords.y
-            < cave_perlin.get_layered(
-                &[
-                    NoiseLayer { scale: 0.002, weight: 4.0 },
-                    NoiseLayer { scale: 0.02, weight: 1.0 },
-                    NoiseLayer { scale: 0.08, weight: 3.0 },
-                ],
-                [coords.x, coords.z],
-            ) - 15.0
-            && coords.y
-                > cave_perlin.get_layered(
-                    &[
-                        NoiseLayer { scale: 0.002, weight: 3.0 },
-                        NoiseLayer { scale: 0.04, weight: 3.0 },
-                        NoiseLayer { scale: 0.08, weight: 0.3 },
-                    ],
-                    [coords.x, coords.z],
-                ) - 30.0
-            && cave_perlin.get_layered(
-                &[
-                    NoiseLayer { scale: 0.03, weight: 0.7 },
-                    NoiseLayer { scale: 0.08, weight: 0.2 },
-                    NoiseLayer { scale: 0.1, weight: 0.02 },
-                ],
-                [coords.x, coords.z],
-            ) < 0.4 * cave_perlin.get([coords.y * 0.09, 0.0])
-            || cave_perlin.get_layered(
-                &[
-                    NoiseLayer { scale: 0.03, weight: 0.7 },
-                    NoiseLayer { scale: 0.08, weight: 0.2 },
-                    NoiseLayer { scale: 0.1, weight: 0.02 },
-                ],
-                [coords.x, coords.z],
-            ) < -0.8 + 0.5 * cave_perlin.get([coords.y * 0.02, coords.x * 0.02 + coords.z * 0.03])
-                && coords.y > -30.0
+        let cave_shape = cave_perlin.get_layered(&caves.shape_layers, column);
+        // Caves are only carved into the terrain, so that they do not drain the sea
+        if voxel_type.is_solid()
+            && (coords.y < cave_perlin.get_layered(&caves.ceiling_layers, column) + caves.ceiling_offset
+                && coords.y > cave_perlin.get_layered(&caves.floor_layers, column) + caves.floor_offset
+                && cave_shape < 0.4 * cave_perlin.get([coords.y * 0.09, 0.0])
+                || cave_shape < -0.8 + 0.5 * cave_perlin.get([coords.y * 0.02, coords.x * 0.02 + coords.z * 0.03])
+                    && coords.y > caves.min_tunnel_height)
         {
-            // Air
+            VoxelType::Air
         } else {
-            chunk_voxel_data.set_voxel_data(pos, VoxelData::new(voxel_type));
+            voxel_type
         }
-    });
+    }
 
-    chunk_voxel_data.try_convert_into_uniform();
+    /// Whether the voxel is open to the sky and rests on a solid voxel of the terrain surface, which excludes cave floors
+    fn is_exposed_surface(&self, coords: Vector3<f64>) -> bool {
+        coords.y >= self.height(coords.x, coords.z)
+            && self
+                .base_voxel(coords - Vector3::unit_y())
+                .is_solid()
+    }
 
-    chunk_voxel_data
+    /// Whether the voxel is above the snow line or in a frozen biome
+    fn is_frozen(&self, coords: Vector3<f64>) -> bool {
+        coords.y >= self.preset.snow_line
+            || self
+                .biome(coords.x, coords.z)
+                .is_some_and(|biome| biome.frozen)
+    }
+
+    /// Decoration stage, which covers exposed surfaces with snow and the sea surface with ice where it is frozen
+    fn decorated_voxel(&self, coords: Vector3<f64>) -> VoxelType {
+        match self.base_voxel(coords) {
+            VoxelType::Air if self.is_frozen(coords) && self.is_exposed_surface(coords) => VoxelType::Snow,
+            VoxelType::Water if coords.y + 1.0 >= self.preset.sea_level && self.is_frozen(coords) => VoxelType::Ice,
+            voxel_type => voxel_type,
+        }
+    }
 }
 
-struct NoiseLayer {
-    pub weight: f64,
-    pub scale: f64,
+/// Height of the terrain surface of [flat_perlin_terrain] in the column at x and z
+fn terrain_height(preset: &WorldgenPreset, noise: &dyn NoiseSource, x: f64, z: f64) -> f64 {
+    let layered_noise = noise.get_layered(&preset.terrain_layers, [x, z]);
+    let normalized_height = (layered_noise + 1.0) / 2.0;
+
+    preset.base_height + preset.height_range * normalized_height
+}
+
+/// Terrain heights of [flat_perlin_terrain] for a square area of `size`x`size` samples, without generating any chunks.
+///
+/// The samples are `blocks_per_sample` voxels apart and centered around `center`. They are stored row by row along the x axis.
+pub fn terrain_heightmap(world_seed: u32, preset: &WorldgenPreset, center: [f64; 2], size: usize, blocks_per_sample: f64) -> Vec<f64> {
+    let terrain_noise = preset.terrain_noise.build(world_seed);
+    let offset = size as f64 / 2.0;
+
+    iproduct!(0..size, 0..size)
+        .map(|(row, column)| {
+            let x = center[0] + (column as f64 - offset) * blocks_per_sample;
+            let z = center[1] + (row as f64 - offset) * blocks_per_sample;
+
+            terrain_height(preset, terrain_noise.as_ref(), x, z)
+        })
+        .collect()
 }
 
 trait LayeredNoiseGenerator {
-    fn get_layered(&mut self, octaves: &[NoiseLayer], point: [f64; 2]) -> f64;
+    fn get_layered(&self, octaves: &[NoiseLayer], point: [f64; 2]) -> f64;
 }
 
-impl LayeredNoiseGenerator for Perlin {
-    fn get_layered(&mut self, octaves: &[NoiseLayer], point: [f64; 2]) -> f64 {
+impl<T: NoiseSource + ?Sized> LayeredNoiseGenerator for T {
+    fn get_layered(&self, octaves: &[NoiseLayer], point: [f64; 2]) -> f64 {
         octaves
             .iter()
-            .map(|layer| layer.weight * self.get([point[0] * layer.scale, point[1] * layer.scale]))
+            .map(|layer| layer.weight * self.sample([point[0] * layer.scale, point[1] * layer.scale]))
             .sum()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::world::location::ChunkLocation;
+    use crate::world::voxel_data::VoxelType;
+    use crate::world::world_config::WorldConfig;
+    use crate::world::worldgen::WorldGenerator;
+    use crate::world::worldgen_preset::WorldgenPreset;
+
+    #[test]
+    fn test_structures_are_deterministic_and_span_chunks() {
+        let mut preset = WorldgenPreset::default();
+        preset.structures.tree_attempts = 32;
+        preset.structures.boulder_chance = 1.0;
+        let generator = WorldGenerator::new(
+            WorldConfig {
+                seed: 5,
+                ..WorldConfig::default()
+            },
+            preset,
+        );
+
+        let mut has_outside_voxels = false;
+        for (x, y, z) in itertools::iproduct!(-2..2, -1..=0, -2..2) {
+            let chunk_location = ChunkLocation::new(Vector3::new(x, y, z));
+            let chunk = generator.generate_chunk(chunk_location);
+
+            assert_eq!(generator.outside_structure_voxels(chunk_location), chunk.outside_voxels);
+            assert!(chunk
+                .outside_voxels
+                .iter()
+                .all(|(location, _)| location.separate().0 != chunk_location));
+            has_outside_voxels |= chunk
+                .outside_voxels
+                .iter()
+                .any(|&(_, ty)| ty == VoxelType::Leaves);
+        }
+
+        assert!(has_outside_voxels, "expected some tree crowns to reach into neighboring chunks");
+    }
+}
@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::ron_file;
+use crate::world::noise_source::NoiseKind;
+use crate::world::voxel_data::VoxelType;
+
+/// One octave of layered noise
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NoiseLayer {
+    pub weight: f64,
+    pub scale: f64,
+}
+
+/// Surface blocks of the terrain where the biome noise is at most `max_value`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Biome {
+    pub name: String,
+    /// Upper bound of the biome noise for this biome. The last biome is used for all values above the other biomes.
+    pub max_value: f64,
+    /// Type of the topmost voxel of the terrain
+    pub surface: VoxelType,
+    /// Type of the voxels between the surface and the stone below it
+    pub filler: VoxelType,
+    /// Whether the whole biome is covered with snow and its sea with ice, regardless of the [WorldgenPreset::snow_line]
+    #[serde(default)]
+    pub frozen: bool,
+}
+
+/// Noise and thresholds of the caves that are carved into the terrain
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CavePreset {
+    /// Noise of the height below which caves are carved
+    pub ceiling_layers: Vec<NoiseLayer>,
+    pub ceiling_offset: f64,
+    /// Noise of the height above which caves are carved
+    pub floor_layers: Vec<NoiseLayer>,
+    pub floor_offset: f64,
+    /// Noise that decides where the caves and tunnels are in the horizontal plane
+    pub shape_layers: Vec<NoiseLayer>,
+    /// Height above which tunnels are carved, independently of the cave ceiling and floor
+    pub min_tunnel_height: f64,
+}
+
+impl Default for CavePreset {
+    fn default() -> Self {
+        Self {
+            ceiling_layers: vec![
+                NoiseLayer { scale: 0.002, weight: 4.0 },
+                NoiseLayer { scale: 0.02, weight: 1.0 },
+                NoiseLayer { scale: 0.08, weight: 3.0 },
+            ],
+            ceiling_offset: -15.0,
+            floor_layers: vec![
+                NoiseLayer { scale: 0.002, weight: 3.0 },
+                NoiseLayer { scale: 0.04, weight: 3.0 },
+                NoiseLayer { scale: 0.08, weight: 0.3 },
+            ],
+            floor_offset: -30.0,
+            shape_layers: vec![
+                NoiseLayer { scale: 0.03, weight: 0.7 },
+                NoiseLayer { scale: 0.08, weight: 0.2 },
+                NoiseLayer { scale: 0.1, weight: 0.02 },
+            ],
+            min_tunnel_height: -30.0,
+        }
+    }
+}
+
+/// Amount and size of the structures that are placed on the terrain surface after the terrain of a chunk is generated
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructurePreset {
+    /// Number of random columns of every chunk in which a tree is placed, if the column is covered with grass
+    pub tree_attempts: u32,
+    /// Smallest and largest height of tree trunks
+    pub trunk_height: [i32; 2],
+    /// Probability that a chunk has a boulder in a random column of its surface
+    pub boulder_chance: f64,
+    /// Radius of the largest boulders
+    pub max_boulder_radius: i32,
+}
+
+impl Default for StructurePreset {
+    fn default() -> Self {
+        Self {
+            tree_attempts: 2,
+            trunk_height: [4, 6],
+            boulder_chance: 0.2,
+            max_boulder_radius: 2,
+        }
+    }
+}
+
+/// All parameters of the terrain generator, which are loaded from a preset file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldgenPreset {
+    /// Noise of the terrain height
+    pub terrain_noise: NoiseKind,
+    pub terrain_layers: Vec<NoiseLayer>,
+    /// Height of the terrain where its layered noise is at its minimum
+    pub base_height: f64,
+    /// Height difference between the lowest and the highest terrain
+    pub height_range: f64,
+    /// Height of the sea surface. All air above the terrain and below this height is filled with water.
+    pub sea_level: f64,
+    /// Columns whose terrain surface is less than this height above the sea level are covered with sand
+    pub beach_height: f64,
+    /// Depth below the terrain surface at which stone begins
+    pub stone_depth: f64,
+    /// Height above which exposed surfaces are covered with snow
+    pub snow_line: f64,
+    /// Noise that selects the biome of a column from [WorldgenPreset::biomes]
+    pub biome_layers: Vec<NoiseLayer>,
+    /// Biomes sorted by their [Biome::max_value]
+    pub biomes: Vec<Biome>,
+    pub caves: CavePreset,
+    pub structures: StructurePreset,
+    /// Chunks above this chunk height are always empty
+    pub max_chunk_y: i32,
+    /// Chunks below this chunk height are always solid stone
+    pub min_chunk_y: i32,
+}
+
+impl Default for WorldgenPreset {
+    fn default() -> Self {
+        Self {
+            terrain_noise: NoiseKind::default(),
+            terrain_layers: vec![
+                NoiseLayer { scale: 0.002, weight: 1.5 },
+                NoiseLayer { scale: 0.007, weight: 0.9 },
+                NoiseLayer { scale: 0.02, weight: 0.3 },
+                NoiseLayer { scale: 0.07, weight: 0.06 },
+                NoiseLayer { scale: 0.4, weight: 0.03 },
+            ],
+            base_height: 1.0,
+            height_range: 16.0,
+            sea_level: 4.0,
+            beach_height: 1.5,
+            stone_depth: 6.0,
+            snow_line: 13.0,
+            biome_layers: vec![NoiseLayer { scale: 0.001, weight: 1.0 }],
+            biomes: vec![
+                Biome {
+                    name: "Tundra".to_owned(),
+                    max_value: -0.45,
+                    surface: VoxelType::Grass,
+                    filler: VoxelType::Dirt,
+                    frozen: true,
+                },
+                Biome {
+                    name: "Plains".to_owned(),
+                    max_value: 0.4,
+                    surface: VoxelType::Grass,
+                    filler: VoxelType::Dirt,
+                    frozen: false,
+                },
+                Biome {
+                    name: "Rocky".to_owned(),
+                    max_value: 1.0,
+                    surface: VoxelType::Stone,
+                    filler: VoxelType::Stone,
+                    frozen: false,
+                },
+            ],
+            caves: CavePreset::default(),
+            structures: StructurePreset::default(),
+            max_chunk_y: 2,
+            min_chunk_y: -3,
+        }
+    }
+}
+
+impl WorldgenPreset {
+    /// Loads the preset from the given file. If the file does not exist or is invalid, the default preset is used instead.
+    pub fn load(path: &Path) -> Self {
+        ron_file::load_or_default(path, "worldgen preset")
+    }
+
+    pub fn try_load(path: &Path) -> Result<Self> {
+        ron_file::load(path, "worldgen preset")
+    }
+
+    /// The biome for a value of the biome noise
+    pub fn biome(&self, value: f64) -> Option<&Biome> {
+        self.biomes
+            .iter()
+            .find(|biome| value <= biome.max_value)
+            .or(self.biomes.last())
+    }
+}